@@ -1,5 +1,13 @@
 # Build script for Crusty-Crawler (Windows PowerShell)
-# Creates a distributable zip package
+# Creates a distributable zip package, or with -Msi an installer that registers the boot-time
+# daemon task and ships the default config (see packaging\windows\main.wxs)
+#
+#   .\build-package.ps1         zip with the install scripts
+#   .\build-package.ps1 -Msi    needs the WiX Toolset v3 (candle.exe and light.exe on PATH)
+
+param(
+    [switch]$Msi
+)
 
 $ErrorActionPreference = "Stop"
 
@@ -13,6 +21,30 @@ Write-Host "🦀 Building Crusty-Crawler v$VERSION..." -ForegroundColor Cyan
 Write-Host "📦 Compiling release build..." -ForegroundColor Yellow
 cargo build --release
 
+if ($Msi) {
+    Write-Host "📦 Building MSI installer..." -ForegroundColor Yellow
+    $wixDir = "target\wix"
+    New-Item -ItemType Directory -Force -Path $wixDir, "dist" | Out-Null
+    candle.exe -nologo -arch x64 "-dVersion=$VERSION" "-dCargoTargetBinDir=target\release" `
+        -out "$wixDir\" "packaging\windows\main.wxs"
+    if ($LASTEXITCODE -ne 0) { throw "candle.exe failed" }
+    $msiPath = "dist\$PACKAGE_NAME-x64.msi"
+    light.exe -nologo -out $msiPath "$wixDir\main.wixobj"
+    if ($LASTEXITCODE -ne 0) { throw "light.exe failed" }
+
+    $hash = Get-FileHash $msiPath -Algorithm SHA256
+    $hash.Hash + "  $PACKAGE_NAME-x64.msi" | Out-File "$msiPath.sha256"
+
+    Write-Host ""
+    Write-Host "✅ Installer created successfully!" -ForegroundColor Green
+    Write-Host "📍 Location: $msiPath" -ForegroundColor Green
+    Write-Host "🔐 Checksum: $msiPath.sha256" -ForegroundColor Green
+    Write-Host ""
+    Write-Host "To install silently on a fleet machine:" -ForegroundColor Cyan
+    Write-Host "  msiexec /i $PACKAGE_NAME-x64.msi /qn" -ForegroundColor White
+    exit 0
+}
+
 # Create package directory
 Write-Host "📁 Creating package directory..." -ForegroundColor Yellow
 $distPath = "dist\$PACKAGE_NAME"
@@ -29,6 +61,7 @@ Copy-Item "target\release\RustSystemChecker.exe" "$distPath\$APP_NAME.exe"
 Write-Host "🎨 Copying assets..." -ForegroundColor Yellow
 Copy-Item -Recurse "Assets" "$distPath\"
 Copy-Item -Recurse "public" "$distPath\"
+Copy-Item "packaging\crusty_auth.default.json" "$distPath\"
 
 # Copy license and readme
 Write-Host "📄 Copying documentation..." -ForegroundColor Yellow
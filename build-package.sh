@@ -1,12 +1,74 @@
 #!/bin/bash
 # Build script for Crusty-Crawler
-# Creates a distributable tar package
+# Creates a distributable tar package, or with "deb" / "rpm" a package that installs the
+# systemd service and the default config (see packaging/ and [package.metadata.*] in Cargo.toml)
+#
+#   ./build-package.sh        tar.gz with the install scripts
+#   ./build-package.sh deb    needs cargo-deb           (cargo install cargo-deb)
+#   ./build-package.sh rpm    needs cargo-generate-rpm  (cargo install cargo-generate-rpm)
+#   ./build-package.sh docker container image from the Dockerfile
 
 set -e
 
 VERSION="0.1.0"
 APP_NAME="crusty-crawler"
 PACKAGE_NAME="${APP_NAME}-${VERSION}"
+FORMAT="${1:-tar}"
+# Servers don't need the desktop GUI, see the features in Cargo.toml
+HEADLESS_FEATURES="tls,exporters,checks"
+
+checksum() {
+    cd dist
+    sha256sum "$1" > "$1.sha256"
+    cd ..
+    echo ""
+    echo "[SUCCESS] Package created successfully!"
+    echo "[INFO] Location: dist/$1"
+    echo "[INFO] Checksum: dist/$1.sha256"
+}
+
+case "$FORMAT" in
+    tar)
+        ;;
+    deb)
+        echo "[BUILD] Building Crusty-Crawler v${VERSION} .deb..."
+        mkdir -p dist
+        # cargo-deb builds the release itself, with the features from [package.metadata.deb]
+        DEB=$(cargo deb --output dist/ | tail -n 1)
+        checksum "$(basename "$DEB")"
+        echo ""
+        echo "To install:  sudo apt install ./$(basename "$DEB")"
+        echo "The service is enabled but not started, create a user first:"
+        echo "  sudo crusty-crawler --cli --data-dir /var/lib/crusty-crawler"
+        echo "  sudo systemctl start crusty-crawler"
+        exit 0
+        ;;
+    rpm)
+        echo "[BUILD] Building Crusty-Crawler v${VERSION} .rpm..."
+        cargo build --release --no-default-features --features "${HEADLESS_FEATURES}"
+        mkdir -p dist
+        RPM=$(cargo generate-rpm --output dist/ && ls -t dist/*.rpm | head -n 1)
+        checksum "$(basename "$RPM")"
+        echo ""
+        echo "To install:  sudo dnf install ./$(basename "$RPM")"
+        echo "Then create a user and start the service:"
+        echo "  sudo crusty-crawler --cli --data-dir /var/lib/crusty-crawler"
+        echo "  sudo systemctl enable --now crusty-crawler"
+        exit 0
+        ;;
+    docker)
+        echo "[BUILD] Building Crusty-Crawler v${VERSION} container image..."
+        docker build -t "${APP_NAME}:${VERSION}" -t "${APP_NAME}:latest" .
+        echo ""
+        echo "[SUCCESS] Image ${APP_NAME}:${VERSION} built successfully!"
+        echo "To run:  docker run -d -p 3000:3000 -v crusty-data:/data ${APP_NAME}:${VERSION}"
+        exit 0
+        ;;
+    *)
+        echo "[ERROR] Unknown package format '${FORMAT}', use tar, deb, rpm or docker"
+        exit 1
+        ;;
+esac
 
 echo "[BUILD] Building Crusty-Crawler v${VERSION}..."
 
@@ -27,6 +89,7 @@ cp "target/release/RustSystemChecker.exe" "dist/${PACKAGE_NAME}/${APP_NAME}.exe"
 echo "[COPY] Copying assets..."
 cp -r Assets "dist/${PACKAGE_NAME}/"
 cp -r public "dist/${PACKAGE_NAME}/"
+cp packaging/crusty_auth.default.json "dist/${PACKAGE_NAME}/"
 
 # Copy license and readme
 echo "[COPY] Copying documentation..."
@@ -72,6 +72,10 @@ if (Test-Path "Assets") {
 if (Test-Path "public") {
     Copy-Item -Recurse "public" "$INSTALL_DIR\"
 }
+# Copied into the data directory on first start
+if (Test-Path "crusty_auth.default.json") {
+    Copy-Item "crusty_auth.default.json" "$INSTALL_DIR\"
+}
 
 # Copy documentation
 if (Test-Path "LICENSE") {
@@ -76,6 +76,10 @@ fi
 if [ -d "public" ]; then
     cp -r public "${INSTALL_DIR}/"
 fi
+# Copied into the data directory on first start
+if [ -f "crusty_auth.default.json" ]; then
+    cp crusty_auth.default.json "${INSTALL_DIR}/"
+fi
 
 # Create symlink in /usr/local/bin
 echo -e "${YELLOW}[LINK] Creating symlinks...${NC}"
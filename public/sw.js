@@ -0,0 +1,44 @@
+// Service worker for the installable dashboard. Nothing is cached, the dashboard only
+// shows live data, it is here for alert notifications
+self.addEventListener("install", () => self.skipWaiting());
+self.addEventListener("activate", (event) => event.waitUntil(self.clients.claim()));
+
+// Navigations that fail while offline get a short page instead of the browser error
+self.addEventListener("fetch", (event) => {
+    if (event.request.mode !== "navigate") {
+        return;
+    }
+    event.respondWith(
+        fetch(event.request).catch(
+            () =>
+                new Response(
+                    "<body style='font-family:monospace;background:#1e1e1e;color:#ffcc00'>" +
+                        "<h1>Agent unreachable</h1><p>Check your connection and try again.</p></body>",
+                    { headers: { "Content-Type": "text/html" } },
+                ),
+        ),
+    );
+});
+
+self.addEventListener("push", (event) => {
+    const data = event.data ? event.data.json() : {};
+    event.waitUntil(
+        self.registration.showNotification(data.title || "Crusty-Crawler", {
+            body: data.body || "",
+            // One notification per check, replaced when its state changes
+            tag: data.tag,
+            renotify: true,
+            icon: "/icons/192.png",
+            requireInteraction: data.state === "CRITICAL",
+        }),
+    );
+});
+
+self.addEventListener("notificationclick", (event) => {
+    event.notification.close();
+    event.waitUntil(
+        self.clients.matchAll({ type: "window" }).then((windows) =>
+            windows.length > 0 ? windows[0].focus() : self.clients.openWindow("/"),
+        ),
+    );
+});
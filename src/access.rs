@@ -0,0 +1,193 @@
+// Access module for Crusty-Crawler
+// Request extractors that check the token or web session once for every API route, so handlers
+// get the user and role instead of parsing tokens themselves, and auth failures look the same
+// everywhere
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UserRole {
+    // Users from before roles existed had full access, so they stay admins
+    #[default]
+    Admin,
+    // Can read everything but not acknowledge findings or start scans and speed tests
+    Viewer,
+}
+
+impl UserRole {
+    pub fn label(&self) -> &'static str {
+        match self {
+            UserRole::Admin => "admin",
+            UserRole::Viewer => "viewer",
+        }
+    }
+}
+
+// The user behind the request's token, from ?token= or an Authorization: Bearer header
+pub struct AuthedUser {
+    pub username: String,
+    pub role: UserRole,
+}
+
+// An AuthedUser with the admin role, for routes that change what the server reports
+pub struct AdminUser(pub AuthedUser);
+
+// Who may read the dashboard and status pages, the only routes kiosk tokens open
+pub enum DashboardViewer {
+    User(AuthedUser),
+    // The display's name
+    Kiosk(String),
+}
+
+// The client's address, set by serve_app. None when the router is driven directly as in the
+// tests
+pub struct ClientIp(pub Option<std::net::IpAddr>);
+
+pub enum AuthError {
+    MissingToken,
+    InvalidToken,
+    Forbidden,
+    // A kiosk token on a route other than the dashboard's
+    KioskToken,
+    // An enrolled host's credential before an admin approved the host, or after one denied it
+    NotApproved,
+    // The router wasn't built by create_app
+    NoServerState,
+}
+
+impl axum::response::IntoResponse for AuthError {
+    fn into_response(self) -> axum::response::Response {
+        let (status, message) = match self {
+            AuthError::MissingToken => (StatusCode::UNAUTHORIZED, "Missing token"),
+            AuthError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid or expired token"),
+            AuthError::Forbidden => (StatusCode::FORBIDDEN, "This needs the admin role"),
+            AuthError::KioskToken => (
+                StatusCode::FORBIDDEN,
+                "Kiosk tokens can only open the dashboard",
+            ),
+            AuthError::NotApproved => (
+                StatusCode::FORBIDDEN,
+                "This host hasn't been approved by an admin",
+            ),
+            AuthError::NoServerState => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Server state unavailable",
+            ),
+        };
+        (status, Json(serde_json::json!({ "error": message }))).into_response()
+    }
+}
+
+fn authenticate(
+    server_state: &Arc<Mutex<ServerState>>,
+    token: Option<String>,
+    ip: Option<std::net::IpAddr>,
+) -> Result<AuthedUser, AuthError> {
+    let token = token.ok_or(AuthError::MissingToken)?;
+    let user = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        let username = match auth_manager.authenticate_token(&token, ip) {
+            Ok(username) => username,
+            Err(_) if auth_manager.is_kiosk_token(&token) => return Err(AuthError::KioskToken),
+            Err(_) => return Err(AuthError::InvalidToken),
+        };
+        let role = auth_manager
+            .config
+            .users
+            .get(&username)
+            .map(|user| user.role)
+            .ok_or(AuthError::InvalidToken)?;
+        AuthedUser { username, role }
+    };
+    save_token_usage_if_due();
+    Ok(user)
+}
+
+fn client_ip(parts: &axum::http::request::Parts) -> Option<std::net::IpAddr> {
+    parts
+        .extensions
+        .get::<axum::extract::ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip())
+}
+
+impl<S: Send + Sync> axum::extract::FromRequestParts<S> for ClientIp {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(ClientIp(client_ip(parts)))
+    }
+}
+
+// The token from ?token= or the Authorization header
+fn parts_token(parts: &axum::http::request::Parts) -> Option<String> {
+    // A malformed query string just means no token
+    let query = Query::<TokenQuery>::try_from_uri(&parts.uri)
+        .map(|Query(query)| query)
+        .unwrap_or(TokenQuery { token: None });
+    request_token(&query, &parts.headers)
+}
+
+fn parts_server_state(
+    parts: &axum::http::request::Parts,
+) -> Result<&Arc<Mutex<ServerState>>, AuthError> {
+    parts
+        .extensions
+        .get::<Arc<Mutex<ServerState>>>()
+        .ok_or(AuthError::NoServerState)
+}
+
+impl<S: Send + Sync> axum::extract::FromRequestParts<S> for AuthedUser {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let server_state = parts_server_state(parts)?;
+        authenticate(server_state, parts_token(parts), client_ip(parts))
+    }
+}
+
+impl<S: Send + Sync> axum::extract::FromRequestParts<S> for DashboardViewer {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let server_state = parts_server_state(parts)?;
+        let token = parts_token(parts);
+        let ip = client_ip(parts);
+        match authenticate(server_state, token.clone(), ip) {
+            Err(AuthError::KioskToken) => {
+                let state = server_state.lock().unwrap();
+                let auth_manager = state.auth_manager.lock().unwrap();
+                token
+                    .and_then(|token| auth_manager.authenticate_kiosk_token(&token, ip))
+                    .map(DashboardViewer::Kiosk)
+                    .ok_or(AuthError::InvalidToken)
+            }
+            result => result.map(DashboardViewer::User),
+        }
+    }
+}
+
+impl<S: Send + Sync> axum::extract::FromRequestParts<S> for AdminUser {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let user =
+            <AuthedUser as axum::extract::FromRequestParts<S>>::from_request_parts(parts, state)
+                .await?;
+        match user.role {
+            UserRole::Admin => Ok(AdminUser(user)),
+            UserRole::Viewer => Err(AuthError::Forbidden),
+        }
+    }
+}
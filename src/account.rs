@@ -0,0 +1,77 @@
+// Account module for Crusty-Crawler
+// Self-service changes to the signed-in user's own password and email, from the GUI settings
+// and the web dashboard. Both need the current password and end up on the event timeline
+
+// Response of GET /api/account
+#[derive(Serialize)]
+pub struct AccountInfo {
+    pub username: String,
+    pub email: String,
+    pub role: UserRole,
+    // created_at for users from before password changes were recorded
+    pub password_changed_at: String,
+}
+
+// Body of PUT /api/account/password
+#[derive(Deserialize)]
+pub struct PasswordChange {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+// Body of PUT /api/account/email
+#[derive(Deserialize)]
+pub struct EmailChange {
+    pub current_password: String,
+    pub email: String,
+}
+
+impl AuthManager {
+    pub fn account_info(&self, username: &str) -> Option<AccountInfo> {
+        self.config.users.get(username).map(|user| AccountInfo {
+            username: user.username.clone(),
+            email: user.email.clone(),
+            role: user.role,
+            password_changed_at: user
+                .password_changed_at
+                .clone()
+                .unwrap_or_else(|| user.created_at.clone()),
+        })
+    }
+
+    // Recovery mails go to this address, so it has to be one nobody else uses
+    pub fn change_email(
+        &mut self,
+        username: &str,
+        current_password: &str,
+        email: &str,
+    ) -> Result<(), String> {
+        self.authenticate(username, current_password)?;
+        let email = email.trim();
+        if !email.contains('@') {
+            return Err("Please enter a valid email address".to_string());
+        }
+        if self
+            .config
+            .users
+            .values()
+            .any(|user| user.username != username && user.email.eq_ignore_ascii_case(email))
+        {
+            return Err("Email address already in use".to_string());
+        }
+
+        let user = self
+            .config
+            .users
+            .get_mut(username)
+            .ok_or("User not found")?;
+        let previous = std::mem::replace(&mut user.email, email.to_string());
+        self.save_config().map_err(|e| e.to_string())?;
+        record_event(
+            EventKind::Account,
+            &format!("Email of {} changed", username),
+            &format!("{} -> {}", previous, email),
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+// Adaptive module for Crusty-Crawler
+// Watches CPU and I/O pressure and stretches the intervals of the expensive collectors
+// (hardware-query, the tool collectors such as smartctl, the process table) while the host is
+// struggling, so the agent never makes an incident worse. The state is shown in the API
+
+const LOAD_MONITOR_INTERVAL: Duration = Duration::from_secs(10);
+// Pressure has to fall this far below the thresholds before collection speeds up again
+const LOAD_RECOVERY_RATIO: f64 = 0.75;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AdaptiveCollectionConfig {
+    pub enabled: bool,
+    // PSI "some avg10" of /proc/pressure/cpu, or the 1 minute load per core without PSI
+    pub cpu_pressure_percent: f64,
+    // PSI "some avg10" of /proc/pressure/io, Linux only
+    pub io_pressure_percent: f64,
+    // Expensive collectors run this many times less often while degraded
+    pub backoff_factor: u32,
+}
+
+impl Default for AdaptiveCollectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            cpu_pressure_percent: 80.0,
+            io_pressure_percent: 40.0,
+            backoff_factor: 4,
+        }
+    }
+}
+
+impl AdaptiveCollectionConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=100.0).contains(&self.cpu_pressure_percent)
+            || !(0.0..=100.0).contains(&self.io_pressure_percent)
+        {
+            return Err(
+                "metrics.adaptive pressure thresholds must be between 0 and 100".to_string(),
+            );
+        }
+        if self.backoff_factor == 0 {
+            return Err("metrics.adaptive.backoff_factor must be above 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct DegradedCollection {
+    pub since: String,
+    pub reason: String,
+    pub backoff_factor: u32,
+}
+
+// Response of GET /api/collection
+#[derive(Serialize, Clone)]
+pub struct CollectionStatus {
+    pub cpu_pressure_percent: Option<f64>,
+    pub io_pressure_percent: Option<f64>,
+    // None while collecting at the normal pace
+    pub degraded: Option<DegradedCollection>,
+    pub power_source: PowerSource,
+    // Set on battery, see power.rs
+    pub power_saving: Option<DegradedCollection>,
+}
+
+static COLLECTION_STATUS: Mutex<CollectionStatus> = Mutex::new(CollectionStatus {
+    cpu_pressure_percent: None,
+    io_pressure_percent: None,
+    degraded: None,
+    power_source: PowerSource::Unknown,
+    power_saving: None,
+});
+
+// The avg10 of the "some" line, e.g. "some avg10=1.23 avg60=0.80 avg300=0.20 total=12345"
+fn parse_pressure(content: &str) -> Option<f64> {
+    content
+        .lines()
+        .find(|line| line.starts_with("some "))?
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("avg10="))?
+        .parse()
+        .ok()
+}
+
+#[cfg(target_os = "linux")]
+fn read_pressure(resource: &str) -> Option<f64> {
+    parse_pressure(&fs::read_to_string(format!("/proc/pressure/{}", resource)).ok()?)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_pressure(_resource: &str) -> Option<f64> {
+    None
+}
+
+fn cpu_pressure() -> Option<f64> {
+    read_pressure("cpu").or_else(|| {
+        let cores = std::thread::available_parallelism().ok()?.get() as f64;
+        let load = sysinfo::System::load_average().one;
+        // Windows reports no load average
+        (load > 0.0).then(|| (load / cores * 100.0).min(100.0))
+    })
+}
+
+// Enters degraded mode when either pressure crosses its threshold and leaves it once both are
+// well below them again
+fn next_collection_state(
+    config: &AdaptiveCollectionConfig,
+    current: Option<&DegradedCollection>,
+    cpu: Option<f64>,
+    io: Option<f64>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Option<DegradedCollection> {
+    if !config.enabled {
+        return None;
+    }
+    let ratio = if current.is_some() {
+        LOAD_RECOVERY_RATIO
+    } else {
+        1.0
+    };
+    let mut reasons = Vec::new();
+    if let Some(cpu) = cpu
+        && cpu >= config.cpu_pressure_percent * ratio
+    {
+        reasons.push(format!("CPU pressure {:.0}%", cpu));
+    }
+    if let Some(io) = io
+        && io >= config.io_pressure_percent * ratio
+    {
+        reasons.push(format!("I/O pressure {:.0}%", io));
+    }
+    if reasons.is_empty() {
+        return None;
+    }
+    Some(DegradedCollection {
+        since: current.map_or_else(|| now.to_rfc3339(), |current| current.since.clone()),
+        reason: reasons.join(", "),
+        backoff_factor: config.backoff_factor.max(1),
+    })
+}
+
+pub fn collection_status() -> CollectionStatus {
+    COLLECTION_STATUS.lock().unwrap().clone()
+}
+
+// `interval` stretched by the backoff factors while degraded or saving power
+pub fn collection_interval(interval: Duration) -> Duration {
+    let status = COLLECTION_STATUS.lock().unwrap();
+    [&status.degraded, &status.power_saving]
+        .into_iter()
+        .flatten()
+        .fold(interval, |interval, backoff| {
+            interval * backoff.backoff_factor
+        })
+}
+
+fn update_collection_status(config: &AdaptiveCollectionConfig) {
+    let cpu = cpu_pressure();
+    let io = read_pressure("io");
+    let mut status = COLLECTION_STATUS.lock().unwrap();
+    let degraded = next_collection_state(
+        config,
+        status.degraded.as_ref(),
+        cpu,
+        io,
+        chrono::Utc::now(),
+    );
+    match (&status.degraded, &degraded) {
+        (None, Some(degraded)) => println!(
+            "🐢 Host under load ({}), expensive collectors run {}x less often",
+            degraded.reason, degraded.backoff_factor
+        ),
+        (Some(_), None) => println!("🐇 Load back to normal, collecting at the usual pace"),
+        _ => {}
+    }
+    status.cpu_pressure_percent = cpu;
+    status.io_pressure_percent = io;
+    status.degraded = degraded;
+}
+
+fn spawn_load_monitor(server_state: Arc<Mutex<ServerState>>) {
+    std::thread::spawn(move || {
+        loop {
+            let (config, power) = {
+                let state = server_state.lock().unwrap();
+                let auth_manager = state.auth_manager.lock().unwrap();
+                (
+                    auth_manager.config.metrics.adaptive.clone(),
+                    auth_manager.config.metrics.power.clone(),
+                )
+            };
+            update_collection_status(&config);
+            update_power_saving(&power);
+            std::thread::sleep(LOAD_MONITOR_INTERVAL);
+        }
+    });
+}
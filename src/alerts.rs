@@ -0,0 +1,270 @@
+// Alerts module for Crusty-Crawler
+// Tracks active and recently resolved alerts raised from check state changes. Alerts raised
+// close together are grouped into one incident under the first of them, so a full disk that
+// breaks five checks pages once
+
+use std::collections::VecDeque;
+
+const MAX_RECENT_ALERTS: usize = 50;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Alert {
+    pub id: u64,
+    pub check: String,
+    pub state: CheckState,
+    pub message: String,
+    pub raised_at: String,
+    pub resolved_at: Option<String>,
+    pub acknowledged_by: Option<String>,
+    // First perfdata value of the result and the threshold it crossed, for notifications
+    #[serde(default)]
+    pub value: Option<f64>,
+    #[serde(default)]
+    pub threshold: Option<f64>,
+    // Id of the alert that opened the incident this one was grouped into
+    #[serde(default)]
+    pub parent_id: Option<u64>,
+}
+
+impl Alert {
+    // The incident is named after the alert that opened it
+    pub fn incident_id(&self) -> u64 {
+        self.parent_id.unwrap_or(self.id)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AlertGroupingConfig {
+    pub enabled: bool,
+    // Alerts raised within this many seconds of an open incident's latest alert join it
+    pub window_secs: u64,
+    // Tags by check name, e.g. {"disk": ["storage"], "postgres": ["storage"]}. A tagged alert
+    // only joins an incident with a shared tag or no tags at all, untagged ones join any
+    pub tags: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for AlertGroupingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window_secs: 120,
+            tags: BTreeMap::new(),
+        }
+    }
+}
+
+impl AlertGroupingConfig {
+    fn tags(&self, check: &str) -> &[String] {
+        self.tags.get(check).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.window_secs > 3600 {
+            return Err("checks.grouping.window_secs must be at most 3600".to_string());
+        }
+        Ok(())
+    }
+}
+
+// The threshold of the alert's own severity, falling back to the warning one
+fn crossed_threshold(result: &CheckResult) -> Option<f64> {
+    let perf = result.perfdata.first()?;
+    match result.state {
+        CheckState::Critical => perf.crit.or(perf.warn),
+        _ => perf.warn,
+    }
+}
+
+// Shortens an RFC 3339 timestamp for display
+pub fn format_timestamp(timestamp: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map(|t| {
+            t.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        })
+        .unwrap_or_else(|_| timestamp.to_string())
+}
+
+#[derive(Default)]
+pub struct AlertManager {
+    next_id: u64,
+    active: HashMap<String, Alert>, // check name -> Alert
+    recent: VecDeque<Alert>,
+    new_critical: Vec<Alert>,
+    // The severity each open incident last paged at, by incident id
+    notified: HashMap<u64, CheckState>,
+}
+
+impl AlertManager {
+    // The incident a new alert for `check` joins, if any is open and recent enough
+    fn open_incident(
+        &self,
+        check: &str,
+        grouping: &AlertGroupingConfig,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Option<u64> {
+        if !grouping.enabled || grouping.window_secs == 0 {
+            return None;
+        }
+        let window = chrono::Duration::seconds(grouping.window_secs as i64);
+        let tags = grouping.tags(check);
+        // Incident id -> (raised within the window, any tags, shares a tag)
+        let mut incidents: BTreeMap<u64, (bool, bool, bool)> = BTreeMap::new();
+        for alert in self.active.values() {
+            let (recent, tagged, shared) = incidents.entry(alert.incident_id()).or_default();
+            *recent |= chrono::DateTime::parse_from_rfc3339(&alert.raised_at)
+                .is_ok_and(|raised| now - raised.with_timezone(&chrono::Utc) <= window);
+            let alert_tags = grouping.tags(&alert.check);
+            *tagged |= !alert_tags.is_empty();
+            *shared |= alert_tags.iter().any(|tag| tags.contains(tag));
+        }
+        // The oldest matching incident
+        incidents
+            .into_iter()
+            .find(|(_, (recent, tagged, shared))| {
+                *recent && (tags.is_empty() || !*tagged || *shared)
+            })
+            .map(|(id, _)| id)
+    }
+
+    // Whether a change returned by process_result should page anyone. Grouped alerts ride on
+    // their incident's notification unless they are worse than what it paged at, and the
+    // recovery goes out once the whole incident is over
+    pub fn notifies(&mut self, alert: &Alert) -> bool {
+        let incident = alert.incident_id();
+        if alert.state == CheckState::Ok {
+            let over = !self
+                .active
+                .values()
+                .any(|active| active.incident_id() == incident);
+            if over {
+                self.notified.remove(&incident);
+            }
+            return over;
+        }
+        match self.notified.get(&incident) {
+            Some(notified) if alert.parent_id.is_some() && alert.state <= *notified => false,
+            _ => {
+                self.notified.insert(incident, alert.state);
+                true
+            }
+        }
+    }
+
+    // A parent of `check` that has an active alert, its problems are the parent's fault then
+    pub fn failing_parent(
+        &self,
+        check: &str,
+        dependencies: &BTreeMap<String, Vec<String>>,
+    ) -> Option<String> {
+        dependencies
+            .get(check)?
+            .iter()
+            .find(|parent| self.active.contains_key(parent.as_str()))
+            .cloned()
+    }
+
+    // Alerts grouped under `incident`, the one that opened it excluded
+    pub fn grouped(&self, incident: u64) -> Vec<Alert> {
+        let mut alerts: Vec<Alert> = self
+            .active
+            .values()
+            .filter(|alert| alert.parent_id == Some(incident))
+            .cloned()
+            .collect();
+        alerts.sort_by_key(|alert| alert.id);
+        alerts
+    }
+
+    // Updates alert state from a check result and returns the alert if it was raised or changed state
+    pub fn process_result(
+        &mut self,
+        result: &CheckResult,
+        grouping: &AlertGroupingConfig,
+    ) -> Option<Alert> {
+        if result.state == CheckState::Ok {
+            let mut alert = self.active.remove(&result.name)?;
+            alert.resolved_at = Some(chrono::Utc::now().to_rfc3339());
+            alert.message = result.output.clone();
+            self.push_recent(alert.clone());
+            return Some(Alert {
+                state: CheckState::Ok,
+                ..alert
+            });
+        }
+
+        let value = result.perfdata.first().map(|perf| perf.value);
+        if let Some(alert) = self.active.get_mut(&result.name) {
+            alert.message = result.output.clone();
+            alert.value = value;
+            alert.threshold = crossed_threshold(result);
+            if alert.state == result.state {
+                return None;
+            }
+
+            // A change of severity needs to be looked at again
+            alert.state = result.state;
+            alert.acknowledged_by = None;
+            let alert = alert.clone();
+            if alert.state == CheckState::Critical {
+                self.new_critical.push(alert.clone());
+            }
+            return Some(alert);
+        }
+
+        let now = chrono::Utc::now();
+        let parent_id = self.open_incident(&result.name, grouping, now);
+        self.next_id += 1;
+        let alert = Alert {
+            id: self.next_id,
+            check: result.name.clone(),
+            state: result.state,
+            message: result.output.clone(),
+            raised_at: now.to_rfc3339(),
+            resolved_at: None,
+            acknowledged_by: None,
+            value,
+            threshold: crossed_threshold(result),
+            parent_id,
+        };
+        if alert.state == CheckState::Critical {
+            self.new_critical.push(alert.clone());
+        }
+        self.active.insert(result.name.clone(), alert.clone());
+        Some(alert)
+    }
+
+    pub fn acknowledge(&mut self, id: u64, username: &str) -> Result<(), String> {
+        let alert = self
+            .active
+            .values_mut()
+            .find(|a| a.id == id)
+            .ok_or("Alert not found or already resolved")?;
+        alert.acknowledged_by = Some(username.to_string());
+        Ok(())
+    }
+
+    // Active alerts, most severe first
+    pub fn active(&self) -> Vec<Alert> {
+        let mut alerts: Vec<Alert> = self.active.values().cloned().collect();
+        alerts.sort_by(|a, b| b.state.cmp(&a.state).then(a.id.cmp(&b.id)));
+        alerts
+    }
+
+    // Recently resolved alerts, newest first
+    pub fn recent(&self) -> Vec<Alert> {
+        self.recent.iter().cloned().collect()
+    }
+
+    // Critical alerts raised since the last call, used for GUI toasts
+    pub fn take_new_critical(&mut self) -> Vec<Alert> {
+        std::mem::take(&mut self.new_critical)
+    }
+
+    fn push_recent(&mut self, alert: Alert) {
+        self.recent.push_front(alert);
+        self.recent.truncate(MAX_RECENT_ALERTS);
+    }
+}
@@ -4,19 +4,101 @@ use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
+const AUTH_CONFIG_FILE: &str = "crusty_auth.json";
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct User {
     pub username: String,
     pub email: String,
     pub password_hash: String,
-    pub access_token: String,
+    // Salted hash from hash_access_token, the token itself is never stored
+    pub access_token_hash: String,
     pub created_at: String,
+    // When the password was last set, created_at for users from before this was recorded
+    #[serde(default)]
+    pub password_changed_at: Option<String>,
+    // bcrypt hash of the token this device keeps in the OS keyring
+    #[serde(default)]
+    pub remember_token_hash: Option<String>,
+    // Web dashboard arrangement, the admin default when None
+    #[serde(default)]
+    pub dashboard_layout: Option<DashboardLayout>,
+    // "viewer" can see everything but not acknowledge findings or start scans and speed tests
+    #[serde(default)]
+    pub role: UserRole,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct AuthConfig {
+    // Schema version, older files are migrated on load
+    #[serde(default = "legacy_config_version")]
+    pub version: u32,
     pub users: HashMap<String, User>, // username -> User
     pub smtp_config: Option<SmtpConfig>,
+    #[serde(default)]
+    pub checks: CheckConfig,
+    #[serde(default)]
+    pub contacts: Vec<Contact>,
+    // Rotations deciding which contacts get paged when
+    #[serde(default)]
+    pub on_call: OnCallConfig,
+    // Overrides of the shipped alert notification templates
+    #[serde(default)]
+    pub notification_templates: NotificationTemplates,
+    // Caps how many notifications go out during an alert storm
+    #[serde(default)]
+    pub notification_throttle: NotificationThrottleConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub status_pages: StatusPageConfig,
+    // Admin policy for the GUI's "Remember me" option
+    #[serde(default = "default_allow_remember_me")]
+    pub allow_remember_me: bool,
+    #[serde(default)]
+    pub sessions: SessionConfig,
+    #[serde(default)]
+    pub password_policy: PasswordPolicy,
+    // Sent but not yet accepted
+    #[serde(default)]
+    pub invitations: Vec<Invitation>,
+    // Wall displays that may only read the dashboard
+    #[serde(default)]
+    pub kiosk_tokens: Vec<KioskToken>,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub otlp: OtlpConfig,
+    #[serde(default)]
+    pub zabbix: ZabbixConfig,
+    #[serde(default)]
+    pub checkmk: CheckmkConfig,
+    #[serde(default)]
+    pub dashboard: DashboardConfig,
+    #[serde(default)]
+    pub push: PushConfig,
+    #[serde(default)]
+    pub mdns: MdnsConfig,
+    #[serde(default)]
+    pub speed_test: SpeedTestConfig,
+    #[serde(default)]
+    pub connections: ConnectionsConfig,
+    #[serde(default)]
+    pub http: HttpServerConfig,
+    #[serde(default)]
+    pub privacy: PrivacyConfig,
+    // Reporting this agent to a manager, and being one
+    #[serde(default)]
+    pub reporter: ReporterConfig,
+    #[serde(default)]
+    pub manager: ManagerConfig,
+    // Replaced versions of this file kept under backups/, 0 keeps none
+    #[serde(default = "default_config_backups")]
+    pub config_backups: usize,
+}
+
+fn default_allow_remember_me() -> bool {
+    true
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -31,30 +113,128 @@ pub struct SmtpConfig {
 impl Default for AuthConfig {
     fn default() -> Self {
         Self {
+            version: AUTH_CONFIG_VERSION,
             users: HashMap::new(),
             smtp_config: None,
+            checks: CheckConfig::default(),
+            contacts: Vec::new(),
+            on_call: OnCallConfig::default(),
+            notification_templates: NotificationTemplates::default(),
+            notification_throttle: NotificationThrottleConfig::default(),
+            metrics: MetricsConfig::default(),
+            status_pages: StatusPageConfig::default(),
+            allow_remember_me: true,
+            sessions: SessionConfig::default(),
+            password_policy: PasswordPolicy::default(),
+            invitations: Vec::new(),
+            kiosk_tokens: Vec::new(),
+            logging: LoggingConfig::default(),
+            otlp: OtlpConfig::default(),
+            zabbix: ZabbixConfig::default(),
+            checkmk: CheckmkConfig::default(),
+            dashboard: DashboardConfig::default(),
+            push: PushConfig::default(),
+            mdns: MdnsConfig::default(),
+            speed_test: SpeedTestConfig::default(),
+            connections: ConnectionsConfig::default(),
+            http: HttpServerConfig::default(),
+            privacy: PrivacyConfig::default(),
+            reporter: ReporterConfig::default(),
+            manager: ManagerConfig::default(),
+            config_backups: default_config_backups(),
+        }
+    }
+}
+
+impl AuthConfig {
+    // Reads the config without creating it, for commands that only look at settings
+    pub fn load_or_default(config_path: &str) -> Self {
+        match fs::read_to_string(config_path) {
+            Ok(data) => parse_auth_config(&data)
+                .map(|(config, _)| config)
+                .unwrap_or_else(|e| {
+                    eprintln!("⚠️ Ignoring invalid {}: {}", config_path, e);
+                    AuthConfig::default()
+                }),
+            Err(_) => AuthConfig::default(),
         }
     }
 }
 
+const RECOVERY_CODE_MINUTES: u64 = 15;
+// Wrong codes before the code is thrown away and recovery has to start over
+const RECOVERY_CODE_ATTEMPTS: u32 = 5;
+
+struct RecoveryCode {
+    code_hash: String,
+    expires_at: Instant,
+    attempts: u32,
+}
+
 pub struct AuthManager {
     config_path: String,
     pub config: AuthConfig,
+    // The config as it was on disk when last read or written, the base for merging a save
+    // with changes another process made in the meantime
+    loaded: serde_json::Value,
+    loaded_etag: String,
+    // Browser sessions by session token, kept in memory only
+    sessions: Mutex<HashMap<String, WebSession>>,
+    // Recovery codes mailed but not yet confirmed, by username
+    recovery_codes: Mutex<HashMap<String, RecoveryCode>>,
+    clock: Arc<dyn Clock>,
+    mailer: Arc<dyn EmailSender>,
 }
 
 impl AuthManager {
     pub fn new(config_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
         let auth_manager = if Path::new(config_path).exists() {
             let config_data = fs::read_to_string(config_path)?;
-            let config: AuthConfig = serde_json::from_str(&config_data)?;
-            Self {
+            let (config, migrated) = match parse_auth_config(&config_data) {
+                Ok(parsed) => parsed,
+                // A damaged file would otherwise lock every user out
+                Err(e) => {
+                    let (config, backup) = restore_config_backup(Path::new(config_path))
+                        .ok_or_else(|| format!("{}: {}", config_path, e))?;
+                    eprintln!(
+                        "⚠️  {} is unreadable ({}), using the backup {}",
+                        config_path,
+                        e,
+                        backup.display()
+                    );
+                    (config, false)
+                }
+            };
+            let mut auth_manager = Self {
                 config_path: config_path.to_string(),
+                loaded: serde_json::to_value(&config)?,
+                loaded_etag: config_etag(&config_data),
                 config,
+                sessions: Mutex::new(HashMap::new()),
+                recovery_codes: Mutex::new(HashMap::new()),
+                clock: Arc::new(SystemClock),
+                mailer: Arc::new(SmtpSender),
+            };
+            if migrated {
+                auth_manager.save_config()?;
+                migrate_config_backups(Path::new(config_path));
+                println!(
+                    "ℹ️  Migrated {} to schema version {}",
+                    config_path, AUTH_CONFIG_VERSION
+                );
             }
+            auth_manager
         } else {
-            let auth_manager = Self {
+            let config = AuthConfig::default();
+            let mut auth_manager = Self {
                 config_path: config_path.to_string(),
-                config: AuthConfig::default(),
+                loaded: serde_json::to_value(&config)?,
+                loaded_etag: String::new(),
+                config,
+                sessions: Mutex::new(HashMap::new()),
+                recovery_codes: Mutex::new(HashMap::new()),
+                clock: Arc::new(SystemClock),
+                mailer: Arc::new(SmtpSender),
             };
             auth_manager.save_config()?;
             auth_manager
@@ -63,9 +243,74 @@ impl AuthManager {
         Ok(auth_manager)
     }
 
-    fn save_config(&self) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn config_path(&self) -> &str {
+        &self.config_path
+    }
+
+    // Re-reads crusty_auth.json, e.g. after an admin edited it by hand. An invalid file leaves
+    // the current config in place, otherwise returns what changed
+    pub fn reload(&mut self) -> Result<Vec<String>, String> {
+        let config_data = fs::read_to_string(&self.config_path).map_err(|e| e.to_string())?;
+        // Our own saves wake the watcher too
+        let etag = config_etag(&config_data);
+        if etag == self.loaded_etag {
+            return Ok(Vec::new());
+        }
+        let (config, _) = parse_auth_config(&config_data)?;
+        config.validate()?;
+        let changes = config_diff(&self.config, &config);
+        self.loaded = serde_json::to_value(&config).map_err(|e| e.to_string())?;
+        self.loaded_etag = etag;
+        self.config = config;
+        if !changes.is_empty() {
+            record_event(
+                EventKind::ConfigChange,
+                &format!("Configuration reloaded, {} change(s)", changes.len()),
+                &changes.join("\n"),
+            );
+        }
+        Ok(changes)
+    }
+
+    // Saves under the lock file. If another process changed the file since we read it, our
+    // edits are merged on top of theirs, or the save fails when both touched the same setting.
+    // The previous contents go to backups/ first, a failed backup doesn't block the save
+    fn save_config(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = Path::new(&self.config_path);
+        let _lock = ConfigFileLock::acquire(path)?;
+
+        let previous = fs::read_to_string(path).ok();
+        if let Some(previous) = &previous
+            && config_etag(previous) != self.loaded_etag
+            // An unparseable file can't be merged, it survives in the backups
+            && let Ok((theirs, _)) = parse_auth_config(previous)
+        {
+            let theirs = serde_json::to_value(&theirs)?;
+            let ours = serde_json::to_value(&self.config)?;
+            let merged = merge_config(&self.loaded, &ours, &theirs).map_err(|conflicts| {
+                format!(
+                    "{} was changed elsewhere in the meantime ({}), reload and try again",
+                    self.config_path,
+                    conflicts.join(", ")
+                )
+            })?;
+            self.config = serde_json::from_value(merged)?;
+            println!(
+                "ℹ️  Merged changes another process made to {}",
+                self.config_path
+            );
+        }
+
         let config_data = serde_json::to_string_pretty(&self.config)?;
-        fs::write(&self.config_path, config_data)?;
+        if let Some(previous) = &previous
+            && *previous != config_data
+            && let Err(e) = backup_config(path, previous, self.config.config_backups)
+        {
+            eprintln!("⚠️  Failed to back up {}: {}", self.config_path, e);
+        }
+        write_file_atomic(path, config_data.as_bytes())?;
+        self.loaded = serde_json::to_value(&self.config)?;
+        self.loaded_etag = config_etag(&config_data);
         Ok(())
     }
 
@@ -75,6 +320,17 @@ impl AuthManager {
         password: &str,
         email: &str,
         access_token: &str,
+    ) -> Result<(), String> {
+        self.register_user_with_role(username, password, email, access_token, UserRole::Admin)
+    }
+
+    pub fn register_user_with_role(
+        &mut self,
+        username: &str,
+        password: &str,
+        email: &str,
+        access_token: &str,
+        role: UserRole,
     ) -> Result<(), String> {
         if self.config.users.contains_key(username) {
             return Err("Username already exists".to_string());
@@ -84,42 +340,46 @@ impl AuthManager {
             return Err("Username must be at least 3 characters".to_string());
         }
 
-        if password.len() < 8 {
-            return Err("Password must be at least 8 characters".to_string());
-        }
+        self.config.password_policy.check(password)?;
 
         if access_token.len() < 8 {
             return Err("Access token must be at least 8 characters".to_string());
         }
 
         // Check if access token is already in use
-        for user in self.config.users.values() {
-            if user.access_token == access_token {
-                return Err("Access token already in use".to_string());
-            }
+        if self.token_owner(access_token).is_some() {
+            return Err("Access token already in use".to_string());
         }
 
         let password_hash = hash(password, DEFAULT_COST).map_err(|e| e.to_string())?;
-        let created_at = chrono::Utc::now().to_rfc3339();
+        let created_at = self.clock.utc_now().to_rfc3339();
 
         let user = User {
             username: username.to_string(),
             email: email.to_string(),
             password_hash,
-            access_token: access_token.to_string(),
+            access_token_hash: hash_access_token(access_token),
+            password_changed_at: Some(created_at.clone()),
             created_at,
+            remember_token_hash: None,
+            dashboard_layout: None,
+            role,
         };
 
         self.config.users.insert(username.to_string(), user);
-        self.save_config().map_err(|e| e.to_string())?;
+        if let Err(e) = self.save_config() {
+            // Not on disk, so not registered
+            self.config.users.remove(username);
+            return Err(e.to_string());
+        }
 
         Ok(())
     }
 
-    pub fn authenticate(&self, username: &str, password: &str) -> Result<String, String> {
+    pub fn authenticate(&self, username: &str, password: &str) -> Result<(), String> {
         if let Some(user) = self.config.users.get(username) {
             if verify(password, &user.password_hash).map_err(|e| e.to_string())? {
-                Ok(user.access_token.clone())
+                Ok(())
             } else {
                 Err("Invalid password".to_string())
             }
@@ -128,57 +388,185 @@ impl AuthManager {
         }
     }
 
+    fn token_owner(&self, token: &str) -> Option<&User> {
+        self.config
+            .users
+            .values()
+            .find(|user| verify_access_token(token, &user.access_token_hash))
+    }
+
     pub fn validate_token(&self, token: &str) -> Result<String, String> {
-        for user in self.config.users.values() {
-            if user.access_token == token {
-                return Ok(user.username.clone());
-            }
+        if let Some(user) = self.token_owner(token) {
+            return Ok(user.username.clone());
+        }
+        if let Some(username) = self.validate_web_session(token) {
+            return Ok(username);
         }
         Err("Invalid access token".to_string())
     }
 
-    pub fn recover_credentials(&self, email: &str) -> Result<(), String> {
+    fn recovery_user(&self, email: &str) -> Result<(String, String, SmtpConfig), String> {
         let user = self
             .config
             .users
             .values()
             .find(|u| u.email == email)
             .ok_or("No user found with that email address")?;
+        let Some(smtp_config) = self.config.smtp_config.clone() else {
+            return Err(
+                "Email configuration not set up. Please contact administrator.".to_string(),
+            );
+        };
+        Ok((user.username.clone(), user.email.clone(), smtp_config))
+    }
 
-        if let Some(smtp_config) = &self.config.smtp_config {
-            self.send_recovery_email(user, smtp_config)
-        } else {
-            Err("Email configuration not set up. Please contact administrator.".to_string())
-        }
-    }
-
-    fn send_recovery_email(&self, user: &User, smtp_config: &SmtpConfig) -> Result<(), String> {
-        println!("=== RECOVERY EMAIL ===");
-        println!("To: {}", user.email);
-        println!("Subject: Crusty Server Credentials Recovery");
-        println!();
-        println!("Hello {},", user.username);
-        println!();
-        println!("Here are your Crusty Server credentials:");
-        println!("Username: {}", user.username);
-        println!("Access Token: {}", user.access_token);
-        println!();
-        println!("Use the username and password to log into the application.");
-        println!("Use the access token to access the web interface.");
-        println!();
-        println!("If you didn't request this, please ignore this message.");
-        println!("=== END EMAIL ===");
-
-        std::thread::sleep(std::time::Duration::from_secs(2));
+    // Only the hash of the access token is kept, so recovery mails a new one. This first step
+    // only mails a one-time code, nothing changes until confirm_recovery gets it back, so typing
+    // someone else's address doesn't lock them out
+    pub fn recover_credentials(&mut self, email: &str) -> Result<(), String> {
+        let (username, email, smtp_config) = self.recovery_user(email)?;
+        let code = format!("{:06}", rand::random_range(0..1_000_000));
+        let body = format!(
+            "Hello {},\n\n\
+             Your Crusty Server recovery code is {}. Enter it in the application within {} \
+             minutes to get a new access token.\n\n\
+             If you didn't request this, please ignore this message, your credentials stay as \
+             they are.\n",
+            username, code, RECOVERY_CODE_MINUTES
+        );
+        self.mailer
+            .send(&smtp_config, &email, "Crusty Server recovery code", &body)?;
+        self.recovery_codes.lock().unwrap().insert(
+            username,
+            RecoveryCode {
+                code_hash: hash_access_token(&code),
+                expires_at: self.clock.now() + Duration::from_secs(RECOVERY_CODE_MINUTES * 60),
+                attempts: 0,
+            },
+        );
         Ok(())
     }
 
+    // Mails a new access token once the code matches. The old token keeps working until that
+    // mail went out, a failed delivery leaves the code valid for another try
+    pub fn confirm_recovery(&mut self, email: &str, code: &str) -> Result<(), String> {
+        let (username, email, smtp_config) = self.recovery_user(email)?;
+        {
+            let mut codes = self.recovery_codes.lock().unwrap();
+            let invalid = "Invalid or expired recovery code".to_string();
+            let Some(pending) = codes.get_mut(&username) else {
+                return Err(invalid);
+            };
+            if pending.expires_at <= self.clock.now() {
+                codes.remove(&username);
+                return Err(invalid);
+            }
+            if !verify_access_token(code.trim(), &pending.code_hash) {
+                pending.attempts += 1;
+                if pending.attempts >= RECOVERY_CODE_ATTEMPTS {
+                    codes.remove(&username);
+                }
+                return Err(invalid);
+            }
+        }
+
+        let access_token = AuthManager::generate_suggested_token();
+        self.send_recovery_email(&username, &email, &access_token, &smtp_config)?;
+        self.recovery_codes.lock().unwrap().remove(&username);
+        self.replace_access_token(&username, &access_token)
+    }
+
+    fn send_recovery_email(
+        &self,
+        username: &str,
+        email: &str,
+        access_token: &str,
+        smtp_config: &SmtpConfig,
+    ) -> Result<(), String> {
+        let body = format!(
+            "Hello {},\n\n\
+             Here are your Crusty Server credentials:\n\
+             Username: {}\n\
+             New Access Token: {}\n\n\
+             Use the username and password to log into the application.\n\
+             Use the access token to access the web interface, the previous one no longer works.\n\n\
+             If you didn't request this, please ignore this message.\n",
+            username, username, access_token
+        );
+        self.mailer.send(
+            smtp_config,
+            email,
+            "Crusty Server Credentials Recovery",
+            &body,
+        )
+    }
+
     pub fn configure_smtp(&mut self, smtp_config: SmtpConfig) -> Result<(), String> {
         self.config.smtp_config = Some(smtp_config);
         self.save_config().map_err(|e| e.to_string())?;
         Ok(())
     }
 
+    pub fn configure_checks(&mut self, checks: CheckConfig) -> Result<(), String> {
+        self.config.checks = checks;
+        self.save_config().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn configure_status_pages(&mut self, status_pages: StatusPageConfig) -> Result<(), String> {
+        self.config.status_pages = status_pages;
+        self.save_config().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn set_remember_token(
+        &mut self,
+        username: &str,
+        token: Option<&str>,
+    ) -> Result<(), String> {
+        let token_hash = match token {
+            Some(token) => Some(hash(token, DEFAULT_COST).map_err(|e| e.to_string())?),
+            None => None,
+        };
+        let user = self
+            .config
+            .users
+            .get_mut(username)
+            .ok_or("User not found")?;
+        user.remember_token_hash = token_hash;
+        self.save_config().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn verify_remember_token(&self, username: &str, token: &str) -> bool {
+        if !self.config.allow_remember_me {
+            return false;
+        }
+        self.config
+            .users
+            .get(username)
+            .and_then(|user| user.remember_token_hash.as_ref())
+            .is_some_and(|token_hash| verify(token, token_hash).unwrap_or(false))
+    }
+
+    pub fn configure_sessions(&mut self, sessions: SessionConfig) -> Result<(), String> {
+        self.config.sessions = sessions;
+        self.save_config().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn set_allow_remember_me(&mut self, allow: bool) -> Result<(), String> {
+        self.config.allow_remember_me = allow;
+        // Turning it off also signs out every remembered device
+        if !allow {
+            for user in self.config.users.values_mut() {
+                user.remember_token_hash = None;
+            }
+        }
+        self.save_config().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
     pub fn has_users(&self) -> bool {
         !self.config.users.is_empty()
     }
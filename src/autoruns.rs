@@ -0,0 +1,116 @@
+// Autoruns module for Crusty-Crawler
+// Windows persistence points for the security baseline: Run/RunOnce registry values and
+// scheduled tasks, the usual places malware hides to survive a reboot
+
+#[cfg(windows)]
+const AUTORUN_KEYS: &[&str] = &[
+    "HKLM\\Software\\Microsoft\\Windows\\CurrentVersion\\Run",
+    "HKLM\\Software\\Microsoft\\Windows\\CurrentVersion\\RunOnce",
+    "HKLM\\Software\\WOW6432Node\\Microsoft\\Windows\\CurrentVersion\\Run",
+    "HKLM\\Software\\WOW6432Node\\Microsoft\\Windows\\CurrentVersion\\RunOnce",
+    "HKCU\\Software\\Microsoft\\Windows\\CurrentVersion\\Run",
+    "HKCU\\Software\\Microsoft\\Windows\\CurrentVersion\\RunOnce",
+];
+
+// "    OneDrive    REG_SZ    "C:\Program Files\...\OneDrive.exe" /background"
+#[cfg(windows)]
+fn parse_reg_value(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    for kind in ["REG_SZ", "REG_EXPAND_SZ"] {
+        let marker = format!("    {}    ", kind);
+        if let Some((name, command)) = line.split_once(&marker) {
+            return Some((name.trim().to_string(), command.trim().to_string()));
+        }
+    }
+    None
+}
+
+// The command is part of the key, pointing an existing entry somewhere else is a new finding
+#[cfg(windows)]
+fn observe_autoruns() -> Vec<SecurityFinding> {
+    let mut autoruns = Vec::new();
+    for key in AUTORUN_KEYS {
+        let Ok(output) = std::process::Command::new("reg")
+            .args(["query", key])
+            .output()
+        else {
+            continue;
+        };
+        // A missing key exits non-zero, e.g. WOW6432Node on 32-bit Windows
+        if !output.status.success() {
+            continue;
+        }
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let Some((name, command)) = parse_reg_value(line) else {
+                continue;
+            };
+            autoruns.push(SecurityFinding {
+                kind: SecurityFindingKind::Autorun,
+                key: format!("{}\\{} = {}", key, name, command),
+                detail: format!("{} runs {} at logon", name, command),
+            });
+        }
+    }
+    autoruns
+}
+
+// Splits one line of schtasks' CSV output, fields are always quoted
+#[cfg(windows)]
+fn parse_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+// Column positions of `schtasks /query /v /fo csv` are fixed, the header text is localized
+#[cfg(windows)]
+fn observe_scheduled_tasks() -> Vec<SecurityFinding> {
+    let Ok(output) = std::process::Command::new("schtasks")
+        .args(["/query", "/v", "/fo", "csv", "/nh"])
+        .output()
+    else {
+        return Vec::new();
+    };
+
+    // A task with several triggers is listed once per trigger
+    let mut tasks: BTreeMap<String, SecurityFinding> = BTreeMap::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let fields = parse_csv_line(line);
+        let (Some(name), Some(command)) = (fields.get(1), fields.get(8)) else {
+            continue;
+        };
+        if !name.starts_with('\\') {
+            continue;
+        }
+        tasks.entry(name.clone()).or_insert(SecurityFinding {
+            kind: SecurityFindingKind::ScheduledTask,
+            key: name.clone(),
+            detail: format!("{} runs {}", name, command),
+        });
+    }
+    tasks.into_values().collect()
+}
+
+#[cfg(not(windows))]
+fn observe_autoruns() -> Vec<SecurityFinding> {
+    Vec::new()
+}
+
+#[cfg(not(windows))]
+fn observe_scheduled_tasks() -> Vec<SecurityFinding> {
+    Vec::new()
+}
@@ -0,0 +1,253 @@
+// AV devices module for Crusty-Crawler
+// For signage and kiosk machines: the monitors on each DRM connector with their native
+// resolution, and the sound cards ALSA knows about. They're part of the /api/peripherals
+// inventory, and the displays check alerts when a monitor is unplugged or an expected audio
+// device is missing. Linux only
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DisplayOutput {
+    // e.g. HDMI-A-1
+    pub connector: String,
+    pub card: String,
+    pub connected: bool,
+    // Whether a mode is set on it
+    pub enabled: bool,
+    // The first mode the monitor offers, its native one, e.g. 1920x1080
+    pub resolution: Option<String>,
+}
+
+impl DisplayOutput {
+    // The sysfs entry, e.g. card0-HDMI-A-1. Connector names repeat across GPUs
+    pub fn name(&self) -> String {
+        format!("{}-{}", self.card, self.connector)
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct AudioDevice {
+    pub index: u32,
+    // ALSA's short name, e.g. PCH
+    pub id: String,
+    pub driver: String,
+    pub name: String,
+}
+
+// One /sys/class/drm entry such as card0-HDMI-A-1, None for the cards themselves
+pub fn drm_display(entry: &str, status: &str, enabled: &str, modes: &str) -> Option<DisplayOutput> {
+    let (card, connector) = entry.split_once('-')?;
+    if !card.starts_with("card") || connector.starts_with("Writeback") {
+        return None;
+    }
+    Some(DisplayOutput {
+        connector: connector.to_string(),
+        card: card.to_string(),
+        connected: status.trim() == "connected",
+        enabled: enabled.trim() == "enabled",
+        resolution: modes.lines().next().map(|mode| mode.trim().to_string()),
+    })
+}
+
+// /proc/asound/cards, two lines per card:
+// " 0 [PCH            ]: HDA-Intel - HDA Intel PCH" and the card's long name indented below
+pub fn parse_asound_cards(cards: &str) -> Vec<AudioDevice> {
+    cards
+        .lines()
+        .filter_map(|line| {
+            let (index, rest) = line.trim_start().split_once(' ')?;
+            let index = index.parse().ok()?;
+            let (id, rest) = rest.trim_start().strip_prefix('[')?.split_once("]:")?;
+            let (driver, name) = rest.trim().split_once(" - ")?;
+            Some(AudioDevice {
+                index,
+                id: id.trim().to_string(),
+                driver: driver.to_string(),
+                name: name.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn read_displays() -> Vec<DisplayOutput> {
+    let Ok(entries) = fs::read_dir("/sys/class/drm") else {
+        return Vec::new();
+    };
+    let mut displays: Vec<DisplayOutput> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let read = |file: &str| fs::read_to_string(entry.path().join(file)).unwrap_or_default();
+            drm_display(
+                &entry.file_name().to_string_lossy(),
+                &read("status"),
+                &read("enabled"),
+                &read("modes"),
+            )
+        })
+        .collect();
+    displays.sort_by(|a, b| (&a.card, &a.connector).cmp(&(&b.card, &b.connector)));
+    displays
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_displays() -> Vec<DisplayOutput> {
+    Vec::new()
+}
+
+fn read_audio_devices() -> Vec<AudioDevice> {
+    fs::read_to_string("/proc/asound/cards")
+        .map(|cards| parse_asound_cards(&cards))
+        .unwrap_or_default()
+}
+
+// Connectors, by card, with a monitor at the last scan and every one that had one since the agent started
+#[derive(Default)]
+struct DisplayState {
+    connected: BTreeSet<String>,
+    seen: BTreeSet<String>,
+}
+
+static DISPLAY_STATE: Mutex<Option<DisplayState>> = Mutex::new(None);
+
+// Plugging and unplugging after the first scan become timeline events
+fn track_displays(displays: &[DisplayOutput]) {
+    let connected: BTreeSet<String> = displays
+        .iter()
+        .filter(|display| display.connected)
+        .map(DisplayOutput::name)
+        .collect();
+    let mut state = DISPLAY_STATE.lock().unwrap();
+    if let Some(previous) = state.as_ref() {
+        for name in previous.connected.difference(&connected) {
+            record_event(
+                EventKind::Device,
+                &format!("Monitor on {} disconnected", name),
+                "",
+            );
+        }
+        for name in connected.difference(&previous.connected) {
+            record_event(
+                EventKind::Device,
+                &format!("Monitor on {} connected", name),
+                "",
+            );
+        }
+    }
+    let state = state.get_or_insert_with(DisplayState::default);
+    state.seen.extend(connected.iter().cloned());
+    state.connected = connected;
+}
+
+fn seen_displays() -> BTreeSet<String> {
+    DISPLAY_STATE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|state| state.seen.clone())
+        .unwrap_or_default()
+}
+
+pub fn displays_available(config: &PeripheralConfig) -> bool {
+    cfg!(target_os = "linux") && config.enabled
+}
+
+// CRITICAL while fewer monitors than expected are connected, or one that was connected since
+// the agent started isn't anymore. WARNING while an expected audio device is missing
+pub fn displays_check_result(
+    report: &PeripheralReport,
+    config: &PeripheralConfig,
+    seen: &BTreeSet<String>,
+) -> CheckResult {
+    let connected: Vec<&DisplayOutput> = report
+        .displays
+        .iter()
+        .filter(|display| display.connected)
+        .collect();
+    let mut problems = Vec::new();
+    if connected.len() < config.expected_monitors {
+        problems.push(format!(
+            "{} of {} monitors connected",
+            connected.len(),
+            config.expected_monitors
+        ));
+    }
+    let unplugged: Vec<&String> = seen
+        .iter()
+        .filter(|name| !connected.iter().any(|d| &d.name() == *name))
+        .collect();
+    for name in &unplugged {
+        problems.push(format!("monitor on {} disconnected", name));
+    }
+    let missing_audio: Vec<&String> = config
+        .expected_audio
+        .iter()
+        .filter(|expected| {
+            let expected = expected.to_lowercase();
+            !report.audio_devices.iter().any(|device| {
+                device.name.to_lowercase().contains(&expected)
+                    || device.id.to_lowercase().contains(&expected)
+            })
+        })
+        .collect();
+    for expected in &missing_audio {
+        problems.push(format!("no audio device matching '{}'", expected));
+    }
+
+    let state = if connected.len() < config.expected_monitors || !unplugged.is_empty() {
+        CheckState::Critical
+    } else if !missing_audio.is_empty() {
+        CheckState::Warning
+    } else {
+        CheckState::Ok
+    };
+    let output = if problems.is_empty() {
+        let monitors: Vec<String> = connected
+            .iter()
+            .map(|display| match &display.resolution {
+                Some(resolution) => format!("{} {}", display.name(), resolution),
+                None => display.name(),
+            })
+            .collect();
+        let mut output = format!("{} monitor(s)", connected.len());
+        if !monitors.is_empty() {
+            output.push_str(&format!(": {}", monitors.join(", ")));
+        }
+        output.push_str(&format!(", {} audio device(s)", report.audio_devices.len()));
+        output
+    } else {
+        problems.join(", ")
+    };
+    CheckResult::new(
+        "displays",
+        state,
+        output,
+        vec![
+            PerfData::new("monitors", connected.len() as f64, ""),
+            PerfData::new("audio_devices", report.audio_devices.len() as f64, ""),
+        ],
+    )
+}
+
+fn check_displays(config: &PeripheralConfig) -> CheckResult {
+    let report = read_peripherals(config);
+    displays_check_result(&report, config, &seen_displays())
+}
+
+pub fn av_device_metrics(report: &PeripheralReport) -> Vec<Metric> {
+    let mut metrics: Vec<Metric> = report
+        .displays
+        .iter()
+        .map(|display| {
+            Metric::new(
+                "display_connected",
+                if display.connected { 1.0 } else { 0.0 },
+            )
+            .label("card", &display.card)
+            .label("connector", &display.connector)
+        })
+        .collect();
+    metrics.push(Metric::new(
+        "audio_devices",
+        report.audio_devices.len() as f64,
+    ));
+    metrics
+}
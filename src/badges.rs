@@ -0,0 +1,94 @@
+// Badges module for Crusty-Crawler
+// Small SVG badges of a check's state, or the overall health, for embedding in wikis and
+// READMEs. Checks published on the public status page need no token
+
+const BADGE_CHAR_WIDTH: usize = 7;
+const BADGE_PADDING: usize = 10;
+
+fn badge_color(state: CheckState) -> &'static str {
+    match state {
+        CheckState::Ok => "#4c1",
+        CheckState::Warning => "#dfb317",
+        CheckState::Critical => "#e05d44",
+        CheckState::Unknown => "#9f9f9f",
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    html_escape(text).replace('\'', "&apos;")
+}
+
+// Flat badge with the label on grey and the message in the state's colour
+pub fn render_badge(label: &str, message: &str, state: CheckState) -> String {
+    let label_width = label.chars().count() * BADGE_CHAR_WIDTH + BADGE_PADDING;
+    let message_width = message.chars().count() * BADGE_CHAR_WIDTH + BADGE_PADDING;
+    let width = label_width + message_width;
+    let (label, message) = (xml_escape(label), xml_escape(message));
+    format!(
+        concat!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"20\" ",
+            "role=\"img\" aria-label=\"{label}: {message}\">",
+            "<title>{label}: {message}</title>",
+            "<rect width=\"{label_width}\" height=\"20\" fill=\"#555\"/>",
+            "<rect x=\"{label_width}\" width=\"{message_width}\" height=\"20\" fill=\"{color}\"/>",
+            "<g fill=\"#fff\" text-anchor=\"middle\" ",
+            "font-family=\"Verdana,DejaVu Sans,sans-serif\" font-size=\"11\">",
+            "<text x=\"{label_x}\" y=\"14\">{label}</text>",
+            "<text x=\"{message_x}\" y=\"14\">{message}</text>",
+            "</g></svg>"
+        ),
+        width = width,
+        label = label,
+        message = message,
+        label_width = label_width,
+        message_width = message_width,
+        color = badge_color(state),
+        label_x = label_width / 2,
+        message_x = label_width + message_width / 2,
+    )
+}
+
+// The first perfdata value with the state, e.g. "42.1% OK"
+fn badge_message(result: &CheckResult) -> String {
+    match result.perfdata.first() {
+        Some(perf) => format!(
+            "{}{} {}",
+            format_perf_number(perf.value),
+            perf.uom,
+            result.state.label()
+        ),
+        None => result.state.label().to_string(),
+    }
+}
+
+// Label, message and state of the badge for `name`, a check or "overall". Public viewers
+// only get the state, like on /public
+pub async fn badge(
+    runner: &CheckRunner,
+    name: &str,
+    detailed: bool,
+) -> Option<(String, String, CheckState)> {
+    let max_age = Duration::from_secs(runner.config.cache_max_age_secs);
+    if name == "overall" {
+        let mut overall = CheckState::Ok;
+        for check in available_checks(&runner.config) {
+            if let Some((result, _)) = runner.cached(&check, max_age).await {
+                overall = overall.max(result.state);
+            }
+        }
+        return Some(("health".to_string(), overall.label().to_string(), overall));
+    }
+
+    let (result, _) = runner.cached(name, max_age).await?;
+    let message = if detailed {
+        badge_message(&result)
+    } else {
+        result.state.label().to_string()
+    };
+    Some((result.name, message, result.state))
+}
+
+// Whether the badge can be served without a token
+pub fn public_badge(config: &PublicStatusConfig, name: &str) -> bool {
+    config.enabled && (name == "overall" || config.checks.iter().any(|check| check == name))
+}
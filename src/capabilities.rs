@@ -0,0 +1,152 @@
+// Capabilities module for Crusty-Crawler
+// Finds out once at startup which sensor sources this machine has. VMs and containers have no
+// thermal or power sensors, so those collectors are turned off and reported as unsupported
+// instead of filling the status with "not available" lines
+
+#[derive(Serialize, Clone)]
+pub struct UnsupportedCollector {
+    pub collector: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct PlatformCapabilities {
+    // e.g. "kvm" or "vmware", None on bare metal or when it can't be told
+    pub virtualization: Option<String>,
+    pub container: bool,
+    // Temperatures, fans and voltages from sysinfo, SMC, SBC or IPMI
+    pub sensors: bool,
+    // Power and thermal profiles from hardware-query
+    pub hardware_query: bool,
+    pub unsupported: Vec<UnsupportedCollector>,
+}
+
+static PLATFORM_CAPABILITIES: std::sync::OnceLock<PlatformCapabilities> =
+    std::sync::OnceLock::new();
+
+// Known hypervisors by the DMI product name or vendor
+fn detect_virtualization(product: &str, vendor: &str) -> Option<String> {
+    const HYPERVISORS: &[(&str, &str)] = &[
+        ("kvm", "kvm"),
+        ("qemu", "qemu"),
+        ("vmware", "vmware"),
+        ("virtualbox", "virtualbox"),
+        ("innotek", "virtualbox"),
+        ("xen", "xen"),
+        ("virtual machine", "hyper-v"),
+        ("amazon ec2", "amazon"),
+        ("google compute engine", "google"),
+        ("parallels", "parallels"),
+    ];
+    let identity = format!("{} {}", product, vendor).to_lowercase();
+    HYPERVISORS
+        .iter()
+        .find(|(marker, _)| identity.contains(marker))
+        .map(|(_, name)| name.to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn read_virtualization() -> Option<String> {
+    let read = |path: &str| fs::read_to_string(path).unwrap_or_default();
+    detect_virtualization(
+        &read("/sys/class/dmi/id/product_name"),
+        &read("/sys/class/dmi/id/sys_vendor"),
+    )
+    .or_else(|| {
+        // An unknown hypervisor still sets the CPU flag
+        read("/proc/cpuinfo")
+            .lines()
+            .any(|line| {
+                line.starts_with("flags") && line.split_whitespace().any(|f| f == "hypervisor")
+            })
+            .then(|| "unknown".to_string())
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_virtualization() -> Option<String> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn in_container() -> bool {
+    Path::new("/.dockerenv").exists()
+        || Path::new("/run/.containerenv").exists()
+        || env::var_os("container").is_some()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn in_container() -> bool {
+    false
+}
+
+fn capabilities_from(
+    virtualization: Option<String>,
+    container: bool,
+    sensors: bool,
+    hardware_query: bool,
+) -> PlatformCapabilities {
+    let reason = match (&virtualization, container) {
+        (_, true) => "unsupported in a container".to_string(),
+        (Some(hypervisor), _) => format!("unsupported on a {} VM", hypervisor),
+        (None, false) => "unsupported on this platform".to_string(),
+    };
+    let mut unsupported = Vec::new();
+    if !sensors {
+        for collector in ["components", "sensors"] {
+            unsupported.push(UnsupportedCollector {
+                collector: collector.to_string(),
+                reason: reason.clone(),
+            });
+        }
+    }
+    if !hardware_query {
+        unsupported.push(UnsupportedCollector {
+            collector: "hardware_query".to_string(),
+            reason,
+        });
+    }
+    PlatformCapabilities {
+        virtualization,
+        container,
+        sensors,
+        hardware_query,
+        unsupported,
+    }
+}
+
+fn detect_platform_capabilities() -> PlatformCapabilities {
+    let sensors = !sysinfo::Components::new_with_refreshed_list()
+        .list()
+        .is_empty()
+        || !platform_sensors().is_empty();
+    capabilities_from(
+        read_virtualization(),
+        in_container(),
+        sensors,
+        hardware_query_supported(),
+    )
+}
+
+pub fn platform_capabilities() -> &'static PlatformCapabilities {
+    PLATFORM_CAPABILITIES.get_or_init(detect_platform_capabilities)
+}
+
+// The reason `collector` is turned off, None when this machine supports it
+pub fn unsupported_reason(collector: &str) -> Option<&'static str> {
+    platform_capabilities()
+        .unsupported
+        .iter()
+        .find(|unsupported| unsupported.collector == collector)
+        .map(|unsupported| unsupported.reason.as_str())
+}
+
+// Runs the detection before the server starts, so the first request doesn't wait for it
+pub fn init_platform_capabilities() {
+    for unsupported in &platform_capabilities().unsupported {
+        println!(
+            "ℹ️  {} collector disabled, {}",
+            unsupported.collector, unsupported.reason
+        );
+    }
+}
@@ -0,0 +1,614 @@
+// Central module for Crusty-Crawler
+// Push mode for small fleets: agents report their metrics and check states to a manager. A full
+// snapshot goes out every few reports and only what changed in between, numbered so the manager
+// can rebuild each host's snapshot and ask for a full one when a report goes missing
+
+const REPORT_PATH: &str = "/api/central/report";
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ReporterConfig {
+    pub enabled: bool,
+    // Base URL of the manager, e.g. https://manager.example:3000
+    pub manager_url: String,
+    // The manager's standby, reported to while the manager can't be reached
+    pub standby_url: String,
+    // The credential from enrolling, or an access token on the manager
+    pub token: String,
+    // One-time token from the manager's admin, exchanged for `token` on the first run
+    pub join_token: String,
+    // Where the manager reaches this agent's API, sent along when enrolling
+    pub agent_url: String,
+    pub interval_secs: u64,
+    // Every nth report is a full snapshot, the ones in between only carry changes
+    pub full_every: u32,
+    // Configuration bundles pushed to this agent must be signed with this key, none are
+    // accepted while it's empty
+    pub manager_public_key: String,
+    // Version of the last bundle applied, older ones are refused
+    pub applied_config_version: u64,
+    // hash_access_token of the credential the manager pushes bundles with
+    pub manager_token_hash: String,
+}
+
+impl Default for ReporterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            manager_url: String::new(),
+            standby_url: String::new(),
+            token: String::new(),
+            join_token: String::new(),
+            agent_url: String::new(),
+            interval_secs: 60,
+            full_every: 10,
+            manager_public_key: String::new(),
+            applied_config_version: 0,
+            manager_token_hash: String::new(),
+        }
+    }
+}
+
+impl ReporterConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.manager_public_key.is_empty()
+            && base64url_decode(&self.manager_public_key)
+                .ok()
+                .and_then(|key| p256::PublicKey::from_sec1_bytes(&key).ok())
+                .is_none()
+        {
+            return Err("reporter.manager_public_key is not a P-256 public key".to_string());
+        }
+        if !self.enabled {
+            return Ok(());
+        }
+        if !(self.manager_url.starts_with("http://") || self.manager_url.starts_with("https://")) {
+            return Err("reporter.manager_url must be an http:// or https:// URL".to_string());
+        }
+        if self.token.is_empty() && self.join_token.is_empty() {
+            return Err("reporter.token or reporter.join_token must be set".to_string());
+        }
+        for (field, url) in [
+            ("agent_url", &self.agent_url),
+            ("standby_url", &self.standby_url),
+        ] {
+            if !url.is_empty() && !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!(
+                    "reporter.{} must be an http:// or https:// URL",
+                    field
+                ));
+            }
+        }
+        if self.interval_secs < 10 {
+            return Err("reporter.interval_secs must be at least 10".to_string());
+        }
+        if self.full_every == 0 {
+            return Err("reporter.full_every must be above 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ManagerConfig {
+    // Accept reports from agents and list them under /api/central/hosts
+    pub enabled: bool,
+    // A host that hasn't reported for this long is shown as down
+    pub offline_after_secs: u64,
+    // What the manager knows about hosts beyond their reports
+    pub hosts: Vec<ManagedHost>,
+    // Pushed to every host with an agent_url from POST /api/central/config/push
+    pub fleet_settings: FleetSettings,
+    // Issued to agents that haven't enrolled yet, each works once
+    pub join_tokens: Vec<JoinToken>,
+    // A second manager that takes over while this one is down
+    pub cluster: ClusterConfig,
+    // Who hears about which hosts' alerts, the first matching route wins
+    pub alert_routes: Vec<AlertRoute>,
+}
+
+impl Default for ManagerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            offline_after_secs: 300,
+            hosts: Vec::new(),
+            fleet_settings: FleetSettings::default(),
+            join_tokens: Vec::new(),
+            cluster: ClusterConfig::default(),
+            alert_routes: Vec::new(),
+        }
+    }
+}
+
+impl ManagerConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        self.cluster.validate()?;
+        let mut seen = BTreeSet::new();
+        for host in &self.hosts {
+            if host.name.is_empty() {
+                return Err("manager.hosts: every host needs a name".to_string());
+            }
+            if !seen.insert(&host.name) {
+                return Err(format!("manager.hosts: {} is listed twice", host.name));
+            }
+            if let Some(mac) = &host.mac {
+                parse_mac_address(mac)
+                    .map_err(|e| format!("manager.hosts.{}: {}", host.name, e))?;
+            }
+            if let Some(broadcast) = &host.wake_broadcast
+                && broadcast.parse::<std::net::Ipv4Addr>().is_err()
+            {
+                return Err(format!(
+                    "manager.hosts.{}: wake_broadcast '{}' is not an IPv4 address",
+                    host.name, broadcast
+                ));
+            }
+            if host.tags.iter().any(|tag| tag.trim().is_empty()) {
+                return Err(format!("manager.hosts.{}: tags can't be empty", host.name));
+            }
+            if let Some(url) = &host.agent_url {
+                if !(url.starts_with("http://") || url.starts_with("https://")) {
+                    return Err(format!(
+                        "manager.hosts.{}: agent_url must be an http:// or https:// URL",
+                        host.name
+                    ));
+                }
+                if host.agent_token.is_none() {
+                    return Err(format!(
+                        "manager.hosts.{}: agent_url needs an agent_token",
+                        host.name
+                    ));
+                }
+            }
+        }
+        // Checked as an agent on defaults would apply them
+        if !self.fleet_settings.is_empty() {
+            let mut fleet = AuthConfig::default();
+            self.fleet_settings.apply_to(&mut fleet);
+            fleet
+                .validate()
+                .map_err(|e| format!("manager.fleet_settings: {}", e))?;
+        }
+        Ok(())
+    }
+
+    pub fn host(&self, name: &str) -> Option<&ManagedHost> {
+        self.hosts.iter().find(|host| host.name == name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+#[serde(default)]
+pub struct ManagedHost {
+    // As the host reports itself
+    pub name: String,
+    // For Wake-on-LAN, e.g. 00:11:22:33:44:55
+    pub mac: Option<String>,
+    // Broadcast address of the host's subnet when the manager isn't on it, 255.255.255.255
+    // otherwise
+    pub wake_broadcast: Option<String>,
+    // Base URL of the host's own API, where fleet settings are pushed to
+    pub agent_url: Option<String>,
+    // An admin's access token on the host, or the credential it gave when enrolling
+    pub agent_token: Option<String>,
+    // None for hosts that report with a user's access token
+    pub enrollment: Option<HostEnrollment>,
+    // For filtering the fleet grid, e.g. "production"
+    pub environment: Option<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ReportedCheck {
+    pub state: CheckState,
+    pub output: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct HostSnapshot {
+    // Keyed by series as in the exposition format, e.g. disk_used_percent{mount="/"}
+    pub metrics: BTreeMap<String, f64>,
+    pub checks: BTreeMap<String, ReportedCheck>,
+}
+
+// Added or changed entries and the keys of removed ones
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+#[serde(default)]
+pub struct SnapshotDiff {
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metrics: BTreeMap<String, f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed_metrics: Vec<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub checks: BTreeMap<String, ReportedCheck>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed_checks: Vec<String>,
+}
+
+fn changed_entries<T: Clone + PartialEq>(
+    from: &BTreeMap<String, T>,
+    to: &BTreeMap<String, T>,
+) -> (BTreeMap<String, T>, Vec<String>) {
+    let changed = to
+        .iter()
+        .filter(|(key, value)| from.get(*key) != Some(value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    let removed = from
+        .keys()
+        .filter(|key| !to.contains_key(*key))
+        .cloned()
+        .collect();
+    (changed, removed)
+}
+
+impl HostSnapshot {
+    pub fn diff(&self, next: &HostSnapshot) -> SnapshotDiff {
+        let (metrics, removed_metrics) = changed_entries(&self.metrics, &next.metrics);
+        let (checks, removed_checks) = changed_entries(&self.checks, &next.checks);
+        SnapshotDiff {
+            metrics,
+            removed_metrics,
+            checks,
+            removed_checks,
+        }
+    }
+
+    pub fn apply(&mut self, diff: SnapshotDiff) {
+        for key in &diff.removed_metrics {
+            self.metrics.remove(key);
+        }
+        self.metrics.extend(diff.metrics);
+        for key in &diff.removed_checks {
+            self.checks.remove(key);
+        }
+        self.checks.extend(diff.checks);
+    }
+
+    pub fn overall(&self) -> CheckState {
+        self.checks
+            .values()
+            .map(|check| check.state)
+            .max()
+            .unwrap_or(CheckState::Ok)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ReportBody {
+    Full { snapshot: HostSnapshot },
+    // Applies to the report with the sequence number right before this one
+    Diff { diff: SnapshotDiff },
+}
+
+// Body of POST /api/central/report
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct HostReport {
+    pub host: String,
+    // Counts up from 1 every time the agent starts, which always begins with a full snapshot
+    pub sequence: u64,
+    pub collected_at: String,
+    #[serde(flatten)]
+    pub body: ReportBody,
+}
+
+// The agent's side: what it last sent, so the next report can be a diff against it
+#[derive(Default)]
+pub struct SnapshotReporter {
+    sequence: u64,
+    since_full: u32,
+    last: Option<HostSnapshot>,
+}
+
+impl SnapshotReporter {
+    pub fn next_report(
+        &mut self,
+        host: &str,
+        snapshot: HostSnapshot,
+        full_every: u32,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> HostReport {
+        self.sequence += 1;
+        let body = match &self.last {
+            Some(last) if self.since_full < full_every => {
+                self.since_full += 1;
+                ReportBody::Diff {
+                    diff: last.diff(&snapshot),
+                }
+            }
+            _ => {
+                self.since_full = 1;
+                ReportBody::Full {
+                    snapshot: snapshot.clone(),
+                }
+            }
+        };
+        self.last = Some(snapshot);
+        HostReport {
+            host: host.to_string(),
+            sequence: self.sequence,
+            collected_at: now.to_rfc3339(),
+            body,
+        }
+    }
+
+    // After a report that may not have arrived, the next one is a full snapshot
+    pub fn resync(&mut self) {
+        self.last = None;
+    }
+}
+
+// The manager's side, one per reporting host
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReportedHost {
+    pub host: String,
+    pub sequence: u64,
+    pub collected_at: String,
+    // RFC 3339, when the manager received the last report
+    pub last_report: String,
+    pub overall: CheckState,
+    pub snapshot: HostSnapshot,
+}
+
+static REPORTED_HOSTS: Mutex<BTreeMap<String, ReportedHost>> = Mutex::new(BTreeMap::new());
+
+// A diff that doesn't follow the last report is refused, the agent answers with a full snapshot
+pub fn receive_report(
+    hosts: &mut BTreeMap<String, ReportedHost>,
+    report: HostReport,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<(), String> {
+    let snapshot = match report.body {
+        ReportBody::Full { snapshot } => snapshot,
+        ReportBody::Diff { diff } => {
+            let Some(known) = hosts.get(&report.host) else {
+                return Err(format!("No snapshot of {} yet", report.host));
+            };
+            if report.sequence != known.sequence + 1 {
+                return Err(format!(
+                    "Report {} of {} doesn't follow {}",
+                    report.sequence, report.host, known.sequence
+                ));
+            }
+            let mut snapshot = known.snapshot.clone();
+            snapshot.apply(diff);
+            snapshot
+        }
+    };
+    hosts.insert(
+        report.host.clone(),
+        ReportedHost {
+            host: report.host,
+            sequence: report.sequence,
+            collected_at: report.collected_at,
+            last_report: now.to_rfc3339(),
+            overall: snapshot.overall(),
+            snapshot,
+        },
+    );
+    Ok(())
+}
+
+pub fn record_host_report(report: HostReport) -> Result<(), String> {
+    let host = report.host.clone();
+    let now = chrono::Utc::now();
+    let mut hosts = REPORTED_HOSTS.lock().unwrap();
+    receive_report(&mut hosts, report, now)?;
+    record_fleet_history(&host, &hosts[&host].snapshot, now);
+    Ok(())
+}
+
+// Entry of GET /api/central/hosts
+#[derive(Serialize, Clone, Debug)]
+pub struct FleetHost {
+    pub name: String,
+    pub online: bool,
+    // Unknown while the host is down
+    pub overall: CheckState,
+    pub mac: Option<String>,
+    pub enrollment: Option<EnrollmentStatus>,
+    pub metadata: Option<HostMetadata>,
+    pub environment: Option<String>,
+    pub tags: Vec<String>,
+    pub agent_url: Option<String>,
+    // None for configured hosts that haven't reported since the manager started
+    pub report: Option<ReportedHost>,
+}
+
+// Reporting hosts and the configured ones that haven't reported, by name
+pub fn fleet_hosts(
+    config: &ManagerConfig,
+    reported: &BTreeMap<String, ReportedHost>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Vec<FleetHost> {
+    let mut names: BTreeSet<&String> = reported.keys().collect();
+    names.extend(config.hosts.iter().map(|host| &host.name));
+    names
+        .into_iter()
+        .map(|name| {
+            let report = reported.get(name).cloned();
+            let online = report.as_ref().is_some_and(|report| {
+                chrono::DateTime::parse_from_rfc3339(&report.last_report).is_ok_and(|last| {
+                    (now - last.with_timezone(&chrono::Utc)).num_seconds()
+                        < config.offline_after_secs as i64
+                })
+            });
+            let managed = config.host(name);
+            let enrollment = managed.and_then(|host| host.enrollment.as_ref());
+            FleetHost {
+                name: name.clone(),
+                online,
+                overall: match &report {
+                    Some(report) if online => report.overall,
+                    _ => CheckState::Unknown,
+                },
+                mac: managed.and_then(|host| host.mac.clone()),
+                enrollment: enrollment.map(|enrollment| enrollment.status),
+                metadata: enrollment.map(|enrollment| enrollment.metadata.clone()),
+                environment: managed.and_then(|host| host.environment.clone()),
+                tags: managed.map(|host| host.tags.clone()).unwrap_or_default(),
+                agent_url: managed.and_then(|host| host.agent_url.clone()),
+                report,
+            }
+        })
+        .collect()
+}
+
+pub fn reported_fleet_hosts(config: &ManagerConfig) -> Vec<FleetHost> {
+    fleet_hosts(config, &REPORTED_HOSTS.lock().unwrap(), chrono::Utc::now())
+}
+
+// The series as in the exposition format, without the value
+fn series_key(metric: &Metric) -> String {
+    let line = metric.to_string();
+    match line.rsplit_once(' ') {
+        Some((key, _)) => key.to_string(),
+        None => line,
+    }
+}
+
+// Values are rounded to two decimals, so noise below that doesn't make it into every diff
+pub fn host_snapshot(metrics: &[Metric], checks: &[CheckResult]) -> HostSnapshot {
+    HostSnapshot {
+        metrics: metrics
+            .iter()
+            .map(|metric| (series_key(metric), (metric.value * 100.0).round() / 100.0))
+            .collect(),
+        checks: checks
+            .iter()
+            .map(|check| {
+                (
+                    check.name.clone(),
+                    ReportedCheck {
+                        state: check.state,
+                        output: check.output.clone(),
+                    },
+                )
+            })
+            .collect(),
+    }
+}
+
+async fn send_host_report(
+    client: &reqwest::Client,
+    manager_url: &str,
+    token: &str,
+    report: &HostReport,
+) -> Result<(), String> {
+    let url = format!("{}{}", manager_url.trim_end_matches('/'), REPORT_PATH);
+    let response = client
+        .post(&url)
+        .bearer_auth(token)
+        .json(report)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("{} {}", status, body));
+    }
+    Ok(())
+}
+
+fn spawn_central_reporter(server_state: Arc<Mutex<ServerState>>) {
+    std::thread::spawn(move || {
+        let rt = match Runtime::new() {
+            Ok(rt) => rt,
+            Err(e) => {
+                eprintln!("❌ Failed to start the central reporter: {}", e);
+                return;
+            }
+        };
+
+        rt.block_on(async {
+            let client = reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()
+                .unwrap_or_default();
+            let mut reporter = SnapshotReporter::default();
+            // Only logged when it changes, a manager that's down fails every report
+            let mut last_error = String::new();
+            loop {
+                let (config, metrics_config, runner) = {
+                    let state = server_state.lock().unwrap();
+                    let runner = CheckRunner::from_state(&state);
+                    let auth_manager = state.auth_manager.lock().unwrap();
+                    (
+                        auth_manager.config.reporter.clone(),
+                        auth_manager.config.metrics.clone(),
+                        runner,
+                    )
+                };
+
+                let host = sysinfo::System::host_name().unwrap_or_else(|| "unknown host".to_string());
+                if config.enabled && config.token.is_empty() {
+                    match enroll_with_manager(&client, &config, &host).await {
+                        Ok((response, agent_token)) => {
+                            let state = server_state.lock().unwrap();
+                            let mut auth_manager = state.auth_manager.lock().unwrap();
+                            match auth_manager.complete_enrollment(&response, &agent_token) {
+                                Ok(()) => println!(
+                                    "🤝 Enrolled with {}, reports start once an admin approves this host",
+                                    config.manager_url
+                                ),
+                                Err(e) => eprintln!("❌ Failed to save the enrollment: {}", e),
+                            }
+                            last_error.clear();
+                        }
+                        Err(e) => {
+                            if e != last_error {
+                                eprintln!("❌ Failed to enroll with {}: {}", config.manager_url, e);
+                            }
+                            last_error = e;
+                        }
+                    }
+                } else if config.enabled {
+                    let metrics = collect_metrics(&metrics_config).await;
+                    // The check loop keeps the cache warm, this rarely runs anything
+                    let max_age = Duration::from_secs(runner.config.interval_secs.max(5) * 2);
+                    let mut checks = Vec::new();
+                    for name in available_checks(&runner.config) {
+                        if let Some((result, _)) = runner.cached(&name, max_age).await {
+                            checks.push(result);
+                        }
+                    }
+
+                    let report = reporter.next_report(
+                        &host,
+                        host_snapshot(&metrics, &checks),
+                        config.full_every,
+                        chrono::Utc::now(),
+                    );
+                    let mut sent =
+                        send_host_report(&client, &config.manager_url, &config.token, &report)
+                            .await;
+                    if let Err(e) = &sent
+                        && !config.standby_url.is_empty()
+                    {
+                        sent = send_host_report(&client, &config.standby_url, &config.token, &report)
+                            .await
+                            .map_err(|standby| format!("{}, standby: {}", e, standby));
+                    }
+                    match sent {
+                        Ok(()) => last_error.clear(),
+                        Err(e) => {
+                            reporter.resync();
+                            if e != last_error {
+                                eprintln!("❌ Failed to report to {}: {}", config.manager_url, e);
+                            }
+                            last_error = e;
+                        }
+                    }
+                } else {
+                    reporter.resync();
+                }
+
+                tokio::time::sleep(Duration::from_secs(config.interval_secs.max(10))).await;
+            }
+        });
+    });
+}
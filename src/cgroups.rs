@@ -0,0 +1,325 @@
+// Cgroup module for Crusty-Crawler
+// Reads the CPU and memory limits of the cgroup the agent runs in, so containerized
+// deployments report usage relative to the container rather than the host
+
+#[derive(Clone, Default)]
+pub struct CgroupStats {
+    pub version: u8,
+    pub in_container: bool,
+    pub cpu_quota_cores: Option<f64>,
+    pub cpu_usage_usec: Option<u64>,
+    pub memory_limit: Option<u64>,
+    pub memory_usage: Option<u64>,
+    pub nr_periods: u64,
+    pub nr_throttled: u64,
+    pub throttled_usec: u64,
+}
+
+impl CgroupStats {
+    pub fn has_limits(&self) -> bool {
+        self.cpu_quota_cores.is_some() || self.memory_limit.is_some()
+    }
+
+    // Only worth reporting when something actually constrains the agent
+    pub fn is_relevant(&self) -> bool {
+        self.in_container || self.has_limits()
+    }
+
+    pub fn memory_percent(&self) -> Option<f64> {
+        match (self.memory_usage, self.memory_limit) {
+            (Some(usage), Some(limit)) if limit > 0 => Some(usage as f64 / limit as f64 * 100.0),
+            _ => None,
+        }
+    }
+}
+
+// CPU usage over a sampling window relative to the cgroup quota
+pub struct CgroupCpuSample {
+    pub usage_percent: f64,
+    pub quota_cores: f64,
+    pub throttled_periods: u64,
+}
+
+pub fn running_in_container() -> bool {
+    if Path::new("/.dockerenv").exists() || Path::new("/run/.containerenv").exists() {
+        return true;
+    }
+    if std::env::var_os("container").is_some() {
+        return true;
+    }
+
+    fs::read_to_string("/proc/1/cgroup")
+        .map(|cgroup| {
+            ["docker", "kubepods", "containerd", "lxc", "libpod"]
+                .iter()
+                .any(|marker| cgroup.contains(marker))
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "linux")]
+fn read_trimmed(path: &std::path::Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+// Parses "key value" lines such as cpu.stat and memory.stat
+#[cfg(target_os = "linux")]
+fn read_keyed(path: &std::path::Path) -> HashMap<String, u64> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let key = parts.next()?;
+            let value = parts.next()?.parse().ok()?;
+            Some((key.to_string(), value))
+        })
+        .collect()
+}
+
+// Path of our own cgroup for the given controller ("" for the unified v2 hierarchy)
+#[cfg(target_os = "linux")]
+fn own_cgroup_path(controller: &str) -> Option<String> {
+    let cgroup = fs::read_to_string("/proc/self/cgroup").ok()?;
+    cgroup.lines().find_map(|line| {
+        let mut parts = line.splitn(3, ':');
+        let _id = parts.next()?;
+        let controllers = parts.next()?;
+        let path = parts.next()?;
+        let matches = if controller.is_empty() {
+            controllers.is_empty()
+        } else {
+            controllers.split(',').any(|c| c == controller)
+        };
+        matches.then(|| path.to_string())
+    })
+}
+
+// Inside a cgroup namespace our path may not exist under /sys/fs/cgroup, fall back to the root
+#[cfg(target_os = "linux")]
+fn cgroup_dir(mount: &str, relative: Option<String>) -> std::path::PathBuf {
+    let root = std::path::PathBuf::from(mount);
+    if let Some(relative) = relative {
+        let candidate = root.join(relative.trim_start_matches('/'));
+        if candidate.exists() {
+            return candidate;
+        }
+    }
+    root
+}
+
+#[cfg(target_os = "linux")]
+fn read_cgroup_v2() -> CgroupStats {
+    let dir = cgroup_dir("/sys/fs/cgroup", own_cgroup_path(""));
+    let mut stats = CgroupStats {
+        version: 2,
+        ..Default::default()
+    };
+
+    // cpu.max is "<quota> <period>" or "max <period>"
+    if let Some(cpu_max) = read_trimmed(&dir.join("cpu.max")) {
+        let mut parts = cpu_max.split_whitespace();
+        if let (Some(quota), Some(period)) = (parts.next(), parts.next())
+            && let (Ok(quota), Ok(period)) = (quota.parse::<f64>(), period.parse::<f64>())
+            && period > 0.0
+        {
+            stats.cpu_quota_cores = Some(quota / period);
+        }
+    }
+
+    let cpu_stat = read_keyed(&dir.join("cpu.stat"));
+    stats.cpu_usage_usec = cpu_stat.get("usage_usec").copied();
+    stats.nr_periods = cpu_stat.get("nr_periods").copied().unwrap_or(0);
+    stats.nr_throttled = cpu_stat.get("nr_throttled").copied().unwrap_or(0);
+    stats.throttled_usec = cpu_stat.get("throttled_usec").copied().unwrap_or(0);
+
+    stats.memory_limit = read_trimmed(&dir.join("memory.max")).and_then(|v| v.parse().ok());
+    if let Some(current) =
+        read_trimmed(&dir.join("memory.current")).and_then(|v| v.parse::<u64>().ok())
+    {
+        // Like docker stats, don't count reclaimable page cache as usage
+        let inactive = read_keyed(&dir.join("memory.stat"))
+            .get("inactive_file")
+            .copied()
+            .unwrap_or(0);
+        stats.memory_usage = Some(current.saturating_sub(inactive));
+    }
+
+    stats
+}
+
+#[cfg(target_os = "linux")]
+fn read_cgroup_v1() -> CgroupStats {
+    let cpu_dir = ["/sys/fs/cgroup/cpu,cpuacct", "/sys/fs/cgroup/cpu"]
+        .iter()
+        .find(|p| Path::new(p).exists())
+        .map(|mount| cgroup_dir(mount, own_cgroup_path("cpu")));
+    let memory_dir = cgroup_dir("/sys/fs/cgroup/memory", own_cgroup_path("memory"));
+    let mut stats = CgroupStats {
+        version: 1,
+        ..Default::default()
+    };
+
+    if let Some(cpu_dir) = cpu_dir {
+        let quota =
+            read_trimmed(&cpu_dir.join("cpu.cfs_quota_us")).and_then(|v| v.parse::<i64>().ok());
+        let period =
+            read_trimmed(&cpu_dir.join("cpu.cfs_period_us")).and_then(|v| v.parse::<i64>().ok());
+        if let (Some(quota), Some(period)) = (quota, period) {
+            // A quota of -1 means unlimited
+            if quota > 0 && period > 0 {
+                stats.cpu_quota_cores = Some(quota as f64 / period as f64);
+            }
+        }
+
+        stats.cpu_usage_usec = read_trimmed(&cpu_dir.join("cpuacct.usage"))
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(|nanos| nanos / 1000);
+
+        let cpu_stat = read_keyed(&cpu_dir.join("cpu.stat"));
+        stats.nr_periods = cpu_stat.get("nr_periods").copied().unwrap_or(0);
+        stats.nr_throttled = cpu_stat.get("nr_throttled").copied().unwrap_or(0);
+        stats.throttled_usec = cpu_stat.get("throttled_time").copied().unwrap_or(0) / 1000;
+    }
+
+    // Unlimited v1 memory cgroups report a huge page-aligned number instead of "max"
+    stats.memory_limit = read_trimmed(&memory_dir.join("memory.limit_in_bytes"))
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|limit| *limit < (1u64 << 62));
+    if let Some(usage) =
+        read_trimmed(&memory_dir.join("memory.usage_in_bytes")).and_then(|v| v.parse::<u64>().ok())
+    {
+        let inactive = read_keyed(&memory_dir.join("memory.stat"))
+            .get("total_inactive_file")
+            .copied()
+            .unwrap_or(0);
+        stats.memory_usage = Some(usage.saturating_sub(inactive));
+    }
+
+    stats
+}
+
+#[cfg(target_os = "linux")]
+pub fn read_cgroup_stats() -> Option<CgroupStats> {
+    let mut stats = if Path::new("/sys/fs/cgroup/cgroup.controllers").exists() {
+        read_cgroup_v2()
+    } else if Path::new("/sys/fs/cgroup/memory").exists() {
+        read_cgroup_v1()
+    } else {
+        return None;
+    };
+
+    stats.in_container = running_in_container();
+    Some(stats)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_cgroup_stats() -> Option<CgroupStats> {
+    None
+}
+
+// Measures cgroup CPU usage against its quota over the given window
+pub async fn sample_cgroup_cpu(window: Duration) -> Option<CgroupCpuSample> {
+    let before = read_cgroup_stats()?;
+    let quota_cores = before.cpu_quota_cores?;
+    let start_usage = before.cpu_usage_usec?;
+
+    tokio::time::sleep(window).await;
+
+    let after = read_cgroup_stats()?;
+    let used_usec = after.cpu_usage_usec?.saturating_sub(start_usage) as f64;
+    let available_usec = window.as_micros() as f64 * quota_cores;
+
+    Some(CgroupCpuSample {
+        usage_percent: if available_usec > 0.0 {
+            used_usec / available_usec * 100.0
+        } else {
+            0.0
+        },
+        quota_cores,
+        throttled_periods: after.nr_throttled.saturating_sub(before.nr_throttled),
+    })
+}
+
+pub fn cgroup_metrics(stats: &CgroupStats) -> Vec<Metric> {
+    let version = stats.version.to_string();
+    let mut metrics = vec![
+        Metric::new(
+            "cgroup_in_container",
+            if stats.in_container { 1.0 } else { 0.0 },
+        )
+        .label("version", &version),
+        Metric::new("cgroup_cpu_periods_total", stats.nr_periods as f64),
+        Metric::new(
+            "cgroup_cpu_throttled_periods_total",
+            stats.nr_throttled as f64,
+        ),
+        Metric::new(
+            "cgroup_cpu_throttled_seconds_total",
+            stats.throttled_usec as f64 / 1_000_000.0,
+        ),
+    ];
+
+    if let Some(cores) = stats.cpu_quota_cores {
+        metrics.push(Metric::new("cgroup_cpu_limit_cores", cores));
+    }
+    if let Some(usage) = stats.cpu_usage_usec {
+        metrics.push(Metric::new(
+            "cgroup_cpu_usage_seconds_total",
+            usage as f64 / 1_000_000.0,
+        ));
+    }
+    if let Some(limit) = stats.memory_limit {
+        metrics.push(Metric::new("cgroup_memory_limit_bytes", limit as f64));
+    }
+    if let Some(usage) = stats.memory_usage {
+        metrics.push(Metric::new("cgroup_memory_usage_bytes", usage as f64));
+    }
+    if let Some(percent) = stats.memory_percent() {
+        metrics.push(Metric::new("cgroup_memory_usage_percent", percent));
+    }
+
+    metrics
+}
+
+pub fn cgroup_status(stats: &CgroupStats) -> String {
+    let mut output = String::new();
+    output.push_str("\n=== Container Limits ===\n");
+    output.push_str(&format!(
+        "Cgroup: v{}{}\n",
+        stats.version,
+        if stats.in_container {
+            " (inside a container)"
+        } else {
+            ""
+        }
+    ));
+
+    match stats.cpu_quota_cores {
+        Some(cores) => output.push_str(&format!("CPU Limit: {:.2} cores\n", cores)),
+        None => output.push_str("CPU Limit: unlimited\n"),
+    }
+    if stats.nr_throttled > 0 {
+        output.push_str(&format!(
+            "⚠️ CPU throttled in {} of {} periods ({:.1}s total)\n",
+            stats.nr_throttled,
+            stats.nr_periods,
+            stats.throttled_usec as f64 / 1_000_000.0
+        ));
+    }
+
+    match (stats.memory_usage, stats.memory_limit) {
+        (Some(usage), Some(limit)) => output.push_str(&format!(
+            "Memory: {} MB of {} MB ({:.1}%)\n",
+            usage / 1024 / 1024,
+            limit / 1024 / 1024,
+            stats.memory_percent().unwrap_or(0.0)
+        )),
+        (Some(usage), None) => {
+            output.push_str(&format!("Memory: {} MB (no limit)\n", usage / 1024 / 1024))
+        }
+        _ => output.push_str("Memory: not available\n"),
+    }
+
+    output
+}
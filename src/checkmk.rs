@@ -0,0 +1,225 @@
+// Checkmk module for Crusty-Crawler
+// Answers on the Checkmk agent port with agent-format sections, so a Checkmk site can monitor
+// the host without installing another agent
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CheckmkConfig {
+    pub enabled: bool,
+    pub bind: String,
+    // Addresses or CIDR ranges allowed to connect, like the agent's only_from. Empty allows
+    // everyone, the agent protocol itself has no authentication
+    pub only_from: Vec<String>,
+}
+
+impl Default for CheckmkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: "[::]:6556".to_string(),
+            only_from: Vec::new(),
+        }
+    }
+}
+
+fn ip_allowed(ip: std::net::IpAddr, rule: &str) -> bool {
+    use std::net::IpAddr;
+
+    let (address, prefix) = match rule.split_once('/') {
+        Some((address, prefix)) => (address, prefix.parse::<u32>().ok()),
+        None => (rule, None),
+    };
+    let Ok(network) = address.trim().parse::<IpAddr>() else {
+        return false;
+    };
+    // Clients show up as IPv4-mapped addresses on dual-stack listeners
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        v4 => v4,
+    };
+
+    let (ip, network, bits) = match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            (u32::from(ip) as u128, u32::from(network) as u128, 32)
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => (u128::from(ip), u128::from(network), 128),
+        _ => return false,
+    };
+    let prefix = prefix.unwrap_or(bits).min(bits);
+    if prefix == 0 {
+        return true;
+    }
+    let shift = bits - prefix;
+    ip >> shift == network >> shift
+}
+
+// Local check metrics are name=value;warn;crit;min;max without units
+fn checkmk_metrics(perfdata: &[PerfData]) -> String {
+    if perfdata.is_empty() {
+        return "-".to_string();
+    }
+    let optional = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+    perfdata
+        .iter()
+        .map(|p| {
+            format!(
+                "{}={};{};{};{};{}",
+                prometheus_name(&p.label),
+                p.value,
+                optional(p.warn),
+                optional(p.crit),
+                optional(p.min),
+                optional(p.max)
+            )
+            .trim_end_matches(';')
+            .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+fn checkmk_output(checks: &[CheckResult], devices: &DeviceFilters) -> String {
+    let mut sys = sysinfo::System::new();
+    sys.refresh_memory();
+    let host = sysinfo::System::host_name().unwrap_or_else(|| "localhost".to_string());
+
+    let mut out = String::from("<<<check_mk>>>\n");
+    out.push_str(&format!(
+        "Version: crusty-crawler-{}\n",
+        env!("CARGO_PKG_VERSION")
+    ));
+    out.push_str(&format!("AgentOS: {}\n", std::env::consts::OS));
+    out.push_str(&format!("Hostname: {}\n", host));
+
+    out.push_str("<<<uptime>>>\n");
+    out.push_str(&format!("{}\n", sysinfo::System::uptime()));
+
+    // Same layout as /proc/meminfo, which the mem section parser expects
+    out.push_str("<<<mem>>>\n");
+    for (key, bytes) in [
+        ("MemTotal", sys.total_memory()),
+        ("MemFree", sys.free_memory()),
+        ("MemAvailable", sys.available_memory()),
+        ("SwapTotal", sys.total_swap()),
+        ("SwapFree", sys.free_swap()),
+    ] {
+        out.push_str(&format!("{}: {} kB\n", key, bytes / 1024));
+    }
+
+    // Load averages, then the processor count on its own line
+    let load = sysinfo::System::load_average();
+    out.push_str("<<<cpu>>>\n");
+    out.push_str(&format!(
+        "{:.2} {:.2} {:.2} 1/1 {}\n{}\n",
+        load.one,
+        load.five,
+        load.fifteen,
+        std::process::id(),
+        sysinfo::System::physical_core_count().unwrap_or(1).max(1)
+    ));
+
+    out.push_str("<<<df>>>\n");
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    for disk in disks.list().iter().filter(|disk| devices.allows_disk(disk)) {
+        let total = disk.total_space() / 1024;
+        let available = disk.available_space() / 1024;
+        let used = total.saturating_sub(available);
+        let percent = (used * 100).checked_div(total).unwrap_or(0);
+        out.push_str(&format!(
+            "{} {} {} {} {} {}% {}\n",
+            disk.name().to_string_lossy().replace(' ', "_"),
+            disk.file_system().to_string_lossy(),
+            total,
+            used,
+            available,
+            percent,
+            disk.mount_point().to_string_lossy()
+        ));
+    }
+
+    // Every Crusty check becomes a Checkmk service through the local check section
+    out.push_str("<<<local:sep(0)>>>\n");
+    for check in checks {
+        out.push_str(&format!(
+            "{} \"Crusty {}\" {} {}\n",
+            check.state.exit_code(),
+            check.name,
+            checkmk_metrics(&check.perfdata),
+            check.output.replace('\n', " ")
+        ));
+    }
+    out
+}
+
+// The bind address is read once at startup, only_from is checked against the current config
+fn spawn_checkmk_listener(server_state: Arc<Mutex<ServerState>>) {
+    let config = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.checkmk.clone()
+    };
+    if !config.enabled {
+        return;
+    }
+
+    std::thread::spawn(move || {
+        let rt = match Runtime::new() {
+            Ok(rt) => rt,
+            Err(e) => {
+                eprintln!("❌ Failed to start Checkmk listener: {}", e);
+                return;
+            }
+        };
+
+        rt.block_on(async {
+            let listener = match config.bind.parse::<SocketAddr>() {
+                Ok(addr) => bind_tcp_listener(addr),
+                Err(_) => tokio::net::TcpListener::bind(&config.bind).await,
+            };
+            let listener = match listener {
+                Ok(listener) => listener,
+                Err(e) => {
+                    eprintln!(
+                        "❌ Failed to bind Checkmk listener to {}: {}",
+                        config.bind, e
+                    );
+                    return;
+                }
+            };
+            println!("📟 Checkmk agent listening on {}", config.bind);
+
+            loop {
+                let Ok((mut stream, peer)) = listener.accept().await else {
+                    continue;
+                };
+                let (only_from, runner) = {
+                    let state = server_state.lock().unwrap();
+                    let runner = CheckRunner::from_state(&state);
+                    let auth_manager = state.auth_manager.lock().unwrap();
+                    (auth_manager.config.checkmk.only_from.clone(), runner)
+                };
+                if !only_from.is_empty()
+                    && !only_from.iter().any(|rule| ip_allowed(peer.ip(), rule))
+                {
+                    eprintln!("⚠️  Refused Checkmk connection from {}", peer.ip());
+                    continue;
+                }
+
+                tokio::spawn(async move {
+                    use tokio::io::AsyncWriteExt;
+
+                    let max_age = Duration::from_secs(runner.config.interval_secs.max(5) * 2);
+                    let mut checks = Vec::new();
+                    for name in available_checks(&runner.config) {
+                        if let Some((result, _)) = runner.cached(&name, max_age).await {
+                            checks.push(result);
+                        }
+                    }
+                    let output = checkmk_output(&checks, &runner.devices);
+                    let _ = stream.write_all(output.as_bytes()).await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        });
+    });
+}
@@ -0,0 +1,731 @@
+// Checks module for Crusty-Crawler
+// Turns the collected statistics into Nagios-style check results with perfdata
+
+use std::fmt;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum CheckState {
+    Ok,
+    Warning,
+    Critical,
+    Unknown,
+}
+
+impl CheckState {
+    // Nagios plugin exit code for this state
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CheckState::Ok => 0,
+            CheckState::Warning => 1,
+            CheckState::Critical => 2,
+            CheckState::Unknown => 3,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CheckState::Ok => "OK",
+            CheckState::Warning => "WARNING",
+            CheckState::Critical => "CRITICAL",
+            CheckState::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+// A single perfdata item: 'label'=value[UOM];[warn];[crit];[min];[max]
+#[derive(Serialize, Clone, Debug)]
+pub struct PerfData {
+    pub label: String,
+    pub value: f64,
+    pub uom: String,
+    pub warn: Option<f64>,
+    pub crit: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl PerfData {
+    pub fn new(label: &str, value: f64, uom: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            value,
+            uom: uom.to_string(),
+            warn: None,
+            crit: None,
+            min: None,
+            max: None,
+        }
+    }
+
+    pub fn thresholds(mut self, thresholds: &Thresholds) -> Self {
+        self.warn = Some(thresholds.warning);
+        self.crit = Some(thresholds.critical);
+        self
+    }
+
+    pub fn range(mut self, min: f64, max: f64) -> Self {
+        self.min = Some(min);
+        self.max = Some(max);
+        self
+    }
+}
+
+fn format_perf_number(value: f64) -> String {
+    let formatted = format!("{:.2}", value);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+impl fmt::Display for PerfData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Labels containing spaces, quotes or '=' must be single-quoted
+        let label = if self.label.contains([' ', '=', '\'']) {
+            format!("'{}'", self.label.replace('\'', "''"))
+        } else {
+            self.label.clone()
+        };
+
+        let optional = [self.warn, self.crit, self.min, self.max]
+            .iter()
+            .map(|v| v.map(format_perf_number).unwrap_or_default())
+            .collect::<Vec<_>>();
+
+        let mut fields = format!("{}={}{}", label, format_perf_number(self.value), self.uom);
+        for value in &optional {
+            fields.push(';');
+            fields.push_str(value);
+        }
+
+        // Trailing empty fields can be dropped
+        write!(f, "{}", fields.trim_end_matches(';'))
+    }
+}
+
+// Space separated perfdata string as expected after the '|' in plugin output
+pub fn format_perfdata(perfdata: &[PerfData]) -> String {
+    perfdata
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Thresholds {
+    pub warning: f64,
+    pub critical: f64,
+}
+
+impl Thresholds {
+    pub fn evaluate(&self, value: f64) -> CheckState {
+        if value >= self.critical {
+            CheckState::Critical
+        } else if value >= self.warning {
+            CheckState::Warning
+        } else {
+            CheckState::Ok
+        }
+    }
+
+    // For values where lower is worse, like signal strength
+    pub fn evaluate_below(&self, value: f64) -> CheckState {
+        if value <= self.critical {
+            CheckState::Critical
+        } else if value <= self.warning {
+            CheckState::Warning
+        } else {
+            CheckState::Ok
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CheckConfig {
+    pub cpu: Thresholds,
+    pub memory: Thresholds,
+    pub disk: Thresholds,
+    // Signal strength in dBm, alerts when it drops to or below these
+    pub wifi_signal: Thresholds,
+    // Percent of fs.file-max, pid_max and nf_conntrack_max in use
+    pub kernel_limits: Thresholds,
+    // Available entropy in bits, alerts when it drops to or below these
+    pub entropy: Thresholds,
+    // How long a cached result may be served to pollers before re-running the check
+    pub cache_max_age_secs: u64,
+    // How often the background loop runs every check to keep alerts current
+    pub interval_secs: u64,
+    pub databases: Vec<DatabaseCheck>,
+    pub http: Vec<HttpCheck>,
+    // Baseline of listeners and long-running processes for the security check
+    pub security: SecurityConfig,
+    // Files and directories hashed on a schedule for the fim check
+    pub fim: FimConfig,
+    // OOM kills and core dumps found in the journal
+    pub crashes: CrashConfig,
+    // Hardware error signatures looked for in the kernel log
+    pub kernel_log: KernelLogConfig,
+    // Print queues, USB devices, monitors and sound cards, for the printers and displays checks
+    pub peripherals: PeripheralConfig,
+    // Which alerts are folded into one incident and notification
+    pub grouping: AlertGroupingConfig,
+    // Parent checks by check name, e.g. {"website": ["nginx"]}. A check's notifications are
+    // held back while one of its parents is alerting
+    pub dependencies: BTreeMap<String, Vec<String>>,
+    // Weights behind the 0-100 host health score
+    pub health_score: HealthScoreConfig,
+}
+
+impl Default for CheckConfig {
+    fn default() -> Self {
+        Self {
+            cpu: Thresholds {
+                warning: 80.0,
+                critical: 95.0,
+            },
+            memory: Thresholds {
+                warning: 80.0,
+                critical: 95.0,
+            },
+            disk: Thresholds {
+                warning: 85.0,
+                critical: 95.0,
+            },
+            wifi_signal: Thresholds {
+                warning: -70.0,
+                critical: -80.0,
+            },
+            kernel_limits: Thresholds {
+                warning: 80.0,
+                critical: 95.0,
+            },
+            entropy: Thresholds {
+                warning: 200.0,
+                critical: 64.0,
+            },
+            cache_max_age_secs: 30,
+            interval_secs: 60,
+            databases: Vec::new(),
+            http: Vec::new(),
+            security: SecurityConfig::default(),
+            fim: FimConfig::default(),
+            crashes: CrashConfig::default(),
+            kernel_log: KernelLogConfig::default(),
+            peripherals: PeripheralConfig::default(),
+            grouping: AlertGroupingConfig::default(),
+            dependencies: BTreeMap::new(),
+            health_score: HealthScoreConfig::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct CheckResult {
+    pub name: String,
+    pub state: CheckState,
+    pub output: String,
+    pub perfdata: Vec<PerfData>,
+    pub checked_at: String,
+}
+
+impl CheckResult {
+    pub fn new(name: &str, state: CheckState, output: String, perfdata: Vec<PerfData>) -> Self {
+        Self {
+            name: name.to_string(),
+            state,
+            output,
+            perfdata,
+            checked_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    // Full plugin output line: "STATE - output | perfdata"
+    pub fn plugin_output(&self) -> String {
+        if self.perfdata.is_empty() {
+            format!("{} - {}", self.state.label(), self.output)
+        } else {
+            format!(
+                "{} - {} | {}",
+                self.state.label(),
+                self.output,
+                format_perfdata(&self.perfdata)
+            )
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CheckResponse<'a> {
+    #[serde(flatten)]
+    result: &'a CheckResult,
+    exit_code: i32,
+    perfdata_string: String,
+    plugin_output: String,
+    cached: bool,
+    age_seconds: u64,
+}
+
+impl<'a> CheckResponse<'a> {
+    fn new(result: &'a CheckResult, age: Option<Duration>) -> Self {
+        Self {
+            result,
+            exit_code: result.state.exit_code(),
+            perfdata_string: format_perfdata(&result.perfdata),
+            plugin_output: result.plugin_output(),
+            cached: age.is_some(),
+            age_seconds: age.map(|a| a.as_secs()).unwrap_or(0),
+        }
+    }
+}
+
+struct CachedCheck {
+    result: CheckResult,
+    collected_at: Instant,
+}
+
+// Last result of every check so pollers don't trigger a fresh run on each request
+#[derive(Default)]
+pub struct CheckCache {
+    entries: HashMap<String, CachedCheck>,
+}
+
+impl CheckCache {
+    // Returns the cached result and its age if it is younger than max_age
+    pub fn get_fresh(&self, name: &str, max_age: Duration) -> Option<(CheckResult, Duration)> {
+        self.entries.get(name).and_then(|cached| {
+            let age = cached.collected_at.elapsed();
+            if age <= max_age {
+                Some((cached.result.clone(), age))
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn store(&mut self, result: CheckResult) {
+        self.entries.insert(
+            result.name.clone(),
+            CachedCheck {
+                result,
+                collected_at: Instant::now(),
+            },
+        );
+    }
+}
+
+async fn check_cpu(thresholds: &Thresholds, system: &dyn SystemInfoProvider) -> CheckResult {
+    // Inside a CPU-limited cgroup, usage only means something relative to the quota
+    if let Some(sample) = sample_cgroup_cpu(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL).await {
+        let mut state = thresholds.evaluate(sample.usage_percent);
+        let mut output = format!(
+            "CPU usage is {:.1}% of the {:.2} core limit",
+            sample.usage_percent, sample.quota_cores
+        );
+        if sample.throttled_periods > 0 {
+            state = state.max(CheckState::Warning);
+            output.push_str(&format!(
+                ", throttled in {} periods",
+                sample.throttled_periods
+            ));
+        }
+
+        return CheckResult::new(
+            "cpu",
+            state,
+            output,
+            vec![
+                PerfData::new("cpu", sample.usage_percent, "%").thresholds(thresholds),
+                PerfData::new("throttled_periods", sample.throttled_periods as f64, "c"),
+            ],
+        );
+    }
+
+    system.cpu_usage();
+    tokio::time::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL).await;
+    let usage = system.cpu_usage() as f64;
+    CheckResult::new(
+        "cpu",
+        thresholds.evaluate(usage),
+        format!("CPU usage is {:.1}%", usage),
+        vec![
+            PerfData::new("cpu", usage, "%")
+                .thresholds(thresholds)
+                .range(0.0, 100.0),
+        ],
+    )
+}
+
+fn check_memory(thresholds: &Thresholds, system: &dyn SystemInfoProvider) -> CheckResult {
+    let (mut used, mut total) = system.memory();
+    let mut scope = "Memory";
+
+    // Report against the cgroup limit when it is tighter than the host memory
+    if let Some(stats) = read_cgroup_stats()
+        && let (Some(limit), Some(usage)) = (stats.memory_limit, stats.memory_usage)
+        && limit > 0
+        && (total == 0 || limit < total)
+    {
+        total = limit;
+        used = usage;
+        scope = "Container memory";
+    }
+
+    if total == 0 {
+        return CheckResult::new(
+            "memory",
+            CheckState::Unknown,
+            "Memory information not available".to_string(),
+            Vec::new(),
+        );
+    }
+
+    let percent = used as f64 / total as f64 * 100.0;
+    let total_mb = (total / 1024 / 1024) as f64;
+    let warn_mb = total_mb * thresholds.warning / 100.0;
+    let crit_mb = total_mb * thresholds.critical / 100.0;
+
+    let mut used_perf =
+        PerfData::new("memory_used", (used / 1024 / 1024) as f64, "MB").range(0.0, total_mb);
+    used_perf.warn = Some(warn_mb.floor());
+    used_perf.crit = Some(crit_mb.floor());
+
+    CheckResult::new(
+        "memory",
+        thresholds.evaluate(percent),
+        format!(
+            "{} usage is {:.1}% ({} MB of {} MB)",
+            scope,
+            percent,
+            used / 1024 / 1024,
+            total / 1024 / 1024
+        ),
+        vec![
+            PerfData::new("memory", percent, "%")
+                .thresholds(thresholds)
+                .range(0.0, 100.0),
+            used_perf,
+        ],
+    )
+}
+
+fn check_disk_usage(thresholds: &Thresholds, devices: &DeviceFilters) -> CheckResult {
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    let mut state = CheckState::Ok;
+    let mut summaries = Vec::new();
+    let mut perfdata = Vec::new();
+
+    for disk in disks.list().iter().filter(|disk| devices.allows_disk(disk)) {
+        let total = disk.total_space();
+        if total == 0 {
+            continue;
+        }
+
+        let used = total.saturating_sub(disk.available_space());
+        let percent = used as f64 / total as f64 * 100.0;
+        let mount = disk.mount_point().to_string_lossy().to_string();
+
+        state = state.max(thresholds.evaluate(percent));
+        summaries.push(format!("{} {:.1}%", mount, percent));
+        perfdata.push(
+            PerfData::new(&mount, percent, "%")
+                .thresholds(thresholds)
+                .range(0.0, 100.0),
+        );
+    }
+
+    if perfdata.is_empty() {
+        return CheckResult::new(
+            "disk",
+            CheckState::Unknown,
+            "No disks with capacity information found".to_string(),
+            Vec::new(),
+        );
+    }
+
+    CheckResult::new(
+        "disk",
+        state,
+        format!("Disk usage: {}", summaries.join(", ")),
+        perfdata,
+    )
+}
+
+pub const BUILTIN_CHECKS: &[&str] = &["cpu", "memory", "disk"];
+
+// Built-in checks, the hardware-specific ones this machine supports, configured checks and
+// derived metrics with thresholds
+pub fn available_checks(config: &CheckConfig) -> Vec<String> {
+    let mut checks: Vec<String> = BUILTIN_CHECKS.iter().map(|name| name.to_string()).collect();
+    if read_throttle_status().is_some() {
+        checks.push("throttling".to_string());
+    }
+    if ipmi_available() {
+        checks.push("ipmi".to_string());
+    }
+    if wifi_available() {
+        checks.push("wifi".to_string());
+    }
+    if !read_raid_arrays().is_empty() {
+        checks.push("raid".to_string());
+    }
+    if !read_kernel_limits().is_empty() {
+        checks.push("kernel_limits".to_string());
+    }
+    if !read_zfs_pools().is_empty() {
+        checks.push("zfs".to_string());
+    }
+    if !read_btrfs_filesystems().is_empty() {
+        checks.push("btrfs".to_string());
+    }
+    if config.security.enabled {
+        checks.push("security".to_string());
+    }
+    if !config.fim.paths.is_empty() {
+        checks.push("fim".to_string());
+    }
+    if crashes_available(&config.crashes) {
+        checks.push("crashes".to_string());
+    }
+    if kernel_log_available(&config.kernel_log) {
+        checks.push("kernel_log".to_string());
+    }
+    if printers_available(&config.peripherals) {
+        checks.push("printers".to_string());
+    }
+    if displays_available(&config.peripherals) {
+        checks.push("displays".to_string());
+    }
+    checks.extend(config.databases.iter().map(|db| db.name.clone()));
+    checks.extend(config.http.iter().map(|http| http.name.clone()));
+    checks.extend(derived_checks());
+    checks
+}
+
+// Parents run before the checks depending on them, so a child's failure finds its parent's
+// alert already raised
+fn order_by_dependencies(
+    mut names: Vec<String>,
+    dependencies: &BTreeMap<String, Vec<String>>,
+) -> Vec<String> {
+    let mut ordered = Vec::with_capacity(names.len());
+    while !names.is_empty() {
+        let ready = names
+            .iter()
+            .position(|name| {
+                dependencies
+                    .get(name)
+                    .is_none_or(|parents| parents.iter().all(|parent| !names.contains(parent)))
+            })
+            // Cycles are refused when the config is loaded
+            .unwrap_or(0);
+        ordered.push(names.remove(ready));
+    }
+    ordered
+}
+
+fn visit_dependencies<'a>(
+    check: &'a str,
+    dependencies: &'a BTreeMap<String, Vec<String>>,
+    path: &mut Vec<&'a str>,
+) -> Result<(), String> {
+    if path.contains(&check) {
+        path.push(check);
+        return Err(format!(
+            "checks.dependencies: circular dependency {}",
+            path.join(" -> ")
+        ));
+    }
+    path.push(check);
+    for parent in dependencies.get(check).into_iter().flatten() {
+        visit_dependencies(parent, dependencies, path)?;
+    }
+    path.pop();
+    Ok(())
+}
+
+pub fn validate_dependencies(dependencies: &BTreeMap<String, Vec<String>>) -> Result<(), String> {
+    for check in dependencies.keys() {
+        visit_dependencies(check, dependencies, &mut Vec::new())?;
+    }
+    Ok(())
+}
+
+pub async fn run_check(
+    name: &str,
+    config: &CheckConfig,
+    devices: &DeviceFilters,
+    system: &dyn SystemInfoProvider,
+) -> Option<CheckResult> {
+    match name {
+        "cpu" => Some(check_cpu(&config.cpu, system).await),
+        "memory" => Some(check_memory(&config.memory, system)),
+        "disk" => Some(check_disk_usage(&config.disk, devices)),
+        "throttling" => Some(check_throttling()),
+        "ipmi" => Some(check_ipmi()),
+        "wifi" => Some(check_wifi(&config.wifi_signal)),
+        "kernel_limits" => Some(check_kernel_limits(config)),
+        "raid" => Some(check_raid()),
+        "zfs" => Some(check_zfs()),
+        "btrfs" => Some(check_btrfs()),
+        "security" if config.security.enabled => Some(check_security(&config.security).await),
+        "fim" if !config.fim.paths.is_empty() => Some(check_fim(&config.fim)),
+        "crashes" if crashes_available(&config.crashes) => Some(check_crashes(&config.crashes)),
+        "kernel_log" if kernel_log_available(&config.kernel_log) => {
+            Some(check_kernel_log(&config.kernel_log))
+        }
+        "printers" if printers_available(&config.peripherals) => {
+            Some(check_printers(&config.peripherals))
+        }
+        "displays" if displays_available(&config.peripherals) => {
+            Some(check_displays(&config.peripherals))
+        }
+        _ => {
+            if let Some(db) = config.databases.iter().find(|db| db.name == name) {
+                Some(check_database(db).await)
+            } else if let Some(http) = config.http.iter().find(|http| http.name == name) {
+                Some(check_http(http).await)
+            } else {
+                check_derived(name)
+            }
+        }
+    }
+}
+
+// Everything needed to run checks without holding the ServerState lock
+#[derive(Clone)]
+pub struct CheckRunner {
+    pub config: CheckConfig,
+    pub cache: Arc<Mutex<CheckCache>>,
+    pub alerts: Arc<Mutex<AlertManager>>,
+    pub contacts: Vec<Contact>,
+    pub notifications: NotificationSettings,
+    pub push: PushConfig,
+    pub devices: DeviceFilters,
+    pub system: Arc<dyn SystemInfoProvider>,
+}
+
+impl CheckRunner {
+    fn from_state(state: &ServerState) -> Self {
+        let auth_manager = state.auth_manager.lock().unwrap();
+        Self {
+            config: auth_manager.config.checks.clone(),
+            cache: state.check_cache.clone(),
+            alerts: state.alert_manager.clone(),
+            contacts: auth_manager.config.contacts.clone(),
+            notifications: NotificationSettings {
+                templates: auth_manager.config.notification_templates.clone(),
+                smtp_config: auth_manager.config.smtp_config.clone(),
+                on_call: auth_manager.config.on_call.clone(),
+                throttle: auth_manager.config.notification_throttle.clone(),
+            },
+            push: auth_manager.config.push.clone(),
+            devices: auth_manager.config.metrics.devices.clone(),
+            system: state.system.clone(),
+        }
+    }
+
+    // Runs a check immediately, caches the result and feeds it into alerting
+    pub async fn run(&self, name: &str) -> Option<CheckResult> {
+        let mut span = start_span(
+            format!("check {}", name),
+            vec![opentelemetry::KeyValue::new("check.name", name.to_string())],
+        );
+        let result = run_check(name, &self.config, &self.devices, self.system.as_ref()).await;
+        if let Some(result) = &result {
+            span.set_attribute(opentelemetry::KeyValue::new(
+                "check.state",
+                result.state.label(),
+            ));
+        }
+        end_span(span, None);
+
+        let result = result?;
+        self.cache.lock().unwrap().store(result.clone());
+        record_check_state(&result);
+
+        let (changed, notify) = {
+            let mut alerts = self.alerts.lock().unwrap();
+            let changed = alerts.process_result(&result, &self.config.grouping);
+            let notify = changed.as_ref().is_some_and(|alert| {
+                let parent = alerts.failing_parent(&alert.check, &self.config.dependencies);
+                if let Some(parent) = &parent {
+                    println!(
+                        "🔕 Not notifying about {}, it depends on {} which is alerting",
+                        alert.check, parent
+                    );
+                }
+                parent.is_none() && alerts.notifies(alert)
+            });
+            (changed, notify)
+        };
+        if let Some(alert) = changed {
+            record_alert_event(&alert);
+            if notify {
+                tokio::spawn(dispatch_push(self.push.clone(), alert.clone()));
+                tokio::spawn(dispatch_notifications(
+                    self.contacts.clone(),
+                    self.notifications.clone(),
+                    alert,
+                ));
+            }
+        }
+
+        Some(result)
+    }
+
+    // Serve a cached result when it is fresh enough, otherwise run the check
+    pub async fn cached(
+        &self,
+        name: &str,
+        max_age: Duration,
+    ) -> Option<(CheckResult, Option<Duration>)> {
+        if let Some((result, age)) = self.cache.lock().unwrap().get_fresh(name, max_age) {
+            return Some((result, Some(age)));
+        }
+
+        self.run(name).await.map(|result| (result, None))
+    }
+
+    pub async fn run_all(&self) -> Vec<CheckResult> {
+        let mut results = Vec::new();
+        let names =
+            order_by_dependencies(available_checks(&self.config), &self.config.dependencies);
+        for name in names {
+            if let Some(result) = self.run(&name).await {
+                results.push(result);
+            }
+        }
+        results
+    }
+}
+
+// Periodically runs every check so alerts fire even when nobody is polling the API
+fn spawn_check_loop(server_state: Arc<Mutex<ServerState>>) {
+    std::thread::spawn(move || {
+        let rt = match Runtime::new() {
+            Ok(rt) => rt,
+            Err(e) => {
+                eprintln!("❌ Failed to start check loop: {}", e);
+                return;
+            }
+        };
+
+        rt.block_on(async {
+            loop {
+                let runner = CheckRunner::from_state(&server_state.lock().unwrap());
+                let results = runner.run_all().await;
+                record_health_score(&compute_health_score(
+                    &runner.config.health_score,
+                    &results,
+                    &resource_usage(&runner.devices),
+                ));
+
+                let interval = runner.config.interval_secs.max(5);
+                tokio::time::sleep(Duration::from_secs(interval)).await;
+            }
+        });
+    });
+}
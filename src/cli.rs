@@ -8,6 +8,26 @@ pub fn run_cli() -> Result<(), Box<dyn std::error::Error>> {
     println!("==========================\n");
 
     let server_state = Arc::new(Mutex::new(ServerState::default()));
+    init_platform_capabilities();
+    spawn_check_loop(server_state.clone());
+    spawn_ebpf_probes(server_state.clone());
+    spawn_scheduler(server_state.clone());
+    spawn_dashboard_stream(server_state.clone());
+    spawn_central_reporter(server_state.clone());
+    spawn_cluster_sync(server_state.clone());
+    spawn_fleet_alerting(server_state.clone());
+    spawn_derived_metrics(server_state.clone());
+    spawn_load_monitor(server_state.clone());
+    spawn_config_watcher(server_state.clone());
+    spawn_otlp_exporter(server_state.clone());
+    spawn_statsd_listener(server_state.clone());
+    spawn_zabbix_sender(server_state.clone());
+    spawn_checkmk_listener(server_state.clone());
+    spawn_event_timeline();
+    spawn_diagnostic_snapshots(server_state.clone());
+    spawn_mdns_advertiser(server_state.clone());
+    spawn_crash_monitor(server_state.clone());
+    spawn_kernel_log_scanner(server_state.clone());
 
     // Check if setup is needed
     let needs_setup = {
@@ -48,10 +68,16 @@ fn setup_wizard(server_state: &Arc<Mutex<ServerState>>) -> Result<(), Box<dyn st
     };
 
     // Get password
+    let policy = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.password_policy.clone()
+    };
     let password = loop {
-        let pass1 = rpassword::prompt_password("Enter password (min 8 characters): ")?;
-        if pass1.len() < 8 {
-            println!("❌ Password must be at least 8 characters.\n");
+        let pass1 =
+            rpassword::prompt_password(format!("Enter password ({}): ", policy.describe()))?;
+        if let Err(e) = policy.check(&pass1) {
+            println!("❌ {}.\n", e);
             continue;
         }
         
@@ -112,7 +138,7 @@ fn setup_wizard(server_state: &Arc<Mutex<ServerState>>) -> Result<(), Box<dyn st
         Ok(()) => {
             println!("\n✅ User registered successfully!");
             println!("📝 Your access token: {}\n", access_token);
-            println!("⚠️  Save this token - you'll need it to access the web interface.\n");
+            println!("⚠️  Save this token now - it isn't stored and you'll need it for the web interface.\n");
         }
         Err(e) => {
             println!("\n❌ Registration failed: {}", e);
@@ -134,8 +160,10 @@ fn main_menu(server_state: Arc<Mutex<ServerState>>) -> Result<(), Box<dyn std::e
         println!("5. Configure SMTP");
         println!("6. View Configuration");
         println!("7. Run as Service (daemon mode)");
-        println!("8. Exit");
-        print!("\nSelect option (1-8): ");
+        println!("8. Rotate Access Token");
+        println!("9. Change Password");
+        println!("10. Exit");
+        print!("\nSelect option (1-10): ");
         io::stdout().flush()?;
 
         let mut input = String::new();
@@ -149,8 +177,11 @@ fn main_menu(server_state: Arc<Mutex<ServerState>>) -> Result<(), Box<dyn std::e
             "5" => configure_smtp(&server_state)?,
             "6" => view_config(&server_state)?,
             "7" => run_daemon(&server_state)?,
-            "8" => {
+            "8" => rotate_access_token(&server_state)?,
+            "9" => change_password(&server_state)?,
+            "10" => {
                 println!("\n👋 Goodbye!");
+                shutdown_otlp();
                 break;
             }
             _ => println!("❌ Invalid option. Please try again."),
@@ -192,21 +223,22 @@ fn start_server(server_state: &Arc<Mutex<ServerState>>) -> Result<(), Box<dyn st
     std::thread::spawn(move || {
         rt.block_on(async {
             let app = create_app(server_state_clone.clone());
-            let addr = std::net::SocketAddr::from(([0, 0, 0, 0], port));
+            let http_config = http_config(&server_state_clone);
+            let addr = std::net::SocketAddr::from((std::net::Ipv6Addr::UNSPECIFIED, port));
 
-            let listener = tokio::net::TcpListener::bind(addr).await;
+            let listener = bind_tcp_listener(addr);
             match listener {
                 Ok(listener) => {
                     println!("✅ Server started successfully!");
-                    println!("📍 Access at: http://localhost:{}", port);
-                    println!("🌐 Network access: http://[YOUR-IP]:{}", port);
+                    print_lan_urls(port);
 
-                    let server = axum::serve(listener, app);
+                    let server = serve_app(listener, app, http_config);
 
                     tokio::select! {
-                        _ = server => {
-                            println!("Server stopped normally");
-                        }
+                        result = server => match result {
+                            Ok(()) => println!("Server stopped normally"),
+                            Err(e) => eprintln!("❌ Server stopped: {}", e),
+                        },
                         _ = rx => {
                             println!("Server received shutdown signal");
                         }
@@ -265,8 +297,7 @@ fn show_status(server_state: &Arc<Mutex<ServerState>>) -> Result<(), Box<dyn std
     println!("Port: {}", port);
     
     if is_running {
-        println!("Local URL: http://localhost:{}", port);
-        println!("Network URL: http://[YOUR-IP]:{}", port);
+        print_lan_urls(port);
     }
 
     Ok(())
@@ -376,6 +407,62 @@ fn view_config(server_state: &Arc<Mutex<ServerState>>) -> Result<(), Box<dyn std
     Ok(())
 }
 
+fn rotate_access_token(
+    server_state: &Arc<Mutex<ServerState>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    print!("\nUsername: ");
+    io::stdout().flush()?;
+    let mut username = String::new();
+    io::stdin().read_line(&mut username)?;
+    let password = rpassword::prompt_password("Password: ")?;
+
+    let state = server_state.lock().unwrap();
+    let mut auth_manager = state.auth_manager.lock().unwrap();
+    let rotated = auth_manager
+        .authenticate(username.trim(), &password)
+        .and_then(|()| auth_manager.rotate_access_token(username.trim()));
+    match rotated {
+        Ok(token) => {
+            println!("\n📝 Your new access token: {}", token);
+            println!("⚠️  It is only shown once, the old token no longer works.");
+        }
+        Err(e) => println!("❌ {}", e),
+    }
+
+    Ok(())
+}
+
+fn change_password(
+    server_state: &Arc<Mutex<ServerState>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    print!("\nUsername: ");
+    io::stdout().flush()?;
+    let mut username = String::new();
+    io::stdin().read_line(&mut username)?;
+    let password = rpassword::prompt_password("Current password: ")?;
+
+    let policy = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.password_policy.clone()
+    };
+    let new_password =
+        rpassword::prompt_password(format!("New password ({}): ", policy.describe()))?;
+    if rpassword::prompt_password("Confirm new password: ")? != new_password {
+        println!("❌ Passwords do not match.");
+        return Ok(());
+    }
+
+    let state = server_state.lock().unwrap();
+    let mut auth_manager = state.auth_manager.lock().unwrap();
+    match auth_manager.change_password(username.trim(), &password, &new_password) {
+        Ok(()) => println!("✅ Password changed!"),
+        Err(e) => println!("❌ {}", e),
+    }
+
+    Ok(())
+}
+
 fn run_daemon(server_state: &Arc<Mutex<ServerState>>) -> Result<(), Box<dyn std::error::Error>> {
     println!("\n🔄 Starting in daemon mode...");
     println!("Press Ctrl+C to stop the server.\n");
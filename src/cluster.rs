@@ -0,0 +1,322 @@
+// Cluster module for Crusty-Crawler
+// Two managers sharing one fleet: each pulls the other's host reports over the API and keeps the
+// newest per host, and the standby also copies the primary's hosts, join tokens and fleet
+// settings. Agents report to the standby while the primary is down, and once the primary has
+// been unreachable for a while the standby takes over fleet alerting
+
+const CLUSTER_STATE_PATH: &str = "/api/central/cluster/state";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ClusterRole {
+    // A single manager
+    #[default]
+    Standalone,
+    Primary,
+    Standby,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ClusterConfig {
+    pub role: ClusterRole,
+    // Base URL of the other manager
+    pub peer_url: String,
+    // An admin's access token on the other manager
+    pub peer_token: String,
+    pub sync_interval_secs: u64,
+    // The standby takes over once the primary has been unreachable this long
+    pub failover_after_secs: u64,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            role: ClusterRole::Standalone,
+            peer_url: String::new(),
+            peer_token: String::new(),
+            sync_interval_secs: 30,
+            failover_after_secs: 120,
+        }
+    }
+}
+
+impl ClusterConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.role == ClusterRole::Standalone {
+            return Ok(());
+        }
+        if !(self.peer_url.starts_with("http://") || self.peer_url.starts_with("https://")) {
+            return Err("manager.cluster.peer_url must be an http:// or https:// URL".to_string());
+        }
+        if self.peer_token.is_empty() {
+            return Err("manager.cluster.peer_token must be set".to_string());
+        }
+        if self.sync_interval_secs < 5 {
+            return Err("manager.cluster.sync_interval_secs must be at least 5".to_string());
+        }
+        if self.failover_after_secs < self.sync_interval_secs * 2 {
+            return Err(
+                "manager.cluster.failover_after_secs must be at least twice sync_interval_secs"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
+// Body of GET /api/central/cluster/state
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ClusterState {
+    pub hosts: Vec<ManagedHost>,
+    pub join_tokens: Vec<JoinToken>,
+    pub fleet_settings: FleetSettings,
+    pub reported: Vec<ReportedHost>,
+}
+
+// GET /api/central/cluster
+#[derive(Serialize, Clone, Default)]
+pub struct ClusterStatus {
+    pub role: ClusterRole,
+    // Whether this manager handles fleet alerting, a standby only while the primary is down
+    pub active: bool,
+    pub peer_reachable: bool,
+    pub last_sync: Option<String>,
+    // When the peer stopped answering
+    pub peer_down_since: Option<String>,
+    pub last_error: Option<String>,
+}
+
+static CLUSTER_STATUS: Mutex<Option<ClusterStatus>> = Mutex::new(None);
+
+pub fn cluster_state(config: &ManagerConfig) -> ClusterState {
+    ClusterState {
+        hosts: config.hosts.clone(),
+        join_tokens: config.join_tokens.clone(),
+        fleet_settings: config.fleet_settings.clone(),
+        reported: REPORTED_HOSTS.lock().unwrap().values().cloned().collect(),
+    }
+}
+
+// Keeps whichever side heard from a host last, returns how many were taken from the peer
+pub fn merge_reported_hosts(
+    local: &mut BTreeMap<String, ReportedHost>,
+    remote: Vec<ReportedHost>,
+) -> usize {
+    let received =
+        |host: &ReportedHost| chrono::DateTime::parse_from_rfc3339(&host.last_report).ok();
+    let mut taken = 0;
+    for host in remote {
+        let newer = match local.get(&host.host) {
+            Some(known) => received(&host) > received(known),
+            None => true,
+        };
+        if newer {
+            local.insert(host.host.clone(), host);
+            taken += 1;
+        }
+    }
+    taken
+}
+
+pub fn cluster_active(
+    config: &ClusterConfig,
+    peer_down_since: Option<chrono::DateTime<chrono::Utc>>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> bool {
+    match config.role {
+        ClusterRole::Standalone | ClusterRole::Primary => true,
+        ClusterRole::Standby => peer_down_since
+            .is_some_and(|since| (now - since).num_seconds() >= config.failover_after_secs as i64),
+    }
+}
+
+// False only on a standby whose primary is up
+pub fn manager_active() -> bool {
+    CLUSTER_STATUS
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_none_or(|status| status.active)
+}
+
+pub fn cluster_status(config: &ClusterConfig) -> ClusterStatus {
+    CLUSTER_STATUS
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| ClusterStatus {
+            role: config.role,
+            active: true,
+            ..Default::default()
+        })
+}
+
+impl AuthManager {
+    // The standby's copy of the primary's fleet configuration, saved only when it changed
+    pub fn apply_cluster_state(&mut self, state: &ClusterState) -> Result<bool, String> {
+        let manager = &self.config.manager;
+        let current = serde_json::to_value((
+            &manager.hosts,
+            &manager.join_tokens,
+            &manager.fleet_settings,
+        ))
+        .map_err(|e| e.to_string())?;
+        let synced =
+            serde_json::to_value((&state.hosts, &state.join_tokens, &state.fleet_settings))
+                .map_err(|e| e.to_string())?;
+        if current == synced {
+            return Ok(false);
+        }
+        let manager = &mut self.config.manager;
+        manager.hosts = state.hosts.clone();
+        manager.join_tokens = state.join_tokens.clone();
+        manager.fleet_settings = state.fleet_settings.clone();
+        self.save_config().map_err(|e| e.to_string())?;
+        record_event(
+            EventKind::ConfigChange,
+            "Synced the fleet configuration from the primary manager",
+            &format!("{} host(s)", state.hosts.len()),
+        );
+        Ok(true)
+    }
+}
+
+async fn fetch_cluster_state(
+    client: &reqwest::Client,
+    config: &ClusterConfig,
+) -> Result<ClusterState, String> {
+    let response = client
+        .get(format!(
+            "{}{}",
+            config.peer_url.trim_end_matches('/'),
+            CLUSTER_STATE_PATH
+        ))
+        .bearer_auth(&config.peer_token)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("{} {}", status, body));
+    }
+    response.json().await.map_err(|e| e.to_string())
+}
+
+fn spawn_cluster_sync(server_state: Arc<Mutex<ServerState>>) {
+    std::thread::spawn(move || {
+        let rt = match Runtime::new() {
+            Ok(rt) => rt,
+            Err(e) => {
+                eprintln!("❌ Failed to start the cluster sync: {}", e);
+                return;
+            }
+        };
+
+        rt.block_on(async {
+            let client = reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default();
+            let mut peer_down_since: Option<chrono::DateTime<chrono::Utc>> = None;
+            let mut last_sync: Option<String> = None;
+            let mut last_error: Option<String> = None;
+            loop {
+                let config = {
+                    let state = server_state.lock().unwrap();
+                    let auth_manager = state.auth_manager.lock().unwrap();
+                    auth_manager.config.manager.clone()
+                };
+                let cluster = &config.cluster;
+                if !config.enabled || cluster.role == ClusterRole::Standalone {
+                    *CLUSTER_STATUS.lock().unwrap() = None;
+                    peer_down_since = None;
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                    continue;
+                }
+
+                let now = chrono::Utc::now();
+                match fetch_cluster_state(&client, cluster).await {
+                    Ok(peer) => {
+                        if let Some(since) = peer_down_since.take() {
+                            println!(
+                                "🔗 Manager at {} is back after {}s",
+                                cluster.peer_url,
+                                (now - since).num_seconds()
+                            );
+                        }
+                        if cluster.role == ClusterRole::Standby {
+                            let state = server_state.lock().unwrap();
+                            let mut auth_manager = state.auth_manager.lock().unwrap();
+                            if let Err(e) = auth_manager.apply_cluster_state(&peer) {
+                                eprintln!("❌ Failed to save the synced fleet: {}", e);
+                            }
+                        }
+                        merge_reported_hosts(&mut REPORTED_HOSTS.lock().unwrap(), peer.reported);
+                        last_sync = Some(now.to_rfc3339());
+                        last_error = None;
+                    }
+                    Err(e) => {
+                        if last_error.as_ref() != Some(&e) {
+                            eprintln!("❌ Failed to sync with {}: {}", cluster.peer_url, e);
+                        }
+                        peer_down_since.get_or_insert(now);
+                        last_error = Some(e);
+                    }
+                }
+
+                let active = cluster_active(cluster, peer_down_since, now);
+                {
+                    let mut status = CLUSTER_STATUS.lock().unwrap();
+                    let was_active = status.as_ref().is_none_or(|status| status.active);
+                    if cluster.role == ClusterRole::Standby && active != was_active {
+                        if active {
+                            println!(
+                                "⚠️  Primary manager {} is unreachable, this standby takes over",
+                                cluster.peer_url
+                            );
+                        } else {
+                            println!(
+                                "🔗 Primary manager {} is back, standing by",
+                                cluster.peer_url
+                            );
+                        }
+                    }
+                    *status = Some(ClusterStatus {
+                        role: cluster.role,
+                        active,
+                        peer_reachable: peer_down_since.is_none(),
+                        last_sync: last_sync.clone(),
+                        peer_down_since: peer_down_since.map(|since| since.to_rfc3339()),
+                        last_error: last_error.clone(),
+                    });
+                }
+
+                tokio::time::sleep(Duration::from_secs(cluster.sync_interval_secs.max(5))).await;
+            }
+        });
+    });
+}
+
+pub fn cluster_metrics() -> Vec<Metric> {
+    let Some(status) = CLUSTER_STATUS.lock().unwrap().clone() else {
+        return Vec::new();
+    };
+    let role = match status.role {
+        ClusterRole::Standalone => "standalone",
+        ClusterRole::Primary => "primary",
+        ClusterRole::Standby => "standby",
+    };
+    vec![
+        Metric::new(
+            "manager_cluster_active",
+            if status.active { 1.0 } else { 0.0 },
+        )
+        .label("role", role),
+        Metric::new(
+            "manager_cluster_peer_reachable",
+            if status.peer_reachable { 1.0 } else { 0.0 },
+        ),
+    ]
+}
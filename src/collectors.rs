@@ -0,0 +1,307 @@
+// Tool collectors module for Crusty-Crawler
+// Runs common third-party tools (smartctl, lm-sensors, nvidia-smi) on a schedule and maps
+// their output into metrics, so coverage can grow without new Rust code
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolParser {
+    // smartctl JSON for every device found by `smartctl --scan`
+    Smartctl,
+    // `sensors -j`
+    Sensors,
+    // `nvidia-smi --query-gpu=... --format=csv`
+    NvidiaSmi,
+}
+
+impl ToolParser {
+    fn name(&self) -> &'static str {
+        match self {
+            ToolParser::Smartctl => "smartctl",
+            ToolParser::Sensors => "sensors",
+            ToolParser::NvidiaSmi => "nvidia-smi",
+        }
+    }
+}
+
+fn default_collector_interval_secs() -> u64 {
+    60
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ToolCollector {
+    pub parser: ToolParser,
+    // Overrides the default command line, e.g. ["sudo", "smartctl", "-a", "-j", "/dev/sda"]
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default = "default_collector_interval_secs")]
+    pub interval_secs: u64,
+    // Cron expression, overrides interval_secs
+    #[serde(default)]
+    pub schedule: Option<String>,
+}
+
+// Latest metrics of each configured collector, by position in the config
+static LATEST_TOOL_METRICS: Mutex<BTreeMap<usize, Vec<Metric>>> = Mutex::new(BTreeMap::new());
+
+const NVIDIA_SMI_FIELDS: &str = "index,name,temperature.gpu,utilization.gpu,utilization.memory,memory.used,memory.total,power.draw,fan.speed";
+
+async fn run_tool(command: &[String]) -> Result<String, String> {
+    let (program, args) = command.split_first().ok_or("Empty collector command")?;
+    // Bare names are looked up in the plugins directory before PATH
+    let plugin = plugin_dir().join(program);
+    let executable = if !program.contains(['/', '\\']) && plugin.is_file() {
+        plugin.into_os_string()
+    } else {
+        program.into()
+    };
+    let output = tokio::time::timeout(
+        Duration::from_secs(30),
+        tokio::process::Command::new(executable).args(args).output(),
+    )
+    .await
+    .map_err(|_| format!("{} timed out", program))?
+    .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+
+    // smartctl uses its exit code as a bit mask of disk problems but still prints JSON
+    if output.stdout.is_empty() {
+        return Err(format!(
+            "{} exited with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn to_command(parts: &[&str]) -> Vec<String> {
+    parts.iter().map(|part| part.to_string()).collect()
+}
+
+pub fn parse_smartctl(json: &serde_json::Value) -> Vec<Metric> {
+    let device = json["device"]["name"].as_str().unwrap_or("unknown");
+    let mut metrics = Vec::new();
+
+    if let Some(passed) = json["smart_status"]["passed"].as_bool() {
+        metrics.push(
+            Metric::new("smart_healthy", if passed { 1.0 } else { 0.0 }).label("device", device),
+        );
+    }
+    if let Some(temperature) = json["temperature"]["current"].as_f64() {
+        metrics.push(Metric::new("smart_temperature_celsius", temperature).label("device", device));
+    }
+    if let Some(hours) = json["power_on_time"]["hours"].as_f64() {
+        metrics.push(Metric::new("smart_power_on_hours", hours).label("device", device));
+    }
+
+    // ATA drives: reallocated sectors, pending sectors, CRC errors and friends
+    for attribute in json["ata_smart_attributes"]["table"]
+        .as_array()
+        .into_iter()
+        .flatten()
+    {
+        if let (Some(name), Some(raw)) = (
+            attribute["name"].as_str(),
+            attribute["raw"]["value"].as_f64(),
+        ) {
+            metrics.push(
+                Metric::new("smart_attribute_raw", raw)
+                    .label("device", device)
+                    .label("attribute", name),
+            );
+        }
+    }
+
+    // NVMe drives report a health log instead of attributes
+    let nvme = &json["nvme_smart_health_information_log"];
+    for (field, name) in [
+        ("percentage_used", "smart_nvme_percentage_used"),
+        ("available_spare", "smart_nvme_available_spare_percent"),
+        ("media_errors", "smart_nvme_media_errors"),
+        ("unsafe_shutdowns", "smart_nvme_unsafe_shutdowns"),
+    ] {
+        if let Some(value) = nvme[field].as_f64() {
+            metrics.push(Metric::new(name, value).label("device", device));
+        }
+    }
+
+    metrics
+}
+
+// `sensors -j` nests chip -> feature -> "temp1_input": 42.0
+pub fn parse_sensors(json: &serde_json::Value) -> Vec<Metric> {
+    let mut metrics = Vec::new();
+    let Some(chips) = json.as_object() else {
+        return metrics;
+    };
+
+    for (chip, features) in chips {
+        for (feature, readings) in features.as_object().into_iter().flatten() {
+            for (key, value) in readings.as_object().into_iter().flatten() {
+                let (Some(value), Some(kind)) = (value.as_f64(), key.strip_suffix("_input")) else {
+                    continue;
+                };
+                let name = match kind.trim_end_matches(|c: char| c.is_ascii_digit()) {
+                    "temp" => "sensors_temperature_celsius",
+                    "fan" => "sensors_fan_rpm",
+                    "in" => "sensors_voltage_volts",
+                    "power" => "sensors_power_watts",
+                    "curr" => "sensors_current_amps",
+                    _ => continue,
+                };
+                metrics.push(
+                    Metric::new(name, value)
+                        .label("chip", chip)
+                        .label("sensor", feature),
+                );
+            }
+        }
+    }
+    metrics
+}
+
+pub fn parse_nvidia_smi(csv: &str) -> Vec<Metric> {
+    let mut metrics = Vec::new();
+    for line in csv.lines() {
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        if fields.len() < 9 {
+            continue;
+        }
+        let (gpu, name) = (fields[0], fields[1]);
+        let value = |index: usize| fields[index].parse::<f64>().ok();
+
+        for (index, metric, scale) in [
+            (2, "gpu_temperature_celsius", 1.0),
+            (3, "gpu_utilization_percent", 1.0),
+            (4, "gpu_memory_utilization_percent", 1.0),
+            (5, "gpu_memory_used_bytes", 1024.0 * 1024.0),
+            (6, "gpu_memory_total_bytes", 1024.0 * 1024.0),
+            (7, "gpu_power_watts", 1.0),
+            (8, "gpu_fan_percent", 1.0),
+        ] {
+            // Unsupported fields read "[N/A]"
+            if let Some(value) = value(index) {
+                metrics.push(
+                    Metric::new(metric, value * scale)
+                        .label("gpu", gpu)
+                        .label("name", name),
+                );
+            }
+        }
+    }
+    metrics
+}
+
+async fn run_collector(collector: &ToolCollector) -> Result<Vec<Metric>, String> {
+    match collector.parser {
+        ToolParser::Smartctl if collector.command.is_empty() => {
+            let scan: serde_json::Value =
+                serde_json::from_str(&run_tool(&to_command(&["smartctl", "--scan", "-j"])).await?)
+                    .map_err(|e| e.to_string())?;
+            let mut metrics = Vec::new();
+            for device in scan["devices"].as_array().into_iter().flatten() {
+                let Some(name) = device["name"].as_str() else {
+                    continue;
+                };
+                // One unreadable device shouldn't hide the others
+                match run_tool(&to_command(&["smartctl", "-a", "-j", name])).await {
+                    Ok(output) => match serde_json::from_str(&output) {
+                        Ok(json) => metrics.extend(parse_smartctl(&json)),
+                        Err(e) => eprintln!("❌ Invalid smartctl output for {}: {}", name, e),
+                    },
+                    Err(e) => eprintln!("❌ {}", e),
+                }
+            }
+            Ok(metrics)
+        }
+        ToolParser::Smartctl => {
+            let json = serde_json::from_str(&run_tool(&collector.command).await?)
+                .map_err(|e| e.to_string())?;
+            Ok(parse_smartctl(&json))
+        }
+        ToolParser::Sensors => {
+            let command = if collector.command.is_empty() {
+                to_command(&["sensors", "-j"])
+            } else {
+                collector.command.clone()
+            };
+            let json =
+                serde_json::from_str(&run_tool(&command).await?).map_err(|e| e.to_string())?;
+            Ok(parse_sensors(&json))
+        }
+        ToolParser::NvidiaSmi => {
+            let command = if collector.command.is_empty() {
+                vec![
+                    "nvidia-smi".to_string(),
+                    format!("--query-gpu={}", NVIDIA_SMI_FIELDS),
+                    "--format=csv,noheader,nounits".to_string(),
+                ]
+            } else {
+                collector.command.clone()
+            };
+            Ok(parse_nvidia_smi(&run_tool(&command).await?))
+        }
+    }
+}
+
+// The parser's name, numbered when several collectors use the same parser
+fn collector_name(collectors: &[ToolCollector], index: usize) -> String {
+    let parser = collectors[index].parser;
+    let same: Vec<usize> = (0..collectors.len())
+        .filter(|i| collectors[*i].parser == parser)
+        .collect();
+    if same.len() > 1 {
+        let position = same.iter().position(|i| *i == index).unwrap_or_default();
+        format!("{} #{}", parser.name(), position + 1)
+    } else {
+        parser.name().to_string()
+    }
+}
+
+// Runs the collector at `index` for the scheduler and keeps its metrics
+async fn run_scheduled_collector(
+    collectors: &[ToolCollector],
+    index: usize,
+) -> Result<String, String> {
+    let collector = &collectors[index];
+    let span = start_span(
+        format!("collector {}", collector.parser.name()),
+        vec![opentelemetry::KeyValue::new(
+            "collector.parser",
+            collector.parser.name(),
+        )],
+    );
+    let name = collector_name(collectors, index);
+    match run_collector(collector).await {
+        Ok(metrics) => {
+            end_span(span, None);
+            record_collector_result(&name, Ok(()));
+            let summary = format!("{} metric(s)", metrics.len());
+            LATEST_TOOL_METRICS.lock().unwrap().insert(index, metrics);
+            Ok(summary)
+        }
+        Err(e) => {
+            end_span(span, Some(&e));
+            record_collector_result(&name, Err(&e));
+            Err(e)
+        }
+    }
+}
+
+// Drops the metrics of collectors removed from the config
+fn forget_removed_collectors(count: usize) {
+    LATEST_TOOL_METRICS
+        .lock()
+        .unwrap()
+        .retain(|index, _| *index < count);
+}
+
+// Results of the most recent run of every collector
+pub fn tool_metrics() -> Vec<Metric> {
+    LATEST_TOOL_METRICS
+        .lock()
+        .unwrap()
+        .values()
+        .flat_map(|metrics| metrics.iter().cloned())
+        .collect()
+}
@@ -0,0 +1,282 @@
+// Compare module for Crusty-Crawler
+// The manager keeps five minute buckets of CPU, memory and root disk usage per reporting host, so
+// /api/central/compare can put the hosts of a cluster on one chart and point out the one that's
+// off from the rest
+
+const FLEET_HISTORY_FILE: &str = "crusty_fleet_history.json";
+const FLEET_HISTORY_DAYS: i64 = 7;
+// How far a host's average has to be from the median of the others to count as the outlier
+const OUTLIER_PERCENT_POINTS: f64 = 10.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ComparedMetric {
+    Cpu,
+    Memory,
+    // The filesystem mounted at /
+    Disk,
+}
+
+impl ComparedMetric {
+    const ALL: [ComparedMetric; 3] = [
+        ComparedMetric::Cpu,
+        ComparedMetric::Memory,
+        ComparedMetric::Disk,
+    ];
+
+    // Percent used, None when the host doesn't report the series it needs
+    fn value(self, snapshot: &HostSnapshot) -> Option<f64> {
+        let series = |key: &str| snapshot.metrics.get(key).copied();
+        let percent = |used: f64, total: f64| (total > 0.0).then(|| used / total * 100.0);
+        match self {
+            ComparedMetric::Cpu => series("cpu_usage_percent"),
+            ComparedMetric::Memory => {
+                percent(series("memory_used_bytes")?, series("memory_total_bytes")?)
+            }
+            ComparedMetric::Disk => {
+                let total = series("disk_total_bytes{mount=\"/\"}")?;
+                let available = series("disk_available_bytes{mount=\"/\"}")?;
+                percent(total - available, total)
+            }
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct FleetHistory {
+    // Finished buckets per host and metric, oldest first
+    hosts: BTreeMap<String, BTreeMap<ComparedMetric, VecDeque<Rollup>>>,
+    // Bucket start and samples of the buckets still filling, lost on restart
+    #[serde(skip)]
+    pending: BTreeMap<(String, ComparedMetric), (i64, Vec<f64>)>,
+}
+
+impl FleetHistory {
+    // Returns whether a bucket was finished
+    pub fn record(
+        &mut self,
+        host: &str,
+        snapshot: &HostSnapshot,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> bool {
+        let bucket = now.timestamp().div_euclid(ROLLUP_SECS) * ROLLUP_SECS;
+        let cutoff = now.timestamp() - FLEET_HISTORY_DAYS * 86400;
+        let mut finished = false;
+        for metric in ComparedMetric::ALL {
+            let Some(value) = metric.value(snapshot) else {
+                continue;
+            };
+            let pending = self
+                .pending
+                .entry((host.to_string(), metric))
+                .or_insert((bucket, Vec::new()));
+            if pending.0 != bucket {
+                let (start, values) = std::mem::replace(pending, (bucket, Vec::new()));
+                if let Some(rollup) = Rollup::of(start, &values) {
+                    let series = self
+                        .hosts
+                        .entry(host.to_string())
+                        .or_default()
+                        .entry(metric)
+                        .or_default();
+                    series.push_back(rollup);
+                    while series.front().is_some_and(|rollup| rollup.start < cutoff) {
+                        series.pop_front();
+                    }
+                    finished = true;
+                }
+            }
+            pending.1.push(value);
+        }
+        finished
+    }
+
+    // Every host with history of `metric`
+    fn hosts_with(&self, metric: ComparedMetric) -> BTreeSet<String> {
+        let mut hosts: BTreeSet<String> = self
+            .hosts
+            .iter()
+            .filter(|(_, series)| series.contains_key(&metric))
+            .map(|(host, _)| host.clone())
+            .collect();
+        hosts.extend(
+            self.pending
+                .keys()
+                .filter(|(_, recorded)| *recorded == metric)
+                .map(|(host, _)| host.clone()),
+        );
+        hosts
+    }
+
+    // The finished buckets and the one still filling
+    fn rollups(&self, host: &str, metric: ComparedMetric) -> Vec<Rollup> {
+        let mut rollups: Vec<Rollup> = self
+            .hosts
+            .get(host)
+            .and_then(|series| series.get(&metric))
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect();
+        if let Some((start, values)) = self.pending.get(&(host.to_string(), metric)) {
+            rollups.extend(Rollup::of(*start, values));
+        }
+        rollups
+    }
+}
+
+// Loaded from FLEET_HISTORY_FILE on first use
+static FLEET_HISTORY: Mutex<Option<FleetHistory>> = Mutex::new(None);
+
+fn with_fleet_history<T>(f: impl FnOnce(&mut FleetHistory) -> T) -> T {
+    let mut history = FLEET_HISTORY.lock().unwrap();
+    let history = history.get_or_insert_with(|| {
+        fs::read_to_string(data_path(FLEET_HISTORY_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    });
+    f(history)
+}
+
+// Called with every report the manager accepts, saves whenever a bucket is finished
+pub fn record_fleet_history(
+    host: &str,
+    snapshot: &HostSnapshot,
+    now: chrono::DateTime<chrono::Utc>,
+) {
+    with_fleet_history(|history| {
+        if !history.record(host, snapshot, now) {
+            return;
+        }
+        let saved = serde_json::to_string(history)
+            .map_err(|e| e.to_string())
+            .and_then(|data| {
+                fs::write(data_path(FLEET_HISTORY_FILE), data).map_err(|e| e.to_string())
+            });
+        if let Err(e) = saved {
+            eprintln!(
+                "⚠️  Failed to save fleet history to {}: {}",
+                FLEET_HISTORY_FILE, e
+            );
+        }
+    });
+}
+
+#[derive(Deserialize)]
+pub struct ComparisonQuery {
+    pub metric: ComparedMetric,
+    // Comma separated host names, every host with history when left out
+    pub hosts: Option<String>,
+    // RFC 3339, the last six hours by default
+    pub from: Option<String>,
+    pub to: Option<String>,
+    // Seconds per point, five minutes by default
+    pub interval: Option<u64>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct HostComparison {
+    pub host: String,
+    // (RFC 3339 start of the interval, percent used), like /api/history/query
+    pub points: Vec<(String, f64)>,
+    // Over the whole range, None without data in it
+    pub average: Option<f64>,
+}
+
+// Response of GET /api/central/compare
+#[derive(Serialize, Clone, Debug)]
+pub struct Comparison {
+    pub metric: ComparedMetric,
+    pub from: String,
+    pub to: String,
+    pub interval_secs: i64,
+    pub hosts: Vec<HostComparison>,
+    // The host whose average is furthest from the median, when at least three hosts have data
+    // and it's more than OUTLIER_PERCENT_POINTS away
+    pub outlier: Option<String>,
+}
+
+fn comparison_outlier(hosts: &[HostComparison]) -> Option<String> {
+    let mut averages: Vec<(&str, f64)> = hosts
+        .iter()
+        .filter_map(|host| Some((host.host.as_str(), host.average?)))
+        .collect();
+    if averages.len() < 3 {
+        return None;
+    }
+    averages.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let middle = averages.len() / 2;
+    let median = if averages.len().is_multiple_of(2) {
+        (averages[middle - 1].1 + averages[middle].1) / 2.0
+    } else {
+        averages[middle].1
+    };
+    averages
+        .into_iter()
+        .map(|(host, average)| (host, (average - median).abs()))
+        .filter(|(_, distance)| *distance > OUTLIER_PERCENT_POINTS)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(host, _)| host.to_string())
+}
+
+pub fn compare_hosts(
+    history: &FleetHistory,
+    query: &ComparisonQuery,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Comparison, String> {
+    let to = parse_query_time(&query.to, now.timestamp() + 1)?;
+    let from = parse_query_time(&query.from, to - DEFAULT_QUERY_HOURS * 3600)?;
+    if from >= to {
+        return Err("from must be before to".to_string());
+    }
+    let requested = query.interval.unwrap_or(ROLLUP_SECS as u64) as i64;
+    let interval = requested
+        .max((to - from + MAX_QUERY_POINTS - 1) / MAX_QUERY_POINTS)
+        .max(1);
+
+    let known = history.hosts_with(query.metric);
+    let names: Vec<String> = match &query.hosts {
+        Some(hosts) => hosts
+            .split(',')
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect(),
+        None => known.iter().cloned().collect(),
+    };
+
+    let mut hosts = Vec::new();
+    for name in names {
+        if !known.contains(&name) {
+            return Err(format!("No history of {}", name));
+        }
+        let rollups: Vec<Rollup> = history
+            .rollups(&name, query.metric)
+            .into_iter()
+            .filter(|rollup| rollup.start >= from && rollup.start < to)
+            .collect();
+        hosts.push(HostComparison {
+            points: downsample(&rollups, from, to, interval, Aggregation::Avg),
+            average: (!rollups.is_empty()).then(|| aggregate(&rollups, Aggregation::Avg)),
+            host: name,
+        });
+    }
+
+    let timestamp = |secs: i64| {
+        chrono::DateTime::from_timestamp(secs, 0)
+            .map(|time| time.to_rfc3339())
+            .unwrap_or_default()
+    };
+    Ok(Comparison {
+        metric: query.metric,
+        from: timestamp(from),
+        to: timestamp(to),
+        interval_secs: interval,
+        outlier: comparison_outlier(&hosts),
+        hosts,
+    })
+}
+
+pub fn compare_fleet_hosts(query: &ComparisonQuery) -> Result<Comparison, String> {
+    with_fleet_history(|history| compare_hosts(history, query, chrono::Utc::now()))
+}
@@ -1,11 +1,91 @@
 use sysinfo::Components;
 
-pub async fn check_components() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SensorKind {
+    Temperature,
+    Fan,
+    Power,
+    Voltage,
+}
+
+impl SensorKind {
+    pub fn unit(&self) -> &'static str {
+        match self {
+            SensorKind::Temperature => "°C",
+            SensorKind::Fan => "RPM",
+            SensorKind::Power => "W",
+            SensorKind::Voltage => "V",
+        }
+    }
+}
+
+// A reading from a platform-specific sensor source that sysinfo doesn't cover
+#[derive(Serialize, Clone)]
+pub struct SensorReading {
+    pub label: String,
+    pub kind: SensorKind,
+    pub value: f64,
+}
+
+impl SensorReading {
+    pub fn new(label: &str, kind: SensorKind, value: f64) -> Self {
+        Self {
+            label: label.to_string(),
+            kind,
+            value,
+        }
+    }
+}
+
+impl fmt::Display for SensorReading {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            SensorKind::Temperature => {
+                write!(f, "{}: {:.1}{}", self.label, self.value, self.kind.unit())
+            }
+            SensorKind::Voltage => {
+                write!(f, "{}: {:.2} {}", self.label, self.value, self.kind.unit())
+            }
+            _ => write!(f, "{}: {:.0} {}", self.label, self.value, self.kind.unit()),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn platform_sensors() -> Vec<SensorReading> {
+    smc_sensors().unwrap_or_else(|e| {
+        eprintln!("❌ Error reading SMC sensors: {}", e);
+        Vec::new()
+    })
+}
+
+#[cfg(target_os = "linux")]
+pub fn platform_sensors() -> Vec<SensorReading> {
+    let mut readings = sbc_sensors();
+    readings.extend(ipmi_sensor_readings());
+    readings
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+pub fn platform_sensors() -> Vec<SensorReading> {
+    Vec::new()
+}
+
+pub async fn check_components(
+    devices: &DeviceFilters,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    if let Some(reason) = unsupported_reason("components") {
+        return Ok(vec![reason.to_string()]);
+    }
     let components = Components::new_with_refreshed_list();
     let mut result = Vec::new();
 
     for component in components.list() {
         let label = component.label();
+        if !devices.allows_component(label) {
+            continue;
+        }
         let temperature = component.temperature(); // This returns an Option<f32>
 
         let info_string = match temperature {
@@ -15,6 +95,12 @@ pub async fn check_components() -> Result<Vec<String>, Box<dyn std::error::Error
         result.push(info_string);
     }
 
+    for sensor in platform_sensors() {
+        if devices.allows_component(&sensor.label) {
+            result.push(sensor.to_string());
+        }
+    }
+
     // Handle case with no components found
     if result.is_empty() {
         result.push("No system components were detected.".to_string());
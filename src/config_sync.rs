@@ -0,0 +1,133 @@
+// Config sync module for Crusty-Crawler
+// Keeps the GUI, the CLI and the API from losing each other's edits to crusty_auth.json: writes
+// are serialized with a lock file, and a save on top of a file someone else changed is merged
+
+const CONFIG_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+// A lock this old was left behind by a process that died mid-save
+const CONFIG_LOCK_STALE: Duration = Duration::from_secs(30);
+
+// Content hash of the file as last read or written, an mtime can miss two saves in one second
+pub fn config_etag(data: &str) -> String {
+    use sha2::Digest;
+
+    format!("{:x}", sha2::Sha256::digest(data.as_bytes()))
+}
+
+// Held for the length of a save, across processes. Removed again when dropped
+pub struct ConfigFileLock {
+    path: PathBuf,
+}
+
+impl ConfigFileLock {
+    pub fn acquire(config_path: &Path) -> Result<Self, String> {
+        let mut name = config_path.file_name().unwrap_or_default().to_os_string();
+        name.push(".lock");
+        let path = config_path.with_file_name(name);
+
+        let started = Instant::now();
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    let _ = write!(file, "{}", std::process::id());
+                    return Ok(Self { path });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    let stale = fs::metadata(&path)
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok())
+                        .is_some_and(|age| age > CONFIG_LOCK_STALE);
+                    if stale {
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                    if started.elapsed() > CONFIG_LOCK_TIMEOUT {
+                        return Err(format!(
+                            "{} is being saved by another process, try again",
+                            config_path.display()
+                        ));
+                    }
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                Err(e) => return Err(format!("Failed to lock {}: {}", path.display(), e)),
+            }
+        }
+    }
+}
+
+impl Drop for ConfigFileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Three-way merge of one value: a side that left it as it was in `base` takes the other side's
+// version. Objects merge key by key, anything else both sides changed differently is a conflict
+fn merge_config_value(
+    path: &str,
+    base: Option<&serde_json::Value>,
+    ours: Option<&serde_json::Value>,
+    theirs: Option<&serde_json::Value>,
+    conflicts: &mut Vec<String>,
+) -> Option<serde_json::Value> {
+    use serde_json::Value;
+
+    if ours == base || ours == theirs {
+        return theirs.cloned();
+    }
+    if theirs == base {
+        return ours.cloned();
+    }
+
+    let (Some(Value::Object(ours)), Some(Value::Object(theirs))) = (ours, theirs) else {
+        conflicts.push(path.to_string());
+        return ours.cloned();
+    };
+    let empty = serde_json::Map::new();
+    let base = match base {
+        Some(Value::Object(base)) => base,
+        _ => &empty,
+    };
+    let mut keys: Vec<&String> = ours.keys().chain(theirs.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    let mut merged = serde_json::Map::new();
+    for key in keys {
+        let child = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", path, key)
+        };
+        if let Some(value) = merge_config_value(
+            &child,
+            base.get(key),
+            ours.get(key),
+            theirs.get(key),
+            conflicts,
+        ) {
+            merged.insert(key.clone(), value);
+        }
+    }
+    Some(Value::Object(merged))
+}
+
+// Applies the edits between `base` and `ours` on top of `theirs`, or names the settings both
+// sides changed
+pub fn merge_config(
+    base: &serde_json::Value,
+    ours: &serde_json::Value,
+    theirs: &serde_json::Value,
+) -> Result<serde_json::Value, Vec<String>> {
+    let mut conflicts = Vec::new();
+    let merged = merge_config_value("", Some(base), Some(ours), Some(theirs), &mut conflicts);
+    if conflicts.is_empty() {
+        Ok(merged.unwrap_or_default())
+    } else {
+        Err(conflicts)
+    }
+}
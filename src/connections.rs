@@ -0,0 +1,382 @@
+// Connections module for Crusty-Crawler
+// Inventory of TCP and UDP sockets with their owning process, optionally enriched with reverse
+// DNS and the GeoIP country of the remote peer to spot unexpected foreign connections
+
+const REVERSE_DNS_TTL: Duration = Duration::from_secs(60 * 60);
+// New lookups per request, the rest are filled in by later refreshes
+const REVERSE_DNS_BATCH: usize = 64;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ConnectionsConfig {
+    // Look up host names of remote peers, cached for an hour
+    pub reverse_dns: bool,
+    // Path to a GeoLite2-Country or GeoIP2-Country .mmdb file, empty disables countries
+    pub geoip_database: String,
+    // ISO country codes peers are expected in, connections elsewhere are flagged. Empty
+    // flags nothing
+    pub expected_countries: Vec<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Connection {
+    pub protocol: &'static str,
+    pub local: SocketAddr,
+    // None for listeners and unconnected UDP sockets
+    pub remote: Option<SocketAddr>,
+    pub state: String,
+    pub pid: Option<u32>,
+    pub process: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    pub unexpected: bool,
+}
+
+#[derive(Serialize)]
+pub struct ConnectionsReport {
+    pub connections: Vec<Connection>,
+    pub reverse_dns: bool,
+    pub geoip: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geoip_error: Option<String>,
+}
+
+static REVERSE_DNS_CACHE: Mutex<BTreeMap<std::net::IpAddr, (Instant, Option<String>)>> =
+    Mutex::new(BTreeMap::new());
+// Reopened when the configured path changes
+type GeoipReader = (String, Arc<maxminddb::Reader<Vec<u8>>>);
+static GEOIP_READER: Mutex<Option<GeoipReader>> = Mutex::new(None);
+
+// Loopback, private and link-local peers have no country and rarely a useful PTR record
+fn public_ip(ip: &std::net::IpAddr) -> bool {
+    match ip {
+        std::net::IpAddr::V4(v4) => {
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast())
+        }
+        std::net::IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => public_ip(&std::net::IpAddr::V4(v4)),
+            None => {
+                !(v6.is_loopback() || v6.is_unspecified() || v6.is_multicast())
+                    && (v6.segments()[0] & 0xfe00) != 0xfc00
+                    && (v6.segments()[0] & 0xffc0) != 0xfe80
+            }
+        },
+    }
+}
+
+// /proc/net/tcp prints addresses as the kernel's 32-bit words in host byte order
+#[cfg(target_os = "linux")]
+fn parse_proc_address(value: &str) -> Option<SocketAddr> {
+    let (address, port) = value.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let mut bytes = Vec::with_capacity(16);
+    for chunk in address.as_bytes().chunks(8) {
+        let word = u32::from_str_radix(std::str::from_utf8(chunk).ok()?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+    let ip = match bytes.len() {
+        4 => std::net::IpAddr::from(<[u8; 4]>::try_from(bytes).ok()?),
+        16 => std::net::IpAddr::from(<[u8; 16]>::try_from(bytes).ok()?),
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+#[cfg(target_os = "linux")]
+fn tcp_state_name(code: &str) -> &'static str {
+    match code {
+        "01" => "ESTABLISHED",
+        "02" => "SYN_SENT",
+        "03" => "SYN_RECV",
+        "04" => "FIN_WAIT1",
+        "05" => "FIN_WAIT2",
+        "06" => "TIME_WAIT",
+        "07" => "CLOSE",
+        "08" => "CLOSE_WAIT",
+        "09" => "LAST_ACK",
+        "0A" => "LISTEN",
+        "0B" => "CLOSING",
+        _ => "UNKNOWN",
+    }
+}
+
+// Socket inodes of every process we may look at, other users' need root
+#[cfg(target_os = "linux")]
+fn socket_owners() -> HashMap<u64, (u32, String)> {
+    let mut owners = HashMap::new();
+    let Ok(processes) = fs::read_dir("/proc") else {
+        return owners;
+    };
+    for process in processes.flatten() {
+        let Ok(pid) = process.file_name().to_string_lossy().parse::<u32>() else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(process.path().join("fd")) else {
+            continue;
+        };
+        let name = fs::read_to_string(process.path().join("comm"))
+            .map(|comm| comm.trim().to_string())
+            .unwrap_or_default();
+        for fd in fds.flatten() {
+            let Ok(target) = fs::read_link(fd.path()) else {
+                continue;
+            };
+            let target = target.to_string_lossy();
+            if let Some(inode) = target
+                .strip_prefix("socket:[")
+                .and_then(|rest| rest.strip_suffix(']'))
+                .and_then(|inode| inode.parse().ok())
+            {
+                owners.insert(inode, (pid, name.clone()));
+            }
+        }
+    }
+    owners
+}
+
+#[cfg(target_os = "linux")]
+fn read_connections() -> Vec<Connection> {
+    let owners = socket_owners();
+    let mut connections = Vec::new();
+    for (file, protocol) in [
+        ("/proc/net/tcp", "tcp"),
+        ("/proc/net/tcp6", "tcp"),
+        ("/proc/net/udp", "udp"),
+        ("/proc/net/udp6", "udp"),
+    ] {
+        let Ok(table) = fs::read_to_string(file) else {
+            continue;
+        };
+        for line in table.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 10 {
+                continue;
+            }
+            let (Some(local), Some(remote)) =
+                (parse_proc_address(fields[1]), parse_proc_address(fields[2]))
+            else {
+                continue;
+            };
+            let state = match (protocol, fields[3]) {
+                ("udp", "07") => "UNCONNECTED",
+                ("udp", _) => "ESTABLISHED",
+                (_, code) => tcp_state_name(code),
+            };
+            let owner = fields[9].parse().ok().and_then(|inode| owners.get(&inode));
+            connections.push(Connection {
+                protocol,
+                local,
+                remote: (!remote.ip().is_unspecified()).then_some(remote),
+                state: state.to_string(),
+                pid: owner.map(|(pid, _)| *pid),
+                process: owner.map(|(_, name)| name.clone()),
+                remote_host: None,
+                country: None,
+                unexpected: false,
+            });
+        }
+    }
+    connections
+}
+
+// "[fe80::1%4]:123", "0.0.0.0:135" or "*:*" from netstat
+#[cfg(windows)]
+fn parse_netstat_address(value: &str) -> Option<SocketAddr> {
+    match value.split_once('%') {
+        // Drop the zone, it isn't needed to show the peer
+        Some((address, rest)) => {
+            let port = rest.split_once(']').map(|(_, port)| port).unwrap_or("");
+            format!("{}]{}", address, port).parse().ok()
+        }
+        None => value.parse().ok(),
+    }
+}
+
+#[cfg(windows)]
+fn read_connections() -> Vec<Connection> {
+    let Ok(output) = std::process::Command::new("netstat").arg("-ano").output() else {
+        return Vec::new();
+    };
+    let mut sys = sysinfo::System::new();
+    sys.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
+
+    let mut connections = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        // UDP rows have no state column
+        let (protocol, local, remote, state, pid) = match fields.as_slice() {
+            ["TCP", local, remote, state, pid] => ("tcp", local, remote, *state, pid),
+            ["UDP", local, remote, pid] => ("udp", local, remote, "UNCONNECTED", pid),
+            _ => continue,
+        };
+        let Some(local) = parse_netstat_address(local) else {
+            continue;
+        };
+        let remote = parse_netstat_address(remote).filter(|remote| !remote.ip().is_unspecified());
+        let pid: Option<u32> = pid.parse().ok().filter(|pid| *pid != 0);
+        let state = if state == "LISTENING" {
+            "LISTEN"
+        } else {
+            state
+        };
+        connections.push(Connection {
+            protocol,
+            local,
+            remote,
+            state: state.to_string(),
+            pid,
+            process: pid
+                .and_then(|pid| sys.process(sysinfo::Pid::from_u32(pid)))
+                .map(|process| process.name().to_string_lossy().to_string()),
+            remote_host: None,
+            country: None,
+            unexpected: false,
+        });
+    }
+    connections
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn read_connections() -> Vec<Connection> {
+    Vec::new()
+}
+
+fn geoip_reader(path: &str) -> Result<Arc<maxminddb::Reader<Vec<u8>>>, String> {
+    let mut reader = GEOIP_READER.lock().unwrap();
+    if let Some((open_path, reader)) = &*reader
+        && open_path == path
+    {
+        return Ok(reader.clone());
+    }
+    let opened =
+        Arc::new(maxminddb::Reader::open_readfile(path).map_err(|e| format!("{}: {}", path, e))?);
+    *reader = Some((path.to_string(), opened.clone()));
+    Ok(opened)
+}
+
+fn geoip_country(reader: &maxminddb::Reader<Vec<u8>>, ip: std::net::IpAddr) -> Option<String> {
+    let ip = match ip {
+        std::net::IpAddr::V6(v6) => v6.to_ipv4_mapped().map(std::net::IpAddr::V4).unwrap_or(ip),
+        ip => ip,
+    };
+    let record: maxminddb::geoip2::Country = reader.lookup(ip).ok()?;
+    record
+        .country
+        .or(record.registered_country)
+        .and_then(|country| country.iso_code)
+        .map(str::to_string)
+}
+
+// Cached names are used right away, new addresses are looked up in parallel off the runtime
+async fn reverse_dns(ips: Vec<std::net::IpAddr>) -> HashMap<std::net::IpAddr, String> {
+    let mut names = HashMap::new();
+    let mut missing = Vec::new();
+    {
+        let cache = REVERSE_DNS_CACHE.lock().unwrap();
+        for ip in ips {
+            match cache.get(&ip) {
+                Some((looked_up, name)) if looked_up.elapsed() < REVERSE_DNS_TTL => {
+                    if let Some(name) = name {
+                        names.insert(ip, name.clone());
+                    }
+                }
+                _ => missing.push(ip),
+            }
+        }
+    }
+
+    let lookups: Vec<_> = missing
+        .into_iter()
+        .take(REVERSE_DNS_BATCH)
+        .map(|ip| {
+            tokio::task::spawn_blocking(move || {
+                // Without a PTR record the resolver hands back the address itself
+                let name = dns_lookup::lookup_addr(&ip)
+                    .ok()
+                    .filter(|name| name.parse::<std::net::IpAddr>().is_err());
+                (ip, name)
+            })
+        })
+        .collect();
+    for lookup in lookups {
+        if let Ok((ip, name)) = lookup.await {
+            REVERSE_DNS_CACHE
+                .lock()
+                .unwrap()
+                .insert(ip, (Instant::now(), name.clone()));
+            if let Some(name) = name {
+                names.insert(ip, name);
+            }
+        }
+    }
+    names
+}
+
+pub async fn connections_report(config: &ConnectionsConfig) -> ConnectionsReport {
+    // Walks every process's file descriptors on Linux
+    let mut connections = tokio::task::spawn_blocking(read_connections)
+        .await
+        .unwrap_or_default();
+    connections.sort_by(|a, b| {
+        (a.protocol, a.remote.is_none(), a.local, a.remote).cmp(&(
+            b.protocol,
+            b.remote.is_none(),
+            b.local,
+            b.remote,
+        ))
+    });
+
+    let (geoip, geoip_error) = if config.geoip_database.is_empty() {
+        (None, None)
+    } else {
+        match geoip_reader(&config.geoip_database) {
+            Ok(reader) => (Some(reader), None),
+            Err(e) => (None, Some(e)),
+        }
+    };
+    if let Some(reader) = &geoip {
+        for connection in &mut connections {
+            let Some(remote) = connection.remote.filter(|remote| public_ip(&remote.ip())) else {
+                continue;
+            };
+            connection.country = geoip_country(reader, remote.ip());
+            connection.unexpected = !config.expected_countries.is_empty()
+                && connection.country.as_ref().is_some_and(|country| {
+                    !config
+                        .expected_countries
+                        .iter()
+                        .any(|expected| expected.eq_ignore_ascii_case(country))
+                });
+        }
+    }
+
+    if config.reverse_dns {
+        let mut ips: Vec<std::net::IpAddr> = connections
+            .iter()
+            .filter_map(|connection| connection.remote.map(|remote| remote.ip()))
+            .filter(|ip| !ip.is_loopback())
+            .collect();
+        ips.sort();
+        ips.dedup();
+        let names = reverse_dns(ips).await;
+        for connection in &mut connections {
+            connection.remote_host = connection
+                .remote
+                .and_then(|remote| names.get(&remote.ip()).cloned());
+        }
+    }
+
+    ConnectionsReport {
+        connections,
+        reverse_dns: config.reverse_dns,
+        geoip: geoip.is_some(),
+        geoip_error,
+    }
+}
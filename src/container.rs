@@ -0,0 +1,164 @@
+// Container module for Crusty-Crawler
+// `container` runs the headless server tuned for Docker: the config comes from environment
+// variables, logs go to stdout as JSON lines, data lives in /data and SIGTERM from the runtime
+// stops it cleanly even as PID 1
+
+const CONTAINER_DATA_DIR: &str = "/data";
+// CRUSTY_CONFIG__HTTP__TLS_CERT sets http.tls_cert, double underscores separate the levels
+const ENV_CONFIG_PREFIX: &str = "CRUSTY_CONFIG__";
+
+// The whole config as JSON, merged over crusty_auth.json before the single settings
+const ENV_CONFIG_JSON: &str = "CRUSTY_CONFIG";
+
+// Nested objects are merged key by key, anything else is replaced
+fn merge_json(base: &mut serde_json::Value, overlay: serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                merge_json(base.entry(key).or_insert(serde_json::Value::Null), value);
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+// Values are read as JSON when they parse, so numbers, booleans and lists keep their type, and
+// as plain strings otherwise
+fn env_config_value(value: &str) -> serde_json::Value {
+    serde_json::from_str(value).unwrap_or_else(|_| serde_json::Value::String(value.to_string()))
+}
+
+// The names of the variables that were applied
+fn apply_env_config(
+    config: &mut serde_json::Value,
+    vars: &[(String, String)],
+) -> Result<Vec<String>, String> {
+    let mut applied = Vec::new();
+    if let Some((name, json)) = vars.iter().find(|(name, _)| name == ENV_CONFIG_JSON) {
+        let overlay: serde_json::Value =
+            serde_json::from_str(json).map_err(|e| format!("{}: {}", name, e))?;
+        if !overlay.is_object() {
+            return Err(format!("{} must be a JSON object", name));
+        }
+        merge_json(config, overlay);
+        applied.push(name.clone());
+    }
+
+    let mut settings: Vec<&(String, String)> = vars
+        .iter()
+        .filter(|(name, _)| name.starts_with(ENV_CONFIG_PREFIX))
+        .collect();
+    settings.sort();
+    for (name, value) in settings {
+        let path: Vec<String> = name[ENV_CONFIG_PREFIX.len()..]
+            .split("__")
+            .map(str::to_lowercase)
+            .collect();
+        if path.iter().any(String::is_empty) {
+            return Err(format!("{} is not a valid setting name", name));
+        }
+        let mut target = &mut *config;
+        for key in &path {
+            if target.is_null() {
+                *target = serde_json::Value::Object(serde_json::Map::new());
+            }
+            target = target
+                .as_object_mut()
+                .ok_or_else(|| format!("{}: {} is not a section", name, key))?
+                .entry(key.clone())
+                .or_insert(serde_json::Value::Null);
+        }
+        *target = env_config_value(value);
+        applied.push(name.clone());
+    }
+    Ok(applied)
+}
+
+impl AuthManager {
+    // Applied once at startup and saved, so reloads and the API work on the same config
+    pub fn configure_from_env(&mut self, vars: &[(String, String)]) -> Result<Vec<String>, String> {
+        let mut value = serde_json::to_value(&self.config).map_err(|e| e.to_string())?;
+        let applied = apply_env_config(&mut value, vars)?;
+        if applied.is_empty() {
+            return Ok(applied);
+        }
+        let (config, _) = parse_auth_config(&value.to_string())?;
+        config.validate()?;
+        self.config = config;
+        self.save_config().map_err(|e| e.to_string())?;
+        Ok(applied)
+    }
+
+    // The first admin from CRUSTY_ADMIN_USER, _PASSWORD, _EMAIL and _TOKEN, since nobody can
+    // answer the CLI setup inside a container. Ignored once any user exists
+    pub fn bootstrap_admin_from_env(&mut self, vars: &[(String, String)]) -> Result<bool, String> {
+        let var = |name: &str| {
+            vars.iter()
+                .find(|(var, _)| var == name)
+                .map(|(_, value)| value.clone())
+        };
+        if self.has_users() {
+            return Ok(false);
+        }
+        let (Some(username), Some(password)) =
+            (var("CRUSTY_ADMIN_USER"), var("CRUSTY_ADMIN_PASSWORD"))
+        else {
+            return Ok(false);
+        };
+        let email = var("CRUSTY_ADMIN_EMAIL").unwrap_or_default();
+        let token = var("CRUSTY_ADMIN_TOKEN").ok_or("CRUSTY_ADMIN_TOKEN has to be set as well")?;
+        self.register_user(&username, &password, &email, &token)?;
+        Ok(true)
+    }
+}
+
+// Uses /data unless --data-dir or CRUSTY_DATA_DIR says otherwise, before the data directory
+// is set up
+pub fn init_container_data_dir(args: &mut Vec<String>) {
+    let container = args.get(1).map(String::as_str) == Some("container");
+    let explicit = env::var_os("CRUSTY_DATA_DIR").is_some()
+        || args
+            .iter()
+            .any(|arg| arg == "--data-dir" || arg.starts_with("--data-dir="));
+    if container && !explicit {
+        args.push(format!("--data-dir={}", CONTAINER_DATA_DIR));
+    }
+}
+
+pub fn run_container_mode(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut options = parse_daemon_args(args)?;
+    // Held until the function returns so the shutdown messages are still JSON
+    let _log_capture = start_json_logging()
+        .inspect_err(|e| eprintln!("⚠️  {}, logging plain text", e))
+        .ok();
+
+    if std::process::id() == 1 {
+        // SIGTERM and SIGINT are handled, but exited helper processes aren't reaped
+        println!("ℹ️  Running as PID 1, start the container with --init to reap helper processes");
+    }
+
+    let vars: Vec<(String, String)> = env::vars().collect();
+    {
+        let mut auth_manager = AuthManager::new(&data_file(AUTH_CONFIG_FILE))?;
+        let applied = auth_manager.configure_from_env(&vars)?;
+        if !applied.is_empty() {
+            println!(
+                "⚙️  Configuration from the environment: {}",
+                applied.join(", ")
+            );
+        }
+        if auth_manager.bootstrap_admin_from_env(&vars)? {
+            println!("👤 Created admin user from CRUSTY_ADMIN_USER");
+        }
+    }
+    if options.port.is_none()
+        && let Some((_, port)) = vars.iter().find(|(name, _)| name == "CRUSTY_PORT")
+    {
+        options.port = Some(
+            port.parse()
+                .map_err(|_| format!("Invalid CRUSTY_PORT: {}", port))?,
+        );
+    }
+
+    serve_headless(&options)
+}
@@ -0,0 +1,268 @@
+// Control module for Crusty-Crawler
+// Local control socket so `status`, `stop`, `alerts` and `reload` talk to the running daemon
+// instead of a fresh in-process state that knows nothing about it
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt};
+
+const DEFAULT_CONTROL_SOCKET: &str = "crusty-crawler.sock";
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize)]
+struct DaemonInfo {
+    pid: u32,
+    version: String,
+    uptime_secs: u64,
+    port: u16,
+    server_running: bool,
+    checks: usize,
+    active_alerts: usize,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ControlResponse {
+    ok: bool,
+    message: String,
+    #[serde(default)]
+    info: Option<DaemonInfo>,
+    #[serde(default)]
+    alerts: Vec<Alert>,
+}
+
+impl ControlResponse {
+    fn message(ok: bool, message: impl Into<String>) -> Self {
+        Self {
+            ok,
+            message: message.into(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ControlContext {
+    server_state: Arc<Mutex<ServerState>>,
+    shutdown: Arc<tokio::sync::Notify>,
+    started_at: Instant,
+}
+
+impl ControlContext {
+    fn handle(&self, command: &str) -> ControlResponse {
+        match command {
+            "status" => {
+                let state = self.server_state.lock().unwrap();
+                let checks = {
+                    let auth_manager = state.auth_manager.lock().unwrap();
+                    available_checks(&auth_manager.config.checks).len()
+                };
+                ControlResponse {
+                    ok: true,
+                    info: Some(DaemonInfo {
+                        pid: std::process::id(),
+                        version: env!("CARGO_PKG_VERSION").to_string(),
+                        uptime_secs: self.started_at.elapsed().as_secs(),
+                        port: state.port,
+                        server_running: state.is_running,
+                        checks,
+                        active_alerts: state.alert_manager.lock().unwrap().active().len(),
+                    }),
+                    ..Default::default()
+                }
+            }
+            "alerts" => {
+                let state = self.server_state.lock().unwrap();
+                ControlResponse {
+                    ok: true,
+                    alerts: state.alert_manager.lock().unwrap().active(),
+                    ..Default::default()
+                }
+            }
+            "reload" => match reload_config(&self.server_state) {
+                Ok(changes) if changes.is_empty() => {
+                    ControlResponse::message(true, "Configuration reloaded, nothing changed")
+                }
+                Ok(changes) => ControlResponse::message(
+                    true,
+                    format!("Configuration reloaded:\n   {}", changes.join("\n   ")),
+                ),
+                Err(e) => ControlResponse::message(false, format!("Reload failed: {}", e)),
+            },
+            "stop" => {
+                println!("🛑 Stop requested over the control socket");
+                self.shutdown.notify_one();
+                ControlResponse::message(true, "Stopping")
+            }
+            other => ControlResponse::message(false, format!("Unknown command '{}'", other)),
+        }
+    }
+
+    // One command line in, one JSON line out
+    async fn serve<S: AsyncRead + AsyncWrite + Unpin>(self, stream: S) {
+        let mut stream = tokio::io::BufReader::new(stream);
+        let mut command = String::new();
+        let read = tokio::time::timeout(CONTROL_TIMEOUT, stream.read_line(&mut command)).await;
+        if !matches!(read, Ok(Ok(n)) if n > 0) {
+            return;
+        }
+
+        let response = self.handle(command.trim());
+        let mut line = serde_json::to_string(&response).unwrap_or_default();
+        line.push('\n');
+        let _ = stream.get_mut().write_all(line.as_bytes()).await;
+    }
+}
+
+// Owner-only permissions on the socket file are the access control, so only the user
+// running the daemon (and root) can stop it
+#[cfg(unix)]
+fn spawn_control_socket(
+    socket: &str,
+    server_state: Arc<Mutex<ServerState>>,
+    shutdown: Arc<tokio::sync::Notify>,
+) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    // Left over from a daemon that didn't shut down cleanly, the PID check already
+    // made sure nothing is running
+    let _ = fs::remove_file(socket);
+    let listener = tokio::net::UnixListener::bind(socket)?;
+    fs::set_permissions(socket, fs::Permissions::from_mode(0o600))?;
+
+    let context = ControlContext {
+        server_state,
+        shutdown,
+        started_at: Instant::now(),
+    };
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(context.clone().serve(stream));
+                }
+                Err(e) => eprintln!("❌ Control socket accept failed: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+// Named pipes are created per connection, a new instance is ready before the last one is served
+#[cfg(not(unix))]
+fn spawn_control_socket(
+    socket: &str,
+    server_state: Arc<Mutex<ServerState>>,
+    shutdown: Arc<tokio::sync::Notify>,
+) -> io::Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let pipe = control_pipe_name(socket);
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(&pipe)?;
+
+    let context = ControlContext {
+        server_state,
+        shutdown,
+        started_at: Instant::now(),
+    };
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = server.connect().await {
+                eprintln!("❌ Control pipe connect failed: {}", e);
+                continue;
+            }
+            let client = server;
+            server = match ServerOptions::new().create(&pipe) {
+                Ok(server) => server,
+                Err(e) => {
+                    eprintln!("❌ Failed to create control pipe: {}", e);
+                    return;
+                }
+            };
+            tokio::spawn(context.clone().serve(client));
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn control_pipe_name(socket: &str) -> String {
+    format!(r"\\.\pipe\{}", socket.replace(['\\', '/', ':'], "_"))
+}
+
+fn control_request(socket: &str, command: &str) -> Result<ControlResponse, String> {
+    use std::io::BufRead;
+
+    #[cfg(unix)]
+    let mut stream = {
+        let stream = std::os::unix::net::UnixStream::connect(socket)
+            .map_err(|e| format!("Cannot connect to {}: {}", socket, e))?;
+        stream.set_read_timeout(Some(CONTROL_TIMEOUT)).ok();
+        stream
+    };
+    #[cfg(not(unix))]
+    let mut stream = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(control_pipe_name(socket))
+        .map_err(|e| format!("Cannot connect to {}: {}", socket, e))?;
+
+    writeln!(stream, "{}", command).map_err(|e| e.to_string())?;
+    let mut line = String::new();
+    io::BufReader::new(stream)
+        .read_line(&mut line)
+        .map_err(|e| e.to_string())?;
+    serde_json::from_str(&line).map_err(|e| format!("Invalid response from daemon: {}", e))
+}
+
+fn print_daemon_info(info: &DaemonInfo) {
+    println!("● Crusty-Crawler is running (PID {})", info.pid);
+    println!("   Version:  {}", info.version);
+    println!(
+        "   Uptime:   {}h {}m",
+        info.uptime_secs / 3600,
+        info.uptime_secs % 3600 / 60
+    );
+    println!(
+        "   Server:   {} on port {}",
+        if info.server_running {
+            "listening"
+        } else {
+            "stopped"
+        },
+        info.port
+    );
+    println!("   Checks:   {}", info.checks);
+    println!("   Alerts:   {} active", info.active_alerts);
+}
+
+pub fn show_daemon_alerts(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let options = parse_daemon_args(args)?;
+    let response = control_request(&options.socket, "alerts")
+        .map_err(|e| format!("Crusty-Crawler is not reachable: {}", e))?;
+
+    if response.alerts.is_empty() {
+        println!("✅ No active alerts");
+    }
+    for alert in &response.alerts {
+        let parent = response
+            .alerts
+            .iter()
+            .find(|parent| Some(parent.id) == alert.parent_id);
+        println!(
+            "[{}] {} since {}: {}{}{}",
+            alert.state.label(),
+            alert.check,
+            format_timestamp(&alert.raised_at),
+            alert.message,
+            parent
+                .map(|parent| format!(" (grouped with {})", parent.check))
+                .unwrap_or_default(),
+            alert
+                .acknowledged_by
+                .as_ref()
+                .map(|user| format!(" (acknowledged by {})", user))
+                .unwrap_or_default()
+        );
+    }
+    Ok(())
+}
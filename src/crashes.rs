@@ -0,0 +1,319 @@
+// Crashes module for Crusty-Crawler
+// Finds OOM-killer kills and core dumps in the systemd journal, because a service the kernel
+// killed for memory just disappears without a word in its own logs. Each one becomes a timeline
+// event and the crashes check alerts while any happened recently. Linux only
+
+// Kept for /api/crashes, older ones only live on in the timeline
+const MAX_RECENT_CRASHES: usize = 100;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CrashConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    // The check stays alerting this long after the last crash
+    pub alert_minutes: u64,
+}
+
+impl Default for CrashConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 60,
+            alert_minutes: 60,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CrashKind {
+    OomKill,
+    CoreDump,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct CrashEvent {
+    pub kind: CrashKind,
+    pub timestamp: String,
+    pub process: String,
+    pub pid: u32,
+    // The log line it was found in
+    pub message: String,
+}
+
+impl CrashEvent {
+    fn title(&self) -> String {
+        match self.kind {
+            CrashKind::OomKill => format!("OOM killer ended {} (PID {})", self.process, self.pid),
+            CrashKind::CoreDump => format!("{} (PID {}) dumped core", self.process, self.pid),
+        }
+    }
+}
+
+// One line of `journalctl -o short-unix`
+pub struct JournalLine {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    // "kernel" or the syslog identifier without the PID, e.g. "systemd-coredump"
+    pub source: String,
+    pub message: String,
+}
+
+// "1714564800.123456 host kernel: message" or "... host systemd-coredump[999]: message"
+fn parse_journal_line(line: &str) -> Option<JournalLine> {
+    let (timestamp, rest) = line.split_once(' ')?;
+    let (_host, rest) = rest.split_once(' ')?;
+    let (source, message) = rest.split_once(": ")?;
+    let micros = (timestamp.parse::<f64>().ok()? * 1_000_000.0) as i64;
+    Some(JournalLine {
+        timestamp: chrono::DateTime::from_timestamp_micros(micros)?,
+        source: source.split('[').next().unwrap_or(source).to_string(),
+        message: message.to_string(),
+    })
+}
+
+// Journal entries since `since` matching any of the journalctl field matches, e.g.
+// "_TRANSPORT=kernel"
+pub async fn read_journal(
+    since: chrono::DateTime<chrono::Utc>,
+    matches: &[&str],
+) -> Result<Vec<JournalLine>, String> {
+    let mut command = tokio::process::Command::new("journalctl");
+    command.args([
+        "--no-pager",
+        "--quiet",
+        "-o",
+        "short-unix",
+        &format!("--since=@{}", since.timestamp()),
+    ]);
+    for (index, field) in matches.iter().enumerate() {
+        if index > 0 {
+            command.arg("+");
+        }
+        command.arg(field);
+    }
+    let output = command
+        .output()
+        .await
+        .map_err(|e| format!("journalctl failed to run: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "journalctl failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(parse_journal_line)
+        .collect())
+}
+
+// "<marker>1234 (name)..." -> (1234, name)
+fn pid_and_process(message: &str, marker: &str) -> Option<(u32, String)> {
+    let rest = &message[message.find(marker)? + marker.len()..];
+    let (pid, rest) = rest.split_once(" (")?;
+    let (process, _) = rest.split_once(')')?;
+    Some((pid.parse().ok()?, process.to_string()))
+}
+
+// Older kernels log "Kill process" before "Killed process", only the second one counts
+pub fn parse_crash(line: &JournalLine) -> Option<CrashEvent> {
+    let (kind, (pid, process)) = match line.source.as_str() {
+        "kernel" => (
+            CrashKind::OomKill,
+            pid_and_process(&line.message, "Killed process ")?,
+        ),
+        "systemd-coredump" if line.message.contains("dumped core") => (
+            CrashKind::CoreDump,
+            pid_and_process(&line.message, "Process ")?,
+        ),
+        _ => return None,
+    };
+    Some(CrashEvent {
+        kind,
+        timestamp: line.timestamp.to_rfc3339(),
+        process,
+        pid,
+        message: line.message.clone(),
+    })
+}
+
+struct CrashScan {
+    scanned_at: chrono::DateTime<chrono::Utc>,
+    error: Option<String>,
+}
+
+static RECENT_CRASHES: Mutex<VecDeque<CrashEvent>> = Mutex::new(VecDeque::new());
+static LAST_CRASH_SCAN: Mutex<Option<CrashScan>> = Mutex::new(None);
+
+// The first scan looks back over the alert window to fill the check, only crashes found after
+// that become events so restarts don't repeat them
+async fn scan_crashes(config: &CrashConfig) {
+    let now = chrono::Utc::now();
+    let previous = LAST_CRASH_SCAN
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|scan| scan.scanned_at);
+    let since = previous.unwrap_or(now - chrono::Duration::minutes(config.alert_minutes as i64));
+
+    let lines = read_journal(
+        since,
+        &["_TRANSPORT=kernel", "SYSLOG_IDENTIFIER=systemd-coredump"],
+    )
+    .await;
+    let error = match lines {
+        Ok(lines) => {
+            let mut recent = RECENT_CRASHES.lock().unwrap();
+            for crash in lines.iter().filter_map(parse_crash) {
+                // --since has whole seconds, the boundary second comes around twice
+                if recent.contains(&crash) {
+                    continue;
+                }
+                if previous.is_some() {
+                    record_event(EventKind::Crash, &crash.title(), &crash.message);
+                }
+                if recent.len() == MAX_RECENT_CRASHES {
+                    recent.pop_front();
+                }
+                recent.push_back(crash);
+            }
+            None
+        }
+        Err(e) => Some(e),
+    };
+    *LAST_CRASH_SCAN.lock().unwrap() = Some(CrashScan {
+        scanned_at: now,
+        error,
+    });
+}
+
+// Newest first
+pub fn recent_crashes() -> Vec<CrashEvent> {
+    RECENT_CRASHES
+        .lock()
+        .unwrap()
+        .iter()
+        .rev()
+        .cloned()
+        .collect()
+}
+
+// Returns how many crashes were removed
+pub fn remove_crashes_before(cutoff: chrono::DateTime<chrono::Utc>) -> usize {
+    let mut recent = RECENT_CRASHES.lock().unwrap();
+    let before = recent.len();
+    recent.retain(|crash| {
+        chrono::DateTime::parse_from_rfc3339(&crash.timestamp)
+            .is_ok_and(|timestamp| timestamp >= cutoff)
+    });
+    before - recent.len()
+}
+
+pub fn crashes_available(config: &CrashConfig) -> bool {
+    cfg!(target_os = "linux") && config.enabled
+}
+
+// OOM kills are CRITICAL and core dumps WARNING while within the alert window
+pub fn crash_check_result(
+    crashes: &[CrashEvent],
+    config: &CrashConfig,
+    now: chrono::DateTime<chrono::Utc>,
+) -> CheckResult {
+    let cutoff = now - chrono::Duration::minutes(config.alert_minutes as i64);
+    let recent: Vec<&CrashEvent> = crashes
+        .iter()
+        .filter(|crash| {
+            chrono::DateTime::parse_from_rfc3339(&crash.timestamp)
+                .is_ok_and(|timestamp| timestamp >= cutoff)
+        })
+        .collect();
+    let count = |kind: CrashKind| recent.iter().filter(|crash| crash.kind == kind).count();
+    let (oom_kills, core_dumps) = (count(CrashKind::OomKill), count(CrashKind::CoreDump));
+    let perfdata = vec![
+        PerfData::new("oom_kills", oom_kills as f64, ""),
+        PerfData::new("core_dumps", core_dumps as f64, ""),
+    ];
+    if recent.is_empty() {
+        return CheckResult::new(
+            "crashes",
+            CheckState::Ok,
+            format!(
+                "No OOM kills or core dumps in the last {} minutes",
+                config.alert_minutes
+            ),
+            perfdata,
+        );
+    }
+
+    let state = if oom_kills > 0 {
+        CheckState::Critical
+    } else {
+        CheckState::Warning
+    };
+    let mut titles: Vec<String> = recent.iter().take(5).map(|crash| crash.title()).collect();
+    if recent.len() > titles.len() {
+        titles.push("...".to_string());
+    }
+    CheckResult::new(
+        "crashes",
+        state,
+        format!(
+            "{} OOM kill(s) and {} core dump(s) in the last {} minutes: {}",
+            oom_kills,
+            core_dumps,
+            config.alert_minutes,
+            titles.join(", ")
+        ),
+        perfdata,
+    )
+}
+
+fn check_crashes(config: &CrashConfig) -> CheckResult {
+    match &*LAST_CRASH_SCAN.lock().unwrap() {
+        None => CheckResult::new(
+            "crashes",
+            CheckState::Unknown,
+            "The journal hasn't been scanned for crashes yet".to_string(),
+            Vec::new(),
+        ),
+        Some(CrashScan {
+            error: Some(error), ..
+        }) => CheckResult::new(
+            "crashes",
+            CheckState::Unknown,
+            format!("Can't scan the journal for crashes: {}", error),
+            Vec::new(),
+        ),
+        Some(_) => crash_check_result(&recent_crashes(), config, chrono::Utc::now()),
+    }
+}
+
+fn spawn_crash_monitor(server_state: Arc<Mutex<ServerState>>) {
+    if !cfg!(target_os = "linux") {
+        return;
+    }
+    std::thread::spawn(move || {
+        let rt = match Runtime::new() {
+            Ok(rt) => rt,
+            Err(e) => {
+                eprintln!("❌ Failed to start crash monitor: {}", e);
+                return;
+            }
+        };
+
+        rt.block_on(async {
+            loop {
+                let config = {
+                    let state = server_state.lock().unwrap();
+                    let auth_manager = state.auth_manager.lock().unwrap();
+                    auth_manager.config.checks.crashes.clone()
+                };
+                if config.enabled {
+                    scan_crashes(&config).await;
+                }
+                tokio::time::sleep(Duration::from_secs(config.interval_secs.max(10))).await;
+            }
+        });
+    });
+}
@@ -0,0 +1,191 @@
+// Credentials module for Crusty-Crawler
+// When, from where and how often each access token and web session was used, so admins can
+// spot credentials nobody needs anymore or that turn up somewhere unexpected, and revoke them
+
+const TOKEN_USAGE_FILE: &str = "token_usage.json";
+// Written back at most this often rather than on every request
+const TOKEN_USAGE_SAVE_EVERY: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct CredentialUsage {
+    // RFC 3339, None if never used
+    pub last_used: Option<String>,
+    pub last_ip: Option<String>,
+    pub requests: u64,
+}
+
+impl CredentialUsage {
+    fn record(&mut self, at: chrono::DateTime<chrono::Utc>, ip: Option<std::net::IpAddr>) {
+        self.last_used = Some(at.to_rfc3339());
+        if let Some(ip) = ip {
+            self.last_ip = Some(ip.to_string());
+        }
+        self.requests += 1;
+    }
+}
+
+// Sessions keep their usage in memory and end with the process, access tokens' is saved
+#[derive(Serialize, Deserialize, Clone)]
+struct StoredTokenUsage {
+    // Usage starts over when the token is rotated
+    token_id: String,
+    #[serde(flatten)]
+    usage: CredentialUsage,
+}
+
+struct TokenUsageStore {
+    // By username
+    tokens: BTreeMap<String, StoredTokenUsage>,
+    last_saved: Instant,
+}
+
+static TOKEN_USAGE: Mutex<Option<TokenUsageStore>> = Mutex::new(None);
+
+fn with_token_usage<T>(f: impl FnOnce(&mut TokenUsageStore) -> T) -> T {
+    let mut store = TOKEN_USAGE.lock().unwrap();
+    let store = store.get_or_insert_with(|| TokenUsageStore {
+        tokens: fs::read_to_string(data_path(TOKEN_USAGE_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default(),
+        last_saved: Instant::now(),
+    });
+    f(store)
+}
+
+// Writes the token usage back when it's due. Only the snapshot is taken under the lock, call
+// it with the auth manager unlocked so other requests don't wait on the disk
+pub fn save_token_usage_if_due() {
+    let snapshot = with_token_usage(|store| {
+        if store.last_saved.elapsed() < TOKEN_USAGE_SAVE_EVERY {
+            return None;
+        }
+        store.last_saved = Instant::now();
+        Some(serde_json::to_string_pretty(&store.tokens))
+    });
+    let Some(snapshot) = snapshot else {
+        return;
+    };
+    let saved = snapshot.map_err(|e| e.to_string()).and_then(|data| {
+        write_file_atomic(&data_path(TOKEN_USAGE_FILE), data.as_bytes()).map_err(|e| e.to_string())
+    });
+    if let Err(e) = saved {
+        eprintln!("⚠️  Failed to save {}: {}", TOKEN_USAGE_FILE, e);
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialKind {
+    AccessToken,
+    WebSession,
+}
+
+#[derive(Serialize, Clone)]
+pub struct CredentialReport {
+    pub username: String,
+    pub kind: CredentialKind,
+    // Web sessions are revoked by this id, access tokens by username
+    pub id: Option<String>,
+    pub expires_in_secs: Option<u64>,
+    #[serde(flatten)]
+    pub usage: CredentialUsage,
+}
+
+impl AuthManager {
+    // validate_token that also records the use, save_token_usage_if_due writes it back
+    pub fn authenticate_token(
+        &self,
+        token: &str,
+        ip: Option<std::net::IpAddr>,
+    ) -> Result<String, String> {
+        let now = self.clock.utc_now();
+        if let Some(user) = self.token_owner(token) {
+            let token_id = access_token_id(&user.access_token_hash).to_string();
+            with_token_usage(|store| {
+                let stored = store
+                    .tokens
+                    .entry(user.username.clone())
+                    .or_insert_with(|| StoredTokenUsage {
+                        token_id: token_id.clone(),
+                        usage: CredentialUsage::default(),
+                    });
+                if stored.token_id != token_id {
+                    *stored = StoredTokenUsage {
+                        token_id,
+                        usage: CredentialUsage::default(),
+                    };
+                }
+                stored.usage.record(now, ip);
+            });
+            return Ok(user.username.clone());
+        }
+
+        let username = self
+            .validate_web_session(token)
+            .ok_or("Invalid access token")?;
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(token) {
+            session.usage.record(now, ip);
+        }
+        Ok(username)
+    }
+
+    // Every user's access token followed by the open web sessions
+    pub fn credential_report(&self) -> Vec<CredentialReport> {
+        let token_usage = with_token_usage(|store| store.tokens.clone());
+        let mut users: Vec<&User> = self.config.users.values().collect();
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        let mut report: Vec<CredentialReport> = users
+            .into_iter()
+            .map(|user| CredentialReport {
+                username: user.username.clone(),
+                kind: CredentialKind::AccessToken,
+                id: None,
+                expires_in_secs: None,
+                usage: token_usage
+                    .get(&user.username)
+                    .filter(|stored| stored.token_id == access_token_id(&user.access_token_hash))
+                    .map(|stored| stored.usage.clone())
+                    .unwrap_or_default(),
+            })
+            .collect();
+
+        let now = self.clock.now();
+        let sessions = self.sessions.lock().unwrap();
+        let mut open: Vec<&WebSession> = sessions
+            .values()
+            .filter(|session| !session.single_use && session.expires_at > now)
+            .collect();
+        open.sort_by(|a, b| (&a.username, a.expires_at).cmp(&(&b.username, b.expires_at)));
+        report.extend(open.into_iter().map(|session| CredentialReport {
+            username: session.username.clone(),
+            kind: CredentialKind::WebSession,
+            id: Some(session.id.clone()),
+            expires_in_secs: Some(session.expires_at.duration_since(now).as_secs()),
+            usage: session.usage.clone(),
+        }));
+        report
+    }
+
+    pub fn web_session_owner(&self, id: &str) -> Option<String> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions
+            .values()
+            .find(|session| session.id == id)
+            .map(|session| session.username.clone())
+    }
+
+    // The browser has to be signed in with the access token again
+    pub fn revoke_web_session(&self, id: &str) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        let before = sessions.len();
+        sessions.retain(|_, session| session.id != id);
+        sessions.len() != before
+    }
+
+    // Swaps the token for one nobody knows, the user gets a working one by rotating it in the
+    // GUI or through credential recovery
+    pub fn revoke_access_token(&mut self, username: &str) -> Result<(), String> {
+        self.rotate_access_token(username).map(|_| ())
+    }
+}
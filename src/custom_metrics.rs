@@ -0,0 +1,197 @@
+// Custom metrics module for Crusty-Crawler
+// Gauges and counters pushed by local applications through POST /api/custom-metrics, shown on
+// the dashboards and exported with the system metrics
+
+const MAX_CUSTOM_SERIES: usize = 1000;
+const CUSTOM_HISTORY: usize = 120;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CustomMetricKind {
+    #[default]
+    Gauge,
+    // Pushed values are increments added to a running total
+    Counter,
+}
+
+#[derive(Deserialize)]
+pub struct CustomMetricSample {
+    pub name: String,
+    pub value: f64,
+    #[serde(default, rename = "type")]
+    pub kind: CustomMetricKind,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+pub struct CustomMetricsPush {
+    pub metrics: Vec<CustomMetricSample>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct CustomSeries {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: CustomMetricKind,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+    pub value: f64,
+    pub updated_at: String,
+    // Oldest first, (RFC 3339 timestamp, value)
+    pub history: VecDeque<(String, f64)>,
+}
+
+// Series are keyed by name and tags
+type CustomSeriesKey = (String, BTreeMap<String, String>);
+
+static CUSTOM_METRICS: Mutex<BTreeMap<CustomSeriesKey, CustomSeries>> = Mutex::new(BTreeMap::new());
+
+fn valid_metric_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// All or nothing, a bad sample rejects the whole push so applications notice
+pub fn record_custom_metrics(push: CustomMetricsPush) -> Result<usize, String> {
+    for sample in &push.metrics {
+        if !valid_metric_name(&sample.name) {
+            return Err(format!(
+                "Invalid metric name '{}', use letters, digits and underscores",
+                sample.name
+            ));
+        }
+        if let Some(key) = sample.tags.keys().find(|key| !valid_metric_name(key)) {
+            return Err(format!("Invalid tag name '{}' on {}", key, sample.name));
+        }
+        if !sample.value.is_finite() {
+            return Err(format!("Value of {} must be a finite number", sample.name));
+        }
+    }
+
+    let mut series = CUSTOM_METRICS.lock().unwrap();
+    let new_series = push
+        .metrics
+        .iter()
+        .filter(|sample| !series.contains_key(&(sample.name.clone(), sample.tags.clone())))
+        .count();
+    if series.len() + new_series > MAX_CUSTOM_SERIES {
+        return Err(format!(
+            "Too many custom series, the limit is {}",
+            MAX_CUSTOM_SERIES
+        ));
+    }
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let count = push.metrics.len();
+    for sample in push.metrics {
+        let entry = series
+            .entry((sample.name.clone(), sample.tags.clone()))
+            .or_insert_with(|| CustomSeries {
+                name: sample.name,
+                kind: sample.kind,
+                tags: sample.tags,
+                value: 0.0,
+                updated_at: now.clone(),
+                history: VecDeque::new(),
+            });
+        entry.kind = sample.kind;
+        entry.value = match sample.kind {
+            CustomMetricKind::Gauge => sample.value,
+            CustomMetricKind::Counter => entry.value + sample.value,
+        };
+        entry.updated_at = now.clone();
+        if entry.history.len() == CUSTOM_HISTORY {
+            entry.history.pop_front();
+        }
+        entry.history.push_back((now.clone(), entry.value));
+    }
+    Ok(count)
+}
+
+pub fn custom_series() -> Vec<CustomSeries> {
+    CUSTOM_METRICS.lock().unwrap().values().cloned().collect()
+}
+
+pub fn custom_metrics() -> Vec<Metric> {
+    custom_series()
+        .into_iter()
+        .map(|series| {
+            let suffix = match series.kind {
+                CustomMetricKind::Counter if !series.name.ends_with("_total") => "_total",
+                _ => "",
+            };
+            Metric {
+                name: format!("custom_{}{}", series.name, suffix),
+                value: series.value,
+                labels: series.tags,
+            }
+        })
+        .collect()
+}
+
+fn format_custom_series(series: &CustomSeries) -> String {
+    let tags: Vec<String> = series
+        .tags
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    if tags.is_empty() {
+        format!("{}: {}", series.name, series.value)
+    } else {
+        format!("{} [{}]: {}", series.name, tags.join(", "), series.value)
+    }
+}
+
+// Section for the text status served to the web dashboard
+pub fn custom_metrics_status() -> String {
+    let series = custom_series();
+    if series.is_empty() {
+        return String::new();
+    }
+    let mut out = String::from("\nCustom Metrics:\n");
+    for series in &series {
+        out.push_str(&format!("  {}\n", format_custom_series(series)));
+    }
+    out
+}
+
+#[cfg(feature = "gui")]
+impl MainState {
+    fn show_custom_metrics(&self, ui: &mut egui::Ui) {
+        let series = custom_series();
+        if series.is_empty() {
+            return;
+        }
+
+        ui.separator();
+        ui.heading("📈 Custom Metrics");
+        egui::Frame::group(ui.style())
+            .inner_margin(egui::Margin::same(10))
+            .show(ui, |ui| {
+                egui::Grid::new("custom_metrics")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for series in &series {
+                            ui.monospace(format_custom_series(series));
+                            let history: Vec<f64> =
+                                series.history.iter().map(|(_, value)| *value).collect();
+                            let (min, max) = history
+                                .iter()
+                                .fold((f64::MAX, f64::MIN), |(lo, hi), v| (lo.min(*v), hi.max(*v)));
+                            ui.small(format!(
+                                "{} samples, range {} to {}, updated {}",
+                                history.len(),
+                                min,
+                                max,
+                                format_timestamp(&series.updated_at)
+                            ));
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+}
@@ -0,0 +1,321 @@
+// Daemon module for Crusty-Crawler
+// Runs the server headless without touching stdin: PID file, signal handling and optional detach
+
+const DEFAULT_PID_FILE: &str = "crusty-crawler.pid";
+
+struct DaemonOptions {
+    detach: bool,
+    pid_file: String,
+    socket: String,
+    // Web server port, the default 3000 without it
+    port: Option<u16>,
+}
+
+fn parse_daemon_args(args: &[String]) -> Result<DaemonOptions, String> {
+    let mut options = DaemonOptions {
+        detach: false,
+        pid_file: data_file(DEFAULT_PID_FILE),
+        socket: data_file(DEFAULT_CONTROL_SOCKET),
+        port: None,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--detach" => options.detach = true,
+            "--foreground" => options.detach = false,
+            "--pid-file" => {
+                options.pid_file = args.next().ok_or("--pid-file needs a path")?.clone();
+            }
+            "--socket" => {
+                options.socket = args.next().ok_or("--socket needs a path")?.clone();
+            }
+            "--port" => {
+                let port = args.next().ok_or("--port needs a number")?;
+                options.port = Some(
+                    port.parse()
+                        .map_err(|_| format!("Invalid port number: {}", port))?,
+                );
+            }
+            // The mode flags themselves
+            "--daemon" | "daemon" | "start" | "stop" | "status" | "alerts" | "reload" | "--cli"
+            | "--no-gui" | "container" => {}
+            other => return Err(format!("Unknown option '{}'", other)),
+        }
+    }
+    Ok(options)
+}
+
+// PID from the PID file if that process is still alive, stale files are ignored
+fn running_pid(pid_file: &str) -> Option<u32> {
+    let pid: u32 = fs::read_to_string(pid_file).ok()?.trim().parse().ok()?;
+    if pid == std::process::id() {
+        return None;
+    }
+    let pid = sysinfo::Pid::from_u32(pid);
+    let mut sys = sysinfo::System::new();
+    sys.refresh_processes(sysinfo::ProcessesToUpdate::Some(&[pid]), true);
+    sys.process(pid).map(|_| pid.as_u32())
+}
+
+// Detach from the terminal: fork, new session, stdio to /dev/null. Must run before any thread
+// is started, forking only carries the calling thread over
+#[cfg(unix)]
+fn detach() -> Result<(), String> {
+    use std::os::fd::AsRawFd;
+
+    unsafe extern "C" {
+        fn fork() -> i32;
+        fn setsid() -> i32;
+        fn dup2(old: i32, new: i32) -> i32;
+    }
+
+    match unsafe { fork() } {
+        -1 => return Err(format!("fork failed: {}", io::Error::last_os_error())),
+        0 => {}
+        _ => std::process::exit(0),
+    }
+    if unsafe { setsid() } == -1 {
+        return Err(format!("setsid failed: {}", io::Error::last_os_error()));
+    }
+
+    let null = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")
+        .map_err(|e| format!("Failed to open /dev/null: {}", e))?;
+    for fd in 0..=2 {
+        unsafe { dup2(null.as_raw_fd(), fd) };
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn detach() -> Result<(), String> {
+    Err("--detach is only supported on Unix, install the Windows service instead".to_string())
+}
+
+// Quiet when nothing changed, the GUI's own saves trigger the file watcher too
+fn reload_config(server_state: &Arc<Mutex<ServerState>>) -> Result<Vec<String>, String> {
+    let state = server_state.lock().unwrap();
+    let mut auth_manager = state.auth_manager.lock().unwrap();
+    match auth_manager.reload() {
+        // Background loops read the config on every pass, only the port needs a restart
+        Ok(changes) => {
+            if !changes.is_empty() {
+                println!("🔄 Configuration reloaded, {} change(s):", changes.len());
+                for change in &changes {
+                    println!("   {}", change);
+                }
+            }
+            Ok(changes)
+        }
+        Err(e) => {
+            eprintln!(
+                "❌ Failed to reload configuration, keeping the old one: {}",
+                e
+            );
+            Err(e)
+        }
+    }
+}
+
+// Blocks until SIGTERM, SIGINT or a stop over the control socket, reloading the config on
+// every SIGHUP
+#[cfg(unix)]
+async fn wait_for_shutdown(
+    server_state: &Arc<Mutex<ServerState>>,
+    shutdown: &tokio::sync::Notify,
+) -> io::Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+    loop {
+        tokio::select! {
+            _ = terminate.recv() => return Ok(()),
+            _ = interrupt.recv() => return Ok(()),
+            _ = shutdown.notified() => return Ok(()),
+            _ = hangup.recv() => {
+                let _ = reload_config(server_state);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_shutdown(
+    _server_state: &Arc<Mutex<ServerState>>,
+    shutdown: &tokio::sync::Notify,
+) -> io::Result<()> {
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result,
+        _ = shutdown.notified() => Ok(()),
+    }
+}
+
+pub fn run_daemon_mode(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let options = parse_daemon_args(args)?;
+
+    if let Some(pid) = running_pid(&options.pid_file) {
+        return Err(format!(
+            "Crusty-Crawler is already running with PID {} ({})",
+            pid, options.pid_file
+        )
+        .into());
+    }
+
+    if options.detach {
+        // systemd tracks the process itself and would lose it after a fork
+        if env::var_os("INVOCATION_ID").is_some() {
+            println!("⚠️  Started by systemd, staying in the foreground");
+        } else {
+            detach()?;
+        }
+    }
+
+    // Held until the function returns so the last messages still reach the file
+    let logging = AuthConfig::load_or_default(&data_file(AUTH_CONFIG_FILE)).logging;
+    let _log_capture = if logging.enabled {
+        start_file_logging(&logging)
+            .inspect_err(|e| eprintln!("⚠️  {}, logging to stdout only", e))
+            .ok()
+    } else {
+        None
+    };
+
+    serve_headless(&options)
+}
+
+// The server and background loops until a stop signal, for daemon and container mode
+fn serve_headless(options: &DaemonOptions) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(&options.pid_file, format!("{}\n", std::process::id()))
+        .map_err(|e| format!("Failed to write PID file {}: {}", options.pid_file, e))?;
+    println!(
+        "🦀 Crusty-Crawler daemon started (PID {}, PID file {})",
+        std::process::id(),
+        options.pid_file
+    );
+
+    let server_state = Arc::new(Mutex::new(ServerState::default()));
+    {
+        let mut state = server_state.lock().unwrap();
+        if let Some(port) = options.port {
+            state.port = port;
+        }
+        let auth_manager = state.auth_manager.lock().unwrap();
+        if !auth_manager.has_users() {
+            eprintln!("⚠️  No users configured, run with --cli once to create one");
+        }
+    }
+    init_platform_capabilities();
+    spawn_check_loop(server_state.clone());
+    spawn_ebpf_probes(server_state.clone());
+    spawn_scheduler(server_state.clone());
+    spawn_dashboard_stream(server_state.clone());
+    spawn_central_reporter(server_state.clone());
+    spawn_cluster_sync(server_state.clone());
+    spawn_fleet_alerting(server_state.clone());
+    spawn_derived_metrics(server_state.clone());
+    spawn_load_monitor(server_state.clone());
+    spawn_config_watcher(server_state.clone());
+    spawn_otlp_exporter(server_state.clone());
+    spawn_statsd_listener(server_state.clone());
+    spawn_zabbix_sender(server_state.clone());
+    spawn_checkmk_listener(server_state.clone());
+    spawn_event_timeline();
+    spawn_diagnostic_snapshots(server_state.clone());
+    spawn_mdns_advertiser(server_state.clone());
+    spawn_crash_monitor(server_state.clone());
+    spawn_kernel_log_scanner(server_state.clone());
+
+    let result = start_server(&server_state).and_then(|_| {
+        let rt = Runtime::new()?;
+        rt.block_on(async {
+            let shutdown = Arc::new(tokio::sync::Notify::new());
+            if let Err(e) =
+                spawn_control_socket(&options.socket, server_state.clone(), shutdown.clone())
+            {
+                eprintln!("⚠️  Control socket {} unavailable: {}", options.socket, e);
+            }
+            wait_for_shutdown(&server_state, &shutdown).await
+        })?;
+        Ok(())
+    });
+
+    println!("🛑 Shutting down...");
+    record_event(EventKind::AgentStop, "Agent stopped", "");
+    if server_state.lock().unwrap().is_running {
+        stop_server(&server_state)?;
+    }
+    shutdown_otlp();
+    let _ = fs::remove_file(&options.pid_file);
+    #[cfg(unix)]
+    let _ = fs::remove_file(&options.socket);
+    result
+}
+
+pub fn stop_daemon(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let options = parse_daemon_args(args)?;
+    let control_error = match control_request(&options.socket, "stop") {
+        Ok(response) => {
+            println!("🛑 {}", response.message);
+            return Ok(());
+        }
+        Err(e) => e,
+    };
+
+    // Fall back to a signal for daemons without a reachable socket
+    let pid = running_pid(&options.pid_file)
+        .ok_or_else(|| format!("Crusty-Crawler is not running ({})", options.pid_file))?;
+    eprintln!("⚠️  {}, sending a signal instead", control_error);
+
+    let pid = sysinfo::Pid::from_u32(pid);
+    let mut sys = sysinfo::System::new();
+    sys.refresh_processes(sysinfo::ProcessesToUpdate::Some(&[pid]), true);
+    let process = sys.process(pid).ok_or("Process exited")?;
+    // Windows has no SIGTERM, fall back to terminating the process
+    let sent = process
+        .kill_with(sysinfo::Signal::Term)
+        .unwrap_or_else(|| process.kill());
+    if !sent {
+        return Err(format!("Failed to signal PID {}", pid).into());
+    }
+    println!("🛑 Sent stop signal to PID {}", pid);
+    Ok(())
+}
+
+// Exit code follows the LSB init script convention: 0 running, 3 not running
+pub fn daemon_status(args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+    let options = parse_daemon_args(args)?;
+    if let Ok(ControlResponse {
+        info: Some(info), ..
+    }) = control_request(&options.socket, "status")
+    {
+        print_daemon_info(&info);
+        return Ok(0);
+    }
+
+    match running_pid(&options.pid_file) {
+        Some(pid) => {
+            println!("● Crusty-Crawler is running (PID {})", pid);
+            println!("   Control socket {} is not reachable", options.socket);
+            Ok(0)
+        }
+        None => {
+            println!("● Crusty-Crawler is not running");
+            Ok(3)
+        }
+    }
+}
+
+pub fn reload_daemon(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let options = parse_daemon_args(args)?;
+    let response = control_request(&options.socket, "reload")
+        .map_err(|e| format!("Crusty-Crawler is not reachable: {}", e))?;
+    if !response.ok {
+        return Err(response.message.into());
+    }
+    println!("🔄 {}", response.message);
+    Ok(())
+}
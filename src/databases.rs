@@ -0,0 +1,371 @@
+// Database checks module for Crusty-Crawler
+// Connects to configured PostgreSQL, MySQL and Redis instances and reports connectivity,
+// connection usage, replication lag and slow queries
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseKind {
+    Postgres,
+    Mysql,
+    Redis,
+}
+
+impl DatabaseKind {
+    fn default_port(&self) -> u16 {
+        match self {
+            DatabaseKind::Postgres => 5432,
+            DatabaseKind::Mysql => 3306,
+            DatabaseKind::Redis => 6379,
+        }
+    }
+}
+
+fn default_database_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_connection_thresholds() -> Thresholds {
+    Thresholds {
+        warning: 80.0,
+        critical: 95.0,
+    }
+}
+
+fn default_lag_thresholds() -> Thresholds {
+    Thresholds {
+        warning: 30.0,
+        critical: 300.0,
+    }
+}
+
+fn default_slow_query_thresholds() -> Thresholds {
+    Thresholds {
+        warning: 1.0,
+        critical: 10.0,
+    }
+}
+
+fn default_slow_query_secs() -> u64 {
+    5
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DatabaseCheck {
+    // Check name used by the checks API, e.g. "postgres-main"
+    pub name: String,
+    pub kind: DatabaseKind,
+    #[serde(default = "default_database_host")]
+    pub host: String,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub database: Option<String>,
+    // Percentage of the server's connection limit in use
+    #[serde(default = "default_connection_thresholds")]
+    pub connections: Thresholds,
+    // Seconds a replica is behind its primary
+    #[serde(default = "default_lag_thresholds")]
+    pub replication_lag: Thresholds,
+    // Number of queries running longer than slow_query_secs (Redis: slow log entries)
+    #[serde(default = "default_slow_query_thresholds")]
+    pub slow_queries: Thresholds,
+    #[serde(default = "default_slow_query_secs")]
+    pub slow_query_secs: u64,
+}
+
+impl DatabaseCheck {
+    fn port(&self) -> u16 {
+        self.port.unwrap_or_else(|| self.kind.default_port())
+    }
+
+    fn host(&self) -> &str {
+        bare_host(&self.host)
+    }
+}
+
+#[derive(Default)]
+struct DatabaseStats {
+    connections: Option<f64>,
+    max_connections: Option<f64>,
+    // None on a primary
+    replication_lag: Option<f64>,
+    replication_broken: bool,
+    slow_queries: Option<f64>,
+}
+
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(10);
+
+async fn run_client(mut command: tokio::process::Command) -> Result<String, String> {
+    let output = tokio::time::timeout(DATABASE_TIMEOUT, command.output())
+        .await
+        .map_err(|_| "Timed out connecting to the database".to_string())?
+        .map_err(|e| format!("Failed to run database client: {}", e))?;
+
+    if !output.status.success() {
+        // Plugin output has to stay on one line
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join(" "));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// Uses psql so authentication methods and pg_service files work as they do for the admin
+async fn postgres_stats(db: &DatabaseCheck) -> Result<DatabaseStats, String> {
+    let query = format!(
+        "SELECT (SELECT count(*) FROM pg_stat_activity), \
+         current_setting('max_connections'), \
+         CASE WHEN pg_is_in_recovery() THEN \
+           COALESCE(EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp()), 0) END, \
+         (SELECT count(*) FROM pg_stat_activity WHERE state = 'active' \
+           AND now() - query_start > interval '{} seconds')",
+        db.slow_query_secs
+    );
+
+    let mut command = tokio::process::Command::new("psql");
+    command
+        .args(["-h", db.host(), "-p", &db.port().to_string()])
+        .args(["-U", db.username.as_deref().unwrap_or("postgres")])
+        .args(["-d", db.database.as_deref().unwrap_or("postgres")])
+        .args(["-w", "-At", "-F", "|", "-c", &query]);
+    if let Some(password) = &db.password {
+        command.env("PGPASSWORD", password);
+    }
+
+    let output = run_client(command).await?;
+    let fields: Vec<&str> = output.trim().split('|').collect();
+    if fields.len() < 4 {
+        return Err(format!("Unexpected psql output: {}", output.trim()));
+    }
+
+    Ok(DatabaseStats {
+        connections: fields[0].parse().ok(),
+        max_connections: fields[1].parse().ok(),
+        replication_lag: fields[2].parse().ok(),
+        replication_broken: false,
+        slow_queries: fields[3].parse().ok(),
+    })
+}
+
+fn mysql_command(db: &DatabaseCheck) -> tokio::process::Command {
+    let mut command = tokio::process::Command::new("mysql");
+    command
+        .args(["-h", db.host(), "-P", &db.port().to_string()])
+        .args(["-u", db.username.as_deref().unwrap_or("root")]);
+    if let Some(database) = &db.database {
+        command.arg(database);
+    }
+    if let Some(password) = &db.password {
+        command.env("MYSQL_PWD", password);
+    }
+    command
+}
+
+async fn mysql_stats(db: &DatabaseCheck) -> Result<DatabaseStats, String> {
+    let query = format!(
+        "SELECT \
+         (SELECT VARIABLE_VALUE FROM performance_schema.global_status WHERE VARIABLE_NAME = 'Threads_connected'), \
+         @@max_connections, \
+         (SELECT COUNT(*) FROM information_schema.PROCESSLIST WHERE COMMAND = 'Query' AND TIME > {})",
+        db.slow_query_secs
+    );
+    let mut command = mysql_command(db);
+    command.args(["-N", "-B", "-e", &query]);
+
+    let output = run_client(command).await?;
+    let fields: Vec<&str> = output.trim().split('\t').collect();
+    let mut stats = DatabaseStats {
+        connections: fields.first().and_then(|v| v.parse().ok()),
+        max_connections: fields.get(1).and_then(|v| v.parse().ok()),
+        slow_queries: fields.get(2).and_then(|v| v.parse().ok()),
+        ..Default::default()
+    };
+
+    // Empty on a primary, MySQL before 8.0.22 only knows the SLAVE spelling
+    let mut replica = String::new();
+    for statement in ["SHOW REPLICA STATUS", "SHOW SLAVE STATUS"] {
+        let mut command = mysql_command(db);
+        command.args(["-E", "-e", statement]);
+        if let Ok(output) = run_client(command).await {
+            replica = output;
+            break;
+        }
+    }
+    for line in replica.lines() {
+        if let Some((key, value)) = line.trim().split_once(": ")
+            && (key == "Seconds_Behind_Source" || key == "Seconds_Behind_Master")
+        {
+            // NULL means the replication threads are stopped
+            match value.trim().parse() {
+                Ok(lag) => stats.replication_lag = Some(lag),
+                Err(_) => stats.replication_broken = true,
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+// Sends one command over RESP and returns the reply as text
+async fn redis_command(
+    stream: &mut tokio::io::BufReader<tokio::net::TcpStream>,
+    args: &[&str],
+) -> Result<String, String> {
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+    let mut request = format!("*{}\r\n", args.len());
+    for arg in args {
+        request.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+    }
+    stream
+        .get_mut()
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut header = String::new();
+    stream
+        .read_line(&mut header)
+        .await
+        .map_err(|e| e.to_string())?;
+    let header = header.trim_end();
+    match header.chars().next() {
+        Some('+') | Some(':') => Ok(header[1..].to_string()),
+        Some('-') => Err(header[1..].to_string()),
+        Some('$') => {
+            let length: usize = header[1..].parse().map_err(|_| "Invalid Redis reply")?;
+            let mut body = vec![0; length + 2];
+            stream
+                .read_exact(&mut body)
+                .await
+                .map_err(|e| e.to_string())?;
+            body.truncate(length);
+            Ok(String::from_utf8_lossy(&body).to_string())
+        }
+        _ => Err(format!("Unexpected Redis reply: {}", header)),
+    }
+}
+
+async fn redis_stats(db: &DatabaseCheck) -> Result<DatabaseStats, String> {
+    let stream = tokio::time::timeout(
+        DATABASE_TIMEOUT,
+        tokio::net::TcpStream::connect((db.host(), db.port())),
+    )
+    .await
+    .map_err(|_| "Timed out connecting to Redis".to_string())?
+    .map_err(|e| format!("Failed to connect to Redis: {}", e))?;
+    let mut stream = tokio::io::BufReader::new(stream);
+
+    if let Some(password) = &db.password {
+        match &db.username {
+            Some(username) => redis_command(&mut stream, &["AUTH", username, password]).await?,
+            None => redis_command(&mut stream, &["AUTH", password]).await?,
+        };
+    }
+
+    let info = redis_command(&mut stream, &["INFO"]).await?;
+    let fields: HashMap<&str, &str> = info
+        .lines()
+        .filter_map(|line| line.trim().split_once(':'))
+        .collect();
+
+    let mut stats = DatabaseStats {
+        connections: fields.get("connected_clients").and_then(|v| v.parse().ok()),
+        max_connections: fields.get("maxclients").and_then(|v| v.parse().ok()),
+        slow_queries: redis_command(&mut stream, &["SLOWLOG", "LEN"])
+            .await
+            .ok()
+            .and_then(|v| v.parse().ok()),
+        ..Default::default()
+    };
+
+    if fields.get("role") == Some(&"slave") {
+        if fields.get("master_link_status") != Some(&"up") {
+            stats.replication_broken = true;
+        }
+        stats.replication_lag = fields
+            .get("master_last_io_seconds_ago")
+            .and_then(|v| v.parse().ok());
+    }
+
+    Ok(stats)
+}
+
+pub async fn check_database(db: &DatabaseCheck) -> CheckResult {
+    let stats = match db.kind {
+        DatabaseKind::Postgres => postgres_stats(db).await,
+        DatabaseKind::Mysql => mysql_stats(db).await,
+        DatabaseKind::Redis => redis_stats(db).await,
+    };
+
+    let stats = match stats {
+        Ok(stats) => stats,
+        Err(e) => {
+            return CheckResult::new(
+                &db.name,
+                CheckState::Critical,
+                format!(
+                    "Cannot connect to {}: {}",
+                    host_port(&db.host, db.port()),
+                    e
+                ),
+                Vec::new(),
+            );
+        }
+    };
+
+    let mut state = CheckState::Ok;
+    let mut summaries = Vec::new();
+    let mut perfdata = Vec::new();
+
+    if let Some(connections) = stats.connections {
+        let mut perf = PerfData::new("connections", connections, "");
+        match stats.max_connections.filter(|max| *max > 0.0) {
+            Some(max) => {
+                let percent = connections / max * 100.0;
+                state = state.max(db.connections.evaluate(percent));
+                summaries.push(format!("{} of {} connections", connections, max));
+                perf.warn = Some((max * db.connections.warning / 100.0).floor());
+                perf.crit = Some((max * db.connections.critical / 100.0).floor());
+                perf = perf.range(0.0, max);
+            }
+            None => summaries.push(format!("{} connections", connections)),
+        }
+        perfdata.push(perf);
+    }
+
+    if stats.replication_broken {
+        state = CheckState::Critical;
+        summaries.push("replication is not running".to_string());
+    } else if let Some(lag) = stats.replication_lag {
+        state = state.max(db.replication_lag.evaluate(lag));
+        summaries.push(format!("replication lag {:.0}s", lag));
+        perfdata.push(PerfData::new("replication_lag", lag, "s").thresholds(&db.replication_lag));
+    }
+
+    if let Some(slow) = stats.slow_queries {
+        state = state.max(db.slow_queries.evaluate(slow));
+        summaries.push(format!("{} slow queries", slow));
+        perfdata.push(PerfData::new("slow_queries", slow, "").thresholds(&db.slow_queries));
+    }
+
+    let kind = match db.kind {
+        DatabaseKind::Postgres => "PostgreSQL",
+        DatabaseKind::Mysql => "MySQL",
+        DatabaseKind::Redis => "Redis",
+    };
+    CheckResult::new(
+        &db.name,
+        state,
+        format!("{} {}: {}", kind, db.host, summaries.join(", ")),
+        perfdata,
+    )
+}
@@ -0,0 +1,188 @@
+// Demo module for Crusty-Crawler
+// `--demo` swaps the host's CPU, memory, disk, network and temperature readings for synthetic
+// ones with daily traffic patterns and the occasional threshold breach, so the dashboard,
+// alerting and exporters can be shown without a real workload
+
+static DEMO_SYSTEM: std::sync::OnceLock<Arc<DemoSystem>> = std::sync::OnceLock::new();
+
+const DEMO_CORES: usize = 4;
+const DEMO_MEMORY_BYTES: u64 = 16 * 1024 * 1024 * 1024;
+const DEMO_DISK_BYTES: u64 = 512 * 1024 * 1024 * 1024;
+// On average one CPU spike and one memory leak every this often
+const DEMO_SPIKE_EVERY: Duration = Duration::from_secs(20 * 60);
+const DEMO_LEAK_EVERY: Duration = Duration::from_secs(45 * 60);
+
+struct DemoState {
+    last_sample: Instant,
+    cpu_spike_until: Option<Instant>,
+    leak_started: Option<Instant>,
+    memory_percent: f64,
+    disk_used_bytes: f64,
+    received_bytes: f64,
+    transmitted_bytes: f64,
+}
+
+pub struct DemoSystem {
+    state: Mutex<DemoState>,
+}
+
+// Takes --demo out of the arguments, like --data-dir
+pub fn init_demo_mode(args: &mut Vec<String>) {
+    let before = args.len();
+    args.retain(|arg| arg != "--demo");
+    if args.len() != before {
+        DEMO_SYSTEM.get_or_init(|| Arc::new(DemoSystem::new()));
+        println!("🎭 Demo mode: CPU, memory, disk and network readings are synthetic");
+    }
+}
+
+pub fn demo_system() -> Option<Arc<DemoSystem>> {
+    DEMO_SYSTEM.get().cloned()
+}
+
+// 0.0 at 04:00 local time, 1.0 at 16:00, the shape of office traffic
+fn diurnal_factor() -> f64 {
+    use chrono::Timelike;
+
+    let now = chrono::Local::now();
+    let hours = now.hour() as f64 + now.minute() as f64 / 60.0;
+    0.5 - 0.5 * ((hours - 4.0) / 24.0 * std::f64::consts::TAU).cos()
+}
+
+// Roughly normal noise from the sum of uniform samples
+fn demo_noise(scale: f64) -> f64 {
+    use rand::Rng;
+
+    let mut rng = rand::rng();
+    let sum: f64 = (0..4).map(|_| rng.random_range(-1.0..1.0)).sum();
+    sum / 2.0 * scale
+}
+
+// True with the chance of at least one event in `elapsed` when they come `every` on average
+fn demo_event(elapsed: Duration, every: Duration) -> bool {
+    rand::random::<f64>() < elapsed.as_secs_f64() / every.as_secs_f64()
+}
+
+impl DemoSystem {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(DemoState {
+                last_sample: Instant::now(),
+                cpu_spike_until: None,
+                leak_started: None,
+                memory_percent: 48.0,
+                disk_used_bytes: DEMO_DISK_BYTES as f64 * 0.62,
+                received_bytes: 0.0,
+                transmitted_bytes: 0.0,
+            }),
+        }
+    }
+
+    // Moves the simulation forward to now and returns the current CPU and memory usage
+    fn advance(&self) -> (f64, f64) {
+        use rand::Rng;
+
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last_sample);
+        state.last_sample = now;
+        let load = diurnal_factor();
+
+        if state.cpu_spike_until.is_none_or(|until| until < now)
+            && demo_event(elapsed, DEMO_SPIKE_EVERY)
+        {
+            let length = rand::rng().random_range(90..300);
+            state.cpu_spike_until = Some(now + Duration::from_secs(length));
+        }
+        let cpu = match state.cpu_spike_until {
+            Some(until) if until > now => 93.0 + demo_noise(3.0),
+            _ => 12.0 + 35.0 * load + demo_noise(6.0),
+        };
+
+        // A leak climbs past the memory thresholds over ten minutes, then gets "restarted"
+        if state.leak_started.is_none() && demo_event(elapsed, DEMO_LEAK_EVERY) {
+            state.leak_started = Some(now);
+        }
+        let memory = match state.leak_started {
+            Some(started) if now.duration_since(started) < Duration::from_secs(600) => {
+                let progress = now.duration_since(started).as_secs_f64() / 600.0;
+                state.memory_percent + (96.0 - state.memory_percent) * progress
+            }
+            Some(_) => {
+                state.leak_started = None;
+                state.memory_percent
+            }
+            None => {
+                state.memory_percent = (state.memory_percent + demo_noise(0.5)).clamp(40.0, 65.0);
+                state.memory_percent
+            }
+        };
+
+        // Traffic follows the working day, the disk slowly fills with logs
+        let seconds = elapsed.as_secs_f64();
+        let rate = 4_000_000.0 * (0.1 + load) * (1.0 + demo_noise(0.2)).max(0.0);
+        state.received_bytes += rate * seconds;
+        state.transmitted_bytes += rate * 0.3 * seconds;
+        state.disk_used_bytes =
+            (state.disk_used_bytes + 20_000.0 * seconds).min(DEMO_DISK_BYTES as f64 * 0.97);
+
+        (cpu.clamp(0.0, 100.0), memory)
+    }
+
+    pub fn metrics(&self) -> Vec<Metric> {
+        let (cpu, memory_percent) = self.advance();
+        let state = self.state.lock().unwrap();
+        let memory_used = DEMO_MEMORY_BYTES as f64 * memory_percent / 100.0;
+
+        let mut metrics = vec![Metric::new("cpu_usage_percent", cpu)];
+        for core in 0..DEMO_CORES {
+            metrics.push(
+                Metric::new(
+                    "cpu_core_usage_percent",
+                    (cpu + demo_noise(8.0)).clamp(0.0, 100.0),
+                )
+                .label("cpu", &format!("cpu{}", core)),
+            );
+        }
+        metrics.extend([
+            Metric::new("memory_total_bytes", DEMO_MEMORY_BYTES as f64),
+            Metric::new("memory_used_bytes", memory_used),
+            Metric::new("swap_total_bytes", 4.0 * 1024.0 * 1024.0 * 1024.0),
+            Metric::new("swap_used_bytes", (memory_percent - 80.0).max(0.0) * 1e8),
+            Metric::new("disk_total_bytes", DEMO_DISK_BYTES as f64).label("mount", "/"),
+            Metric::new(
+                "disk_available_bytes",
+                DEMO_DISK_BYTES as f64 - state.disk_used_bytes,
+            )
+            .label("mount", "/"),
+            Metric::new("network_received_bytes_total", state.received_bytes.round())
+                .label("interface", "eth0"),
+            Metric::new(
+                "network_transmitted_bytes_total",
+                state.transmitted_bytes.round(),
+            )
+            .label("interface", "eth0"),
+            Metric::new("component_temperature_celsius", 38.0 + cpu * 0.45)
+                .label("component", "Package id 0"),
+        ]);
+        metrics
+    }
+}
+
+impl SystemInfoProvider for DemoSystem {
+    fn system_name(&self) -> Option<String> {
+        Some("Crusty Demo".to_string())
+    }
+
+    fn cpu_usage(&self) -> f32 {
+        self.advance().0 as f32
+    }
+
+    fn memory(&self) -> (u64, u64) {
+        let memory_percent = self.advance().1;
+        (
+            (DEMO_MEMORY_BYTES as f64 * memory_percent / 100.0) as u64,
+            DEMO_MEMORY_BYTES,
+        )
+    }
+}
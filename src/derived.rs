@@ -0,0 +1,299 @@
+// Derived metrics module for Crusty-Crawler
+// Metrics computed from the collected ones with arithmetic expressions, e.g.
+// "memory_used_bytes / memory_total_bytes * 100". They are exported like any other metric,
+// sampled into the chart history and become checks when given thresholds
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DerivedMetric {
+    pub name: String,
+    // + - * / and parentheses over numbers and metric names. A bare name sums every series of
+    // the metric, disk_total_bytes{mount="/"} picks the ones with those labels
+    pub expression: String,
+    // Makes the metric a check of the same name
+    #[serde(default)]
+    pub thresholds: Option<Thresholds>,
+}
+
+enum DerivedExpr {
+    Number(f64),
+    Metric(String, BTreeMap<String, String>),
+    Negate(Box<DerivedExpr>),
+    Binary(char, Box<DerivedExpr>, Box<DerivedExpr>),
+}
+
+struct ExprParser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl ExprParser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        self.chars.peek().copied()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.chars.next();
+                Ok(())
+            }
+            Some(c) => Err(format!("expected '{}' but found '{}'", expected, c)),
+            None => Err(format!("expected '{}' at the end", expected)),
+        }
+    }
+
+    fn take_while(&mut self, accept: impl Fn(char) -> bool) -> String {
+        let mut taken = String::new();
+        while let Some(c) = self.chars.next_if(|c| accept(*c)) {
+            taken.push(c);
+        }
+        taken
+    }
+
+    fn expression(&mut self) -> Result<DerivedExpr, String> {
+        let mut left = self.term()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.chars.next();
+            left = DerivedExpr::Binary(op, Box::new(left), Box::new(self.term()?));
+        }
+        Ok(left)
+    }
+
+    fn term(&mut self) -> Result<DerivedExpr, String> {
+        let mut left = self.factor()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.chars.next();
+            left = DerivedExpr::Binary(op, Box::new(left), Box::new(self.factor()?));
+        }
+        Ok(left)
+    }
+
+    fn factor(&mut self) -> Result<DerivedExpr, String> {
+        match self.peek() {
+            Some('-') => {
+                self.chars.next();
+                Ok(DerivedExpr::Negate(Box::new(self.factor()?)))
+            }
+            Some('(') => {
+                self.chars.next();
+                let inner = self.expression()?;
+                self.expect(')')?;
+                Ok(inner)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let number = self.take_while(|c| c.is_ascii_digit() || c == '.');
+                number
+                    .parse()
+                    .map(DerivedExpr::Number)
+                    .map_err(|_| format!("invalid number '{}'", number))
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                let labels = if self.peek() == Some('{') {
+                    self.labels()?
+                } else {
+                    BTreeMap::new()
+                };
+                Ok(DerivedExpr::Metric(name, labels))
+            }
+            Some(c) => Err(format!("unexpected '{}'", c)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+
+    // {key="value", ...}
+    fn labels(&mut self) -> Result<BTreeMap<String, String>, String> {
+        self.expect('{')?;
+        let mut labels = BTreeMap::new();
+        while self.peek() != Some('}') {
+            let key = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+            if key.is_empty() {
+                return Err("expected a label name".to_string());
+            }
+            self.expect('=')?;
+            self.expect('"')?;
+            let value = self.take_while(|c| c != '"');
+            self.expect('"')?;
+            labels.insert(key, value);
+            if self.peek() == Some(',') {
+                self.chars.next();
+            }
+        }
+        self.chars.next();
+        Ok(labels)
+    }
+}
+
+impl DerivedExpr {
+    fn parse(expression: &str) -> Result<Self, String> {
+        let mut parser = ExprParser {
+            chars: expression.chars().peekable(),
+        };
+        let parsed = parser.expression()?;
+        match parser.peek() {
+            Some(c) => Err(format!("unexpected '{}'", c)),
+            None => Ok(parsed),
+        }
+    }
+
+    fn evaluate(&self, metrics: &[Metric]) -> Result<f64, String> {
+        match self {
+            DerivedExpr::Number(value) => Ok(*value),
+            DerivedExpr::Metric(name, labels) => {
+                let mut matching = metrics
+                    .iter()
+                    .filter(|metric| {
+                        metric.name == *name
+                            && labels
+                                .iter()
+                                .all(|(key, value)| metric.labels.get(key) == Some(value))
+                    })
+                    .peekable();
+                if matching.peek().is_none() {
+                    return Err(format!("no metric {} to derive from", name));
+                }
+                Ok(matching.map(|metric| metric.value).sum())
+            }
+            DerivedExpr::Negate(inner) => Ok(-inner.evaluate(metrics)?),
+            DerivedExpr::Binary(op, left, right) => {
+                let (left, right) = (left.evaluate(metrics)?, right.evaluate(metrics)?);
+                match op {
+                    '+' => Ok(left + right),
+                    '-' => Ok(left - right),
+                    '*' => Ok(left * right),
+                    _ if right == 0.0 => Err("division by zero".to_string()),
+                    _ => Ok(left / right),
+                }
+            }
+        }
+    }
+}
+
+pub fn validate_derived_metrics(derived: &[DerivedMetric]) -> Result<(), String> {
+    let mut names = Vec::new();
+    for metric in derived {
+        if !valid_metric_name(&metric.name) {
+            return Err(format!(
+                "metrics.derived: invalid name '{}', use letters, digits and underscores",
+                metric.name
+            ));
+        }
+        if names.contains(&metric.name.as_str()) {
+            return Err(format!("metrics.derived: duplicate name '{}'", metric.name));
+        }
+        names.push(metric.name.as_str());
+        DerivedExpr::parse(&metric.expression)
+            .map_err(|e| format!("metrics.derived '{}': {}", metric.name, e))?;
+    }
+    Ok(())
+}
+
+struct DerivedValue {
+    value: Result<f64, String>,
+    thresholds: Option<Thresholds>,
+}
+
+// Result of the latest evaluation of every configured derived metric
+static DERIVED_VALUES: Mutex<BTreeMap<String, DerivedValue>> = Mutex::new(BTreeMap::new());
+
+// Evaluated in config order, so a derived metric can use the ones before it
+pub fn evaluate_derived_metrics(derived: &[DerivedMetric], metrics: &[Metric]) -> Vec<Metric> {
+    if derived.is_empty() {
+        return Vec::new();
+    }
+    let mut all = metrics.to_vec();
+    let mut values = BTreeMap::new();
+    for definition in derived {
+        let value = DerivedExpr::parse(&definition.expression)
+            .and_then(|expression| expression.evaluate(&all))
+            .and_then(|value| {
+                if value.is_finite() {
+                    Ok(value)
+                } else {
+                    Err(format!("{} is not a finite number", definition.name))
+                }
+            });
+        if let Ok(value) = value {
+            all.push(Metric::new(&definition.name, value));
+        }
+        values.insert(
+            definition.name.clone(),
+            DerivedValue {
+                value,
+                thresholds: definition.thresholds,
+            },
+        );
+    }
+    *DERIVED_VALUES.lock().unwrap() = values;
+    all.split_off(metrics.len())
+}
+
+// Derived metrics with thresholds, each is a check
+pub fn derived_checks() -> Vec<String> {
+    DERIVED_VALUES
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, derived)| derived.thresholds.is_some())
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+pub fn check_derived(name: &str) -> Option<CheckResult> {
+    let values = DERIVED_VALUES.lock().unwrap();
+    let derived = values.get(name)?;
+    let thresholds = derived.thresholds?;
+    Some(match &derived.value {
+        Ok(value) => CheckResult::new(
+            name,
+            thresholds.evaluate(*value),
+            format!("{} is {}", name, format_perf_number(*value)),
+            vec![PerfData::new(name, *value, "").thresholds(&thresholds)],
+        ),
+        Err(e) => CheckResult::new(
+            name,
+            CheckState::Unknown,
+            format!("Can't derive {}: {}", name, e),
+            Vec::new(),
+        ),
+    })
+}
+
+// Keeps the derived metrics current for their checks and charts, pollers and exporters
+// evaluate them too when they collect
+fn spawn_derived_metrics(server_state: Arc<Mutex<ServerState>>) {
+    std::thread::spawn(move || {
+        let rt = match Runtime::new() {
+            Ok(rt) => rt,
+            Err(e) => {
+                eprintln!("❌ Failed to start derived metrics: {}", e);
+                return;
+            }
+        };
+
+        rt.block_on(async {
+            loop {
+                let config = {
+                    let state = server_state.lock().unwrap();
+                    let auth_manager = state.auth_manager.lock().unwrap();
+                    auth_manager.config.metrics.clone()
+                };
+                if config.derived.is_empty() {
+                    DERIVED_VALUES.lock().unwrap().clear();
+                } else {
+                    let derived: Vec<(String, f64)> = collect_metrics(&config)
+                        .await
+                        .into_iter()
+                        .filter(|metric| config.derived.iter().any(|d| d.name == metric.name))
+                        .map(|metric| (metric.name, metric.value))
+                        .collect();
+                    push_metric_history(derived);
+                }
+                tokio::time::sleep(collection_interval(Duration::from_secs(
+                    HISTORY_INTERVAL_SECS,
+                )))
+                .await;
+            }
+        });
+    });
+}
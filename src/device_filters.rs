@@ -0,0 +1,95 @@
+// Device filters module for Crusty-Crawler
+// Which disks, network interfaces and components are reported at all. Set once under
+// metrics.devices and applied by the metrics, the checks, the status text and the Checkmk agent
+// output, so pseudo filesystems and container plumbing stay out of every one of them
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct PatternFilter {
+    // Patterns with * wildcards, an empty include list lets everything through that isn't
+    // excluded
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl PatternFilter {
+    fn excluding(patterns: &[&str]) -> Self {
+        Self {
+            include: Vec::new(),
+            exclude: patterns.iter().map(|pattern| pattern.to_string()).collect(),
+        }
+    }
+
+    // A device goes by several names, e.g. a disk's mount point, device and filesystem. It's
+    // kept when any of them is included and none is excluded
+    pub fn allows(&self, names: &[&str]) -> bool {
+        let matches = |patterns: &[String]| {
+            patterns
+                .iter()
+                .any(|pattern| names.iter().any(|name| wildcard_match(pattern, name)))
+        };
+        (self.include.is_empty() || matches(&self.include)) && !matches(&self.exclude)
+    }
+
+    fn validate(&self, field: &str) -> Result<(), String> {
+        if self
+            .include
+            .iter()
+            .chain(&self.exclude)
+            .any(|pattern| pattern.trim().is_empty())
+        {
+            return Err(format!("metrics.devices.{}: empty pattern", field));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct DeviceFilters {
+    // Matched against the mount point, the device name and the filesystem type
+    pub disks: PatternFilter,
+    pub interfaces: PatternFilter,
+    // Matched against the sensor label
+    pub components: PatternFilter,
+}
+
+impl Default for DeviceFilters {
+    fn default() -> Self {
+        Self {
+            disks: PatternFilter::excluding(&[
+                "tmpfs",
+                "devtmpfs",
+                "squashfs",
+                "/snap/*",
+                "/dev/loop*",
+            ]),
+            interfaces: PatternFilter::excluding(&["veth*", "docker0"]),
+            components: PatternFilter::default(),
+        }
+    }
+}
+
+impl DeviceFilters {
+    pub fn allows_disk(&self, disk: &sysinfo::Disk) -> bool {
+        self.disks.allows(&[
+            &disk.mount_point().to_string_lossy(),
+            &disk.name().to_string_lossy(),
+            &disk.file_system().to_string_lossy(),
+        ])
+    }
+
+    pub fn allows_interface(&self, interface: &str) -> bool {
+        self.interfaces.allows(&[interface])
+    }
+
+    pub fn allows_component(&self, label: &str) -> bool {
+        self.components.allows(&[label])
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.disks.validate("disks")?;
+        self.interfaces.validate("interfaces")?;
+        self.components.validate("components")
+    }
+}
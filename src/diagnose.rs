@@ -0,0 +1,221 @@
+// Diagnose module for Crusty-Crawler
+// "What changed?" for incident triage. A small snapshot of process memory, disk use and
+// interface counters is kept every minute for the last hour, /api/diagnose compares the newest
+// one against the one from N minutes ago and lists the biggest movers
+
+const DIAGNOSTIC_INTERVAL: Duration = Duration::from_secs(60);
+// An hour of snapshots at the default interval, plus the one it's compared against
+const DIAGNOSTIC_SNAPSHOTS: usize = 61;
+const DEFAULT_DIAGNOSE_MINUTES: u64 = 15;
+const DEFAULT_DIAGNOSE_TOP: usize = 5;
+
+pub struct DiagnosticSnapshot {
+    pub taken_at: chrono::DateTime<chrono::Utc>,
+    // Resident memory summed by process name, PIDs don't survive restarts
+    pub process_memory: BTreeMap<String, u64>,
+    // Used bytes by mount point
+    pub disk_used: BTreeMap<String, u64>,
+    // Bytes received plus transmitted so far by interface
+    pub interface_bytes: BTreeMap<String, u64>,
+}
+
+#[derive(Deserialize)]
+pub struct DiagnoseQuery {
+    pub minutes: Option<u64>,
+    pub top: Option<usize>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Mover {
+    pub name: String,
+    pub before: f64,
+    pub after: f64,
+    pub change: f64,
+}
+
+// Response of GET /api/diagnose, each list is the top movers, biggest growth first
+#[derive(Serialize)]
+pub struct DiagnoseReport {
+    pub since: String,
+    pub until: String,
+    // Processes by resident memory in bytes
+    pub processes: Vec<Mover>,
+    // Mount points by used bytes
+    pub disks: Vec<Mover>,
+    // Interfaces by throughput in bytes per second
+    pub interfaces: Vec<Mover>,
+}
+
+static DIAGNOSTIC_HISTORY: Mutex<VecDeque<DiagnosticSnapshot>> = Mutex::new(VecDeque::new());
+
+fn take_diagnostic_snapshot(devices: &DeviceFilters) -> DiagnosticSnapshot {
+    let mut sys = sysinfo::System::new();
+    sys.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
+    let mut process_memory = BTreeMap::new();
+    for process in sys.processes().values() {
+        *process_memory
+            .entry(process.name().to_string_lossy().to_string())
+            .or_insert(0) += process.memory();
+    }
+
+    let disk_used = sysinfo::Disks::new_with_refreshed_list()
+        .list()
+        .iter()
+        .filter(|disk| devices.allows_disk(disk))
+        .map(|disk| {
+            (
+                disk.mount_point().to_string_lossy().to_string(),
+                disk.total_space().saturating_sub(disk.available_space()),
+            )
+        })
+        .collect();
+
+    let interface_bytes = sysinfo::Networks::new_with_refreshed_list()
+        .iter()
+        .filter(|(interface, _)| devices.allows_interface(interface))
+        .map(|(interface, data)| {
+            (
+                interface.clone(),
+                data.total_received() + data.total_transmitted(),
+            )
+        })
+        .collect();
+
+    DiagnosticSnapshot {
+        taken_at: chrono::Utc::now(),
+        process_memory,
+        disk_used,
+        interface_bytes,
+    }
+}
+
+// Only growth counts, names that vanished or shrank are left out
+fn biggest_movers(
+    before: &BTreeMap<String, f64>,
+    after: &BTreeMap<String, f64>,
+    top: usize,
+) -> Vec<Mover> {
+    let mut movers: Vec<Mover> = after
+        .iter()
+        .map(|(name, after)| {
+            let before = before.get(name).copied().unwrap_or(0.0);
+            Mover {
+                name: name.clone(),
+                before,
+                after: *after,
+                change: after - before,
+            }
+        })
+        .filter(|mover| mover.change > 0.0)
+        .collect();
+    movers.sort_by(|a, b| b.change.total_cmp(&a.change));
+    movers.truncate(top);
+    movers
+}
+
+fn as_values(values: &BTreeMap<String, u64>) -> BTreeMap<String, f64> {
+    values
+        .iter()
+        .map(|(name, value)| (name.clone(), *value as f64))
+        .collect()
+}
+
+// Bytes per second of each interface between two snapshots
+fn interface_rates(
+    earlier: &DiagnosticSnapshot,
+    later: &DiagnosticSnapshot,
+) -> BTreeMap<String, f64> {
+    let secs = (later.taken_at - earlier.taken_at).num_milliseconds() as f64 / 1000.0;
+    if secs <= 0.0 {
+        return BTreeMap::new();
+    }
+    later
+        .interface_bytes
+        .iter()
+        .filter_map(|(interface, bytes)| {
+            // Counters reset when an interface comes back up
+            let previous = earlier.interface_bytes.get(interface)?;
+            Some((
+                interface.clone(),
+                bytes.saturating_sub(*previous) as f64 / secs,
+            ))
+        })
+        .collect()
+}
+
+// Compares the newest snapshot with the newest one at least `minutes` old, or the oldest kept.
+// Interface throughput is the rate just before each of the two. Oldest snapshot first
+pub fn diagnose(
+    snapshots: &[DiagnosticSnapshot],
+    minutes: u64,
+    top: usize,
+) -> Result<DiagnoseReport, String> {
+    let [.., previous, latest] = snapshots else {
+        return Err("Not enough history yet, snapshots are taken every minute".to_string());
+    };
+    let cutoff = latest.taken_at - chrono::Duration::minutes(minutes as i64);
+    let then = snapshots
+        .iter()
+        .rposition(|snapshot| snapshot.taken_at <= cutoff)
+        .unwrap_or(0)
+        .min(snapshots.len() - 2);
+    let base = &snapshots[then];
+
+    let rate_then = match then.checked_sub(1) {
+        Some(before) => interface_rates(&snapshots[before], base),
+        None => BTreeMap::new(),
+    };
+    Ok(DiagnoseReport {
+        since: base.taken_at.to_rfc3339(),
+        until: latest.taken_at.to_rfc3339(),
+        processes: biggest_movers(
+            &as_values(&base.process_memory),
+            &as_values(&latest.process_memory),
+            top,
+        ),
+        disks: biggest_movers(
+            &as_values(&base.disk_used),
+            &as_values(&latest.disk_used),
+            top,
+        ),
+        interfaces: biggest_movers(&rate_then, &interface_rates(previous, latest), top),
+    })
+}
+
+pub fn diagnose_report(query: &DiagnoseQuery) -> Result<DiagnoseReport, String> {
+    let mut history = DIAGNOSTIC_HISTORY.lock().unwrap();
+    diagnose(
+        history.make_contiguous(),
+        query.minutes.unwrap_or(DEFAULT_DIAGNOSE_MINUTES),
+        query.top.unwrap_or(DEFAULT_DIAGNOSE_TOP).max(1),
+    )
+}
+
+// Returns how many snapshots were removed
+pub fn remove_diagnostic_snapshots_before(cutoff: chrono::DateTime<chrono::Utc>) -> usize {
+    let mut history = DIAGNOSTIC_HISTORY.lock().unwrap();
+    let before = history.len();
+    history.retain(|snapshot| snapshot.taken_at >= cutoff);
+    before - history.len()
+}
+
+fn spawn_diagnostic_snapshots(server_state: Arc<Mutex<ServerState>>) {
+    std::thread::spawn(move || {
+        loop {
+            let devices = {
+                let state = server_state.lock().unwrap();
+                let auth_manager = state.auth_manager.lock().unwrap();
+                auth_manager.config.metrics.devices.clone()
+            };
+            let snapshot = take_diagnostic_snapshot(&devices);
+            {
+                let mut history = DIAGNOSTIC_HISTORY.lock().unwrap();
+                if history.len() == DIAGNOSTIC_SNAPSHOTS {
+                    history.pop_front();
+                }
+                history.push_back(snapshot);
+            }
+            std::thread::sleep(collection_interval(DIAGNOSTIC_INTERVAL));
+        }
+    });
+}
@@ -1,10 +1,10 @@
 use sysinfo::Disks;
 
-async fn check_disks() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+async fn check_disks(devices: &DeviceFilters) -> Result<Vec<String>, Box<dyn std::error::Error>> {
     let disks = Disks::new_with_refreshed_list();
     let mut result = Vec::new();
 
-    for disk in disks.list() {
+    for disk in disks.list().iter().filter(|disk| devices.allows_disk(disk)) {
         let info = format!("{:?}", disk.name());
         result.push(info);
     }
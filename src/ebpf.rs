@@ -0,0 +1,174 @@
+// eBPF probe module for Crusty-Crawler (Linux, `ebpf` cargo feature)
+// Samples block I/O latency and TCP retransmits through bpftrace, which sysinfo can't provide
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct EbpfConfig {
+    pub enabled: bool,
+    // How long each probe run traces the kernel
+    pub window_secs: u64,
+    // Pause between probe runs
+    pub interval_secs: u64,
+}
+
+impl Default for EbpfConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_secs: 5,
+            interval_secs: 60,
+        }
+    }
+}
+
+#[cfg(all(feature = "ebpf", target_os = "linux"))]
+static LATEST_EBPF_METRICS: Mutex<Vec<Metric>> = Mutex::new(Vec::new());
+
+#[cfg(all(feature = "ebpf", target_os = "linux"))]
+fn bpftrace_script(window_secs: u64) -> String {
+    format!(
+        r#"
+tracepoint:block:block_rq_issue {{ @start[args.dev, args.sector] = nsecs; }}
+tracepoint:block:block_rq_complete /@start[args.dev, args.sector]/ {{
+    @disk_usecs = hist((nsecs - @start[args.dev, args.sector]) / 1000);
+    delete(@start[args.dev, args.sector]);
+}}
+tracepoint:tcp:tcp_retransmit_skb {{ @tcp_retransmits = count(); }}
+interval:s:{} {{ exit(); }}
+END {{ clear(@start); }}
+"#,
+        window_secs.max(1)
+    )
+}
+
+// A histogram bucket as printed by `bpftrace -f json`
+#[cfg(all(feature = "ebpf", target_os = "linux"))]
+#[derive(Deserialize)]
+struct HistBucket {
+    #[serde(default)]
+    max: Option<f64>,
+    #[serde(default)]
+    min: Option<f64>,
+    count: u64,
+}
+
+// Upper bound of the bucket containing the given quantile
+#[cfg(all(feature = "ebpf", target_os = "linux"))]
+fn histogram_quantile(buckets: &[HistBucket], quantile: f64) -> Option<f64> {
+    let total: u64 = buckets.iter().map(|b| b.count).sum();
+    if total == 0 {
+        return None;
+    }
+
+    let target = (total as f64 * quantile).ceil() as u64;
+    let mut seen = 0;
+    for bucket in buckets {
+        seen += bucket.count;
+        if seen >= target {
+            return bucket.max.or(bucket.min);
+        }
+    }
+    buckets.last().and_then(|b| b.max.or(b.min))
+}
+
+#[cfg(all(feature = "ebpf", target_os = "linux"))]
+async fn run_probes(config: &EbpfConfig) -> Result<Vec<Metric>, String> {
+    let output = tokio::process::Command::new("bpftrace")
+        .args(["-f", "json", "-e", &bpftrace_script(config.window_secs)])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run bpftrace: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "bpftrace exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let mut metrics = Vec::new();
+    let mut retransmits = 0u64;
+
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Ok(event) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        match event["type"].as_str() {
+            Some("hist") => {
+                let buckets: Vec<HistBucket> =
+                    serde_json::from_value(event["data"]["@disk_usecs"].clone())
+                        .unwrap_or_default();
+                let samples: u64 = buckets.iter().map(|b| b.count).sum();
+                metrics.push(Metric::new("disk_io_latency_samples", samples as f64));
+                for (label, quantile) in [("0.5", 0.5), ("0.95", 0.95), ("0.99", 0.99)] {
+                    if let Some(value) = histogram_quantile(&buckets, quantile) {
+                        metrics.push(
+                            Metric::new("disk_io_latency_microseconds", value)
+                                .label("quantile", label),
+                        );
+                    }
+                }
+            }
+            Some("map") => {
+                if let Some(count) = event["data"]["@tcp_retransmits"].as_u64() {
+                    retransmits = count;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    metrics.push(Metric::new(
+        "tcp_retransmits_per_second",
+        retransmits as f64 / config.window_secs.max(1) as f64,
+    ));
+    Ok(metrics)
+}
+
+// Runs the probes in the background so metric collection never waits on a trace window
+#[cfg(all(feature = "ebpf", target_os = "linux"))]
+fn spawn_ebpf_probes(server_state: Arc<Mutex<ServerState>>) {
+    std::thread::spawn(move || {
+        let rt = match Runtime::new() {
+            Ok(rt) => rt,
+            Err(e) => {
+                eprintln!("❌ Failed to start eBPF probes: {}", e);
+                return;
+            }
+        };
+
+        rt.block_on(async {
+            loop {
+                let config = {
+                    let state = server_state.lock().unwrap();
+                    let auth_manager = state.auth_manager.lock().unwrap();
+                    auth_manager.config.metrics.ebpf.clone()
+                };
+
+                if config.enabled {
+                    match run_probes(&config).await {
+                        Ok(metrics) => *LATEST_EBPF_METRICS.lock().unwrap() = metrics,
+                        Err(e) => eprintln!("❌ eBPF probes failed: {}", e),
+                    }
+                }
+
+                tokio::time::sleep(Duration::from_secs(config.interval_secs.max(5))).await;
+            }
+        });
+    });
+}
+
+#[cfg(not(all(feature = "ebpf", target_os = "linux")))]
+fn spawn_ebpf_probes(_server_state: Arc<Mutex<ServerState>>) {}
+
+// Results of the most recent probe run
+#[cfg(all(feature = "ebpf", target_os = "linux"))]
+pub fn ebpf_metrics() -> Vec<Metric> {
+    LATEST_EBPF_METRICS.lock().unwrap().clone()
+}
+
+#[cfg(not(all(feature = "ebpf", target_os = "linux")))]
+pub fn ebpf_metrics() -> Vec<Metric> {
+    Vec::new()
+}
@@ -0,0 +1,127 @@
+// Email module for Crusty-Crawler
+// Sends mail through the configured SMTP server and keeps the recent failures for diagnostics
+
+#[derive(Clone)]
+pub struct EmailFailure {
+    pub at: String,
+    pub recipient: String,
+    pub error: String,
+}
+
+const MAX_EMAIL_FAILURES: usize = 20;
+
+static EMAIL_FAILURES: Mutex<VecDeque<EmailFailure>> = Mutex::new(VecDeque::new());
+
+fn smtp_transport(config: &SmtpConfig) -> Result<lettre::SmtpTransport, String> {
+    use lettre::transport::smtp::authentication::Credentials;
+
+    let builder = match (config.use_tls, config.port) {
+        // Port 465 expects TLS from the first byte, everything else upgrades with STARTTLS
+        (true, 465) => lettre::SmtpTransport::relay(&config.server),
+        (true, _) => lettre::SmtpTransport::starttls_relay(&config.server),
+        (false, _) => Ok(lettre::SmtpTransport::builder_dangerous(&config.server)),
+    }
+    .map_err(|e| format!("Invalid SMTP server {}: {}", config.server, e))?;
+
+    let mut builder = builder
+        .port(config.port)
+        .timeout(Some(Duration::from_secs(10)));
+    if !config.username.is_empty() {
+        builder = builder.credentials(Credentials::new(
+            config.username.clone(),
+            config.password.clone(),
+        ));
+    }
+    Ok(builder.build())
+}
+
+// Servers that authenticate with a full address usually only accept it as the sender
+fn sender_address(config: &SmtpConfig) -> String {
+    if config.username.contains('@') {
+        config.username.clone()
+    } else {
+        let host = sysinfo::System::host_name().unwrap_or_else(|| "localhost".to_string());
+        format!("crusty@{}", host)
+    }
+}
+
+// Connects, says EHLO, upgrades to TLS and authenticates without sending anything
+pub fn test_smtp_connection(config: &SmtpConfig) -> Result<String, String> {
+    let transport = smtp_transport(config)?;
+    match transport.test_connection() {
+        Ok(true) => {
+            let mut result = format!("Connected to {}:{}", config.server, config.port);
+            if config.use_tls {
+                result.push_str(" over TLS");
+            }
+            if !config.username.is_empty() {
+                result.push_str(&format!(", authenticated as {}", config.username));
+            }
+            Ok(result)
+        }
+        Ok(false) => Err(format!(
+            "{}:{} accepted the connection but did not answer NOOP",
+            config.server, config.port
+        )),
+        Err(e) => {
+            // lettre keeps the server's reply in the error's source
+            let mut message = e.to_string();
+            if let Some(cause) = std::error::Error::source(&e) {
+                message.push_str(&format!(": {}", cause));
+            }
+            Err(message)
+        }
+    }
+}
+
+fn record_email_failure(recipient: &str, error: &str) {
+    let mut failures = EMAIL_FAILURES.lock().unwrap();
+    failures.push_back(EmailFailure {
+        at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        recipient: recipient.to_string(),
+        error: error.to_string(),
+    });
+    while failures.len() > MAX_EMAIL_FAILURES {
+        failures.pop_front();
+    }
+}
+
+pub fn send_email(config: &SmtpConfig, to: &str, subject: &str, body: &str) -> Result<(), String> {
+    use lettre::Transport;
+
+    let result = (|| {
+        let message = lettre::Message::builder()
+            .from(
+                sender_address(config)
+                    .parse()
+                    .map_err(|e| format!("Invalid sender address: {}", e))?,
+            )
+            .to(to
+                .parse()
+                .map_err(|e| format!("Invalid recipient address: {}", e))?)
+            .subject(subject)
+            .body(body.to_string())
+            .map_err(|e| e.to_string())?;
+        smtp_transport(config)?
+            .send(&message)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    })();
+
+    if let Err(e) = &result {
+        eprintln!("❌ Failed to send email to {}: {}", to, e);
+        record_email_failure(to, e);
+    }
+    result
+}
+
+// Newest first
+pub fn recent_email_failures() -> Vec<EmailFailure> {
+    EMAIL_FAILURES
+        .lock()
+        .unwrap()
+        .iter()
+        .rev()
+        .cloned()
+        .collect()
+}
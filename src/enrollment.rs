@@ -0,0 +1,347 @@
+// Enrollment module for Crusty-Crawler
+// Agents join a manager with a one-time join token an admin issued. The agent sends its host
+// metadata and a credential for its own API, and gets a reporting credential and the manager's
+// signing key in return. Its reports are refused until an admin approves the host
+
+const JOIN_TOKEN_LIFETIME_HOURS: i64 = 24;
+const ENROLL_PATH: &str = "/api/central/enroll";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct JoinToken {
+    // hash_access_token of the token, shown only when it's issued
+    pub token_hash: String,
+    pub created_by: String,
+    pub created_at: String,
+    pub expires_at: String,
+}
+
+impl JoinToken {
+    fn expired(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        chrono::DateTime::parse_from_rfc3339(&self.expires_at)
+            .is_ok_and(|expires_at| expires_at <= now)
+    }
+}
+
+#[derive(Serialize)]
+pub struct IssuedJoinToken {
+    pub token: String,
+    pub expires_at: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum EnrollmentStatus {
+    Pending,
+    Approved,
+    Denied,
+}
+
+// What an agent tells the manager about itself when it joins
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+#[serde(default)]
+pub struct HostMetadata {
+    pub os: String,
+    pub kernel: String,
+    pub agent_version: String,
+    // Where the manager reaches the agent's API, from reporter.agent_url
+    pub agent_url: Option<String>,
+}
+
+impl HostMetadata {
+    fn of_this_host(agent_url: &str) -> Self {
+        Self {
+            os: sysinfo::System::long_os_version().unwrap_or_default(),
+            kernel: sysinfo::System::kernel_version().unwrap_or_default(),
+            agent_version: env!("CARGO_PKG_VERSION").to_string(),
+            agent_url: (!agent_url.is_empty()).then(|| agent_url.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct HostEnrollment {
+    pub status: EnrollmentStatus,
+    // hash_access_token of the credential the agent reports with
+    pub credential_hash: String,
+    pub metadata: HostMetadata,
+    pub requested_at: String,
+    pub decided_by: Option<String>,
+    pub decided_at: Option<String>,
+}
+
+// Body of POST /api/central/enroll
+#[derive(Serialize, Deserialize)]
+pub struct EnrollmentRequest {
+    pub join_token: String,
+    pub host: String,
+    pub metadata: HostMetadata,
+    // What the manager pushes fleet settings to the agent with
+    pub agent_token: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EnrollmentResponse {
+    // Goes into reporter.token
+    pub credential: String,
+    // Goes into reporter.manager_public_key
+    pub manager_public_key: String,
+}
+
+impl AuthManager {
+    pub fn create_join_token(&mut self, created_by: &str) -> Result<IssuedJoinToken, String> {
+        let token = AuthManager::generate_suggested_token();
+        let now = self.clock.utc_now();
+        let expires_at = (now + chrono::Duration::hours(JOIN_TOKEN_LIFETIME_HOURS)).to_rfc3339();
+        let join_tokens = &mut self.config.manager.join_tokens;
+        join_tokens.retain(|join_token| !join_token.expired(now));
+        join_tokens.push(JoinToken {
+            token_hash: hash_access_token(&token),
+            created_by: created_by.to_string(),
+            created_at: now.to_rfc3339(),
+            expires_at: expires_at.clone(),
+        });
+        self.save_config().map_err(|e| e.to_string())?;
+        record_event(
+            EventKind::Account,
+            &format!("{} issued a join token", created_by),
+            &format!("Valid until {}", expires_at),
+        );
+        Ok(IssuedJoinToken { token, expires_at })
+    }
+
+    // Uses up the join token, the host stays pending until an admin decides
+    pub fn enroll_host(
+        &mut self,
+        request: &EnrollmentRequest,
+        manager_public_key: String,
+    ) -> Result<EnrollmentResponse, String> {
+        let name = request.host.trim();
+        if name.is_empty() {
+            return Err("The host needs a name".to_string());
+        }
+        if request.agent_token.len() < 8 {
+            return Err("agent_token must be at least 8 characters".to_string());
+        }
+        if let Some(url) = &request.metadata.agent_url
+            && !(url.starts_with("http://") || url.starts_with("https://"))
+        {
+            return Err("agent_url must be an http:// or https:// URL".to_string());
+        }
+        let now = self.clock.utc_now();
+        let position = self
+            .config
+            .manager
+            .join_tokens
+            .iter()
+            .position(|join_token| {
+                verify_access_token(request.join_token.trim(), &join_token.token_hash)
+                    && !join_token.expired(now)
+            })
+            .ok_or("Invalid or expired join token")?;
+        if self.config.manager.host(name).is_some_and(|host| {
+            host.enrollment
+                .as_ref()
+                .is_some_and(|enrollment| enrollment.status == EnrollmentStatus::Approved)
+        }) {
+            return Err(format!("{} is already enrolled", name));
+        }
+
+        self.config.manager.join_tokens.remove(position);
+        let credential = AuthManager::generate_suggested_token();
+        let enrollment = HostEnrollment {
+            status: EnrollmentStatus::Pending,
+            credential_hash: hash_access_token(&credential),
+            metadata: request.metadata.clone(),
+            requested_at: now.to_rfc3339(),
+            decided_by: None,
+            decided_at: None,
+        };
+        let hosts = &mut self.config.manager.hosts;
+        let host = match hosts.iter().position(|host| host.name == name) {
+            Some(index) => &mut hosts[index],
+            None => {
+                hosts.push(ManagedHost {
+                    name: name.to_string(),
+                    ..Default::default()
+                });
+                hosts.last_mut().unwrap()
+            }
+        };
+        host.agent_url = request.metadata.agent_url.clone();
+        host.agent_token = Some(request.agent_token.clone());
+        host.enrollment = Some(enrollment);
+        self.save_config().map_err(|e| e.to_string())?;
+        record_event(
+            EventKind::Account,
+            &format!("{} asked to join the fleet", name),
+            &format!(
+                "{} {}, agent {}",
+                request.metadata.os, request.metadata.kernel, request.metadata.agent_version
+            ),
+        );
+        Ok(EnrollmentResponse {
+            credential,
+            manager_public_key,
+        })
+    }
+
+    // False when the host never enrolled
+    pub fn decide_enrollment(
+        &mut self,
+        name: &str,
+        approve: bool,
+        decided_by: &str,
+    ) -> Result<bool, String> {
+        let now = self.clock.utc_now();
+        let Some(enrollment) = self
+            .config
+            .manager
+            .hosts
+            .iter_mut()
+            .find(|host| host.name == name)
+            .and_then(|host| host.enrollment.as_mut())
+        else {
+            return Ok(false);
+        };
+        enrollment.status = if approve {
+            EnrollmentStatus::Approved
+        } else {
+            EnrollmentStatus::Denied
+        };
+        enrollment.decided_by = Some(decided_by.to_string());
+        enrollment.decided_at = Some(now.to_rfc3339());
+        self.save_config().map_err(|e| e.to_string())?;
+        record_event(
+            EventKind::Account,
+            &format!(
+                "{} {} {}",
+                decided_by,
+                if approve { "approved" } else { "denied" },
+                name
+            ),
+            "",
+        );
+        Ok(true)
+    }
+
+    // The enrolled host `credential` belongs to
+    pub fn credential_host(&self, credential: &str) -> Option<(&str, EnrollmentStatus)> {
+        self.config.manager.hosts.iter().find_map(|host| {
+            let enrollment = host.enrollment.as_ref()?;
+            verify_access_token(credential, &enrollment.credential_hash)
+                .then_some((host.name.as_str(), enrollment.status))
+        })
+    }
+
+    // The agent's side, once the manager accepted its join token
+    pub fn complete_enrollment(
+        &mut self,
+        response: &EnrollmentResponse,
+        agent_token: &str,
+    ) -> Result<(), String> {
+        let reporter = &mut self.config.reporter;
+        reporter.token = response.credential.clone();
+        reporter.manager_public_key = response.manager_public_key.clone();
+        reporter.manager_token_hash = hash_access_token(agent_token);
+        reporter.join_token.clear();
+        let manager_url = reporter.manager_url.clone();
+        self.save_config().map_err(|e| e.to_string())?;
+        record_event(
+            EventKind::ConfigChange,
+            &format!("Enrolled with the manager at {}", manager_url),
+            "Reports are refused until an admin there approves this host",
+        );
+        Ok(())
+    }
+}
+
+// Returns the response and the credential this agent gave the manager for its API
+async fn enroll_with_manager(
+    client: &reqwest::Client,
+    config: &ReporterConfig,
+    host: &str,
+) -> Result<(EnrollmentResponse, String), String> {
+    let agent_token = AuthManager::generate_suggested_token();
+    let request = EnrollmentRequest {
+        join_token: config.join_token.clone(),
+        host: host.to_string(),
+        metadata: HostMetadata::of_this_host(&config.agent_url),
+        agent_token: agent_token.clone(),
+    };
+    let response = client
+        .post(format!(
+            "{}{}",
+            config.manager_url.trim_end_matches('/'),
+            ENROLL_PATH
+        ))
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("{} {}", status, body));
+    }
+    let response = response.json().await.map_err(|e| e.to_string())?;
+    Ok((response, agent_token))
+}
+
+// Who sends a report: a user's access token, or the credential of an approved host
+pub enum ReportingAgent {
+    User(AuthedUser),
+    Host(String),
+}
+
+impl<S: Send + Sync> axum::extract::FromRequestParts<S> for ReportingAgent {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let server_state = parts_server_state(parts)?;
+        let token = parts_token(parts);
+        if let Some(token) = &token {
+            let state = server_state.lock().unwrap();
+            let auth_manager = state.auth_manager.lock().unwrap();
+            match auth_manager.credential_host(token) {
+                Some((host, EnrollmentStatus::Approved)) => {
+                    return Ok(ReportingAgent::Host(host.to_string()));
+                }
+                Some(_) => return Err(AuthError::NotApproved),
+                None => {}
+            }
+        }
+        authenticate(server_state, token, client_ip(parts)).map(ReportingAgent::User)
+    }
+}
+
+// Who may push a configuration bundle to this agent: an admin, or the manager with the
+// credential this agent gave it when enrolling
+pub struct ConfigPusher(pub String);
+
+impl<S: Send + Sync> axum::extract::FromRequestParts<S> for ConfigPusher {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        if let Some(token) = parts_token(parts) {
+            let server_state = parts_server_state(parts)?;
+            let server = server_state.lock().unwrap();
+            let auth_manager = server.auth_manager.lock().unwrap();
+            let reporter = &auth_manager.config.reporter;
+            if !reporter.manager_token_hash.is_empty()
+                && verify_access_token(&token, &reporter.manager_token_hash)
+            {
+                return Ok(ConfigPusher(reporter.manager_url.clone()));
+            }
+        }
+        let AdminUser(user) =
+            <AdminUser as axum::extract::FromRequestParts<S>>::from_request_parts(parts, state)
+                .await?;
+        Ok(ConfigPusher(user.username))
+    }
+}
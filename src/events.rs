@@ -0,0 +1,318 @@
+// Events module for Crusty-Crawler
+// Timeline of alerts, configuration changes, agent restarts and externally posted events,
+// kept next to a short metric history so the dashboard can draw one over the other
+
+const EVENTS_FILE: &str = "crusty_events.json";
+const MAX_EVENTS: usize = 1000;
+// Six hours of samples at the default interval
+const HISTORY_POINTS: usize = 720;
+const HISTORY_INTERVAL_SECS: u64 = 30;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Alert,
+    ConfigChange,
+    AgentStart,
+    AgentStop,
+    External,
+    // Interface changes such as Wi-Fi roaming
+    Network,
+    // New listeners and processes outside the security baseline
+    Security,
+    // Users changing their own password or email
+    Account,
+    // OOM kills and core dumps
+    Crash,
+    // Hardware errors in the kernel log
+    Kernel,
+    // Monitors plugged in or unplugged
+    Device,
+}
+
+impl EventKind {
+    fn parse(value: &str) -> Option<Self> {
+        serde_json::from_value(serde_json::Value::String(value.to_string())).ok()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TimelineEvent {
+    pub id: u64,
+    pub kind: EventKind,
+    pub timestamp: String,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub detail: String,
+    // Who posted an external event, e.g. "deploy" or "ci"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<CheckState>,
+}
+
+// Body of POST /api/events
+#[derive(Deserialize)]
+pub struct ExternalEvent {
+    pub title: String,
+    #[serde(default)]
+    pub detail: String,
+    #[serde(default)]
+    pub source: Option<String>,
+    // RFC 3339, defaults to now. Lets a deploy tool report when it actually ran
+    #[serde(default)]
+    pub timestamp: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct EventFilter {
+    pub since: Option<String>,
+    pub kind: Option<String>,
+}
+
+// Loaded from EVENTS_FILE on first use so restarts keep the timeline
+static EVENTS: Mutex<Option<VecDeque<TimelineEvent>>> = Mutex::new(None);
+static METRIC_HISTORY: Mutex<BTreeMap<String, VecDeque<(String, f64)>>> =
+    Mutex::new(BTreeMap::new());
+
+fn with_events<T>(f: impl FnOnce(&mut VecDeque<TimelineEvent>) -> T) -> T {
+    let mut events = EVENTS.lock().unwrap();
+    let events = events.get_or_insert_with(|| {
+        fs::read_to_string(data_path(EVENTS_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    });
+    f(events)
+}
+
+fn push_event(
+    kind: EventKind,
+    timestamp: String,
+    title: String,
+    detail: String,
+    source: Option<String>,
+    state: Option<CheckState>,
+) {
+    with_events(|events| {
+        let event = TimelineEvent {
+            id: events.iter().map(|e| e.id).max().unwrap_or(0) + 1,
+            kind,
+            timestamp,
+            title,
+            detail,
+            source,
+            state,
+        };
+        if events.len() == MAX_EVENTS {
+            events.pop_front();
+        }
+        // Externally posted events may be backdated, keep the timeline sorted
+        let position = events.partition_point(|e| e.timestamp <= event.timestamp);
+        events.insert(position, event);
+        save_events(events);
+    });
+}
+
+fn save_events(events: &VecDeque<TimelineEvent>) {
+    let saved = serde_json::to_string(events)
+        .map_err(|e| e.to_string())
+        .and_then(|data| fs::write(data_path(EVENTS_FILE), data).map_err(|e| e.to_string()));
+    if let Err(e) = saved {
+        eprintln!(
+            "⚠️  Failed to save event timeline to {}: {}",
+            EVENTS_FILE, e
+        );
+    }
+}
+
+// Returns how many events were removed. Unparseable timestamps count as old
+pub fn remove_events_before(cutoff: chrono::DateTime<chrono::Utc>) -> usize {
+    with_events(|events| {
+        let before = events.len();
+        events.retain(|event| {
+            chrono::DateTime::parse_from_rfc3339(&event.timestamp)
+                .is_ok_and(|timestamp| timestamp >= cutoff)
+        });
+        let removed = before - events.len();
+        if removed > 0 {
+            save_events(events);
+        }
+        removed
+    })
+}
+
+pub fn record_event(kind: EventKind, title: &str, detail: &str) {
+    push_event(
+        kind,
+        chrono::Utc::now().to_rfc3339(),
+        title.to_string(),
+        detail.to_string(),
+        None,
+        None,
+    );
+}
+
+pub fn record_alert_event(alert: &Alert) {
+    let title = if alert.state == CheckState::Ok {
+        format!("{} recovered", alert.check)
+    } else {
+        format!("{} {}", alert.check, alert.state.label())
+    };
+    push_event(
+        EventKind::Alert,
+        chrono::Utc::now().to_rfc3339(),
+        title,
+        alert.message.clone(),
+        None,
+        Some(alert.state),
+    );
+}
+
+pub fn record_external_event(event: ExternalEvent) -> Result<(), String> {
+    let title = event.title.trim();
+    if title.is_empty() {
+        return Err("Event title must not be empty".to_string());
+    }
+    if title.chars().count() > 200 {
+        return Err("Event title is limited to 200 characters".to_string());
+    }
+    let timestamp = match &event.timestamp {
+        Some(timestamp) => chrono::DateTime::parse_from_rfc3339(timestamp)
+            .map_err(|e| format!("Invalid timestamp '{}': {}", timestamp, e))?
+            .with_timezone(&chrono::Utc)
+            .to_rfc3339(),
+        None => chrono::Utc::now().to_rfc3339(),
+    };
+    push_event(
+        EventKind::External,
+        timestamp,
+        title.to_string(),
+        event.detail,
+        event.source,
+        None,
+    );
+    Ok(())
+}
+
+pub fn timeline_events(filter: &EventFilter) -> Result<Vec<TimelineEvent>, String> {
+    let since = match &filter.since {
+        Some(since) => Some(
+            chrono::DateTime::parse_from_rfc3339(since)
+                .map_err(|e| format!("Invalid since '{}': {}", since, e))?,
+        ),
+        None => None,
+    };
+    let kind = match &filter.kind {
+        Some(kind) => Some(EventKind::parse(kind).ok_or(format!("Unknown event kind '{}'", kind))?),
+        None => None,
+    };
+
+    Ok(with_events(|events| {
+        events
+            .iter()
+            .filter(|event| kind.is_none_or(|kind| event.kind == kind))
+            .filter(|event| {
+                since.is_none_or(|since| {
+                    chrono::DateTime::parse_from_rfc3339(&event.timestamp)
+                        .is_ok_and(|timestamp| timestamp >= since)
+                })
+            })
+            .cloned()
+            .collect()
+    }))
+}
+
+// Series name -> (RFC 3339 timestamp, value), oldest first
+pub fn metric_history() -> BTreeMap<String, VecDeque<(String, f64)>> {
+    METRIC_HISTORY.lock().unwrap().clone()
+}
+
+// Returns how many samples were removed, series left empty are dropped
+pub fn remove_metric_history_before(cutoff: chrono::DateTime<chrono::Utc>) -> usize {
+    let mut history = METRIC_HISTORY.lock().unwrap();
+    let mut removed = 0;
+    for series in history.values_mut() {
+        let before = series.len();
+        series.retain(|(timestamp, _)| {
+            chrono::DateTime::parse_from_rfc3339(timestamp)
+                .is_ok_and(|timestamp| timestamp >= cutoff)
+        });
+        removed += before - series.len();
+    }
+    history.retain(|_, series| !series.is_empty());
+    removed
+}
+
+fn sample_metric_history(sys: &mut sysinfo::System) {
+    sys.refresh_cpu_usage();
+    sys.refresh_memory();
+    let percent = |used: u64, total: u64| {
+        if total > 0 {
+            used as f64 / total as f64 * 100.0
+        } else {
+            0.0
+        }
+    };
+    let mut samples = vec![
+        (
+            "cpu_usage_percent".to_string(),
+            sys.global_cpu_usage() as f64,
+        ),
+        (
+            "memory_used_percent".to_string(),
+            percent(sys.used_memory(), sys.total_memory()),
+        ),
+        (
+            "swap_used_percent".to_string(),
+            percent(sys.used_swap(), sys.total_swap()),
+        ),
+        (
+            "load_average_1m".to_string(),
+            sysinfo::System::load_average().one,
+        ),
+    ];
+    // Floods and broadcast storms show up here before anywhere else
+    samples.extend(protocol_packet_rates());
+
+    push_metric_history(samples);
+}
+
+// Appends one sample per series, stamped now
+pub fn push_metric_history(samples: Vec<(String, f64)>) {
+    let now = chrono::Utc::now();
+    record_history_rollups(&samples, now);
+    let now = now.to_rfc3339();
+    let mut history = METRIC_HISTORY.lock().unwrap();
+    for (name, value) in samples {
+        let series = history.entry(name).or_default();
+        if series.len() == HISTORY_POINTS {
+            series.pop_front();
+        }
+        series.push_back((now.clone(), value));
+    }
+}
+
+// Marks the agent start on the timeline and keeps the chart history filled
+fn spawn_event_timeline() {
+    record_event(
+        EventKind::AgentStart,
+        "Agent started",
+        &format!(
+            "PID {}, version {}",
+            std::process::id(),
+            env!("CARGO_PKG_VERSION")
+        ),
+    );
+
+    std::thread::spawn(|| {
+        let mut sys = sysinfo::System::new();
+        // CPU usage needs two refreshes to have something to compare against
+        sys.refresh_cpu_usage();
+        std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+        loop {
+            sample_metric_history(&mut sys);
+            std::thread::sleep(Duration::from_secs(HISTORY_INTERVAL_SECS));
+        }
+    });
+}
@@ -0,0 +1,124 @@
+// Exporter module for Crusty-Crawler
+// Prometheus /metrics endpoint with a configurable name prefix, injected labels and
+// include/exclude filters to keep scrape cardinality down
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PrometheusConfig {
+    pub enabled: bool,
+    // Prepended to every metric name as <prefix>_<name>, empty for none
+    pub prefix: String,
+    // Adds host="<hostname>" to every sample
+    pub host_label: bool,
+    // Extra labels on every sample, e.g. {"env": "prod"}. A metric's own labels win
+    pub labels: BTreeMap<String, String>,
+    // Metric name patterns with * wildcards, matched before the prefix is added.
+    // An empty include list exports everything that isn't excluded
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl Default for PrometheusConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            prefix: "crusty".to_string(),
+            host_label: true,
+            labels: BTreeMap::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+// Glob match where * stands for any run of characters
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard at all
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+// Prometheus names only allow [a-zA-Z0-9_:]
+fn prometheus_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == ':' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+impl PrometheusConfig {
+    fn exports(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| wildcard_match(p, name)))
+            && !self.exclude.iter().any(|p| wildcard_match(p, name))
+    }
+
+    pub fn apply(&self, metrics: Vec<Metric>) -> Vec<Metric> {
+        let mut labels = self.labels.clone();
+        if self.host_label
+            && let Some(host) = sysinfo::System::host_name()
+        {
+            labels.entry("host".to_string()).or_insert(host);
+        }
+
+        metrics
+            .into_iter()
+            .filter(|metric| self.exports(&metric.name))
+            .map(|mut metric| {
+                metric.name = if self.prefix.is_empty() {
+                    prometheus_name(&metric.name)
+                } else {
+                    prometheus_name(&format!("{}_{}", self.prefix, metric.name))
+                };
+                for (key, value) in &labels {
+                    metric
+                        .labels
+                        .entry(key.clone())
+                        .or_insert_with(|| value.clone());
+                }
+                metric
+            })
+            .collect()
+    }
+}
+
+// Text exposition format, samples of one metric have to be grouped under a single TYPE line
+pub fn prometheus_exposition(metrics: &[Metric]) -> String {
+    let mut families: BTreeMap<&str, Vec<&Metric>> = BTreeMap::new();
+    for metric in metrics {
+        families.entry(&metric.name).or_default().push(metric);
+    }
+
+    let mut out = String::new();
+    for (name, samples) in families {
+        let kind = if name.ends_with("_total") {
+            "counter"
+        } else {
+            "gauge"
+        };
+        out.push_str(&format!("# TYPE {} {}\n", name, kind));
+        for sample in samples {
+            out.push_str(&format!("{}\n", sample));
+        }
+    }
+    out
+}
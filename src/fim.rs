@@ -0,0 +1,398 @@
+// File integrity module for Crusty-Crawler
+// Hashes configured files and directories on a schedule and reports what was added, removed or
+// modified since the stored baseline, so tampering with /etc or a web root gets noticed
+
+const FIM_BASELINE_FILE: &str = "crusty_fim_baseline.json";
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct FimConfig {
+    // Files or directories, directories are walked recursively. Empty disables monitoring
+    pub paths: Vec<String>,
+    // Paths containing any of these are skipped, e.g. "/etc/mtab" or ".cache"
+    pub exclude: Vec<String>,
+    pub interval_secs: u64,
+    // Cron expression, overrides interval_secs
+    pub schedule: Option<String>,
+    // Stops a misconfigured path like "/" from hashing the whole disk
+    pub max_files: usize,
+}
+
+impl Default for FimConfig {
+    fn default() -> Self {
+        Self {
+            paths: Vec::new(),
+            exclude: Vec::new(),
+            interval_secs: 60 * 60,
+            schedule: None,
+            max_files: 20_000,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct FileEntry {
+    pub sha256: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FimChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Serialize, Clone)]
+pub struct FimChange {
+    pub path: String,
+    pub change: FimChangeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<FileEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<FileEntry>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct FimBaseline {
+    created_at: String,
+    files: BTreeMap<String, FileEntry>,
+}
+
+#[derive(Clone)]
+struct FimScan {
+    scanned_at: String,
+    files: BTreeMap<String, FileEntry>,
+    truncated: bool,
+    errors: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct FimReport {
+    pub enabled: bool,
+    pub baseline_created_at: Option<String>,
+    pub last_scan_at: Option<String>,
+    pub files: usize,
+    pub truncated: bool,
+    pub errors: Vec<String>,
+    pub changes: Vec<FimChange>,
+}
+
+// Body of POST /api/security/fim/accept, no paths accepts every change
+#[derive(Deserialize, Default)]
+pub struct FimAccept {
+    #[serde(default)]
+    pub paths: Option<Vec<String>>,
+}
+
+static FIM_BASELINE: Mutex<Option<Option<FimBaseline>>> = Mutex::new(None);
+static LAST_FIM_SCAN: Mutex<Option<FimScan>> = Mutex::new(None);
+
+fn with_fim_baseline<T>(f: impl FnOnce(&mut Option<FimBaseline>) -> T) -> T {
+    let mut baseline = FIM_BASELINE.lock().unwrap();
+    let baseline = baseline.get_or_insert_with(|| {
+        fs::read_to_string(data_path(FIM_BASELINE_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+    });
+    f(baseline)
+}
+
+fn save_fim_baseline(baseline: &FimBaseline) -> Result<(), String> {
+    let data = serde_json::to_string_pretty(baseline).map_err(|e| e.to_string())?;
+    fs::write(data_path(FIM_BASELINE_FILE), data)
+        .map_err(|e| format!("Failed to save {}: {}", FIM_BASELINE_FILE, e))
+}
+
+fn hash_file(path: &Path) -> std::io::Result<FileEntry> {
+    use sha2::Digest;
+
+    let mut file = fs::File::open(path)?;
+    let mut hasher = sha2::Sha256::new();
+    let size = std::io::copy(&mut file, &mut hasher)?;
+    let modified = file
+        .metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(|modified| chrono::DateTime::<chrono::Utc>::from(modified).to_rfc3339());
+    Ok(FileEntry {
+        sha256: format!("{:x}", hasher.finalize()),
+        size,
+        modified,
+    })
+}
+
+// Symlinks are not followed, a link into /proc or back up the tree would never end
+fn walk_fim_path(path: &Path, config: &FimConfig, scan: &mut FimScan) {
+    let display = path.to_string_lossy().to_string();
+    if config
+        .exclude
+        .iter()
+        .any(|exclude| display.contains(exclude.as_str()))
+    {
+        return;
+    }
+    if scan.files.len() >= config.max_files {
+        scan.truncated = true;
+        return;
+    }
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            scan.errors.push(format!("{}: {}", display, e));
+            return;
+        }
+    };
+    if metadata.is_dir() {
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                scan.errors.push(format!("{}: {}", display, e));
+                return;
+            }
+        };
+        let mut children: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+        children.sort();
+        for child in children {
+            walk_fim_path(&child, config, scan);
+        }
+    } else if metadata.is_file() {
+        match hash_file(path) {
+            Ok(entry) => {
+                scan.files.insert(display, entry);
+            }
+            Err(e) => scan.errors.push(format!("{}: {}", display, e)),
+        }
+    }
+}
+
+fn scan_fim_paths(config: &FimConfig) -> FimScan {
+    let mut scan = FimScan {
+        scanned_at: chrono::Utc::now().to_rfc3339(),
+        files: BTreeMap::new(),
+        truncated: false,
+        errors: Vec::new(),
+    };
+    for path in &config.paths {
+        walk_fim_path(Path::new(path), config, &mut scan);
+    }
+    scan
+}
+
+fn fim_changes(
+    baseline: &BTreeMap<String, FileEntry>,
+    current: &BTreeMap<String, FileEntry>,
+) -> Vec<FimChange> {
+    let mut changes = Vec::new();
+    for (path, before) in baseline {
+        match current.get(path) {
+            None => changes.push(FimChange {
+                path: path.clone(),
+                change: FimChangeKind::Removed,
+                before: Some(before.clone()),
+                after: None,
+            }),
+            Some(after) if after.sha256 != before.sha256 => changes.push(FimChange {
+                path: path.clone(),
+                change: FimChangeKind::Modified,
+                before: Some(before.clone()),
+                after: Some(after.clone()),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (path, after) in current {
+        if !baseline.contains_key(path) {
+            changes.push(FimChange {
+                path: path.clone(),
+                change: FimChangeKind::Added,
+                before: None,
+                after: Some(after.clone()),
+            });
+        }
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    changes
+}
+
+// Baseline files outside the configured paths or excluded drop out of the comparison, so editing
+// the config doesn't report everything under a path as deleted
+fn current_fim_changes(config: &FimConfig) -> Vec<FimChange> {
+    let Some(scan) = LAST_FIM_SCAN.lock().unwrap().clone() else {
+        return Vec::new();
+    };
+    with_fim_baseline(|baseline| {
+        let Some(baseline) = baseline else {
+            return Vec::new();
+        };
+        let monitored: BTreeMap<String, FileEntry> = baseline
+            .files
+            .iter()
+            .filter(|(path, _)| {
+                config
+                    .paths
+                    .iter()
+                    .any(|root| Path::new(path.as_str()).starts_with(root))
+                    && !config
+                        .exclude
+                        .iter()
+                        .any(|exclude| path.contains(exclude.as_str()))
+            })
+            .map(|(path, entry)| (path.clone(), entry.clone()))
+            .collect();
+        fim_changes(&monitored, &scan.files)
+    })
+}
+
+// Hashes everything and compares it to the baseline. Paths with nothing in the baseline yet,
+// including every path on the first scan, are taken as they are
+pub async fn run_fim_scan(config: &FimConfig) -> Vec<FimChange> {
+    let scan_config = config.clone();
+    let Ok(scan) = tokio::task::spawn_blocking(move || scan_fim_paths(&scan_config)).await else {
+        return Vec::new();
+    };
+
+    let previous = LAST_FIM_SCAN.lock().unwrap().replace(scan.clone());
+    with_fim_baseline(|baseline| {
+        let baseline = baseline.get_or_insert_with(|| FimBaseline {
+            created_at: scan.scanned_at.clone(),
+            files: BTreeMap::new(),
+        });
+        let mut learned = false;
+        for root in &config.paths {
+            let under_root = |path: &String| Path::new(path.as_str()).starts_with(root);
+            if baseline.files.keys().any(under_root) {
+                continue;
+            }
+            for (path, entry) in scan.files.iter().filter(|(path, _)| under_root(path)) {
+                baseline.files.insert(path.clone(), entry.clone());
+                learned = true;
+            }
+        }
+        if learned && let Err(e) = save_fim_baseline(baseline) {
+            eprintln!("⚠️  {}", e);
+        }
+    });
+
+    let changes = current_fim_changes(config);
+    // Only what changed since the previous scan goes on the timeline
+    let newly_changed: Vec<&FimChange> = match &previous {
+        Some(previous) => changes
+            .iter()
+            .filter(|change| previous.files.get(&change.path) != scan.files.get(&change.path))
+            .collect(),
+        None => changes.iter().collect(),
+    };
+    if !newly_changed.is_empty() {
+        let detail: Vec<String> = newly_changed
+            .iter()
+            .map(|change| format!("{:?} {}", change.change, change.path).to_lowercase())
+            .collect();
+        record_event(
+            EventKind::Security,
+            &format!(
+                "{} file(s) changed since the integrity baseline",
+                newly_changed.len()
+            ),
+            &detail.join("\n"),
+        );
+    }
+    changes
+}
+
+pub fn fim_report(config: &FimConfig) -> FimReport {
+    let scan = LAST_FIM_SCAN.lock().unwrap().clone();
+    FimReport {
+        enabled: !config.paths.is_empty(),
+        baseline_created_at: with_fim_baseline(|baseline| {
+            baseline.as_ref().map(|b| b.created_at.clone())
+        }),
+        last_scan_at: scan.as_ref().map(|scan| scan.scanned_at.clone()),
+        files: scan.as_ref().map_or(0, |scan| scan.files.len()),
+        truncated: scan.as_ref().is_some_and(|scan| scan.truncated),
+        errors: scan.map(|scan| scan.errors).unwrap_or_default(),
+        changes: current_fim_changes(config),
+    }
+}
+
+// Takes the last scan's version of the given paths (or all of them) into the baseline
+pub fn accept_fim_changes(config: &FimConfig, paths: Option<&[String]>) -> Result<usize, String> {
+    let scan = LAST_FIM_SCAN
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or("No integrity scan has run yet")?;
+    let changes = current_fim_changes(config);
+    with_fim_baseline(|baseline| {
+        let baseline = baseline
+            .as_mut()
+            .ok_or("No integrity baseline recorded yet")?;
+        let mut accepted = 0;
+        for change in &changes {
+            if paths.is_some_and(|paths| !paths.contains(&change.path)) {
+                continue;
+            }
+            match scan.files.get(&change.path) {
+                Some(entry) => baseline.files.insert(change.path.clone(), entry.clone()),
+                None => baseline.files.remove(&change.path),
+            };
+            accepted += 1;
+        }
+        if accepted > 0 {
+            save_fim_baseline(baseline)?;
+            record_event(
+                EventKind::Security,
+                &format!(
+                    "Accepted {} file change(s) into the integrity baseline",
+                    accepted
+                ),
+                "",
+            );
+        }
+        Ok(accepted)
+    })
+}
+
+fn check_fim(config: &FimConfig) -> CheckResult {
+    if LAST_FIM_SCAN.lock().unwrap().is_none() {
+        return CheckResult::new(
+            "fim",
+            CheckState::Unknown,
+            "No integrity scan has completed yet".to_string(),
+            Vec::new(),
+        );
+    }
+    let changes = current_fim_changes(config);
+    let count = |kind: FimChangeKind| changes.iter().filter(|c| c.change == kind).count();
+    let perfdata = vec![
+        PerfData::new("added", count(FimChangeKind::Added) as f64, ""),
+        PerfData::new("removed", count(FimChangeKind::Removed) as f64, ""),
+        PerfData::new("modified", count(FimChangeKind::Modified) as f64, ""),
+    ];
+    if changes.is_empty() {
+        return CheckResult::new(
+            "fim",
+            CheckState::Ok,
+            "All monitored files match the integrity baseline".to_string(),
+            perfdata,
+        );
+    }
+
+    let mut paths: Vec<&str> = changes.iter().map(|c| c.path.as_str()).take(5).collect();
+    if changes.len() > paths.len() {
+        paths.push("...");
+    }
+    CheckResult::new(
+        "fim",
+        CheckState::Warning,
+        format!(
+            "{} file(s) changed since the baseline: {}",
+            changes.len(),
+            paths.join(", ")
+        ),
+        perfdata,
+    )
+}
@@ -0,0 +1,239 @@
+// Fleet alerts module for Crusty-Crawler
+// In manager mode the checks each host reports, and whether it still reports at all, raise
+// alerts on the manager. They are grouped per host like local alerts, and each incident's
+// notification goes to the contacts of the first of manager.alert_routes matching the host's
+// name, tags or environment, or to every contact when none does
+
+const FLEET_ALERT_INTERVAL_SECS: u64 = 30;
+// The alert a host raises when it stops reporting
+const FLEET_REPORTING_CHECK: &str = "reporting";
+
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+#[serde(default)]
+pub struct AlertRoute {
+    pub name: String,
+    // Host name patterns where * stands for any run of characters, e.g. "db-*"
+    pub hosts: Vec<String>,
+    // Any of them, from manager.hosts
+    pub tags: Vec<String>,
+    pub environments: Vec<String>,
+    // Names from contacts
+    pub contacts: Vec<String>,
+}
+
+impl AlertRoute {
+    // Every list that isn't empty has to match, a route without any matches every host
+    pub fn matches(&self, host: &FleetHost) -> bool {
+        (self.hosts.is_empty()
+            || self
+                .hosts
+                .iter()
+                .any(|pattern| wildcard_match(pattern, &host.name)))
+            && (self.tags.is_empty() || self.tags.iter().any(|tag| host.tags.contains(tag)))
+            && (self.environments.is_empty()
+                || host
+                    .environment
+                    .as_ref()
+                    .is_some_and(|environment| self.environments.contains(environment)))
+    }
+}
+
+// The first route matching `host`
+pub fn route_alert<'a>(routes: &'a [AlertRoute], host: &FleetHost) -> Option<&'a AlertRoute> {
+    routes.iter().find(|route| route.matches(host))
+}
+
+impl ManagerConfig {
+    pub fn validate_alert_routes(&self, contacts: &[Contact]) -> Result<(), String> {
+        let mut names = BTreeSet::new();
+        for route in &self.alert_routes {
+            if route.name.is_empty() {
+                return Err("manager.alert_routes: every route needs a name".to_string());
+            }
+            if !names.insert(&route.name) {
+                return Err(format!(
+                    "manager.alert_routes: duplicate route '{}'",
+                    route.name
+                ));
+            }
+            if route.contacts.is_empty() {
+                return Err(format!(
+                    "manager.alert_routes '{}': needs at least one contact",
+                    route.name
+                ));
+            }
+            if let Some(unknown) = route
+                .contacts
+                .iter()
+                .find(|name| !contacts.iter().any(|contact| &contact.name == *name))
+            {
+                return Err(format!(
+                    "manager.alert_routes '{}': no contact named '{}'",
+                    route.name, unknown
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// A down host's last report is stale, only the missing reports count then
+fn fleet_check_results(host: &FleetHost, offline_after_secs: u64) -> Vec<CheckResult> {
+    let Some(report) = &host.report else {
+        return Vec::new();
+    };
+    if !host.online {
+        let output = format!(
+            "No report for {}s, the last came at {}",
+            offline_after_secs, report.last_report
+        );
+        return vec![CheckResult::new(
+            FLEET_REPORTING_CHECK,
+            CheckState::Critical,
+            output,
+            Vec::new(),
+        )];
+    }
+    let mut results = vec![CheckResult::new(
+        FLEET_REPORTING_CHECK,
+        CheckState::Ok,
+        "Reporting".to_string(),
+        Vec::new(),
+    )];
+    results.extend(report.snapshot.checks.iter().map(|(name, check)| {
+        CheckResult::new(name, check.state, check.output.clone(), Vec::new())
+    }));
+    results
+}
+
+// Feeds the fleet's reports into one alert manager per host and returns the alerts that should
+// page someone, incidents already aggregated. Hosts waiting for approval are left out
+pub fn evaluate_fleet_alerts(
+    alerts: &mut BTreeMap<String, AlertManager>,
+    hosts: &[FleetHost],
+    config: &ManagerConfig,
+    grouping: &AlertGroupingConfig,
+) -> Vec<(FleetHost, Alert)> {
+    let mut notify = Vec::new();
+    for host in hosts {
+        if host
+            .enrollment
+            .is_some_and(|status| status != EnrollmentStatus::Approved)
+        {
+            continue;
+        }
+        let manager = alerts.entry(host.name.clone()).or_default();
+        for result in fleet_check_results(host, config.offline_after_secs) {
+            if let Some(alert) = manager.process_result(&result, grouping)
+                && manager.notifies(&alert)
+            {
+                notify.push((host.clone(), alert));
+            }
+        }
+    }
+    notify
+}
+
+static FLEET_ALERTS: Mutex<BTreeMap<String, AlertManager>> = Mutex::new(BTreeMap::new());
+
+// Entry of GET /api/central/alerts
+#[derive(Serialize, Clone)]
+pub struct FleetAlert {
+    pub host: String,
+    // The route its notifications go to, None for every contact
+    pub route: Option<String>,
+    #[serde(flatten)]
+    pub alert: Alert,
+}
+
+pub fn active_fleet_alerts(config: &ManagerConfig) -> Vec<FleetAlert> {
+    let hosts = reported_fleet_hosts(config);
+    let alerts = FLEET_ALERTS.lock().unwrap();
+    hosts
+        .iter()
+        .filter_map(|host| Some((host, alerts.get(&host.name)?)))
+        .flat_map(|(host, manager)| {
+            let route = route_alert(&config.alert_routes, host).map(|route| route.name.clone());
+            manager.active().into_iter().map(move |alert| FleetAlert {
+                host: host.name.clone(),
+                route: route.clone(),
+                alert,
+            })
+        })
+        .collect()
+}
+
+fn spawn_fleet_alerting(server_state: Arc<Mutex<ServerState>>) {
+    std::thread::spawn(move || {
+        let rt = match Runtime::new() {
+            Ok(rt) => rt,
+            Err(e) => {
+                eprintln!("❌ Failed to start fleet alerting: {}", e);
+                return;
+            }
+        };
+
+        rt.block_on(async {
+            loop {
+                tokio::time::sleep(Duration::from_secs(FLEET_ALERT_INTERVAL_SECS)).await;
+                let (manager, grouping, contacts, settings) = {
+                    let state = server_state.lock().unwrap();
+                    let auth_manager = state.auth_manager.lock().unwrap();
+                    let config = &auth_manager.config;
+                    (
+                        config.manager.clone(),
+                        config.checks.grouping.clone(),
+                        config.contacts.clone(),
+                        NotificationSettings {
+                            templates: config.notification_templates.clone(),
+                            smtp_config: config.smtp_config.clone(),
+                            on_call: config.on_call.clone(),
+                            throttle: config.notification_throttle.clone(),
+                        },
+                    )
+                };
+                if !manager.enabled {
+                    continue;
+                }
+
+                let hosts = reported_fleet_hosts(&manager);
+                let notify = evaluate_fleet_alerts(
+                    &mut FLEET_ALERTS.lock().unwrap(),
+                    &hosts,
+                    &manager,
+                    &grouping,
+                );
+                // A standby keeps its alerts up to date but leaves paging to the primary
+                if !manager_active() {
+                    continue;
+                }
+                for (host, alert) in notify {
+                    let (route, recipients) = match route_alert(&manager.alert_routes, &host) {
+                        Some(route) => (
+                            route.name.as_str(),
+                            contacts
+                                .iter()
+                                .filter(|contact| route.contacts.contains(&contact.name))
+                                .cloned()
+                                .collect(),
+                        ),
+                        None => ("every contact", contacts.clone()),
+                    };
+                    println!(
+                        "📣 {} {} on {}, notifying {}",
+                        alert.check,
+                        alert.state.label(),
+                        host.name,
+                        route
+                    );
+                    tokio::spawn(dispatch_host_notifications(
+                        recipients,
+                        settings.clone(),
+                        alert,
+                        host.name,
+                    ));
+                }
+            }
+        });
+    });
+}
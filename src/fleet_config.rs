@@ -0,0 +1,240 @@
+// Fleet config module for Crusty-Crawler
+// Single-pane configuration for a small fleet: the manager keeps the checks, thresholds and
+// notification settings its agents should run in manager.fleet_settings, signs them with its own
+// key and pushes them to each agent's admin API. Agents only apply bundles signed by the key in
+// reporter.manager_public_key, and never one older than what they already have
+
+use p256::ecdsa::signature::Verifier as _;
+
+// The manager's signing key, only readable by the agent's user
+const MANAGER_KEY_FILE: &str = "crusty_manager_key";
+const CONFIG_BUNDLE_PATH: &str = "/api/central/config";
+
+// Sections left out stay as the agent has them
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct FleetSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checks: Option<CheckConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contacts: Option<Vec<Contact>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_call: Option<OnCallConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notification_templates: Option<NotificationTemplates>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notification_throttle: Option<NotificationThrottleConfig>,
+}
+
+impl FleetSettings {
+    pub fn is_empty(&self) -> bool {
+        self.checks.is_none()
+            && self.contacts.is_none()
+            && self.on_call.is_none()
+            && self.notification_templates.is_none()
+            && self.notification_throttle.is_none()
+    }
+
+    pub fn apply_to(&self, config: &mut AuthConfig) {
+        if let Some(checks) = &self.checks {
+            config.checks = checks.clone();
+        }
+        if let Some(contacts) = &self.contacts {
+            config.contacts = contacts.clone();
+        }
+        if let Some(on_call) = &self.on_call {
+            config.on_call = on_call.clone();
+        }
+        if let Some(templates) = &self.notification_templates {
+            config.notification_templates = templates.clone();
+        }
+        if let Some(throttle) = &self.notification_throttle {
+            config.notification_throttle = throttle.clone();
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ConfigBundle {
+    // Milliseconds since the epoch when it was signed, agents refuse anything not newer
+    pub version: u64,
+    pub issued_at: String,
+    pub settings: FleetSettings,
+}
+
+// Body of PUT /api/central/config. The bundle stays the exact JSON that was signed
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SignedConfigBundle {
+    pub bundle: String,
+    // Base64url ECDSA P-256 signature (r || s) of `bundle`
+    pub signature: String,
+}
+
+// What one agent made of a push
+#[derive(Serialize, Clone, Debug)]
+pub struct ConfigPushResult {
+    pub host: String,
+    // Settings that changed, empty when the agent already had them
+    pub changes: Vec<String>,
+    pub error: Option<String>,
+}
+
+pub fn sign_config_bundle(
+    key: &p256::SecretKey,
+    bundle: &ConfigBundle,
+) -> Result<SignedConfigBundle, String> {
+    let bundle = serde_json::to_string(bundle).map_err(|e| e.to_string())?;
+    let signature: p256::ecdsa::Signature =
+        p256::ecdsa::SigningKey::from(key).sign(bundle.as_bytes());
+    Ok(SignedConfigBundle {
+        bundle,
+        signature: base64url(&signature.to_bytes()),
+    })
+}
+
+// `public_key` is the manager's, base64url as GET /api/central/config/key returns it
+pub fn verify_config_bundle(
+    public_key: &str,
+    signed: &SignedConfigBundle,
+) -> Result<ConfigBundle, String> {
+    let key = p256::ecdsa::VerifyingKey::from_sec1_bytes(&base64url_decode(public_key)?)
+        .map_err(|_| "reporter.manager_public_key is not a P-256 public key".to_string())?;
+    let signature = p256::ecdsa::Signature::from_slice(&base64url_decode(&signed.signature)?)
+        .map_err(|_| "Malformed bundle signature".to_string())?;
+    key.verify(signed.bundle.as_bytes(), &signature)
+        .map_err(|_| "The bundle isn't signed by the manager".to_string())?;
+    serde_json::from_str(&signed.bundle).map_err(|e| e.to_string())
+}
+
+// Created on first use, like the VAPID key
+fn manager_signing_key() -> Result<p256::SecretKey, String> {
+    let path = data_path(MANAGER_KEY_FILE);
+    if let Ok(data) = fs::read_to_string(&path) {
+        return p256::SecretKey::from_slice(&base64url_decode(data.trim())?)
+            .map_err(|_| format!("Invalid signing key in {}", MANAGER_KEY_FILE));
+    }
+    let key = random_secret_key();
+    // Owner-only from the moment it exists, never readable by others in between
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(&path)
+        .and_then(|mut file| file.write_all(base64url(&key.to_bytes()).as_bytes()))
+        .map_err(|e| format!("Failed to write {}: {}", MANAGER_KEY_FILE, e))?;
+    Ok(key)
+}
+
+// What agents put in reporter.manager_public_key
+pub fn manager_public_key() -> Result<String, String> {
+    Ok(base64url(&public_key_bytes(&manager_signing_key()?)))
+}
+
+async fn send_config_bundle(
+    client: &reqwest::Client,
+    host: &ManagedHost,
+    signed: &SignedConfigBundle,
+) -> Result<Vec<String>, String> {
+    let (Some(url), Some(token)) = (&host.agent_url, &host.agent_token) else {
+        return Err("No agent_url and agent_token configured".to_string());
+    };
+    let response = client
+        .put(format!(
+            "{}{}",
+            url.trim_end_matches('/'),
+            CONFIG_BUNDLE_PATH
+        ))
+        .bearer_auth(token)
+        .json(signed)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("{} {}", status, body));
+    }
+    response.json().await.map_err(|e| e.to_string())
+}
+
+// Signs manager.fleet_settings once and sends it to every host with an agent_url
+pub async fn push_fleet_settings(config: &ManagerConfig) -> Result<Vec<ConfigPushResult>, String> {
+    if config.fleet_settings.is_empty() {
+        return Err("manager.fleet_settings is empty, there is nothing to push".to_string());
+    }
+    let now = chrono::Utc::now();
+    let bundle = ConfigBundle {
+        version: now.timestamp_millis() as u64,
+        issued_at: now.to_rfc3339(),
+        settings: config.fleet_settings.clone(),
+    };
+    let signed = sign_config_bundle(&manager_signing_key()?, &bundle)?;
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .unwrap_or_default();
+    let mut results = Vec::new();
+    for host in config.hosts.iter().filter(|host| host.agent_url.is_some()) {
+        let (changes, error) = match send_config_bundle(&client, host, &signed).await {
+            Ok(changes) => (changes, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+        results.push(ConfigPushResult {
+            host: host.name.clone(),
+            changes,
+            error,
+        });
+    }
+    Ok(results)
+}
+
+impl AuthManager {
+    // The agent's side of a push, returns the settings that changed
+    pub fn apply_config_bundle(
+        &mut self,
+        signed: &SignedConfigBundle,
+        pushed_by: &str,
+    ) -> Result<Vec<String>, String> {
+        let public_key = &self.config.reporter.manager_public_key;
+        if public_key.is_empty() {
+            return Err(
+                "reporter.manager_public_key isn't set, this agent doesn't take configuration from a manager"
+                    .to_string(),
+            );
+        }
+        let bundle = verify_config_bundle(public_key, signed)?;
+        if bundle.version <= self.config.reporter.applied_config_version {
+            return Err(format!(
+                "Bundle {} isn't newer than the applied {}",
+                bundle.version, self.config.reporter.applied_config_version
+            ));
+        }
+
+        let mut config = self.config.clone();
+        bundle.settings.apply_to(&mut config);
+        config.reporter.applied_config_version = bundle.version;
+        config.validate()?;
+        let changes: Vec<String> = config_diff(&self.config, &config)
+            .into_iter()
+            .filter(|change| !change.starts_with("reporter.applied_config_version"))
+            .collect();
+        self.config = config;
+        self.save_config().map_err(|e| e.to_string())?;
+        record_event(
+            EventKind::ConfigChange,
+            &format!(
+                "{} pushed configuration bundle {}, {} change(s)",
+                pushed_by,
+                bundle.version,
+                changes.len()
+            ),
+            &changes.join("\n"),
+        );
+        Ok(changes)
+    }
+}
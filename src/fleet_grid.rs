@@ -0,0 +1,94 @@
+// Fleet grid module for Crusty-Crawler
+// The manager's wallboard: one tile per host colored by its worst check state, filtered by the
+// environment and tags from manager.hosts. /fleet renders it, /api/central/grid serves the same
+// data to custom wallboards
+
+// Query of /api/central/grid and /fleet
+#[derive(Deserialize, Default)]
+pub struct FleetGridQuery {
+    pub environment: Option<String>,
+    pub tag: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FleetTile {
+    pub name: String,
+    // UNKNOWN while the host is down
+    pub state: CheckState,
+    pub online: bool,
+    pub environment: Option<String>,
+    pub tags: Vec<String>,
+    // Checks that aren't OK, worst first
+    pub failing: Vec<String>,
+    pub last_report: Option<String>,
+    // The host's own dashboard, from its agent_url
+    pub dashboard_url: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FleetGrid {
+    pub tiles: Vec<FleetTile>,
+    // Every environment and tag in the fleet, for the filters
+    pub environments: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+fn fleet_tile(host: &FleetHost) -> FleetTile {
+    let mut failing: Vec<(&String, CheckState)> = match &host.report {
+        Some(report) if host.online => report
+            .snapshot
+            .checks
+            .iter()
+            .filter(|(_, check)| check.state != CheckState::Ok)
+            .map(|(name, check)| (name, check.state))
+            .collect(),
+        _ => Vec::new(),
+    };
+    failing.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    FleetTile {
+        name: host.name.clone(),
+        state: host.overall,
+        online: host.online,
+        environment: host.environment.clone(),
+        tags: host.tags.clone(),
+        failing: failing.into_iter().map(|(name, _)| name.clone()).collect(),
+        last_report: host
+            .report
+            .as_ref()
+            .map(|report| report.last_report.clone()),
+        dashboard_url: host.agent_url.clone(),
+    }
+}
+
+// Hosts waiting for approval or denied aren't part of the fleet yet
+pub fn fleet_grid(hosts: &[FleetHost], query: &FleetGridQuery) -> FleetGrid {
+    let hosts: Vec<&FleetHost> = hosts
+        .iter()
+        .filter(|host| {
+            host.enrollment
+                .is_none_or(|status| status == EnrollmentStatus::Approved)
+        })
+        .collect();
+    let environments: BTreeSet<String> = hosts
+        .iter()
+        .filter_map(|host| host.environment.clone())
+        .collect();
+    let tags: BTreeSet<String> = hosts
+        .iter()
+        .flat_map(|host| host.tags.iter().cloned())
+        .collect();
+    // An empty filter from the page's "All" option matches every host
+    let environment = query.environment.as_ref().filter(|value| !value.is_empty());
+    let tag = query.tag.as_ref().filter(|value| !value.is_empty());
+    let tiles = hosts
+        .into_iter()
+        .filter(|host| environment.is_none_or(|value| host.environment.as_ref() == Some(value)))
+        .filter(|host| tag.is_none_or(|value| host.tags.contains(value)))
+        .map(fleet_tile)
+        .collect();
+    FleetGrid {
+        tiles,
+        environments: environments.into_iter().collect(),
+        tags: tags.into_iter().collect(),
+    }
+}
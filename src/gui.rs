@@ -0,0 +1,1215 @@
+// GUI module for Crusty-Crawler
+// The eframe desktop app: setup, login, recovery and the main window. Only built with the
+// `gui` feature, headless builds run the CLI instead
+
+use eframe::egui;
+
+enum AppState {
+    Setup(SetupState),
+    Login(LoginState),
+    Main(MainState),
+    Recovery(RecoveryState),
+    PasswordExpired(PasswordExpiredState),
+}
+
+struct SetupState {
+    username: String,
+    password: String,
+    confirm_password: String,
+    email: String,
+    access_token: String,
+    error_message: String,
+    show_token_suggestion: bool,
+}
+
+struct LoginState {
+    username: String,
+    password: String,
+    email: String,
+    error_message: String,
+    show_recovery: bool,
+    // The code being typed in, Some once the recovery mail went out
+    recovery_code: Option<String>,
+    remember_me: bool,
+}
+
+// Shown after a login with a password older than the policy allows
+struct PasswordExpiredState {
+    username: String,
+    current_password: String,
+    new_password: String,
+    confirm_password: String,
+    remember_me: bool,
+    error_message: String,
+}
+
+struct RecoveryState {
+    email: String,
+    // The code being typed in, Some once the recovery mail went out
+    code: Option<String>,
+    message: String,
+    is_success: bool,
+}
+
+struct SmtpConfigState {
+    server: String,
+    port: String,
+    username: String,
+    password: String,
+    use_tls: bool,
+    message: String,
+    testing: bool,
+    // Filled in by the connection test thread
+    test_result: Arc<Mutex<Option<Result<String, String>>>>,
+}
+
+#[derive(PartialEq)]
+enum MainView {
+    Dashboard,
+    Alerts,
+    Settings,
+}
+
+struct Toast {
+    message: String,
+    created_at: Instant,
+}
+
+const TOAST_DURATION: Duration = Duration::from_secs(8);
+
+struct MainState {
+    port_input: String,
+    server_state: Arc<Mutex<ServerState>>,
+    status_message: String,
+    current_user: String,
+    view: MainView,
+    toasts: Vec<Toast>,
+    settings: Box<SettingsState>,
+    last_activity: Instant,
+    locked: bool,
+    unlock_password: String,
+    unlock_error: String,
+    qr_link: Option<QrLink>,
+}
+
+impl MainState {
+    fn new(server_state: Arc<Mutex<ServerState>>, current_user: String) -> Self {
+        let settings = Box::new(SettingsState::load(&server_state));
+        Self {
+            port_input: "3000".to_string(),
+            server_state,
+            status_message: String::new(),
+            current_user,
+            view: MainView::Dashboard,
+            toasts: Vec::new(),
+            settings,
+            last_activity: Instant::now(),
+            locked: false,
+            unlock_password: String::new(),
+            unlock_error: String::new(),
+            qr_link: None,
+        }
+    }
+
+    fn start_server(&mut self) {
+        let port = match self.port_input.parse::<u16>() {
+            Ok(p) => p,
+            Err(_) => {
+                self.status_message = format!("Invalid port number: {}", self.port_input);
+                return;
+            }
+        };
+
+        let server_state = self.server_state.clone();
+
+        {
+            let state = server_state.lock().unwrap();
+            if state.is_running {
+                self.status_message = "Server is already running!".to_string();
+                return;
+            }
+        }
+
+        // Creates a new runtime for the server
+        let rt = Runtime::new().unwrap();
+
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+        {
+            let mut state = server_state.lock().unwrap();
+            state.is_running = true;
+            state.port = port;
+            state.shutdown_sender = Some(shutdown_tx);
+        }
+
+        let server_state_clone = server_state.clone();
+
+        // Spawn the server in a separate thread
+        std::thread::spawn(move || {
+            rt.block_on(async {
+                let app = create_app(server_state_clone.clone());
+                let http_config = http_config(&server_state_clone);
+                let addr = SocketAddr::from((std::net::Ipv6Addr::UNSPECIFIED, port));
+
+                println!("🚀 Server starting on port {}", port);
+
+                let listener = bind_tcp_listener(addr);
+                match listener {
+                    Ok(listener) => {
+                        println!("✅ Server running on port {}", port);
+                        print_lan_urls(port);
+
+                        let server = serve_app(listener, app, http_config);
+
+                        tokio::select! {
+                            result = server => match result {
+                                Ok(()) => println!("Server stopped normally"),
+                                Err(e) => eprintln!("❌ Server stopped: {}", e),
+                            },
+                            _ = shutdown_rx => {
+                                println!("Server received shutdown signal");
+                            }
+                        };
+                    }
+                    Err(e) => {
+                        eprintln!("❌ Failed to bind to port {}: {}", port, e);
+                        let mut state = server_state_clone.lock().unwrap();
+                        state.is_running = false;
+                    }
+                }
+
+                let mut state = server_state_clone.lock().unwrap();
+                state.is_running = false;
+                state.shutdown_sender = None;
+            });
+        });
+        self.status_message = format!(
+            "✅ Server hosted on port {} (accessible from any device)",
+            port
+        );
+    }
+
+    fn stop_server(&mut self) {
+        let shutdown_sender = {
+            let mut state = self.server_state.lock().unwrap();
+            state.shutdown_sender.take()
+        };
+
+        if let Some(sender) = shutdown_sender {
+            // Send shutdown signal - ignore error if receiver is dropped
+            let _ = sender.send(());
+            self.status_message = "🛑 Server shutdown initiated...".to_string();
+        } else {
+            self.status_message = "❌ Server is not running".to_string();
+        }
+
+        // Immediately mark as not running for UI responsiveness
+        {
+            let mut state = self.server_state.lock().unwrap();
+            state.is_running = false;
+        }
+    }
+
+    // Turn newly raised critical alerts into toasts and drop expired ones
+    fn update_toasts(&mut self) {
+        let new_critical = {
+            let state = self.server_state.lock().unwrap();
+            let mut alert_manager = state.alert_manager.lock().unwrap();
+            alert_manager.take_new_critical()
+        };
+
+        for alert in new_critical {
+            self.toasts.push(Toast {
+                message: format!("🚨 {} is CRITICAL: {}", alert.check, alert.message),
+                created_at: Instant::now(),
+            });
+        }
+
+        self.toasts
+            .retain(|toast| toast.created_at.elapsed() < TOAST_DURATION);
+    }
+
+    fn show_toasts(&self, ctx: &egui::Context) {
+        if self.toasts.is_empty() {
+            return;
+        }
+
+        egui::Area::new(egui::Id::new("alert_toasts"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                for toast in &self.toasts {
+                    egui::Frame::popup(ui.style())
+                        .fill(egui::Color32::from_rgb(120, 20, 20))
+                        .inner_margin(egui::Margin::same(8))
+                        .show(ui, |ui| {
+                            ui.colored_label(egui::Color32::WHITE, &toast.message);
+                        });
+                    ui.add_space(4.0);
+                }
+            });
+    }
+
+    fn show_alerts(&mut self, ui: &mut egui::Ui) {
+        let alert_manager = self.server_state.lock().unwrap().alert_manager.clone();
+        let (active, recent) = {
+            let alert_manager = alert_manager.lock().unwrap();
+            (alert_manager.active(), alert_manager.recent())
+        };
+
+        ui.heading("🔔 Active Alerts");
+        egui::Frame::group(ui.style())
+            .inner_margin(egui::Margin::same(10))
+            .show(ui, |ui| {
+                if active.is_empty() {
+                    ui.colored_label(egui::Color32::GREEN, "✅ No active alerts");
+                }
+
+                for alert in &active {
+                    ui.horizontal(|ui| {
+                        ui.colored_label(state_color(alert.state), alert.state.label());
+                        ui.strong(&alert.check);
+                        ui.label(&alert.message);
+                        if let Some(parent) = active.iter().find(|a| Some(a.id) == alert.parent_id)
+                        {
+                            ui.small(format!("↳ grouped with {}", parent.check));
+                        }
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if let Some(user) = &alert.acknowledged_by {
+                                ui.colored_label(
+                                    egui::Color32::GRAY,
+                                    format!("✔ Acknowledged by {}", user),
+                                );
+                            } else if ui.button("✔ Acknowledge").clicked() {
+                                let result = alert_manager
+                                    .lock()
+                                    .unwrap()
+                                    .acknowledge(alert.id, &self.current_user);
+                                if let Err(e) = result {
+                                    self.status_message = e;
+                                }
+                            }
+                            ui.small(format!("since {}", format_timestamp(&alert.raised_at)));
+                        });
+                    });
+                }
+            });
+
+        ui.add_space(10.0);
+        ui.heading("🕘 Recent Alerts");
+        egui::Frame::group(ui.style())
+            .inner_margin(egui::Margin::same(10))
+            .show(ui, |ui| {
+                if recent.is_empty() {
+                    ui.label("No resolved alerts yet");
+                }
+
+                egui::ScrollArea::vertical()
+                    .max_height(250.0)
+                    .show(ui, |ui| {
+                        for alert in &recent {
+                            ui.horizontal(|ui| {
+                                ui.colored_label(state_color(alert.state), alert.state.label());
+                                ui.strong(&alert.check);
+                                ui.label(&alert.message);
+                                if let Some(resolved_at) = &alert.resolved_at {
+                                    ui.small(format!(
+                                        "{} → resolved {}",
+                                        format_timestamp(&alert.raised_at),
+                                        format_timestamp(resolved_at)
+                                    ));
+                                }
+                            });
+                        }
+                    });
+            });
+
+        let findings = latest_security_findings();
+        ui.add_space(10.0);
+        ui.heading("🛡 Security Baseline");
+        egui::Frame::group(ui.style())
+            .inner_margin(egui::Margin::same(10))
+            .show(ui, |ui| {
+                if findings.is_empty() {
+                    ui.colored_label(
+                        egui::Color32::GREEN,
+                        "✅ Nothing outside the security baseline",
+                    );
+                }
+
+                for finding in &findings {
+                    ui.horizontal(|ui| {
+                        ui.colored_label(egui::Color32::YELLOW, finding.kind.label());
+                        ui.strong(&finding.key);
+                        ui.label(&finding.detail);
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui.button("✔ Accept into baseline").clicked() {
+                                self.status_message =
+                                    match accept_security_finding(finding.kind, &finding.key) {
+                                        Ok(()) => format!("{} accepted into baseline", finding.key),
+                                        Err(e) => e,
+                                    };
+                            }
+                        });
+                    });
+                }
+            });
+    }
+}
+
+fn state_color(state: CheckState) -> egui::Color32 {
+    match state {
+        CheckState::Ok => egui::Color32::GREEN,
+        CheckState::Warning => egui::Color32::YELLOW,
+        CheckState::Critical => egui::Color32::RED,
+        CheckState::Unknown => egui::Color32::from_rgb(255, 140, 0),
+    }
+}
+
+struct MyApp {
+    app_state: AppState,
+    server_state: Arc<Mutex<ServerState>>,
+    // Remove these duplicate fields since they're in MainState:
+    // port_input: String,
+    // status_message: String,
+}
+
+impl Default for MyApp {
+    fn default() -> Self {
+        let auth_manager = AuthManager::new(&data_file(AUTH_CONFIG_FILE))
+            .unwrap_or_else(|_| AuthManager::new(&data_file(AUTH_CONFIG_FILE)).unwrap());
+
+        let has_users = auth_manager.has_users();
+        // An expired password has to be changed at the login screen first
+        let remembered_user = if has_users {
+            remembered_login(&auth_manager)
+                .filter(|username| !auth_manager.password_expired(username))
+        } else {
+            None
+        };
+        if let Some(username) = &remembered_user {
+            record_login(Some(username), LoginMethod::RememberMe, None, None);
+        }
+
+        let initial_state = if !has_users {
+            AppState::Setup(SetupState {
+                username: String::new(),
+                password: String::new(),
+                confirm_password: String::new(),
+                email: String::new(),
+                access_token: String::new(),
+                error_message: String::new(),
+                show_token_suggestion: true,
+            })
+        } else {
+            AppState::Login(LoginState {
+                username: String::new(),
+                password: String::new(),
+                email: String::new(),
+                error_message: String::new(),
+                show_recovery: false,
+                recovery_code: None,
+                remember_me: false,
+            })
+        };
+
+        let server_state = Arc::new(Mutex::new(ServerState::default()));
+        init_platform_capabilities();
+        spawn_check_loop(server_state.clone());
+        spawn_ebpf_probes(server_state.clone());
+        spawn_scheduler(server_state.clone());
+        spawn_dashboard_stream(server_state.clone());
+        spawn_central_reporter(server_state.clone());
+        spawn_cluster_sync(server_state.clone());
+        spawn_fleet_alerting(server_state.clone());
+        spawn_derived_metrics(server_state.clone());
+        spawn_load_monitor(server_state.clone());
+        spawn_config_watcher(server_state.clone());
+        spawn_otlp_exporter(server_state.clone());
+        spawn_statsd_listener(server_state.clone());
+        spawn_zabbix_sender(server_state.clone());
+        spawn_checkmk_listener(server_state.clone());
+        spawn_event_timeline();
+        spawn_diagnostic_snapshots(server_state.clone());
+        spawn_mdns_advertiser(server_state.clone());
+        spawn_crash_monitor(server_state.clone());
+        spawn_kernel_log_scanner(server_state.clone());
+
+        let app_state = match remembered_user {
+            Some(username) => AppState::Main(MainState::new(server_state.clone(), username)),
+            None => initial_state,
+        };
+
+        Self {
+            app_state,
+            server_state,
+            // Remove these:
+            // status_message: String::new(),
+            // port_input: String::new(),
+        }
+    }
+}
+
+enum AppAction {
+    None,
+    SwitchToLogin(LoginState),
+    SwitchToMain(MainState),
+    SwitchToRecovery,
+    SwitchToPasswordExpired(PasswordExpiredState),
+}
+
+impl eframe::App for MyApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let mut action = AppAction::None;
+        if let AppState::Main(main_state) = &mut self.app_state
+            && main_state.check_idle(ctx)
+        {
+            main_state.show_lock_screen(ctx);
+            return;
+        }
+        match &mut self.app_state {
+            AppState::Setup(setup_state) => {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.heading("🦀 Crusty Server - First Time Setup");
+                    ui.separator();
+
+                    ui.label("Create your administrator account:");
+
+                    ui.horizontal(|ui| {
+                        ui.label("Username:");
+                        ui.text_edit_singleline(&mut setup_state.username);
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Password:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut setup_state.password).password(true),
+                        );
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Confirm Password:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut setup_state.confirm_password)
+                                .password(true),
+                        );
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Email:");
+                        ui.text_edit_singleline(&mut setup_state.email);
+                    });
+
+                    ui.separator();
+                    ui.heading("Access Token Configuration");
+                    ui.label("This token will be used to access the web interface.");
+
+                    ui.horizontal(|ui| {
+                        ui.label("Access Token:");
+                        ui.text_edit_singleline(&mut setup_state.access_token);
+
+                        if ui.button("🎲 Suggest Token").clicked() {
+                            setup_state.access_token = AuthManager::generate_suggested_token();
+                        }
+                    });
+
+                    if setup_state.show_token_suggestion && setup_state.access_token.is_empty() {
+                        ui.colored_label(
+                            egui::Color32::YELLOW,
+                            "💡 Click 'Suggest Token' to generate a secure token",
+                        );
+                    }
+
+                    if !setup_state.error_message.is_empty() {
+                        ui.colored_label(egui::Color32::RED, &setup_state.error_message);
+                    }
+
+                    ui.separator();
+
+                    if ui.button("✅ Complete Setup").clicked() {
+                        // Validate inputs
+                        if setup_state.username.len() < 3 {
+                            setup_state.error_message =
+                                "Username must be at least 3 characters".to_string();
+                        } else if setup_state.password != setup_state.confirm_password {
+                            setup_state.error_message = "Passwords do not match".to_string();
+                        } else if setup_state.access_token.len() < 8 {
+                            setup_state.error_message =
+                                "Access token must be at least 8 characters".to_string();
+                        } else if !setup_state.email.contains('@') {
+                            setup_state.error_message =
+                                "Please enter a valid email address".to_string();
+                        } else {
+                            // Try to register the user
+                            let server_state = self.server_state.lock().unwrap();
+                            let mut auth_manager = server_state.auth_manager.lock().unwrap();
+                            match auth_manager.register_user(
+                                &setup_state.username,
+                                &setup_state.password,
+                                &setup_state.email,
+                                &setup_state.access_token,
+                            ) {
+                                Ok(()) => {
+                                    action = AppAction::SwitchToLogin(LoginState {
+                                        username: setup_state.username.clone(),
+                                        password: String::new(),
+                                        email: String::new(),
+                                        error_message: String::new(),
+                                        show_recovery: false,
+                                        recovery_code: None,
+                                        remember_me: false,
+                                    });
+                                }
+                                Err(e) => {
+                                    setup_state.error_message = e;
+                                }
+                            }
+                        }
+                    }
+                });
+            }
+
+            AppState::Login(login_state) => {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.heading("🦀 Crusty Server - Login");
+                    ui.separator();
+
+                    ui.horizontal(|ui| {
+                        ui.label("Username:");
+                        ui.text_edit_singleline(&mut login_state.username);
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Password:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut login_state.password).password(true),
+                        );
+                    });
+
+                    let allow_remember_me = {
+                        let server_state = self.server_state.lock().unwrap();
+                        let auth_manager = server_state.auth_manager.lock().unwrap();
+                        auth_manager.config.allow_remember_me
+                    };
+                    if allow_remember_me {
+                        ui.checkbox(&mut login_state.remember_me, "Remember me on this device");
+                    }
+
+                    if !login_state.error_message.is_empty() {
+                        ui.colored_label(egui::Color32::RED, &login_state.error_message);
+                    }
+
+                    ui.separator();
+
+                    if ui.button("🔑 Login").clicked() {
+                        let result = {
+                            let server_state = self.server_state.lock().unwrap();
+                            let auth_manager = server_state.auth_manager.lock().unwrap();
+                            auth_manager.authenticate(&login_state.username, &login_state.password)
+                        };
+                        record_login(
+                            Some(&login_state.username),
+                            LoginMethod::Password,
+                            None,
+                            result.as_ref().err().map(String::as_str),
+                        );
+                        let expired = result.is_ok() && {
+                            let server_state = self.server_state.lock().unwrap();
+                            let auth_manager = server_state.auth_manager.lock().unwrap();
+                            auth_manager.password_expired(&login_state.username)
+                        };
+                        match result {
+                            Ok(()) if expired => {
+                                action = AppAction::SwitchToPasswordExpired(PasswordExpiredState {
+                                    username: login_state.username.clone(),
+                                    current_password: login_state.password.clone(),
+                                    new_password: String::new(),
+                                    confirm_password: String::new(),
+                                    remember_me: login_state.remember_me,
+                                    error_message: String::new(),
+                                });
+                            }
+                            Ok(()) => {
+                                if login_state.remember_me {
+                                    let server_state = self.server_state.lock().unwrap();
+                                    let mut auth_manager =
+                                        server_state.auth_manager.lock().unwrap();
+                                    if let Err(e) =
+                                        remember_login(&mut auth_manager, &login_state.username)
+                                    {
+                                        eprintln!("⚠️ {}", e);
+                                    }
+                                }
+                                action = AppAction::SwitchToMain(MainState::new(
+                                    self.server_state.clone(),
+                                    login_state.username.clone(),
+                                ));
+                            }
+                            Err(e) => {
+                                login_state.error_message = e;
+                            }
+                        }
+                    }
+
+                    if ui.button("🔓 Forgot Credentials?").clicked() {
+                        login_state.show_recovery = true;
+                    }
+
+                    if login_state.show_recovery {
+                        ui.separator();
+                        ui.heading("Recover Credentials");
+                        ui.label("Enter your email address to receive a new access token:");
+
+                        ui.horizontal(|ui| {
+                            ui.label("Email:");
+                            ui.text_edit_singleline(&mut login_state.email);
+                        });
+
+                        if let Some(code) = &mut login_state.recovery_code {
+                            ui.horizontal(|ui| {
+                                ui.label("Code:");
+                                ui.text_edit_singleline(code);
+                            });
+                            if ui.button("✅ Confirm Code").clicked() {
+                                let server_state = self.server_state.lock().unwrap();
+                                let mut auth_manager = server_state.auth_manager.lock().unwrap();
+                                match auth_manager.confirm_recovery(&login_state.email, code) {
+                                    Ok(()) => {
+                                        login_state.error_message =
+                                            "New access token sent! Check your inbox.".to_string();
+                                        login_state.show_recovery = false;
+                                        login_state.recovery_code = None;
+                                    }
+                                    Err(e) => {
+                                        login_state.error_message = e;
+                                    }
+                                }
+                            }
+                        } else if ui.button("📧 Send Recovery Email").clicked() {
+                            let server_state = self.server_state.lock().unwrap();
+                            let mut auth_manager = server_state.auth_manager.lock().unwrap();
+                            match auth_manager.recover_credentials(&login_state.email) {
+                                Ok(()) => {
+                                    login_state.error_message =
+                                        "Recovery code sent! Check your inbox.".to_string();
+                                    login_state.recovery_code = Some(String::new());
+                                }
+                                Err(e) => {
+                                    login_state.error_message = e;
+                                }
+                            }
+                        }
+
+                        if ui.button("❌ Cancel").clicked() {
+                            login_state.show_recovery = false;
+                            login_state.recovery_code = None;
+                        }
+                    }
+                });
+            }
+
+            AppState::Main(main_state) => {
+                main_state.update_toasts();
+                main_state.show_toasts(ctx);
+                ctx.request_repaint_after(Duration::from_secs(1));
+
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    // Header section with icon and title
+                    ui.horizontal(|ui| {
+                        ui.heading("🦀 Crusty Server");
+                        ui.label("v1.0.0");
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            ui.label(format!("Logged in as: {}", main_state.current_user));
+                            if ui.button("🔒 Lock").clicked() {
+                                main_state.lock();
+                            }
+                            if ui.button("🚪 Logout").clicked() {
+                                // Logging out also forgets this device
+                                {
+                                    let state = main_state.server_state.lock().unwrap();
+                                    let mut auth_manager = state.auth_manager.lock().unwrap();
+                                    forget_login(&mut auth_manager, &main_state.current_user);
+                                }
+                                action = AppAction::SwitchToLogin(LoginState {
+                                    username: String::new(),
+                                    password: String::new(),
+                                    email: String::new(),
+                                    error_message: String::new(),
+                                    show_recovery: false,
+                                    recovery_code: None,
+                                    remember_me: false,
+                                });
+                            }
+                        });
+                    });
+                    ui.separator();
+
+                    // View selection
+                    let active_alerts = {
+                        let state = main_state.server_state.lock().unwrap();
+                        let alert_manager = state.alert_manager.lock().unwrap();
+                        alert_manager.active().len()
+                    };
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(
+                            &mut main_state.view,
+                            MainView::Dashboard,
+                            "📊 Dashboard",
+                        );
+                        ui.selectable_value(
+                            &mut main_state.view,
+                            MainView::Alerts,
+                            format!("🔔 Alerts ({})", active_alerts),
+                        );
+                        let settings = ui.selectable_value(
+                            &mut main_state.view,
+                            MainView::Settings,
+                            "⚙️ Settings",
+                        );
+                        // Start from the saved config every time settings are opened
+                        if settings.changed() {
+                            *main_state.settings = SettingsState::load(&main_state.server_state);
+                        }
+                    });
+                    ui.separator();
+
+                    match main_state.view {
+                        MainView::Alerts => {
+                            main_state.show_alerts(ui);
+                            return;
+                        }
+                        MainView::Settings => {
+                            main_state.show_settings(ui, ctx);
+                            return;
+                        }
+                        MainView::Dashboard => {}
+                    }
+
+                    // Server control section
+                    ui.vertical(|ui| {
+                        ui.heading("Server Control");
+
+                        let (is_running, current_port) = {
+                            let state = main_state.server_state.lock().unwrap();
+                            (state.is_running, state.port)
+                        };
+
+                        ui.horizontal(|ui| {
+                            if !is_running {
+                                if ui
+                                    .add(
+                                        egui::Button::new("🚀 Start Server")
+                                            .fill(egui::Color32::from_rgb(46, 125, 50)),
+                                    )
+                                    .clicked()
+                                {
+                                    main_state.start_server();
+                                }
+                            } else {
+                                if ui
+                                    .add(
+                                        egui::Button::new("🛑 Stop Server")
+                                            .fill(egui::Color32::from_rgb(211, 47, 47)),
+                                    )
+                                    .clicked()
+                                {
+                                    main_state.stop_server();
+                                }
+                            }
+
+                            // Status indicator
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    if is_running {
+                                        ui.colored_label(
+                                            egui::Color32::GREEN,
+                                            format!("● Running on port {}", current_port),
+                                        );
+                                    } else {
+                                        ui.colored_label(egui::Color32::GRAY, "● Stopped");
+                                    }
+                                },
+                            );
+                        });
+
+                        // Status message with better styling
+                        if !main_state.status_message.is_empty() {
+                            ui.separator();
+                            egui::Frame::group(ui.style())
+                                .fill(egui::Color32::from_rgba_unmultiplied(30, 30, 30, 100))
+                                .inner_margin(egui::Margin::same(8))
+                                .show(ui, |ui| {
+                                    ui.horizontal(|ui| {
+                                        ui.label("📢");
+                                        ui.label(&main_state.status_message);
+                                    });
+                                });
+                        }
+                    });
+
+                    // Server information section (only when running)
+                    let (is_running, current_port, last_update) = {
+                        let state = main_state.server_state.lock().unwrap();
+                        let hardware_state = state.hardware_state.lock().unwrap();
+                        let last_update = hardware_state.last_update.elapsed().as_secs();
+                        (state.is_running, state.port, last_update)
+                    };
+
+                    if is_running {
+                        ui.separator();
+                        ui.vertical(|ui| {
+                            ui.heading("📊 Server Information");
+
+                            egui::Frame::group(ui.style())
+                                .inner_margin(egui::Margin::same(10))
+                                .show(ui, |ui| {
+                                    ui.label("📍 Access URLs:");
+                                    ui.indent("urls", |ui| {
+                                        let local = format!("http://localhost:{}/", current_port);
+                                        ui.horizontal(|ui| {
+                                            ui.monospace("Local:  ");
+                                            ui.hyperlink(&local);
+                                        });
+                                        let addresses = lan_addresses();
+                                        if addresses.is_empty() {
+                                            ui.colored_label(
+                                                egui::Color32::YELLOW,
+                                                "No network addresses found, only this computer can connect",
+                                            );
+                                        }
+                                        for address in addresses {
+                                            ui.horizontal(|ui| {
+                                                ui.monospace("Network:");
+                                                ui.hyperlink(address.url(current_port));
+                                                ui.small(&address.interface);
+                                            });
+                                        }
+                                    });
+                                    // Interfaces can come and go, e.g. when Wi-Fi reconnects
+                                    ui.ctx().request_repaint_after(LAN_REFRESH);
+                                    ui.add_space(5.0);
+                                    main_state.show_qr_code(ui, current_port);
+                                });
+
+                            ui.add_space(10.0);
+
+                            // Hardware monitoring status, hidden where there are no sensors
+                            if platform_capabilities().hardware_query {
+                                ui.heading("🔧 Hardware Monitoring");
+                                egui::Frame::group(ui.style())
+                                    .inner_margin(egui::Margin::same(10))
+                                    .show(ui, |ui| {
+                                        ui.horizontal(|ui| {
+                                            ui.label("Last updated:");
+                                            if last_update < 60 {
+                                                ui.colored_label(
+                                                    egui::Color32::GREEN,
+                                                    format!("{} seconds ago", last_update),
+                                                );
+                                            } else {
+                                                ui.colored_label(
+                                                    egui::Color32::YELLOW,
+                                                    format!("{} seconds ago", last_update),
+                                                );
+                                            }
+                                        });
+                                        ui.label("⏱️ Power and thermal data refreshes every 60s");
+                                    });
+                                ui.add_space(10.0);
+                            }
+
+                            ui.heading("🩺 Collector Health");
+                            egui::Frame::group(ui.style())
+                                .inner_margin(egui::Margin::same(10))
+                                .show(ui, |ui| {
+                                    if let Some(degraded) = collection_status().degraded {
+                                        ui.colored_label(
+                                            egui::Color32::YELLOW,
+                                            format!(
+                                                "🐢 Host under load ({}), collecting {}x less often",
+                                                degraded.reason, degraded.backoff_factor
+                                            ),
+                                        );
+                                    }
+                                    for unsupported in &platform_capabilities().unsupported {
+                                        ui.horizontal(|ui| {
+                                            ui.colored_label(egui::Color32::GRAY, "OFF");
+                                            ui.strong(&unsupported.collector);
+                                            ui.label(&unsupported.reason);
+                                        });
+                                    }
+                                    let collectors = collector_health();
+                                    if collectors.is_empty() {
+                                        ui.label("No collector has run yet");
+                                    }
+                                    for collector in &collectors {
+                                        ui.horizontal(|ui| {
+                                            let color = match collector.state {
+                                                CollectorState::Ok => egui::Color32::GREEN,
+                                                CollectorState::Degraded => egui::Color32::YELLOW,
+                                                CollectorState::Failing => egui::Color32::RED,
+                                            };
+                                            ui.colored_label(color, collector.state.label());
+                                            ui.strong(&collector.name);
+                                            if collector.consecutive_failures > 0
+                                                && let Some(error) = &collector.last_error
+                                            {
+                                                ui.label(format!(
+                                                    "{} failures in a row: {}",
+                                                    collector.consecutive_failures, error
+                                                ));
+                                            }
+                                        });
+                                    }
+                                });
+                        });
+                    }
+
+                    main_state.show_custom_metrics(ui);
+
+                    // Instructions section
+                    ui.separator();
+                    ui.vertical(|ui| {
+                        ui.heading("💡 Instructions");
+
+                        egui::Frame::group(ui.style())
+                            .fill(egui::Color32::from_rgba_unmultiplied(25, 25, 35, 100))
+                            .inner_margin(egui::Margin::same(10))
+                            .show(ui, |ui| {
+                                ui.vertical(|ui| {
+                                    ui.horizontal(|ui| {
+                                        ui.label("1.");
+                                        ui.label("Set the port under ⚙️ Settings → Server (default: 3000)");
+                                    });
+                                    ui.horizontal(|ui| {
+                                        ui.label("2.");
+                                        ui.label("Click 'Start Server' to begin hosting");
+                                    });
+                                    ui.horizontal(|ui| {
+                                        ui.label("3.");
+                                        ui.label("Access the status page from any browser");
+                                    });
+                                    ui.horizontal(|ui| {
+                                        ui.label("4.");
+                                        ui.label("Use 'Stop Server' to shut down");
+                                    });
+                                });
+                            });
+                    });
+
+                    // Footer
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.small(
+                            "Created for Nagios Enterprises LLC • 2025 Summer Nintern Program",
+                        );
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            ui.small("🦀 Powered by Rust");
+                        });
+                    });
+                });
+            }
+
+            AppState::Recovery(recovery_state) => {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.heading("🔓 Recover Credentials");
+                    ui.separator();
+
+                    ui.label("Enter your email address to receive a new access token:");
+
+                    ui.horizontal(|ui| {
+                        ui.label("Email:");
+                        ui.text_edit_singleline(&mut recovery_state.email);
+                    });
+
+                    if !recovery_state.message.is_empty() {
+                        let color = if recovery_state.is_success {
+                            egui::Color32::GREEN
+                        } else {
+                            egui::Color32::RED
+                        };
+                        ui.colored_label(color, &recovery_state.message);
+                    }
+
+                    ui.separator();
+
+                    if let Some(code) = &mut recovery_state.code {
+                        ui.horizontal(|ui| {
+                            ui.label("Code:");
+                            ui.text_edit_singleline(code);
+                        });
+                        if ui.button("✅ Confirm Code").clicked() {
+                            let server_state = self.server_state.lock().unwrap();
+                            let mut auth_manager = server_state.auth_manager.lock().unwrap();
+                            match auth_manager.confirm_recovery(&recovery_state.email, code) {
+                                Ok(()) => {
+                                    recovery_state.message =
+                                        "New access token sent! Check your inbox.".to_string();
+                                    recovery_state.is_success = true;
+                                    recovery_state.code = None;
+                                }
+                                Err(e) => {
+                                    recovery_state.message = e;
+                                    recovery_state.is_success = false;
+                                }
+                            }
+                        }
+                    } else if ui.button("📧 Send Recovery Email").clicked() {
+                        let server_state = self.server_state.lock().unwrap();
+                        let mut auth_manager = server_state.auth_manager.lock().unwrap();
+                        match auth_manager.recover_credentials(&recovery_state.email) {
+                            Ok(()) => {
+                                recovery_state.message =
+                                    "Recovery code sent! Check your inbox.".to_string();
+                                recovery_state.is_success = true;
+                                recovery_state.code = Some(String::new());
+                            }
+                            Err(e) => {
+                                recovery_state.message = e;
+                                recovery_state.is_success = false;
+                            }
+                        }
+                    }
+
+                    if ui.button("⬅️ Back to Login").clicked() {
+                        action = AppAction::SwitchToLogin(LoginState {
+                            username: String::new(),
+                            password: String::new(),
+                            email: String::new(),
+                            error_message: String::new(),
+                            show_recovery: false,
+                            recovery_code: None,
+                            remember_me: false,
+                        });
+                    }
+                });
+            }
+
+            AppState::PasswordExpired(expired_state) => {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.heading("🔒 Password Expired");
+                    ui.separator();
+
+                    let policy = {
+                        let server_state = self.server_state.lock().unwrap();
+                        let auth_manager = server_state.auth_manager.lock().unwrap();
+                        auth_manager.config.password_policy.clone()
+                    };
+                    ui.label(format!(
+                        "Your password is older than {} days, choose a new one with {}.",
+                        policy.max_age_days,
+                        policy.describe()
+                    ));
+
+                    ui.horizontal(|ui| {
+                        ui.label("New Password:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut expired_state.new_password)
+                                .password(true),
+                        );
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Confirm Password:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut expired_state.confirm_password)
+                                .password(true),
+                        );
+                    });
+
+                    if !expired_state.error_message.is_empty() {
+                        ui.colored_label(egui::Color32::RED, &expired_state.error_message);
+                    }
+
+                    ui.separator();
+
+                    if ui.button("✅ Change Password").clicked() {
+                        if expired_state.new_password != expired_state.confirm_password {
+                            expired_state.error_message = "Passwords do not match".to_string();
+                        } else {
+                            let server_state = self.server_state.lock().unwrap();
+                            let mut auth_manager = server_state.auth_manager.lock().unwrap();
+                            let changed = auth_manager.change_password(
+                                &expired_state.username,
+                                &expired_state.current_password,
+                                &expired_state.new_password,
+                            );
+                            match changed {
+                                Ok(()) => {
+                                    if expired_state.remember_me
+                                        && let Err(e) = remember_login(
+                                            &mut auth_manager,
+                                            &expired_state.username,
+                                        )
+                                    {
+                                        eprintln!("⚠️ {}", e);
+                                    }
+                                    action = AppAction::SwitchToMain(MainState::new(
+                                        self.server_state.clone(),
+                                        expired_state.username.clone(),
+                                    ));
+                                }
+                                Err(e) => {
+                                    expired_state.error_message = e;
+                                }
+                            }
+                        }
+                    }
+
+                    if ui.button("⬅️ Back to Login").clicked() {
+                        action = AppAction::SwitchToLogin(LoginState {
+                            username: expired_state.username.clone(),
+                            password: String::new(),
+                            email: String::new(),
+                            error_message: String::new(),
+                            show_recovery: false,
+                            recovery_code: None,
+                            remember_me: false,
+                        });
+                    }
+                });
+            }
+        }
+        match action {
+            AppAction::SwitchToLogin(login_state) => {
+                self.app_state = AppState::Login(login_state);
+            }
+            AppAction::SwitchToMain(main_state) => {
+                self.app_state = AppState::Main(main_state);
+            }
+            AppAction::SwitchToRecovery => {
+                self.app_state = AppState::Recovery(RecoveryState {
+                    email: String::new(),
+                    code: None,
+                    message: String::new(),
+                    is_success: false,
+                });
+            }
+            AppAction::SwitchToPasswordExpired(expired_state) => {
+                self.app_state = AppState::PasswordExpired(expired_state);
+            }
+            AppAction::None => {}
+        }
+    }
+}
+
+pub fn run_gui() -> Result<(), Box<dyn std::error::Error>> {
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_icon(std::sync::Arc::new(egui::IconData {
+            rgba: image::load_from_memory(include_bytes!("../Assets/icon.png"))
+                .unwrap()
+                .to_rgba8()
+                .to_vec(),
+            width: 250,
+            height: 325,
+        })),
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        "Crusty Crawler",
+        options,
+        Box::new(|_cc| Ok(Box::<MyApp>::default())),
+    )?;
+    Ok(())
+}
@@ -1,3 +1,4 @@
+#[cfg(feature = "checks")]
 use hardware_query::HardwareInfo;
 
 pub struct HardwareMonitorState {
@@ -18,6 +19,7 @@ impl Default for HardwareMonitorState {
     }
 }
 
+#[cfg(feature = "checks")]
 pub fn update_hardware_info(hardware_state: &mut HardwareMonitorState) {
     match HardwareInfo::query() {
         Ok(hw_info) => {
@@ -70,9 +72,11 @@ pub fn update_hardware_info(hardware_state: &mut HardwareMonitorState) {
             hardware_state.thermal_info = Some(thermal_output);
             hardware_state.optimization_suggestions = suggestions;
             hardware_state.last_update = Instant::now();
+            record_collector_result("hardware_query", Ok(()));
         }
         Err(e) => {
             let error_msg = format!("Error querying hardware: {}", e);
+            record_collector_result("hardware_query", Err(&e.to_string()));
             hardware_state.power_info = Some(error_msg.clone());
             hardware_state.thermal_info = Some(error_msg);
             hardware_state.last_update = Instant::now();
@@ -80,14 +84,45 @@ pub fn update_hardware_info(hardware_state: &mut HardwareMonitorState) {
     }
 }
 
+// VMs answer the query but have neither a power profile nor temperatures
+#[cfg(feature = "checks")]
+pub fn hardware_query_supported() -> bool {
+    HardwareInfo::query().is_ok_and(|hw_info| {
+        hw_info.power_profile().is_some() || hw_info.thermal().max_temperature().is_some()
+    })
+}
+
+#[cfg(not(feature = "checks"))]
+pub fn hardware_query_supported() -> bool {
+    false
+}
+
+// Power and thermal profiles come from hardware-query, which only the checks feature pulls in
+#[cfg(not(feature = "checks"))]
+pub fn update_hardware_info(hardware_state: &mut HardwareMonitorState) {
+    let unavailable = "Not available in this build (checks feature disabled)\n".to_string();
+    hardware_state.power_info = Some(unavailable.clone());
+    hardware_state.thermal_info = Some(unavailable);
+    hardware_state.last_update = Instant::now();
+}
+
 #[warn(private_interfaces)]
 pub fn get_hardware_status(server_state: &std::sync::Mutex<crate::ServerState>) -> String {
     let mut output = String::new();
+    if let Some(reason) = unsupported_reason("hardware_query") {
+        output.push_str(&format!("\n=== Power and Thermal ===\n{}\n", reason));
+        return output;
+    }
 
     // Update hardware info if needed
     {
         let state = server_state.lock().unwrap();
-        if state.hardware_state.lock().unwrap().last_update.elapsed() > Duration::from_secs(60) {
+        // hardware-query is slow, it's refreshed less often while the host is busy and not at
+        // all on battery
+        if !power_saving_active()
+            && state.hardware_state.lock().unwrap().last_update.elapsed()
+                > collection_interval(Duration::from_secs(60))
+        {
             update_hardware_info(&mut state.hardware_state.lock().unwrap());
         }
     }
@@ -110,6 +145,12 @@ pub fn get_hardware_status(server_state: &std::sync::Mutex<crate::ServerState>)
         } else {
             output.push_str("Thermal info not available\n");
         }
+        if let Some(sbc_status) = sbc_thermal_status() {
+            output.push_str(&sbc_status);
+        }
+        if let Some(ipmi_status) = ipmi_thermal_status() {
+            output.push_str(&ipmi_status);
+        }
 
         if !hardware_state.optimization_suggestions.is_empty() {
             output.push_str("\n=== Optimization Suggestions ===\n");
@@ -0,0 +1,138 @@
+// Health module for Crusty-Crawler
+// Failure counts and the last error of every collector, so a sensor that keeps failing (e.g.
+// hardware-query on a VM) shows up in /api/self and the GUI instead of just leaving gaps
+
+// Consecutive failures a collector may have before it's reported as failing
+const COLLECTOR_ERROR_BUDGET: u32 = 3;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CollectorState {
+    Ok,
+    // Failed recently, but still within the error budget
+    Degraded,
+    Failing,
+}
+
+impl CollectorState {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CollectorState::Ok => "OK",
+            CollectorState::Degraded => "DEGRADED",
+            CollectorState::Failing => "FAILING",
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct CollectorHealth {
+    pub name: String,
+    pub state: CollectorState,
+    pub consecutive_failures: u32,
+    pub total_runs: u64,
+    pub total_failures: u64,
+    pub last_success: Option<String>,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<String>,
+}
+
+impl CollectorHealth {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            state: CollectorState::Ok,
+            consecutive_failures: 0,
+            total_runs: 0,
+            total_failures: 0,
+            last_success: None,
+            last_error: None,
+            last_error_at: None,
+        }
+    }
+
+    fn record(&mut self, result: Result<(), &str>, now: chrono::DateTime<chrono::Utc>) {
+        self.total_runs += 1;
+        match result {
+            Ok(()) => {
+                self.consecutive_failures = 0;
+                self.last_success = Some(now.to_rfc3339());
+            }
+            Err(e) => {
+                self.consecutive_failures += 1;
+                self.total_failures += 1;
+                self.last_error = Some(e.to_string());
+                self.last_error_at = Some(now.to_rfc3339());
+            }
+        }
+        self.state = match self.consecutive_failures {
+            0 => CollectorState::Ok,
+            n if n < COLLECTOR_ERROR_BUDGET => CollectorState::Degraded,
+            _ => CollectorState::Failing,
+        };
+    }
+}
+
+// By collector name, kept in memory since start
+static COLLECTOR_HEALTH: Mutex<BTreeMap<String, CollectorHealth>> = Mutex::new(BTreeMap::new());
+
+// Called by every collector after each run. Logs once when it runs out of error budget and
+// once when it recovers, not on every failure
+pub fn record_collector_result(name: &str, result: Result<(), &str>) {
+    let mut health = COLLECTOR_HEALTH.lock().unwrap();
+    let collector = health
+        .entry(name.to_string())
+        .or_insert_with(|| CollectorHealth::new(name));
+    let before = collector.state;
+    collector.record(result, chrono::Utc::now());
+    match (before, collector.state) {
+        (CollectorState::Failing, CollectorState::Ok) => {
+            println!("✅ {} collector recovered", name)
+        }
+        (before, CollectorState::Failing) if before != CollectorState::Failing => eprintln!(
+            "❌ {} collector failed {} times in a row: {}",
+            name,
+            collector.consecutive_failures,
+            collector.last_error.as_deref().unwrap_or_default()
+        ),
+        _ => {}
+    }
+}
+
+pub fn collector_health() -> Vec<CollectorHealth> {
+    COLLECTOR_HEALTH.lock().unwrap().values().cloned().collect()
+}
+
+// So alert rules can fire on a collector that stopped working
+pub fn collector_health_metrics() -> Vec<Metric> {
+    collector_health()
+        .iter()
+        .map(|collector| {
+            Metric::new(
+                "collector_consecutive_failures",
+                collector.consecutive_failures as f64,
+            )
+            .label("collector", &collector.name)
+        })
+        .collect()
+}
+
+// Response of GET /api/self
+#[derive(Serialize)]
+pub struct SelfReport {
+    pub version: &'static str,
+    pub pid: u32,
+    pub collection: CollectionStatus,
+    pub collectors: Vec<CollectorHealth>,
+    // Collectors turned off because this machine has nothing for them to read
+    pub platform: PlatformCapabilities,
+}
+
+pub fn self_report() -> SelfReport {
+    SelfReport {
+        version: env!("CARGO_PKG_VERSION"),
+        pid: std::process::id(),
+        collection: collection_status(),
+        collectors: collector_health(),
+        platform: platform_capabilities().clone(),
+    }
+}
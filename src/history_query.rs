@@ -0,0 +1,316 @@
+// History query module for Crusty-Crawler
+// Long charts without shipping every sample to the browser. The chart history is also rolled up
+// into five minute buckets kept for a month, and /api/history/query aggregates the raw samples
+// and the buckets before them into intervals with avg, min, max or p95
+
+const HISTORY_ROLLUPS_FILE: &str = "crusty_history_rollups.json";
+const ROLLUP_SECS: i64 = 300;
+const ROLLUP_DAYS: i64 = 31;
+// Intervals are widened so no series has more points than this
+const MAX_QUERY_POINTS: i64 = 1000;
+const DEFAULT_QUERY_HOURS: i64 = 6;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregation {
+    #[default]
+    Avg,
+    Min,
+    Max,
+    P95,
+}
+
+// Summary of the samples in one bucket, a raw sample is a bucket of one
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Rollup {
+    // Unix seconds
+    pub start: i64,
+    pub count: u32,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+    pub p95: f64,
+}
+
+// Nearest rank
+fn percentile_95(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let rank = (values.len() as f64 * 0.95).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1]
+}
+
+impl Rollup {
+    fn sample(start: i64, value: f64) -> Self {
+        Self {
+            start,
+            count: 1,
+            sum: value,
+            min: value,
+            max: value,
+            p95: value,
+        }
+    }
+
+    fn of(start: i64, values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        Some(Self {
+            start,
+            count: values.len() as u32,
+            sum: values.iter().sum(),
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            p95: percentile_95(values.to_vec()),
+        })
+    }
+}
+
+// The p95 of several buckets is the p95 of their p95s, close enough for a chart
+fn aggregate(rollups: &[Rollup], aggregation: Aggregation) -> f64 {
+    match aggregation {
+        Aggregation::Avg => {
+            let count: u32 = rollups.iter().map(|rollup| rollup.count).sum();
+            rollups.iter().map(|rollup| rollup.sum).sum::<f64>() / count.max(1) as f64
+        }
+        Aggregation::Min => rollups
+            .iter()
+            .map(|rollup| rollup.min)
+            .fold(f64::INFINITY, f64::min),
+        Aggregation::Max => rollups
+            .iter()
+            .map(|rollup| rollup.max)
+            .fold(f64::NEG_INFINITY, f64::max),
+        Aggregation::P95 => percentile_95(rollups.iter().map(|rollup| rollup.p95).collect()),
+    }
+}
+
+// One point per interval from `from` that has data, stamped with the interval's start.
+// Rollups are sorted by start
+pub fn downsample(
+    rollups: &[Rollup],
+    from: i64,
+    to: i64,
+    interval: i64,
+    aggregation: Aggregation,
+) -> Vec<(String, f64)> {
+    let mut points = Vec::new();
+    let in_range: Vec<Rollup> = rollups
+        .iter()
+        .filter(|rollup| rollup.start >= from && rollup.start < to)
+        .copied()
+        .collect();
+    for group in
+        in_range.chunk_by(|a, b| (a.start - from) / interval == (b.start - from) / interval)
+    {
+        let start = from + (group[0].start - from) / interval * interval;
+        let Some(timestamp) = chrono::DateTime::from_timestamp(start, 0) else {
+            continue;
+        };
+        points.push((timestamp.to_rfc3339(), aggregate(group, aggregation)));
+    }
+    points
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct HistoryRollups {
+    // Finished buckets per series, oldest first
+    series: BTreeMap<String, VecDeque<Rollup>>,
+    // Bucket start and samples of the bucket still filling, lost on restart
+    #[serde(skip)]
+    pending: BTreeMap<String, (i64, Vec<f64>)>,
+}
+
+// Loaded from HISTORY_ROLLUPS_FILE on first use
+static HISTORY_ROLLUPS: Mutex<Option<HistoryRollups>> = Mutex::new(None);
+
+fn with_history_rollups<T>(f: impl FnOnce(&mut HistoryRollups) -> T) -> T {
+    let mut rollups = HISTORY_ROLLUPS.lock().unwrap();
+    let rollups = rollups.get_or_insert_with(|| {
+        fs::read_to_string(data_path(HISTORY_ROLLUPS_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    });
+    f(rollups)
+}
+
+// Called with every chart history sample, saves whenever a bucket is finished
+pub fn record_history_rollups(samples: &[(String, f64)], now: chrono::DateTime<chrono::Utc>) {
+    let bucket = now.timestamp().div_euclid(ROLLUP_SECS) * ROLLUP_SECS;
+    let cutoff = now.timestamp() - ROLLUP_DAYS * 86400;
+    with_history_rollups(|rollups| {
+        let mut finished = false;
+        for (name, value) in samples {
+            let pending = rollups
+                .pending
+                .entry(name.clone())
+                .or_insert((bucket, Vec::new()));
+            if pending.0 != bucket {
+                let (start, values) = std::mem::replace(pending, (bucket, Vec::new()));
+                if let Some(rollup) = Rollup::of(start, &values) {
+                    let series = rollups.series.entry(name.clone()).or_default();
+                    series.push_back(rollup);
+                    while series.front().is_some_and(|rollup| rollup.start < cutoff) {
+                        series.pop_front();
+                    }
+                    finished = true;
+                }
+            }
+            pending.1.push(*value);
+        }
+        if finished {
+            save_history_rollups(rollups);
+        }
+    });
+}
+
+fn save_history_rollups(rollups: &HistoryRollups) {
+    let saved = serde_json::to_string(rollups)
+        .map_err(|e| e.to_string())
+        .and_then(|data| {
+            fs::write(data_path(HISTORY_ROLLUPS_FILE), data).map_err(|e| e.to_string())
+        });
+    if let Err(e) = saved {
+        eprintln!(
+            "⚠️  Failed to save history rollups to {}: {}",
+            HISTORY_ROLLUPS_FILE, e
+        );
+    }
+}
+
+// Returns how many buckets were removed, including ones still filling
+pub fn remove_history_rollups_before(cutoff: chrono::DateTime<chrono::Utc>) -> usize {
+    let cutoff = cutoff.timestamp();
+    with_history_rollups(|rollups| {
+        let mut removed = 0;
+        for series in rollups.series.values_mut() {
+            let before = series.len();
+            series.retain(|rollup| rollup.start >= cutoff);
+            removed += before - series.len();
+        }
+        rollups.series.retain(|_, series| !series.is_empty());
+        let pending = rollups.pending.len();
+        rollups.pending.retain(|_, (start, _)| *start >= cutoff);
+        if removed > 0 {
+            save_history_rollups(rollups);
+        }
+        removed + pending - rollups.pending.len()
+    })
+}
+
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    // Comma separated series names, all of them when left out
+    pub series: Option<String>,
+    // RFC 3339, the last six hours by default
+    pub from: Option<String>,
+    pub to: Option<String>,
+    // Seconds per point
+    pub interval: Option<u64>,
+    #[serde(default)]
+    pub aggregation: Aggregation,
+}
+
+// Response of GET /api/history/query
+#[derive(Serialize)]
+pub struct HistoryQueryResult {
+    pub from: String,
+    pub to: String,
+    // The requested interval, or wider to stay under MAX_QUERY_POINTS points
+    pub interval_secs: i64,
+    pub aggregation: Aggregation,
+    // Series name -> (RFC 3339 start of the interval, value), like /api/history
+    pub series: BTreeMap<String, Vec<(String, f64)>>,
+}
+
+fn parse_query_time(time: &Option<String>, default: i64) -> Result<i64, String> {
+    match time {
+        Some(time) => chrono::DateTime::parse_from_rfc3339(time)
+            .map(|time| time.timestamp())
+            .map_err(|e| format!("Invalid time '{}': {}", time, e)),
+        None => Ok(default),
+    }
+}
+
+// The raw samples, with the rollups from before the oldest of them
+fn series_rollups(
+    raw: &VecDeque<(String, f64)>,
+    rollups: Option<&VecDeque<Rollup>>,
+) -> Vec<Rollup> {
+    let samples: Vec<Rollup> = raw
+        .iter()
+        .filter_map(|(timestamp, value)| {
+            let timestamp = chrono::DateTime::parse_from_rfc3339(timestamp).ok()?;
+            Some(Rollup::sample(timestamp.timestamp(), *value))
+        })
+        .collect();
+    let raw_start = samples.first().map_or(i64::MAX, |sample| sample.start);
+    let mut all: Vec<Rollup> = rollups
+        .into_iter()
+        .flatten()
+        .filter(|rollup| rollup.start + ROLLUP_SECS <= raw_start)
+        .copied()
+        .collect();
+    all.extend(samples);
+    all
+}
+
+pub fn query_history(
+    query: &HistoryQuery,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<HistoryQueryResult, String> {
+    let to = parse_query_time(&query.to, now.timestamp() + 1)?;
+    let from = parse_query_time(&query.from, to - DEFAULT_QUERY_HOURS * 3600)?;
+    if from >= to {
+        return Err("from must be before to".to_string());
+    }
+    let requested = query.interval.unwrap_or(HISTORY_INTERVAL_SECS) as i64;
+    let interval = requested
+        .max((to - from + MAX_QUERY_POINTS - 1) / MAX_QUERY_POINTS)
+        .max(1);
+
+    let raw = metric_history();
+    let names: Vec<String> = match &query.series {
+        Some(series) => series
+            .split(',')
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect(),
+        None => {
+            let mut names: BTreeSet<String> = raw.keys().cloned().collect();
+            with_history_rollups(|rollups| names.extend(rollups.series.keys().cloned()));
+            names.into_iter().collect()
+        }
+    };
+
+    let mut series = BTreeMap::new();
+    for name in names {
+        let rollups = with_history_rollups(|rollups| rollups.series.get(&name).cloned());
+        let Some(raw) = raw
+            .get(&name)
+            .cloned()
+            .or(rollups.is_some().then(VecDeque::new))
+        else {
+            return Err(format!("No history for series '{}'", name));
+        };
+        let all = series_rollups(&raw, rollups.as_ref());
+        series.insert(
+            name,
+            downsample(&all, from, to, interval, query.aggregation),
+        );
+    }
+
+    let timestamp = |secs: i64| {
+        chrono::DateTime::from_timestamp(secs, 0)
+            .map(|time| time.to_rfc3339())
+            .unwrap_or_default()
+    };
+    Ok(HistoryQueryResult {
+        from: timestamp(from),
+        to: timestamp(to),
+        interval_secs: interval,
+        aggregation: query.aggregation,
+        series,
+    })
+}
@@ -0,0 +1,202 @@
+// Host score module for Crusty-Crawler
+// A single 0-100 number for how healthy the host is, the weighted average of the check states
+// and the CPU, memory and disk pressure. Meant for fleet overviews and wall displays where a
+// list of checks doesn't fit
+
+// Resource use below this costs no points, from here to 100% it costs all of them
+const PRESSURE_FREE_PERCENT: f64 = 70.0;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct HealthScoreConfig {
+    // Check name -> weight, e.g. {"website": 5}. 0 leaves a check out of the score
+    pub weights: BTreeMap<String, f64>,
+    // Weight of every check not listed in weights
+    pub check_weight: f64,
+    pub cpu_weight: f64,
+    pub memory_weight: f64,
+    // Of the fullest disk
+    pub disk_weight: f64,
+}
+
+impl Default for HealthScoreConfig {
+    fn default() -> Self {
+        Self {
+            weights: BTreeMap::new(),
+            check_weight: 1.0,
+            cpu_weight: 1.0,
+            memory_weight: 1.0,
+            disk_weight: 1.0,
+        }
+    }
+}
+
+impl HealthScoreConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        let valid = |weight: &f64| weight.is_finite() && *weight >= 0.0;
+        if let Some((check, _)) = self.weights.iter().find(|(_, weight)| !valid(weight)) {
+            return Err(format!(
+                "checks.health_score.weights: weight of {} must be 0 or more",
+                check
+            ));
+        }
+        if ![
+            self.check_weight,
+            self.cpu_weight,
+            self.memory_weight,
+            self.disk_weight,
+        ]
+        .iter()
+        .all(valid)
+        {
+            return Err("checks.health_score: weights must be 0 or more".to_string());
+        }
+        Ok(())
+    }
+}
+
+// CPU, memory and fullest disk use in percent
+#[derive(Clone, Copy)]
+pub struct ResourceUsage {
+    // Last history sample, None until the sampler has run once
+    pub cpu_percent: Option<f64>,
+    pub memory_percent: f64,
+    pub disk_percent: f64,
+}
+
+pub fn resource_usage(devices: &DeviceFilters) -> ResourceUsage {
+    let mut sys = sysinfo::System::new();
+    sys.refresh_memory();
+    let memory_percent = if sys.total_memory() > 0 {
+        sys.used_memory() as f64 / sys.total_memory() as f64 * 100.0
+    } else {
+        0.0
+    };
+    let disk_percent = sysinfo::Disks::new_with_refreshed_list()
+        .list()
+        .iter()
+        .filter(|disk| disk.total_space() > 0 && devices.allows_disk(disk))
+        .map(|disk| {
+            (disk.total_space() - disk.available_space()) as f64 / disk.total_space() as f64 * 100.0
+        })
+        .fold(0.0, f64::max);
+    let cpu_percent = metric_history()
+        .get("cpu_usage_percent")
+        .and_then(|series| series.back())
+        .map(|(_, value)| *value);
+    ResourceUsage {
+        cpu_percent,
+        memory_percent,
+        disk_percent,
+    }
+}
+
+// What one check or resource contributed, for explaining the score
+#[derive(Serialize, Clone)]
+pub struct ScoreComponent {
+    pub name: String,
+    pub weight: f64,
+    pub score: f64,
+}
+
+// Response of GET /api/health-score
+#[derive(Serialize, Clone)]
+pub struct HealthScore {
+    pub score: f64,
+    pub components: Vec<ScoreComponent>,
+}
+
+fn state_score(state: CheckState) -> f64 {
+    match state {
+        CheckState::Ok => 100.0,
+        // Not knowing is better than a warning but still not healthy
+        CheckState::Unknown => 75.0,
+        CheckState::Warning => 50.0,
+        CheckState::Critical => 0.0,
+    }
+}
+
+fn pressure_score(percent: f64) -> f64 {
+    ((100.0 - percent) / (100.0 - PRESSURE_FREE_PERCENT) * 100.0).clamp(0.0, 100.0)
+}
+
+// 100 when nothing carries any weight
+pub fn compute_health_score(
+    config: &HealthScoreConfig,
+    results: &[CheckResult],
+    usage: &ResourceUsage,
+) -> HealthScore {
+    let mut components: Vec<ScoreComponent> = results
+        .iter()
+        .map(|result| ScoreComponent {
+            name: result.name.clone(),
+            weight: config
+                .weights
+                .get(&result.name)
+                .copied()
+                .unwrap_or(config.check_weight),
+            score: state_score(result.state),
+        })
+        .collect();
+    let resources = [
+        ("cpu", config.cpu_weight, usage.cpu_percent),
+        ("memory", config.memory_weight, Some(usage.memory_percent)),
+        ("disk", config.disk_weight, Some(usage.disk_percent)),
+    ];
+    for (name, weight, percent) in resources {
+        if let Some(percent) = percent {
+            components.push(ScoreComponent {
+                name: name.to_string(),
+                weight,
+                score: pressure_score(percent),
+            });
+        }
+    }
+    components.retain(|component| component.weight > 0.0);
+
+    let total_weight: f64 = components.iter().map(|component| component.weight).sum();
+    let score = if total_weight > 0.0 {
+        components
+            .iter()
+            .map(|component| component.weight * component.score)
+            .sum::<f64>()
+            / total_weight
+    } else {
+        100.0
+    };
+    HealthScore {
+        score: (score * 10.0).round() / 10.0,
+        components,
+    }
+}
+
+pub async fn host_health_score(runner: &CheckRunner) -> HealthScore {
+    let max_age = Duration::from_secs(runner.config.cache_max_age_secs);
+    let mut results = Vec::new();
+    for name in available_checks(&runner.config) {
+        if let Some((result, _)) = runner.cached(&name, max_age).await {
+            results.push(result);
+        }
+    }
+    compute_health_score(
+        &runner.config.health_score,
+        &results,
+        &resource_usage(&runner.devices),
+    )
+}
+
+// Score after the last pass of the check loop, exported as host_health_score
+static LATEST_HEALTH_SCORE: Mutex<Option<f64>> = Mutex::new(None);
+
+pub fn record_health_score(score: &HealthScore) {
+    *LATEST_HEALTH_SCORE.lock().unwrap() = Some(score.score);
+}
+
+pub fn health_score_metrics() -> Vec<Metric> {
+    LATEST_HEALTH_SCORE
+        .lock()
+        .unwrap()
+        .map(|score| Metric::new("host_health_score", score))
+        .into_iter()
+        .collect()
+}
@@ -0,0 +1,160 @@
+// HTTP checks module for Crusty-Crawler
+// Active checks against web applications with status, body and response time assertions
+
+fn default_http_method() -> String {
+    "GET".to_string()
+}
+
+fn default_response_time_thresholds() -> Thresholds {
+    Thresholds {
+        warning: 1000.0,
+        critical: 5000.0,
+    }
+}
+
+fn default_http_timeout_secs() -> u64 {
+    10
+}
+
+fn default_verify_tls() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HttpCheck {
+    // Check name used by the checks API, e.g. "intranet-login"
+    pub name: String,
+    pub url: String,
+    #[serde(default = "default_http_method")]
+    pub method: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    // Any 2xx or 3xx status passes when empty
+    #[serde(default)]
+    pub expected_status: Vec<u16>,
+    // Regex the response body has to match
+    #[serde(default)]
+    pub body_regex: Option<String>,
+    // Regex the response body must not match, e.g. "(?i)exception"
+    #[serde(default)]
+    pub body_not_regex: Option<String>,
+    // Milliseconds
+    #[serde(default = "default_response_time_thresholds")]
+    pub response_time: Thresholds,
+    #[serde(default = "default_http_timeout_secs")]
+    pub timeout_secs: u64,
+    // Disable for internal apps with self-signed certificates
+    #[serde(default = "default_verify_tls")]
+    pub verify_tls: bool,
+}
+
+fn http_check_failure(check: &HttpCheck, state: CheckState, message: String) -> CheckResult {
+    CheckResult::new(
+        &check.name,
+        state,
+        format!("{} {}: {}", check.method, check.url, message),
+        Vec::new(),
+    )
+}
+
+pub async fn check_http(check: &HttpCheck) -> CheckResult {
+    let method = match reqwest::Method::from_bytes(check.method.to_uppercase().as_bytes()) {
+        Ok(method) => method,
+        Err(_) => {
+            return http_check_failure(
+                check,
+                CheckState::Unknown,
+                format!("Invalid method {}", check.method),
+            );
+        }
+    };
+
+    let client = match reqwest::Client::builder()
+        .danger_accept_invalid_certs(!check.verify_tls)
+        .timeout(Duration::from_secs(check.timeout_secs))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => return http_check_failure(check, CheckState::Unknown, e.to_string()),
+    };
+
+    let mut request = client.request(method, &check.url);
+    for (name, value) in &check.headers {
+        request = request.header(name, value);
+    }
+    if let Some(body) = &check.body {
+        request = request.body(body.clone());
+    }
+
+    let started = Instant::now();
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            let message = if e.is_timeout() {
+                format!("No response within {}s", check.timeout_secs)
+            } else {
+                e.to_string()
+            };
+            return http_check_failure(check, CheckState::Critical, message);
+        }
+    };
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let mut state = check.response_time.evaluate(elapsed_ms);
+    let mut problems = Vec::new();
+
+    let status_ok = if check.expected_status.is_empty() {
+        status.is_success() || status.is_redirection()
+    } else {
+        check.expected_status.contains(&status.as_u16())
+    };
+    if !status_ok {
+        state = CheckState::Critical;
+        problems.push(format!("unexpected status {}", status.as_u16()));
+    }
+
+    for (pattern, should_match) in [(&check.body_regex, true), (&check.body_not_regex, false)] {
+        let Some(pattern) = pattern else {
+            continue;
+        };
+        match regex::Regex::new(pattern) {
+            Ok(regex) if regex.is_match(&body) != should_match => {
+                state = CheckState::Critical;
+                problems.push(if should_match {
+                    format!("body does not match /{}/", pattern)
+                } else {
+                    format!("body matches /{}/", pattern)
+                });
+            }
+            Ok(_) => {}
+            Err(e) => {
+                state = state.max(CheckState::Unknown);
+                problems.push(format!("invalid regex /{}/: {}", pattern, e));
+            }
+        }
+    }
+
+    let mut output = format!(
+        "HTTP {} in {:.0} ms, {} bytes",
+        status.as_u16(),
+        elapsed_ms,
+        body.len()
+    );
+    if !problems.is_empty() {
+        output.push_str(&format!(" - {}", problems.join(", ")));
+    }
+
+    CheckResult::new(
+        &check.name,
+        state,
+        output,
+        vec![
+            PerfData::new("time", elapsed_ms, "ms").thresholds(&check.response_time),
+            PerfData::new("size", body.len() as f64, "B"),
+        ],
+    )
+}
@@ -0,0 +1,201 @@
+// Invitations module for Crusty-Crawler
+// Admins invite people by email instead of choosing passwords for them. The invitee gets a
+// one-time signup code, picks their own username and password on /signup and is shown an
+// access token for the role the admin chose
+
+const INVITATION_LIFETIME_DAYS: i64 = 7;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Invitation {
+    // hash_access_token of the signup code, the code itself is only in the email
+    pub code_hash: String,
+    pub email: String,
+    pub role: UserRole,
+    pub invited_by: String,
+    pub created_at: String,
+    pub expires_at: String,
+}
+
+impl Invitation {
+    // Names the invitation in lists and DELETE /api/invitations/{id}
+    pub fn id(&self) -> &str {
+        access_token_id(&self.code_hash)
+    }
+
+    fn expired(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        chrono::DateTime::parse_from_rfc3339(&self.expires_at)
+            .is_ok_and(|expires_at| expires_at <= now)
+    }
+}
+
+// Body of POST /api/invitations
+#[derive(Deserialize)]
+pub struct InvitationRequest {
+    pub email: String,
+    // Least privilege unless the admin asks for more
+    #[serde(default = "invited_role")]
+    pub role: UserRole,
+}
+
+fn invited_role() -> UserRole {
+    UserRole::Viewer
+}
+
+// An Invitation as listed to admins
+#[derive(Serialize)]
+pub struct PendingInvitation {
+    pub id: String,
+    pub email: String,
+    pub role: UserRole,
+    pub invited_by: String,
+    pub created_at: String,
+    pub expires_at: String,
+}
+
+// Body of POST /api/signup
+#[derive(Deserialize)]
+pub struct Signup {
+    pub code: String,
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Serialize)]
+pub struct SignupResult {
+    pub username: String,
+    // Shown once on the signup page, only its hash is kept
+    pub access_token: String,
+}
+
+impl AuthManager {
+    // Emails the signup code to `email` and returns it, `base_url` is where the server is
+    // reachable
+    pub fn invite_user(
+        &mut self,
+        invited_by: &str,
+        email: &str,
+        role: UserRole,
+        base_url: &str,
+    ) -> Result<String, String> {
+        let email = email.trim();
+        if !email.contains('@') {
+            return Err("Please enter a valid email address".to_string());
+        }
+        if self
+            .config
+            .users
+            .values()
+            .any(|user| user.email.eq_ignore_ascii_case(email))
+        {
+            return Err("A user with that email address already exists".to_string());
+        }
+        let Some(smtp_config) = self.config.smtp_config.clone() else {
+            return Err("Email configuration not set up, invitations can't be sent".to_string());
+        };
+
+        let code = AuthManager::generate_suggested_token();
+        let now = self.clock.utc_now();
+        let expires_at = now + chrono::Duration::days(INVITATION_LIFETIME_DAYS);
+        let body = format!(
+            "Hello,\n\n\
+             {} invited you to Crusty Server.\n\n\
+             Open {}signup?code={} to choose your username and password,\n\
+             or enter this signup code there: {}\n\n\
+             The invitation expires on {}. If you weren't expecting it, please ignore this \
+             message.\n",
+            invited_by,
+            base_url,
+            code,
+            code,
+            expires_at.format("%Y-%m-%d %H:%M UTC")
+        );
+        self.mailer
+            .send(&smtp_config, email, "Your Crusty Server invitation", &body)?;
+
+        // Inviting the same address again replaces the earlier invitation
+        self.config.invitations.retain(|invitation| {
+            !invitation.email.eq_ignore_ascii_case(email) && !invitation.expired(now)
+        });
+        self.config.invitations.push(Invitation {
+            code_hash: hash_access_token(&code),
+            email: email.to_string(),
+            role,
+            invited_by: invited_by.to_string(),
+            created_at: now.to_rfc3339(),
+            expires_at: expires_at.to_rfc3339(),
+        });
+        self.save_config().map_err(|e| e.to_string())?;
+        record_event(
+            EventKind::Account,
+            &format!("{} invited {}", invited_by, email),
+            &format!("As {}", role.label()),
+        );
+        Ok(code)
+    }
+
+    pub fn pending_invitations(&self) -> Vec<PendingInvitation> {
+        let now = self.clock.utc_now();
+        self.config
+            .invitations
+            .iter()
+            .filter(|invitation| !invitation.expired(now))
+            .map(|invitation| PendingInvitation {
+                id: invitation.id().to_string(),
+                email: invitation.email.clone(),
+                role: invitation.role,
+                invited_by: invitation.invited_by.clone(),
+                created_at: invitation.created_at.clone(),
+                expires_at: invitation.expires_at.clone(),
+            })
+            .collect()
+    }
+
+    pub fn revoke_invitation(&mut self, id: &str) -> Result<bool, String> {
+        let before = self.config.invitations.len();
+        self.config
+            .invitations
+            .retain(|invitation| invitation.id() != id);
+        if self.config.invitations.len() == before {
+            return Ok(false);
+        }
+        self.save_config().map_err(|e| e.to_string())?;
+        Ok(true)
+    }
+
+    // Creates the invited user, the invitation can't be used again
+    pub fn accept_invitation(&mut self, signup: &Signup) -> Result<SignupResult, String> {
+        let now = self.clock.utc_now();
+        let position = self
+            .config
+            .invitations
+            .iter()
+            .position(|invitation| {
+                verify_access_token(signup.code.trim(), &invitation.code_hash)
+                    && !invitation.expired(now)
+            })
+            .ok_or("Invalid or expired signup code")?;
+
+        // Saved together with the new user, put back if the signup is refused
+        let invitation = self.config.invitations.remove(position);
+        let access_token = AuthManager::generate_suggested_token();
+        if let Err(e) = self.register_user_with_role(
+            &signup.username,
+            &signup.password,
+            &invitation.email,
+            &access_token,
+            invitation.role,
+        ) {
+            self.config.invitations.insert(position, invitation);
+            return Err(e);
+        }
+        record_event(
+            EventKind::Account,
+            &format!("{} joined", signup.username),
+            &format!("Invited by {}", invitation.invited_by),
+        );
+        Ok(SignupResult {
+            username: signup.username.clone(),
+            access_token,
+        })
+    }
+}
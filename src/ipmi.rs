@@ -0,0 +1,222 @@
+// IPMI module for Crusty-Crawler
+// Reads BMC sensors on server hardware through ipmitool: precise temperatures, fans,
+// voltages, PSU status and chassis intrusion
+
+// BMCs answer slowly, so one SDR read is shared by components, metrics and checks
+const IPMI_CACHE_SECS: u64 = 30;
+
+#[derive(Clone)]
+pub struct IpmiSensor {
+    pub name: String,
+    pub state: CheckState,
+    pub reading: Option<f64>,
+    pub unit: String,
+    // Event text of discrete sensors, e.g. "Presence detected, Failure detected"
+    pub event: String,
+}
+
+impl IpmiSensor {
+    fn sensor_kind(&self) -> Option<SensorKind> {
+        match self.unit.as_str() {
+            "degrees C" => Some(SensorKind::Temperature),
+            "RPM" => Some(SensorKind::Fan),
+            "Watts" => Some(SensorKind::Power),
+            "Volts" => Some(SensorKind::Voltage),
+            _ => None,
+        }
+    }
+}
+
+static IPMI_CACHE: Mutex<Option<(Instant, Vec<IpmiSensor>)>> = Mutex::new(None);
+
+// Discrete events ipmitool still reports with an "ok" status
+const IPMI_FAILURE_EVENTS: &[&str] = &[
+    "Failure detected",
+    "Predictive failure",
+    "Power Supply AC lost",
+    "AC out-of-range",
+    "General Chassis intrusion",
+    "Drive Fault",
+];
+
+#[cfg(target_os = "linux")]
+pub fn ipmi_available() -> bool {
+    ["/dev/ipmi0", "/dev/ipmi/0", "/dev/ipmidev/0"]
+        .iter()
+        .any(|device| Path::new(device).exists())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn ipmi_available() -> bool {
+    false
+}
+
+// Parses one "name | id | status | entity | reading" line of `ipmitool sdr elist`
+fn parse_sdr_line(line: &str) -> Option<IpmiSensor> {
+    let fields: Vec<&str> = line.split('|').map(|f| f.trim()).collect();
+    if fields.len() < 5 {
+        return None;
+    }
+
+    let (name, status, reading) = (fields[0], fields[2], fields[4]);
+    // "ns" means no reading, usually an unpopulated socket or PSU slot
+    if status == "ns" || name.is_empty() {
+        return None;
+    }
+
+    let mut state = match status {
+        "ok" => CheckState::Ok,
+        s if s.contains("nr") || s.contains("cr") => CheckState::Critical,
+        s if s.contains("nc") => CheckState::Warning,
+        _ => CheckState::Unknown,
+    };
+
+    let mut parts = reading.splitn(2, ' ');
+    let (value, unit, event) = match parts.next().and_then(|v| v.parse::<f64>().ok()) {
+        Some(value) => (
+            Some(value),
+            parts.next().unwrap_or_default().to_string(),
+            String::new(),
+        ),
+        None => (None, String::new(), reading.to_string()),
+    };
+
+    if IPMI_FAILURE_EVENTS
+        .iter()
+        .any(|failure| event.to_lowercase().contains(&failure.to_lowercase()))
+    {
+        state = CheckState::Critical;
+    }
+
+    Some(IpmiSensor {
+        name: name.to_string(),
+        state,
+        reading: value,
+        unit,
+        event,
+    })
+}
+
+fn query_ipmi_sensors() -> Result<Vec<IpmiSensor>, String> {
+    let output = std::process::Command::new("ipmitool")
+        .args(["sdr", "elist"])
+        .output()
+        .map_err(|e| format!("Failed to run ipmitool: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "ipmitool exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(parse_sdr_line)
+        .collect())
+}
+
+// BMC sensors, served from cache when the last read is recent
+pub fn read_ipmi_sensors() -> Result<Vec<IpmiSensor>, String> {
+    if !ipmi_available() {
+        return Ok(Vec::new());
+    }
+
+    let mut cache = IPMI_CACHE.lock().unwrap();
+    if let Some((read_at, sensors)) = cache.as_ref()
+        && read_at.elapsed() < Duration::from_secs(IPMI_CACHE_SECS)
+    {
+        return Ok(sensors.clone());
+    }
+
+    let sensors = query_ipmi_sensors()?;
+    *cache = Some((Instant::now(), sensors.clone()));
+    Ok(sensors)
+}
+
+pub fn ipmi_sensor_readings() -> Vec<SensorReading> {
+    match read_ipmi_sensors() {
+        Ok(sensors) => sensors
+            .iter()
+            .filter_map(|sensor| {
+                Some(SensorReading::new(
+                    &format!("BMC {}", sensor.name),
+                    sensor.sensor_kind()?,
+                    sensor.reading?,
+                ))
+            })
+            .collect(),
+        Err(e) => {
+            eprintln!("❌ Error reading IPMI sensors: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+// Discrete BMC sensors and anything out of range, for the thermal panel
+pub fn ipmi_thermal_status() -> Option<String> {
+    let sensors = read_ipmi_sensors().ok().filter(|s| !s.is_empty())?;
+    let mut output = format!("BMC Sensors: {}\n", sensors.len());
+
+    for sensor in &sensors {
+        if sensor.state != CheckState::Ok {
+            let reading = match sensor.reading {
+                Some(value) => format!("{} {}", value, sensor.unit),
+                None => sensor.event.clone(),
+            };
+            output.push_str(&format!(
+                "⚠️ {}: {} ({})\n",
+                sensor.name,
+                reading,
+                sensor.state.label()
+            ));
+        } else if sensor.reading.is_none() && !sensor.event.is_empty() {
+            output.push_str(&format!("{}: {}\n", sensor.name, sensor.event));
+        }
+    }
+
+    Some(output)
+}
+
+pub fn check_ipmi() -> CheckResult {
+    let sensors = match read_ipmi_sensors() {
+        Ok(sensors) if !sensors.is_empty() => sensors,
+        Ok(_) => {
+            return CheckResult::new(
+                "ipmi",
+                CheckState::Unknown,
+                "No BMC sensors found".to_string(),
+                Vec::new(),
+            );
+        }
+        Err(e) => return CheckResult::new("ipmi", CheckState::Unknown, e, Vec::new()),
+    };
+
+    let state = sensors
+        .iter()
+        .map(|s| s.state)
+        .max()
+        .unwrap_or(CheckState::Ok);
+    let failing: Vec<String> = sensors
+        .iter()
+        .filter(|s| s.state != CheckState::Ok)
+        .map(|s| format!("{} is {}", s.name, s.state.label()))
+        .collect();
+
+    let output = if failing.is_empty() {
+        format!("All {} BMC sensors OK", sensors.len())
+    } else {
+        failing.join(", ")
+    };
+
+    let perfdata = sensors
+        .iter()
+        .filter_map(|s| {
+            let label = s.name.to_lowercase().replace(' ', "_");
+            s.reading.map(|value| PerfData::new(&label, value, ""))
+        })
+        .collect();
+
+    CheckResult::new("ipmi", state, output, perfdata)
+}
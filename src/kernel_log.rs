@@ -0,0 +1,366 @@
+// Kernel log module for Crusty-Crawler
+// Scans the kernel messages in the journal for signs of failing hardware: disk I/O errors,
+// machine checks, USB resets and network links going down. Matches are counted per signature,
+// reported on the timeline and alerted on while recent. Linux only
+
+// Kept for /api/kernel-log
+const MAX_KERNEL_MATCHES: usize = 200;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct KernelSignature {
+    pub name: String,
+    // Case-sensitive substrings of the message, any one of them matches
+    pub contains: Vec<String>,
+    // CRITICAL instead of WARNING
+    #[serde(default)]
+    pub critical: bool,
+}
+
+impl KernelSignature {
+    fn new(name: &str, contains: &[&str], critical: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            contains: contains.iter().map(|text| text.to_string()).collect(),
+            critical,
+        }
+    }
+
+    fn matches(&self, message: &str) -> bool {
+        self.contains
+            .iter()
+            .any(|text| message.contains(text.as_str()))
+    }
+}
+
+fn builtin_kernel_signatures() -> Vec<KernelSignature> {
+    vec![
+        KernelSignature::new(
+            "io_error",
+            &["I/O error", "blk_update_request", "critical medium error"],
+            true,
+        ),
+        KernelSignature::new("mce", &["Machine check", "Hardware Error"], true),
+        KernelSignature::new(
+            "filesystem",
+            &[
+                "EXT4-fs error",
+                "BTRFS error",
+                "Corruption of in-memory data detected",
+            ],
+            true,
+        ),
+        // Unplugging is a plain disconnect, resets mean the device or port is misbehaving
+        KernelSignature::new(
+            "usb_reset",
+            &[
+                "reset high-speed USB device",
+                "reset full-speed USB device",
+                "reset SuperSpeed",
+            ],
+            false,
+        ),
+        KernelSignature::new("link_flap", &["Link is Down"], false),
+    ]
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct KernelLogConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    // The check stays alerting this long after the last match
+    pub alert_minutes: u64,
+    // Checked after the built-in signatures
+    pub signatures: Vec<KernelSignature>,
+}
+
+impl Default for KernelLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 60,
+            alert_minutes: 60,
+            signatures: Vec::new(),
+        }
+    }
+}
+
+impl KernelLogConfig {
+    fn all_signatures(&self) -> Vec<KernelSignature> {
+        let mut signatures = builtin_kernel_signatures();
+        signatures.extend(self.signatures.iter().cloned());
+        signatures
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_secs < 10 {
+            return Err("checks.kernel_log.interval_secs must be at least 10".to_string());
+        }
+        if self.alert_minutes == 0 {
+            return Err("checks.kernel_log.alert_minutes must be above 0".to_string());
+        }
+        for signature in &self.signatures {
+            if !valid_metric_name(&signature.name) {
+                return Err(format!(
+                    "checks.kernel_log.signatures: invalid name '{}', use letters, digits and \
+                     underscores",
+                    signature.name
+                ));
+            }
+            if signature.contains.iter().all(|text| text.is_empty()) {
+                return Err(format!(
+                    "checks.kernel_log.signatures '{}': needs some text to look for",
+                    signature.name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct KernelMatch {
+    pub signature: String,
+    pub critical: bool,
+    pub timestamp: String,
+    pub message: String,
+}
+
+// Response of GET /api/kernel-log
+#[derive(Serialize, Clone, Default)]
+pub struct KernelLogReport {
+    // Matches per signature since the agent started
+    pub counts: BTreeMap<String, u64>,
+    // Newest first
+    pub recent: Vec<KernelMatch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_scan: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// The first signature that matches, so a line is only counted once
+pub fn match_kernel_line(
+    signatures: &[KernelSignature],
+    line: &JournalLine,
+) -> Option<KernelMatch> {
+    let signature = signatures
+        .iter()
+        .find(|signature| signature.matches(&line.message))?;
+    Some(KernelMatch {
+        signature: signature.name.clone(),
+        critical: signature.critical,
+        timestamp: line.timestamp.to_rfc3339(),
+        message: line.message.clone(),
+    })
+}
+
+#[derive(Default)]
+struct KernelLogState {
+    counts: BTreeMap<String, u64>,
+    recent: VecDeque<KernelMatch>,
+    // Newest line seen, the next scan starts after it
+    last_line: Option<chrono::DateTime<chrono::Utc>>,
+    last_scan: Option<chrono::DateTime<chrono::Utc>>,
+    error: Option<String>,
+}
+
+static KERNEL_LOG: Mutex<Option<KernelLogState>> = Mutex::new(None);
+
+// The first scan looks back over the alert window to fill the check, only matches found after
+// that become events so restarts don't repeat them
+async fn scan_kernel_log(config: &KernelLogConfig) {
+    let now = chrono::Utc::now();
+    let (first, since) = match &*KERNEL_LOG.lock().unwrap() {
+        Some(KernelLogState {
+            last_scan: Some(last_scan),
+            last_line,
+            ..
+        }) => (false, last_line.unwrap_or(*last_scan)),
+        _ => (
+            true,
+            now - chrono::Duration::minutes(config.alert_minutes as i64),
+        ),
+    };
+    let lines = read_journal(since, &["_TRANSPORT=kernel"]).await;
+
+    let mut state = KERNEL_LOG.lock().unwrap();
+    let state = state.get_or_insert_with(KernelLogState::default);
+    state.last_scan = Some(now);
+    let lines = match lines {
+        Ok(lines) => lines,
+        Err(e) => {
+            state.error = Some(e);
+            return;
+        }
+    };
+    state.error = None;
+
+    let signatures = config.all_signatures();
+    let mut found: BTreeMap<String, (u64, KernelMatch)> = BTreeMap::new();
+    // --since has whole seconds, lines up to the last one seen come around again
+    let last_line = state.last_line;
+    for line in lines
+        .iter()
+        .filter(|line| last_line.is_none_or(|last| line.timestamp > last))
+    {
+        state.last_line = Some(line.timestamp);
+        let Some(matched) = match_kernel_line(&signatures, line) else {
+            continue;
+        };
+        *state.counts.entry(matched.signature.clone()).or_insert(0) += 1;
+        let entry = found
+            .entry(matched.signature.clone())
+            .or_insert((0, matched.clone()));
+        entry.0 += 1;
+        entry.1 = matched.clone();
+        if state.recent.len() == MAX_KERNEL_MATCHES {
+            state.recent.pop_front();
+        }
+        state.recent.push_back(matched);
+    }
+    if first {
+        return;
+    }
+    // One event per signature and scan, a flapping link would flood the timeline otherwise
+    for (signature, (count, latest)) in found {
+        record_event(
+            EventKind::Kernel,
+            &format!("{} kernel {} message(s)", count, signature),
+            &latest.message,
+        );
+    }
+}
+
+pub fn kernel_log_report() -> KernelLogReport {
+    let state = KERNEL_LOG.lock().unwrap();
+    let Some(state) = state.as_ref() else {
+        return KernelLogReport::default();
+    };
+    KernelLogReport {
+        counts: state.counts.clone(),
+        recent: state.recent.iter().rev().cloned().collect(),
+        last_scan: state.last_scan.map(|scan| scan.to_rfc3339()),
+        error: state.error.clone(),
+    }
+}
+
+pub fn kernel_log_metrics() -> Vec<Metric> {
+    kernel_log_report()
+        .counts
+        .iter()
+        .map(|(signature, count)| {
+            Metric::new("kernel_log_matches_total", *count as f64).label("signature", signature)
+        })
+        .collect()
+}
+
+pub fn kernel_log_available(config: &KernelLogConfig) -> bool {
+    cfg!(target_os = "linux") && config.enabled
+}
+
+// CRITICAL while a critical signature matched within the alert window, WARNING for the others
+pub fn kernel_log_check_result(
+    matches: &[KernelMatch],
+    config: &KernelLogConfig,
+    now: chrono::DateTime<chrono::Utc>,
+) -> CheckResult {
+    let cutoff = now - chrono::Duration::minutes(config.alert_minutes as i64);
+    let recent: Vec<&KernelMatch> = matches
+        .iter()
+        .filter(|matched| {
+            chrono::DateTime::parse_from_rfc3339(&matched.timestamp)
+                .is_ok_and(|timestamp| timestamp >= cutoff)
+        })
+        .collect();
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for matched in &recent {
+        *counts.entry(matched.signature.as_str()).or_insert(0) += 1;
+    }
+    let perfdata = counts
+        .iter()
+        .map(|(signature, count)| PerfData::new(signature, *count as f64, ""))
+        .collect();
+    let Some(latest) = recent.first() else {
+        return CheckResult::new(
+            "kernel_log",
+            CheckState::Ok,
+            format!(
+                "No hardware errors in the kernel log in the last {} minutes",
+                config.alert_minutes
+            ),
+            perfdata,
+        );
+    };
+
+    let state = if recent.iter().any(|matched| matched.critical) {
+        CheckState::Critical
+    } else {
+        CheckState::Warning
+    };
+    let summary: Vec<String> = counts
+        .iter()
+        .map(|(signature, count)| format!("{} {}", count, signature))
+        .collect();
+    CheckResult::new(
+        "kernel_log",
+        state,
+        format!(
+            "Kernel log in the last {} minutes: {}, latest: {}",
+            config.alert_minutes,
+            summary.join(", "),
+            latest.message
+        ),
+        perfdata,
+    )
+}
+
+fn check_kernel_log(config: &KernelLogConfig) -> CheckResult {
+    let report = kernel_log_report();
+    if let Some(error) = &report.error {
+        return CheckResult::new(
+            "kernel_log",
+            CheckState::Unknown,
+            format!("Can't scan the kernel log: {}", error),
+            Vec::new(),
+        );
+    }
+    if report.last_scan.is_none() {
+        return CheckResult::new(
+            "kernel_log",
+            CheckState::Unknown,
+            "The kernel log hasn't been scanned yet".to_string(),
+            Vec::new(),
+        );
+    }
+    kernel_log_check_result(&report.recent, config, chrono::Utc::now())
+}
+
+fn spawn_kernel_log_scanner(server_state: Arc<Mutex<ServerState>>) {
+    if !cfg!(target_os = "linux") {
+        return;
+    }
+    std::thread::spawn(move || {
+        let rt = match Runtime::new() {
+            Ok(rt) => rt,
+            Err(e) => {
+                eprintln!("❌ Failed to start kernel log scanner: {}", e);
+                return;
+            }
+        };
+
+        rt.block_on(async {
+            loop {
+                let config = {
+                    let state = server_state.lock().unwrap();
+                    let auth_manager = state.auth_manager.lock().unwrap();
+                    auth_manager.config.checks.kernel_log.clone()
+                };
+                if config.enabled {
+                    scan_kernel_log(&config).await;
+                }
+                tokio::time::sleep(Duration::from_secs(config.interval_secs.max(10))).await;
+            }
+        });
+    });
+}
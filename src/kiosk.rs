@@ -0,0 +1,145 @@
+// Kiosk module for Crusty-Crawler
+// Tokens for wall-mounted displays. They belong to no user and only open the dashboard and
+// status pages, so one left in a hallway browser can't export history or reach admin routes
+
+// Dashboard panels a kiosk shows, the rest need a user's token
+const KIOSK_PANELS: &[&str] = &["status"];
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct KioskToken {
+    // Where the display hangs, e.g. "Server room"
+    pub name: String,
+    // hash_access_token of the token, shown only when it's created
+    pub token_hash: String,
+    pub created_by: String,
+    pub created_at: String,
+}
+
+impl KioskToken {
+    pub fn id(&self) -> &str {
+        access_token_id(&self.token_hash)
+    }
+}
+
+// A KioskToken as listed in the GUI
+pub struct KioskTokenInfo {
+    pub id: String,
+    pub name: String,
+    pub created_by: String,
+    pub created_at: String,
+    pub usage: CredentialUsage,
+}
+
+// By token id, kept in memory like web session usage
+static KIOSK_USAGE: Mutex<BTreeMap<String, CredentialUsage>> = Mutex::new(BTreeMap::new());
+
+impl AuthManager {
+    pub fn create_kiosk_token(&mut self, name: &str, created_by: &str) -> Result<String, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Give the display a name".to_string());
+        }
+        if self
+            .config
+            .kiosk_tokens
+            .iter()
+            .any(|kiosk| kiosk.name.eq_ignore_ascii_case(name))
+        {
+            return Err(format!("There is already a kiosk token named '{}'", name));
+        }
+
+        let token = AuthManager::generate_suggested_token();
+        self.config.kiosk_tokens.push(KioskToken {
+            name: name.to_string(),
+            token_hash: hash_access_token(&token),
+            created_by: created_by.to_string(),
+            created_at: self.clock.utc_now().to_rfc3339(),
+        });
+        self.save_config().map_err(|e| e.to_string())?;
+        record_event(
+            EventKind::Account,
+            &format!("{} created kiosk token '{}'", created_by, name),
+            "",
+        );
+        Ok(token)
+    }
+
+    pub fn kiosk_tokens(&self) -> Vec<KioskTokenInfo> {
+        let usage = KIOSK_USAGE.lock().unwrap();
+        self.config
+            .kiosk_tokens
+            .iter()
+            .map(|kiosk| KioskTokenInfo {
+                id: kiosk.id().to_string(),
+                name: kiosk.name.clone(),
+                created_by: kiosk.created_by.clone(),
+                created_at: kiosk.created_at.clone(),
+                usage: usage.get(kiosk.id()).cloned().unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    pub fn revoke_kiosk_token(&mut self, id: &str, revoked_by: &str) -> Result<bool, String> {
+        let Some(position) = self
+            .config
+            .kiosk_tokens
+            .iter()
+            .position(|kiosk| kiosk.id() == id)
+        else {
+            return Ok(false);
+        };
+        let kiosk = self.config.kiosk_tokens.remove(position);
+        self.save_config().map_err(|e| e.to_string())?;
+        KIOSK_USAGE.lock().unwrap().remove(id);
+        record_event(
+            EventKind::Account,
+            &format!("{} revoked kiosk token '{}'", revoked_by, kiosk.name),
+            "",
+        );
+        Ok(true)
+    }
+
+    fn kiosk_token(&self, token: &str) -> Option<&KioskToken> {
+        self.config
+            .kiosk_tokens
+            .iter()
+            .find(|kiosk| verify_access_token(token, &kiosk.token_hash))
+    }
+
+    pub fn is_kiosk_token(&self, token: &str) -> bool {
+        self.kiosk_token(token).is_some()
+    }
+
+    // The display's name if `token` is a kiosk token, recording the use
+    pub fn authenticate_kiosk_token(
+        &self,
+        token: &str,
+        ip: Option<std::net::IpAddr>,
+    ) -> Option<String> {
+        let kiosk = self.kiosk_token(token)?;
+        KIOSK_USAGE
+            .lock()
+            .unwrap()
+            .entry(kiosk.id().to_string())
+            .or_default()
+            .record(self.clock.utc_now(), ip);
+        Some(kiosk.name.clone())
+    }
+
+    // The admin default with everything but KIOSK_PANELS hidden
+    pub fn kiosk_layout(&self) -> UserDashboardLayout {
+        let mut layout = self
+            .config
+            .dashboard
+            .default_layout
+            .clone()
+            .with_missing_panels();
+        for panel in &mut layout.panels {
+            panel.visible = panel.visible && KIOSK_PANELS.contains(&panel.id.as_str());
+        }
+        UserDashboardLayout {
+            layout,
+            saved: false,
+        }
+    }
+}
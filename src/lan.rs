@@ -0,0 +1,172 @@
+// LAN module for Crusty-Crawler
+// Finds the machine's network addresses so the GUI, CLI and login page can show real URLs
+// instead of asking the user to look up their IP
+
+// The GUI asks every frame, interfaces are re-read at most this often
+const LAN_REFRESH: Duration = Duration::from_secs(10);
+
+#[derive(Clone, PartialEq)]
+pub struct LanAddress {
+    pub interface: String,
+    pub ip: std::net::IpAddr,
+}
+
+impl LanAddress {
+    // IPv6 addresses get their brackets from SocketAddr
+    pub fn url(&self, port: u16) -> String {
+        format!("http://{}/", SocketAddr::new(self.ip, port))
+    }
+}
+
+static LAN_ADDRESSES: Mutex<Option<(Instant, Vec<LanAddress>)>> = Mutex::new(None);
+
+// Address of the interface that routes off this machine, nothing is actually sent. IPv6-only
+// networks have no IPv4 route, so the documentation prefix of each family is tried in turn
+fn routed_ip() -> Option<std::net::IpAddr> {
+    [("0.0.0.0:0", "192.0.2.1:9"), ("[::]:0", "[2001:db8::1]:9")]
+        .iter()
+        .find_map(|(local, remote)| {
+            let socket = std::net::UdpSocket::bind(local).ok()?;
+            socket.connect(remote).ok()?;
+            Some(socket.local_addr().ok()?.ip())
+        })
+}
+
+fn reachable_ip(ip: &std::net::IpAddr) -> bool {
+    match ip {
+        std::net::IpAddr::V4(v4) => {
+            !v4.is_loopback() && !v4.is_unspecified() && !v4.is_link_local()
+        }
+        // Link-local addresses need a zone in the URL, which browsers don't accept
+        std::net::IpAddr::V6(v6) => {
+            !v6.is_loopback()
+                && !v6.is_unspecified()
+                && !v6.is_multicast()
+                && (v6.segments()[0] & 0xffc0) != 0xfe80
+        }
+    }
+}
+
+fn detect_lan_addresses() -> Vec<LanAddress> {
+    let networks = Networks::new_with_refreshed_list();
+    let mut addresses: Vec<LanAddress> = networks
+        .iter()
+        .flat_map(|(interface, data)| {
+            data.ip_networks()
+                .iter()
+                .filter(|network| reachable_ip(&network.addr))
+                .map(|network| LanAddress {
+                    interface: interface.clone(),
+                    ip: network.addr,
+                })
+        })
+        .collect();
+
+    // The address other machines most likely reach us on first, then IPv4 before IPv6
+    let routed = routed_ip();
+    addresses.sort_by_key(|address| {
+        (
+            Some(address.ip) != routed,
+            address.ip.is_ipv6(),
+            address.interface.clone(),
+            address.ip,
+        )
+    });
+    addresses.dedup();
+    addresses
+}
+
+pub fn lan_addresses() -> Vec<LanAddress> {
+    let mut cache = LAN_ADDRESSES.lock().unwrap();
+    match &*cache {
+        Some((checked_at, addresses)) if checked_at.elapsed() < LAN_REFRESH => addresses.clone(),
+        _ => {
+            let addresses = detect_lan_addresses();
+            *cache = Some((Instant::now(), addresses.clone()));
+            addresses
+        }
+    }
+}
+
+// How other devices reach the server, for links that are opened elsewhere like invitation
+// emails and kiosk displays
+pub fn lan_base_url(port: u16, tls: bool) -> String {
+    let url = lan_addresses()
+        .first()
+        .map(|address| address.url(port))
+        .unwrap_or_else(|| format!("http://localhost:{}/", port));
+    if tls {
+        url.replacen("http://", "https://", 1)
+    } else {
+        url
+    }
+}
+
+// One line per address for console output
+fn print_lan_urls(port: u16) {
+    println!("📍 Local:   http://localhost:{}/", port);
+    let addresses = lan_addresses();
+    if addresses.is_empty() {
+        println!("🌐 Network: no network addresses found");
+    }
+    for address in addresses {
+        println!("🌐 Network: {} ({})", address.url(port), address.interface);
+    }
+}
+
+// Interface names come from the OS and end up in the login page
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Configured hosts may be written as [::1] like in a URL, clients want the bare address
+fn bare_host(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host)
+}
+
+// host:port with brackets around IPv6 literals
+fn host_port(host: &str, port: u16) -> String {
+    let host = bare_host(host);
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+fn bind_socket(addr: SocketAddr) -> io::Result<tokio::net::TcpListener> {
+    let socket = socket2::Socket::new(
+        socket2::Domain::for_address(addr),
+        socket2::Type::STREAM,
+        Some(socket2::Protocol::TCP),
+    )?;
+    // Windows makes IPv6 sockets IPv6-only unless told otherwise
+    if addr.is_ipv6() && addr.ip().is_unspecified() {
+        socket.set_only_v6(false)?;
+    }
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    tokio::net::TcpListener::from_std(socket.into())
+}
+
+// [::] accepts IPv4 clients too, as ::ffff:a.b.c.d. Hosts with IPv6 disabled fall back to
+// 0.0.0.0 so binding the wildcard address keeps working everywhere
+fn bind_tcp_listener(addr: SocketAddr) -> io::Result<tokio::net::TcpListener> {
+    match bind_socket(addr) {
+        Err(e)
+            if addr.ip() == std::net::IpAddr::V6(std::net::Ipv6Addr::UNSPECIFIED)
+                && e.kind() != io::ErrorKind::AddrInUse =>
+        {
+            bind_socket(SocketAddr::from(([0, 0, 0, 0], addr.port())))
+        }
+        result => result,
+    }
+}
@@ -0,0 +1,153 @@
+// Latency module for Crusty-Crawler
+// Per-route request duration histograms for the self-metrics, a log line for every request
+// over logging.slow_request_ms and the 50 slowest requests since start at /api/self/slow
+
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+const MAX_SLOW_REQUESTS: usize = 50;
+
+#[derive(Default)]
+struct RouteLatency {
+    // Requests at or below each bucket's bound, not cumulative
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum_secs: f64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct SlowRequest {
+    pub at: String,
+    pub method: String,
+    pub route: String,
+    // Without the query string, it can carry a token
+    pub path: String,
+    pub status: u16,
+    pub duration_ms: f64,
+}
+
+#[derive(Serialize)]
+pub struct SlowRequestReport {
+    pub threshold_ms: u64,
+    // Slowest first
+    pub requests: Vec<SlowRequest>,
+}
+
+// Keyed by (method, route pattern)
+static ROUTE_LATENCY: Mutex<BTreeMap<(String, String), RouteLatency>> = Mutex::new(BTreeMap::new());
+static SLOWEST_REQUESTS: Mutex<Vec<SlowRequest>> = Mutex::new(Vec::new());
+
+fn record_latency(method: &str, route: &str, duration: Duration) {
+    let secs = duration.as_secs_f64();
+    let mut latency = ROUTE_LATENCY.lock().unwrap();
+    let entry = latency
+        .entry((method.to_string(), route.to_string()))
+        .or_default();
+    entry.count += 1;
+    entry.sum_secs += secs;
+    if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| secs <= *bound) {
+        entry.buckets[bucket] += 1;
+    }
+}
+
+fn record_slow_request(request: SlowRequest) {
+    let mut slowest = SLOWEST_REQUESTS.lock().unwrap();
+    if slowest.len() == MAX_SLOW_REQUESTS
+        && slowest
+            .last()
+            .is_some_and(|fastest| fastest.duration_ms >= request.duration_ms)
+    {
+        return;
+    }
+    let index = slowest.partition_point(|slow| slow.duration_ms >= request.duration_ms);
+    slowest.insert(index, request);
+    slowest.truncate(MAX_SLOW_REQUESTS);
+}
+
+// Outermost layer, so the time includes every other middleware
+async fn time_request(
+    axum::extract::State(server_state): axum::extract::State<Arc<Mutex<ServerState>>>,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<axum::extract::MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        // Static files would add one series per file
+        .unwrap_or_else(|| "static".to_string());
+    let path = request.uri().path().to_string();
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let duration = started.elapsed();
+    record_latency(&method, &route, duration);
+
+    let duration_ms = duration.as_secs_f64() * 1000.0;
+    let threshold_ms = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.logging.slow_request_ms
+    };
+    if threshold_ms > 0 && duration_ms >= threshold_ms as f64 {
+        println!(
+            "🐢 Slow request: {} {} took {:.0} ms ({})",
+            method,
+            path,
+            duration_ms,
+            response.status().as_u16()
+        );
+    }
+    record_slow_request(SlowRequest {
+        at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        method,
+        route,
+        path,
+        status: response.status().as_u16(),
+        duration_ms,
+    });
+    response
+}
+
+// Prometheus histogram series: cumulative _bucket{le} counts, _sum and _count per route
+pub fn http_latency_metrics() -> Vec<Metric> {
+    let latency = ROUTE_LATENCY.lock().unwrap();
+    let mut metrics = Vec::new();
+    for ((method, route), entry) in latency.iter() {
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(entry.buckets) {
+            cumulative += count;
+            metrics.push(
+                Metric::new("http_request_duration_seconds_bucket", cumulative as f64)
+                    .label("method", method)
+                    .label("route", route)
+                    .label("le", &bound.to_string()),
+            );
+        }
+        metrics.push(
+            Metric::new("http_request_duration_seconds_bucket", entry.count as f64)
+                .label("method", method)
+                .label("route", route)
+                .label("le", "+Inf"),
+        );
+        metrics.push(
+            Metric::new("http_request_duration_seconds_sum", entry.sum_secs)
+                .label("method", method)
+                .label("route", route),
+        );
+        metrics.push(
+            Metric::new("http_request_duration_seconds_count", entry.count as f64)
+                .label("method", method)
+                .label("route", route),
+        );
+    }
+    metrics
+}
+
+pub fn slow_request_report(threshold_ms: u64) -> SlowRequestReport {
+    SlowRequestReport {
+        threshold_ms,
+        requests: SLOWEST_REQUESTS.lock().unwrap().clone(),
+    }
+}
@@ -0,0 +1,154 @@
+// Layouts module for Crusty-Crawler
+// Per-user arrangement of the web dashboard panels, stored with the user in crusty_auth.json,
+// with an admin-defined default for users who never saved their own
+
+// Panels the web dashboard knows how to draw
+const DASHBOARD_PANELS: &[&str] = &["status", "history", "events", "connections", "fleet"];
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct PanelLayout {
+    pub id: String,
+    #[serde(default = "default_panel_visible")]
+    pub visible: bool,
+    // Grid columns out of 2
+    #[serde(default = "default_panel_columns")]
+    pub columns: u8,
+    // Pixels, None lets the panel size itself
+    #[serde(default)]
+    pub height: Option<u32>,
+}
+
+fn default_panel_visible() -> bool {
+    true
+}
+
+fn default_panel_columns() -> u8 {
+    2
+}
+
+// Panels are drawn in list order
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct DashboardLayout {
+    pub panels: Vec<PanelLayout>,
+}
+
+impl Default for DashboardLayout {
+    fn default() -> Self {
+        Self {
+            panels: DASHBOARD_PANELS
+                .iter()
+                .map(|id| PanelLayout {
+                    id: id.to_string(),
+                    visible: true,
+                    columns: 2,
+                    height: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct DashboardConfig {
+    // Used for everyone without a saved layout
+    pub default_layout: DashboardLayout,
+}
+
+impl DashboardLayout {
+    pub fn validate(&self) -> Result<(), String> {
+        let mut seen = Vec::new();
+        for panel in &self.panels {
+            if !DASHBOARD_PANELS.contains(&panel.id.as_str()) {
+                return Err(format!(
+                    "Unknown panel '{}', expected one of {}",
+                    panel.id,
+                    DASHBOARD_PANELS.join(", ")
+                ));
+            }
+            if seen.contains(&panel.id) {
+                return Err(format!("Panel '{}' is listed twice", panel.id));
+            }
+            if !(1..=2).contains(&panel.columns) {
+                return Err(format!("Panel '{}' must span 1 or 2 columns", panel.id));
+            }
+            if let Some(height) = panel.height
+                && !(80..=2000).contains(&height)
+            {
+                return Err(format!(
+                    "Panel '{}' height must be between 80 and 2000 pixels",
+                    panel.id
+                ));
+            }
+            seen.push(panel.id.clone());
+        }
+        Ok(())
+    }
+
+    // Layouts saved before a panel existed get it appended, so new panels aren't invisible
+    fn with_missing_panels(mut self) -> Self {
+        for id in DASHBOARD_PANELS {
+            if !self.panels.iter().any(|panel| panel.id == *id) {
+                self.panels.push(PanelLayout {
+                    id: id.to_string(),
+                    visible: true,
+                    columns: 2,
+                    height: None,
+                });
+            }
+        }
+        self
+    }
+}
+
+#[derive(Serialize)]
+pub struct UserDashboardLayout {
+    #[serde(flatten)]
+    pub layout: DashboardLayout,
+    // False while the user is on the admin default
+    pub saved: bool,
+}
+
+impl AuthManager {
+    pub fn dashboard_layout(&self, username: &str) -> UserDashboardLayout {
+        match self
+            .config
+            .users
+            .get(username)
+            .and_then(|user| user.dashboard_layout.clone())
+        {
+            Some(layout) => UserDashboardLayout {
+                layout: layout.with_missing_panels(),
+                saved: true,
+            },
+            None => UserDashboardLayout {
+                layout: self
+                    .config
+                    .dashboard
+                    .default_layout
+                    .clone()
+                    .with_missing_panels(),
+                saved: false,
+            },
+        }
+    }
+
+    // None goes back to the admin default
+    pub fn set_dashboard_layout(
+        &mut self,
+        username: &str,
+        layout: Option<DashboardLayout>,
+    ) -> Result<(), String> {
+        if let Some(layout) = &layout {
+            layout.validate()?;
+        }
+        let user = self
+            .config
+            .users
+            .get_mut(username)
+            .ok_or("User not found")?;
+        user.dashboard_layout = layout;
+        self.save_config().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+// Limits module for Crusty-Crawler
+// Usage of the kernel limits behind the "mysterious" failures: open file descriptors against
+// fs.file-max, PIDs against kernel.pid_max, conntrack entries against nf_conntrack_max, and the
+// entropy pool. Linux only
+
+// A used count against its limit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LimitUsage {
+    pub used: u64,
+    pub max: u64,
+}
+
+impl LimitUsage {
+    pub fn percent(&self) -> f64 {
+        if self.max == 0 {
+            0.0
+        } else {
+            self.used as f64 / self.max as f64 * 100.0
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct KernelLimits {
+    pub file_descriptors: Option<LimitUsage>,
+    // Threads count against pid_max, they take their IDs from the same space
+    pub pids: Option<LimitUsage>,
+    // Only while the netfilter conntrack module is loaded
+    pub conntrack: Option<LimitUsage>,
+    // Bits, kernels since 5.18 always report 256
+    pub entropy_bits: Option<u64>,
+}
+
+impl KernelLimits {
+    fn usages(&self) -> Vec<(&'static str, LimitUsage)> {
+        [
+            ("file_descriptors", self.file_descriptors),
+            ("pids", self.pids),
+            ("conntrack", self.conntrack),
+        ]
+        .into_iter()
+        .filter_map(|(name, usage)| usage.map(|usage| (name, usage)))
+        .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.usages().is_empty() && self.entropy_bits.is_none()
+    }
+}
+
+// /proc/sys/fs/file-nr is "allocated unused max", unused is always 0 on current kernels
+fn parse_file_nr(content: &str) -> Option<LimitUsage> {
+    let fields: Vec<u64> = content
+        .split_whitespace()
+        .map(|field| field.parse().ok())
+        .collect::<Option<_>>()?;
+    let [allocated, unused, max] = fields[..] else {
+        return None;
+    };
+    Some(LimitUsage {
+        used: allocated.saturating_sub(unused),
+        max,
+    })
+}
+
+// The fourth field of /proc/loadavg is "running/total" scheduling entities
+fn parse_loadavg_threads(content: &str) -> Option<u64> {
+    content
+        .split_whitespace()
+        .nth(3)?
+        .split_once('/')?
+        .1
+        .parse()
+        .ok()
+}
+
+#[cfg(target_os = "linux")]
+pub fn read_kernel_limits() -> KernelLimits {
+    let read = |path: &str| fs::read_to_string(path).ok();
+    let number = |path: &str| read(path).and_then(|content| content.trim().parse::<u64>().ok());
+    let usage = |used: Option<u64>, max: Option<u64>| {
+        Some(LimitUsage {
+            used: used?,
+            max: max?,
+        })
+    };
+    KernelLimits {
+        file_descriptors: read("/proc/sys/fs/file-nr").and_then(|content| parse_file_nr(&content)),
+        pids: usage(
+            read("/proc/loadavg").and_then(|content| parse_loadavg_threads(&content)),
+            number("/proc/sys/kernel/pid_max"),
+        ),
+        conntrack: usage(
+            number("/proc/sys/net/netfilter/nf_conntrack_count"),
+            number("/proc/sys/net/netfilter/nf_conntrack_max"),
+        ),
+        entropy_bits: number("/proc/sys/kernel/random/entropy_avail"),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_kernel_limits() -> KernelLimits {
+    KernelLimits::default()
+}
+
+pub fn kernel_limit_metrics(limits: &KernelLimits) -> Vec<Metric> {
+    let mut metrics = Vec::new();
+    for (name, usage) in limits.usages() {
+        metrics.push(Metric::new(
+            &format!("kernel_{}_used", name),
+            usage.used as f64,
+        ));
+        metrics.push(Metric::new(
+            &format!("kernel_{}_max", name),
+            usage.max as f64,
+        ));
+    }
+    if let Some(bits) = limits.entropy_bits {
+        metrics.push(Metric::new("kernel_entropy_available_bits", bits as f64));
+    }
+    metrics
+}
+
+pub fn kernel_limits_result(
+    limits: &KernelLimits,
+    usage_thresholds: &Thresholds,
+    entropy_thresholds: &Thresholds,
+) -> CheckResult {
+    let mut state = CheckState::Ok;
+    let mut summaries = Vec::new();
+    let mut perfdata = Vec::new();
+    for (name, usage) in limits.usages() {
+        let percent = usage.percent();
+        state = state.max(usage_thresholds.evaluate(percent));
+        summaries.push(format!(
+            "{} {}/{} ({:.1}%)",
+            name.replace('_', " "),
+            usage.used,
+            usage.max,
+            percent
+        ));
+        perfdata.push(
+            PerfData::new(name, percent, "%")
+                .thresholds(usage_thresholds)
+                .range(0.0, 100.0),
+        );
+    }
+    if let Some(bits) = limits.entropy_bits {
+        state = state.max(entropy_thresholds.evaluate_below(bits as f64));
+        summaries.push(format!("entropy {} bits", bits));
+        perfdata.push(PerfData::new("entropy", bits as f64, "").thresholds(entropy_thresholds));
+    }
+    CheckResult::new(
+        "kernel_limits",
+        state,
+        format!("Kernel limits: {}", summaries.join(", ")),
+        perfdata,
+    )
+}
+
+fn check_kernel_limits(config: &CheckConfig) -> CheckResult {
+    let limits = read_kernel_limits();
+    if limits.is_empty() {
+        return CheckResult::new(
+            "kernel_limits",
+            CheckState::Unknown,
+            "No kernel limits could be read".to_string(),
+            Vec::new(),
+        );
+    }
+    kernel_limits_result(&limits, &config.kernel_limits, &config.entropy)
+}
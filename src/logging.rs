@@ -0,0 +1,319 @@
+// Logging module for Crusty-Crawler
+// Copies everything the daemon prints into a log file with size and age based rotation,
+// so messages survive when nobody is watching stdout
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LoggingConfig {
+    // Only used by daemon mode, interactive modes keep printing to the terminal
+    pub enabled: bool,
+    pub file: String,
+    // Rotate once the file is this big, 0 disables size based rotation
+    pub max_size_mb: u64,
+    // Rotate once the file is this old, 0 disables time based rotation
+    pub rotate_every_hours: u64,
+    // Rotated files kept next to the live one as <file>.1 (newest) to <file>.N
+    pub keep_files: usize,
+    // HTTP requests slower than this are logged, 0 disables the log line
+    pub slow_request_ms: u64,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            file: "crusty-crawler.log".to_string(),
+            max_size_mb: 10,
+            rotate_every_hours: 24,
+            keep_files: 5,
+            slow_request_ms: 1000,
+        }
+    }
+}
+
+struct RotatingLog {
+    config: LoggingConfig,
+    file: fs::File,
+    size: u64,
+    opened_at: Instant,
+}
+
+impl RotatingLog {
+    // A relative file name is kept in the data directory
+    fn open(mut config: LoggingConfig) -> io::Result<Self> {
+        config.file = data_file(&config.file);
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.file)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            config,
+            file,
+            size,
+            opened_at: Instant::now(),
+        })
+    }
+
+    fn rotation_due(&self) -> bool {
+        let max_size = self.config.max_size_mb * 1024 * 1024;
+        let max_age = Duration::from_secs(self.config.rotate_every_hours * 3600);
+        (max_size > 0 && self.size >= max_size)
+            || (!max_age.is_zero() && self.opened_at.elapsed() >= max_age)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let path = &self.config.file;
+        let keep = self.config.keep_files;
+        if keep == 0 {
+            let _ = fs::remove_file(path);
+        } else {
+            let _ = fs::remove_file(format!("{}.{}", path, keep));
+            for index in (1..keep).rev() {
+                let _ = fs::rename(
+                    format!("{}.{}", path, index),
+                    format!("{}.{}", path, index + 1),
+                );
+            }
+            fs::rename(path, format!("{}.1", path))?;
+        }
+        *self = Self::open(self.config.clone())?;
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.size > 0 && self.rotation_due() {
+            self.rotate()?;
+        }
+        let entry = format!(
+            "{} {}\n",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+            line
+        );
+        self.file.write_all(entry.as_bytes())?;
+        self.size += entry.len() as u64;
+        Ok(())
+    }
+}
+
+// Keeps stdout and stderr redirected into the log until dropped
+pub struct LogCapture {
+    #[cfg(unix)]
+    saved_stdout: i32,
+    #[cfg(unix)]
+    saved_stderr: i32,
+    writers: Vec<std::thread::JoinHandle<()>>,
+}
+
+#[cfg(unix)]
+mod log_fds {
+    unsafe extern "C" {
+        pub fn pipe(fds: *mut i32) -> i32;
+        pub fn dup(fd: i32) -> i32;
+        pub fn dup2(old: i32, new: i32) -> i32;
+        pub fn close(fd: i32) -> i32;
+    }
+}
+
+#[cfg(unix)]
+pub fn start_file_logging(config: &LoggingConfig) -> Result<LogCapture, String> {
+    use std::os::fd::FromRawFd;
+
+    let mut log = RotatingLog::open(config.clone())
+        .map_err(|e| format!("Failed to open log file {}: {}", data_file(&config.file), e))?;
+
+    let mut fds = [0; 2];
+    if unsafe { log_fds::pipe(fds.as_mut_ptr()) } == -1 {
+        return Err(format!("pipe failed: {}", io::Error::last_os_error()));
+    }
+    let (read_fd, write_fd) = (fds[0], fds[1]);
+
+    io::stdout().flush().ok();
+    let (saved_stdout, saved_stderr) = unsafe { (log_fds::dup(1), log_fds::dup(2)) };
+    unsafe {
+        log_fds::dup2(write_fd, 1);
+        log_fds::dup2(write_fd, 2);
+        log_fds::close(write_fd);
+    }
+
+    // Still echo to the original stdout so journald and terminals keep seeing output
+    let mut echo = unsafe { fs::File::from_raw_fd(log_fds::dup(saved_stdout)) };
+    let reader = unsafe { fs::File::from_raw_fd(read_fd) };
+    let writer = std::thread::spawn(move || {
+        use std::io::BufRead;
+
+        for line in io::BufReader::new(reader).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            let _ = writeln!(echo, "{}", line);
+            if let Err(e) = log.write_line(&line) {
+                let _ = writeln!(echo, "❌ Failed to write log file: {}", e);
+            }
+        }
+    });
+
+    Ok(LogCapture {
+        saved_stdout,
+        saved_stderr,
+        writers: vec![writer],
+    })
+}
+
+// One JSON object per line for container log drivers. Everything printed to stderr is an
+// error unless it carries the warning sign
+fn json_log_line(stderr: bool, line: &str) -> String {
+    let level = match (stderr, line.starts_with('⚠')) {
+        (false, _) => "info",
+        (true, true) => "warn",
+        (true, false) => "error",
+    };
+    serde_json::json!({
+        "time": chrono::Utc::now().to_rfc3339(),
+        "level": level,
+        "message": line.trim(),
+    })
+    .to_string()
+}
+
+// Rewrites stdout and stderr as JSON lines on the original stdout until dropped
+#[cfg(unix)]
+pub fn start_json_logging() -> Result<LogCapture, String> {
+    use std::os::fd::FromRawFd;
+
+    io::stdout().flush().ok();
+    let (saved_stdout, saved_stderr) = unsafe { (log_fds::dup(1), log_fds::dup(2)) };
+    let out = Arc::new(Mutex::new(unsafe {
+        fs::File::from_raw_fd(log_fds::dup(saved_stdout))
+    }));
+
+    let mut writers = Vec::new();
+    for fd in [1, 2] {
+        let mut fds = [0; 2];
+        if unsafe { log_fds::pipe(fds.as_mut_ptr()) } == -1 {
+            return Err(format!("pipe failed: {}", io::Error::last_os_error()));
+        }
+        unsafe {
+            log_fds::dup2(fds[1], fd);
+            log_fds::close(fds[1]);
+        }
+
+        let reader = unsafe { fs::File::from_raw_fd(fds[0]) };
+        let out = out.clone();
+        writers.push(std::thread::spawn(move || {
+            use std::io::BufRead;
+
+            for line in io::BufReader::new(reader).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let _ = writeln!(out.lock().unwrap(), "{}", json_log_line(fd == 2, &line));
+            }
+        }));
+    }
+
+    Ok(LogCapture {
+        saved_stdout,
+        saved_stderr,
+        writers,
+    })
+}
+
+#[cfg(not(unix))]
+pub fn start_file_logging(_config: &LoggingConfig) -> Result<LogCapture, String> {
+    Err("File logging is only supported on Unix".to_string())
+}
+
+#[cfg(not(unix))]
+pub fn start_json_logging() -> Result<LogCapture, String> {
+    Err("JSON logging is only supported on Unix".to_string())
+}
+
+impl Drop for LogCapture {
+    // Putting the original descriptors back closes the pipes, the writers drain them and exit
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            io::stdout().flush().ok();
+            unsafe {
+                log_fds::dup2(self.saved_stdout, 1);
+                log_fds::dup2(self.saved_stderr, 2);
+                log_fds::close(self.saved_stdout);
+                log_fds::close(self.saved_stderr);
+            }
+        }
+        for writer in self.writers.drain(..) {
+            let _ = writer.join();
+        }
+    }
+}
+
+fn parse_logs_args(args: &[String]) -> Result<(usize, bool), String> {
+    let mut lines = 50;
+    let mut follow = false;
+    let mut args = args
+        .iter()
+        .skip_while(|arg| *arg != "logs")
+        .skip(1)
+        .peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tail" | "-n" => {
+                // A bare --tail keeps the default line count
+                if let Some(count) = args.peek().and_then(|v| v.parse().ok()) {
+                    lines = count;
+                    args.next();
+                }
+            }
+            "--follow" | "-f" => follow = true,
+            other => return Err(format!("Unknown logs option '{}'", other)),
+        }
+    }
+    Ok((lines, follow))
+}
+
+// `logs [--tail N] [--follow]` prints the newest entries of the daemon's log file
+pub fn show_logs(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let (lines, follow) = parse_logs_args(args)?;
+    let log_file = data_file(
+        &AuthConfig::load_or_default(&data_file(AUTH_CONFIG_FILE))
+            .logging
+            .file,
+    );
+
+    let content = fs::read_to_string(&log_file)
+        .map_err(|e| format!("Cannot read log file {}: {}", log_file, e))?;
+    let all: Vec<&str> = content.lines().collect();
+    for line in &all[all.len().saturating_sub(lines)..] {
+        println!("{}", line);
+    }
+
+    if follow {
+        let mut position = content.len() as u64;
+        loop {
+            std::thread::sleep(Duration::from_millis(500));
+            let Ok(metadata) = fs::metadata(&log_file) else {
+                continue;
+            };
+            // Rotated, start again from the top of the new file
+            if metadata.len() < position {
+                position = 0;
+            }
+            if metadata.len() > position {
+                use std::io::{Read, Seek};
+
+                let mut file = fs::File::open(&log_file)?;
+                file.seek(io::SeekFrom::Start(position))?;
+                let mut new = String::new();
+                file.read_to_string(&mut new)?;
+                position += new.len() as u64;
+                print!("{}", new);
+                io::stdout().flush()?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,136 @@
+// Login history module for Crusty-Crawler
+// Every GUI and web sign-in attempt with its time, address and outcome, shown to each user in
+// their settings and to admins through /api/logins
+
+const LOGIN_HISTORY_FILE: &str = "login_history.json";
+const MAX_LOGINS: usize = 2000;
+const DEFAULT_LOGIN_LIMIT: usize = 100;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginMethod {
+    // The GUI login form
+    Password,
+    // The GUI at launch, from the OS keyring
+    RememberMe,
+    // The GUI lock screen
+    Unlock,
+    // The dashboard opened with the access token
+    AccessToken,
+    // The dashboard opened from the GUI's QR code
+    LoginLink,
+}
+
+impl LoginMethod {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LoginMethod::Password => "GUI password",
+            LoginMethod::RememberMe => "GUI remember me",
+            LoginMethod::Unlock => "GUI unlock",
+            LoginMethod::AccessToken => "Web access token",
+            LoginMethod::LoginLink => "Web QR code",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LoginRecord {
+    pub timestamp: String,
+    // None when the token matched nobody
+    pub username: Option<String>,
+    pub method: LoginMethod,
+    // None for the GUI, which runs on this machine
+    pub ip: Option<String>,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+// Query of GET /api/logins
+#[derive(Deserialize)]
+pub struct LoginFilter {
+    pub username: Option<String>,
+    pub limit: Option<usize>,
+}
+
+// Loaded from LOGIN_HISTORY_FILE on first use, oldest first
+static LOGIN_HISTORY: Mutex<Option<VecDeque<LoginRecord>>> = Mutex::new(None);
+
+fn with_login_history<T>(f: impl FnOnce(&mut VecDeque<LoginRecord>) -> T) -> T {
+    let mut logins = LOGIN_HISTORY.lock().unwrap();
+    let logins = logins.get_or_insert_with(|| {
+        fs::read_to_string(data_path(LOGIN_HISTORY_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    });
+    f(logins)
+}
+
+// `failure` is the reason a failed attempt was turned away
+pub fn record_login(
+    username: Option<&str>,
+    method: LoginMethod,
+    ip: Option<std::net::IpAddr>,
+    failure: Option<&str>,
+) {
+    let record = LoginRecord {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        username: username.map(str::to_string),
+        method,
+        ip: ip.map(|ip| ip.to_string()),
+        success: failure.is_none(),
+        reason: failure.map(str::to_string),
+    };
+    with_login_history(|logins| {
+        if logins.len() == MAX_LOGINS {
+            logins.pop_front();
+        }
+        logins.push_back(record);
+        save_login_history(logins);
+    });
+}
+
+fn save_login_history(logins: &VecDeque<LoginRecord>) {
+    let saved = serde_json::to_string(logins)
+        .map_err(|e| e.to_string())
+        .and_then(|data| {
+            write_file_atomic(&data_path(LOGIN_HISTORY_FILE), data.as_bytes())
+                .map_err(|e| e.to_string())
+        });
+    if let Err(e) = saved {
+        eprintln!(
+            "⚠️  Failed to save login history to {}: {}",
+            LOGIN_HISTORY_FILE, e
+        );
+    }
+}
+
+// Returns how many sign-ins were removed
+pub fn remove_logins_before(cutoff: chrono::DateTime<chrono::Utc>) -> usize {
+    with_login_history(|logins| {
+        let before = logins.len();
+        logins.retain(|login| {
+            chrono::DateTime::parse_from_rfc3339(&login.timestamp)
+                .is_ok_and(|timestamp| timestamp >= cutoff)
+        });
+        let removed = before - logins.len();
+        if removed > 0 {
+            save_login_history(logins);
+        }
+        removed
+    })
+}
+
+// Newest first, everyone's when no username is given
+pub fn login_history(username: Option<&str>, limit: Option<usize>) -> Vec<LoginRecord> {
+    with_login_history(|logins| {
+        logins
+            .iter()
+            .rev()
+            .filter(|login| username.is_none() || login.username.as_deref() == username)
+            .take(limit.unwrap_or(DEFAULT_LOGIN_LIMIT))
+            .cloned()
+            .collect()
+    })
+}
@@ -8,14 +8,22 @@
 //
 // I only plan on working on this until Blake returns from his vacation.
 
-use eframe::egui;
+// AuthManager has settings and unlock methods only the GUI calls
+#![cfg_attr(not(feature = "gui"), allow(dead_code))]
+
 use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
 use tokio::runtime::Runtime;
 use std::env;
 
 // Axum Server Components
-use axum::{Router, extract::Query, http::StatusCode, response::Html, routing::get};
+use axum::{
+    Json, Router,
+    extract::Query,
+    http::StatusCode,
+    response::Html,
+    routing::{get, post},
+};
 use serde::{Deserialize, Serialize};
 use tower_http::services::ServeDir;
 
@@ -25,7 +33,106 @@ include!("components.rs");
 include!("disks.rs");
 include!("hardware_statistics.rs");
 include!("auth.rs");
+include!("tokens.rs");
+include!("passwords.rs");
+include!("account.rs");
+include!("invitations.rs");
+include!("kiosk.rs");
+include!("paths.rs");
+include!("persistence.rs");
+include!("config_sync.rs");
+include!("providers.rs");
+include!("access.rs");
+include!("credentials.rs");
+include!("login_history.rs");
 include!("cli.rs");
+include!("checks.rs");
+include!("alerts.rs");
+include!("notifications.rs");
+include!("oncall.rs");
+include!("templates.rs");
+include!("metrics.rs");
+include!("processes.rs");
+include!("adaptive.rs");
+include!("power.rs");
+include!("health.rs");
+include!("capabilities.rs");
+include!("cgroups.rs");
+include!("ebpf.rs");
+include!("smc.rs");
+include!("sbc.rs");
+include!("ipmi.rs");
+include!("raid.rs");
+include!("pools.rs");
+include!("databases.rs");
+include!("http_checks.rs");
+include!("collectors.rs");
+include!("status_pages.rs");
+include!("sla.rs");
+include!("badges.rs");
+include!("email.rs");
+#[cfg(feature = "gui")]
+include!("settings.rs");
+#[cfg(feature = "gui")]
+include!("remember.rs");
+include!("sessions.rs");
+include!("snapshot.rs");
+include!("daemon.rs");
+include!("container.rs");
+include!("logging.rs");
+include!("top.rs");
+include!("control.rs");
+include!("reload.rs");
+include!("exporter.rs");
+include!("otlp.rs");
+include!("statsd.rs");
+include!("custom_metrics.rs");
+include!("derived.rs");
+include!("device_filters.rs");
+include!("zabbix.rs");
+include!("checkmk.rs");
+include!("events.rs");
+include!("layouts.rs");
+include!("overview.rs");
+include!("host_score.rs");
+include!("diagnose.rs");
+include!("crashes.rs");
+include!("kernel_log.rs");
+include!("peripherals.rs");
+include!("av_devices.rs");
+include!("limits.rs");
+include!("history_query.rs");
+include!("privacy.rs");
+include!("scheduler.rs");
+include!("throttle.rs");
+include!("stream.rs");
+include!("central.rs");
+include!("wake.rs");
+include!("fleet_config.rs");
+include!("enrollment.rs");
+include!("cluster.rs");
+include!("fleet_grid.rs");
+include!("compare.rs");
+include!("fleet_alerts.rs");
+include!("push.rs");
+#[cfg(feature = "gui")]
+include!("qr.rs");
+include!("lan.rs");
+include!("mdns.rs");
+include!("protocols.rs");
+include!("wifi.rs");
+include!("speedtest.rs");
+include!("connections.rs");
+include!("security.rs");
+include!("autoruns.rs");
+include!("fim.rs");
+include!("demo.rs");
+include!("replay.rs");
+include!("latency.rs");
+include!("server.rs");
+#[cfg(feature = "gui")]
+include!("gui.rs");
+include!("tests.rs");
 
 // Web parameters query
 #[derive(Deserialize)]
@@ -33,277 +140,1517 @@ struct TokenQuery {
     token: Option<String>,
 }
 
-// Shared state between GUI and server
-struct ServerState {
-    is_running: bool,
-    port: u16,
-    shutdown_sender: Option<tokio::sync::oneshot::Sender<()>>,
-    hardware_state: Arc<Mutex<HardwareMonitorState>>,
-    auth_manager: Arc<Mutex<AuthManager>>,
+#[derive(Deserialize)]
+struct CheckQuery {
+    max_age: Option<u64>,
+}
+
+// Shared state between GUI and server
+struct ServerState {
+    is_running: bool,
+    port: u16,
+    shutdown_sender: Option<tokio::sync::oneshot::Sender<()>>,
+    hardware_state: Arc<Mutex<HardwareMonitorState>>,
+    auth_manager: Arc<Mutex<AuthManager>>,
+    check_cache: Arc<Mutex<CheckCache>>,
+    alert_manager: Arc<Mutex<AlertManager>>,
+    system: Arc<dyn SystemInfoProvider>,
+}
+
+impl ServerState {
+    fn new(auth_manager: AuthManager, system: Arc<dyn SystemInfoProvider>) -> Self {
+        Self {
+            is_running: false,
+            port: 3000,
+            shutdown_sender: None,
+            hardware_state: Arc::new(Mutex::new(HardwareMonitorState::default())),
+            auth_manager: Arc::new(Mutex::new(auth_manager)),
+            check_cache: Arc::new(Mutex::new(CheckCache::default())),
+            alert_manager: Arc::new(Mutex::new(AlertManager::default())),
+            system,
+        }
+    }
+}
+
+impl Default for ServerState {
+    fn default() -> Self {
+        let auth_manager = AuthManager::new(&data_file(AUTH_CONFIG_FILE))
+            .unwrap_or_else(|_| AuthManager::new("crust_auth.json").unwrap());
+        Self::new(auth_manager, system_provider())
+    }
+}
+
+// Axum apllication and routing of information
+fn create_app(server_state: Arc<Mutex<ServerState>>) -> Router {
+    let server_state_clone = server_state.clone();
+    let checks_state = server_state.clone();
+    let check_state = server_state.clone();
+    let run_check_state = server_state.clone();
+    let metrics_state = server_state.clone();
+    let prometheus_state = server_state.clone();
+    let layout_state = server_state.clone();
+    let save_layout_state = server_state.clone();
+    let reset_layout_state = server_state.clone();
+    let account_state = server_state.clone();
+    let password_state = server_state.clone();
+    let email_state = server_state.clone();
+    let overview_state = server_state.clone();
+    let health_score_state = server_state.clone();
+    let push_key_state = server_state.clone();
+    let test_push_state = server_state.clone();
+    let speed_test_state = server_state.clone();
+    let run_speed_test_state = server_state.clone();
+    let connections_state = server_state.clone();
+    let security_state = server_state.clone();
+    let accept_security_state = server_state.clone();
+    let fim_state = server_state.clone();
+    let fim_scan_state = server_state.clone();
+    let fim_accept_state = server_state.clone();
+    let status_page_state = server_state.clone();
+    let named_page_state = server_state.clone();
+    let public_page_state = server_state.clone();
+    let slow_requests_state = server_state.clone();
+    let latency_state = server_state.clone();
+    let credentials_state = server_state.clone();
+    let revoke_session_state = server_state.clone();
+    let revoke_token_state = server_state.clone();
+    let invitations_state = server_state.clone();
+    let invite_state = server_state.clone();
+    let revoke_invitation_state = server_state.clone();
+    let signup_state = server_state.clone();
+    let on_call_state = server_state.clone();
+    let badge_state = server_state.clone();
+    let export_state = server_state.clone();
+    let schedules_state = server_state.clone();
+    let central_report_state = server_state.clone();
+    let central_hosts_state = server_state.clone();
+    let host_mac_state = server_state.clone();
+    let wake_host_state = server_state.clone();
+    let config_bundle_state = server_state.clone();
+    let config_push_state = server_state.clone();
+    let join_token_state = server_state.clone();
+    let enroll_state = server_state.clone();
+    let approve_host_state = server_state.clone();
+    let deny_host_state = server_state.clone();
+    let cluster_state_state = server_state.clone();
+    let cluster_status_state = server_state.clone();
+    let fleet_grid_state = server_state.clone();
+    let compare_state = server_state.clone();
+    let fleet_alerts_state = server_state.clone();
+    let peripherals_state = server_state.clone();
+    // Where the AuthedUser extractor finds the auth manager
+    let auth_state = server_state.clone();
+
+    Router::new()
+        .route(
+            "/api/status",
+            get(move |_: DashboardViewer| status_handler(server_state)),
+        )
+        .route(
+            "/api/checks",
+            get(move |_: DashboardViewer, query: Query<CheckQuery>| {
+                checks_handler(checks_state, query)
+            }),
+        )
+        .route(
+            "/api/checks/{name}",
+            get(
+                move |_: DashboardViewer,
+                      name: axum::extract::Path<String>,
+                      query: Query<CheckQuery>| {
+                    check_handler(check_state, name, query)
+                },
+            ),
+        )
+        .route(
+            "/api/checks/{name}/run",
+            post(move |_: AdminUser, name: axum::extract::Path<String>| {
+                run_check_handler(run_check_state, name)
+            }),
+        )
+        .route(
+            "/api/metrics",
+            get(move |_: AuthedUser| metrics_handler(metrics_state)),
+        )
+        .route(
+            "/metrics",
+            get(move |_: AuthedUser| prometheus_handler(prometheus_state)),
+        )
+        .route(
+            "/api/custom-metrics",
+            get(|_: AuthedUser| custom_metrics_handler()).post(
+                |_: AuthedUser, push: Json<CustomMetricsPush>| push_custom_metrics_handler(push),
+            ),
+        )
+        .route(
+            "/api/events",
+            get(|_: AuthedUser, filter: Query<EventFilter>| events_handler(filter))
+                .post(|_: AuthedUser, event: Json<ExternalEvent>| post_event_handler(event)),
+        )
+        .route(
+            "/api/oncall",
+            get(move |_: AuthedUser| on_call_handler(on_call_state)),
+        )
+        .route("/api/history", get(|_: AuthedUser| history_handler()))
+        .route(
+            "/api/history/query",
+            get(|_: AuthedUser, query: Query<HistoryQuery>| history_query_handler(query)),
+        )
+        .route(
+            "/api/sla",
+            get(|_: AuthedUser, query: Query<SlaQuery>| sla_handler(query)),
+        )
+        .route(
+            "/sla",
+            get(|_: AuthedUser, query: Query<SlaQuery>| sla_page_handler(query)),
+        )
+        .route("/api/crashes", get(|_: AuthedUser| crashes_handler()))
+        .route("/api/kernel-log", get(|_: AuthedUser| kernel_log_handler()))
+        .route(
+            "/api/peripherals",
+            get(move |_: AuthedUser| peripherals_handler(peripherals_state)),
+        )
+        .route(
+            "/api/diagnose",
+            get(|_: AuthedUser, query: Query<DiagnoseQuery>| diagnose_handler(query)),
+        )
+        .route("/api/collection", get(|_: AuthedUser| collection_handler()))
+        .route(
+            "/api/schedules",
+            get(move |_: AuthedUser| schedules_handler(schedules_state)),
+        )
+        .route(
+            "/api/layout",
+            get(move |viewer: DashboardViewer| layout_handler(layout_state, viewer))
+                .put(move |user: AuthedUser, layout: Json<DashboardLayout>| {
+                    save_layout_handler(save_layout_state, user, layout)
+                })
+                .delete(move |user: AuthedUser| reset_layout_handler(reset_layout_state, user)),
+        )
+        .route(
+            "/api/account",
+            get(move |user: AuthedUser| account_handler(account_state, user)),
+        )
+        .route(
+            "/api/account/password",
+            axum::routing::put(move |user: AuthedUser, change: Json<PasswordChange>| {
+                change_password_handler(password_state, user, change)
+            }),
+        )
+        .route(
+            "/api/account/email",
+            axum::routing::put(move |user: AuthedUser, change: Json<EmailChange>| {
+                change_email_handler(email_state, user, change)
+            }),
+        )
+        .route(
+            "/api/overview",
+            get(move |_: DashboardViewer| overview_handler(overview_state)),
+        )
+        .route("/api/stream", get(|_: DashboardViewer| stream_handler()))
+        .route(
+            "/api/health-score",
+            get(move |_: DashboardViewer| health_score_handler(health_score_state)),
+        )
+        .route(
+            "/api/push/key",
+            get(move |_: AuthedUser| push_key_handler(push_key_state)),
+        )
+        .route(
+            "/api/push/subscribe",
+            post(|user: AuthedUser, subscription: Json<PushSubscription>| {
+                subscribe_push_handler(user, subscription)
+            }),
+        )
+        .route(
+            "/api/push/unsubscribe",
+            post(|user: AuthedUser, request: Json<PushUnsubscribe>| {
+                unsubscribe_push_handler(user, request)
+            }),
+        )
+        .route(
+            "/api/push/test",
+            post(move |user: AuthedUser| test_push_handler(test_push_state, user)),
+        )
+        .route(
+            "/api/speedtest",
+            get(move |_: AuthedUser| speed_test_handler(speed_test_state)),
+        )
+        .route(
+            "/api/speedtest/run",
+            post(move |_: AdminUser| run_speed_test_handler(run_speed_test_state)),
+        )
+        .route(
+            "/api/connections",
+            get(move |_: AuthedUser| connections_handler(connections_state)),
+        )
+        .route(
+            "/api/security",
+            get(move |_: AuthedUser| security_handler(security_state)),
+        )
+        .route(
+            "/api/security/accept",
+            post(move |user: AdminUser, accept: Json<SecurityAccept>| {
+                accept_security_handler(accept_security_state, user, accept)
+            }),
+        )
+        .route(
+            "/api/security/fim",
+            get(move |_: AuthedUser| fim_handler(fim_state)),
+        )
+        .route(
+            "/api/security/fim/scan",
+            post(move |_: AdminUser| fim_scan_handler(fim_scan_state)),
+        )
+        .route(
+            "/api/security/fim/accept",
+            post(move |user: AdminUser, accept: Json<FimAccept>| {
+                fim_accept_handler(fim_accept_state, user, accept)
+            }),
+        )
+        .route("/api/self", get(|_: AuthedUser| self_handler()))
+        .route(
+            "/api/self/slow",
+            get(move |_: AuthedUser| slow_requests_handler(slow_requests_state)),
+        )
+        .route(
+            "/api/credentials",
+            get(move |user: AuthedUser| credentials_handler(credentials_state, user)),
+        )
+        .route(
+            "/api/credentials/sessions/{id}",
+            axum::routing::delete(move |user: AuthedUser, id: axum::extract::Path<String>| {
+                revoke_session_handler(revoke_session_state, user, id)
+            }),
+        )
+        .route(
+            "/api/credentials/tokens/{username}",
+            axum::routing::delete(
+                move |user: AdminUser, username: axum::extract::Path<String>| {
+                    revoke_token_handler(revoke_token_state, user, username)
+                },
+            ),
+        )
+        .route("/api/logins", get(logins_handler))
+        .route(
+            "/api/privacy/purge",
+            post(|user: AdminUser, request: Json<PurgeRequest>| purge_handler(user, request)),
+        )
+        .route(
+            "/api/export",
+            get(move |_: AdminUser| export_handler(export_state)),
+        )
+        .route(
+            "/api/central/report",
+            post(move |agent: ReportingAgent, report: Json<HostReport>| {
+                central_report_handler(central_report_state, agent, report)
+            }),
+        )
+        .route(
+            "/api/central/hosts",
+            get(move |_: AuthedUser| central_hosts_handler(central_hosts_state)),
+        )
+        .route(
+            "/api/central/hosts/{host}/mac",
+            axum::routing::put(
+                move |user: AdminUser,
+                      host: axum::extract::Path<String>,
+                      update: Json<HostMacUpdate>| {
+                    set_host_mac_handler(host_mac_state, user, host, update)
+                },
+            ),
+        )
+        .route(
+            "/api/central/hosts/{host}/wake",
+            post(move |user: AdminUser, host: axum::extract::Path<String>| {
+                wake_host_handler(wake_host_state, user, host)
+            }),
+        )
+        .route(
+            "/api/central/config",
+            axum::routing::put(
+                move |pusher: ConfigPusher, signed: Json<SignedConfigBundle>| {
+                    config_bundle_handler(config_bundle_state, pusher, signed)
+                },
+            ),
+        )
+        .route(
+            "/api/central/config/key",
+            get(|_: AdminUser| manager_key_handler()),
+        )
+        .route(
+            "/api/central/config/push",
+            post(move |user: AdminUser| config_push_handler(config_push_state, user)),
+        )
+        .route(
+            "/api/central/join-tokens",
+            post(move |user: AdminUser| join_token_handler(join_token_state, user)),
+        )
+        // The join token is the credential, the agent has no other yet
+        .route(
+            "/api/central/enroll",
+            post(move |request: Json<EnrollmentRequest>| enroll_handler(enroll_state, request)),
+        )
+        .route(
+            "/api/central/hosts/{host}/approve",
+            post(move |user: AdminUser, host: axum::extract::Path<String>| {
+                enrollment_decision_handler(approve_host_state, user, host, true)
+            }),
+        )
+        .route(
+            "/api/central/hosts/{host}/deny",
+            post(move |user: AdminUser, host: axum::extract::Path<String>| {
+                enrollment_decision_handler(deny_host_state, user, host, false)
+            }),
+        )
+        .route(
+            "/api/central/grid",
+            get(move |_: DashboardViewer, query: Query<FleetGridQuery>| {
+                fleet_grid_handler(fleet_grid_state, query)
+            }),
+        )
+        .route("/fleet", get(|_: DashboardViewer| fleet_page_handler()))
+        .route(
+            "/api/central/compare",
+            get(move |_: AuthedUser, query: Query<ComparisonQuery>| {
+                compare_handler(compare_state, query)
+            }),
+        )
+        .route(
+            "/api/central/alerts",
+            get(move |_: AuthedUser| fleet_alerts_handler(fleet_alerts_state)),
+        )
+        .route(
+            "/api/central/cluster",
+            get(move |_: AuthedUser| cluster_status_handler(cluster_status_state)),
+        )
+        // What the other manager of a cluster syncs from
+        .route(
+            "/api/central/cluster/state",
+            get(move |_: AdminUser| cluster_state_handler(cluster_state_state)),
+        )
+        .route(
+            "/api/invitations",
+            get(move |_: AdminUser| invitations_handler(invitations_state)).post(
+                move |user: AdminUser, request: Json<InvitationRequest>| {
+                    invite_handler(invite_state, user, request)
+                },
+            ),
+        )
+        .route(
+            "/api/invitations/{id}",
+            axum::routing::delete(move |user: AdminUser, id: axum::extract::Path<String>| {
+                revoke_invitation_handler(revoke_invitation_state, user, id)
+            }),
+        )
+        // The invitee has no token yet, the signup code is the credential
+        .route("/signup", get(signup_page_handler))
+        .route(
+            "/api/signup",
+            post(move |signup: Json<Signup>| signup_handler(signup_state, signup)),
+        )
+        .route("/manifest.webmanifest", get(manifest_handler))
+        .route("/sw.js", get(service_worker_handler))
+        .route("/icons/{file}", get(icon_handler))
+        .route("/api/storage", get(|_: AuthedUser| storage_handler()))
+        .route(
+            "/status",
+            get(move |_: DashboardViewer| {
+                status_page_handler(status_page_state, "default".to_string())
+            }),
+        )
+        .route(
+            "/public",
+            get(move || public_status_handler(public_page_state)),
+        )
+        // Checks on the public status page can be embedded without a token
+        .route(
+            "/badge/{name}",
+            get(
+                move |viewer: Result<DashboardViewer, AuthError>,
+                      name: axum::extract::Path<String>| {
+                    badge_handler(badge_state, viewer, name)
+                },
+            ),
+        )
+        .route(
+            "/status/{template}",
+            get(
+                move |_: DashboardViewer,
+                      axum::extract::Path(template): axum::extract::Path<String>| {
+                    status_page_handler(named_page_state, template)
+                },
+            ),
+        )
+        .route(
+            "/",
+            get(move |query: Query<TokenQuery>, ip: ClientIp| {
+                index_handler(server_state_clone, query, ip)
+            }),
+        )
+        .fallback_service(ServeDir::new(resource_dir("public")))
+        .layer(axum::Extension(auth_state))
+        .layer(axum::middleware::from_fn(trace_request))
+        .layer(axum::middleware::from_fn_with_state(
+            latency_state,
+            time_request,
+        ))
+}
+
+fn http_config(server_state: &Arc<Mutex<ServerState>>) -> HttpServerConfig {
+    let state = server_state.lock().unwrap();
+    let auth_manager = state.auth_manager.lock().unwrap();
+    auth_manager.config.http.clone()
+}
+
+// Endpoint handlers, the AuthedUser extractor on each route has already checked the token
+async fn status_handler(server_state: Arc<Mutex<ServerState>>) -> Html<String> {
+    Html(status(server_state).await)
+}
+
+// A check runner along with the requested max age
+fn check_runner(
+    server_state: &Arc<Mutex<ServerState>>,
+    query: &CheckQuery,
+) -> (CheckRunner, Duration) {
+    let runner = CheckRunner::from_state(&server_state.lock().unwrap());
+    let max_age = query.max_age.unwrap_or(runner.config.cache_max_age_secs);
+    (runner, Duration::from_secs(max_age))
+}
+
+async fn checks_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    query: Query<CheckQuery>,
+) -> Json<Vec<serde_json::Value>> {
+    let (runner, max_age) = check_runner(&server_state, &query);
+
+    let mut body = Vec::new();
+    for name in available_checks(&runner.config) {
+        if let Some((result, age)) = runner.cached(&name, max_age).await {
+            body.push(serde_json::to_value(CheckResponse::new(&result, age)).unwrap_or_default());
+        }
+    }
+
+    Json(body)
+}
+
+async fn check_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+    query: Query<CheckQuery>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let (runner, max_age) = check_runner(&server_state, &query);
+
+    let (result, age) = runner
+        .cached(&name, max_age)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(
+        serde_json::to_value(CheckResponse::new(&result, age)).unwrap_or_default(),
+    ))
+}
+
+// Forces an immediate run of a single check, bypassing and refreshing the cache
+async fn run_check_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let runner = CheckRunner::from_state(&server_state.lock().unwrap());
+
+    let result = runner.run(&name).await.ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(
+        serde_json::to_value(CheckResponse::new(&result, None)).unwrap_or_default(),
+    ))
+}
+
+fn metrics_config(server_state: &Arc<Mutex<ServerState>>) -> MetricsConfig {
+    let state = server_state.lock().unwrap();
+    let auth_manager = state.auth_manager.lock().unwrap();
+    auth_manager.config.metrics.clone()
+}
+
+async fn metrics_handler(server_state: Arc<Mutex<ServerState>>) -> Json<Vec<Metric>> {
+    Json(collect_metrics(&metrics_config(&server_state)).await)
+}
+
+// Scrapers and applications can send the token as a bearer token instead of in the URL
+fn request_token(query: &TokenQuery, headers: &axum::http::HeaderMap) -> Option<String> {
+    query.token.clone().or_else(|| {
+        headers
+            .get(axum::http::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::to_string)
+    })
+}
+
+async fn custom_metrics_handler() -> Json<Vec<CustomSeries>> {
+    Json(custom_series())
+}
+
+async fn push_custom_metrics_handler(
+    Json(push): Json<CustomMetricsPush>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let accepted =
+        record_custom_metrics(push).map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    Ok(Json(serde_json::json!({ "accepted": accepted })))
+}
+
+async fn events_handler(
+    Query(filter): Query<EventFilter>,
+) -> Result<Json<Vec<TimelineEvent>>, (StatusCode, String)> {
+    timeline_events(&filter)
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+// For deploy tools and CI, so their changes show up on the dashboard charts
+async fn post_event_handler(
+    Json(event): Json<ExternalEvent>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    record_external_event(event).map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    Ok(StatusCode::CREATED)
+}
+
+async fn on_call_handler(server_state: Arc<Mutex<ServerState>>) -> Json<Vec<OnCallShift>> {
+    let on_call = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.on_call.clone()
+    };
+    Json(on_call.schedule(chrono::Utc::now()))
+}
+
+async fn history_handler() -> Json<BTreeMap<String, VecDeque<(String, f64)>>> {
+    Json(metric_history())
+}
+
+async fn history_query_handler(
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<HistoryQueryResult>, (StatusCode, String)> {
+    query_history(&query, chrono::Utc::now())
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+async fn sla_handler(Query(query): Query<SlaQuery>) -> Json<SlaReport> {
+    Json(sla_report(query.window, chrono::Utc::now()))
+}
+
+async fn diagnose_handler(
+    Query(query): Query<DiagnoseQuery>,
+) -> Result<Json<DiagnoseReport>, (StatusCode, String)> {
+    diagnose_report(&query)
+        .map(Json)
+        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))
+}
+
+async fn sla_page_handler(Query(query): Query<SlaQuery>) -> Result<Html<String>, StatusCode> {
+    let report = sla_report(query.window, chrono::Utc::now());
+    render_sla_page(&report, query.token.as_deref()).map(Html)
+}
+
+async fn collection_handler() -> Json<CollectionStatus> {
+    Json(collection_status())
+}
+
+async fn crashes_handler() -> Json<Vec<CrashEvent>> {
+    Json(recent_crashes())
+}
+
+async fn kernel_log_handler() -> Json<KernelLogReport> {
+    Json(kernel_log_report())
+}
+
+async fn peripherals_handler(
+    server_state: Arc<Mutex<ServerState>>,
+) -> Result<Json<PeripheralReport>, (StatusCode, String)> {
+    let config = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.checks.peripherals.clone()
+    };
+    if !peripherals_available(&config) {
+        return Err((
+            StatusCode::NOT_FOUND,
+            "Peripheral monitoring is off, see checks.peripherals".to_string(),
+        ));
+    }
+    Ok(Json(read_peripherals(&config)))
+}
+
+async fn layout_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    viewer: DashboardViewer,
+) -> Json<UserDashboardLayout> {
+    let state = server_state.lock().unwrap();
+    let auth_manager = state.auth_manager.lock().unwrap();
+    Json(match viewer {
+        DashboardViewer::User(user) => auth_manager.dashboard_layout(&user.username),
+        DashboardViewer::Kiosk(_) => auth_manager.kiosk_layout(),
+    })
+}
+
+async fn save_layout_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    AuthedUser { username, .. }: AuthedUser,
+    Json(layout): Json<DashboardLayout>,
+) -> Result<Json<UserDashboardLayout>, (StatusCode, String)> {
+    let state = server_state.lock().unwrap();
+    let mut auth_manager = state.auth_manager.lock().unwrap();
+    auth_manager
+        .set_dashboard_layout(&username, Some(layout))
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    Ok(Json(auth_manager.dashboard_layout(&username)))
+}
+
+async fn reset_layout_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    AuthedUser { username, .. }: AuthedUser,
+) -> Result<Json<UserDashboardLayout>, (StatusCode, String)> {
+    let state = server_state.lock().unwrap();
+    let mut auth_manager = state.auth_manager.lock().unwrap();
+    auth_manager
+        .set_dashboard_layout(&username, None)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Json(auth_manager.dashboard_layout(&username)))
+}
+
+async fn account_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    AuthedUser { username, .. }: AuthedUser,
+) -> Result<Json<AccountInfo>, AuthError> {
+    let state = server_state.lock().unwrap();
+    let auth_manager = state.auth_manager.lock().unwrap();
+    auth_manager
+        .account_info(&username)
+        .map(Json)
+        .ok_or(AuthError::InvalidToken)
+}
+
+async fn change_password_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    AuthedUser { username, .. }: AuthedUser,
+    Json(change): Json<PasswordChange>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let state = server_state.lock().unwrap();
+    let mut auth_manager = state.auth_manager.lock().unwrap();
+    auth_manager
+        .change_password(&username, &change.current_password, &change.new_password)
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn change_email_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    AuthedUser { username, .. }: AuthedUser,
+    Json(change): Json<EmailChange>,
+) -> Result<Json<AccountInfo>, (StatusCode, String)> {
+    let state = server_state.lock().unwrap();
+    let mut auth_manager = state.auth_manager.lock().unwrap();
+    auth_manager
+        .change_email(&username, &change.current_password, &change.email)
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    auth_manager
+        .account_info(&username)
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "User not found".to_string()))
+}
+
+async fn prometheus_handler(
+    server_state: Arc<Mutex<ServerState>>,
+) -> Result<([(axum::http::header::HeaderName, &'static str); 1], String), StatusCode> {
+    let metrics_config = metrics_config(&server_state);
+    if !metrics_config.prometheus.enabled {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let metrics = metrics_config
+        .prometheus
+        .apply(collect_metrics(&metrics_config).await);
+    Ok((
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        prometheus_exposition(&metrics),
+    ))
+}
+
+async fn overview_handler(server_state: Arc<Mutex<ServerState>>) -> Json<Overview> {
+    let runner = CheckRunner::from_state(&server_state.lock().unwrap());
+    Json(overview(&runner).await)
+}
+
+// Overview snapshots and deltas as server-sent events
+async fn stream_handler() -> impl axum::response::IntoResponse {
+    dashboard_stream()
+}
+
+async fn health_score_handler(server_state: Arc<Mutex<ServerState>>) -> Json<HealthScore> {
+    let runner = CheckRunner::from_state(&server_state.lock().unwrap());
+    Json(host_health_score(&runner).await)
+}
+
+async fn push_key_handler(
+    server_state: Arc<Mutex<ServerState>>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let enabled = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.push.enabled
+    };
+    if !enabled {
+        return Ok(Json(serde_json::json!({ "enabled": false })));
+    }
+    let public_key = vapid_public_key().map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Json(
+        serde_json::json!({ "enabled": true, "public_key": public_key }),
+    ))
+}
+
+async fn subscribe_push_handler(
+    AuthedUser { username, .. }: AuthedUser,
+    Json(subscription): Json<PushSubscription>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    add_push_subscription(&username, subscription)
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    Ok(StatusCode::CREATED)
+}
+
+async fn unsubscribe_push_handler(
+    AuthedUser { username, .. }: AuthedUser,
+    Json(request): Json<PushUnsubscribe>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    match remove_push_subscription(&username, &request.endpoint) {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err((StatusCode::NOT_FOUND, "No such subscription".to_string())),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+async fn test_push_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    AuthedUser { username, .. }: AuthedUser,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let config = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.push.clone()
+    };
+    if !config.enabled {
+        return Err((StatusCode::NOT_FOUND, "Web push is disabled".to_string()));
+    }
+    let delivered = send_test_push(&config, &username)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+    Ok(Json(serde_json::json!({ "delivered": delivered })))
+}
+
+async fn speed_test_handler(server_state: Arc<Mutex<ServerState>>) -> Json<SpeedTestHistory> {
+    let enabled = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.speed_test.enabled
+    };
+    Json(SpeedTestHistory {
+        enabled,
+        results: speed_test_history(),
+    })
+}
+
+async fn connections_handler(server_state: Arc<Mutex<ServerState>>) -> Json<ConnectionsReport> {
+    let config = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.connections.clone()
+    };
+    Json(connections_report(&config).await)
+}
+
+async fn security_handler(server_state: Arc<Mutex<ServerState>>) -> Json<SecurityReport> {
+    let config = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.checks.security.clone()
+    };
+    Json(security_report(&config).await)
+}
+
+async fn self_handler() -> Json<SelfReport> {
+    Json(self_report())
+}
+
+async fn slow_requests_handler(server_state: Arc<Mutex<ServerState>>) -> Json<SlowRequestReport> {
+    let threshold_ms = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.logging.slow_request_ms
+    };
+    Json(slow_request_report(threshold_ms))
+}
+
+// Re-runs the security check afterwards so its alert clears right away
+async fn accept_security_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    AdminUser(user): AdminUser,
+    Json(accept): Json<SecurityAccept>,
+) -> Result<Json<SecurityReport>, (StatusCode, String)> {
+    accept_security_finding(accept.kind, &accept.key)
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    println!(
+        "🔕 {} accepted security finding {}",
+        user.username, accept.key
+    );
+    let runner = CheckRunner::from_state(&server_state.lock().unwrap());
+    runner.run("security").await;
+    Ok(Json(security_report(&runner.config.security).await))
+}
+
+fn fim_config(server_state: &Arc<Mutex<ServerState>>) -> FimConfig {
+    let state = server_state.lock().unwrap();
+    let auth_manager = state.auth_manager.lock().unwrap();
+    auth_manager.config.checks.fim.clone()
+}
+
+async fn fim_handler(server_state: Arc<Mutex<ServerState>>) -> Json<FimReport> {
+    Json(fim_report(&fim_config(&server_state)))
+}
+
+// Hashes the monitored paths now instead of waiting for the schedule, e.g. after a deploy
+async fn fim_scan_handler(
+    server_state: Arc<Mutex<ServerState>>,
+) -> Result<Json<FimReport>, (StatusCode, String)> {
+    let config = fim_config(&server_state);
+    if config.paths.is_empty() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            "No paths configured under checks.fim.paths".to_string(),
+        ));
+    }
+    run_fim_scan(&config).await;
+    let runner = CheckRunner::from_state(&server_state.lock().unwrap());
+    runner.run("fim").await;
+    Ok(Json(fim_report(&config)))
+}
+
+async fn fim_accept_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    AdminUser(user): AdminUser,
+    Json(accept): Json<FimAccept>,
+) -> Result<Json<FimReport>, (StatusCode, String)> {
+    let config = fim_config(&server_state);
+    let accepted = accept_fim_changes(&config, accept.paths.as_deref())
+        .map_err(|e| (StatusCode::CONFLICT, e))?;
+    println!("🔕 {} accepted {} file change(s)", user.username, accepted);
+    let runner = CheckRunner::from_state(&server_state.lock().unwrap());
+    runner.run("fim").await;
+    Ok(Json(fim_report(&config)))
+}
+
+// Runs even when the schedule is off, as long as a target is configured
+async fn run_speed_test_handler(
+    server_state: Arc<Mutex<ServerState>>,
+) -> Result<Json<SpeedTestResult>, (StatusCode, String)> {
+    let config = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.speed_test.clone()
+    };
+    config
+        .target()
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    let result = run_speed_test(&config)
+        .await
+        .map_err(|e| (StatusCode::CONFLICT, e))?;
+    Ok(Json(result))
+}
+
+// Admins see everyone's credentials, other users only their own
+async fn credentials_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    user: AuthedUser,
+) -> Json<Vec<CredentialReport>> {
+    let state = server_state.lock().unwrap();
+    let auth_manager = state.auth_manager.lock().unwrap();
+    Json(
+        auth_manager
+            .credential_report()
+            .into_iter()
+            .filter(|credential| {
+                user.role == UserRole::Admin || credential.username == user.username
+            })
+            .collect(),
+    )
+}
+
+// Users can sign out their own sessions, admins anyone's
+async fn revoke_session_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    user: AuthedUser,
+    axum::extract::Path(id): axum::extract::Path<String>,
+) -> Result<StatusCode, axum::response::Response> {
+    use axum::response::IntoResponse;
+
+    let state = server_state.lock().unwrap();
+    let auth_manager = state.auth_manager.lock().unwrap();
+    let owner = auth_manager
+        .web_session_owner(&id)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "No such session").into_response())?;
+    if user.role != UserRole::Admin && owner != user.username {
+        return Err(AuthError::Forbidden.into_response());
+    }
+    auth_manager.revoke_web_session(&id);
+    println!("⛔ {} revoked a web session of {}", user.username, owner);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn revoke_token_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    AdminUser(user): AdminUser,
+    axum::extract::Path(username): axum::extract::Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let state = server_state.lock().unwrap();
+    let mut auth_manager = state.auth_manager.lock().unwrap();
+    if !auth_manager.config.users.contains_key(&username) {
+        return Err((StatusCode::NOT_FOUND, "No such user".to_string()));
+    }
+    auth_manager
+        .revoke_access_token(&username)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    println!(
+        "⛔ {} revoked the access token of {}",
+        user.username, username
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn invitations_handler(
+    server_state: Arc<Mutex<ServerState>>,
+) -> Json<Vec<PendingInvitation>> {
+    let state = server_state.lock().unwrap();
+    let auth_manager = state.auth_manager.lock().unwrap();
+    Json(auth_manager.pending_invitations())
+}
+
+async fn invite_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    AdminUser(user): AdminUser,
+    Json(request): Json<InvitationRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let state = server_state.lock().unwrap();
+    let mut auth_manager = state.auth_manager.lock().unwrap();
+    let base_url = lan_base_url(state.port, auth_manager.config.http.tls_cert.is_some());
+    auth_manager
+        .invite_user(&user.username, &request.email, request.role, &base_url)
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    println!("✉️  {} invited {}", user.username, request.email);
+    Ok(StatusCode::CREATED)
 }
 
-impl Default for ServerState {
-    fn default() -> Self {
-        let auth_manager = AuthManager::new("crusty_auth.json")
-            .unwrap_or_else(|_| AuthManager::new("crust_auth.json").unwrap());
-
-        Self {
-            is_running: false,
-            port: 3000,
-            shutdown_sender: None,
-            hardware_state: Arc::new(Mutex::new(HardwareMonitorState::default())),
-            auth_manager: Arc::new(Mutex::new(auth_manager)),
+async fn revoke_invitation_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    AdminUser(user): AdminUser,
+    axum::extract::Path(id): axum::extract::Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let state = server_state.lock().unwrap();
+    let mut auth_manager = state.auth_manager.lock().unwrap();
+    match auth_manager.revoke_invitation(&id) {
+        Ok(true) => {
+            println!("⛔ {} revoked an invitation", user.username);
+            Ok(StatusCode::NO_CONTENT)
         }
+        Ok(false) => Err((StatusCode::NOT_FOUND, "No such invitation".to_string())),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
     }
 }
 
-enum AppState {
-    Setup(SetupState),
-    Login(LoginState),
-    Main(MainState),
-    Recovery(RecoveryState),
-    SmtpConfig(SmtpConfigState),
+#[derive(Deserialize)]
+struct SignupQuery {
+    code: Option<String>,
+}
+
+// The link in the invitation email lands here with the code filled in
+async fn signup_page_handler(Query(query): Query<SignupQuery>) -> Html<String> {
+    let code = html_escape(query.code.as_deref().unwrap_or_default());
+    Html(include_str!("../public/signup.html").replace("{{CODE}}", &code))
 }
 
-struct SetupState {
-    username: String,
-    password: String,
-    confirm_password: String,
-    email: String,
-    access_token: String,
-    error_message: String,
-    show_token_suggestion: bool,
+async fn signup_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    Json(signup): Json<Signup>,
+) -> Result<Json<SignupResult>, (StatusCode, String)> {
+    let state = server_state.lock().unwrap();
+    let mut auth_manager = state.auth_manager.lock().unwrap();
+    let result = auth_manager
+        .accept_invitation(&signup)
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    println!("👋 {} accepted an invitation", result.username);
+    Ok(Json(result))
 }
 
-struct LoginState {
-    username: String,
-    password: String,
-    email: String,
-    error_message: String,
-    show_recovery: bool,
+// Admins can read anyone's logins or everyone's, other users only their own
+async fn logins_handler(
+    user: AuthedUser,
+    Query(filter): Query<LoginFilter>,
+) -> Result<Json<Vec<LoginRecord>>, AuthError> {
+    let username = match (user.role, filter.username) {
+        (UserRole::Admin, username) => username,
+        (UserRole::Viewer, Some(username)) if username != user.username => {
+            return Err(AuthError::Forbidden);
+        }
+        (UserRole::Viewer, _) => Some(user.username),
+    };
+    Ok(Json(login_history(username.as_deref(), filter.limit)))
 }
 
-struct RecoveryState {
-    email: String,
-    message: String,
-    is_success: bool,
+async fn schedules_handler(server_state: Arc<Mutex<ServerState>>) -> Json<Vec<ScheduleEntry>> {
+    let state = server_state.lock().unwrap();
+    let auth_manager = state.auth_manager.lock().unwrap();
+    Json(schedule_entries(&auth_manager.config))
 }
 
-struct SmtpConfigState {
-    server: String,
-    port: String,
-    username: String,
-    password: String,
-    use_tls: bool,
-    message: String,
+async fn purge_handler(
+    AdminUser(user): AdminUser,
+    Json(request): Json<PurgeRequest>,
+) -> Json<RemovedData> {
+    Json(purge_data(&request.categories, &user.username))
 }
 
-struct MainState {
-    port_input: String,
+async fn export_handler(
     server_state: Arc<Mutex<ServerState>>,
-    status_message: String,
-    current_user: String,
+) -> Result<Json<DataExport>, (StatusCode, String)> {
+    let (privacy, mut usernames) = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        (
+            auth_manager.config.privacy.clone(),
+            auth_manager
+                .config
+                .users
+                .keys()
+                .cloned()
+                .collect::<Vec<String>>(),
+        )
+    };
+    if privacy.redact_usernames {
+        usernames.extend(os_usernames());
+    }
+    let mut export = collect_data_export();
+    redact_export(&mut export, &privacy, &usernames)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Json(export))
 }
 
-impl MainState {
-    fn start_server(&mut self) {
-        let port = match self.port_input.parse::<u16>() {
-            Ok(p) => p,
-            Err(_) => {
-                self.status_message = format!("Invalid port number: {}", self.port_input);
-                return;
-            }
-        };
+// 409 asks the agent for a full snapshot
+async fn central_report_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    agent: ReportingAgent,
+    Json(report): Json<HostReport>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let enabled = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.manager.enabled
+    };
+    if !enabled {
+        return Err((StatusCode::NOT_FOUND, "Manager mode is off".to_string()));
+    }
+    // An enrolled host can only report as itself
+    if let ReportingAgent::Host(host) = &agent
+        && *host != report.host
+    {
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!("This credential belongs to {}", host),
+        ));
+    }
+    record_host_report(report).map_err(|e| (StatusCode::CONFLICT, e))?;
+    Ok(StatusCode::NO_CONTENT)
+}
 
-        let server_state = self.server_state.clone();
+async fn central_hosts_handler(server_state: Arc<Mutex<ServerState>>) -> Json<Vec<FleetHost>> {
+    let config = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.manager.clone()
+    };
+    Json(reported_fleet_hosts(&config))
+}
 
-        {
-            let state = server_state.lock().unwrap();
-            if state.is_running {
-                self.status_message = "Server is already running!".to_string();
-                return;
-            }
-        }
+async fn set_host_mac_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    AdminUser(user): AdminUser,
+    axum::extract::Path(host): axum::extract::Path<String>,
+    Json(update): Json<HostMacUpdate>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let state = server_state.lock().unwrap();
+    let mut auth_manager = state.auth_manager.lock().unwrap();
+    auth_manager
+        .set_host_mac(&host, update.mac, &user.username)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    Ok(StatusCode::NO_CONTENT)
+}
 
-        // Creates a new runtime for the server
-        let rt = Runtime::new().unwrap();
+async fn wake_host_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    AdminUser(user): AdminUser,
+    axum::extract::Path(name): axum::extract::Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let host = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.manager.host(&name).cloned()
+    };
+    let Some(host) = host else {
+        return Err((StatusCode::NOT_FOUND, format!("Unknown host {}", name)));
+    };
+    wake_host(&host)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    println!("⏰ {} sent a Wake-on-LAN packet to {}", user.username, name);
+    Ok(StatusCode::ACCEPTED)
+}
 
-        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
-        {
-            let mut state = server_state.lock().unwrap();
-            state.is_running = true;
-            state.port = port;
-            state.shutdown_sender = Some(shutdown_tx);
-        }
+// An agent applying what its manager pushed
+async fn config_bundle_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    ConfigPusher(pushed_by): ConfigPusher,
+    Json(signed): Json<SignedConfigBundle>,
+) -> Result<Json<Vec<String>>, (StatusCode, String)> {
+    let state = server_state.lock().unwrap();
+    let mut auth_manager = state.auth_manager.lock().unwrap();
+    let changes = auth_manager
+        .apply_config_bundle(&signed, &pushed_by)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    Ok(Json(changes))
+}
 
-        let server_state_clone = server_state.clone();
+async fn manager_key_handler() -> Result<String, (StatusCode, String)> {
+    manager_public_key().map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+}
 
-        // Spawn the server in a separate thread
-        std::thread::spawn(move || {
-            rt.block_on(async {
-                let app = create_app(server_state_clone.clone());
-                let addr = SocketAddr::from(([0, 0, 0, 0], port));
+async fn config_push_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    AdminUser(user): AdminUser,
+) -> Result<Json<Vec<ConfigPushResult>>, (StatusCode, String)> {
+    let config = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.manager.clone()
+    };
+    if !config.enabled {
+        return Err((StatusCode::NOT_FOUND, "Manager mode is off".to_string()));
+    }
+    let results = push_fleet_settings(&config)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let failed = results
+        .iter()
+        .filter(|result| result.error.is_some())
+        .count();
+    println!(
+        "📦 {} pushed fleet settings to {} host(s), {} failed",
+        user.username,
+        results.len(),
+        failed
+    );
+    Ok(Json(results))
+}
 
-                println!("🚀 Server starting on port {}", port);
+async fn join_token_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    AdminUser(user): AdminUser,
+) -> Result<Json<IssuedJoinToken>, (StatusCode, String)> {
+    let state = server_state.lock().unwrap();
+    let mut auth_manager = state.auth_manager.lock().unwrap();
+    if !auth_manager.config.manager.enabled {
+        return Err((StatusCode::NOT_FOUND, "Manager mode is off".to_string()));
+    }
+    let issued = auth_manager
+        .create_join_token(&user.username)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Json(issued))
+}
 
-                let listener = tokio::net::TcpListener::bind(addr).await;
-                match listener {
-                    Ok(listener) => {
-                        println!("✅ Server running at http://0.0.0.0:{}", port);
-                        println!("   Accessible from any device on your network!");
+async fn enroll_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    Json(request): Json<EnrollmentRequest>,
+) -> Result<Json<EnrollmentResponse>, (StatusCode, String)> {
+    let public_key = manager_public_key().map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let state = server_state.lock().unwrap();
+    let mut auth_manager = state.auth_manager.lock().unwrap();
+    if !auth_manager.config.manager.enabled {
+        return Err((StatusCode::NOT_FOUND, "Manager mode is off".to_string()));
+    }
+    let response = auth_manager
+        .enroll_host(&request, public_key)
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    println!("🤝 {} enrolled and waits for approval", request.host);
+    Ok(Json(response))
+}
 
-                        let server = axum::serve(listener, app);
+async fn fleet_grid_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    Query(query): Query<FleetGridQuery>,
+) -> Result<Json<FleetGrid>, (StatusCode, String)> {
+    let config = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.manager.clone()
+    };
+    if !config.enabled {
+        return Err((StatusCode::NOT_FOUND, "Manager mode is off".to_string()));
+    }
+    Ok(Json(fleet_grid(&reported_fleet_hosts(&config), &query)))
+}
 
-                        tokio::select! {
-                            _ = server => {
-                                println!("Server stopped normally");
-                            }
-                            _ = shutdown_rx => {
-                                println!("Server received shutdown signal");
-                            }
-                        };
-                    }
-                    Err(e) => {
-                        eprintln!("❌ Failed to bind to port {}: {}", port, e);
-                        let mut state = server_state_clone.lock().unwrap();
-                        state.is_running = false;
-                    }
-                }
+async fn compare_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    Query(query): Query<ComparisonQuery>,
+) -> Result<Json<Comparison>, (StatusCode, String)> {
+    {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        if !auth_manager.config.manager.enabled {
+            return Err((StatusCode::NOT_FOUND, "Manager mode is off".to_string()));
+        }
+    }
+    compare_fleet_hosts(&query)
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
 
-                let mut state = server_state_clone.lock().unwrap();
-                state.is_running = false;
-                state.shutdown_sender = None;
-            });
-        });
-        self.status_message = format!(
-            "✅ Server hosted on port {} (accessible from any device)",
-            port
-        );
+async fn fleet_alerts_handler(
+    server_state: Arc<Mutex<ServerState>>,
+) -> Result<Json<Vec<FleetAlert>>, (StatusCode, String)> {
+    let config = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.manager.clone()
+    };
+    if !config.enabled {
+        return Err((StatusCode::NOT_FOUND, "Manager mode is off".to_string()));
     }
+    Ok(Json(active_fleet_alerts(&config)))
+}
 
-    fn stop_server(&mut self) {
-        let shutdown_sender = {
-            let mut state = self.server_state.lock().unwrap();
-            state.shutdown_sender.take()
-        };
+// The token stays in the address, the page passes it on to /api/central/grid
+async fn fleet_page_handler() -> Html<&'static str> {
+    Html(include_str!("../public/fleet.html"))
+}
 
-        if let Some(sender) = shutdown_sender {
-            // Send shutdown signal - ignore error if receiver is dropped
-            let _ = sender.send(());
-            self.status_message = "🛑 Server shutdown initiated...".to_string();
-        } else {
-            self.status_message = "❌ Server is not running".to_string();
-        }
+async fn cluster_status_handler(server_state: Arc<Mutex<ServerState>>) -> Json<ClusterStatus> {
+    let config = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.manager.cluster.clone()
+    };
+    Json(cluster_status(&config))
+}
 
-        // Immediately mark as not running for UI responsiveness
-        {
-            let mut state = self.server_state.lock().unwrap();
-            state.is_running = false;
-        }
+async fn cluster_state_handler(
+    server_state: Arc<Mutex<ServerState>>,
+) -> Result<Json<ClusterState>, (StatusCode, String)> {
+    let config = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.manager.clone()
+    };
+    if !config.enabled {
+        return Err((StatusCode::NOT_FOUND, "Manager mode is off".to_string()));
     }
+    Ok(Json(cluster_state(&config)))
 }
 
-struct MyApp {
-    app_state: AppState,
+async fn enrollment_decision_handler(
     server_state: Arc<Mutex<ServerState>>,
-    // Remove these duplicate fields since they're in MainState:
-    // port_input: String,
-    // status_message: String,
+    AdminUser(user): AdminUser,
+    axum::extract::Path(host): axum::extract::Path<String>,
+    approve: bool,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let state = server_state.lock().unwrap();
+    let mut auth_manager = state.auth_manager.lock().unwrap();
+    match auth_manager.decide_enrollment(&host, approve, &user.username) {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err((StatusCode::NOT_FOUND, format!("{} hasn't enrolled", host))),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
 }
 
-impl Default for MyApp {
-    fn default() -> Self {
-        let auth_manager = AuthManager::new("crusty_auth.json")
-            .unwrap_or_else(|_| AuthManager::new("crusty_auth.json").unwrap());
-
-        let has_users = auth_manager.has_users();
-
-        let initial_state = if !has_users {
-            AppState::Setup(SetupState {
-                username: String::new(),
-                password: String::new(),
-                confirm_password: String::new(),
-                email: String::new(),
-                access_token: String::new(),
-                error_message: String::new(),
-                show_token_suggestion: true,
-            })
-        } else {
-            AppState::Login(LoginState {
-                username: String::new(),
-                password: String::new(),
-                email: String::new(),
-                error_message: String::new(),
-                show_recovery: false,
-            })
-        };
+// PWA files are compiled in like the dashboard, the ServeDir fallback depends on the
+// working directory
+async fn manifest_handler() -> (
+    [(axum::http::header::HeaderName, &'static str); 1],
+    &'static str,
+) {
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "application/manifest+json",
+        )],
+        include_str!("../public/manifest.webmanifest"),
+    )
+}
 
-        Self {
-            app_state: initial_state,
-            server_state: Arc::new(Mutex::new(ServerState::default())),
-            // Remove these:
-            // status_message: String::new(),
-            // port_input: String::new(),
-        }
-    }
+async fn service_worker_handler() -> (
+    [(axum::http::header::HeaderName, &'static str); 2],
+    &'static str,
+) {
+    (
+        [
+            (axum::http::header::CONTENT_TYPE, "text/javascript"),
+            // Browsers check for a new worker on every navigation
+            (axum::http::header::CACHE_CONTROL, "no-cache"),
+        ],
+        include_str!("../public/sw.js"),
+    )
 }
 
-// Axum apllication and routing of information
-fn create_app(server_state: Arc<Mutex<ServerState>>) -> Router {
-    let server_state_clone = server_state.clone();
+async fn icon_handler(
+    axum::extract::Path(file): axum::extract::Path<String>,
+) -> Result<([(axum::http::header::HeaderName, &'static str); 1], Vec<u8>), StatusCode> {
+    let size = match file.as_str() {
+        "192.png" => 192,
+        "512.png" => 512,
+        _ => return Err(StatusCode::NOT_FOUND),
+    };
+    let png = pwa_icon(size).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(([(axum::http::header::CONTENT_TYPE, "image/png")], png))
+}
 
-    Router::new()
-        .route(
-            "/api/status",
-            get(move |query: Query<TokenQuery>| status_handler(server_state, query)),
-        )
-        .route(
-            "/",
-            get(move |query: Query<TokenQuery>| index_handler(server_state_clone, query)),
+async fn storage_handler() -> Json<StorageReport> {
+    Json(read_storage_report())
+}
+
+async fn status_page_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    template: String,
+) -> Result<Html<String>, StatusCode> {
+    let (runner, metrics_config, page_config) = {
+        let state = server_state.lock().unwrap();
+        let runner = CheckRunner::from_state(&state);
+        let auth_manager = state.auth_manager.lock().unwrap();
+        (
+            runner,
+            auth_manager.config.metrics.clone(),
+            auth_manager.config.status_pages.clone(),
         )
-        .fallback_service(ServeDir::new("public"))
+    };
+
+    render_status_page(&runner, &metrics_config, &page_config, &template)
+        .await
+        .map(Html)
 }
 
-// Endpoint handlers with token validation
-async fn status_handler(
+// Unauthenticated, so it only renders the admin's curated subset
+async fn public_status_handler(
     server_state: Arc<Mutex<ServerState>>,
-    query: Query<TokenQuery>,
 ) -> Result<Html<String>, StatusCode> {
-    // Extract token validation into a separate scope to release the lock
-    let is_valid = {
+    let (runner, metrics_config, page_config) = {
         let state = server_state.lock().unwrap();
+        let runner = CheckRunner::from_state(&state);
         let auth_manager = state.auth_manager.lock().unwrap();
+        (
+            runner,
+            auth_manager.config.metrics.clone(),
+            auth_manager.config.status_pages.clone(),
+        )
+    };
 
-        if let Some(token) = &query.token {
-            auth_manager.validate_token(token).is_ok()
-        } else {
-            false
-        }
+    render_public_status_page(&runner, &metrics_config, &page_config)
+        .await
+        .map(Html)
+}
+
+// /badge/cpu.svg works as well, some wikis want the extension
+async fn badge_handler(
+    server_state: Arc<Mutex<ServerState>>,
+    viewer: Result<DashboardViewer, AuthError>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+) -> Result<([(axum::http::header::HeaderName, String); 2], String), axum::response::Response> {
+    use axum::response::IntoResponse;
+
+    let name = name.strip_suffix(".svg").unwrap_or(&name).to_string();
+    let (runner, public) = {
+        let state = server_state.lock().unwrap();
+        let runner = CheckRunner::from_state(&state);
+        let auth_manager = state.auth_manager.lock().unwrap();
+        (runner, auth_manager.config.status_pages.public.clone())
+    };
+    let detailed = match viewer {
+        Ok(_) => true,
+        Err(_) if public_badge(&public, &name) => false,
+        Err(e) => return Err(e.into_response()),
     };
 
-    if is_valid {
-        Ok(Html(status(server_state).await))
-    } else {
-        Err(StatusCode::UNAUTHORIZED)
-    }
+    let (label, message, state) = badge(&runner, &name, detailed)
+        .await
+        .ok_or_else(|| StatusCode::NOT_FOUND.into_response())?;
+    let cache_control = format!(
+        "{}, max-age={}",
+        if detailed { "private" } else { "public" },
+        runner.config.cache_max_age_secs
+    );
+    Ok((
+        [
+            (
+                axum::http::header::CONTENT_TYPE,
+                "image/svg+xml".to_string(),
+            ),
+            (axum::http::header::CACHE_CONTROL, cache_control),
+        ],
+        render_badge(&label, &message, state),
+    ))
 }
 
 async fn index_handler(
     server_state: Arc<Mutex<ServerState>>,
     query: Query<TokenQuery>,
+    ClientIp(ip): ClientIp,
 ) -> Result<Html<String>, StatusCode> {
     let state = server_state.lock().unwrap();
     let auth_manager = state.auth_manager.lock().unwrap();
 
     if let Some(token) = &query.token {
-        if auth_manager.validate_token(token).is_ok() {
-            let html_content = include_str!("../public/index.html")
-                .replace("{{TOKEN}}", token)
-                .replace("{{PORT}}", &state.port.to_string());
-            Ok(Html(html_content))
+        let mut kiosk = false;
+        // The page only ever sees a session token, which expires
+        let session = if let Some(username) = auth_manager.redeem_login_link(token) {
+            record_login(Some(&username), LoginMethod::LoginLink, ip, None);
+            auth_manager.create_web_session(&username)
         } else {
-            Err(StatusCode::UNAUTHORIZED)
-        }
+            match auth_manager.validate_web_session(token) {
+                // A reload, not a new login
+                Some(_) => token.clone(),
+                None => match auth_manager.validate_token(token) {
+                    Ok(username) => {
+                        record_login(Some(&username), LoginMethod::AccessToken, ip, None);
+                        auth_manager.create_web_session(&username)
+                    }
+                    // Displays keep their token, there's no user to open a session for
+                    Err(_) if auth_manager.authenticate_kiosk_token(token, ip).is_some() => {
+                        kiosk = true;
+                        token.clone()
+                    }
+                    Err(_) => {
+                        record_login(
+                            None,
+                            LoginMethod::AccessToken,
+                            ip,
+                            Some("Invalid or expired token"),
+                        );
+                        return Err(StatusCode::UNAUTHORIZED);
+                    }
+                },
+            }
+        };
+        let html_content = include_str!("../public/index.html")
+            .replace("{{TOKEN}}", &session)
+            .replace("{{KIOSK}}", &kiosk.to_string())
+            .replace("{{PORT}}", &state.port.to_string());
+        Ok(Html(html_content))
     } else {
         // Return a login page for token entry
         let login_html = r#"
@@ -324,6 +1671,7 @@ async fn index_handler(
                 <p>Enter your access token:</p>
                 <input type="password" id="token" placeholder="Access Token">
                 <button onclick="login()">Access System</button>
+                {{ADDRESSES}}
             </div>
             <script>
                 function login() {
@@ -336,42 +1684,66 @@ async fn index_handler(
         </body>
         </html>
         "#;
-        Ok(Html(login_html.to_string()))
+        // Other devices on the network can use any of these
+        let addresses: String = lan_addresses()
+            .iter()
+            .map(|address| {
+                let url = address.url(state.port);
+                format!(
+                    "<li><a href=\"{0}\">{0}</a> ({1})</li>",
+                    url,
+                    html_escape(&address.interface)
+                )
+            })
+            .collect();
+        let addresses = if addresses.is_empty() {
+            String::new()
+        } else {
+            format!("<p>Also reachable at:</p><ul>{}</ul>", addresses)
+        };
+        Ok(Html(login_html.replace("{{ADDRESSES}}", &addresses)))
     }
 }
 
 // Display the system statistics collected
 async fn status(server_state: Arc<Mutex<ServerState>>) -> String {
-    let mut sys = sysinfo::System::new_all();
-    sys.refresh_all();
-    let token = {
+    let (system, devices) = {
         let state = server_state.lock().unwrap();
-        state
-            .auth_manager
-            .lock()
-            .unwrap()
-            .config
-            .users
-            .values()
-            .next()
-            .map(|u| u.access_token.clone())
-            .unwrap_or_default()
+        let auth_manager = state.auth_manager.lock().unwrap();
+        (
+            state.system.clone(),
+            auth_manager.config.metrics.devices.clone(),
+        )
     };
     let mut out = String::new();
     out.push_str(&format!(
         "System name: {:?}\n",
-        sysinfo::System::name().unwrap_or_default()
+        system.system_name().unwrap_or_default()
     ));
+    let (used_memory, _) = system.memory();
     out.push_str(&format!(
         "Memory in Use: {} MB\n",
-        sys.used_memory() / 1024 / 1024
+        used_memory / 1024 / 1024
     ));
-    out.push_str(&format!("CPU usage: {:.1}%\n", sys.global_cpu_usage()));
+    out.push_str(&format!("CPU usage: {:.1}%\n", system.cpu_usage()));
 
     out.push_str(&get_hardware_status(&server_state));
 
+    if let Some(cgroup) = read_cgroup_stats().filter(|stats| stats.is_relevant()) {
+        out.push_str(&cgroup_status(&cgroup));
+    }
+    out.push_str(&custom_metrics_status());
+
+    let storage = read_storage_report();
+    if !storage.raid.is_empty() {
+        out.push_str(&raid_status(&storage.raid));
+    }
+    if !storage.zfs_pools.is_empty() || !storage.btrfs.is_empty() {
+        out.push_str(&pool_status(&storage));
+    }
+
     // Fetch network info
-    match network_info().await {
+    match network_info(&devices).await {
         Ok(networks) => {
             out.push_str("\nNetwork Statistics (Total):\n");
             for net in networks {
@@ -384,7 +1756,7 @@ async fn status(server_state: Arc<Mutex<ServerState>>) -> String {
     }
 
     // Get current network traffic
-    match network_traffic().await {
+    match network_traffic(&devices).await {
         Ok(traffic) => {
             out.push_str("\nCurrent Network Traffic:\n");
             for net in traffic {
@@ -396,7 +1768,12 @@ async fn status(server_state: Arc<Mutex<ServerState>>) -> String {
         }
     }
 
-    match check_components().await {
+    let wifi = read_wifi_interfaces();
+    if !wifi.is_empty() {
+        out.push_str(&wifi_status(&wifi));
+    }
+
+    match check_components(&devices).await {
         Ok(components) => {
             out.push_str("\nComponents:\n");
             if components.is_empty() {
@@ -411,7 +1788,7 @@ async fn status(server_state: Arc<Mutex<ServerState>>) -> String {
         }
     }
 
-    match check_disks().await {
+    match check_disks(&devices).await {
         Ok(disks) => {
             out.push_str("\nDisks:\n");
             if disks.is_empty() {
@@ -425,606 +1802,76 @@ async fn status(server_state: Arc<Mutex<ServerState>>) -> String {
             out.push_str(&format!("\nError checking disks: {}\n", e));
         }
     }
-    out.push_str(&format!(
-        "\nAccess URL: http://localhost:3000/?token={}",
-        token
-    ));
+    out.push_str("\nAccess URL: http://localhost:3000/");
     out
 }
 
-enum AppAction {
-    None,
-    SwitchToLogin(LoginState),
-    SwitchToMain(MainState),
-    SwitchToRecovery,
-    SwitchToSmtpConfig(String), // pass current user for return
-}
-
-impl eframe::App for MyApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        let mut action = AppAction::None;
-        match &mut self.app_state {
-            AppState::Setup(setup_state) => {
-                egui::CentralPanel::default().show(ctx, |ui| {
-                    ui.heading("🦀 Crusty Server - First Time Setup");
-                    ui.separator();
-
-                    ui.label("Create your administrator account:");
-
-                    ui.horizontal(|ui| {
-                        ui.label("Username:");
-                        ui.text_edit_singleline(&mut setup_state.username);
-                    });
-
-                    ui.horizontal(|ui| {
-                        ui.label("Password:");
-                        ui.add(
-                            egui::TextEdit::singleline(&mut setup_state.password).password(true),
-                        );
-                    });
-
-                    ui.horizontal(|ui| {
-                        ui.label("Confirm Password:");
-                        ui.add(
-                            egui::TextEdit::singleline(&mut setup_state.confirm_password)
-                                .password(true),
-                        );
-                    });
-
-                    ui.horizontal(|ui| {
-                        ui.label("Email:");
-                        ui.text_edit_singleline(&mut setup_state.email);
-                    });
-
-                    ui.separator();
-                    ui.heading("Access Token Configuration");
-                    ui.label("This token will be used to access the web interface.");
-
-                    ui.horizontal(|ui| {
-                        ui.label("Access Token:");
-                        ui.text_edit_singleline(&mut setup_state.access_token);
-
-                        if ui.button("🎲 Suggest Token").clicked() {
-                            setup_state.access_token = AuthManager::generate_suggested_token();
-                        }
-                    });
-
-                    if setup_state.show_token_suggestion && setup_state.access_token.is_empty() {
-                        ui.colored_label(
-                            egui::Color32::YELLOW,
-                            "💡 Click 'Suggest Token' to generate a secure token",
-                        );
-                    }
-
-                    if !setup_state.error_message.is_empty() {
-                        ui.colored_label(egui::Color32::RED, &setup_state.error_message);
-                    }
-
-                    ui.separator();
-
-                    if ui.button("✅ Complete Setup").clicked() {
-                        // Validate inputs
-                        if setup_state.username.len() < 3 {
-                            setup_state.error_message =
-                                "Username must be at least 3 characters".to_string();
-                        } else if setup_state.password.len() < 8 {
-                            setup_state.error_message =
-                                "Password must be at least 8 characters".to_string();
-                        } else if setup_state.password != setup_state.confirm_password {
-                            setup_state.error_message = "Passwords do not match".to_string();
-                        } else if setup_state.access_token.len() < 8 {
-                            setup_state.error_message =
-                                "Access token must be at least 8 characters".to_string();
-                        } else if !setup_state.email.contains('@') {
-                            setup_state.error_message =
-                                "Please enter a valid email address".to_string();
-                        } else {
-                            // Try to register the user
-                            let server_state = self.server_state.lock().unwrap();
-                            let mut auth_manager = server_state.auth_manager.lock().unwrap();
-                            match auth_manager.register_user(
-                                &setup_state.username,
-                                &setup_state.password,
-                                &setup_state.email,
-                                &setup_state.access_token,
-                            ) {
-                                Ok(()) => {
-                                    action = AppAction::SwitchToLogin(LoginState {
-                                        username: setup_state.username.clone(),
-                                        password: String::new(),
-                                        email: String::new(),
-                                        error_message: String::new(),
-                                        show_recovery: false,
-                                    });
-                                }
-                                Err(e) => {
-                                    setup_state.error_message = e;
-                                }
-                            }
-                        }
-                    }
-                });
-            }
-
-            AppState::Login(login_state) => {
-                egui::CentralPanel::default().show(ctx, |ui| {
-                    ui.heading("🦀 Crusty Server - Login");
-                    ui.separator();
-
-                    ui.horizontal(|ui| {
-                        ui.label("Username:");
-                        ui.text_edit_singleline(&mut login_state.username);
-                    });
-
-                    ui.horizontal(|ui| {
-                        ui.label("Password:");
-                        ui.add(
-                            egui::TextEdit::singleline(&mut login_state.password).password(true),
-                        );
-                    });
-
-                    if !login_state.error_message.is_empty() {
-                        ui.colored_label(egui::Color32::RED, &login_state.error_message);
-                    }
-
-                    ui.separator();
-
-                    if ui.button("🔑 Login").clicked() {
-                        let server_state = self.server_state.lock().unwrap();
-                        let auth_manager = server_state.auth_manager.lock().unwrap();
-                        match auth_manager
-                            .authenticate(&login_state.username, &login_state.password)
-                        {
-                            Ok(_token) => {
-                                action = AppAction::SwitchToMain(MainState {
-                                    port_input: "3000".to_string(),
-                                    server_state: self.server_state.clone(),
-                                    status_message: String::new(),
-                                    current_user: login_state.username.clone(),
-                                });
-                            }
-                            Err(e) => {
-                                login_state.error_message = e;
-                            }
-                        }
-                    }
-
-                    if ui.button("🔓 Forgot Credentials?").clicked() {
-                        login_state.show_recovery = true;
-                    }
-
-                    if login_state.show_recovery {
-                        ui.separator();
-                        ui.heading("Recover Credentials");
-                        ui.label("Enter your email address to receive your credentials:");
-
-                        ui.horizontal(|ui| {
-                            ui.label("Email:");
-                            ui.text_edit_singleline(&mut login_state.email);
-                        });
-
-                        if ui.button("📧 Send Recovery Email").clicked() {
-                            let server_state = self.server_state.lock().unwrap();
-                            let auth_manager = server_state.auth_manager.lock().unwrap();
-                            match auth_manager.recover_credentials(&login_state.email) {
-                                Ok(()) => {
-                                    login_state.error_message =
-                                        "Recovery email sent! Check your inbox.".to_string();
-                                    login_state.show_recovery = false;
-                                }
-                                Err(e) => {
-                                    login_state.error_message = e;
-                                }
-                            }
-                        }
-
-                        if ui.button("❌ Cancel").clicked() {
-                            login_state.show_recovery = false;
-                        }
-                    }
-                });
-            }
-
-            AppState::Main(main_state) => {
-                egui::CentralPanel::default().show(ctx, |ui| {
-                    // Header section with icon and title
-                    ui.horizontal(|ui| {
-                        ui.heading("🦀 Crusty Server");
-                        ui.label("v1.0.0");
-                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                            ui.label(format!("Logged in as: {}", main_state.current_user));
-                            if ui.button("🚪 Logout").clicked() {
-                                action = AppAction::SwitchToLogin(LoginState {
-                                    username: String::new(),
-                                    password: String::new(),
-                                    email: String::new(),
-                                    error_message: String::new(),
-                                    show_recovery: false,
-                                });
-                            }
-                        });
-                    });
-                    ui.separator();
-
-                    // Server configuration section
-                    ui.vertical(|ui| {
-                        ui.heading("Server Configuration");
-
-                        egui::Frame::group(ui.style())
-                            .inner_margin(egui::Margin::same(10))
-                            .show(ui, |ui| {
-                                ui.horizontal(|ui| {
-                                    ui.label("Port:")
-                                        .on_hover_text("Port number for the web server");
-                                    ui.add(
-                                        egui::TextEdit::singleline(&mut main_state.port_input)
-                                            .desired_width(80.0),
-                                    );
-
-                                    // Visual port validation
-                                    if main_state.port_input.parse::<u16>().is_err() {
-                                        ui.colored_label(egui::Color32::RED, "❌ Invalid port");
-                                    } else {
-                                        ui.colored_label(egui::Color32::GREEN, "✅ Valid");
-                                    }
-                                });
-                            });
-                    });
-                    ui.separator();
-
-                    // Server control section
-                    ui.vertical(|ui| {
-                        ui.heading("Server Control");
-
-                        let (is_running, current_port) = {
-                            let state = main_state.server_state.lock().unwrap();
-                            (state.is_running, state.port)
-                        };
-
-                        ui.horizontal(|ui| {
-                            if !is_running {
-                                if ui
-                                    .add(
-                                        egui::Button::new("🚀 Start Server")
-                                            .fill(egui::Color32::from_rgb(46, 125, 50)),
-                                    )
-                                    .clicked()
-                                {
-                                    main_state.start_server();
-                                }
-                            } else {
-                                if ui
-                                    .add(
-                                        egui::Button::new("🛑 Stop Server")
-                                            .fill(egui::Color32::from_rgb(211, 47, 47)),
-                                    )
-                                    .clicked()
-                                {
-                                    main_state.stop_server();
-                                }
-                            }
-
-                            // Status indicator
-                            ui.with_layout(
-                                egui::Layout::right_to_left(egui::Align::Center),
-                                |ui| {
-                                    if is_running {
-                                        ui.colored_label(
-                                            egui::Color32::GREEN,
-                                            format!("● Running on port {}", current_port),
-                                        );
-                                    } else {
-                                        ui.colored_label(egui::Color32::GRAY, "● Stopped");
-                                    }
-                                },
-                            );
-                        });
-
-                        // Status message with better styling
-                        if !main_state.status_message.is_empty() {
-                            ui.separator();
-                            egui::Frame::group(ui.style())
-                                .fill(egui::Color32::from_rgba_unmultiplied(30, 30, 30, 100))
-                                .inner_margin(egui::Margin::same(8))
-                                .show(ui, |ui| {
-                                    ui.horizontal(|ui| {
-                                        ui.label("📢");
-                                        ui.label(&main_state.status_message);
-                                    });
-                                });
-                        }
-                    });
-
-                    // Server information section (only when running)
-                    let (is_running, current_port, last_update) = {
-                        let state = main_state.server_state.lock().unwrap();
-                        let hardware_state = state.hardware_state.lock().unwrap();
-                        let last_update = hardware_state.last_update.elapsed().as_secs();
-                        (state.is_running, state.port, last_update)
-                    };
-
-                    if is_running {
-                        ui.separator();
-                        ui.vertical(|ui| {
-                            ui.heading("📊 Server Information");
-
-                            egui::Frame::group(ui.style())
-                                .inner_margin(egui::Margin::same(10))
-                                .show(ui, |ui| {
-                                    ui.label("📍 Access URLs:");
-                                    ui.indent("urls", |ui| {
-                                        ui.monospace(format!(
-                                            "Local:    http://localhost:{}",
-                                            current_port
-                                        ));
-                                        ui.monospace(format!(
-                                            "Network:  http://[YOUR-IP]:{}",
-                                            current_port
-                                        ));
-                                    });
-
-                                    ui.add_space(5.0);
-                                    ui.label(
-                                        "💡 Replace [YOUR-IP] with your computer's IP address",
-                                    );
-                                    ui.colored_label(
-                                        egui::Color32::LIGHT_BLUE,
-                                        "🌐 Accessible from any device on your network!",
-                                    );
-                                });
-
-                            ui.add_space(10.0);
-
-                            // Hardware monitoring status
-                            ui.heading("🔧 Hardware Monitoring");
-                            egui::Frame::group(ui.style())
-                                .inner_margin(egui::Margin::same(10))
-                                .show(ui, |ui| {
-                                    ui.horizontal(|ui| {
-                                        ui.label("Last updated:");
-                                        if last_update < 60 {
-                                            ui.colored_label(
-                                                egui::Color32::GREEN,
-                                                format!("{} seconds ago", last_update),
-                                            );
-                                        } else {
-                                            ui.colored_label(
-                                                egui::Color32::YELLOW,
-                                                format!("{} seconds ago", last_update),
-                                            );
-                                        }
-                                    });
-                                    ui.label("⏱️ Power and thermal data refreshes every 60s");
-                                });
-                        });
-                    }
-
-                    // Instructions section
-                    ui.separator();
-                    ui.vertical(|ui| {
-                        ui.heading("💡 Instructions");
-
-                        egui::Frame::group(ui.style())
-                            .fill(egui::Color32::from_rgba_unmultiplied(25, 25, 35, 100))
-                            .inner_margin(egui::Margin::same(10))
-                            .show(ui, |ui| {
-                                ui.vertical(|ui| {
-                                    ui.horizontal(|ui| {
-                                        ui.label("1.");
-                                        ui.label("Enter a port number (default: 3000)");
-                                    });
-                                    ui.horizontal(|ui| {
-                                        ui.label("2.");
-                                        ui.label("Click 'Start Server' to begin hosting");
-                                    });
-                                    ui.horizontal(|ui| {
-                                        ui.label("3.");
-                                        ui.label("Access the status page from any browser");
-                                    });
-                                    ui.horizontal(|ui| {
-                                        ui.label("4.");
-                                        ui.label("Use 'Stop Server' to shut down");
-                                    });
-                                });
-                            });
-                    });
-
-                    // Footer
-                    ui.separator();
-                    ui.horizontal(|ui| {
-                        ui.small(
-                            "Created for Nagios Enterprises LLC • 2025 Summer Nintern Program",
-                        );
-                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                            ui.small("🦀 Powered by Rust");
-                        });
-                    });
-                });
-            }
-
-            AppState::Recovery(recovery_state) => {
-                egui::CentralPanel::default().show(ctx, |ui| {
-                    ui.heading("🔓 Recover Credentials");
-                    ui.separator();
-
-                    ui.label("Enter your email address to receive your login credentials:");
-
-                    ui.horizontal(|ui| {
-                        ui.label("Email:");
-                        ui.text_edit_singleline(&mut recovery_state.email);
-                    });
-
-                    if !recovery_state.message.is_empty() {
-                        let color = if recovery_state.is_success {
-                            egui::Color32::GREEN
-                        } else {
-                            egui::Color32::RED
-                        };
-                        ui.colored_label(color, &recovery_state.message);
-                    }
-
-                    ui.separator();
-
-                    if ui.button("📧 Send Recovery Email").clicked() {
-                        let server_state = self.server_state.lock().unwrap();
-                        let auth_manager = server_state.auth_manager.lock().unwrap();
-                        match auth_manager.recover_credentials(&recovery_state.email) {
-                            Ok(()) => {
-                                recovery_state.message =
-                                    "Recovery email sent! Check your inbox.".to_string();
-                                recovery_state.is_success = true;
-                            }
-                            Err(e) => {
-                                recovery_state.message = e;
-                                recovery_state.is_success = false;
-                            }
-                        }
-                    }
-
-                    if ui.button("⬅️ Back to Login").clicked() {
-                        action = AppAction::SwitchToLogin(LoginState {
-                            username: String::new(),
-                            password: String::new(),
-                            email: String::new(),
-                            error_message: String::new(),
-                            show_recovery: false,
-                        });
-                    }
-                });
-            }
-
-            AppState::SmtpConfig(smtp_state) => {
-                egui::CentralPanel::default().show(ctx, |ui| {
-                    ui.heading("📧 SMTP Configuration");
-                    ui.separator();
-
-                    ui.label("Configure email settings for password recovery:");
-
-                    ui.horizontal(|ui| {
-                        ui.label("SMTP Server:");
-                        ui.text_edit_singleline(&mut smtp_state.server);
-                    });
-
-                    ui.horizontal(|ui| {
-                        ui.label("Port:");
-                        ui.text_edit_singleline(&mut smtp_state.port);
-                    });
-
-                    ui.horizontal(|ui| {
-                        ui.label("Username:");
-                        ui.text_edit_singleline(&mut smtp_state.username);
-                    });
-
-                    ui.horizontal(|ui| {
-                        ui.label("Password:");
-                        ui.add(egui::TextEdit::singleline(&mut smtp_state.password).password(true));
-                    });
-
-                    ui.horizontal(|ui| {
-                        ui.checkbox(&mut smtp_state.use_tls, "Use TLS");
-                    });
-
-                    if !smtp_state.message.is_empty() {
-                        ui.colored_label(egui::Color32::GREEN, &smtp_state.message);
-                    }
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Check for CLI mode flags
+    let mut args: Vec<String> = env::args().collect();
+    init_container_data_dir(&mut args);
+    init_data_dir(&mut args)?;
+    init_demo_mode(&mut args);
+    init_replay(&mut args)?;
+
+    // One-shot collection for cron jobs and other monitoring systems
+    if args.get(1).map(String::as_str) == Some("snapshot") {
+        std::process::exit(run_snapshot(&args[2..]));
+    }
+    
+    if args.get(1).map(String::as_str) == Some("logs") {
+        return show_logs(&args[1..]);
+    }
+    if args.get(1).map(String::as_str) == Some("top") {
+        return run_top();
+    }
+    if args.get(1).map(String::as_str) == Some("push-keys") {
+        return run_push_keys(&args[2..]);
+    }
+    if args.get(1).map(String::as_str) == Some("discover") {
+        return run_discover(&args[2..]);
+    }
+    if args.get(1).map(String::as_str) == Some("record") {
+        return run_record(&args[2..]);
+    }
 
-                    ui.separator();
-
-                    if ui.button("💾 Save Configuration").clicked() {
-                        match smtp_state.port.parse::<u16>() {
-                            Ok(port) => {
-                                let smtp_config = SmtpConfig {
-                                    server: smtp_state.server.clone(),
-                                    port,
-                                    username: smtp_state.username.clone(),
-                                    password: smtp_state.password.clone(),
-                                    use_tls: smtp_state.use_tls,
-                                };
-
-                                let server_state = self.server_state.lock().unwrap();
-                                let mut auth_manager = server_state.auth_manager.lock().unwrap();
-                                match auth_manager.configure_smtp(smtp_config) {
-                                    Ok(()) => {
-                                        smtp_state.message =
-                                            "SMTP configuration saved successfully!".to_string();
-                                    }
-                                    Err(e) => {
-                                        smtp_state.message = format!("Error: {}", e);
-                                    }
-                                }
-                            }
-                            Err(_) => {
-                                smtp_state.message = "Invalid port number".to_string();
-                            }
-                        }
-                    }
+    if args.get(1).map(String::as_str) == Some("container") {
+        return run_container_mode(&args[1..]);
+    }
 
-                    if ui.button("⬅️ Back").clicked() {
-                        action = AppAction::SwitchToSmtpConfig("admin".to_string());
-                    }
-                });
-            }
-        }
-        match action {
-            AppAction::SwitchToLogin(login_state) => {
-                self.app_state = AppState::Login(login_state);
-            }
-            AppAction::SwitchToMain(main_state) => {
-                self.app_state = AppState::Main(main_state);
-            }
-            AppAction::SwitchToRecovery => {
-                self.app_state = AppState::Recovery(RecoveryState {
-                    email: String::new(),
-                    message: String::new(),
-                    is_success: false,
-                });
-            }
-            AppAction::SwitchToSmtpConfig(current_user) => {
-                self.app_state = AppState::Main(MainState {
-                    port_input: "3000".to_string(),
-                    server_state: self.server_state.clone(),
-                    status_message: String::new(),
-                    current_user,
-                });
-            }
-            AppAction::None => {}
-        }
+    // Headless service commands never read stdin
+    if args
+        .iter()
+        .any(|arg| matches!(arg.as_str(), "--daemon" | "daemon" | "start"))
+    {
+        return run_daemon_mode(&args[1..]);
+    }
+    if args.iter().any(|arg| arg == "stop") {
+        return stop_daemon(&args[1..]);
+    }
+    if args.iter().any(|arg| arg == "status") {
+        std::process::exit(daemon_status(&args[1..])?);
+    }
+    if args.iter().any(|arg| arg == "alerts") {
+        return show_daemon_alerts(&args[1..]);
+    }
+    if args.iter().any(|arg| arg == "reload") {
+        return reload_daemon(&args[1..]);
     }
-}
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Check for CLI mode flags
-    let args: Vec<String> = env::args().collect();
-    
-    // Check for --cli, --no-gui, or daemon flags
-    let cli_mode = args.iter().any(|arg| {
-        matches!(arg.as_str(), "--cli" | "--no-gui" | "--daemon" | "daemon" | "start" | "stop" | "status")
-    });
+    // Check for --cli or --no-gui flags
+    let cli_mode = args
+        .iter()
+        .any(|arg| matches!(arg.as_str(), "--cli" | "--no-gui"));
 
-    if cli_mode {
-        // Run in CLI mode
+    if cli_mode || !cfg!(feature = "gui") {
+        // Run in CLI mode, the only one in builds without the gui feature
         run_cli()?;
         Ok(())
     } else {
         // Run in GUI mode
-        let options = eframe::NativeOptions {
-            viewport: egui::ViewportBuilder::default().with_icon(std::sync::Arc::new(egui::IconData {
-                rgba: image::load_from_memory(include_bytes!("../Assets/icon.png"))
-                    .unwrap()
-                    .to_rgba8()
-                    .to_vec(),
-                width: 250,
-                height: 325,
-            })),
-            ..Default::default()
-        };
-
-        eframe::run_native(
-            "Crusty Crawler",
-            options,
-            Box::new(|_cc| Ok(Box::<MyApp>::default())),
-        )?;
-        
+        #[cfg(feature = "gui")]
+        run_gui()?;
         Ok(())
     }
 }
@@ -0,0 +1,219 @@
+// mDNS module for Crusty-Crawler
+// Advertises the web server as _crusty._tcp on the local network so other agents and a manager
+// can find it without configuration, and browses for them with `discover`
+
+const MDNS_SERVICE_TYPE: &str = "_crusty._tcp.local.";
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MdnsConfig {
+    pub advertise: bool,
+    // Instance name shown by `discover`, the machine's hostname when empty
+    pub instance: String,
+}
+
+impl Default for MdnsConfig {
+    fn default() -> Self {
+        Self {
+            advertise: true,
+            instance: String::new(),
+        }
+    }
+}
+
+impl MdnsConfig {
+    fn instance_name(&self) -> String {
+        if self.instance.is_empty() {
+            sysinfo::System::host_name().unwrap_or_else(|| "crusty".to_string())
+        } else {
+            self.instance.clone()
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct DiscoveredAgent {
+    pub instance: String,
+    pub host: String,
+    pub addresses: Vec<std::net::IpAddr>,
+    pub port: u16,
+    pub version: Option<String>,
+}
+
+impl DiscoveredAgent {
+    fn from_service(info: &mdns_sd::ServiceInfo) -> Self {
+        let mut addresses: Vec<std::net::IpAddr> = info.get_addresses().iter().copied().collect();
+        addresses.sort_by_key(|ip| (ip.is_ipv6(), *ip));
+        Self {
+            instance: info
+                .get_fullname()
+                .trim_end_matches(MDNS_SERVICE_TYPE)
+                .trim_end_matches('.')
+                .to_string(),
+            host: info.get_hostname().trim_end_matches('.').to_string(),
+            addresses,
+            port: info.get_port(),
+            version: info.get_property_val_str("version").map(str::to_string),
+        }
+    }
+}
+
+fn mdns_service(config: &MdnsConfig, port: u16) -> Result<mdns_sd::ServiceInfo, String> {
+    let instance = config.instance_name();
+    let host = sysinfo::System::host_name().unwrap_or_else(|| instance.clone());
+    let properties = [("version", env!("CARGO_PKG_VERSION")), ("path", "/")];
+    let service = mdns_sd::ServiceInfo::new(
+        MDNS_SERVICE_TYPE,
+        &instance,
+        &format!("{}.local.", host),
+        "",
+        port,
+        &properties[..],
+    )
+    .map_err(|e| e.to_string())?;
+    // Addresses follow the interfaces, like the URLs from lan_addresses()
+    Ok(service.enable_addr_auto())
+}
+
+// Registers while the web server runs and re-registers when the port or name changes
+fn spawn_mdns_advertiser(server_state: Arc<Mutex<ServerState>>) {
+    std::thread::spawn(move || {
+        let mut daemon: Option<mdns_sd::ServiceDaemon> = None;
+        // Full name and port of what's currently registered
+        let mut registered: Option<(String, u16)> = None;
+        loop {
+            let (config, port) = {
+                let state = server_state.lock().unwrap();
+                let config = state.auth_manager.lock().unwrap().config.mdns.clone();
+                (config, state.is_running.then_some(state.port))
+            };
+            let wanted = port.filter(|_| config.advertise).map(|port| {
+                (
+                    format!("{}.{}", config.instance_name(), MDNS_SERVICE_TYPE),
+                    port,
+                )
+            });
+
+            if wanted != registered {
+                if let (Some(daemon), Some((fullname, _))) = (&daemon, &registered)
+                    && let Err(e) = daemon.unregister(fullname)
+                {
+                    eprintln!("⚠️ Failed to withdraw mDNS advertisement: {}", e);
+                }
+                registered = None;
+
+                if let Some((fullname, port)) = wanted {
+                    if daemon.is_none() {
+                        match mdns_sd::ServiceDaemon::new() {
+                            Ok(started) => daemon = Some(started),
+                            Err(e) => eprintln!("❌ Failed to start mDNS: {}", e),
+                        }
+                    }
+                    if let Some(daemon) = &daemon {
+                        match mdns_service(&config, port)
+                            .and_then(|service| daemon.register(service).map_err(|e| e.to_string()))
+                        {
+                            Ok(()) => registered = Some((fullname, port)),
+                            Err(e) => eprintln!("❌ Failed to advertise via mDNS: {}", e),
+                        }
+                    }
+                }
+            }
+            std::thread::sleep(Duration::from_secs(5));
+        }
+    });
+}
+
+pub fn discover_agents(timeout: Duration) -> Result<Vec<DiscoveredAgent>, String> {
+    let daemon = mdns_sd::ServiceDaemon::new().map_err(|e| e.to_string())?;
+    let events = daemon
+        .browse(MDNS_SERVICE_TYPE)
+        .map_err(|e| e.to_string())?;
+
+    let mut agents: HashMap<String, DiscoveredAgent> = HashMap::new();
+    let deadline = Instant::now() + timeout;
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        match events.recv_timeout(remaining) {
+            Ok(mdns_sd::ServiceEvent::ServiceResolved(info)) => {
+                agents.insert(
+                    info.get_fullname().to_string(),
+                    DiscoveredAgent::from_service(&info),
+                );
+            }
+            Ok(mdns_sd::ServiceEvent::ServiceRemoved(_, fullname)) => {
+                agents.remove(&fullname);
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    let _ = daemon.shutdown();
+
+    let mut agents: Vec<DiscoveredAgent> = agents.into_values().collect();
+    agents.sort_by(|a, b| a.instance.cmp(&b.instance));
+    Ok(agents)
+}
+
+fn parse_discover_args(args: &[String]) -> Result<(Duration, bool), String> {
+    let mut timeout = Duration::from_secs(3);
+    let mut json = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--timeout" => {
+                let secs: u64 = args
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .filter(|secs| (1..=60).contains(secs))
+                    .ok_or("--timeout needs a number of seconds between 1 and 60")?;
+                timeout = Duration::from_secs(secs);
+            }
+            "--json" => json = true,
+            other => return Err(format!("Unknown argument: {}", other)),
+        }
+    }
+    Ok((timeout, json))
+}
+
+// `discover [--timeout SECS] [--json]` lists agents advertising on the local network
+pub fn run_discover(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let (timeout, json) = match parse_discover_args(args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            eprintln!("Usage: discover [--timeout SECS] [--json]");
+            std::process::exit(2);
+        }
+    };
+
+    if !json {
+        println!("🔎 Looking for agents for {}s...", timeout.as_secs());
+    }
+    let agents = discover_agents(timeout)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&agents)?);
+        return Ok(());
+    }
+
+    if agents.is_empty() {
+        println!("No agents found");
+        return Ok(());
+    }
+    println!("{:<24} {:<24} {:<10} URL", "INSTANCE", "HOST", "VERSION");
+    for agent in &agents {
+        let url = agent
+            .addresses
+            .first()
+            .map(|ip| format!("http://{}/", SocketAddr::new(*ip, agent.port)))
+            .unwrap_or_else(|| format!("http://{}:{}/", agent.host, agent.port));
+        println!(
+            "{:<24} {:<24} {:<10} {}",
+            agent.instance,
+            agent.host,
+            agent.version.as_deref().unwrap_or("-"),
+            url
+        );
+    }
+    println!("{} agent(s) found", agents.len());
+    Ok(())
+}
@@ -0,0 +1,338 @@
+// Metrics module for Crusty-Crawler
+// Collects numeric samples from every data source into a single list of named, labelled metrics
+
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Metric {
+    pub name: String,
+    pub value: f64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl Metric {
+    pub fn new(name: &str, value: f64) -> Self {
+        Self {
+            name: name.to_string(),
+            value,
+            labels: BTreeMap::new(),
+        }
+    }
+
+    pub fn label(mut self, key: &str, value: &str) -> Self {
+        self.labels.insert(key.to_string(), value.to_string());
+        self
+    }
+}
+
+// Label values escaped as the exposition format wants them, a newline would start a new sample
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// Prometheus-style sample: name{key="value"} value
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.labels.is_empty() {
+            let labels: Vec<String> = self
+                .labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, escape_label_value(value)))
+                .collect();
+            write!(f, "{{{}}}", labels.join(","))?;
+        }
+        write!(f, " {}", self.value)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MetricsConfig {
+    // Windows performance counter paths sampled through PDH, wildcards allowed
+    pub windows_counters: Vec<String>,
+    pub ebpf: EbpfConfig,
+    // Third-party tools run on a schedule and parsed into metrics
+    pub collectors: Vec<ToolCollector>,
+    // Naming and filtering for the /metrics scrape endpoint, /api/metrics is left untouched
+    pub prometheus: PrometheusConfig,
+    pub statsd: StatsdConfig,
+    // Grouped per-process series, off by default
+    pub processes: ProcessMetricsConfig,
+    // Backs off the expensive collectors while the host is under pressure
+    pub adaptive: AdaptiveCollectionConfig,
+    // Backs off further and skips the speed test and hardware-query on battery
+    pub power: PowerConfig,
+    // Computed from the other metrics on every collection
+    pub derived: Vec<DerivedMetric>,
+    // Disks, interfaces and components left out of metrics, checks and status output
+    pub devices: DeviceFilters,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            windows_counters: vec![
+                "\\Processor(_Total)\\% Processor Time".to_string(),
+                "\\LogicalDisk(*)\\Avg. Disk Queue Length".to_string(),
+            ],
+            ebpf: EbpfConfig::default(),
+            collectors: Vec::new(),
+            prometheus: PrometheusConfig::default(),
+            statsd: StatsdConfig::default(),
+            processes: ProcessMetricsConfig::default(),
+            adaptive: AdaptiveCollectionConfig::default(),
+            power: PowerConfig::default(),
+            derived: Vec::new(),
+            devices: DeviceFilters::default(),
+        }
+    }
+}
+
+async fn system_metrics(devices: &DeviceFilters) -> Vec<Metric> {
+    let mut metrics = Vec::new();
+
+    let mut sys = sysinfo::System::new();
+    sys.refresh_cpu_usage();
+    tokio::time::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL).await;
+    sys.refresh_cpu_usage();
+    sys.refresh_memory();
+
+    metrics.push(Metric::new(
+        "cpu_usage_percent",
+        sys.global_cpu_usage() as f64,
+    ));
+    for cpu in sys.cpus() {
+        metrics.push(
+            Metric::new("cpu_core_usage_percent", cpu.cpu_usage() as f64).label("cpu", cpu.name()),
+        );
+    }
+    metrics.push(Metric::new("memory_total_bytes", sys.total_memory() as f64));
+    metrics.push(Metric::new("memory_used_bytes", sys.used_memory() as f64));
+    metrics.push(Metric::new("swap_total_bytes", sys.total_swap() as f64));
+    metrics.push(Metric::new("swap_used_bytes", sys.used_swap() as f64));
+
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    for disk in disks.list().iter().filter(|disk| devices.allows_disk(disk)) {
+        let mount = disk.mount_point().to_string_lossy();
+        metrics.push(
+            Metric::new("disk_total_bytes", disk.total_space() as f64).label("mount", &mount),
+        );
+        metrics.push(
+            Metric::new("disk_available_bytes", disk.available_space() as f64)
+                .label("mount", &mount),
+        );
+    }
+
+    let networks = sysinfo::Networks::new_with_refreshed_list();
+    for (interface, data) in networks
+        .iter()
+        .filter(|(interface, _)| devices.allows_interface(interface))
+    {
+        metrics.push(
+            Metric::new("network_received_bytes_total", data.total_received() as f64)
+                .label("interface", interface),
+        );
+        metrics.push(
+            Metric::new(
+                "network_transmitted_bytes_total",
+                data.total_transmitted() as f64,
+            )
+            .label("interface", interface),
+        );
+    }
+
+    let components = sysinfo::Components::new_with_refreshed_list();
+    for component in components.list() {
+        if !devices.allows_component(component.label()) {
+            continue;
+        }
+        if let Some(temperature) = component.temperature() {
+            metrics.push(
+                Metric::new("component_temperature_celsius", temperature as f64)
+                    .label("component", component.label()),
+            );
+        }
+    }
+
+    for sensor in platform_sensors()
+        .into_iter()
+        .filter(|sensor| devices.allows_component(&sensor.label))
+    {
+        let metric = match sensor.kind {
+            SensorKind::Temperature => Metric::new("component_temperature_celsius", sensor.value)
+                .label("component", &sensor.label),
+            SensorKind::Fan => {
+                Metric::new("fan_speed_rpm", sensor.value).label("fan", &sensor.label)
+            }
+            SensorKind::Power => {
+                Metric::new("power_watts", sensor.value).label("rail", &sensor.label)
+            }
+            SensorKind::Voltage => {
+                Metric::new("voltage_volts", sensor.value).label("sensor", &sensor.label)
+            }
+        };
+        metrics.push(metric);
+    }
+
+    metrics
+}
+
+// Splits "\\HOST\Object(Instance)\Counter" into a metric name and instance label
+#[cfg(windows)]
+fn pdh_metric(path: &str, value: f64) -> Metric {
+    let trimmed = path.trim_start_matches('\\');
+    let mut parts = trimmed.splitn(3, '\\');
+    let _host = parts.next();
+    let object = parts.next().unwrap_or_default();
+    let counter = parts.next().unwrap_or_default();
+
+    let (object_name, instance) = match (object.find('('), object.rfind(')')) {
+        (Some(start), Some(end)) if end > start => {
+            (&object[..start], Some(&object[start + 1..end]))
+        }
+        _ => (object, None),
+    };
+
+    let sanitized = format!("pdh_{}_{}", object_name, counter)
+        .to_lowercase()
+        .replace('%', "percent")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>()
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_");
+
+    let mut metric =
+        Metric::new(&sanitized, value).label("counter", &format!("\\{}\\{}", object, counter));
+    if let Some(instance) = instance {
+        metric = metric.label("instance", instance);
+    }
+    metric
+}
+
+// Samples the configured counters once with typeperf, which ships with every Windows install
+#[cfg(windows)]
+async fn windows_counter_metrics(counters: &[String]) -> Result<Vec<Metric>, String> {
+    if counters.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let output = tokio::process::Command::new("typeperf")
+        .args(counters)
+        .args(["-sc", "1"])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run typeperf: {}", e))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut rows = stdout
+        .lines()
+        .filter(|line| line.starts_with('"'))
+        .map(|line| {
+            line.trim()
+                .trim_matches('"')
+                .split("\",\"")
+                .map(|field| field.to_string())
+                .collect::<Vec<_>>()
+        });
+
+    let header = rows.next().ok_or("typeperf returned no counters")?;
+    let values = rows.next().ok_or("typeperf returned no samples")?;
+
+    // The first column is the sample timestamp
+    Ok(header
+        .iter()
+        .zip(values.iter())
+        .skip(1)
+        .filter_map(|(path, value)| {
+            value
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .map(|value| pdh_metric(path, value))
+        })
+        .collect())
+}
+
+#[cfg(windows)]
+async fn platform_metrics(config: &MetricsConfig) -> Vec<Metric> {
+    let counters = windows_counter_metrics(&config.windows_counters).await;
+    record_collector_result(
+        "windows_counters",
+        counters.as_ref().map(|_| ()).map_err(|e| e.as_str()),
+    );
+    counters.unwrap_or_default()
+}
+
+#[cfg(target_os = "linux")]
+async fn platform_metrics(_config: &MetricsConfig) -> Vec<Metric> {
+    let mut metrics = read_cgroup_stats()
+        .filter(|stats| stats.is_relevant())
+        .map(|stats| cgroup_metrics(&stats))
+        .unwrap_or_default();
+    if let Some(status) = read_throttle_status() {
+        metrics.extend(throttle_metrics(&status));
+    }
+    metrics.extend(ebpf_metrics());
+    metrics.extend(kernel_limit_metrics(&read_kernel_limits()));
+    metrics
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+async fn platform_metrics(_config: &MetricsConfig) -> Vec<Metric> {
+    Vec::new()
+}
+
+pub async fn collect_metrics(config: &MetricsConfig) -> Vec<Metric> {
+    // Only what gets pushed to us is real in demo and replay mode
+    let synthetic = match metric_replay() {
+        Some(replay) => Some(replay.metrics()),
+        None => demo_system().map(|demo| demo.metrics()),
+    };
+    if let Some(mut metrics) = synthetic {
+        metrics.extend(statsd_metrics());
+        metrics.extend(custom_metrics());
+        metrics.extend(http_latency_metrics());
+        metrics.extend(http_connection_metrics());
+        metrics.extend(dashboard_stream_metrics());
+        metrics.extend(cluster_metrics());
+        metrics.extend(collector_health_metrics());
+        metrics.extend(health_score_metrics());
+        let derived = evaluate_derived_metrics(&config.derived, &metrics);
+        metrics.extend(derived);
+        return metrics;
+    }
+
+    let mut metrics = system_metrics(&config.devices).await;
+    metrics.extend(protocol_metrics());
+    metrics.extend(wifi_metrics(&read_wifi_interfaces()));
+    metrics.extend(platform_metrics(config).await);
+    if config.processes.enabled {
+        metrics.extend(cached_process_metrics(&config.processes).await);
+    }
+    let storage = read_storage_report();
+    metrics.extend(raid_metrics(&storage.raid));
+    metrics.extend(pool_metrics(&storage));
+    metrics.extend(tool_metrics());
+    metrics.extend(statsd_metrics());
+    metrics.extend(custom_metrics());
+    metrics.extend(speed_test_metrics());
+    metrics.extend(kernel_log_metrics());
+    metrics.extend(peripheral_metrics());
+    metrics.extend(http_latency_metrics());
+    metrics.extend(http_connection_metrics());
+    metrics.extend(dashboard_stream_metrics());
+    metrics.extend(cluster_metrics());
+    metrics.extend(collector_health_metrics());
+    metrics.extend(health_score_metrics());
+    let derived = evaluate_derived_metrics(&config.derived, &metrics);
+    metrics.extend(derived);
+    metrics
+}
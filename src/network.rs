@@ -5,12 +5,13 @@
 use std::time::{Duration, Instant};
 use sysinfo::Networks;
 
-async fn network_info() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+async fn network_info(devices: &DeviceFilters) -> Result<Vec<String>, Box<dyn std::error::Error>> {
     // Implementation of network_info function
     let networks = Networks::new_with_refreshed_list();
 
     let output = networks
         .iter()
+        .filter(|(interface_name, _)| devices.allows_interface(interface_name))
         .map(|(interface_name, data)| {
             format!(
                 "{interface_name}: {} MB (down) / {} MB (Up)",
@@ -23,7 +24,9 @@ async fn network_info() -> Result<Vec<String>, Box<dyn std::error::Error>> {
     Ok(output)
 }
 
-async fn network_traffic() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+async fn network_traffic(
+    devices: &DeviceFilters,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
     let mut networks = Networks::new();
     let mut results = Vec::new();
 
@@ -46,7 +49,10 @@ async fn network_traffic() -> Result<Vec<String>, Box<dyn std::error::Error>> {
     // Second measurement
     networks.refresh(true);
 
-    for (interface_name, data) in networks.iter() {
+    for (interface_name, data) in networks
+        .iter()
+        .filter(|(interface_name, _)| devices.allows_interface(interface_name))
+    {
         if let Some((_, prev_received, prev_transmitted)) = previous_data
             .iter()
             .find(|(name, _, _)| name == interface_name)
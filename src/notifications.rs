@@ -0,0 +1,256 @@
+// Notifications module for Crusty-Crawler
+// Delivers alert notifications to contacts over email, Pushover, Telegram, SMS and webhooks,
+// with the content rendered from notification_templates
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum NotificationChannel {
+    Pushover {
+        user_key: String,
+        api_token: String,
+    },
+    Telegram {
+        bot_token: String,
+        chat_id: String,
+    },
+    // Any Twilio-compatible SMS gateway
+    Sms {
+        account_sid: String,
+        auth_token: String,
+        from: String,
+        to: String,
+        #[serde(default = "default_sms_api_url")]
+        api_url: String,
+    },
+    // Through smtp_config
+    Email {
+        to: String,
+    },
+    // POSTs the webhook template's JSON
+    Webhook {
+        url: String,
+    },
+}
+
+fn default_sms_api_url() -> String {
+    "https://api.twilio.com".to_string()
+}
+
+fn default_severities() -> Vec<CheckState> {
+    vec![
+        CheckState::Warning,
+        CheckState::Critical,
+        CheckState::Unknown,
+    ]
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Contact {
+    pub name: String,
+    // Alert states this contact wants to hear about, include OK for recoveries
+    #[serde(default = "default_severities")]
+    pub severities: Vec<CheckState>,
+    #[serde(default)]
+    pub channels: Vec<NotificationChannel>,
+}
+
+impl Contact {
+    pub fn wants(&self, state: CheckState) -> bool {
+        self.severities.contains(&state)
+    }
+}
+
+impl NotificationChannel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NotificationChannel::Pushover { .. } => "pushover",
+            NotificationChannel::Telegram { .. } => "telegram",
+            NotificationChannel::Sms { .. } => "sms",
+            NotificationChannel::Email { .. } => "email",
+            NotificationChannel::Webhook { .. } => "webhook",
+        }
+    }
+
+    pub async fn send(
+        &self,
+        client: &reqwest::Client,
+        templates: &NotificationTemplates,
+        context: &tera::Context,
+        state: CheckState,
+        smtp_config: Option<&SmtpConfig>,
+    ) -> Result<(), String> {
+        let render = |kind| templates.render_or_default(kind, context);
+        let request = match self {
+            NotificationChannel::Pushover {
+                user_key,
+                api_token,
+            } => {
+                let priority = if state == CheckState::Critical {
+                    "1"
+                } else {
+                    "0"
+                };
+                client
+                    .post("https://api.pushover.net/1/messages.json")
+                    .form(&[
+                        ("token", api_token.as_str()),
+                        ("user", user_key.as_str()),
+                        ("title", &render(TemplateKind::Subject)),
+                        ("message", &render(TemplateKind::Pushover)),
+                        ("priority", priority),
+                    ])
+            }
+            NotificationChannel::Telegram { bot_token, chat_id } => client
+                .post(format!(
+                    "https://api.telegram.org/bot{}/sendMessage",
+                    bot_token
+                ))
+                .json(&serde_json::json!({
+                    "chat_id": chat_id,
+                    "text": render(TemplateKind::Telegram),
+                })),
+            NotificationChannel::Sms {
+                account_sid,
+                auth_token,
+                from,
+                to,
+                api_url,
+            } => client
+                .post(format!(
+                    "{}/2010-04-01/Accounts/{}/Messages.json",
+                    api_url.trim_end_matches('/'),
+                    account_sid
+                ))
+                .basic_auth(account_sid, Some(auth_token))
+                .form(&[
+                    ("From", from.as_str()),
+                    ("To", to.as_str()),
+                    ("Body", &render(TemplateKind::Sms)),
+                ]),
+            NotificationChannel::Webhook { url } => client
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(render(TemplateKind::Webhook)),
+            NotificationChannel::Email { to } => {
+                let smtp_config = smtp_config
+                    .cloned()
+                    .ok_or("Email configuration not set up")?;
+                let (to, subject, body) = (
+                    to.clone(),
+                    render(TemplateKind::Subject),
+                    render(TemplateKind::Email),
+                );
+                // lettre's transport blocks
+                return tokio::task::spawn_blocking(move || {
+                    send_email(&smtp_config, &to, &subject, &body)
+                })
+                .await
+                .map_err(|e| e.to_string())?;
+            }
+        };
+
+        let response = request
+            .timeout(std::time::Duration::from_secs(15))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} returned HTTP {}",
+                self.name(),
+                response.status()
+            ))
+        }
+    }
+}
+
+fn alert_subject(alert: &Alert) -> String {
+    let host = sysinfo::System::host_name().unwrap_or_else(|| "unknown host".to_string());
+    format!(
+        "[Crusty] {} {} on {}",
+        alert.state.label(),
+        alert.check,
+        host
+    )
+}
+
+// What an alert is sent with besides the contacts
+#[derive(Clone)]
+pub struct NotificationSettings {
+    pub templates: NotificationTemplates,
+    pub smtp_config: Option<SmtpConfig>,
+    pub on_call: OnCallConfig,
+    pub throttle: NotificationThrottleConfig,
+}
+
+// Sends the alert to every contact on call whose severity filter matches, as far as the
+// throttle lets it through
+pub async fn dispatch_notifications(
+    contacts: Vec<Contact>,
+    settings: NotificationSettings,
+    alert: Alert,
+) {
+    let host = sysinfo::System::host_name().unwrap_or_else(|| "unknown host".to_string());
+    dispatch_host_notifications(contacts, settings, alert, host).await
+}
+
+// The same for an alert about `host`, e.g. a fleet host in manager mode
+pub async fn dispatch_host_notifications(
+    contacts: Vec<Contact>,
+    settings: NotificationSettings,
+    alert: Alert,
+    host: String,
+) {
+    let recipients: Vec<&Contact> = settings
+        .on_call
+        .recipients(&contacts, chrono::Utc::now())
+        .into_iter()
+        .filter(|c| c.wants(alert.state))
+        .collect();
+    if recipients.is_empty() {
+        return;
+    }
+
+    let client = reqwest::Client::new();
+    let context = alert_template_context(&alert, &host, chrono::Utc::now());
+
+    for contact in recipients {
+        for channel in &contact.channels {
+            let channel_key = format!("{} via {}", contact.name, channel.name());
+            if let Admission::Suppressed { first } =
+                admit_notification(&settings.throttle, &channel_key, &alert)
+            {
+                if first {
+                    println!("🔕 Throttling notifications to {}", channel_key);
+                    tokio::spawn(send_throttle_summary(
+                        client.clone(),
+                        settings.clone(),
+                        channel_key,
+                        channel.clone(),
+                    ));
+                }
+                continue;
+            }
+            if let Err(e) = channel
+                .send(
+                    &client,
+                    &settings.templates,
+                    &context,
+                    alert.state,
+                    settings.smtp_config.as_ref(),
+                )
+                .await
+            {
+                eprintln!(
+                    "❌ Failed to notify {} via {}: {}",
+                    contact.name,
+                    channel.name(),
+                    e
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,217 @@
+// On-call module for Crusty-Crawler
+// Rotations of contacts that hand off on a fixed schedule, for small teams without a paging
+// service. A contact in a rotation is only notified during their shift, overrides cover swaps
+// and sick days
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Rotation {
+    pub name: String,
+    // Contact names in handoff order
+    pub contacts: Vec<String>,
+    // RFC 3339 time the first contact went on call, later handoffs follow every shift_hours
+    pub start: String,
+    #[serde(default = "default_shift_hours")]
+    pub shift_hours: u64,
+}
+
+fn default_shift_hours() -> u64 {
+    168
+}
+
+// Someone covering a rotation from start until end, both RFC 3339
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OnCallOverride {
+    pub rotation: String,
+    pub contact: String,
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct OnCallConfig {
+    pub rotations: Vec<Rotation>,
+    pub overrides: Vec<OnCallOverride>,
+}
+
+// Who has a rotation right now, for /api/oncall and the GUI
+#[derive(Serialize, Clone)]
+pub struct OnCallShift {
+    pub rotation: String,
+    pub contact: String,
+    pub overridden: bool,
+    // When the shift or the override ends
+    pub until: String,
+    // Who the schedule hands off to then
+    pub next: String,
+}
+
+fn parse_shift_time(time: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|time| time.with_timezone(&chrono::Utc))
+}
+
+impl Rotation {
+    // Index into contacts of the scheduled shift and when it ends. The schedule repeats in
+    // both directions, so a start in the future still has someone on call
+    fn scheduled(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Option<(usize, chrono::DateTime<chrono::Utc>)> {
+        let start = parse_shift_time(&self.start)?;
+        if self.contacts.is_empty() || self.shift_hours == 0 {
+            return None;
+        }
+        let shift_secs = self.shift_hours as i64 * 3600;
+        let shifts = (now - start).num_seconds().div_euclid(shift_secs);
+        let index = shifts.rem_euclid(self.contacts.len() as i64) as usize;
+        Some((
+            index,
+            start + chrono::Duration::seconds(shift_secs * (shifts + 1)),
+        ))
+    }
+}
+
+impl OnCallConfig {
+    fn active_override(
+        &self,
+        rotation: &str,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Option<&OnCallOverride> {
+        // The latest override wins when they overlap
+        self.overrides.iter().rev().find(|o| {
+            o.rotation == rotation
+                && parse_shift_time(&o.start).is_some_and(|start| start <= now)
+                && parse_shift_time(&o.end).is_some_and(|end| now < end)
+        })
+    }
+
+    pub fn shift(
+        &self,
+        rotation: &Rotation,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Option<OnCallShift> {
+        let (index, handoff) = rotation.scheduled(now)?;
+        if let Some(o) = self.active_override(&rotation.name, now) {
+            // Hands back to whoever is scheduled when the override ends
+            let end = parse_shift_time(&o.end)?;
+            let (next, _) = rotation.scheduled(end)?;
+            return Some(OnCallShift {
+                rotation: rotation.name.clone(),
+                contact: o.contact.clone(),
+                overridden: true,
+                until: end.to_rfc3339(),
+                next: rotation.contacts[next].clone(),
+            });
+        }
+        Some(OnCallShift {
+            rotation: rotation.name.clone(),
+            contact: rotation.contacts[index].clone(),
+            overridden: false,
+            until: handoff.to_rfc3339(),
+            next: rotation.contacts[(index + 1) % rotation.contacts.len()].clone(),
+        })
+    }
+
+    pub fn schedule(&self, now: chrono::DateTime<chrono::Utc>) -> Vec<OnCallShift> {
+        self.rotations
+            .iter()
+            .filter_map(|rotation| self.shift(rotation, now))
+            .collect()
+    }
+
+    // Contacts outside every rotation are always notified, the rest only while on call
+    pub fn recipients<'a>(
+        &self,
+        contacts: &'a [Contact],
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Vec<&'a Contact> {
+        let on_call: Vec<String> = self
+            .schedule(now)
+            .into_iter()
+            .map(|shift| shift.contact)
+            .collect();
+        contacts
+            .iter()
+            .filter(|contact| {
+                on_call.contains(&contact.name)
+                    || !self
+                        .rotations
+                        .iter()
+                        .any(|rotation| rotation.contacts.contains(&contact.name))
+            })
+            .collect()
+    }
+
+    pub fn validate(&self, contacts: &[Contact]) -> Result<(), String> {
+        let known = |name: &str| contacts.iter().any(|contact| contact.name == name);
+        let mut names = Vec::new();
+        for rotation in &self.rotations {
+            if rotation.name.is_empty() {
+                return Err("on_call.rotations: every rotation needs a name".to_string());
+            }
+            if names.contains(&rotation.name.as_str()) {
+                return Err(format!(
+                    "on_call.rotations: duplicate rotation '{}'",
+                    rotation.name
+                ));
+            }
+            names.push(rotation.name.as_str());
+            if rotation.contacts.is_empty() {
+                return Err(format!(
+                    "on_call.rotations '{}': needs at least one contact",
+                    rotation.name
+                ));
+            }
+            if let Some(unknown) = rotation.contacts.iter().find(|name| !known(name.as_str())) {
+                return Err(format!(
+                    "on_call.rotations '{}': no contact named '{}'",
+                    rotation.name, unknown
+                ));
+            }
+            if parse_shift_time(&rotation.start).is_none() {
+                return Err(format!(
+                    "on_call.rotations '{}': start must be an RFC 3339 time",
+                    rotation.name
+                ));
+            }
+            if rotation.shift_hours == 0 {
+                return Err(format!(
+                    "on_call.rotations '{}': shift_hours must be above 0",
+                    rotation.name
+                ));
+            }
+        }
+
+        for o in &self.overrides {
+            if !names.contains(&o.rotation.as_str()) {
+                return Err(format!(
+                    "on_call.overrides: no rotation named '{}'",
+                    o.rotation
+                ));
+            }
+            if !known(&o.contact) {
+                return Err(format!(
+                    "on_call.overrides: no contact named '{}'",
+                    o.contact
+                ));
+            }
+            match (parse_shift_time(&o.start), parse_shift_time(&o.end)) {
+                (Some(start), Some(end)) if start < end => {}
+                (Some(_), Some(_)) => {
+                    return Err(format!(
+                        "on_call.overrides: {} ends before it starts",
+                        o.contact
+                    ));
+                }
+                _ => {
+                    return Err(
+                        "on_call.overrides: start and end must be RFC 3339 times".to_string()
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+// OTLP module for Crusty-Crawler
+// Exports metrics and traces of check runs, collector runs and HTTP requests over OTLP/HTTP.
+// The exporter needs the `exporters` feature, spans are still created without it but go nowhere
+
+use opentelemetry::KeyValue;
+#[cfg(feature = "exporters")]
+use opentelemetry::metrics::MeterProvider;
+use opentelemetry::trace::{Span as _, Tracer};
+#[cfg(feature = "exporters")]
+use opentelemetry_otlp::{WithExportConfig, WithHttpConfig};
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct OtlpConfig {
+    pub enabled: bool,
+    // Base URL of the collector's OTLP/HTTP receiver, /v1/metrics and /v1/traces are appended
+    pub endpoint: String,
+    // Sent with every export, e.g. an API key for a hosted backend
+    pub headers: HashMap<String, String>,
+    pub export_metrics: bool,
+    pub export_traces: bool,
+    pub interval_secs: u64,
+    pub service_name: String,
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: "http://localhost:4318".to_string(),
+            headers: HashMap::new(),
+            export_metrics: true,
+            export_traces: true,
+            interval_secs: 60,
+            service_name: "crusty-crawler".to_string(),
+        }
+    }
+}
+
+// Flushed on shutdown so the last batch isn't lost
+#[cfg(feature = "exporters")]
+static OTLP_PROVIDERS: Mutex<(
+    Option<opentelemetry_sdk::metrics::SdkMeterProvider>,
+    Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+)> = Mutex::new((None, None));
+
+#[cfg(feature = "exporters")]
+fn otlp_resource(config: &OtlpConfig) -> opentelemetry_sdk::Resource {
+    let mut builder =
+        opentelemetry_sdk::Resource::builder().with_service_name(config.service_name.clone());
+    if let Some(host) = sysinfo::System::host_name() {
+        builder = builder.with_attribute(KeyValue::new("host.name", host));
+    }
+    builder.build()
+}
+
+#[cfg(feature = "exporters")]
+fn otlp_url(config: &OtlpConfig, signal: &str) -> String {
+    format!("{}/v1/{}", config.endpoint.trim_end_matches('/'), signal)
+}
+
+#[cfg(feature = "exporters")]
+fn metric_attributes(metric: &Metric) -> Vec<KeyValue> {
+    metric
+        .labels
+        .iter()
+        .map(|(key, value)| KeyValue::new(key.clone(), value.clone()))
+        .collect()
+}
+
+// Spans go to the global tracer, which does nothing until OTLP tracing is started
+fn start_span(name: String, attributes: Vec<KeyValue>) -> opentelemetry::global::BoxedSpan {
+    let mut span = opentelemetry::global::tracer("crusty-crawler").start(name);
+    span.set_attributes(attributes);
+    span
+}
+
+fn end_span(mut span: opentelemetry::global::BoxedSpan, error: Option<&str>) {
+    if let Some(error) = error {
+        span.set_status(opentelemetry::trace::Status::error(error.to_string()));
+    }
+    span.end();
+}
+
+// One span per request, named after the route pattern so IDs in paths don't explode cardinality
+async fn trace_request(
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<axum::extract::MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let mut span = start_span(
+        format!("{} {}", method, route),
+        vec![
+            KeyValue::new("http.request.method", method),
+            KeyValue::new("http.route", route),
+        ],
+    );
+
+    let response = next.run(request).await;
+    let status = response.status();
+    span.set_attribute(KeyValue::new(
+        "http.response.status_code",
+        status.as_u16() as i64,
+    ));
+    end_span(span, status.is_server_error().then_some(status.as_str()));
+    response
+}
+
+// Metrics are sampled on the export interval and recorded as gauges, cumulative *_total
+// values included, since the agent only ever sees their current reading
+#[cfg(feature = "exporters")]
+async fn export_metrics_loop(
+    server_state: Arc<Mutex<ServerState>>,
+    provider: opentelemetry_sdk::metrics::SdkMeterProvider,
+) {
+    let meter = provider.meter("crusty-crawler");
+    let mut gauges: HashMap<String, opentelemetry::metrics::Gauge<f64>> = HashMap::new();
+
+    loop {
+        let (metrics_config, interval) = {
+            let state = server_state.lock().unwrap();
+            let auth_manager = state.auth_manager.lock().unwrap();
+            (
+                auth_manager.config.metrics.clone(),
+                auth_manager.config.otlp.interval_secs.max(10),
+            )
+        };
+
+        for metric in collect_metrics(&metrics_config).await {
+            let gauge = gauges
+                .entry(metric.name.clone())
+                .or_insert_with(|| meter.f64_gauge(metric.name.clone()).build());
+            gauge.record(metric.value, &metric_attributes(&metric));
+        }
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+    }
+}
+
+// Endpoint and headers are read once at startup, changing them needs a restart
+#[cfg(feature = "exporters")]
+fn spawn_otlp_exporter(server_state: Arc<Mutex<ServerState>>) {
+    let config = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.otlp.clone()
+    };
+    if !config.enabled {
+        return;
+    }
+    let interval = Duration::from_secs(config.interval_secs.max(10));
+
+    if config.export_traces {
+        match opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(otlp_url(&config, "traces"))
+            .with_headers(config.headers.clone())
+            .build()
+        {
+            Ok(exporter) => {
+                let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+                    .with_batch_exporter(exporter)
+                    .with_resource(otlp_resource(&config))
+                    .build();
+                opentelemetry::global::set_tracer_provider(provider.clone());
+                OTLP_PROVIDERS.lock().unwrap().1 = Some(provider);
+            }
+            Err(e) => eprintln!("❌ Failed to start OTLP trace export: {}", e),
+        }
+    }
+
+    if config.export_metrics {
+        match opentelemetry_otlp::MetricExporter::builder()
+            .with_http()
+            .with_endpoint(otlp_url(&config, "metrics"))
+            .with_headers(config.headers.clone())
+            .build()
+        {
+            Ok(exporter) => {
+                let reader = opentelemetry_sdk::metrics::PeriodicReader::builder(exporter)
+                    .with_interval(interval)
+                    .build();
+                let provider = opentelemetry_sdk::metrics::SdkMeterProvider::builder()
+                    .with_reader(reader)
+                    .with_resource(otlp_resource(&config))
+                    .build();
+                OTLP_PROVIDERS.lock().unwrap().0 = Some(provider.clone());
+
+                std::thread::spawn(move || match Runtime::new() {
+                    Ok(rt) => rt.block_on(export_metrics_loop(server_state, provider)),
+                    Err(e) => eprintln!("❌ Failed to start OTLP metrics export: {}", e),
+                });
+            }
+            Err(e) => eprintln!("❌ Failed to start OTLP metrics export: {}", e),
+        }
+    }
+
+    println!("📡 Exporting OTLP to {}", config.endpoint);
+}
+
+#[cfg(feature = "exporters")]
+fn shutdown_otlp() {
+    let (meter_provider, tracer_provider) = std::mem::take(&mut *OTLP_PROVIDERS.lock().unwrap());
+    if let Some(provider) = tracer_provider
+        && let Err(e) = provider.shutdown()
+    {
+        eprintln!("⚠️  OTLP trace flush failed: {}", e);
+    }
+    if let Some(provider) = meter_provider
+        && let Err(e) = provider.shutdown()
+    {
+        eprintln!("⚠️  OTLP metrics flush failed: {}", e);
+    }
+}
+
+#[cfg(not(feature = "exporters"))]
+fn spawn_otlp_exporter(server_state: Arc<Mutex<ServerState>>) {
+    let state = server_state.lock().unwrap();
+    if state.auth_manager.lock().unwrap().config.otlp.enabled {
+        eprintln!("⚠️  OTLP export is enabled but this build has no exporters feature");
+    }
+}
+
+#[cfg(not(feature = "exporters"))]
+fn shutdown_otlp() {}
@@ -0,0 +1,74 @@
+// Overview module for Crusty-Crawler
+// Small health summary for phones polling the dashboard, a few hundred bytes instead of the
+// full status text
+
+#[derive(Serialize)]
+pub struct OverviewProblem {
+    pub name: String,
+    pub state: CheckState,
+    pub output: String,
+}
+
+#[derive(Serialize)]
+pub struct Overview {
+    pub host: String,
+    pub generated_at: String,
+    pub overall: CheckState,
+    // 0-100, see /api/health-score for how it's made up
+    pub health_score: f64,
+    // Last history sample, None until the sampler has run once
+    pub cpu_percent: Option<f64>,
+    pub memory_percent: f64,
+    // Fullest disk
+    pub disk_percent: f64,
+    pub load_1m: f64,
+    pub uptime_secs: u64,
+    pub active_alerts: usize,
+    // Only checks that aren't OK
+    pub problems: Vec<OverviewProblem>,
+    // Set while expensive collectors are backed off, see /api/collection
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub degraded: Option<DegradedCollection>,
+}
+
+fn round1(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+async fn overview(runner: &CheckRunner) -> Overview {
+    let max_age = Duration::from_secs(runner.config.cache_max_age_secs);
+    let mut overall = CheckState::Ok;
+    let mut problems = Vec::new();
+    let mut results = Vec::new();
+    for name in available_checks(&runner.config) {
+        if let Some((result, _)) = runner.cached(&name, max_age).await {
+            overall = overall.max(result.state);
+            if result.state != CheckState::Ok {
+                problems.push(OverviewProblem {
+                    name: result.name.clone(),
+                    state: result.state,
+                    output: result.output.clone(),
+                });
+            }
+            results.push(result);
+        }
+    }
+
+    let usage = resource_usage(&runner.devices);
+    let health_score = compute_health_score(&runner.config.health_score, &results, &usage);
+
+    Overview {
+        host: sysinfo::System::host_name().unwrap_or_else(|| "unknown host".to_string()),
+        generated_at: chrono::Utc::now().to_rfc3339(),
+        overall,
+        health_score: health_score.score,
+        cpu_percent: usage.cpu_percent.map(round1),
+        memory_percent: round1(usage.memory_percent),
+        disk_percent: round1(usage.disk_percent),
+        load_1m: round1(sysinfo::System::load_average().one),
+        uptime_secs: sysinfo::System::uptime(),
+        active_alerts: runner.alerts.lock().unwrap().active().len(),
+        problems,
+        degraded: collection_status().degraded,
+    }
+}
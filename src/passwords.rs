@@ -0,0 +1,163 @@
+// Passwords module for Crusty-Crawler
+// The admin-configured rules GUI passwords have to meet, checked by AuthManager whenever a
+// password is set, and the password change that enforces them
+
+// One per line, lowercase
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    // Also catches them with digits or symbols tacked on, like "Password2025!"
+    pub reject_common: bool,
+    // Days before the GUI asks for a new password at login, 0 never expires them
+    pub max_age_days: u64,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            require_uppercase: false,
+            require_lowercase: false,
+            require_digit: false,
+            require_symbol: false,
+            reject_common: true,
+            max_age_days: 0,
+        }
+    }
+}
+
+fn is_common_password(password: &str) -> bool {
+    let password = password.to_lowercase();
+    let stem = password.trim_end_matches(|c: char| !c.is_alphabetic());
+    COMMON_PASSWORDS
+        .lines()
+        .any(|common| common == password || common == stem)
+}
+
+// What the policy calls the class, and whether a character belongs to it
+type CharClass = (&'static str, fn(char) -> bool);
+
+impl PasswordPolicy {
+    fn required_classes(&self) -> Vec<CharClass> {
+        let classes: [(bool, CharClass); 4] = [
+            (
+                self.require_uppercase,
+                ("an uppercase letter", char::is_uppercase),
+            ),
+            (
+                self.require_lowercase,
+                ("a lowercase letter", char::is_lowercase),
+            ),
+            (self.require_digit, ("a digit", |c| c.is_ascii_digit())),
+            (self.require_symbol, ("a symbol", |c| !c.is_alphanumeric())),
+        ];
+        classes
+            .into_iter()
+            .filter(|(required, _)| *required)
+            .map(|(_, class)| class)
+            .collect()
+    }
+
+    // "at least 8 characters with a digit and a symbol", for prompts and hints
+    pub fn describe(&self) -> String {
+        let classes: Vec<&str> = self
+            .required_classes()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        let description = format!("at least {} characters", self.min_length);
+        match classes.split_last() {
+            None => description,
+            Some((last, [])) => format!("{} with {}", description, last),
+            Some((last, rest)) => {
+                format!("{} with {} and {}", description, rest.join(", "), last)
+            }
+        }
+    }
+
+    pub fn check(&self, password: &str) -> Result<(), String> {
+        if password.chars().count() < self.min_length {
+            return Err(format!(
+                "Password must be at least {} characters",
+                self.min_length
+            ));
+        }
+        for (name, matches) in self.required_classes() {
+            if !password.chars().any(matches) {
+                return Err(format!("Password must contain {}", name));
+            }
+        }
+        if self.reject_common && is_common_password(password) {
+            return Err("Password is too common, choose another one".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl AuthManager {
+    pub fn configure_password_policy(&mut self, policy: PasswordPolicy) -> Result<(), String> {
+        if policy.min_length == 0 {
+            return Err("Minimum length must be at least 1".to_string());
+        }
+        self.config.password_policy = policy;
+        self.save_config().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    // Needs the current password even when it has expired, so a stolen session can't change it
+    pub fn change_password(
+        &mut self,
+        username: &str,
+        current_password: &str,
+        new_password: &str,
+    ) -> Result<(), String> {
+        self.authenticate(username, current_password)?;
+        if new_password == current_password {
+            return Err("The new password must differ from the current one".to_string());
+        }
+        self.config.password_policy.check(new_password)?;
+
+        let password_hash = hash(new_password, DEFAULT_COST).map_err(|e| e.to_string())?;
+        let changed_at = self.clock.utc_now().to_rfc3339();
+        let user = self
+            .config
+            .users
+            .get_mut(username)
+            .ok_or("User not found")?;
+        user.password_hash = password_hash;
+        user.password_changed_at = Some(changed_at);
+        self.save_config().map_err(|e| e.to_string())?;
+        record_event(
+            EventKind::Account,
+            &format!("Password of {} changed", username),
+            "",
+        );
+        Ok(())
+    }
+
+    // Users from before password ages were tracked count from when they were created
+    pub fn password_expired(&self, username: &str) -> bool {
+        let max_age_days = self.config.password_policy.max_age_days;
+        if max_age_days == 0 {
+            return false;
+        }
+        let Some(user) = self.config.users.get(username) else {
+            return false;
+        };
+        let changed_at = user
+            .password_changed_at
+            .as_ref()
+            .unwrap_or(&user.created_at);
+        chrono::DateTime::parse_from_rfc3339(changed_at).is_ok_and(|changed_at| {
+            self.clock.utc_now() - changed_at.with_timezone(&chrono::Utc)
+                >= chrono::Duration::days(max_age_days as i64)
+        })
+    }
+}
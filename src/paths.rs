@@ -0,0 +1,146 @@
+// Paths module for Crusty-Crawler
+// One data directory for the config, state files, logs and plugins, so the agent behaves the
+// same whatever directory systemd or the Windows service manager starts it from
+
+use std::path::PathBuf;
+
+const APP_DIR_NAME: &str = "crusty-crawler";
+// Installed by the packages next to public/, see packaging/
+const DEFAULT_CONFIG_FILE: &str = "crusty_auth.default.json";
+
+static DATA_DIR: std::sync::OnceLock<PathBuf> = std::sync::OnceLock::new();
+
+// /var/lib for root services, the XDG data home for everyone else
+#[cfg(all(unix, not(target_os = "macos")))]
+fn default_data_dir() -> PathBuf {
+    if env::var("USER").is_ok_and(|user| user == "root") || env::var_os("HOME").is_none() {
+        return PathBuf::from("/var/lib").join(APP_DIR_NAME);
+    }
+    env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(APP_DIR_NAME)
+}
+
+#[cfg(target_os = "macos")]
+fn default_data_dir() -> PathBuf {
+    match env::var_os("HOME") {
+        Some(home) => PathBuf::from(home).join("Library/Application Support/Crusty-Crawler"),
+        None => PathBuf::from("/Library/Application Support/Crusty-Crawler"),
+    }
+}
+
+#[cfg(windows)]
+fn default_data_dir() -> PathBuf {
+    env::var_os("ProgramData")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"))
+        .join("Crusty-Crawler")
+}
+
+// Takes --data-dir out of the arguments so the commands never see it. Without it the
+// CRUSTY_DATA_DIR variable, then an existing crusty_auth.json in the working directory from
+// before the data directory existed, then the platform default are used
+pub fn init_data_dir(args: &mut Vec<String>) -> Result<PathBuf, String> {
+    let mut explicit = None;
+    let mut index = 0;
+    while index < args.len() {
+        if args[index] == "--data-dir" {
+            let dir = args
+                .get(index + 1)
+                .ok_or("--data-dir needs a path")?
+                .clone();
+            args.drain(index..index + 2);
+            explicit = Some(PathBuf::from(dir));
+        } else if let Some(dir) = args[index].strip_prefix("--data-dir=") {
+            explicit = Some(PathBuf::from(dir));
+            args.remove(index);
+        } else {
+            index += 1;
+        }
+    }
+
+    let dir = explicit
+        .or_else(|| env::var_os("CRUSTY_DATA_DIR").map(PathBuf::from))
+        .unwrap_or_else(|| {
+            let default = default_data_dir();
+            if Path::new(AUTH_CONFIG_FILE).exists() && !default.join(AUTH_CONFIG_FILE).exists() {
+                println!(
+                    "ℹ️  Using the working directory for data, {} is already here",
+                    AUTH_CONFIG_FILE
+                );
+                PathBuf::from(".")
+            } else {
+                default
+            }
+        });
+    fs::create_dir_all(&dir)
+        .map_err(|e| format!("Cannot create data directory {}: {}", dir.display(), e))?;
+    let dir = dir.canonicalize().unwrap_or(dir);
+    seed_default_config(&dir, &install_dirs());
+    Ok(DATA_DIR.get_or_init(|| dir).clone())
+}
+
+pub fn data_dir() -> &'static Path {
+    DATA_DIR.get_or_init(default_data_dir)
+}
+
+// Relative names live in the data directory, absolute paths are kept as they are
+pub fn data_path(name: &str) -> PathBuf {
+    data_dir().join(name)
+}
+
+pub fn data_file(name: &str) -> String {
+    data_path(name).to_string_lossy().to_string()
+}
+
+// Executables collectors may call by name, ahead of PATH
+pub fn plugin_dir() -> PathBuf {
+    data_path("plugins")
+}
+
+// Where packages put shipped files: next to the binary, as the MSI and tarballs do, and
+// /usr/share/crusty-crawler for the .deb and .rpm
+fn install_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        .into_iter()
+        .collect();
+    if cfg!(all(unix, not(target_os = "macos"))) {
+        dirs.push(PathBuf::from("/usr/share").join(APP_DIR_NAME));
+    }
+    dirs
+}
+
+// Shipped files such as public/ and templates/: a copy in the data directory wins, then the
+// installed one, then the working directory
+pub fn resource_dir(name: &str) -> PathBuf {
+    std::iter::once(data_path(name))
+        .chain(install_dirs().into_iter().map(|dir| dir.join(name)))
+        .chain(std::iter::once(PathBuf::from(name)))
+        .find(|dir| dir.is_dir())
+        .unwrap_or_else(|| data_path(name))
+}
+
+// Copies the packaged starting config into a data directory without one, so a fleet can ship
+// its thresholds, SMTP and exporter settings inside the package
+fn seed_default_config(dir: &Path, install_dirs: &[PathBuf]) {
+    let config = dir.join(AUTH_CONFIG_FILE);
+    if config.exists() {
+        return;
+    }
+    let Some(default) = install_dirs
+        .iter()
+        .map(|install_dir| install_dir.join(DEFAULT_CONFIG_FILE))
+        .find(|default| default.is_file())
+    else {
+        return;
+    };
+    match fs::copy(&default, &config) {
+        Ok(_) => println!("📄 Created {} from {}", config.display(), default.display()),
+        Err(e) => eprintln!("⚠️  Failed to copy {}: {}", default.display(), e),
+    }
+}
@@ -0,0 +1,394 @@
+// Peripherals module for Crusty-Crawler
+// For small-office desktops: the local CUPS print queues from lpstat, with offline printers and
+// jobs stuck in the queue, and the USB devices plugged into the machine. Off by default, turned
+// on with checks.peripherals.enabled. /api/peripherals serves it with the monitors and sound
+// cards from av_devices.rs, and the printers check alerts
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PeripheralConfig {
+    pub enabled: bool,
+    // Off for kiosks without a print queue, so the printers check doesn't report CUPS missing
+    pub printers: bool,
+    // A job still queued after this long counts as stuck
+    pub stuck_job_minutes: u64,
+    // Monitors a kiosk should have connected, 0 to only alert on ones being unplugged
+    pub expected_monitors: usize,
+    // Sound cards that should be present, matched case-insensitively against ALSA's names
+    pub expected_audio: Vec<String>,
+}
+
+impl Default for PeripheralConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            printers: true,
+            stuck_job_minutes: 30,
+            expected_monitors: 0,
+            expected_audio: Vec::new(),
+        }
+    }
+}
+
+impl PeripheralConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.stuck_job_minutes == 0 {
+            return Err("checks.peripherals.stuck_job_minutes must be above 0".to_string());
+        }
+        if self
+            .expected_audio
+            .iter()
+            .any(|name| name.trim().is_empty())
+        {
+            return Err("checks.peripherals.expected_audio can't have empty names".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Printer {
+    pub name: String,
+    // idle, printing or disabled
+    pub state: String,
+    // Whether the queue accepts jobs
+    pub enabled: bool,
+    // What CUPS says about it, e.g. "Printer is offline"
+    pub message: Option<String>,
+    pub offline: bool,
+    pub queued_jobs: usize,
+    pub stuck_jobs: usize,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct PrintJob {
+    // As lpstat names it, e.g. Office-42
+    pub id: String,
+    pub printer: String,
+    pub user: String,
+    pub size_bytes: u64,
+    // RFC 3339, None when lpstat's date couldn't be read
+    pub submitted_at: Option<String>,
+    // lpstat's own text for the date when it couldn't be read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unreadable_date: Option<String>,
+    pub stuck: bool,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct UsbDevice {
+    // Position in the USB tree, e.g. 1-1.2
+    pub port: String,
+    pub vendor_id: String,
+    pub product_id: String,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+}
+
+// Response of GET /api/peripherals
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct PeripheralReport {
+    pub printers: Vec<Printer>,
+    pub jobs: Vec<PrintJob>,
+    pub usb_devices: Vec<UsbDevice>,
+    pub displays: Vec<DisplayOutput>,
+    pub audio_devices: Vec<AudioDevice>,
+    // Why the print queues couldn't be read, e.g. CUPS isn't installed
+    pub print_error: Option<String>,
+    pub collected_at: String,
+}
+
+// `lpstat -p` in the C locale:
+// "printer Office is idle.  enabled since Fri Oct 16 09:12:44 2026", then the printer's state
+// message indented on the next line when it has one
+pub fn parse_lpstat_printers(output: &str) -> Vec<Printer> {
+    let mut printers: Vec<Printer> = Vec::new();
+    for line in output.lines() {
+        if let Some(rest) = line.strip_prefix("printer ") {
+            let Some((name, status)) = rest.split_once(' ') else {
+                continue;
+            };
+            let state = if status.starts_with("disabled") {
+                "disabled"
+            } else if status.contains("now printing") {
+                "printing"
+            } else {
+                "idle"
+            };
+            printers.push(Printer {
+                name: name.to_string(),
+                state: state.to_string(),
+                enabled: state != "disabled",
+                message: None,
+                offline: state == "disabled",
+                queued_jobs: 0,
+                stuck_jobs: 0,
+            });
+            continue;
+        }
+        let message = line.trim();
+        if let Some(printer) = printers.last_mut()
+            && line.starts_with(char::is_whitespace)
+            && !message.is_empty()
+        {
+            printer.offline |= message.to_lowercase().contains("offline");
+            printer.message = Some(message.to_string());
+        }
+    }
+    printers
+}
+
+// The weekday is left out, chrono rejects one that doesn't match the date and lpstat builds
+// without the C locale print it differently
+fn lpstat_job_time(date: &[&str]) -> Option<String> {
+    let without_weekday = match date.first() {
+        Some(first) if first.chars().all(char::is_alphabetic) && date.len() > 4 => &date[1..],
+        _ => date,
+    };
+    chrono::NaiveDateTime::parse_from_str(&without_weekday.join(" "), "%b %d %H:%M:%S %Y")
+        .ok()
+        .and_then(|time| time.and_local_timezone(chrono::Local).earliest())
+        .map(|time| time.to_rfc3339())
+}
+
+// `lpstat -o` in the C locale, one job per line:
+// "Office-42               alice           102400   Fri Oct 16 08:30:01 2026"
+pub fn parse_lpstat_jobs(output: &str) -> Vec<PrintJob> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let [id, user, size, date @ ..] = words.as_slice() else {
+                return None;
+            };
+            let (printer, _) = id.rsplit_once('-')?;
+            let submitted_at = lpstat_job_time(date);
+            let unreadable_date =
+                (submitted_at.is_none() && !date.is_empty()).then(|| date.join(" "));
+            Some(PrintJob {
+                id: id.to_string(),
+                printer: printer.to_string(),
+                user: user.to_string(),
+                size_bytes: size.parse().unwrap_or(0),
+                submitted_at,
+                unreadable_date,
+                stuck: false,
+            })
+        })
+        .collect()
+}
+
+// Marks the jobs queued for longer than the config allows and counts them per printer
+pub fn peripheral_report(
+    mut printers: Vec<Printer>,
+    mut jobs: Vec<PrintJob>,
+    usb_devices: Vec<UsbDevice>,
+    config: &PeripheralConfig,
+    now: chrono::DateTime<chrono::Utc>,
+) -> PeripheralReport {
+    let cutoff = now - chrono::Duration::minutes(config.stuck_job_minutes as i64);
+    for job in &mut jobs {
+        job.stuck = job
+            .submitted_at
+            .as_ref()
+            .and_then(|time| chrono::DateTime::parse_from_rfc3339(time).ok())
+            .is_some_and(|time| time <= cutoff);
+    }
+    for printer in &mut printers {
+        let queued = jobs.iter().filter(|job| job.printer == printer.name);
+        printer.queued_jobs = queued.clone().count();
+        printer.stuck_jobs = queued.filter(|job| job.stuck).count();
+    }
+    PeripheralReport {
+        printers,
+        jobs,
+        usb_devices,
+        collected_at: now.to_rfc3339(),
+        ..Default::default()
+    }
+}
+
+fn run_lpstat(argument: &str) -> Result<String, String> {
+    let output = std::process::Command::new("lpstat")
+        .arg(argument)
+        .env("LC_ALL", "C")
+        .output()
+        .map_err(|e| format!("lpstat: {}", e))?;
+    // lpstat fails when no printer is set up at all, which isn't an error here
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() && !stderr.contains("No destinations") {
+        return Err(format!("lpstat {}: {}", argument, stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Devices under /sys/bus/usb/devices, without the root hubs and the interfaces
+#[cfg(target_os = "linux")]
+fn read_usb_devices() -> Vec<UsbDevice> {
+    let Ok(entries) = fs::read_dir("/sys/bus/usb/devices") else {
+        return Vec::new();
+    };
+    let mut devices: Vec<UsbDevice> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let port = entry.file_name().to_string_lossy().into_owned();
+            if port.starts_with("usb") || port.contains(':') {
+                return None;
+            }
+            let read = |file: &str| {
+                fs::read_to_string(entry.path().join(file))
+                    .ok()
+                    .map(|value| value.trim().to_string())
+                    .filter(|value| !value.is_empty())
+            };
+            Some(UsbDevice {
+                vendor_id: read("idVendor")?,
+                product_id: read("idProduct")?,
+                manufacturer: read("manufacturer"),
+                product: read("product"),
+                port,
+            })
+        })
+        .collect();
+    devices.sort_by(|a, b| a.port.cmp(&b.port));
+    devices
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_usb_devices() -> Vec<UsbDevice> {
+    Vec::new()
+}
+
+// The last report, for the metrics
+static LAST_PERIPHERALS: Mutex<Option<PeripheralReport>> = Mutex::new(None);
+
+pub fn read_peripherals(config: &PeripheralConfig) -> PeripheralReport {
+    let queues = if config.printers {
+        run_lpstat("-p").and_then(|printers| Ok((printers, run_lpstat("-o")?)))
+    } else {
+        Ok((String::new(), String::new()))
+    };
+    let (printers, jobs, print_error) = match queues {
+        Ok((printers, jobs)) => (
+            parse_lpstat_printers(&printers),
+            parse_lpstat_jobs(&jobs),
+            None,
+        ),
+        Err(e) => (Vec::new(), Vec::new(), Some(e)),
+    };
+    let mut report = peripheral_report(
+        printers,
+        jobs,
+        read_usb_devices(),
+        config,
+        chrono::Utc::now(),
+    );
+    report.print_error = print_error;
+    report.displays = read_displays();
+    report.audio_devices = read_audio_devices();
+    track_displays(&report.displays);
+    *LAST_PERIPHERALS.lock().unwrap() = Some(report.clone());
+    report
+}
+
+// CUPS runs on Linux and macOS
+pub fn peripherals_available(config: &PeripheralConfig) -> bool {
+    cfg!(unix) && config.enabled
+}
+
+pub fn printers_available(config: &PeripheralConfig) -> bool {
+    peripherals_available(config) && config.printers
+}
+
+pub fn peripheral_metrics() -> Vec<Metric> {
+    let Some(report) = LAST_PERIPHERALS.lock().unwrap().clone() else {
+        return Vec::new();
+    };
+    let mut metrics = vec![Metric::new(
+        "usb_devices_connected",
+        report.usb_devices.len() as f64,
+    )];
+    metrics.extend(av_device_metrics(&report));
+    for printer in &report.printers {
+        metrics.push(
+            Metric::new("printer_online", if printer.offline { 0.0 } else { 1.0 })
+                .label("printer", &printer.name),
+        );
+        metrics.push(
+            Metric::new("printer_queued_jobs", printer.queued_jobs as f64)
+                .label("printer", &printer.name),
+        );
+        metrics.push(
+            Metric::new("printer_stuck_jobs", printer.stuck_jobs as f64)
+                .label("printer", &printer.name),
+        );
+    }
+    metrics
+}
+
+// CRITICAL while a printer is offline, WARNING while jobs are stuck
+pub fn printers_check_result(report: &PeripheralReport) -> CheckResult {
+    if let Some(error) = &report.print_error {
+        return CheckResult::new("printers", CheckState::Unknown, error.clone(), Vec::new());
+    }
+    if report.printers.is_empty() {
+        return CheckResult::new(
+            "printers",
+            CheckState::Unknown,
+            "No printers set up".to_string(),
+            Vec::new(),
+        );
+    }
+    let offline: Vec<&Printer> = report.printers.iter().filter(|p| p.offline).collect();
+    let stuck: Vec<&Printer> = report
+        .printers
+        .iter()
+        .filter(|p| p.stuck_jobs > 0)
+        .collect();
+    let state = if !offline.is_empty() {
+        CheckState::Critical
+    } else if !stuck.is_empty() {
+        CheckState::Warning
+    } else {
+        CheckState::Ok
+    };
+    let mut problems: Vec<String> = offline
+        .iter()
+        .map(|printer| {
+            format!(
+                "{} offline{}",
+                printer.name,
+                printer
+                    .message
+                    .as_ref()
+                    .map(|message| format!(" ({})", message))
+                    .unwrap_or_default()
+            )
+        })
+        .collect();
+    problems.extend(
+        stuck
+            .iter()
+            .map(|printer| format!("{} has {} stuck job(s)", printer.name, printer.stuck_jobs)),
+    );
+    let output = if problems.is_empty() {
+        format!("{} printer(s) ready", report.printers.len())
+    } else {
+        problems.join(", ")
+    };
+    let stuck_jobs = report.jobs.iter().filter(|job| job.stuck).count();
+    CheckResult::new(
+        "printers",
+        state,
+        output,
+        vec![
+            PerfData::new("printers", report.printers.len() as f64, ""),
+            PerfData::new("offline", offline.len() as f64, ""),
+            PerfData::new("stuck_jobs", stuck_jobs as f64, ""),
+        ],
+    )
+}
+
+fn check_printers(config: &PeripheralConfig) -> CheckResult {
+    printers_check_result(&read_peripherals(config))
+}
@@ -0,0 +1,172 @@
+// Persistence module for Crusty-Crawler
+// Crash-safe saving of crusty_auth.json: the file is replaced atomically, carries a schema
+// version that older files are migrated from, and the versions it replaces are kept as backups
+
+const AUTH_CONFIG_VERSION: u32 = 3;
+const CONFIG_BACKUP_DIR: &str = "backups";
+
+// Files from before the version field existed
+fn legacy_config_version() -> u32 {
+    1
+}
+
+fn default_config_backups() -> usize {
+    10
+}
+
+// Writes next to the target and renames over it, a crash leaves the old or the new file but
+// never half of one. Owner-only on Unix, the file holds credentials
+pub fn write_file_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp = path.with_file_name(temp_name);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&temp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&temp, path)
+}
+
+fn config_backup_dir(config_path: &Path) -> PathBuf {
+    config_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(CONFIG_BACKUP_DIR)
+}
+
+// Newest first, the timestamp in the name sorts chronologically
+fn config_backups(config_path: &Path) -> Vec<PathBuf> {
+    let prefix = format!(
+        "{}.",
+        config_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+    );
+    let Ok(entries) = fs::read_dir(config_backup_dir(config_path)) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with(&prefix))
+        })
+        .collect();
+    backups.sort();
+    backups.reverse();
+    backups
+}
+
+// Keeps the contents about to be replaced, pruning all but the newest `keep`
+fn backup_config(config_path: &Path, contents: &str, keep: usize) -> io::Result<()> {
+    if keep == 0 {
+        return Ok(());
+    }
+    let dir = config_backup_dir(config_path);
+    fs::create_dir_all(&dir)?;
+    let name = format!(
+        "{}.{}",
+        config_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy(),
+        chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ")
+    );
+    write_file_atomic(&dir.join(name), contents.as_bytes())?;
+    for old in config_backups(config_path).into_iter().skip(keep) {
+        fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+// Upgrades the raw JSON one schema version at a step before serde sees it. Returns whether
+// anything was migrated so the caller can write the upgraded file back
+fn migrate_config(mut value: serde_json::Value) -> Result<(serde_json::Value, bool), String> {
+    if !value.is_object() {
+        return Err("the config must be a JSON object".to_string());
+    }
+    let from = value
+        .get("version")
+        .and_then(|version| version.as_u64())
+        .map_or(legacy_config_version(), |version| version as u32);
+    if from > AUTH_CONFIG_VERSION {
+        return Err(format!(
+            "schema version {} is newer than this build supports ({}), upgrade Crusty-Crawler",
+            from, AUTH_CONFIG_VERSION
+        ));
+    }
+
+    for version in from..AUTH_CONFIG_VERSION {
+        match version {
+            // Unversioned files only lack the version field itself
+            1 => {}
+            // Access tokens were stored in plain text
+            2 => hash_stored_tokens(&mut value),
+            _ => return Err(format!("no migration from schema version {}", version)),
+        }
+    }
+    value["version"] = AUTH_CONFIG_VERSION.into();
+    Ok((value, from < AUTH_CONFIG_VERSION))
+}
+
+fn hash_stored_tokens(value: &mut serde_json::Value) {
+    let Some(users) = value
+        .get_mut("users")
+        .and_then(|users| users.as_object_mut())
+    else {
+        return;
+    };
+    for user in users.values_mut().filter_map(|user| user.as_object_mut()) {
+        if let Some(serde_json::Value::String(token)) = user.remove("access_token") {
+            user.insert(
+                "access_token_hash".to_string(),
+                hash_access_token(&token).into(),
+            );
+        }
+    }
+}
+
+// Brings the backups up to the current schema as well, so none of them keeps what a
+// migration removed from the config, like plain text tokens
+fn migrate_config_backups(config_path: &Path) {
+    for backup in config_backups(config_path) {
+        let Ok(data) = fs::read_to_string(&backup) else {
+            continue;
+        };
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(&data) else {
+            continue;
+        };
+        if let Ok((value, true)) = migrate_config(value)
+            && let Ok(data) = serde_json::to_string_pretty(&value)
+            && let Err(e) = write_file_atomic(&backup, data.as_bytes())
+        {
+            eprintln!("⚠️  Failed to migrate {}: {}", backup.display(), e);
+        }
+    }
+}
+
+pub fn parse_auth_config(data: &str) -> Result<(AuthConfig, bool), String> {
+    let value: serde_json::Value = serde_json::from_str(data).map_err(|e| e.to_string())?;
+    let (value, migrated) = migrate_config(value)?;
+    let config = serde_json::from_value(value).map_err(|e| e.to_string())?;
+    Ok((config, migrated))
+}
+
+// Newest backup that still parses, for when the config itself is damaged
+fn restore_config_backup(config_path: &Path) -> Option<(AuthConfig, PathBuf)> {
+    config_backups(config_path).into_iter().find_map(|backup| {
+        let data = fs::read_to_string(&backup).ok()?;
+        let (config, _) = parse_auth_config(&data).ok()?;
+        Some((config, backup))
+    })
+}
@@ -0,0 +1,440 @@
+// Storage pool module for Crusty-Crawler
+// ZFS pool health, capacity and scrub status and Btrfs per-device error counters,
+// which generic disk usage can't see
+
+#[derive(Serialize, Clone)]
+pub struct ZfsPool {
+    pub name: String,
+    pub health: String,
+    pub size_bytes: u64,
+    pub allocated_bytes: u64,
+    pub free_bytes: u64,
+    pub capacity_percent: f64,
+    pub fragmentation_percent: Option<f64>,
+    // The "scan:" line of zpool status, e.g. "scrub repaired 0B in 00:01:02 with 0 errors on ..."
+    pub scrub: Option<String>,
+    pub scrub_in_progress: bool,
+    pub scrub_errors: Option<u64>,
+    pub data_errors: bool,
+}
+
+impl ZfsPool {
+    pub fn check_state(&self) -> CheckState {
+        let health = match self.health.as_str() {
+            "ONLINE" => CheckState::Ok,
+            "OFFLINE" | "REMOVED" => CheckState::Warning,
+            // DEGRADED has lost redundancy, FAULTED/UNAVAIL/SUSPENDED have lost data access
+            _ => CheckState::Critical,
+        };
+        // ZFS write performance collapses as pools fill up
+        let capacity = if self.capacity_percent >= 90.0 {
+            CheckState::Critical
+        } else if self.capacity_percent >= 80.0 {
+            CheckState::Warning
+        } else {
+            CheckState::Ok
+        };
+        let errors = if self.data_errors {
+            CheckState::Critical
+        } else if self.scrub_errors.unwrap_or(0) > 0 {
+            CheckState::Warning
+        } else {
+            CheckState::Ok
+        };
+        health.max(capacity).max(errors)
+    }
+
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{} {} {:.0}% used ({} GB of {} GB)",
+            self.name,
+            self.health,
+            self.capacity_percent,
+            self.allocated_bytes / 1024 / 1024 / 1024,
+            self.size_bytes / 1024 / 1024 / 1024
+        );
+        if self.data_errors {
+            summary.push_str(", data errors");
+        }
+        if let Some(scrub) = &self.scrub {
+            summary.push_str(&format!(", {}", scrub));
+        }
+        summary
+    }
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct BtrfsDevice {
+    pub device: String,
+    pub write_io_errs: u64,
+    pub read_io_errs: u64,
+    pub flush_io_errs: u64,
+    pub corruption_errs: u64,
+    pub generation_errs: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct BtrfsFilesystem {
+    pub mount: String,
+    pub devices: Vec<BtrfsDevice>,
+}
+
+impl BtrfsFilesystem {
+    // Counters persist until reset with `btrfs device stats -z`
+    pub fn check_state(&self) -> CheckState {
+        self.devices
+            .iter()
+            .map(|d| {
+                if d.write_io_errs + d.read_io_errs + d.corruption_errs > 0 {
+                    CheckState::Critical
+                } else if d.flush_io_errs + d.generation_errs > 0 {
+                    CheckState::Warning
+                } else {
+                    CheckState::Ok
+                }
+            })
+            .max()
+            .unwrap_or(CheckState::Ok)
+    }
+
+    pub fn summary(&self) -> String {
+        let failing: Vec<String> = self
+            .devices
+            .iter()
+            .filter(|d| {
+                d.write_io_errs
+                    + d.read_io_errs
+                    + d.flush_io_errs
+                    + d.corruption_errs
+                    + d.generation_errs
+                    > 0
+            })
+            .map(|d| {
+                format!(
+                    "{} (write {}, read {}, flush {}, corruption {}, generation {})",
+                    d.device,
+                    d.write_io_errs,
+                    d.read_io_errs,
+                    d.flush_io_errs,
+                    d.corruption_errs,
+                    d.generation_errs
+                )
+            })
+            .collect();
+
+        if failing.is_empty() {
+            format!("{} {} devices, no errors", self.mount, self.devices.len())
+        } else {
+            format!("{} errors on {}", self.mount, failing.join(", "))
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct StorageReport {
+    pub raid: Vec<RaidArray>,
+    pub zfs_pools: Vec<ZfsPool>,
+    pub btrfs: Vec<BtrfsFilesystem>,
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// Fills in scrub and error details from `zpool status <pool>`
+fn apply_zpool_status(pool: &mut ZfsPool, status: &str) {
+    let mut lines = status.lines().map(|l| l.trim()).peekable();
+    while let Some(line) = lines.next() {
+        if let Some(scan) = line.strip_prefix("scan:") {
+            let mut scan = scan.trim().to_string();
+            pool.scrub_in_progress = scan.contains("in progress");
+            // Progress is on the following indented lines, e.g. "12.34% done, 00:10:00 to go"
+            while let Some(next) = lines.peek() {
+                if next.contains(':') && !next.contains("done") {
+                    break;
+                }
+                if let Some(done) = next.split(',').find(|part| part.contains("done")) {
+                    scan.push_str(&format!(" ({})", done.trim()));
+                }
+                lines.next();
+            }
+            pool.scrub_errors = scan
+                .split(" with ")
+                .nth(1)
+                .and_then(|rest| rest.split_whitespace().next())
+                .and_then(|count| count.parse().ok());
+            pool.scrub = Some(scan);
+        } else if let Some(errors) = line.strip_prefix("errors:") {
+            pool.data_errors = errors.trim() != "No known data errors";
+        }
+    }
+}
+
+pub fn read_zfs_pools() -> Vec<ZfsPool> {
+    let Some(list) = command_output(
+        "zpool",
+        &["list", "-Hp", "-o", "name,size,alloc,free,frag,cap,health"],
+    ) else {
+        return Vec::new();
+    };
+
+    list.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 7 {
+                return None;
+            }
+            let mut pool = ZfsPool {
+                name: fields[0].to_string(),
+                size_bytes: fields[1].parse().unwrap_or(0),
+                allocated_bytes: fields[2].parse().unwrap_or(0),
+                free_bytes: fields[3].parse().unwrap_or(0),
+                // "-" when the pool doesn't track fragmentation
+                fragmentation_percent: fields[4].trim_end_matches('%').parse().ok(),
+                capacity_percent: fields[5].trim_end_matches('%').parse().unwrap_or(0.0),
+                health: fields[6].to_string(),
+                scrub: None,
+                scrub_in_progress: false,
+                scrub_errors: None,
+                data_errors: false,
+            };
+            if let Some(status) = command_output("zpool", &["status", &pool.name]) {
+                apply_zpool_status(&mut pool, &status);
+            }
+            Some(pool)
+        })
+        .collect()
+}
+
+// Parses "[/dev/sda1].write_io_errs   0" lines of `btrfs device stats`
+fn parse_btrfs_device_stats(stats: &str) -> Vec<BtrfsDevice> {
+    let mut devices: Vec<BtrfsDevice> = Vec::new();
+    for line in stats.lines() {
+        let mut parts = line.split_whitespace();
+        let (Some(key), Some(value)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        let Some((device, counter)) = key.rsplit_once('.') else {
+            continue;
+        };
+        let device = device.trim_start_matches('[').trim_end_matches(']');
+        let value: u64 = value.parse().unwrap_or(0);
+
+        if devices.last().is_none_or(|d| d.device != device) {
+            devices.push(BtrfsDevice {
+                device: device.to_string(),
+                ..Default::default()
+            });
+        }
+        let entry = devices.last_mut().unwrap();
+        match counter {
+            "write_io_errs" => entry.write_io_errs = value,
+            "read_io_errs" => entry.read_io_errs = value,
+            "flush_io_errs" => entry.flush_io_errs = value,
+            "corruption_errs" => entry.corruption_errs = value,
+            "generation_errs" => entry.generation_errs = value,
+            _ => {}
+        }
+    }
+    devices
+}
+
+#[cfg(target_os = "linux")]
+pub fn read_btrfs_filesystems() -> Vec<BtrfsFilesystem> {
+    let Ok(mounts) = fs::read_to_string("/proc/mounts") else {
+        return Vec::new();
+    };
+
+    // Subvolumes mount the same filesystem several times, one stats read per source is enough
+    let mut seen = std::collections::HashSet::new();
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            (fields.len() >= 3 && fields[2] == "btrfs" && seen.insert(fields[0].to_string()))
+                .then(|| fields[1].to_string())
+        })
+        .filter_map(|mount| {
+            let stats = command_output("btrfs", &["device", "stats", &mount])?;
+            Some(BtrfsFilesystem {
+                devices: parse_btrfs_device_stats(&stats),
+                mount,
+            })
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_btrfs_filesystems() -> Vec<BtrfsFilesystem> {
+    Vec::new()
+}
+
+pub fn read_storage_report() -> StorageReport {
+    StorageReport {
+        raid: read_raid_arrays(),
+        zfs_pools: read_zfs_pools(),
+        btrfs: read_btrfs_filesystems(),
+    }
+}
+
+pub fn pool_metrics(report: &StorageReport) -> Vec<Metric> {
+    let mut metrics = Vec::new();
+    for pool in &report.zfs_pools {
+        metrics.push(
+            Metric::new(
+                "zfs_pool_healthy",
+                if pool.health == "ONLINE" { 1.0 } else { 0.0 },
+            )
+            .label("pool", &pool.name)
+            .label("health", &pool.health),
+        );
+        metrics.push(
+            Metric::new("zfs_pool_size_bytes", pool.size_bytes as f64).label("pool", &pool.name),
+        );
+        metrics.push(
+            Metric::new("zfs_pool_allocated_bytes", pool.allocated_bytes as f64)
+                .label("pool", &pool.name),
+        );
+        metrics.push(
+            Metric::new("zfs_pool_capacity_percent", pool.capacity_percent)
+                .label("pool", &pool.name),
+        );
+        if let Some(fragmentation) = pool.fragmentation_percent {
+            metrics.push(
+                Metric::new("zfs_pool_fragmentation_percent", fragmentation)
+                    .label("pool", &pool.name),
+            );
+        }
+        metrics.push(
+            Metric::new(
+                "zfs_pool_scrub_in_progress",
+                if pool.scrub_in_progress { 1.0 } else { 0.0 },
+            )
+            .label("pool", &pool.name),
+        );
+    }
+
+    for filesystem in &report.btrfs {
+        for device in &filesystem.devices {
+            for (kind, count) in [
+                ("write_io", device.write_io_errs),
+                ("read_io", device.read_io_errs),
+                ("flush_io", device.flush_io_errs),
+                ("corruption", device.corruption_errs),
+                ("generation", device.generation_errs),
+            ] {
+                metrics.push(
+                    Metric::new("btrfs_device_errors_total", count as f64)
+                        .label("mount", &filesystem.mount)
+                        .label("device", &device.device)
+                        .label("type", kind),
+                );
+            }
+        }
+    }
+    metrics
+}
+
+pub fn pool_status(report: &StorageReport) -> String {
+    let mut output = String::from("\n=== Storage Pools ===\n");
+    for pool in &report.zfs_pools {
+        let marker = if pool.check_state() == CheckState::Ok {
+            ""
+        } else {
+            "🚨 "
+        };
+        output.push_str(&format!("{}ZFS {}\n", marker, pool.summary()));
+    }
+    for filesystem in &report.btrfs {
+        let marker = if filesystem.check_state() == CheckState::Ok {
+            ""
+        } else {
+            "🚨 "
+        };
+        output.push_str(&format!("{}Btrfs {}\n", marker, filesystem.summary()));
+    }
+    output
+}
+
+pub fn check_zfs() -> CheckResult {
+    let pools = read_zfs_pools();
+    if pools.is_empty() {
+        return CheckResult::new(
+            "zfs",
+            CheckState::Unknown,
+            "No ZFS pools found".to_string(),
+            Vec::new(),
+        );
+    }
+
+    let state = pools
+        .iter()
+        .map(|p| p.check_state())
+        .max()
+        .unwrap_or(CheckState::Ok);
+    let output = pools
+        .iter()
+        .map(|p| p.summary())
+        .collect::<Vec<_>>()
+        .join("; ");
+    let perfdata = pools
+        .iter()
+        .map(|p| {
+            let mut capacity = PerfData::new(&p.name, p.capacity_percent, "%").range(0.0, 100.0);
+            capacity.warn = Some(80.0);
+            capacity.crit = Some(90.0);
+            capacity
+        })
+        .collect();
+
+    CheckResult::new("zfs", state, output, perfdata)
+}
+
+pub fn check_btrfs() -> CheckResult {
+    let filesystems = read_btrfs_filesystems();
+    if filesystems.is_empty() {
+        return CheckResult::new(
+            "btrfs",
+            CheckState::Unknown,
+            "No Btrfs filesystems found".to_string(),
+            Vec::new(),
+        );
+    }
+
+    let state = filesystems
+        .iter()
+        .map(|f| f.check_state())
+        .max()
+        .unwrap_or(CheckState::Ok);
+    let output = filesystems
+        .iter()
+        .map(|f| f.summary())
+        .collect::<Vec<_>>()
+        .join("; ");
+    let perfdata = filesystems
+        .iter()
+        .map(|f| {
+            let errors: u64 = f
+                .devices
+                .iter()
+                .map(|d| {
+                    d.write_io_errs
+                        + d.read_io_errs
+                        + d.flush_io_errs
+                        + d.corruption_errs
+                        + d.generation_errs
+                })
+                .sum();
+            PerfData::new(&format!("{}_errors", f.mount), errors as f64, "c")
+        })
+        .collect();
+
+    CheckResult::new("btrfs", state, output, perfdata)
+}
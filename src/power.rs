@@ -0,0 +1,221 @@
+// Power module for Crusty-Crawler
+// Laptops on battery: the expensive collectors run less often, and the speed test and
+// hardware-query are skipped until the machine is plugged in again. metrics.power.saving
+// overrides what the power supply says
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerSaving {
+    // While running on battery
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PowerConfig {
+    pub saving: PowerSaving,
+    // Expensive collectors run this many times less often while saving power
+    pub backoff_factor: u32,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            saving: PowerSaving::Auto,
+            backoff_factor: 4,
+        }
+    }
+}
+
+impl PowerConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.backoff_factor == 0 {
+            return Err("metrics.power.backoff_factor must be above 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerSource {
+    Ac,
+    Battery,
+    // Nothing reports a power supply, desktops and VMs mostly
+    Unknown,
+}
+
+// One entry of /sys/class/power_supply
+pub struct PowerSupply {
+    // "Mains", "USB" or "Battery"
+    pub kind: String,
+    // "Device" for the batteries of mice and headsets
+    pub scope: Option<String>,
+    pub online: Option<bool>,
+    // "Charging", "Discharging", "Full" or "Not charging" for batteries
+    pub status: Option<String>,
+}
+
+// An adapter that's plugged in wins, without adapters a discharging battery means battery power
+pub fn power_source_of(supplies: &[PowerSupply]) -> PowerSource {
+    let adapters: Vec<&PowerSupply> = supplies
+        .iter()
+        .filter(|supply| matches!(supply.kind.as_str(), "Mains" | "USB" | "USB_C" | "USB_PD"))
+        .collect();
+    let batteries: Vec<&PowerSupply> = supplies
+        .iter()
+        .filter(|supply| supply.kind == "Battery" && supply.scope.as_deref() != Some("Device"))
+        .collect();
+    if batteries.is_empty() {
+        return if adapters.is_empty() {
+            PowerSource::Unknown
+        } else {
+            PowerSource::Ac
+        };
+    }
+    if adapters.iter().any(|adapter| adapter.online == Some(true)) {
+        return PowerSource::Ac;
+    }
+    if !adapters.is_empty()
+        || batteries
+            .iter()
+            .any(|battery| battery.status.as_deref() == Some("Discharging"))
+    {
+        PowerSource::Battery
+    } else {
+        PowerSource::Ac
+    }
+}
+
+// `pmset -g batt` starts with "Now drawing from 'AC Power'" or "'Battery Power'"
+pub fn parse_pmset_power_source(output: &str) -> PowerSource {
+    let Some(line) = output.lines().find(|line| line.contains("drawing from")) else {
+        return PowerSource::Unknown;
+    };
+    if line.contains("Battery Power") {
+        PowerSource::Battery
+    } else {
+        PowerSource::Ac
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn read_power_source() -> PowerSource {
+    let Ok(entries) = fs::read_dir("/sys/class/power_supply") else {
+        return PowerSource::Unknown;
+    };
+    let supplies: Vec<PowerSupply> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let read = |name: &str| {
+                fs::read_to_string(entry.path().join(name))
+                    .ok()
+                    .map(|value| value.trim().to_string())
+            };
+            Some(PowerSupply {
+                kind: read("type")?,
+                scope: read("scope"),
+                online: read("online").map(|online| online == "1"),
+                status: read("status"),
+            })
+        })
+        .collect();
+    power_source_of(&supplies)
+}
+
+#[cfg(target_os = "macos")]
+pub fn read_power_source() -> PowerSource {
+    match std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+    {
+        Ok(output) => parse_pmset_power_source(&String::from_utf8_lossy(&output.stdout)),
+        Err(_) => PowerSource::Unknown,
+    }
+}
+
+#[cfg(windows)]
+pub fn read_power_source() -> PowerSource {
+    // Only the first two fields are read, the rest is there for the layout
+    #[repr(C)]
+    #[derive(Default)]
+    #[allow(dead_code)]
+    struct SystemPowerStatus {
+        ac_line_status: u8,
+        battery_flag: u8,
+        battery_life_percent: u8,
+        system_status_flag: u8,
+        battery_life_time: u32,
+        battery_full_life_time: u32,
+    }
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetSystemPowerStatus(status: *mut SystemPowerStatus) -> i32;
+    }
+
+    let mut status = SystemPowerStatus::default();
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+        return PowerSource::Unknown;
+    }
+    // 128 is "no system battery", 255 "unknown"
+    match (status.ac_line_status, status.battery_flag) {
+        (_, 128) => PowerSource::Unknown,
+        (0, _) => PowerSource::Battery,
+        (1, _) => PowerSource::Ac,
+        _ => PowerSource::Unknown,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn read_power_source() -> PowerSource {
+    PowerSource::Unknown
+}
+
+pub fn next_power_saving(
+    config: &PowerConfig,
+    current: Option<&DegradedCollection>,
+    source: PowerSource,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Option<DegradedCollection> {
+    let reason = match (config.saving, source) {
+        (PowerSaving::Never, _) => return None,
+        (PowerSaving::Always, _) => "power saving is always on",
+        (PowerSaving::Auto, PowerSource::Battery) => "running on battery",
+        (PowerSaving::Auto, _) => return None,
+    };
+    Some(DegradedCollection {
+        since: current.map_or_else(|| now.to_rfc3339(), |current| current.since.clone()),
+        reason: reason.to_string(),
+        backoff_factor: config.backoff_factor.max(1),
+    })
+}
+
+fn update_power_saving(config: &PowerConfig) {
+    let source = read_power_source();
+    let mut status = COLLECTION_STATUS.lock().unwrap();
+    let saving = next_power_saving(
+        config,
+        status.power_saving.as_ref(),
+        source,
+        chrono::Utc::now(),
+    );
+    match (&status.power_saving, &saving) {
+        (None, Some(saving)) => println!(
+            "🔋 Saving power ({}), collectors run {}x less often, no speed tests or hardware queries",
+            saving.reason, saving.backoff_factor
+        ),
+        (Some(_), None) => println!("🔌 Power saving off, collecting at the usual pace"),
+        _ => {}
+    }
+    status.power_source = source;
+    status.power_saving = saving;
+}
+
+// The speed test and hardware-query wait while this is on
+pub fn power_saving_active() -> bool {
+    COLLECTION_STATUS.lock().unwrap().power_saving.is_some()
+}
@@ -0,0 +1,249 @@
+// Privacy module for Crusty-Crawler
+// How long collected data is kept per category, purging categories on demand, and an export of
+// everything kept that can leave out process command lines and usernames
+
+const REDACTED: &str = "[redacted]";
+const RETENTION_INTERVAL: Duration = Duration::from_secs(3600);
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DataCategory {
+    // Chart history and its rollups
+    Metrics,
+    // Sign-in history
+    Audit,
+    // The event timeline
+    Events,
+    // Crashes and the process memory kept for /api/diagnose
+    ProcessNames,
+}
+
+impl DataCategory {
+    pub const ALL: [DataCategory; 4] = [
+        DataCategory::Metrics,
+        DataCategory::Audit,
+        DataCategory::Events,
+        DataCategory::ProcessNames,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DataCategory::Metrics => "metrics",
+            DataCategory::Audit => "audit",
+            DataCategory::Events => "events",
+            DataCategory::ProcessNames => "process names",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct PrivacyConfig {
+    // Days kept per category, 0 keeps data until the size limit of its store pushes it out
+    pub metrics_days: u64,
+    pub audit_days: u64,
+    pub events_days: u64,
+    pub process_names_days: u64,
+    // Cron expression for pruning, hourly otherwise
+    pub retention_schedule: Option<String>,
+    // Applied to /api/export
+    pub redact_command_lines: bool,
+    pub redact_usernames: bool,
+}
+
+impl PrivacyConfig {
+    pub fn retention_days(&self, category: DataCategory) -> u64 {
+        match category {
+            DataCategory::Metrics => self.metrics_days,
+            DataCategory::Audit => self.audit_days,
+            DataCategory::Events => self.events_days,
+            DataCategory::ProcessNames => self.process_names_days,
+        }
+    }
+}
+
+// Body of POST /api/privacy/purge, every category when empty
+#[derive(Deserialize)]
+pub struct PurgeRequest {
+    #[serde(default)]
+    pub categories: Vec<DataCategory>,
+}
+
+// Entries removed per category
+pub type RemovedData = BTreeMap<DataCategory, usize>;
+
+// Returns how many entries were removed from the category's stores
+fn remove_data_before(category: DataCategory, cutoff: chrono::DateTime<chrono::Utc>) -> usize {
+    match category {
+        DataCategory::Metrics => {
+            remove_metric_history_before(cutoff) + remove_history_rollups_before(cutoff)
+        }
+        DataCategory::Audit => remove_logins_before(cutoff),
+        DataCategory::Events => remove_events_before(cutoff),
+        DataCategory::ProcessNames => {
+            remove_crashes_before(cutoff) + remove_diagnostic_snapshots_before(cutoff)
+        }
+    }
+}
+
+pub fn enforce_retention(
+    config: &PrivacyConfig,
+    now: chrono::DateTime<chrono::Utc>,
+) -> RemovedData {
+    let mut removed = RemovedData::new();
+    for category in DataCategory::ALL {
+        let days = config.retention_days(category);
+        if days == 0 {
+            continue;
+        }
+        let cutoff = now - chrono::Duration::days(days as i64);
+        removed.insert(category, remove_data_before(category, cutoff));
+    }
+    removed
+}
+
+// Everything in the categories, whatever its age. The purge itself goes on the timeline
+pub fn purge_data(categories: &[DataCategory], username: &str) -> RemovedData {
+    let categories = if categories.is_empty() {
+        &DataCategory::ALL[..]
+    } else {
+        categories
+    };
+    let removed: RemovedData = categories
+        .iter()
+        .map(|category| {
+            (
+                *category,
+                remove_data_before(*category, chrono::DateTime::<chrono::Utc>::MAX_UTC),
+            )
+        })
+        .collect();
+    let summary: Vec<String> = removed
+        .iter()
+        .map(|(category, count)| format!("{} {}", count, category.label()))
+        .collect();
+    record_event(
+        EventKind::ConfigChange,
+        &format!("{} purged stored data", username),
+        &summary.join(", "),
+    );
+    removed
+}
+
+// Response of GET /api/export
+#[derive(Serialize)]
+pub struct DataExport {
+    pub host: String,
+    pub exported_at: String,
+    pub events: Vec<TimelineEvent>,
+    pub logins: Vec<LoginRecord>,
+    pub crashes: Vec<CrashEvent>,
+    pub security_findings: Vec<SecurityFinding>,
+    pub history: BTreeMap<String, VecDeque<(String, f64)>>,
+}
+
+pub fn collect_data_export() -> DataExport {
+    DataExport {
+        host: sysinfo::System::host_name().unwrap_or_else(|| "unknown host".to_string()),
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        events: timeline_events(&EventFilter {
+            since: None,
+            kind: None,
+        })
+        .unwrap_or_default(),
+        logins: login_history(None, Some(usize::MAX)),
+        crashes: recent_crashes(),
+        security_findings: latest_security_findings(),
+        history: metric_history(),
+    }
+}
+
+// Autorun keys are "<registry key>\<name> = <command>"
+fn redact_command(text: &str) -> String {
+    match text.split_once(" = ") {
+        Some((name, _)) => format!("{} = {}", name, REDACTED),
+        None => text.to_string(),
+    }
+}
+
+// Login accounts of the machine, their names show up in home directory paths of process and
+// crash findings. System accounts are left out, their names are words like bin or daemon
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "macos"))]
+pub fn os_usernames() -> Vec<String> {
+    let first_uid = if cfg!(target_os = "macos") { 500 } else { 1000 };
+    sysinfo::Users::new_with_refreshed_list()
+        .list()
+        .iter()
+        .filter(|user| **user.id() >= first_uid && **user.id() != 65534)
+        .map(|user| user.name().to_string())
+        .collect()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "freebsd", target_os = "macos")))]
+pub fn os_usernames() -> Vec<String> {
+    sysinfo::Users::new_with_refreshed_list()
+        .list()
+        .iter()
+        .map(|user| user.name().to_string())
+        .collect()
+}
+
+// Command lines live in crash log lines, autorun and scheduled task findings and their events.
+// Usernames are replaced as whole words wherever they appear. An export that can't be redacted
+// isn't handed out at all
+pub fn redact_export(
+    export: &mut DataExport,
+    config: &PrivacyConfig,
+    usernames: &[String],
+) -> Result<(), String> {
+    if config.redact_command_lines {
+        for event in &mut export.events {
+            if matches!(event.kind, EventKind::Security | EventKind::Crash) {
+                event.title = redact_command(&event.title);
+                if !event.detail.is_empty() {
+                    event.detail = REDACTED.to_string();
+                }
+            }
+        }
+        for crash in &mut export.crashes {
+            crash.message = REDACTED.to_string();
+        }
+        for finding in &mut export.security_findings {
+            finding.key = redact_command(&finding.key);
+            finding.detail = REDACTED.to_string();
+        }
+    }
+
+    let names: Vec<String> = usernames
+        .iter()
+        .filter(|name| !name.is_empty())
+        .map(|name| regex::escape(name))
+        .collect();
+    if !config.redact_usernames || names.is_empty() {
+        return Ok(());
+    }
+    let pattern = regex::Regex::new(&format!(r"\b(?:{})\b", names.join("|")))
+        .map_err(|e| format!("Can't redact usernames: {}", e))?;
+    let redact = |text: &mut String| {
+        if pattern.is_match(text) {
+            *text = pattern.replace_all(text, REDACTED).to_string();
+        }
+    };
+    for event in &mut export.events {
+        redact(&mut event.title);
+        redact(&mut event.detail);
+    }
+    for login in &mut export.logins {
+        if login.username.is_some() {
+            login.username = Some(REDACTED.to_string());
+        }
+    }
+    for crash in &mut export.crashes {
+        redact(&mut crash.message);
+    }
+    for finding in &mut export.security_findings {
+        redact(&mut finding.key);
+        redact(&mut finding.detail);
+    }
+    Ok(())
+}
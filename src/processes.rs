@@ -0,0 +1,180 @@
+// Processes module for Crusty-Crawler
+// Per-process CPU, memory and count metrics, folded into groups by name rules and capped, so a
+// busy host with thousands of short-lived processes doesn't explode the exporters' cardinality
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ProcessGroupRule {
+    // Regex matched against the process name, e.g. "^php(-fpm)?"
+    pub pattern: String,
+    // Value of the group label, captures like "$1" are expanded
+    pub group: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ProcessMetricsConfig {
+    pub enabled: bool,
+    // Tried in order, the first match decides the group
+    pub groups: Vec<ProcessGroupRule>,
+    // Unmatched processes are grouped by their own name, or all go into "other"
+    pub group_by_name: bool,
+    // Groups exported at most, the ones using the least CPU and memory are folded into "other"
+    pub max_series: usize,
+}
+
+impl Default for ProcessMetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            groups: Vec::new(),
+            group_by_name: true,
+            max_series: 50,
+        }
+    }
+}
+
+const OTHER_PROCESS_GROUP: &str = "other";
+// Walking the process table is expensive, scrapes in between get the last result
+const PROCESS_METRICS_INTERVAL: Duration = Duration::from_secs(15);
+
+static LATEST_PROCESS_METRICS: Mutex<Option<(Instant, Vec<Metric>)>> = Mutex::new(None);
+
+// Name, CPU percent and resident memory of one process
+pub struct ProcessSample {
+    pub name: String,
+    pub cpu_percent: f64,
+    pub memory_bytes: u64,
+}
+
+#[derive(Default)]
+struct ProcessGroupTotals {
+    count: usize,
+    cpu_percent: f64,
+    memory_bytes: u64,
+}
+
+impl ProcessGroupTotals {
+    fn add(&mut self, other: &ProcessGroupTotals) {
+        self.count += other.count;
+        self.cpu_percent += other.cpu_percent;
+        self.memory_bytes += other.memory_bytes;
+    }
+}
+
+impl ProcessMetricsConfig {
+    pub fn compile_groups(&self) -> Result<Vec<(regex::Regex, &str)>, String> {
+        self.groups
+            .iter()
+            .map(|rule| {
+                regex::Regex::new(&rule.pattern)
+                    .map(|regex| (regex, rule.group.as_str()))
+                    .map_err(|e| format!("'{}': {}", rule.pattern, e))
+            })
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.compile_groups()
+            .map_err(|e| format!("metrics.processes.groups {}", e))?;
+        if self.max_series == 0 {
+            return Err("metrics.processes.max_series must be above 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn process_group(rules: &[(regex::Regex, &str)], group_by_name: bool, name: &str) -> String {
+    for (regex, group) in rules {
+        if let Some(captures) = regex.captures(name) {
+            let mut expanded = String::new();
+            captures.expand(group, &mut expanded);
+            return expanded;
+        }
+    }
+    if group_by_name {
+        name.to_string()
+    } else {
+        OTHER_PROCESS_GROUP.to_string()
+    }
+}
+
+pub fn process_group_metrics(
+    config: &ProcessMetricsConfig,
+    samples: &[ProcessSample],
+) -> Vec<Metric> {
+    // Rules are checked when the config is loaded, a bad one here just matches nothing
+    let rules = config.compile_groups().unwrap_or_default();
+    let mut groups: BTreeMap<String, ProcessGroupTotals> = BTreeMap::new();
+    for sample in samples {
+        let totals = groups
+            .entry(process_group(&rules, config.group_by_name, &sample.name))
+            .or_default();
+        totals.count += 1;
+        totals.cpu_percent += sample.cpu_percent;
+        totals.memory_bytes += sample.memory_bytes;
+    }
+
+    let mut other = groups.remove(OTHER_PROCESS_GROUP).unwrap_or_default();
+    let mut ranked: Vec<(String, ProcessGroupTotals)> = groups.into_iter().collect();
+    ranked.sort_by(|(_, a), (_, b)| {
+        b.cpu_percent
+            .total_cmp(&a.cpu_percent)
+            .then(b.memory_bytes.cmp(&a.memory_bytes))
+    });
+    // "other" takes one of the series when anything is folded into it
+    let keep = if ranked.len() + usize::from(other.count > 0) > config.max_series.max(1) {
+        config.max_series.max(1) - 1
+    } else {
+        ranked.len()
+    };
+    for (_, totals) in ranked.drain(keep..) {
+        other.add(&totals);
+    }
+    if other.count > 0 {
+        ranked.push((OTHER_PROCESS_GROUP.to_string(), other));
+    }
+
+    let mut metrics = Vec::new();
+    for (group, totals) in &ranked {
+        metrics.push(Metric::new("process_group_count", totals.count as f64).label("group", group));
+        metrics.push(
+            Metric::new("process_group_cpu_percent", totals.cpu_percent).label("group", group),
+        );
+        metrics.push(
+            Metric::new("process_group_memory_bytes", totals.memory_bytes as f64)
+                .label("group", group),
+        );
+    }
+    metrics
+}
+
+pub async fn process_metrics(config: &ProcessMetricsConfig) -> Vec<Metric> {
+    let mut sys = sysinfo::System::new();
+    // CPU usage is measured between two refreshes
+    sys.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
+    tokio::time::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL).await;
+    sys.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
+
+    let samples: Vec<ProcessSample> = sys
+        .processes()
+        .values()
+        .map(|process| ProcessSample {
+            name: process.name().to_string_lossy().to_string(),
+            cpu_percent: process.cpu_usage() as f64,
+            memory_bytes: process.memory(),
+        })
+        .collect();
+    process_group_metrics(config, &samples)
+}
+
+// process_metrics at most every PROCESS_METRICS_INTERVAL, stretched while the host is busy
+pub async fn cached_process_metrics(config: &ProcessMetricsConfig) -> Vec<Metric> {
+    if let Some((at, metrics)) = &*LATEST_PROCESS_METRICS.lock().unwrap()
+        && at.elapsed() < collection_interval(PROCESS_METRICS_INTERVAL)
+    {
+        return metrics.clone();
+    }
+    let metrics = process_metrics(config).await;
+    *LATEST_PROCESS_METRICS.lock().unwrap() = Some((Instant::now(), metrics.clone()));
+    metrics
+}
@@ -0,0 +1,211 @@
+// Protocols module for Crusty-Crawler
+// Packet counts per protocol from the kernel's SNMP counters, so a UDP flood or an ICMP storm
+// stands out from ordinary TCP traffic. Linux and Windows only, other platforms report nothing
+
+pub const PROTOCOLS: [&str; 3] = ["tcp", "udp", "icmp"];
+
+#[derive(Clone, Copy, Default)]
+pub struct ProtocolCounters {
+    pub received: u64,
+    pub sent: u64,
+    pub errors: u64,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct ProtocolRates {
+    pub received: f64,
+    pub sent: f64,
+}
+
+// Indexed like PROTOCOLS, IPv4 and IPv6 together
+type ProtocolStats = [ProtocolCounters; 3];
+
+// Last reading, rates are the difference to it
+static PROTOCOL_SAMPLE: Mutex<Option<(Instant, ProtocolStats)>> = Mutex::new(None);
+
+// "Tcp: InSegs OutSegs ..." followed by "Tcp: 1 2 ..."
+#[cfg(target_os = "linux")]
+fn snmp_table(text: &str) -> HashMap<String, u64> {
+    let mut values = HashMap::new();
+    let lines: Vec<&str> = text.lines().collect();
+    for pair in lines.chunks(2) {
+        let [names, numbers] = pair else { continue };
+        let (Some((prefix, names)), Some((_, numbers))) =
+            (names.split_once(':'), numbers.split_once(':'))
+        else {
+            continue;
+        };
+        for (name, number) in names.split_whitespace().zip(numbers.split_whitespace()) {
+            // MaxConn is -1 on Linux, nothing here needs it
+            if let Ok(number) = number.parse() {
+                values.insert(format!("{}{}", prefix, name), number);
+            }
+        }
+    }
+    values
+}
+
+#[cfg(target_os = "linux")]
+fn read_protocol_stats() -> Option<ProtocolStats> {
+    let mut values = snmp_table(&fs::read_to_string("/proc/net/snmp").ok()?);
+    // One "Udp6InDatagrams 206" per line, missing when IPv6 is disabled
+    if let Ok(snmp6) = fs::read_to_string("/proc/net/snmp6") {
+        values.extend(snmp6.lines().filter_map(|line| {
+            let (name, number) = line.split_once(char::is_whitespace)?;
+            Some((name.to_string(), number.trim().parse().ok()?))
+        }));
+    }
+    let sum = |names: &[&str]| names.iter().filter_map(|name| values.get(*name)).sum();
+
+    // The Tcp table already covers TCP over IPv6
+    Some([
+        ProtocolCounters {
+            received: sum(&["TcpInSegs"]),
+            sent: sum(&["TcpOutSegs"]),
+            errors: sum(&["TcpInErrs"]),
+        },
+        ProtocolCounters {
+            received: sum(&["UdpInDatagrams", "Udp6InDatagrams"]),
+            sent: sum(&["UdpOutDatagrams", "Udp6OutDatagrams"]),
+            errors: sum(&["UdpInErrors", "Udp6InErrors"]),
+        },
+        ProtocolCounters {
+            received: sum(&["IcmpInMsgs", "Icmp6InMsgs"]),
+            sent: sum(&["IcmpOutMsgs", "Icmp6OutMsgs"]),
+            errors: sum(&["IcmpInErrors", "Icmp6InErrors"]),
+        },
+    ])
+}
+
+// The MIB structures are all DWORDs, so they're read as plain u32 arrays:
+// MIB_TCPSTATS has InSegs, OutSegs and InErrs at 9, 10 and 12, MIB_UDPSTATS has InDatagrams,
+// InErrors and OutDatagrams at 0, 2 and 3, and MIB_ICMP_EX is two MIBICMPSTATS_EX of
+// dwMsgs, dwErrors and 256 per-type counts
+#[cfg(windows)]
+#[link(name = "iphlpapi")]
+unsafe extern "system" {
+    fn GetTcpStatisticsEx(stats: *mut u32, family: u32) -> u32;
+    fn GetUdpStatisticsEx(stats: *mut u32, family: u32) -> u32;
+    fn GetIcmpStatisticsEx(stats: *mut u32, family: u32) -> u32;
+}
+
+#[cfg(windows)]
+fn read_protocol_stats() -> Option<ProtocolStats> {
+    const AF_INET: u32 = 2;
+    const AF_INET6: u32 = 23;
+
+    let mut stats = ProtocolStats::default();
+    let mut any = false;
+    for family in [AF_INET, AF_INET6] {
+        let mut tcp = [0u32; 15];
+        let mut udp = [0u32; 5];
+        let mut icmp = [0u32; 2 * 258];
+        // SAFETY: each buffer is at least as large as the structure the call fills in
+        unsafe {
+            if GetTcpStatisticsEx(tcp.as_mut_ptr(), family) == 0 {
+                stats[0].received += tcp[9] as u64;
+                stats[0].sent += tcp[10] as u64;
+                stats[0].errors += tcp[12] as u64;
+                any = true;
+            }
+            if GetUdpStatisticsEx(udp.as_mut_ptr(), family) == 0 {
+                stats[1].received += udp[0] as u64;
+                stats[1].sent += udp[3] as u64;
+                stats[1].errors += udp[2] as u64;
+                any = true;
+            }
+            if GetIcmpStatisticsEx(icmp.as_mut_ptr(), family) == 0 {
+                stats[2].received += icmp[0] as u64;
+                stats[2].sent += icmp[258] as u64;
+                stats[2].errors += icmp[1] as u64;
+                any = true;
+            }
+        }
+    }
+    any.then_some(stats)
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn read_protocol_stats() -> Option<ProtocolStats> {
+    None
+}
+
+// Counters plus per-second rates since the previous call, rates are None on the first one.
+// Windows counters are 32 bits and wrap, a wrapped interval reports no rate
+fn protocol_stats() -> Option<(ProtocolStats, Option<[ProtocolRates; 3]>)> {
+    let stats = read_protocol_stats()?;
+    let now = Instant::now();
+    let mut sample = PROTOCOL_SAMPLE.lock().unwrap();
+    let rates = sample.as_ref().and_then(|(taken_at, previous)| {
+        let elapsed = now.duration_since(*taken_at).as_secs_f64();
+        if elapsed < 0.5 {
+            return None;
+        }
+        let mut rates = [ProtocolRates::default(); 3];
+        for (rate, (current, previous)) in rates.iter_mut().zip(stats.iter().zip(previous)) {
+            rate.received = current.received.checked_sub(previous.received)? as f64 / elapsed;
+            rate.sent = current.sent.checked_sub(previous.sent)? as f64 / elapsed;
+        }
+        Some(rates)
+    });
+    // Keep the older sample when the last one is too recent, so frequent callers still get rates
+    if rates.is_some() || sample.is_none() {
+        *sample = Some((now, stats));
+    }
+    Some((stats, rates))
+}
+
+pub fn protocol_metrics() -> Vec<Metric> {
+    let Some((stats, rates)) = protocol_stats() else {
+        return Vec::new();
+    };
+    let mut metrics = Vec::new();
+    for (protocol, counters) in PROTOCOLS.iter().zip(stats) {
+        metrics.push(
+            Metric::new("network_packets_total", counters.received as f64)
+                .label("protocol", protocol)
+                .label("direction", "received"),
+        );
+        metrics.push(
+            Metric::new("network_packets_total", counters.sent as f64)
+                .label("protocol", protocol)
+                .label("direction", "sent"),
+        );
+        metrics.push(
+            Metric::new("network_packet_errors_total", counters.errors as f64)
+                .label("protocol", protocol),
+        );
+    }
+    for (protocol, rate) in PROTOCOLS.iter().zip(rates.into_iter().flatten()) {
+        metrics.push(
+            Metric::new("network_packets_per_second", rate.received)
+                .label("protocol", protocol)
+                .label("direction", "received"),
+        );
+        metrics.push(
+            Metric::new("network_packets_per_second", rate.sent)
+                .label("protocol", protocol)
+                .label("direction", "sent"),
+        );
+    }
+    metrics
+}
+
+// Packets per second in both directions, for the dashboard charts
+pub fn protocol_packet_rates() -> Vec<(String, f64)> {
+    protocol_stats()
+        .and_then(|(_, rates)| rates)
+        .map(|rates| {
+            PROTOCOLS
+                .iter()
+                .zip(rates)
+                .map(|(protocol, rate)| {
+                    (
+                        format!("{}_packets_per_second", protocol),
+                        rate.received + rate.sent,
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
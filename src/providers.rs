@@ -0,0 +1,89 @@
+// Providers module for Crusty-Crawler
+// The system, the clock and the mail server behind traits, so checks, AuthManager and the
+// handlers can run against fixed values in tests
+
+pub trait SystemInfoProvider: Send + Sync {
+    fn system_name(&self) -> Option<String>;
+    // Usage since the previous call, the first call only starts the measurement
+    fn cpu_usage(&self) -> f32;
+    // (used, total) in bytes
+    fn memory(&self) -> (u64, u64);
+}
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+    fn utc_now(&self) -> chrono::DateTime<chrono::Utc>;
+}
+
+pub trait EmailSender: Send + Sync {
+    fn send(&self, config: &SmtpConfig, to: &str, subject: &str, body: &str) -> Result<(), String>;
+}
+
+// What the checks and the status page read from: a replay or the demo when one is running,
+// otherwise the host
+pub fn system_provider() -> Arc<dyn SystemInfoProvider> {
+    if let Some(replay) = metric_replay() {
+        return replay;
+    }
+    if let Some(demo) = demo_system() {
+        return demo;
+    }
+    Arc::new(SysinfoProvider::new())
+}
+
+// One System kept between calls, sysinfo measures CPU usage between two refreshes
+pub struct SysinfoProvider {
+    sys: Mutex<sysinfo::System>,
+}
+
+impl SysinfoProvider {
+    pub fn new() -> Self {
+        Self {
+            sys: Mutex::new(sysinfo::System::new()),
+        }
+    }
+}
+
+impl Default for SysinfoProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SystemInfoProvider for SysinfoProvider {
+    fn system_name(&self) -> Option<String> {
+        sysinfo::System::name()
+    }
+
+    fn cpu_usage(&self) -> f32 {
+        let mut sys = self.sys.lock().unwrap();
+        sys.refresh_cpu_usage();
+        sys.global_cpu_usage()
+    }
+
+    fn memory(&self) -> (u64, u64) {
+        let mut sys = self.sys.lock().unwrap();
+        sys.refresh_memory();
+        (sys.used_memory(), sys.total_memory())
+    }
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn utc_now(&self) -> chrono::DateTime<chrono::Utc> {
+        chrono::Utc::now()
+    }
+}
+
+pub struct SmtpSender;
+
+impl EmailSender for SmtpSender {
+    fn send(&self, config: &SmtpConfig, to: &str, subject: &str, body: &str) -> Result<(), String> {
+        send_email(config, to, subject, body)
+    }
+}
@@ -0,0 +1,414 @@
+// Push module for Crusty-Crawler
+// Web Push notifications for the installable dashboard: VAPID key management, browser
+// subscriptions and the encrypted (aes128gcm) push messages sent for alerts
+
+use base64::Engine as _;
+use p256::ecdsa::signature::Signer as _;
+use p256::elliptic_curve::sec1::ToEncodedPoint as _;
+
+// Holds the VAPID private key, so it is only readable by the agent's user
+const PUSH_FILE: &str = "crusty_push.json";
+const PUSH_RECORD_SIZE: u32 = 4096;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PushConfig {
+    pub enabled: bool,
+    // Contact for push services that need to reach whoever runs this agent
+    pub subject: String,
+    // Alert states that send a push, include OK for recoveries
+    pub severities: Vec<CheckState>,
+    // How long push services keep trying to deliver to an offline phone
+    pub ttl_secs: u64,
+}
+
+impl Default for PushConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            subject: "mailto:admin@localhost".to_string(),
+            severities: vec![CheckState::Critical],
+            ttl_secs: 86400,
+        }
+    }
+}
+
+// What the browser's PushSubscription.toJSON() returns
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PushSubscriptionKeys {
+    pub p256dh: String,
+    pub auth: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PushSubscription {
+    pub endpoint: String,
+    pub keys: PushSubscriptionKeys,
+}
+
+#[derive(Deserialize)]
+pub struct PushUnsubscribe {
+    pub endpoint: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct StoredPushSubscription {
+    username: String,
+    created_at: String,
+    #[serde(flatten)]
+    subscription: PushSubscription,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct PushStore {
+    // Base64url P-256 private key
+    vapid_private_key: String,
+    subscriptions: Vec<StoredPushSubscription>,
+}
+
+enum PushOutcome {
+    Delivered,
+    // The browser unsubscribed, the subscription can be dropped
+    Expired,
+}
+
+// Serialises read-modify-write of PUSH_FILE, which is re-read every time so a key rotated
+// from the command line takes effect in a running agent
+static PUSH_FILE_LOCK: Mutex<()> = Mutex::new(());
+
+fn base64url(data: &[u8]) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(data)
+}
+
+fn base64url_decode(value: &str) -> Result<Vec<u8>, String> {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|e| e.to_string())
+}
+
+fn random_secret_key() -> p256::SecretKey {
+    loop {
+        let bytes: [u8; 32] = rand::random();
+        // Fails for zero or values above the curve order, which practically never happens
+        if let Ok(key) = p256::SecretKey::from_slice(&bytes) {
+            return key;
+        }
+    }
+}
+
+fn public_key_bytes(key: &p256::SecretKey) -> Vec<u8> {
+    key.public_key().to_encoded_point(false).as_bytes().to_vec()
+}
+
+fn save_push_store(store: &PushStore) -> Result<(), String> {
+    let data = serde_json::to_string_pretty(store).map_err(|e| e.to_string())?;
+    fs::write(data_path(PUSH_FILE), data)
+        .map_err(|e| format!("Failed to write {}: {}", PUSH_FILE, e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(data_path(PUSH_FILE), fs::Permissions::from_mode(0o600))
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+// Creates the VAPID key on first use
+fn with_push_store<T>(f: impl FnOnce(&mut PushStore) -> T) -> Result<T, String> {
+    let _guard = PUSH_FILE_LOCK.lock().unwrap();
+    let mut store: PushStore = match fs::read_to_string(data_path(PUSH_FILE)) {
+        Ok(data) => serde_json::from_str(&data).map_err(|e| format!("{}: {}", PUSH_FILE, e))?,
+        Err(_) => PushStore::default(),
+    };
+    if store.vapid_private_key.is_empty() {
+        store.vapid_private_key = base64url(&random_secret_key().to_bytes());
+    }
+    let result = f(&mut store);
+    save_push_store(&store)?;
+    Ok(result)
+}
+
+fn vapid_key(store: &PushStore) -> Result<p256::SecretKey, String> {
+    p256::SecretKey::from_slice(&base64url_decode(&store.vapid_private_key)?)
+        .map_err(|_| format!("Invalid VAPID key in {}", PUSH_FILE))
+}
+
+// applicationServerKey for PushManager.subscribe()
+pub fn vapid_public_key() -> Result<String, String> {
+    with_push_store(|store| vapid_key(store).map(|key| base64url(&public_key_bytes(&key))))?
+}
+
+pub fn add_push_subscription(username: &str, subscription: PushSubscription) -> Result<(), String> {
+    reqwest::Url::parse(&subscription.endpoint)
+        .ok()
+        .filter(|url| url.scheme() == "https")
+        .ok_or("Push endpoint must be an https URL")?;
+    let public_key = base64url_decode(&subscription.keys.p256dh)?;
+    p256::PublicKey::from_sec1_bytes(&public_key).map_err(|_| "Invalid p256dh key")?;
+    if base64url_decode(&subscription.keys.auth)?.len() != 16 {
+        return Err("Invalid auth secret".to_string());
+    }
+
+    with_push_store(|store| {
+        // Browsers re-subscribe with the same endpoint, keep one entry per endpoint
+        store
+            .subscriptions
+            .retain(|s| s.subscription.endpoint != subscription.endpoint);
+        store.subscriptions.push(StoredPushSubscription {
+            username: username.to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            subscription,
+        });
+    })
+}
+
+pub fn remove_push_subscription(username: &str, endpoint: &str) -> Result<bool, String> {
+    with_push_store(|store| {
+        let before = store.subscriptions.len();
+        store
+            .subscriptions
+            .retain(|s| !(s.username == username && s.subscription.endpoint == endpoint));
+        store.subscriptions.len() != before
+    })
+}
+
+// RFC 8291 message encryption, a single aes128gcm record
+fn encrypt_push_payload(
+    subscription: &PushSubscription,
+    payload: &[u8],
+) -> Result<Vec<u8>, String> {
+    use aes_gcm::aead::{Aead, KeyInit};
+
+    let ua_public_bytes = base64url_decode(&subscription.keys.p256dh)?;
+    let ua_public =
+        p256::PublicKey::from_sec1_bytes(&ua_public_bytes).map_err(|_| "Invalid p256dh key")?;
+    let auth_secret = base64url_decode(&subscription.keys.auth)?;
+
+    let as_secret = random_secret_key();
+    let as_public_bytes = public_key_bytes(&as_secret);
+    let shared = p256::ecdh::diffie_hellman(as_secret.to_nonzero_scalar(), ua_public.as_affine());
+
+    let mut key_info = b"WebPush: info\0".to_vec();
+    key_info.extend_from_slice(&ua_public_bytes);
+    key_info.extend_from_slice(&as_public_bytes);
+    let mut ikm = [0u8; 32];
+    hkdf::Hkdf::<sha2::Sha256>::new(Some(&auth_secret), shared.raw_secret_bytes())
+        .expand(&key_info, &mut ikm)
+        .map_err(|e| e.to_string())?;
+
+    let salt: [u8; 16] = rand::random();
+    let hkdf = hkdf::Hkdf::<sha2::Sha256>::new(Some(&salt), &ikm);
+    let mut content_key = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    hkdf.expand(b"Content-Encoding: aes128gcm\0", &mut content_key)
+        .and_then(|_| hkdf.expand(b"Content-Encoding: nonce\0", &mut nonce))
+        .map_err(|e| e.to_string())?;
+
+    // 0x02 marks the last (and only) record
+    let mut plaintext = payload.to_vec();
+    plaintext.push(2);
+    let ciphertext = aes_gcm::Aes128Gcm::new_from_slice(&content_key)
+        .map_err(|e| e.to_string())?
+        .encrypt(aes_gcm::Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|e| e.to_string())?;
+
+    let mut body = salt.to_vec();
+    body.extend_from_slice(&PUSH_RECORD_SIZE.to_be_bytes());
+    body.push(as_public_bytes.len() as u8);
+    body.extend_from_slice(&as_public_bytes);
+    body.extend_from_slice(&ciphertext);
+    Ok(body)
+}
+
+// RFC 8292 VAPID header, an ES256 JWT for the push service's origin
+fn vapid_authorization(
+    key: &p256::SecretKey,
+    endpoint: &str,
+    subject: &str,
+) -> Result<String, String> {
+    let audience = reqwest::Url::parse(endpoint)
+        .map_err(|e| e.to_string())?
+        .origin()
+        .ascii_serialization();
+    let header = base64url(br#"{"typ":"JWT","alg":"ES256"}"#);
+    let claims = base64url(
+        serde_json::json!({
+            "aud": audience,
+            "exp": chrono::Utc::now().timestamp() + 12 * 3600,
+            "sub": subject,
+        })
+        .to_string()
+        .as_bytes(),
+    );
+    let signing_input = format!("{}.{}", header, claims);
+    let signature: p256::ecdsa::Signature =
+        p256::ecdsa::SigningKey::from(key).sign(signing_input.as_bytes());
+    Ok(format!(
+        "vapid t={}.{}, k={}",
+        signing_input,
+        base64url(&signature.to_bytes()),
+        base64url(&public_key_bytes(key))
+    ))
+}
+
+async fn send_web_push(
+    client: &reqwest::Client,
+    key: &p256::SecretKey,
+    config: &PushConfig,
+    subscription: &PushSubscription,
+    payload: &[u8],
+    urgent: bool,
+) -> Result<PushOutcome, String> {
+    let response = client
+        .post(&subscription.endpoint)
+        .header(
+            "Authorization",
+            vapid_authorization(key, &subscription.endpoint, &config.subject)?,
+        )
+        .header("Content-Encoding", "aes128gcm")
+        .header("Content-Type", "application/octet-stream")
+        .header("TTL", config.ttl_secs.to_string())
+        .header("Urgency", if urgent { "high" } else { "normal" })
+        .body(encrypt_push_payload(subscription, payload)?)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    match response.status().as_u16() {
+        200..=299 => Ok(PushOutcome::Delivered),
+        404 | 410 => Ok(PushOutcome::Expired),
+        status => Err(format!(
+            "HTTP {}: {}",
+            status,
+            response.text().await.unwrap_or_default()
+        )),
+    }
+}
+
+// Sends to every subscription, or only the user's when given, and drops expired ones.
+// Returns how many were delivered
+async fn send_push(
+    config: &PushConfig,
+    username: Option<&str>,
+    title: &str,
+    body: &str,
+    state: CheckState,
+    tag: &str,
+) -> Result<usize, String> {
+    let (key, subscriptions) =
+        with_push_store(|store| vapid_key(store).map(|key| (key, store.subscriptions.clone())))??;
+    let payload = serde_json::json!({
+        "title": title,
+        "body": body,
+        "state": state.label(),
+        "tag": tag,
+    })
+    .to_string();
+
+    let client = reqwest::Client::new();
+    let mut delivered = 0;
+    let mut expired = Vec::new();
+    for stored in subscriptions
+        .iter()
+        .filter(|s| username.is_none_or(|username| s.username == username))
+    {
+        match send_web_push(
+            &client,
+            &key,
+            config,
+            &stored.subscription,
+            payload.as_bytes(),
+            state == CheckState::Critical,
+        )
+        .await
+        {
+            Ok(PushOutcome::Delivered) => delivered += 1,
+            Ok(PushOutcome::Expired) => expired.push(stored.subscription.endpoint.clone()),
+            Err(e) => eprintln!("❌ Web push to {} failed: {}", stored.username, e),
+        }
+    }
+
+    if !expired.is_empty() {
+        with_push_store(|store| {
+            store
+                .subscriptions
+                .retain(|s| !expired.contains(&s.subscription.endpoint))
+        })?;
+    }
+    Ok(delivered)
+}
+
+pub async fn dispatch_push(config: PushConfig, alert: Alert) {
+    if !config.enabled || !config.severities.contains(&alert.state) {
+        return;
+    }
+    if let Err(e) = send_push(
+        &config,
+        None,
+        &alert_subject(&alert),
+        &alert.message,
+        alert.state,
+        &alert.check,
+    )
+    .await
+    {
+        eprintln!("❌ Web push failed: {}", e);
+    }
+}
+
+pub async fn send_test_push(config: &PushConfig, username: &str) -> Result<usize, String> {
+    send_push(
+        config,
+        Some(username),
+        "[Crusty] Test notification",
+        "Alert notifications are working on this device",
+        CheckState::Ok,
+        "test",
+    )
+    .await
+}
+
+// Square PWA icons cut from the application icon
+#[cfg(feature = "gui")]
+fn pwa_icon(size: u32) -> Result<Vec<u8>, String> {
+    let icon =
+        image::load_from_memory(include_bytes!("../Assets/icon.png")).map_err(|e| e.to_string())?;
+    let icon = icon.resize(size, size, image::imageops::FilterType::Lanczos3);
+    let mut canvas = image::RgbaImage::from_pixel(size, size, image::Rgba([30, 30, 30, 255]));
+    image::imageops::overlay(
+        &mut canvas,
+        &icon,
+        ((size - icon.width()) / 2) as i64,
+        ((size - icon.height()) / 2) as i64,
+    );
+    let mut png = Vec::new();
+    canvas
+        .write_to(&mut io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok(png)
+}
+
+// `image` comes with the gui feature, headless builds let the browser scale the icon
+#[cfg(not(feature = "gui"))]
+fn pwa_icon(_size: u32) -> Result<Vec<u8>, String> {
+    Ok(include_bytes!("../Assets/icon.png").to_vec())
+}
+
+// `push-keys` prints the VAPID public key, `push-keys --rotate` replaces the key pair.
+// Browsers subscribed with the old key can't receive pushes any more, so rotating also
+// drops every subscription
+pub fn run_push_keys(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    if args.iter().any(|arg| arg == "--rotate") {
+        let dropped = with_push_store(|store| {
+            store.vapid_private_key = base64url(&random_secret_key().to_bytes());
+            std::mem::take(&mut store.subscriptions).len()
+        })?;
+        println!(
+            "🔑 Generated a new VAPID key pair, {} subscription(s) removed",
+            dropped
+        );
+        println!("   Users need to enable notifications on the dashboard again");
+    }
+    println!("VAPID public key: {}", vapid_public_key()?);
+    Ok(())
+}
@@ -0,0 +1,96 @@
+// QR module for Crusty-Crawler
+// Shows a QR code of the dashboard URL with a short-lived login link, so a phone can open the
+// dashboard by scanning instead of typing the address and access token
+
+const QR_LINK_LIFETIME: Duration = Duration::from_secs(300);
+
+pub struct QrLink {
+    url: String,
+    texture: egui::TextureHandle,
+    expires_at: Instant,
+}
+
+// Four modules of white quiet zone around the code, which scanners need
+fn qr_image(data: &str) -> Result<egui::ColorImage, String> {
+    const SCALE: usize = 6;
+    const BORDER: usize = 4;
+
+    let code = qrcode::QrCode::new(data.as_bytes()).map_err(|e| e.to_string())?;
+    let modules = code.width();
+    let colors = code.to_colors();
+    let size = (modules + BORDER * 2) * SCALE;
+    let mut image = egui::ColorImage::new([size, size], vec![egui::Color32::WHITE; size * size]);
+    for (index, color) in colors.iter().enumerate() {
+        if *color != qrcode::Color::Dark {
+            continue;
+        }
+        let (x, y) = (index % modules + BORDER, index / modules + BORDER);
+        for dy in 0..SCALE {
+            for dx in 0..SCALE {
+                image[(x * SCALE + dx, y * SCALE + dy)] = egui::Color32::BLACK;
+            }
+        }
+    }
+    Ok(image)
+}
+
+impl MainState {
+    fn create_qr_link(&mut self, ctx: &egui::Context, port: u16) -> Result<(), String> {
+        let address = lan_addresses()
+            .into_iter()
+            .next()
+            .ok_or("No network address found, connect to a network first")?;
+        let token = {
+            let state = self.server_state.lock().unwrap();
+            let auth_manager = state.auth_manager.lock().unwrap();
+            auth_manager.create_login_link(&self.current_user, QR_LINK_LIFETIME)
+        };
+        let url = format!("{}?token={}", address.url(port), token);
+        let texture = ctx.load_texture(
+            "dashboard_qr",
+            qr_image(&url)?,
+            egui::TextureOptions::NEAREST,
+        );
+        self.qr_link = Some(QrLink {
+            url,
+            texture,
+            expires_at: Instant::now() + QR_LINK_LIFETIME,
+        });
+        Ok(())
+    }
+
+    fn show_qr_code(&mut self, ui: &mut egui::Ui, port: u16) {
+        if self
+            .qr_link
+            .as_ref()
+            .is_some_and(|link| link.expires_at <= Instant::now())
+        {
+            self.qr_link = None;
+        }
+
+        match &self.qr_link {
+            None => {
+                if ui.button("📱 Show QR code for phone access").clicked()
+                    && let Err(e) = self.create_qr_link(ui.ctx(), port)
+                {
+                    self.status_message = format!("Could not create QR code: {}", e);
+                }
+            }
+            Some(link) => {
+                let remaining = link.expires_at.saturating_duration_since(Instant::now());
+                ui.image((link.texture.id(), link.texture.size_vec2()));
+                ui.label(format!(
+                    "Scan to open the dashboard, the code works once and expires in {}:{:02}",
+                    remaining.as_secs() / 60,
+                    remaining.as_secs() % 60
+                ));
+                ui.small(&link.url);
+                if ui.button("Hide QR code").clicked() {
+                    self.qr_link = None;
+                }
+                // Keep the countdown moving
+                ui.ctx().request_repaint_after(Duration::from_secs(1));
+            }
+        }
+    }
+}
@@ -0,0 +1,272 @@
+// RAID module for Crusty-Crawler
+// Health of Linux software RAID (mdadm) arrays and, when storcli is installed,
+// MegaRAID/PERC hardware controller virtual drives
+
+#[derive(Serialize, Clone)]
+pub struct RaidArray {
+    pub name: String,
+    // "mdadm" or "storcli"
+    pub source: String,
+    pub level: String,
+    pub state: String,
+    pub devices_total: Option<u32>,
+    pub devices_active: Option<u32>,
+    pub failed_devices: Vec<String>,
+    pub degraded: bool,
+    // resync, recovery, reshape, check or rebuild
+    pub sync_action: Option<String>,
+    pub sync_progress: Option<f64>,
+}
+
+impl RaidArray {
+    pub fn check_state(&self) -> CheckState {
+        if self.degraded || !self.failed_devices.is_empty() || self.state == "inactive" {
+            CheckState::Critical
+        } else {
+            CheckState::Ok
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{} ({}) {}", self.name, self.level, self.state);
+        if let (Some(active), Some(total)) = (self.devices_active, self.devices_total) {
+            summary.push_str(&format!(", {}/{} devices", active, total));
+        }
+        if self.degraded {
+            summary.push_str(", DEGRADED");
+        }
+        if !self.failed_devices.is_empty() {
+            summary.push_str(&format!(", failed: {}", self.failed_devices.join(" ")));
+        }
+        if let Some(action) = &self.sync_action {
+            match self.sync_progress {
+                Some(progress) => summary.push_str(&format!(", {} {:.1}%", action, progress)),
+                None => summary.push_str(&format!(", {} pending", action)),
+            }
+        }
+        summary
+    }
+}
+
+// Parses /proc/mdstat, where each array is a header line followed by indented detail lines
+pub fn parse_mdstat(mdstat: &str) -> Vec<RaidArray> {
+    let mut arrays: Vec<RaidArray> = Vec::new();
+
+    for line in mdstat.lines() {
+        if line.starts_with("md") {
+            // "md0 : active raid1 sdb1[1] sda1[0](F)"
+            let Some((name, rest)) = line.split_once(" : ") else {
+                continue;
+            };
+            let mut words = rest.split_whitespace().peekable();
+            let state = words.next().unwrap_or_default().to_string();
+            // Arrays can be flagged "(auto-read-only)" after the state
+            if words.peek().is_some_and(|w| w.starts_with('(')) {
+                words.next();
+            }
+            let level = match words.peek() {
+                Some(word) if !word.contains('[') => words.next().unwrap_or_default().to_string(),
+                _ => "unknown".to_string(),
+            };
+            let failed_devices = words
+                .filter(|device| device.ends_with("(F)"))
+                .map(|device| device.split('[').next().unwrap_or(device).to_string())
+                .collect();
+
+            arrays.push(RaidArray {
+                name: name.trim().to_string(),
+                source: "mdadm".to_string(),
+                level,
+                state,
+                devices_total: None,
+                devices_active: None,
+                failed_devices,
+                degraded: false,
+                sync_action: None,
+                sync_progress: None,
+            });
+            continue;
+        }
+
+        let Some(array) = arrays.last_mut() else {
+            continue;
+        };
+        let detail = line.trim();
+
+        // "976630336 blocks super 1.2 [2/1] [U_]"
+        if let Some(start) = detail.rfind("] [")
+            && let Some(counts_start) = detail[..start].rfind('[')
+        {
+            let counts = &detail[counts_start + 1..start];
+            if let Some((total, active)) = counts.split_once('/') {
+                array.devices_total = total.parse().ok();
+                array.devices_active = active.parse().ok();
+            }
+            let map = &detail[start + 3..];
+            array.degraded = map.contains('_');
+        }
+
+        // "[==>..........]  recovery = 12.6% (123/456) finish=..." or "resync=DELAYED"
+        for action in ["recovery", "resync", "reshape", "check"] {
+            if let Some(position) = detail.find(action)
+                && detail[position + action.len()..]
+                    .trim_start()
+                    .starts_with('=')
+            {
+                array.sync_action = Some(action.to_string());
+                array.sync_progress = detail[position + action.len()..]
+                    .trim_start_matches([' ', '='])
+                    .split('%')
+                    .next()
+                    .and_then(|p| p.trim().parse().ok());
+                break;
+            }
+        }
+    }
+
+    arrays
+}
+
+#[cfg(target_os = "linux")]
+fn mdadm_arrays() -> Vec<RaidArray> {
+    fs::read_to_string("/proc/mdstat")
+        .map(|mdstat| parse_mdstat(&mdstat))
+        .unwrap_or_default()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn mdadm_arrays() -> Vec<RaidArray> {
+    Vec::new()
+}
+
+// Virtual drives from `storcli /call/vall show J`, silently empty when storcli isn't installed
+fn storcli_arrays() -> Vec<RaidArray> {
+    let output = ["storcli64", "storcli", "perccli64", "perccli"]
+        .iter()
+        .find_map(|tool| {
+            std::process::Command::new(tool)
+                .args(["/call/vall", "show", "J"])
+                .output()
+                .ok()
+        });
+    let Some(output) = output else {
+        return Vec::new();
+    };
+    let Ok(json) = serde_json::from_slice::<serde_json::Value>(&output.stdout) else {
+        return Vec::new();
+    };
+
+    let mut arrays = Vec::new();
+    for controller in json["Controllers"].as_array().into_iter().flatten() {
+        let id = controller["Command Status"]["Controller"].to_string();
+        let drives = controller["Response Data"]["Virtual Drives"].as_array();
+        for drive in drives.into_iter().flatten() {
+            // Optl, Dgrd (degraded), Pdgd (partially degraded), OfLn (offline), Rec (recovering)
+            let state = drive["State"].as_str().unwrap_or("unknown").to_string();
+            arrays.push(RaidArray {
+                name: format!(
+                    "c{}/v{}",
+                    id.trim_matches('"'),
+                    drive["DG/VD"].as_str().unwrap_or("?")
+                ),
+                source: "storcli".to_string(),
+                level: drive["TYPE"].as_str().unwrap_or("unknown").to_lowercase(),
+                degraded: state != "Optl",
+                sync_action: (state == "Rec").then(|| "rebuild".to_string()),
+                state,
+                devices_total: None,
+                devices_active: None,
+                failed_devices: Vec::new(),
+                sync_progress: None,
+            });
+        }
+    }
+    arrays
+}
+
+pub fn read_raid_arrays() -> Vec<RaidArray> {
+    let mut arrays = mdadm_arrays();
+    arrays.extend(storcli_arrays());
+    arrays
+}
+
+pub fn raid_metrics(arrays: &[RaidArray]) -> Vec<Metric> {
+    let mut metrics = Vec::new();
+    for array in arrays {
+        metrics.push(
+            Metric::new(
+                "raid_array_degraded",
+                if array.degraded { 1.0 } else { 0.0 },
+            )
+            .label("array", &array.name)
+            .label("level", &array.level),
+        );
+        if let Some(active) = array.devices_active {
+            metrics.push(
+                Metric::new("raid_devices_active", active as f64).label("array", &array.name),
+            );
+        }
+        if let Some(total) = array.devices_total {
+            metrics
+                .push(Metric::new("raid_devices_total", total as f64).label("array", &array.name));
+        }
+        if let Some(progress) = array.sync_progress {
+            metrics.push(
+                Metric::new("raid_sync_progress_percent", progress)
+                    .label("array", &array.name)
+                    .label("action", array.sync_action.as_deref().unwrap_or("sync")),
+            );
+        }
+    }
+    metrics
+}
+
+pub fn raid_status(arrays: &[RaidArray]) -> String {
+    let mut output = String::from("\n=== RAID Arrays ===\n");
+    for array in arrays {
+        let marker = if array.check_state() == CheckState::Ok {
+            ""
+        } else {
+            "🚨 "
+        };
+        output.push_str(&format!("{}{}\n", marker, array.summary()));
+    }
+    output
+}
+
+pub fn check_raid() -> CheckResult {
+    let arrays = read_raid_arrays();
+    if arrays.is_empty() {
+        return CheckResult::new(
+            "raid",
+            CheckState::Unknown,
+            "No RAID arrays found".to_string(),
+            Vec::new(),
+        );
+    }
+
+    let state = arrays
+        .iter()
+        .map(|a| a.check_state())
+        .max()
+        .unwrap_or(CheckState::Ok);
+    let output = arrays
+        .iter()
+        .map(|a| a.summary())
+        .collect::<Vec<_>>()
+        .join("; ");
+    let degraded = arrays
+        .iter()
+        .filter(|a| a.check_state() != CheckState::Ok)
+        .count();
+
+    CheckResult::new(
+        "raid",
+        state,
+        output,
+        vec![
+            PerfData::new("arrays", arrays.len() as f64, ""),
+            PerfData::new("degraded", degraded as f64, "").range(0.0, arrays.len() as f64),
+        ],
+    )
+}
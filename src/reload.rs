@@ -0,0 +1,281 @@
+// Reload module for Crusty-Crawler
+// Validates and hot-reloads crusty_auth.json when it changes on disk, logging what changed
+
+const CONFIG_WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+impl AuthConfig {
+    // Catches mistakes serde can't, so a bad edit never replaces a working config
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, thresholds) in [
+            ("cpu", &self.checks.cpu),
+            ("memory", &self.checks.memory),
+            ("disk", &self.checks.disk),
+            ("kernel_limits", &self.checks.kernel_limits),
+        ] {
+            if !(0.0..=100.0).contains(&thresholds.warning)
+                || !(0.0..=100.0).contains(&thresholds.critical)
+            {
+                return Err(format!(
+                    "checks.{} thresholds must be between 0 and 100",
+                    name
+                ));
+            }
+            if thresholds.warning > thresholds.critical {
+                return Err(format!(
+                    "checks.{} warning ({}) is above critical ({})",
+                    name, thresholds.warning, thresholds.critical
+                ));
+            }
+        }
+        let wifi = &self.checks.wifi_signal;
+        if !(-120.0..=0.0).contains(&wifi.warning) || !(-120.0..=0.0).contains(&wifi.critical) {
+            return Err("checks.wifi_signal thresholds must be between -120 and 0 dBm".to_string());
+        }
+        if wifi.warning < wifi.critical {
+            return Err(format!(
+                "checks.wifi_signal warning ({}) is below critical ({})",
+                wifi.warning, wifi.critical
+            ));
+        }
+
+        let entropy = &self.checks.entropy;
+        if entropy.warning < entropy.critical {
+            return Err(format!(
+                "checks.entropy warning ({}) is below critical ({})",
+                entropy.warning, entropy.critical
+            ));
+        }
+
+        let speed_test = &self.speed_test;
+        if speed_test.enabled {
+            speed_test.target()?;
+            if speed_test.interval_secs < 300 {
+                return Err("speed_test.interval_secs must be at least 300".to_string());
+            }
+        }
+        if !(1..=60).contains(&speed_test.duration_secs) {
+            return Err("speed_test.duration_secs must be between 1 and 60".to_string());
+        }
+
+        let schedules = [
+            ("speed_test.schedule", &self.speed_test.schedule),
+            ("checks.fim.schedule", &self.checks.fim.schedule),
+            (
+                "privacy.retention_schedule",
+                &self.privacy.retention_schedule,
+            ),
+        ];
+        for (field, schedule) in schedules {
+            if let Some(schedule) = schedule {
+                CronSchedule::parse(schedule).map_err(|e| format!("{}: {}", field, e))?;
+            }
+        }
+        for (index, collector) in self.metrics.collectors.iter().enumerate() {
+            if let Some(schedule) = &collector.schedule {
+                CronSchedule::parse(schedule)
+                    .map_err(|e| format!("metrics.collectors[{}].schedule: {}", index, e))?;
+            }
+        }
+
+        let fim = &self.checks.fim;
+        if fim.interval_secs < 60 {
+            return Err("checks.fim.interval_secs must be at least 60".to_string());
+        }
+        if fim.max_files == 0 {
+            return Err("checks.fim.max_files must be above 0".to_string());
+        }
+        let crashes = &self.checks.crashes;
+        if crashes.interval_secs < 10 {
+            return Err("checks.crashes.interval_secs must be at least 10".to_string());
+        }
+        if crashes.alert_minutes == 0 {
+            return Err("checks.crashes.alert_minutes must be above 0".to_string());
+        }
+        self.checks.kernel_log.validate()?;
+        self.checks.peripherals.validate()?;
+
+        self.checks.grouping.validate()?;
+        validate_dependencies(&self.checks.dependencies)?;
+        self.checks.health_score.validate()?;
+        self.metrics.processes.validate()?;
+        self.metrics.adaptive.validate()?;
+        self.metrics.power.validate()?;
+        self.metrics.devices.validate()?;
+        validate_derived_metrics(&self.metrics.derived)?;
+
+        if self.http.tls_cert.is_some() != self.http.tls_key.is_some() {
+            return Err("http.tls_cert and http.tls_key must be set together".to_string());
+        }
+
+        let mut names: Vec<&str> = BUILTIN_CHECKS.to_vec();
+        let custom = self
+            .checks
+            .databases
+            .iter()
+            .map(|db| db.name.as_str())
+            .chain(self.checks.http.iter().map(|http| http.name.as_str()))
+            .chain(
+                self.metrics
+                    .derived
+                    .iter()
+                    .filter(|derived| derived.thresholds.is_some())
+                    .map(|derived| derived.name.as_str()),
+            );
+        for name in custom {
+            if name.is_empty() {
+                return Err("checks: every database and HTTP check needs a name".to_string());
+            }
+            if names.contains(&name) {
+                return Err(format!("checks: duplicate check name '{}'", name));
+            }
+            names.push(name);
+        }
+
+        for http in &self.checks.http {
+            reqwest::Url::parse(&http.url)
+                .map_err(|e| format!("checks.http '{}': invalid URL: {}", http.name, e))?;
+        }
+
+        for contact in &self.contacts {
+            if contact.name.is_empty() {
+                return Err("contacts: every contact needs a name".to_string());
+            }
+            for channel in &contact.channels {
+                if let NotificationChannel::Webhook { url } = channel {
+                    reqwest::Url::parse(url).map_err(|e| {
+                        format!("contacts '{}': invalid webhook URL: {}", contact.name, e)
+                    })?;
+                }
+            }
+        }
+        self.on_call.validate(&self.contacts)?;
+        self.notification_templates.validate()?;
+        self.notification_throttle.validate()?;
+        self.reporter.validate()?;
+        self.manager.validate()?;
+        self.manager.validate_alert_routes(&self.contacts)?;
+
+        self.dashboard
+            .default_layout
+            .validate()
+            .map_err(|e| format!("dashboard.default_layout: {}", e))?;
+        Ok(())
+    }
+}
+
+// Values under these keys never show up in the log
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    ["password", "token", "secret", "hash", "headers"]
+        .iter()
+        .any(|word| key.contains(word))
+}
+
+fn collect_config_changes(
+    path: &str,
+    old: &serde_json::Value,
+    new: &serde_json::Value,
+    secret: bool,
+    changes: &mut Vec<String>,
+) {
+    use serde_json::Value;
+
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            let mut keys: Vec<&String> = old_map.keys().chain(new_map.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let child = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                collect_config_changes(
+                    &child,
+                    old_map.get(key).unwrap_or(&Value::Null),
+                    new_map.get(key).unwrap_or(&Value::Null),
+                    secret || is_secret_key(key),
+                    changes,
+                );
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items)) => {
+            for index in 0..old_items.len().max(new_items.len()) {
+                collect_config_changes(
+                    &format!("{}[{}]", path, index),
+                    old_items.get(index).unwrap_or(&Value::Null),
+                    new_items.get(index).unwrap_or(&Value::Null),
+                    secret,
+                    changes,
+                );
+            }
+        }
+        _ if old == new => {}
+        _ if secret => changes.push(format!("{} changed", path)),
+        _ => changes.push(format!("{}: {} -> {}", path, old, new)),
+    }
+}
+
+// Human readable list of changed settings, e.g. "checks.cpu.warning: 80.0 -> 85.0"
+pub fn config_diff(old: &AuthConfig, new: &AuthConfig) -> Vec<String> {
+    let (Ok(old), Ok(new)) = (serde_json::to_value(old), serde_json::to_value(new)) else {
+        return Vec::new();
+    };
+    let mut changes = Vec::new();
+    collect_config_changes("", &old, &new, false, &mut changes);
+    changes
+}
+
+// Watches the config's directory rather than the file, editors often save by replacing it
+fn spawn_config_watcher(server_state: Arc<Mutex<ServerState>>) {
+    use notify::Watcher;
+
+    let config_path = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        Path::new(auth_manager.config_path()).to_path_buf()
+    };
+    let Some(file_name) = config_path.file_name().map(|name| name.to_os_string()) else {
+        return;
+    };
+    let directory = match config_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => Path::new(".").to_path_buf(),
+    };
+
+    std::thread::spawn(move || {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut watcher = match notify::recommended_watcher(tx) {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                eprintln!("❌ Failed to start config watcher: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = watcher.watch(&directory, notify::RecursiveMode::NonRecursive) {
+            eprintln!("❌ Failed to watch {}: {}", directory.display(), e);
+            return;
+        }
+
+        let touches_config = |event: &notify::Result<notify::Event>| match event {
+            Ok(event) => {
+                !matches!(event.kind, notify::EventKind::Access(_))
+                    && event
+                        .paths
+                        .iter()
+                        .any(|path| path.file_name() == Some(file_name.as_os_str()))
+            }
+            Err(_) => false,
+        };
+
+        while let Ok(event) = rx.recv() {
+            if !touches_config(&event) {
+                continue;
+            }
+            // Wait for the burst of events from a single save to settle
+            while rx.recv_timeout(CONFIG_WATCH_DEBOUNCE).is_ok() {}
+            let _ = reload_config(&server_state);
+        }
+    });
+}
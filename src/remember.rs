@@ -0,0 +1,54 @@
+// Remember-me module for Crusty-Crawler
+// Keeps a device-bound login token in the OS keyring so the GUI can log in at launch
+
+const KEYRING_SERVICE: &str = "crusty-crawler";
+const KEYRING_ACCOUNT: &str = "remember-me";
+
+fn keyring_entry() -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_ACCOUNT).map_err(|e| e.to_string())
+}
+
+// Only the hash goes into crusty_auth.json, the token itself never leaves the keyring
+pub fn remember_login(auth_manager: &mut AuthManager, username: &str) -> Result<(), String> {
+    if !auth_manager.config.allow_remember_me {
+        return Err("Remember me is disabled by the administrator".to_string());
+    }
+
+    let token = AuthManager::generate_suggested_token();
+    keyring_entry()?
+        .set_password(&format!("{}\n{}", username, token))
+        .map_err(|e| format!("Failed to store login in the keyring: {}", e))?;
+    auth_manager.set_remember_token(username, Some(&token))
+}
+
+// The user whose remembered token is still valid, if any
+pub fn remembered_login(auth_manager: &AuthManager) -> Option<String> {
+    if !auth_manager.config.allow_remember_me {
+        return None;
+    }
+
+    let secret = match keyring_entry().ok()?.get_password() {
+        Ok(secret) => secret,
+        Err(keyring::Error::NoEntry) => return None,
+        Err(e) => {
+            eprintln!("⚠️ Could not read remembered login: {}", e);
+            return None;
+        }
+    };
+    let (username, token) = secret.split_once('\n')?;
+    auth_manager
+        .verify_remember_token(username, token)
+        .then(|| username.to_string())
+}
+
+pub fn forget_login(auth_manager: &mut AuthManager, username: &str) {
+    if let Ok(entry) = keyring_entry() {
+        match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => eprintln!("⚠️ Failed to remove remembered login: {}", e),
+        }
+    }
+    if let Err(e) = auth_manager.set_remember_token(username, None) {
+        eprintln!("⚠️ Failed to revoke remembered login: {}", e);
+    }
+}
@@ -0,0 +1,236 @@
+// Replay module for Crusty-Crawler
+// `record` writes the collected metrics to a file, `--replay FILE` plays them back in place of
+// the host's readings, through the checks, alerts, dashboard and exporters, so alert rules can
+// be tried against a past incident
+
+static METRIC_REPLAY: std::sync::OnceLock<Arc<MetricReplay>> = std::sync::OnceLock::new();
+
+// One line of a recording
+#[derive(Serialize, Deserialize)]
+struct RecordedFrame {
+    at: chrono::DateTime<chrono::Utc>,
+    metrics: Vec<Metric>,
+}
+
+pub struct MetricReplay {
+    name: String,
+    // Seconds after the first frame, with its metrics
+    frames: Vec<(f64, Vec<Metric>)>,
+    speed: f64,
+    started: Instant,
+    finished: std::sync::atomic::AtomicBool,
+}
+
+struct RecordArgs {
+    path: PathBuf,
+    duration: Duration,
+    interval: Duration,
+}
+
+// "90", "90s", "15m" or "2h"
+fn parse_span(value: &str) -> Option<Duration> {
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(split) => value.split_at(split),
+        None => (value, "s"),
+    };
+    let number: u64 = number.parse().ok()?;
+    let secs = match unit {
+        "s" => number,
+        "m" => number * 60,
+        "h" => number * 3600,
+        _ => return None,
+    };
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+fn parse_record_args(args: &[String]) -> Result<RecordArgs, String> {
+    let mut path = None;
+    let mut duration = Duration::from_secs(600);
+    let mut interval = Duration::from_secs(10);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--duration" => {
+                duration = args
+                    .next()
+                    .and_then(|value| parse_span(value))
+                    .ok_or("--duration needs a span such as 600, 10m or 2h")?;
+            }
+            "--interval" => {
+                interval = args
+                    .next()
+                    .and_then(|value| parse_span(value))
+                    .ok_or("--interval needs a span such as 10 or 1m")?;
+            }
+            other if other.starts_with("--") => {
+                return Err(format!("Unknown argument: {}", other));
+            }
+            other => path = Some(PathBuf::from(other)),
+        }
+    }
+    let path = path.ok_or("record needs a file to write to")?;
+    Ok(RecordArgs {
+        path,
+        duration,
+        interval,
+    })
+}
+
+// `record FILE [--duration 10m] [--interval 10s]` samples every metric source on a schedule,
+// one JSON line per sample so an interrupted recording can still be replayed
+pub fn run_record(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let args = match parse_record_args(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            eprintln!("Usage: record FILE [--duration SPAN] [--interval SPAN]");
+            std::process::exit(2);
+        }
+    };
+
+    let config = AuthConfig::load_or_default(&data_file(AUTH_CONFIG_FILE));
+    let mut file = fs::File::create(&args.path)?;
+    println!(
+        "📼 Recording to {} every {}s for {}s, Ctrl+C stops early",
+        args.path.display(),
+        args.interval.as_secs(),
+        args.duration.as_secs()
+    );
+
+    let rt = Runtime::new()?;
+    let started = Instant::now();
+    let mut frames = 0;
+    while started.elapsed() < args.duration {
+        let sampled_at = Instant::now();
+        let frame = RecordedFrame {
+            at: chrono::Utc::now(),
+            metrics: rt.block_on(collect_metrics(&config.metrics)),
+        };
+        writeln!(file, "{}", serde_json::to_string(&frame)?)?;
+        file.flush()?;
+        frames += 1;
+        std::thread::sleep(args.interval.saturating_sub(sampled_at.elapsed()));
+    }
+    println!("📼 Recorded {} sample(s)", frames);
+    Ok(())
+}
+
+// Takes --replay FILE and --replay-speed FACTOR out of the arguments
+pub fn init_replay(args: &mut Vec<String>) -> Result<(), String> {
+    let mut path = None;
+    let mut speed = 1.0;
+    let mut index = 0;
+    while index < args.len() {
+        match args[index].as_str() {
+            "--replay" => {
+                path = Some(args.get(index + 1).ok_or("--replay needs a file")?.clone());
+                args.drain(index..index + 2);
+            }
+            "--replay-speed" => {
+                speed = args
+                    .get(index + 1)
+                    .and_then(|value| value.parse::<f64>().ok())
+                    .filter(|speed| *speed > 0.0)
+                    .ok_or("--replay-speed needs a factor above 0, e.g. 10")?;
+                args.drain(index..index + 2);
+            }
+            _ => index += 1,
+        }
+    }
+    let Some(path) = path else {
+        return Ok(());
+    };
+
+    let replay = MetricReplay::load(Path::new(&path), speed)?;
+    println!(
+        "📼 Replaying {} sample(s) from {} at {}x speed",
+        replay.frames.len(),
+        path,
+        speed
+    );
+    METRIC_REPLAY.get_or_init(|| Arc::new(replay));
+    Ok(())
+}
+
+pub fn metric_replay() -> Option<Arc<MetricReplay>> {
+    METRIC_REPLAY.get().cloned()
+}
+
+impl MetricReplay {
+    fn load(path: &Path, speed: f64) -> Result<Self, String> {
+        let data = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut recorded = Vec::new();
+        for (number, line) in data.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let frame: RecordedFrame = serde_json::from_str(line)
+                .map_err(|e| format!("{} line {}: {}", path.display(), number + 1, e))?;
+            recorded.push(frame);
+        }
+        let first = recorded
+            .first()
+            .map(|frame| frame.at)
+            .ok_or_else(|| format!("{} has no samples", path.display()))?;
+
+        Ok(Self {
+            name: path.to_string_lossy().to_string(),
+            frames: recorded
+                .into_iter()
+                .map(|frame| {
+                    let offset = (frame.at - first).num_milliseconds() as f64 / 1000.0;
+                    (offset, frame.metrics)
+                })
+                .collect(),
+            speed,
+            started: Instant::now(),
+            finished: std::sync::atomic::AtomicBool::new(false),
+        })
+    }
+
+    // The last sample recorded before the current replay position, the final one is held
+    // once the recording runs out
+    pub fn metrics(&self) -> Vec<Metric> {
+        let position = self.started.elapsed().as_secs_f64() * self.speed;
+        let index = self
+            .frames
+            .partition_point(|(offset, _)| *offset <= position)
+            .max(1);
+        if index == self.frames.len()
+            && !self
+                .finished
+                .swap(true, std::sync::atomic::Ordering::Relaxed)
+        {
+            println!(
+                "📼 Replay of {} finished, holding the last sample",
+                self.name
+            );
+        }
+        self.frames[index - 1].1.clone()
+    }
+
+    fn value(&self, name: &str) -> Option<f64> {
+        self.metrics()
+            .into_iter()
+            .find(|metric| metric.name == name && metric.labels.is_empty())
+            .map(|metric| metric.value)
+    }
+}
+
+impl SystemInfoProvider for MetricReplay {
+    fn system_name(&self) -> Option<String> {
+        Some(format!("Replay of {}", self.name))
+    }
+
+    fn cpu_usage(&self) -> f32 {
+        self.value("cpu_usage_percent").unwrap_or_default() as f32
+    }
+
+    fn memory(&self) -> (u64, u64) {
+        (
+            self.value("memory_used_bytes").unwrap_or_default() as u64,
+            self.value("memory_total_bytes").unwrap_or_default() as u64,
+        )
+    }
+}
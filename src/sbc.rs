@@ -0,0 +1,230 @@
+// SBC module for Crusty-Crawler
+// Raspberry Pi and other ARM single board computers: thermal zones, core voltage and
+// the firmware's under-voltage / throttling flags
+
+const THROTTLE_FLAGS: &[(u32, &str)] = &[
+    (0, "under-voltage"),
+    (1, "ARM frequency capped"),
+    (2, "throttled"),
+    (3, "soft temperature limit"),
+];
+
+// Bit 16 onwards repeats the flags as "has occurred since boot"
+const THROTTLE_HISTORY_SHIFT: u32 = 16;
+
+#[derive(Clone, Copy)]
+pub struct ThrottleStatus {
+    pub raw: u32,
+}
+
+impl ThrottleStatus {
+    fn flags(&self, shift: u32) -> Vec<&'static str> {
+        THROTTLE_FLAGS
+            .iter()
+            .filter(|(bit, _)| self.raw & (1 << (bit + shift)) != 0)
+            .map(|(_, name)| *name)
+            .collect()
+    }
+
+    pub fn active(&self) -> Vec<&'static str> {
+        self.flags(0)
+    }
+
+    pub fn since_boot(&self) -> Vec<&'static str> {
+        self.flags(THROTTLE_HISTORY_SHIFT)
+    }
+
+    pub fn under_voltage(&self) -> bool {
+        self.raw & 1 != 0
+    }
+
+    // Accepts "throttled=0x50005" from vcgencmd or the bare hex from sysfs
+    pub fn parse(text: &str) -> Option<Self> {
+        let value = text.trim().trim_start_matches("throttled=");
+        let hex = value.trim_start_matches("0x");
+        u32::from_str_radix(hex, 16).ok().map(|raw| Self { raw })
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn board_model() -> Option<String> {
+    fs::read_to_string("/proc/device-tree/model")
+        .ok()
+        .map(|model| model.trim_end_matches('\0').trim().to_string())
+        .filter(|model| !model.is_empty())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn board_model() -> Option<String> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn vcgencmd(args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new("vcgencmd")
+        .args(args)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// Newer kernels expose the firmware flags in sysfs, older images need vcgencmd
+#[cfg(target_os = "linux")]
+pub fn read_throttle_status() -> Option<ThrottleStatus> {
+    board_model()?;
+    fs::read_to_string("/sys/devices/platform/soc/soc:firmware/get_throttled")
+        .ok()
+        .and_then(|raw| ThrottleStatus::parse(&raw))
+        .or_else(|| vcgencmd(&["get_throttled"]).and_then(|raw| ThrottleStatus::parse(&raw)))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_throttle_status() -> Option<ThrottleStatus> {
+    None
+}
+
+// Board thermal zones are often missing from hwmon, which is all sysinfo reads
+#[cfg(target_os = "linux")]
+fn thermal_zone_sensors() -> Vec<SensorReading> {
+    let Ok(entries) = fs::read_dir("/sys/class/thermal") else {
+        return Vec::new();
+    };
+
+    let mut zones: Vec<_> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with("thermal_zone"))
+        })
+        .collect();
+    zones.sort();
+
+    zones
+        .iter()
+        .filter_map(|zone| {
+            let millidegrees: f64 = fs::read_to_string(zone.join("temp"))
+                .ok()?
+                .trim()
+                .parse()
+                .ok()?;
+            let label = fs::read_to_string(zone.join("type"))
+                .map(|t| t.trim().to_string())
+                .unwrap_or_else(|_| {
+                    zone.file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string()
+                });
+            Some(SensorReading::new(
+                &label,
+                SensorKind::Temperature,
+                millidegrees / 1000.0,
+            ))
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+pub fn sbc_sensors() -> Vec<SensorReading> {
+    if board_model().is_none() {
+        return Vec::new();
+    }
+
+    let mut readings = thermal_zone_sensors();
+    // "volt=0.8600V"
+    if let Some(volts) = vcgencmd(&["measure_volts", "core"]).and_then(|out| {
+        out.trim_start_matches("volt=")
+            .trim_end_matches('V')
+            .parse::<f64>()
+            .ok()
+    }) {
+        readings.push(SensorReading::new("Core", SensorKind::Voltage, volts));
+    }
+    readings
+}
+
+pub fn throttle_metrics(status: &ThrottleStatus) -> Vec<Metric> {
+    let mut metrics = Vec::new();
+    for (bit, name) in THROTTLE_FLAGS {
+        for (scope, shift) in [("current", 0), ("since_boot", THROTTLE_HISTORY_SHIFT)] {
+            let set = status.raw & (1 << (bit + shift)) != 0;
+            metrics.push(
+                Metric::new("sbc_throttle_flag", if set { 1.0 } else { 0.0 })
+                    .label("flag", name)
+                    .label("scope", scope),
+            );
+        }
+    }
+    metrics
+}
+
+// Appended to the thermal panel on boards that report throttling
+pub fn sbc_thermal_status() -> Option<String> {
+    let model = board_model()?;
+    let mut output = format!("Board: {}\n", model);
+
+    if let Some(status) = read_throttle_status() {
+        let active = status.active();
+        let since_boot = status.since_boot();
+        if active.is_empty() {
+            output.push_str("Throttling: none\n");
+        } else {
+            output.push_str(&format!("⚠️ Currently: {}\n", active.join(", ")));
+        }
+        if !since_boot.is_empty() {
+            output.push_str(&format!("Since boot: {}\n", since_boot.join(", ")));
+        }
+    }
+
+    Some(output)
+}
+
+pub fn check_throttling() -> CheckResult {
+    let Some(status) = read_throttle_status() else {
+        return CheckResult::new(
+            "throttling",
+            CheckState::Unknown,
+            "Throttling flags are not available on this board".to_string(),
+            Vec::new(),
+        );
+    };
+
+    let active = status.active();
+    let since_boot = status.since_boot();
+
+    // Under-voltage corrupts SD cards, throttling only costs performance
+    let state = if status.under_voltage() {
+        CheckState::Critical
+    } else if !active.is_empty() {
+        CheckState::Warning
+    } else {
+        CheckState::Ok
+    };
+
+    let mut output = if active.is_empty() {
+        "No throttling".to_string()
+    } else {
+        format!("Currently {}", active.join(", "))
+    };
+    if !since_boot.is_empty() {
+        output.push_str(&format!(" (since boot: {})", since_boot.join(", ")));
+    }
+
+    CheckResult::new(
+        "throttling",
+        state,
+        output,
+        vec![
+            PerfData::new(
+                "under_voltage",
+                if status.under_voltage() { 1.0 } else { 0.0 },
+                "",
+            ),
+            PerfData::new("throttle_flags", active.len() as f64, ""),
+        ],
+    )
+}
@@ -0,0 +1,492 @@
+// Scheduler module for Crusty-Crawler
+// One loop running the periodic jobs (speed tests, file integrity scans, tool collectors and
+// retention pruning) either on their interval or on a cron expression in local time, with
+// /api/schedules showing when each runs next and how its last run went
+
+const SCHEDULER_TICK: Duration = Duration::from_secs(1);
+// Cron expressions that can't match, like "0 0 31 2 *", give up after this many days
+const CRON_SEARCH_DAYS: u32 = 4 * 366;
+
+// Standard five-field cron: minute, hour, day of month, month, day of week. Fields take "*",
+// numbers, ranges, lists and steps like "*/15" or "1-5", months and weekdays also take their
+// English three-letter names. "@hourly", "@daily", "@weekly" and "@monthly" are shorthands
+#[derive(Clone, Debug, PartialEq)]
+pub struct CronSchedule {
+    expression: String,
+    // Bit n set when value n matches
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    // Sunday is 0
+    weekdays: u64,
+    // Like cron, when both day fields are restricted a day matching either one runs
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+fn parse_cron_value(value: &str, min: u32, names: &[&str]) -> Result<u32, String> {
+    if let Some(index) = names
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(value))
+    {
+        return Ok(index as u32 + min);
+    }
+    value
+        .parse()
+        .map_err(|_| format!("'{}' is not a number", value))
+}
+
+// Bitmask of the values the field matches
+fn parse_cron_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let mut mask = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or(format!("'{}' is not a valid step", step))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (
+                    parse_cron_value(start, min, names)?,
+                    parse_cron_value(end, min, names)?,
+                ),
+                // "5/10" means from 5 to the end in steps of 10
+                None if step > 1 => (parse_cron_value(range, min, names)?, max),
+                None => {
+                    let value = parse_cron_value(range, min, names)?;
+                    (value, value)
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("'{}' is outside {}-{}", part, min, max));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "'{}' needs five fields: minute hour day month weekday",
+                expression
+            ));
+        };
+        let field = |name: &str, value: &str, min: u32, max: u32, names: &[&str]| {
+            parse_cron_field(value, min, max, names)
+                .map_err(|e| format!("'{}' {} field: {}", expression, name, e))
+        };
+        let mut weekdays = field("weekday", weekday, 0, 7, &WEEKDAY_NAMES)?;
+        // 7 is Sunday too
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            expression: expression.trim().to_string(),
+            minutes: field("minute", minute, 0, 59, &[])?,
+            hours: field("hour", hour, 0, 23, &[])?,
+            days: field("day", day, 1, 31, &[])?,
+            months: field("month", month, 1, 12, &MONTH_NAMES)?,
+            weekdays,
+            days_restricted: day != "*",
+            weekdays_restricted: weekday != "*",
+        })
+    }
+
+    fn matches_date(&self, date: chrono::NaiveDate) -> bool {
+        use chrono::Datelike;
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    // The first matching minute after `after`
+    pub fn next_after(&self, after: chrono::NaiveDateTime) -> Option<chrono::NaiveDateTime> {
+        use chrono::Timelike;
+        let start = after
+            .with_second(0)?
+            .with_nanosecond(0)?
+            .checked_add_signed(chrono::Duration::minutes(1))?;
+        for offset in 0..CRON_SEARCH_DAYS {
+            let date = start.date() + chrono::Days::new(offset as u64);
+            if !self.matches_date(date) {
+                continue;
+            }
+            for hour in 0..24 {
+                if self.hours & (1 << hour) == 0 {
+                    continue;
+                }
+                for minute in 0..60 {
+                    if self.minutes & (1 << minute) == 0 {
+                        continue;
+                    }
+                    let candidate = date.and_hms_opt(hour, minute, 0)?;
+                    if candidate >= start {
+                        return Some(candidate);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Schedule {
+    Every(Duration),
+    Cron(CronSchedule),
+}
+
+impl Schedule {
+    // The cron expression when one is configured, the interval otherwise
+    pub fn new(cron: Option<&str>, interval: Duration) -> Result<Self, String> {
+        match cron {
+            Some(cron) => CronSchedule::parse(cron).map(Schedule::Cron),
+            None => Ok(Schedule::Every(interval)),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Schedule::Every(interval) => format!("every {}s", interval.as_secs()),
+            Schedule::Cron(cron) => cron.expression.clone(),
+        }
+    }
+
+    // Interval jobs that never ran start right away, cron jobs wait for their first match.
+    // A run missed while the agent was down is caught up once
+    pub fn next_run(
+        &self,
+        last: Option<chrono::DateTime<chrono::Utc>>,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Option<chrono::DateTime<chrono::Utc>> {
+        match self {
+            Schedule::Every(interval) => Some(match last {
+                Some(last) => last + chrono::Duration::from_std(*interval).ok()?,
+                None => now,
+            }),
+            Schedule::Cron(cron) => {
+                let mut after = last
+                    .unwrap_or(now)
+                    .with_timezone(&chrono::Local)
+                    .naive_local();
+                // Times skipped by a DST change don't exist, the next match is taken instead
+                loop {
+                    let next = cron.next_after(after)?;
+                    if let Some(next) =
+                        chrono::TimeZone::from_local_datetime(&chrono::Local, &next).earliest()
+                    {
+                        return Some(next.with_timezone(&chrono::Utc));
+                    }
+                    after = next;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum JobTask {
+    SpeedTest,
+    FimScan,
+    // Position in metrics.collectors
+    Collector(usize),
+    Retention,
+}
+
+struct ScheduledJob {
+    name: String,
+    schedule: Result<Schedule, String>,
+    task: JobTask,
+}
+
+// What the jobs read, cloned once per tick
+#[derive(Clone)]
+struct JobConfig {
+    speed_test: SpeedTestConfig,
+    fim: FimConfig,
+    collectors: Vec<ToolCollector>,
+    privacy: PrivacyConfig,
+}
+
+impl JobConfig {
+    fn from_config(config: &AuthConfig) -> Self {
+        Self {
+            speed_test: config.speed_test.clone(),
+            fim: config.checks.fim.clone(),
+            collectors: config.metrics.collectors.clone(),
+            privacy: config.privacy.clone(),
+        }
+    }
+}
+
+// The jobs the config asks for, with the schedule each one runs on
+fn scheduled_jobs(config: &JobConfig) -> Vec<ScheduledJob> {
+    let mut jobs = Vec::new();
+    if config.speed_test.enabled {
+        jobs.push(ScheduledJob {
+            name: "speed_test".to_string(),
+            schedule: Schedule::new(
+                config.speed_test.schedule.as_deref(),
+                Duration::from_secs(config.speed_test.interval_secs.max(300)),
+            ),
+            task: JobTask::SpeedTest,
+        });
+    }
+    if !config.fim.paths.is_empty() {
+        jobs.push(ScheduledJob {
+            name: "fim".to_string(),
+            schedule: Schedule::new(
+                config.fim.schedule.as_deref(),
+                Duration::from_secs(config.fim.interval_secs.max(60)),
+            ),
+            task: JobTask::FimScan,
+        });
+    }
+    for (index, collector) in config.collectors.iter().enumerate() {
+        if collector.parser == ToolParser::Sensors && unsupported_reason("sensors").is_some() {
+            continue;
+        }
+        jobs.push(ScheduledJob {
+            name: format!("collector {}", collector_name(&config.collectors, index)),
+            // Stretched while the host is under load
+            schedule: Schedule::new(
+                collector.schedule.as_deref(),
+                collection_interval(Duration::from_secs(collector.interval_secs.max(5))),
+            ),
+            task: JobTask::Collector(index),
+        });
+    }
+    if DataCategory::ALL
+        .iter()
+        .any(|category| config.privacy.retention_days(*category) > 0)
+    {
+        jobs.push(ScheduledJob {
+            name: "retention".to_string(),
+            schedule: Schedule::new(
+                config.privacy.retention_schedule.as_deref(),
+                RETENTION_INTERVAL,
+            ),
+            task: JobTask::Retention,
+        });
+    }
+    jobs
+}
+
+#[derive(Serialize, Clone)]
+pub struct JobRun {
+    pub started_at: String,
+    pub duration_ms: u64,
+    pub success: bool,
+    pub message: String,
+}
+
+#[derive(Default)]
+struct JobState {
+    last_started: Option<chrono::DateTime<chrono::Utc>>,
+    running: bool,
+    last_run: Option<JobRun>,
+}
+
+// Entry of GET /api/schedules
+#[derive(Serialize)]
+pub struct ScheduleEntry {
+    pub name: String,
+    pub schedule: String,
+    // None while running, or when a cron expression never matches
+    pub next_run: Option<String>,
+    pub running: bool,
+    pub last_run: Option<JobRun>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+static JOB_STATES: Mutex<BTreeMap<String, JobState>> = Mutex::new(BTreeMap::new());
+
+// Speed test results are kept on disk, so the interval carries over restarts
+fn initial_last_run(task: &JobTask) -> Option<chrono::DateTime<chrono::Utc>> {
+    match task {
+        JobTask::SpeedTest => latest_speed_test_time(),
+        _ => None,
+    }
+}
+
+async fn run_job(task: &JobTask, config: &JobConfig) -> Result<String, String> {
+    match task {
+        JobTask::SpeedTest => {
+            if power_saving_active() {
+                return Ok("Skipped to save power".to_string());
+            }
+            let result = run_speed_test(&config.speed_test).await?;
+            match result.error {
+                Some(error) => Err(error),
+                None => Ok(format!("Tested against {}", result.target)),
+            }
+        }
+        JobTask::FimScan => {
+            let changes = run_fim_scan(&config.fim).await;
+            Ok(format!("{} change(s)", changes.len()))
+        }
+        JobTask::Collector(index) => run_scheduled_collector(&config.collectors, *index).await,
+        JobTask::Retention => {
+            let removed = enforce_retention(&config.privacy, chrono::Utc::now());
+            let summary: Vec<String> = removed
+                .iter()
+                .map(|(category, count)| format!("{} {}", count, category.label()))
+                .collect();
+            Ok(format!("Removed {}", summary.join(", ")))
+        }
+    }
+}
+
+// Marks the job as started when it's due and not still running from last time
+fn start_due_job(job: &ScheduledJob, now: chrono::DateTime<chrono::Utc>) -> bool {
+    let Ok(schedule) = &job.schedule else {
+        return false;
+    };
+    let mut states = JOB_STATES.lock().unwrap();
+    let state = states.entry(job.name.clone()).or_insert_with(|| JobState {
+        last_started: initial_last_run(&job.task),
+        ..Default::default()
+    });
+    if state.running
+        || schedule
+            .next_run(state.last_started, now)
+            .is_none_or(|next| next > now)
+    {
+        return false;
+    }
+    state.running = true;
+    state.last_started = Some(now);
+    true
+}
+
+fn finish_job(
+    name: &str,
+    started_at: chrono::DateTime<chrono::Utc>,
+    duration: Duration,
+    result: Result<String, String>,
+) {
+    let mut states = JOB_STATES.lock().unwrap();
+    let state = states.entry(name.to_string()).or_default();
+    state.running = false;
+    let (success, message) = match result {
+        Ok(message) => (true, message),
+        Err(e) => (false, e),
+    };
+    state.last_run = Some(JobRun {
+        started_at: started_at.to_rfc3339(),
+        duration_ms: duration.as_millis() as u64,
+        success,
+        message,
+    });
+}
+
+pub fn schedule_entries(config: &AuthConfig) -> Vec<ScheduleEntry> {
+    let now = chrono::Utc::now();
+    let jobs = scheduled_jobs(&JobConfig::from_config(config));
+    let states = JOB_STATES.lock().unwrap();
+    jobs.into_iter()
+        .map(|job| {
+            let state = states.get(&job.name);
+            let running = state.is_some_and(|state| state.running);
+            let last_started = match state {
+                Some(state) => state.last_started,
+                None => initial_last_run(&job.task),
+            };
+            let (schedule, next_run, error) = match &job.schedule {
+                Ok(schedule) => (
+                    schedule.describe(),
+                    schedule
+                        .next_run(last_started, now)
+                        .filter(|_| !running)
+                        .map(|next| next.max(now).to_rfc3339()),
+                    None,
+                ),
+                Err(e) => (String::new(), None, Some(e.clone())),
+            };
+            ScheduleEntry {
+                name: job.name,
+                schedule,
+                next_run,
+                running,
+                last_run: state.and_then(|state| state.last_run.clone()),
+                error,
+            }
+        })
+        .collect()
+}
+
+fn spawn_scheduler(server_state: Arc<Mutex<ServerState>>) {
+    std::thread::spawn(move || {
+        let rt = match Runtime::new() {
+            Ok(rt) => rt,
+            Err(e) => {
+                eprintln!("❌ Failed to start the scheduler: {}", e);
+                return;
+            }
+        };
+
+        rt.block_on(async {
+            loop {
+                let config = {
+                    let state = server_state.lock().unwrap();
+                    let auth_manager = state.auth_manager.lock().unwrap();
+                    JobConfig::from_config(&auth_manager.config)
+                };
+                forget_removed_collectors(config.collectors.len());
+
+                let jobs = scheduled_jobs(&config);
+                JOB_STATES
+                    .lock()
+                    .unwrap()
+                    .retain(|name, _| jobs.iter().any(|job| &job.name == name));
+                let now = chrono::Utc::now();
+                for job in jobs {
+                    if !start_due_job(&job, now) {
+                        continue;
+                    }
+                    // A slow speed test mustn't hold up the collectors
+                    let config = config.clone();
+                    tokio::spawn(async move {
+                        let started = Instant::now();
+                        let result = run_job(&job.task, &config).await;
+                        finish_job(&job.name, now, started.elapsed(), result);
+                    });
+                }
+                tokio::time::sleep(SCHEDULER_TICK).await;
+            }
+        });
+    });
+}
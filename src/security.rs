@@ -0,0 +1,368 @@
+// Security module for Crusty-Crawler
+// Intrusion heuristics: remembers which ports normally listen and which long-running processes
+// normally exist, plus autoruns and scheduled tasks on Windows, then flags anything new until
+// an admin accepts it into the baseline
+
+use std::collections::BTreeSet;
+
+const SECURITY_BASELINE_FILE: &str = "crusty_security_baseline.json";
+// UDP sockets above this are almost always the client side of a lookup, not a service
+const EPHEMERAL_PORT_START: u16 = 32768;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SecurityConfig {
+    pub enabled: bool,
+    // Processes younger than this are ignored, commands that come and go aren't persistent
+    pub min_process_age_secs: u64,
+    // Process names that are never reported, e.g. interactive shells
+    pub ignore_processes: Vec<String>,
+    pub ignore_ports: Vec<u16>,
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_process_age_secs: 10 * 60,
+            ignore_processes: Vec::new(),
+            ignore_ports: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityFindingKind {
+    Listener,
+    Process,
+    // Windows Run/RunOnce registry values
+    Autorun,
+    ScheduledTask,
+}
+
+impl SecurityFindingKind {
+    const ALL: [SecurityFindingKind; 4] = [
+        SecurityFindingKind::Listener,
+        SecurityFindingKind::Process,
+        SecurityFindingKind::Autorun,
+        SecurityFindingKind::ScheduledTask,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SecurityFindingKind::Listener => "New listener",
+            SecurityFindingKind::Process => "Unknown process",
+            SecurityFindingKind::Autorun => "New autorun entry",
+            SecurityFindingKind::ScheduledTask => "New scheduled task",
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct SecurityFinding {
+    pub kind: SecurityFindingKind,
+    // What accepting the finding adds to the baseline, e.g. "tcp/4444" or "xmrig"
+    pub key: String,
+    pub detail: String,
+}
+
+// Body of POST /api/security/accept
+#[derive(Deserialize)]
+pub struct SecurityAccept {
+    pub kind: SecurityFindingKind,
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct SecurityBaseline {
+    created_at: String,
+    listeners: BTreeSet<String>,
+    processes: BTreeSet<String>,
+    autoruns: BTreeSet<String>,
+    scheduled_tasks: BTreeSet<String>,
+    // Kinds whose current state has been taken in, newer kinds are learned on their first scan
+    // instead of reporting everything as new
+    #[serde(default = "legacy_learned_kinds")]
+    learned: BTreeSet<SecurityFindingKind>,
+}
+
+// Baselines written before autoruns and scheduled tasks were tracked
+fn legacy_learned_kinds() -> BTreeSet<SecurityFindingKind> {
+    BTreeSet::from([SecurityFindingKind::Listener, SecurityFindingKind::Process])
+}
+
+impl SecurityBaseline {
+    fn entries(&mut self, kind: SecurityFindingKind) -> &mut BTreeSet<String> {
+        match kind {
+            SecurityFindingKind::Listener => &mut self.listeners,
+            SecurityFindingKind::Process => &mut self.processes,
+            SecurityFindingKind::Autorun => &mut self.autoruns,
+            SecurityFindingKind::ScheduledTask => &mut self.scheduled_tasks,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SecurityReport {
+    pub enabled: bool,
+    pub baseline_created_at: Option<String>,
+    pub baseline_listeners: usize,
+    pub baseline_processes: usize,
+    pub baseline_autoruns: usize,
+    pub baseline_scheduled_tasks: usize,
+    pub findings: Vec<SecurityFinding>,
+}
+
+// Loaded from SECURITY_BASELINE_FILE on first use, None inside means no baseline yet
+static SECURITY_BASELINE: Mutex<Option<Option<SecurityBaseline>>> = Mutex::new(None);
+// Result of the last scan, for the GUI which can't wait on one
+static LATEST_FINDINGS: Mutex<Vec<SecurityFinding>> = Mutex::new(Vec::new());
+// Findings already put on the timeline, so each one is recorded once
+static REPORTED_FINDINGS: Mutex<BTreeSet<(SecurityFindingKind, String)>> =
+    Mutex::new(BTreeSet::new());
+
+fn with_security_baseline<T>(f: impl FnOnce(&mut Option<SecurityBaseline>) -> T) -> T {
+    let mut baseline = SECURITY_BASELINE.lock().unwrap();
+    let baseline = baseline.get_or_insert_with(|| {
+        fs::read_to_string(data_path(SECURITY_BASELINE_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+    });
+    f(baseline)
+}
+
+fn save_security_baseline(baseline: &SecurityBaseline) -> Result<(), String> {
+    let data = serde_json::to_string_pretty(baseline).map_err(|e| e.to_string())?;
+    fs::write(data_path(SECURITY_BASELINE_FILE), data)
+        .map_err(|e| format!("Failed to save {}: {}", SECURITY_BASELINE_FILE, e))
+}
+
+// Everything currently listening, one entry per protocol and port
+fn observe_listeners(config: &SecurityConfig) -> Vec<SecurityFinding> {
+    let mut listeners: BTreeMap<String, SecurityFinding> = BTreeMap::new();
+    for connection in read_connections() {
+        let listening = match connection.protocol {
+            "tcp" => connection.state == "LISTEN",
+            _ => connection.remote.is_none() && connection.local.port() < EPHEMERAL_PORT_START,
+        };
+        let port = connection.local.port();
+        if !listening || port == 0 || config.ignore_ports.contains(&port) {
+            continue;
+        }
+        let key = format!("{}/{}", connection.protocol, port);
+        let owner = match (&connection.process, connection.pid) {
+            (Some(process), Some(pid)) => format!(" by {} (pid {})", process, pid),
+            (None, Some(pid)) => format!(" by pid {}", pid),
+            _ => String::new(),
+        };
+        listeners.entry(key.clone()).or_insert(SecurityFinding {
+            kind: SecurityFindingKind::Listener,
+            key,
+            detail: format!("{} on {}{}", connection.protocol, connection.local, owner),
+        });
+    }
+    listeners.into_values().collect()
+}
+
+// Processes that have been running for a while, by name
+fn observe_processes(config: &SecurityConfig) -> Vec<SecurityFinding> {
+    let mut sys = sysinfo::System::new();
+    sys.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
+
+    let mut processes: BTreeMap<String, SecurityFinding> = BTreeMap::new();
+    for (pid, process) in sys.processes() {
+        // Threads are listed as processes on Linux, and kernel threads have no command line
+        if process.thread_kind().is_some() || process.cmd().is_empty() {
+            continue;
+        }
+        if process.run_time() < config.min_process_age_secs {
+            continue;
+        }
+        let name = process.name().to_string_lossy().to_string();
+        if name.is_empty()
+            || config
+                .ignore_processes
+                .iter()
+                .any(|ignored| ignored.eq_ignore_ascii_case(&name))
+        {
+            continue;
+        }
+        let exe = process
+            .exe()
+            .map(|exe| format!(" {}", exe.display()))
+            .unwrap_or_default();
+        processes.entry(name.clone()).or_insert(SecurityFinding {
+            kind: SecurityFindingKind::Process,
+            key: name.clone(),
+            detail: format!(
+                "{} (pid {}){}, running {}",
+                name,
+                pid,
+                exe,
+                format_uptime(process.run_time())
+            ),
+        });
+    }
+    processes.into_values().collect()
+}
+
+// Compares the machine against the baseline. Whatever isn't in the baseline yet is taken in as
+// it is, the agent assumes it was installed on a clean machine
+fn scan_security(config: &SecurityConfig) -> Vec<SecurityFinding> {
+    let observed: Vec<SecurityFinding> = observe_listeners(config)
+        .into_iter()
+        .chain(observe_processes(config))
+        .chain(observe_autoruns())
+        .chain(observe_scheduled_tasks())
+        .collect();
+
+    with_security_baseline(|baseline| {
+        let baseline = baseline.get_or_insert_with(|| SecurityBaseline {
+            created_at: chrono::Utc::now().to_rfc3339(),
+            ..Default::default()
+        });
+        let unlearned: Vec<SecurityFindingKind> = SecurityFindingKind::ALL
+            .into_iter()
+            .filter(|kind| !baseline.learned.contains(kind))
+            .collect();
+        if !unlearned.is_empty() {
+            for item in observed
+                .iter()
+                .filter(|item| unlearned.contains(&item.kind))
+            {
+                baseline.entries(item.kind).insert(item.key.clone());
+            }
+            baseline.learned.extend(unlearned);
+            if let Err(e) = save_security_baseline(baseline) {
+                eprintln!("⚠️  {}", e);
+            }
+        }
+        observed
+            .into_iter()
+            .filter(|item| !baseline.entries(item.kind).contains(&item.key))
+            .collect()
+    })
+}
+
+fn record_new_findings(findings: &[SecurityFinding]) {
+    let mut reported = REPORTED_FINDINGS.lock().unwrap();
+    for finding in findings {
+        if reported.insert((finding.kind, finding.key.clone())) {
+            record_event(
+                EventKind::Security,
+                &format!("{}: {}", finding.kind.label(), finding.key),
+                &finding.detail,
+            );
+        }
+    }
+}
+
+pub async fn security_findings(config: &SecurityConfig) -> Vec<SecurityFinding> {
+    let config = config.clone();
+    // Walks every process's file descriptors on Linux
+    let findings = tokio::task::spawn_blocking(move || scan_security(&config))
+        .await
+        .unwrap_or_default();
+    *LATEST_FINDINGS.lock().unwrap() = findings.clone();
+    findings
+}
+
+pub fn latest_security_findings() -> Vec<SecurityFinding> {
+    LATEST_FINDINGS.lock().unwrap().clone()
+}
+
+async fn check_security(config: &SecurityConfig) -> CheckResult {
+    let findings = security_findings(config).await;
+    record_new_findings(&findings);
+
+    let count = |kind: SecurityFindingKind| findings.iter().filter(|f| f.kind == kind).count();
+    let mut perfdata = vec![
+        PerfData::new(
+            "new_listeners",
+            count(SecurityFindingKind::Listener) as f64,
+            "",
+        ),
+        PerfData::new(
+            "unknown_processes",
+            count(SecurityFindingKind::Process) as f64,
+            "",
+        ),
+    ];
+    if cfg!(windows) {
+        perfdata.push(PerfData::new(
+            "new_autoruns",
+            count(SecurityFindingKind::Autorun) as f64,
+            "",
+        ));
+        perfdata.push(PerfData::new(
+            "new_scheduled_tasks",
+            count(SecurityFindingKind::ScheduledTask) as f64,
+            "",
+        ));
+    }
+    if findings.is_empty() {
+        return CheckResult::new(
+            "security",
+            CheckState::Ok,
+            "Nothing outside the security baseline".to_string(),
+            perfdata,
+        );
+    }
+
+    let summary: Vec<String> = findings
+        .iter()
+        .map(|finding| format!("{} {}", finding.kind.label().to_lowercase(), finding.key))
+        .collect();
+    CheckResult::new(
+        "security",
+        CheckState::Warning,
+        format!("Outside the baseline: {}", summary.join(", ")),
+        perfdata,
+    )
+}
+
+pub async fn security_report(config: &SecurityConfig) -> SecurityReport {
+    let findings = if config.enabled {
+        security_findings(config).await
+    } else {
+        Vec::new()
+    };
+    with_security_baseline(|baseline| SecurityReport {
+        enabled: config.enabled,
+        baseline_created_at: baseline.as_ref().map(|b| b.created_at.clone()),
+        baseline_listeners: baseline.as_ref().map_or(0, |b| b.listeners.len()),
+        baseline_processes: baseline.as_ref().map_or(0, |b| b.processes.len()),
+        baseline_autoruns: baseline.as_ref().map_or(0, |b| b.autoruns.len()),
+        baseline_scheduled_tasks: baseline.as_ref().map_or(0, |b| b.scheduled_tasks.len()),
+        findings,
+    })
+}
+
+// Adds a finding to the baseline so it stops being reported
+pub fn accept_security_finding(kind: SecurityFindingKind, key: &str) -> Result<(), String> {
+    with_security_baseline(|baseline| {
+        let baseline = baseline
+            .as_mut()
+            .ok_or("No baseline has been recorded yet")?;
+        if !baseline.entries(kind).insert(key.to_string()) {
+            return Err(format!("{} is already in the baseline", key));
+        }
+        save_security_baseline(baseline)
+    })?;
+    REPORTED_FINDINGS
+        .lock()
+        .unwrap()
+        .remove(&(kind, key.to_string()));
+    LATEST_FINDINGS
+        .lock()
+        .unwrap()
+        .retain(|finding| finding.kind != kind || finding.key != key);
+    record_event(
+        EventKind::Security,
+        &format!("Accepted into security baseline: {}", key),
+        "",
+    );
+    Ok(())
+}
@@ -0,0 +1,256 @@
+// Server module for Crusty-Crawler
+// Serves the app over HTTP/1.1 and HTTP/2, over TLS when a certificate is configured, and keeps
+// connections open between polls so the dashboard doesn't pay for a new handshake every time
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct HttpServerConfig {
+    // PEM files, relative to the data directory. With both set the server speaks HTTPS and
+    // offers HTTP/2 through ALPN, without them HTTP/2 still works with prior knowledge (h2c)
+    pub tls_cert: Option<String>,
+    pub tls_key: Option<String>,
+    // Connections without a request in flight are closed after this long
+    pub idle_timeout_secs: u64,
+    // HTTP/2 PINGs that keep connections open through NAT and proxies, 0 disables them
+    pub keep_alive_interval_secs: u64,
+    // Time allowed for a request's headers to arrive, stops slowloris clients
+    pub header_read_timeout_secs: u64,
+}
+
+impl Default for HttpServerConfig {
+    fn default() -> Self {
+        Self {
+            tls_cert: None,
+            tls_key: None,
+            idle_timeout_secs: 120,
+            keep_alive_interval_secs: 30,
+            header_read_timeout_secs: 30,
+        }
+    }
+}
+
+static CONNECTIONS_ACCEPTED: AtomicU64 = AtomicU64::new(0);
+static CONNECTIONS_OPEN: AtomicU64 = AtomicU64::new(0);
+// Open connections with at least one request in flight
+static CONNECTIONS_BUSY: AtomicU64 = AtomicU64::new(0);
+
+struct ConnectionActivity {
+    in_flight: AtomicUsize,
+    idle_since: Mutex<Instant>,
+}
+
+impl ConnectionActivity {
+    // None while a request is being served
+    fn idle_for(&self) -> Option<Duration> {
+        (self.in_flight.load(Ordering::SeqCst) == 0)
+            .then(|| self.idle_since.lock().unwrap().elapsed())
+    }
+}
+
+// Marks the connection busy for as long as one of its requests is being handled
+struct RequestGuard(Arc<ConnectionActivity>);
+
+impl RequestGuard {
+    fn new(activity: Arc<ConnectionActivity>) -> Self {
+        if activity.in_flight.fetch_add(1, Ordering::SeqCst) == 0 {
+            CONNECTIONS_BUSY.fetch_add(1, Ordering::Relaxed);
+        }
+        Self(activity)
+    }
+}
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        *self.0.idle_since.lock().unwrap() = Instant::now();
+        if self.0.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            CONNECTIONS_BUSY.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+fn tls_acceptor(cert: &str, key: &str) -> io::Result<tokio_rustls::TlsAcceptor> {
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidInput, e);
+    let cert_path = data_path(cert);
+    let key_path = data_path(key);
+    let certs = CertificateDer::pem_file_iter(&cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| invalid(format!("{}: {}", cert_path.display(), e)))?;
+    let key = PrivateKeyDer::from_pem_file(&key_path)
+        .map_err(|e| invalid(format!("{}: {}", key_path.display(), e)))?;
+
+    let mut config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+    .map_err(|e| invalid(e.to_string()))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(tokio_rustls::TlsAcceptor::from(Arc::new(config)))
+}
+
+// Never constructed, stands in for tokio_rustls::TlsAcceptor in builds without the tls feature
+#[cfg(not(feature = "tls"))]
+#[derive(Clone)]
+enum NoTlsAcceptor {}
+
+#[cfg(not(feature = "tls"))]
+impl NoTlsAcceptor {
+    async fn accept(&self, _stream: tokio::net::TcpStream) -> io::Result<tokio::net::TcpStream> {
+        match *self {}
+    }
+}
+
+#[cfg(not(feature = "tls"))]
+fn tls_acceptor(_cert: &str, _key: &str) -> io::Result<NoTlsAcceptor> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "tls_cert and tls_key are set but this build has no TLS support, rebuild with the tls \
+         feature or put a TLS proxy in front",
+    ))
+}
+
+fn connection_builder(
+    config: &HttpServerConfig,
+) -> hyper_util::server::conn::auto::Builder<hyper_util::rt::TokioExecutor> {
+    use hyper_util::rt::TokioTimer;
+
+    let mut builder =
+        hyper_util::server::conn::auto::Builder::new(hyper_util::rt::TokioExecutor::new());
+    builder
+        .http1()
+        .timer(TokioTimer::new())
+        .keep_alive(true)
+        .header_read_timeout(Duration::from_secs(config.header_read_timeout_secs.max(1)));
+    let mut http2 = builder.http2();
+    http2.timer(TokioTimer::new());
+    if config.keep_alive_interval_secs > 0 {
+        http2
+            .keep_alive_interval(Duration::from_secs(config.keep_alive_interval_secs))
+            .keep_alive_timeout(Duration::from_secs(20));
+    }
+    builder
+}
+
+// Serves one connection until the client hangs up or it sits idle for too long
+async fn serve_connection<I>(
+    io: I,
+    remote: SocketAddr,
+    app: Router,
+    builder: Arc<hyper_util::server::conn::auto::Builder<hyper_util::rt::TokioExecutor>>,
+    idle_timeout: Duration,
+) where
+    I: hyper::rt::Read + hyper::rt::Write + Unpin + Send + 'static,
+{
+    use tower::ServiceExt;
+
+    let activity = Arc::new(ConnectionActivity {
+        in_flight: AtomicUsize::new(0),
+        idle_since: Mutex::new(Instant::now()),
+    });
+    let request_activity = activity.clone();
+    let service =
+        hyper::service::service_fn(move |mut request: hyper::Request<hyper::body::Incoming>| {
+            request
+                .extensions_mut()
+                .insert(axum::extract::ConnectInfo(remote));
+            let guard = RequestGuard::new(request_activity.clone());
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await;
+                drop(guard);
+                response
+            }
+        });
+
+    let connection = builder.serve_connection_with_upgrades(io, service);
+    let mut connection = std::pin::pin!(connection);
+    loop {
+        let wait = activity
+            .idle_for()
+            .map_or(idle_timeout, |idle| idle_timeout.saturating_sub(idle));
+        tokio::select! {
+            _ = connection.as_mut() => return,
+            _ = tokio::time::sleep(wait) => {
+                if activity.idle_for().is_some_and(|idle| idle >= idle_timeout) {
+                    connection.as_mut().graceful_shutdown();
+                    let _ = connection.as_mut().await;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// Replaces axum::serve, which has no TLS and no connection tuning
+pub async fn serve_app(
+    listener: tokio::net::TcpListener,
+    app: Router,
+    config: HttpServerConfig,
+) -> io::Result<()> {
+    let tls = match (&config.tls_cert, &config.tls_key) {
+        (Some(cert), Some(key)) => {
+            let acceptor = tls_acceptor(cert, key)?;
+            println!("🔒 HTTPS enabled, HTTP/2 offered through ALPN");
+            Some(acceptor)
+        }
+        _ => None,
+    };
+    let builder = Arc::new(connection_builder(&config));
+    let idle_timeout = Duration::from_secs(config.idle_timeout_secs.max(1));
+    let handshake_timeout = Duration::from_secs(config.header_read_timeout_secs.max(1));
+
+    loop {
+        let (stream, remote) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            // Usually out of file descriptors, give connections a moment to close
+            Err(e) => {
+                eprintln!("⚠️  Failed to accept a connection: {}", e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let _ = stream.set_nodelay(true);
+        let app = app.clone();
+        let builder = builder.clone();
+        let tls = tls.clone();
+
+        CONNECTIONS_ACCEPTED.fetch_add(1, Ordering::Relaxed);
+        CONNECTIONS_OPEN.fetch_add(1, Ordering::Relaxed);
+        tokio::spawn(async move {
+            match tls {
+                Some(acceptor) => {
+                    if let Ok(Ok(stream)) =
+                        tokio::time::timeout(handshake_timeout, acceptor.accept(stream)).await
+                    {
+                        let io = hyper_util::rt::TokioIo::new(stream);
+                        serve_connection(io, remote, app, builder, idle_timeout).await;
+                    }
+                }
+                None => {
+                    let io = hyper_util::rt::TokioIo::new(stream);
+                    serve_connection(io, remote, app, builder, idle_timeout).await;
+                }
+            }
+            CONNECTIONS_OPEN.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+}
+
+pub fn http_connection_metrics() -> Vec<Metric> {
+    let open = CONNECTIONS_OPEN.load(Ordering::Relaxed);
+    let busy = CONNECTIONS_BUSY.load(Ordering::Relaxed).min(open);
+    vec![
+        Metric::new(
+            "http_connections_accepted_total",
+            CONNECTIONS_ACCEPTED.load(Ordering::Relaxed) as f64,
+        ),
+        Metric::new("http_connections_active", busy as f64),
+        Metric::new("http_connections_idle", (open - busy) as f64),
+    ]
+}
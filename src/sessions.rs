@@ -0,0 +1,174 @@
+// Sessions module for Crusty-Crawler
+// Expiring browser sessions for the web UI and the GUI's idle lock screen
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SessionConfig {
+    // Lock the GUI after this many minutes without input, 0 disables the lock
+    pub gui_idle_lock_mins: u64,
+    // How long a browser session lasts before the access token has to be entered again
+    pub web_session_hours: u64,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            gui_idle_lock_mins: 15,
+            web_session_hours: 12,
+        }
+    }
+}
+
+pub struct WebSession {
+    // Names the session in the credentials list, unlike the token it is safe to show
+    id: String,
+    username: String,
+    expires_at: Instant,
+    // Login links from the GUI's QR code, exchanged for a regular session when opened
+    single_use: bool,
+    usage: CredentialUsage,
+}
+
+impl AuthManager {
+    // Browsers get a session token so the access token doesn't live in the address bar forever
+    pub fn create_web_session(&self, username: &str) -> String {
+        let token = AuthManager::generate_suggested_token();
+        let lifetime = Duration::from_secs(self.config.sessions.web_session_hours.max(1) * 3600);
+
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, session| session.expires_at > self.clock.now());
+        sessions.insert(
+            token.clone(),
+            WebSession {
+                id: AuthManager::generate_suggested_token(),
+                username: username.to_string(),
+                expires_at: self.clock.now() + lifetime,
+                single_use: false,
+                usage: CredentialUsage::default(),
+            },
+        );
+        token
+    }
+
+    pub fn create_login_link(&self, username: &str, lifetime: Duration) -> String {
+        let token = AuthManager::generate_suggested_token();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, session| session.expires_at > self.clock.now());
+        sessions.insert(
+            token.clone(),
+            WebSession {
+                id: AuthManager::generate_suggested_token(),
+                username: username.to_string(),
+                expires_at: self.clock.now() + lifetime,
+                single_use: true,
+                usage: CredentialUsage::default(),
+            },
+        );
+        token
+    }
+
+    // Consumes the link, so a photographed QR code can't be used again
+    pub fn redeem_login_link(&self, token: &str) -> Option<String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get(token) {
+            Some(session) if session.single_use => {
+                let session = sessions.remove(token)?;
+                (session.expires_at > self.clock.now()).then_some(session.username)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn validate_web_session(&self, token: &str) -> Option<String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get(token) {
+            Some(session) if session.single_use => None,
+            Some(session) if session.expires_at > self.clock.now() => {
+                Some(session.username.clone())
+            }
+            Some(_) => {
+                sessions.remove(token);
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+impl MainState {
+    // Any input counts as activity, returns true once the idle limit has passed
+    fn check_idle(&mut self, ctx: &egui::Context) -> bool {
+        if self.locked {
+            return true;
+        }
+        let active = ctx.input(|i| !i.events.is_empty() || i.pointer.is_moving());
+        if active {
+            self.last_activity = Instant::now();
+        }
+
+        let idle_lock_mins = {
+            let state = self.server_state.lock().unwrap();
+            let auth_manager = state.auth_manager.lock().unwrap();
+            auth_manager.config.sessions.gui_idle_lock_mins
+        };
+        if idle_lock_mins > 0
+            && self.last_activity.elapsed() >= Duration::from_secs(idle_lock_mins * 60)
+        {
+            self.lock();
+        }
+        self.locked
+    }
+
+    fn lock(&mut self) {
+        self.locked = true;
+        self.unlock_password.clear();
+        self.unlock_error.clear();
+    }
+
+    // The server and checks keep running behind the lock screen
+    fn show_lock_screen(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("🔒 Crusty Server - Locked");
+            ui.separator();
+            ui.label(format!(
+                "The session is locked. Enter the password for {} to continue.",
+                self.current_user
+            ));
+
+            let password =
+                ui.add(egui::TextEdit::singleline(&mut self.unlock_password).password(true));
+            let submitted = password.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+            if !self.unlock_error.is_empty() {
+                ui.colored_label(egui::Color32::RED, &self.unlock_error);
+            }
+
+            if ui.button("🔓 Unlock").clicked() || submitted {
+                let result = {
+                    let state = self.server_state.lock().unwrap();
+                    let auth_manager = state.auth_manager.lock().unwrap();
+                    auth_manager.authenticate(&self.current_user, &self.unlock_password)
+                };
+                record_login(
+                    Some(&self.current_user),
+                    LoginMethod::Unlock,
+                    None,
+                    result.as_ref().err().map(String::as_str),
+                );
+                match result {
+                    Ok(_) => {
+                        self.locked = false;
+                        self.last_activity = Instant::now();
+                        self.unlock_password.clear();
+                        self.unlock_error.clear();
+                    }
+                    Err(e) => {
+                        self.unlock_password.clear();
+                        self.unlock_error = e;
+                    }
+                }
+            }
+        });
+    }
+}
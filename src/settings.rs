@@ -0,0 +1,925 @@
+// Settings module for Crusty-Crawler
+// The GUI settings view with Server, SMTP, Users and Alerts tabs
+
+#[derive(PartialEq, Clone, Copy)]
+enum SettingsTab {
+    Server,
+    Smtp,
+    Users,
+    Alerts,
+}
+
+// Editable copies of the config, saved per tab
+struct SettingsState {
+    tab: SettingsTab,
+    smtp: SmtpConfigState,
+    status_pages: StatusPageConfig,
+    checks: CheckConfig,
+    sessions: SessionConfig,
+    password_policy: PasswordPolicy,
+    current_password: String,
+    new_password: String,
+    confirm_password: String,
+    new_email: String,
+    email_password: String,
+    invite_email: String,
+    invite_role: UserRole,
+    kiosk_name: String,
+    // Link with a new kiosk token, shown until dismissed like new_token
+    new_kiosk_url: Option<String>,
+    // Shown until dismissed, it can't be looked up again
+    new_token: Option<String>,
+    message: String,
+}
+
+impl SmtpConfigState {
+    fn from_config(config: Option<&SmtpConfig>) -> Self {
+        Self {
+            server: config.map(|c| c.server.clone()).unwrap_or_default(),
+            port: config.map_or(587, |c| c.port).to_string(),
+            username: config.map(|c| c.username.clone()).unwrap_or_default(),
+            password: config.map(|c| c.password.clone()).unwrap_or_default(),
+            use_tls: config.is_none_or(|c| c.use_tls),
+            message: String::new(),
+            testing: false,
+            test_result: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+impl SettingsState {
+    fn load(server_state: &Arc<Mutex<ServerState>>) -> Self {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        Self {
+            tab: SettingsTab::Server,
+            smtp: SmtpConfigState::from_config(auth_manager.config.smtp_config.as_ref()),
+            status_pages: auth_manager.config.status_pages.clone(),
+            checks: auth_manager.config.checks.clone(),
+            sessions: auth_manager.config.sessions.clone(),
+            password_policy: auth_manager.config.password_policy.clone(),
+            current_password: String::new(),
+            new_password: String::new(),
+            confirm_password: String::new(),
+            new_email: String::new(),
+            email_password: String::new(),
+            invite_email: String::new(),
+            invite_role: UserRole::Viewer,
+            kiosk_name: String::new(),
+            new_kiosk_url: None,
+            new_token: None,
+            message: String::new(),
+        }
+    }
+}
+
+fn threshold_editor(ui: &mut egui::Ui, label: &str, thresholds: &mut Thresholds) {
+    ui.label(label);
+    ui.add(
+        egui::DragValue::new(&mut thresholds.warning)
+            .range(0.0..=100.0)
+            .suffix("%"),
+    );
+    ui.add(
+        egui::DragValue::new(&mut thresholds.critical)
+            .range(0.0..=100.0)
+            .suffix("%"),
+    );
+    ui.end_row();
+}
+
+// Last used, from and request count cells of the credentials grids
+fn usage_columns(ui: &mut egui::Ui, usage: &CredentialUsage) {
+    match &usage.last_used {
+        Some(last_used) => ui.label(format_timestamp(last_used)),
+        None => ui.weak("never"),
+    };
+    ui.label(usage.last_ip.as_deref().unwrap_or("-"));
+    ui.label(usage.requests.to_string());
+}
+
+impl MainState {
+    fn show_settings(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let tab = self.settings.tab;
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.settings.tab, SettingsTab::Server, "🖥 Server");
+            ui.selectable_value(&mut self.settings.tab, SettingsTab::Smtp, "📧 SMTP");
+            ui.selectable_value(&mut self.settings.tab, SettingsTab::Users, "👥 Users");
+            ui.selectable_value(&mut self.settings.tab, SettingsTab::Alerts, "🔔 Alerts");
+        });
+        if self.settings.tab != tab {
+            self.settings.message.clear();
+        }
+        ui.separator();
+
+        match self.settings.tab {
+            SettingsTab::Server => self.show_server_settings(ui),
+            SettingsTab::Smtp => self.show_smtp_settings(ui, ctx),
+            SettingsTab::Users => self.show_user_settings(ui),
+            SettingsTab::Alerts => self.show_alert_settings(ui),
+        }
+
+        if !self.settings.message.is_empty() {
+            ui.separator();
+            ui.label(&self.settings.message);
+        }
+    }
+
+    fn show_server_settings(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Server Configuration");
+        egui::Frame::group(ui.style())
+            .inner_margin(egui::Margin::same(10))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Port:")
+                        .on_hover_text("Port number for the web server");
+                    ui.add(egui::TextEdit::singleline(&mut self.port_input).desired_width(80.0));
+
+                    // Visual port validation
+                    if self.port_input.parse::<u16>().is_err() {
+                        ui.colored_label(egui::Color32::RED, "❌ Invalid port");
+                    } else {
+                        ui.colored_label(egui::Color32::GREEN, "✅ Valid");
+                    }
+                });
+                ui.small("Takes effect the next time the server is started");
+            });
+
+        ui.add_space(10.0);
+        ui.heading("Public Status Page");
+        let public = &mut self.settings.status_pages.public;
+        egui::Frame::group(ui.style())
+            .inner_margin(egui::Margin::same(10))
+            .show(ui, |ui| {
+                ui.checkbox(&mut public.enabled, "Serve /public without a token");
+                ui.horizontal(|ui| {
+                    ui.label("Title:");
+                    ui.text_edit_singleline(&mut public.title);
+                });
+                ui.label(format!(
+                    "Published metrics: {}",
+                    if public.metrics.is_empty() {
+                        "none".to_string()
+                    } else {
+                        public.metrics.join(", ")
+                    }
+                ));
+            });
+
+        if ui.button("💾 Save Server Settings").clicked() {
+            let state = self.server_state.lock().unwrap();
+            let mut auth_manager = state.auth_manager.lock().unwrap();
+            self.settings.message =
+                match auth_manager.configure_status_pages(self.settings.status_pages.clone()) {
+                    Ok(()) => "Server settings saved".to_string(),
+                    Err(e) => format!("Error: {}", e),
+                };
+        }
+    }
+
+    fn show_smtp_settings(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let smtp_state = &mut self.settings.smtp;
+        ui.heading("📧 SMTP Configuration");
+        ui.label("Configure email settings for password recovery:");
+
+        ui.horizontal(|ui| {
+            ui.label("SMTP Server:");
+            ui.text_edit_singleline(&mut smtp_state.server);
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Port:");
+            ui.text_edit_singleline(&mut smtp_state.port);
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Username:");
+            ui.text_edit_singleline(&mut smtp_state.username);
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Password:");
+            ui.add(egui::TextEdit::singleline(&mut smtp_state.password).password(true));
+        });
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut smtp_state.use_tls, "Use TLS");
+        });
+
+        if !smtp_state.message.is_empty() {
+            ui.colored_label(egui::Color32::GREEN, &smtp_state.message);
+        }
+
+        if let Some(result) = smtp_state.test_result.lock().unwrap().take() {
+            smtp_state.testing = false;
+            smtp_state.message = match result {
+                Ok(summary) => format!("✅ {}", summary),
+                Err(e) => format!("❌ Connection test failed: {}", e),
+            };
+        }
+
+        ui.horizontal(|ui| {
+            let test_button =
+                ui.add_enabled(!smtp_state.testing, egui::Button::new("🔌 Test Connection"));
+            if test_button.clicked() {
+                match smtp_state.port.parse::<u16>() {
+                    Ok(port) => {
+                        let smtp_config = SmtpConfig {
+                            server: smtp_state.server.clone(),
+                            port,
+                            username: smtp_state.username.clone(),
+                            password: smtp_state.password.clone(),
+                            use_tls: smtp_state.use_tls,
+                        };
+                        smtp_state.testing = true;
+                        smtp_state.message = String::new();
+                        let test_result = smtp_state.test_result.clone();
+                        let ctx = ctx.clone();
+                        // The handshake can take seconds, keep the UI responsive
+                        std::thread::spawn(move || {
+                            let result = test_smtp_connection(&smtp_config);
+                            *test_result.lock().unwrap() = Some(result);
+                            ctx.request_repaint();
+                        });
+                    }
+                    Err(_) => {
+                        smtp_state.message = "Invalid port number".to_string();
+                    }
+                }
+            }
+            if smtp_state.testing {
+                ui.spinner();
+                ui.label("Testing connection...");
+            }
+        });
+
+        egui::CollapsingHeader::new("🩺 Diagnostics").show(ui, |ui| {
+            let failures = recent_email_failures();
+            if failures.is_empty() {
+                ui.label("No failed emails since startup.");
+            }
+            for failure in failures {
+                ui.colored_label(
+                    egui::Color32::RED,
+                    format!("{} to {}: {}", failure.at, failure.recipient, failure.error),
+                );
+            }
+        });
+
+        ui.separator();
+
+        if ui.button("💾 Save Configuration").clicked() {
+            match smtp_state.port.parse::<u16>() {
+                Ok(port) => {
+                    let smtp_config = SmtpConfig {
+                        server: smtp_state.server.clone(),
+                        port,
+                        username: smtp_state.username.clone(),
+                        password: smtp_state.password.clone(),
+                        use_tls: smtp_state.use_tls,
+                    };
+
+                    let server_state = self.server_state.lock().unwrap();
+                    let mut auth_manager = server_state.auth_manager.lock().unwrap();
+                    match auth_manager.configure_smtp(smtp_config) {
+                        Ok(()) => {
+                            smtp_state.message =
+                                "SMTP configuration saved successfully!".to_string();
+                        }
+                        Err(e) => {
+                            smtp_state.message = format!("Error: {}", e);
+                        }
+                    }
+                }
+                Err(_) => {
+                    smtp_state.message = "Invalid port number".to_string();
+                }
+            }
+        }
+    }
+
+    fn show_user_settings(&mut self, ui: &mut egui::Ui) {
+        let mut users: Vec<User> = {
+            let state = self.server_state.lock().unwrap();
+            let auth_manager = state.auth_manager.lock().unwrap();
+            auth_manager.config.users.values().cloned().collect()
+        };
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        let credentials = {
+            let state = self.server_state.lock().unwrap();
+            let auth_manager = state.auth_manager.lock().unwrap();
+            auth_manager.credential_report()
+        };
+        let token_usage = |username: &str| {
+            credentials
+                .iter()
+                .find(|c| c.kind == CredentialKind::AccessToken && c.username == username)
+                .map(|c| c.usage.clone())
+                .unwrap_or_default()
+        };
+        // Applied after the grids are drawn
+        let mut revoke_token = None;
+        let mut revoke_session = None;
+
+        let mut allow_remember_me = {
+            let state = self.server_state.lock().unwrap();
+            let auth_manager = state.auth_manager.lock().unwrap();
+            auth_manager.config.allow_remember_me
+        };
+        ui.heading("🔐 Login Policy");
+        egui::Frame::group(ui.style())
+            .inner_margin(egui::Margin::same(10))
+            .show(ui, |ui| {
+                let toggle = ui.checkbox(
+                    &mut allow_remember_me,
+                    "Allow \"Remember me\" logins from the OS keyring",
+                );
+                if toggle.changed() {
+                    let state = self.server_state.lock().unwrap();
+                    let mut auth_manager = state.auth_manager.lock().unwrap();
+                    self.settings.message = match auth_manager
+                        .set_allow_remember_me(allow_remember_me)
+                    {
+                        Ok(()) if allow_remember_me => "Remember me enabled".to_string(),
+                        Ok(()) => "Remember me disabled, remembered devices signed out".to_string(),
+                        Err(e) => format!("Error: {}", e),
+                    };
+                }
+
+                let sessions = &mut self.settings.sessions;
+                ui.horizontal(|ui| {
+                    ui.label("Lock the GUI after");
+                    ui.add(
+                        egui::DragValue::new(&mut sessions.gui_idle_lock_mins)
+                            .range(0..=1440)
+                            .suffix(" min"),
+                    );
+                    ui.small("idle, 0 never locks");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Web sessions expire after");
+                    ui.add(
+                        egui::DragValue::new(&mut sessions.web_session_hours)
+                            .range(1..=720)
+                            .suffix(" h"),
+                    );
+                });
+                if ui.button("💾 Save Login Policy").clicked() {
+                    let state = self.server_state.lock().unwrap();
+                    let mut auth_manager = state.auth_manager.lock().unwrap();
+                    self.settings.message =
+                        match auth_manager.configure_sessions(self.settings.sessions.clone()) {
+                            Ok(()) => "Login policy saved".to_string(),
+                            Err(e) => format!("Error: {}", e),
+                        };
+                }
+            });
+
+        ui.add_space(10.0);
+        ui.heading("🔏 Password Policy");
+        egui::Frame::group(ui.style())
+            .inner_margin(egui::Margin::same(10))
+            .show(ui, |ui| {
+                let policy = &mut self.settings.password_policy;
+                ui.horizontal(|ui| {
+                    ui.label("Passwords need at least");
+                    ui.add(egui::DragValue::new(&mut policy.min_length).range(1..=128));
+                    ui.label("characters");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("and");
+                    ui.checkbox(&mut policy.require_uppercase, "an uppercase letter");
+                    ui.checkbox(&mut policy.require_lowercase, "a lowercase letter");
+                    ui.checkbox(&mut policy.require_digit, "a digit");
+                    ui.checkbox(&mut policy.require_symbol, "a symbol");
+                });
+                ui.checkbox(&mut policy.reject_common, "Reject common passwords");
+                ui.horizontal(|ui| {
+                    ui.label("Passwords expire after");
+                    ui.add(
+                        egui::DragValue::new(&mut policy.max_age_days)
+                            .range(0..=3650)
+                            .suffix(" days"),
+                    );
+                    ui.small("0 never expires them");
+                });
+                ui.small("Existing passwords are checked against new rules when they're changed");
+                if ui.button("💾 Save Password Policy").clicked() {
+                    let state = self.server_state.lock().unwrap();
+                    let mut auth_manager = state.auth_manager.lock().unwrap();
+                    self.settings.message = match auth_manager
+                        .configure_password_policy(self.settings.password_policy.clone())
+                    {
+                        Ok(()) => "Password policy saved".to_string(),
+                        Err(e) => format!("Error: {}", e),
+                    };
+                }
+            });
+
+        ui.add_space(10.0);
+        ui.heading("👥 Users");
+        egui::Frame::group(ui.style())
+            .inner_margin(egui::Margin::same(10))
+            .show(ui, |ui| {
+                egui::Grid::new("users")
+                    .striped(true)
+                    .num_columns(7)
+                    .show(ui, |ui| {
+                        ui.strong("Username");
+                        ui.strong("Email");
+                        ui.strong("Created");
+                        ui.strong("Token last used");
+                        ui.strong("From");
+                        ui.strong("Requests");
+                        ui.label("");
+                        ui.end_row();
+
+                        for user in &users {
+                            if user.username == self.current_user {
+                                ui.label(format!("{} (you)", user.username));
+                            } else {
+                                ui.label(&user.username);
+                            }
+                            ui.label(&user.email);
+                            ui.label(format_timestamp(&user.created_at));
+                            usage_columns(ui, &token_usage(&user.username));
+                            if ui
+                                .small_button("⛔ Revoke")
+                                .on_hover_text("Replace the token with one nobody knows")
+                                .clicked()
+                            {
+                                revoke_token = Some(user.username.clone());
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        ui.add_space(10.0);
+        ui.heading("🌐 Web Sessions");
+        egui::Frame::group(ui.style())
+            .inner_margin(egui::Margin::same(10))
+            .show(ui, |ui| {
+                let sessions: Vec<&CredentialReport> = credentials
+                    .iter()
+                    .filter(|c| c.kind == CredentialKind::WebSession)
+                    .collect();
+                if sessions.is_empty() {
+                    ui.label("No browser is signed in");
+                    return;
+                }
+                egui::Grid::new("web_sessions")
+                    .striped(true)
+                    .num_columns(6)
+                    .show(ui, |ui| {
+                        ui.strong("User");
+                        ui.strong("Last used");
+                        ui.strong("From");
+                        ui.strong("Requests");
+                        ui.strong("Expires in");
+                        ui.label("");
+                        ui.end_row();
+
+                        for session in sessions {
+                            ui.label(&session.username);
+                            usage_columns(ui, &session.usage);
+                            let expires_in = session.expires_in_secs.unwrap_or_default();
+                            ui.label(format!(
+                                "{}h {:02}m",
+                                expires_in / 3600,
+                                expires_in / 60 % 60
+                            ));
+                            if ui.small_button("⛔ Revoke").clicked() {
+                                revoke_session = session.id.clone();
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        if let Some(username) = revoke_token {
+            let state = self.server_state.lock().unwrap();
+            let mut auth_manager = state.auth_manager.lock().unwrap();
+            self.settings.message = match auth_manager.revoke_access_token(&username) {
+                Ok(()) => format!("Access token of {} revoked", username),
+                Err(e) => format!("Error: {}", e),
+            };
+        }
+        if let Some(id) = revoke_session {
+            let state = self.server_state.lock().unwrap();
+            let auth_manager = state.auth_manager.lock().unwrap();
+            auth_manager.revoke_web_session(&id);
+            self.settings.message = "Web session revoked".to_string();
+        }
+
+        let invitations = {
+            let state = self.server_state.lock().unwrap();
+            let auth_manager = state.auth_manager.lock().unwrap();
+            auth_manager.pending_invitations()
+        };
+        ui.add_space(10.0);
+        ui.heading("✉ Invitations");
+        egui::Frame::group(ui.style())
+            .inner_margin(egui::Margin::same(10))
+            .show(ui, |ui| {
+                ui.label("New users get a signup code by email and choose their own password.");
+                ui.horizontal(|ui| {
+                    ui.label("Email:");
+                    ui.text_edit_singleline(&mut self.settings.invite_email);
+                    egui::ComboBox::from_id_salt("invite_role")
+                        .selected_text(self.settings.invite_role.label())
+                        .show_ui(ui, |ui| {
+                            for role in [UserRole::Viewer, UserRole::Admin] {
+                                ui.selectable_value(
+                                    &mut self.settings.invite_role,
+                                    role,
+                                    role.label(),
+                                );
+                            }
+                        });
+                    if ui.button("✉ Send Invitation").clicked() {
+                        let state = self.server_state.lock().unwrap();
+                        let mut auth_manager = state.auth_manager.lock().unwrap();
+                        let base_url =
+                            lan_base_url(state.port, auth_manager.config.http.tls_cert.is_some());
+                        self.settings.message = match auth_manager.invite_user(
+                            &self.current_user,
+                            &self.settings.invite_email,
+                            self.settings.invite_role,
+                            &base_url,
+                        ) {
+                            Ok(_) => {
+                                let sent =
+                                    format!("Invitation sent to {}", self.settings.invite_email);
+                                self.settings.invite_email.clear();
+                                sent
+                            }
+                            Err(e) => format!("Error: {}", e),
+                        };
+                    }
+                });
+
+                if invitations.is_empty() {
+                    return;
+                }
+                ui.add_space(5.0);
+                egui::Grid::new("invitations")
+                    .striped(true)
+                    .num_columns(5)
+                    .show(ui, |ui| {
+                        ui.strong("Email");
+                        ui.strong("Role");
+                        ui.strong("Invited by");
+                        ui.strong("Expires");
+                        ui.label("");
+                        ui.end_row();
+
+                        for invitation in &invitations {
+                            ui.label(&invitation.email);
+                            ui.label(invitation.role.label());
+                            ui.label(&invitation.invited_by);
+                            ui.label(format_timestamp(&invitation.expires_at));
+                            if ui.small_button("⛔ Revoke").clicked() {
+                                let state = self.server_state.lock().unwrap();
+                                let mut auth_manager = state.auth_manager.lock().unwrap();
+                                self.settings.message = match auth_manager
+                                    .revoke_invitation(&invitation.id)
+                                {
+                                    Ok(_) => format!("Invitation of {} revoked", invitation.email),
+                                    Err(e) => format!("Error: {}", e),
+                                };
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        let kiosks = {
+            let state = self.server_state.lock().unwrap();
+            let auth_manager = state.auth_manager.lock().unwrap();
+            auth_manager.kiosk_tokens()
+        };
+        ui.add_space(10.0);
+        ui.heading("📺 Kiosk Displays");
+        egui::Frame::group(ui.style())
+            .inner_margin(egui::Margin::same(10))
+            .show(ui, |ui| {
+                ui.label(
+                    "Kiosk tokens only open the dashboard and status pages, for screens \
+                     that stay signed in where anyone can walk up to them.",
+                );
+                if let Some(url) = &self.settings.new_kiosk_url {
+                    ui.label("Open this link on the display, it won't be shown again:");
+                    ui.horizontal(|ui| {
+                        ui.monospace(url);
+                        if ui.button("📋 Copy").clicked() {
+                            ui.ctx().copy_text(url.clone());
+                        }
+                    });
+                    if ui.button("✅ Done").clicked() {
+                        self.settings.new_kiosk_url = None;
+                    }
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Display name:");
+                    ui.text_edit_singleline(&mut self.settings.kiosk_name);
+                    if ui.button("➕ Create Kiosk Token").clicked() {
+                        let state = self.server_state.lock().unwrap();
+                        let mut auth_manager = state.auth_manager.lock().unwrap();
+                        let base_url =
+                            lan_base_url(state.port, auth_manager.config.http.tls_cert.is_some());
+                        match auth_manager
+                            .create_kiosk_token(&self.settings.kiosk_name, &self.current_user)
+                        {
+                            Ok(token) => {
+                                self.settings.new_kiosk_url =
+                                    Some(format!("{}?token={}", base_url, token));
+                                self.settings.message =
+                                    format!("Kiosk token for {} created", self.settings.kiosk_name);
+                                self.settings.kiosk_name.clear();
+                            }
+                            Err(e) => self.settings.message = format!("Error: {}", e),
+                        }
+                    }
+                });
+
+                if kiosks.is_empty() {
+                    return;
+                }
+                ui.add_space(5.0);
+                egui::Grid::new("kiosk_tokens")
+                    .striped(true)
+                    .num_columns(7)
+                    .show(ui, |ui| {
+                        ui.strong("Display");
+                        ui.strong("Created by");
+                        ui.strong("Created");
+                        ui.strong("Last used");
+                        ui.strong("From");
+                        ui.strong("Requests");
+                        ui.label("");
+                        ui.end_row();
+
+                        for kiosk in &kiosks {
+                            ui.label(&kiosk.name);
+                            ui.label(&kiosk.created_by);
+                            ui.label(format_timestamp(&kiosk.created_at));
+                            usage_columns(ui, &kiosk.usage);
+                            if ui.small_button("⛔ Revoke").clicked() {
+                                let state = self.server_state.lock().unwrap();
+                                let mut auth_manager = state.auth_manager.lock().unwrap();
+                                self.settings.message = match auth_manager
+                                    .revoke_kiosk_token(&kiosk.id, &self.current_user)
+                                {
+                                    Ok(_) => format!("Kiosk token for {} revoked", kiosk.name),
+                                    Err(e) => format!("Error: {}", e),
+                                };
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        ui.add_space(10.0);
+        ui.heading("🔑 Access Token");
+        egui::Frame::group(ui.style())
+            .inner_margin(egui::Margin::same(10))
+            .show(ui, |ui| match &self.settings.new_token {
+                Some(token) => {
+                    ui.label("Your new access token, copy it now, it won't be shown again:");
+                    ui.horizontal(|ui| {
+                        ui.monospace(token);
+                        if ui.button("📋 Copy").clicked() {
+                            ui.ctx().copy_text(token.clone());
+                        }
+                    });
+                    if ui.button("✅ Done").clicked() {
+                        self.settings.new_token = None;
+                    }
+                }
+                None => {
+                    ui.label(
+                        "Only a hash of your token is stored, so it can't be shown. Rotating \
+                         it gives you a new one and the old token stops working.",
+                    );
+                    if ui.button("🔄 Rotate Access Token").clicked() {
+                        let state = self.server_state.lock().unwrap();
+                        let mut auth_manager = state.auth_manager.lock().unwrap();
+                        match auth_manager.rotate_access_token(&self.current_user) {
+                            Ok(token) => {
+                                self.settings.new_token = Some(token);
+                                self.settings.message = "Access token rotated".to_string();
+                            }
+                            Err(e) => self.settings.message = format!("Error: {}", e),
+                        }
+                    }
+                }
+            });
+
+        ui.add_space(10.0);
+        ui.heading("🔒 Change Password");
+        egui::Frame::group(ui.style())
+            .inner_margin(egui::Margin::same(10))
+            .show(ui, |ui| {
+                egui::Grid::new("change_password")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Current password:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.settings.current_password)
+                                .password(true),
+                        );
+                        ui.end_row();
+                        ui.label("New password:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.settings.new_password)
+                                .password(true),
+                        );
+                        ui.end_row();
+                        ui.label("Confirm new password:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.settings.confirm_password)
+                                .password(true),
+                        );
+                        ui.end_row();
+                    });
+                ui.small(format!(
+                    "New passwords need {}",
+                    self.settings.password_policy.describe()
+                ));
+                if ui.button("🔒 Change Password").clicked() {
+                    if self.settings.new_password != self.settings.confirm_password {
+                        self.settings.message = "Error: Passwords do not match".to_string();
+                        return;
+                    }
+                    let state = self.server_state.lock().unwrap();
+                    let mut auth_manager = state.auth_manager.lock().unwrap();
+                    self.settings.message = match auth_manager.change_password(
+                        &self.current_user,
+                        &self.settings.current_password,
+                        &self.settings.new_password,
+                    ) {
+                        Ok(()) => {
+                            self.settings.current_password.clear();
+                            self.settings.new_password.clear();
+                            self.settings.confirm_password.clear();
+                            "Password changed".to_string()
+                        }
+                        Err(e) => format!("Error: {}", e),
+                    };
+                }
+            });
+
+        let email = users
+            .iter()
+            .find(|user| user.username == self.current_user)
+            .map(|user| user.email.clone())
+            .unwrap_or_default();
+        ui.add_space(10.0);
+        ui.heading("✉ Change Email");
+        egui::Frame::group(ui.style())
+            .inner_margin(egui::Margin::same(10))
+            .show(ui, |ui| {
+                ui.label(format!("Recovery emails currently go to {}", email));
+                egui::Grid::new("change_email")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("New email:");
+                        ui.text_edit_singleline(&mut self.settings.new_email);
+                        ui.end_row();
+                        ui.label("Current password:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.settings.email_password)
+                                .password(true),
+                        );
+                        ui.end_row();
+                    });
+                if ui.button("✉ Change Email").clicked() {
+                    let state = self.server_state.lock().unwrap();
+                    let mut auth_manager = state.auth_manager.lock().unwrap();
+                    self.settings.message = match auth_manager.change_email(
+                        &self.current_user,
+                        &self.settings.email_password,
+                        &self.settings.new_email,
+                    ) {
+                        Ok(()) => {
+                            self.settings.new_email.clear();
+                            self.settings.email_password.clear();
+                            "Email changed".to_string()
+                        }
+                        Err(e) => format!("Error: {}", e),
+                    };
+                }
+            });
+
+        ui.add_space(10.0);
+        ui.heading("🕘 Your Login History");
+        egui::Frame::group(ui.style())
+            .inner_margin(egui::Margin::same(10))
+            .show(ui, |ui| {
+                let logins = login_history(Some(&self.current_user), Some(20));
+                if logins.is_empty() {
+                    ui.label("No logins recorded yet");
+                    return;
+                }
+                egui::Grid::new("login_history")
+                    .striped(true)
+                    .num_columns(4)
+                    .show(ui, |ui| {
+                        ui.strong("Time");
+                        ui.strong("Method");
+                        ui.strong("From");
+                        ui.strong("Result");
+                        ui.end_row();
+
+                        for login in &logins {
+                            ui.label(format_timestamp(&login.timestamp));
+                            ui.label(login.method.label());
+                            ui.label(login.ip.as_deref().unwrap_or("This machine"));
+                            match &login.reason {
+                                None => ui.colored_label(egui::Color32::GREEN, "✅ Success"),
+                                Some(reason) => {
+                                    ui.colored_label(egui::Color32::RED, format!("❌ {}", reason))
+                                }
+                            };
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+
+    fn show_alert_settings(&mut self, ui: &mut egui::Ui) {
+        ui.heading("📏 Thresholds");
+        let checks = &mut self.settings.checks;
+        egui::Frame::group(ui.style())
+            .inner_margin(egui::Margin::same(10))
+            .show(ui, |ui| {
+                egui::Grid::new("thresholds").num_columns(3).show(ui, |ui| {
+                    ui.label("");
+                    ui.strong("Warning");
+                    ui.strong("Critical");
+                    ui.end_row();
+                    threshold_editor(ui, "CPU", &mut checks.cpu);
+                    threshold_editor(ui, "Memory", &mut checks.memory);
+                    threshold_editor(ui, "Disk", &mut checks.disk);
+                    threshold_editor(ui, "Kernel limits", &mut checks.kernel_limits);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Run checks every");
+                    ui.add(
+                        egui::DragValue::new(&mut checks.interval_secs)
+                            .range(5..=3600)
+                            .suffix("s"),
+                    );
+                });
+            });
+
+        if ui.button("💾 Save Alert Settings").clicked() {
+            let state = self.server_state.lock().unwrap();
+            let mut auth_manager = state.auth_manager.lock().unwrap();
+            self.settings.message =
+                match auth_manager.configure_checks(self.settings.checks.clone()) {
+                    Ok(()) => "Alert settings saved".to_string(),
+                    Err(e) => format!("Error: {}", e),
+                };
+        }
+
+        ui.add_space(10.0);
+        ui.heading("📇 Contacts");
+        let (contacts, shifts) = {
+            let state = self.server_state.lock().unwrap();
+            let auth_manager = state.auth_manager.lock().unwrap();
+            (
+                auth_manager.config.contacts.clone(),
+                auth_manager.config.on_call.schedule(chrono::Utc::now()),
+            )
+        };
+        egui::Frame::group(ui.style())
+            .inner_margin(egui::Margin::same(10))
+            .show(ui, |ui| {
+                if contacts.is_empty() {
+                    ui.label("No contacts configured, add them to crusty_auth.json");
+                }
+                for contact in &contacts {
+                    let channels: Vec<&str> = contact.channels.iter().map(|c| c.name()).collect();
+                    let severities: Vec<&str> =
+                        contact.severities.iter().map(|s| s.label()).collect();
+                    ui.horizontal(|ui| {
+                        ui.strong(&contact.name);
+                        ui.label(format!("via {}", channels.join(", ")));
+                        ui.small(format!("on {}", severities.join(", ")));
+                        for shift in shifts.iter().filter(|s| s.contact == contact.name) {
+                            ui.colored_label(
+                                egui::Color32::LIGHT_BLUE,
+                                format!(
+                                    "📟 on call for {} until {}",
+                                    shift.rotation,
+                                    format_timestamp(&shift.until)
+                                ),
+                            );
+                        }
+                    });
+                }
+            });
+    }
+}
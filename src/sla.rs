@@ -0,0 +1,251 @@
+// SLA module for Crusty-Crawler
+// Availability of every check over the last day, week or month, worked out from a log of
+// check state changes kept in the data directory. Time spent CRITICAL counts as downtime
+
+const CHECK_HISTORY_FILE: &str = "crusty_check_history.json";
+// The month window plus a day, older changes are pruned
+const CHECK_HISTORY_DAYS: i64 = 31;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StateChange {
+    pub timestamp: String,
+    pub state: CheckState,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SlaWindow {
+    #[default]
+    Day,
+    Week,
+    // 30 days
+    Month,
+}
+
+impl SlaWindow {
+    fn duration(&self) -> chrono::Duration {
+        match self {
+            SlaWindow::Day => chrono::Duration::days(1),
+            SlaWindow::Week => chrono::Duration::days(7),
+            SlaWindow::Month => chrono::Duration::days(30),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SlaQuery {
+    #[serde(default)]
+    pub window: SlaWindow,
+    // Carried into the report page's window links
+    pub token: Option<String>,
+}
+
+// A stretch of CRITICAL, end is None while it lasts
+#[derive(Serialize)]
+pub struct DowntimeIncident {
+    pub start: String,
+    pub end: Option<String>,
+    // Only the part inside the window
+    pub duration_secs: u64,
+}
+
+#[derive(Serialize)]
+pub struct CheckSla {
+    pub check: String,
+    // Share of the observed time not spent CRITICAL
+    pub availability_percent: f64,
+    // Seconds of the window since the check was first seen
+    pub observed_secs: u64,
+    pub time_in_state: BTreeMap<CheckState, u64>,
+    pub incidents: Vec<DowntimeIncident>,
+}
+
+#[derive(Serialize)]
+pub struct SlaReport {
+    pub window: SlaWindow,
+    pub from: String,
+    pub to: String,
+    pub checks: Vec<CheckSla>,
+}
+
+// Check name -> state changes, oldest first. Loaded from CHECK_HISTORY_FILE on first use
+static CHECK_HISTORY: Mutex<Option<BTreeMap<String, Vec<StateChange>>>> = Mutex::new(None);
+
+fn with_check_history<T>(f: impl FnOnce(&mut BTreeMap<String, Vec<StateChange>>) -> T) -> T {
+    let mut history = CHECK_HISTORY.lock().unwrap();
+    let history = history.get_or_insert_with(|| {
+        fs::read_to_string(data_path(CHECK_HISTORY_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    });
+    f(history)
+}
+
+fn parse_change_time(timestamp: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|timestamp| timestamp.with_timezone(&chrono::Utc))
+}
+
+// Drops changes before the cutoff except the last one, which still says what state the
+// check was in when the window opened
+fn prune_state_changes(changes: &mut Vec<StateChange>, cutoff: chrono::DateTime<chrono::Utc>) {
+    let recent = changes
+        .iter()
+        .position(|change| parse_change_time(&change.timestamp).is_none_or(|t| t >= cutoff))
+        .unwrap_or(changes.len());
+    changes.drain(..recent.saturating_sub(1));
+}
+
+// Only writes when the state differs from the last one logged for the check
+pub fn record_check_state(result: &CheckResult) {
+    with_check_history(|history| {
+        let changes = history.entry(result.name.clone()).or_default();
+        if changes
+            .last()
+            .is_some_and(|last| last.state == result.state)
+        {
+            return;
+        }
+        changes.push(StateChange {
+            timestamp: result.checked_at.clone(),
+            state: result.state,
+        });
+        prune_state_changes(
+            changes,
+            chrono::Utc::now() - chrono::Duration::days(CHECK_HISTORY_DAYS),
+        );
+
+        let saved = serde_json::to_string(&*history)
+            .map_err(|e| e.to_string())
+            .and_then(|data| {
+                fs::write(data_path(CHECK_HISTORY_FILE), data).map_err(|e| e.to_string())
+            });
+        if let Err(e) = saved {
+            eprintln!(
+                "⚠️  Failed to save check history to {}: {}",
+                CHECK_HISTORY_FILE, e
+            );
+        }
+    });
+}
+
+// A state lasts until the next change, None when the check wasn't seen in the window
+fn check_sla(
+    check: &str,
+    changes: &[StateChange],
+    from: chrono::DateTime<chrono::Utc>,
+    to: chrono::DateTime<chrono::Utc>,
+) -> Option<CheckSla> {
+    let mut time_in_state = BTreeMap::new();
+    let mut incidents = Vec::new();
+    let mut observed = 0;
+    for (index, change) in changes.iter().enumerate() {
+        let Some(start) = parse_change_time(&change.timestamp) else {
+            continue;
+        };
+        let end = changes
+            .get(index + 1)
+            .and_then(|next| parse_change_time(&next.timestamp));
+        let secs = (end.unwrap_or(to).min(to) - start.max(from))
+            .num_seconds()
+            .max(0) as u64;
+        if secs == 0 {
+            continue;
+        }
+        observed += secs;
+        *time_in_state.entry(change.state).or_insert(0) += secs;
+        if change.state == CheckState::Critical {
+            incidents.push(DowntimeIncident {
+                start: change.timestamp.clone(),
+                end: end.map(|end| end.to_rfc3339()),
+                duration_secs: secs,
+            });
+        }
+    }
+    if observed == 0 {
+        return None;
+    }
+
+    let down = time_in_state
+        .get(&CheckState::Critical)
+        .copied()
+        .unwrap_or(0);
+    let availability = (observed - down) as f64 / observed as f64 * 100.0;
+    Some(CheckSla {
+        check: check.to_string(),
+        availability_percent: (availability * 1000.0).round() / 1000.0,
+        observed_secs: observed,
+        time_in_state,
+        incidents,
+    })
+}
+
+pub fn sla_report(window: SlaWindow, now: chrono::DateTime<chrono::Utc>) -> SlaReport {
+    let from = now - window.duration();
+    let checks = with_check_history(|history| {
+        history
+            .iter()
+            .filter_map(|(check, changes)| check_sla(check, changes, from, now))
+            .collect()
+    });
+    SlaReport {
+        window,
+        from: from.to_rfc3339(),
+        to: now.to_rfc3339(),
+        checks,
+    }
+}
+
+fn format_duration(secs: u64) -> String {
+    let (days, hours, minutes) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m {}s", minutes, secs % 60)
+    }
+}
+
+// The /sla page, links to the other windows keep the viewer's token
+pub fn render_sla_page(report: &SlaReport, token: Option<&str>) -> Result<String, StatusCode> {
+    let checks: Vec<serde_json::Value> = report
+        .checks
+        .iter()
+        .map(|sla| {
+            serde_json::json!({
+                "check": sla.check,
+                "availability_percent": sla.availability_percent,
+                "observed": format_duration(sla.observed_secs),
+                "states": sla.time_in_state.iter().map(|(state, secs)| {
+                    serde_json::json!({ "state": state, "time": format_duration(*secs) })
+                }).collect::<Vec<_>>(),
+                "incidents": sla.incidents.iter().map(|incident| {
+                    serde_json::json!({
+                        "start": format_timestamp(&incident.start),
+                        "end": incident.end.as_deref().map(format_timestamp),
+                        "duration": format_duration(incident.duration_secs),
+                    })
+                }).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+
+    let mut context = tera::Context::new();
+    context.insert(
+        "host",
+        &sysinfo::System::host_name().unwrap_or_else(|| "unknown host".to_string()),
+    );
+    context.insert("window", &report.window);
+    context.insert(
+        "windows",
+        &[SlaWindow::Day, SlaWindow::Week, SlaWindow::Month],
+    );
+    context.insert("from", &format_timestamp(&report.from));
+    context.insert("to", &format_timestamp(&report.to));
+    context.insert("token", token.unwrap_or_default());
+    context.insert("checks", &checks);
+    render_template("sla", include_str!("../templates/sla.html"), &context)
+}
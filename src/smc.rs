@@ -0,0 +1,246 @@
+// SMC module for Crusty-Crawler (macOS)
+// Reads temperatures, fan speeds and power draw from the Apple System Management Controller
+// through IOKit, since sysinfo reports few or no sensors on Apple hardware
+
+// Known keys: Intel Macs use the TC/TG families, Apple Silicon the Tp/Te/Tg families
+#[cfg(target_os = "macos")]
+const SMC_TEMPERATURE_KEYS: &[(&str, &str)] = &[
+    ("TC0P", "CPU Proximity"),
+    ("TC0D", "CPU Die"),
+    ("TC0E", "CPU Die (PECI)"),
+    ("TC0F", "CPU Die (Filtered)"),
+    ("Tp01", "CPU Performance Core 1"),
+    ("Tp05", "CPU Performance Core 2"),
+    ("Tp09", "CPU Performance Core 3"),
+    ("Tp0D", "CPU Performance Core 4"),
+    ("Te05", "CPU Efficiency Core 1"),
+    ("Te0L", "CPU Efficiency Core 2"),
+    ("TG0P", "GPU Proximity"),
+    ("TG0D", "GPU Die"),
+    ("Tg05", "GPU Cluster 1"),
+    ("Tg0D", "GPU Cluster 2"),
+    ("Tm0P", "Memory Proximity"),
+    ("TB0T", "Battery"),
+    ("TA0P", "Ambient"),
+];
+
+#[cfg(target_os = "macos")]
+const SMC_POWER_KEYS: &[(&str, &str)] = &[
+    ("PSTR", "System Total"),
+    ("PCPC", "CPU Package"),
+    ("PCPG", "GPU"),
+    ("PPBR", "Battery"),
+];
+
+#[cfg(target_os = "macos")]
+const KERNEL_INDEX_SMC: u32 = 2;
+#[cfg(target_os = "macos")]
+const SMC_CMD_READ_BYTES: u8 = 5;
+#[cfg(target_os = "macos")]
+const SMC_CMD_READ_KEYINFO: u8 = 9;
+
+// Mirrors SMCKeyData_t from the AppleSMC user client
+#[cfg(target_os = "macos")]
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct SmcVersion {
+    major: u8,
+    minor: u8,
+    build: u8,
+    reserved: u8,
+    release: u16,
+}
+
+#[cfg(target_os = "macos")]
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct SmcPLimitData {
+    version: u16,
+    length: u16,
+    cpu_p_limit: u32,
+    gpu_p_limit: u32,
+    mem_p_limit: u32,
+}
+
+#[cfg(target_os = "macos")]
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct SmcKeyInfo {
+    data_size: u32,
+    data_type: u32,
+    data_attributes: u8,
+}
+
+#[cfg(target_os = "macos")]
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct SmcKeyData {
+    key: u32,
+    vers: SmcVersion,
+    p_limit_data: SmcPLimitData,
+    key_info: SmcKeyInfo,
+    result: u8,
+    status: u8,
+    data8: u8,
+    data32: u32,
+    bytes: [u8; 32],
+}
+
+// The kernel rejects calls whose struct size doesn't match its own
+#[cfg(target_os = "macos")]
+const _: () = assert!(std::mem::size_of::<SmcKeyData>() == 80);
+
+#[cfg(target_os = "macos")]
+#[link(name = "IOKit", kind = "framework")]
+unsafe extern "C" {
+    static mach_task_self_: u32;
+
+    fn IOServiceMatching(name: *const std::ffi::c_char) -> *mut std::ffi::c_void;
+    fn IOServiceGetMatchingService(main_port: u32, matching: *mut std::ffi::c_void) -> u32;
+    fn IOServiceOpen(service: u32, owning_task: u32, kind: u32, connect: *mut u32) -> i32;
+    fn IOServiceClose(connect: u32) -> i32;
+    fn IOObjectRelease(object: u32) -> i32;
+    fn IOConnectCallStructMethod(
+        connection: u32,
+        selector: u32,
+        input: *const std::ffi::c_void,
+        input_size: usize,
+        output: *mut std::ffi::c_void,
+        output_size: *mut usize,
+    ) -> i32;
+}
+
+#[cfg(target_os = "macos")]
+fn smc_key_code(key: &str) -> u32 {
+    let mut bytes = [b' '; 4];
+    for (slot, byte) in bytes.iter_mut().zip(key.bytes()) {
+        *slot = byte;
+    }
+    u32::from_be_bytes(bytes)
+}
+
+#[cfg(target_os = "macos")]
+struct SmcConnection {
+    connection: u32,
+}
+
+#[cfg(target_os = "macos")]
+impl SmcConnection {
+    fn open() -> Result<Self, String> {
+        unsafe {
+            let matching = IOServiceMatching(c"AppleSMC".as_ptr());
+            // Passing 0 selects the default main port
+            let service = IOServiceGetMatchingService(0, matching);
+            if service == 0 {
+                return Err("AppleSMC service not found".to_string());
+            }
+
+            let mut connection = 0;
+            let status = IOServiceOpen(service, mach_task_self_, 0, &mut connection);
+            IOObjectRelease(service);
+            if status != 0 {
+                return Err(format!(
+                    "Failed to open AppleSMC (IOKit error {:#x})",
+                    status
+                ));
+            }
+            Ok(Self { connection })
+        }
+    }
+
+    fn call(&self, input: &SmcKeyData) -> Option<SmcKeyData> {
+        let mut output = SmcKeyData::default();
+        let mut output_size = std::mem::size_of::<SmcKeyData>();
+        let status = unsafe {
+            IOConnectCallStructMethod(
+                self.connection,
+                KERNEL_INDEX_SMC,
+                input as *const SmcKeyData as *const std::ffi::c_void,
+                std::mem::size_of::<SmcKeyData>(),
+                &mut output as *mut SmcKeyData as *mut std::ffi::c_void,
+                &mut output_size,
+            )
+        };
+        // A non-zero result byte means the key doesn't exist on this model
+        (status == 0 && output.result == 0).then_some(output)
+    }
+
+    // Reads a key and decodes it according to its SMC data type
+    fn read(&self, key: &str) -> Option<f64> {
+        let info = self.call(&SmcKeyData {
+            key: smc_key_code(key),
+            data8: SMC_CMD_READ_KEYINFO,
+            ..Default::default()
+        })?;
+
+        let data = self.call(&SmcKeyData {
+            key: smc_key_code(key),
+            key_info: SmcKeyInfo {
+                data_size: info.key_info.data_size,
+                ..Default::default()
+            },
+            data8: SMC_CMD_READ_BYTES,
+            ..Default::default()
+        })?;
+
+        let size = (info.key_info.data_size as usize).min(data.bytes.len());
+        decode_smc_value(&info.key_info.data_type.to_be_bytes(), &data.bytes[..size])
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Drop for SmcConnection {
+    fn drop(&mut self) {
+        unsafe {
+            IOServiceClose(self.connection);
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn decode_smc_value(data_type: &[u8; 4], bytes: &[u8]) -> Option<f64> {
+    match (data_type, bytes) {
+        // Apple Silicon reports most sensors as little-endian floats
+        (b"flt ", [a, b, c, d, ..]) => Some(f32::from_le_bytes([*a, *b, *c, *d]) as f64),
+        (b"sp78", [a, b, ..]) => Some(i16::from_be_bytes([*a, *b]) as f64 / 256.0),
+        (b"sp87", [a, b, ..]) => Some(i16::from_be_bytes([*a, *b]) as f64 / 128.0),
+        (b"sp96", [a, b, ..]) => Some(i16::from_be_bytes([*a, *b]) as f64 / 64.0),
+        (b"fpe2", [a, b, ..]) => Some(u16::from_be_bytes([*a, *b]) as f64 / 4.0),
+        (b"ui8 ", [a, ..]) => Some(*a as f64),
+        (b"ui16", [a, b, ..]) => Some(u16::from_be_bytes([*a, *b]) as f64),
+        (b"ui32", [a, b, c, d, ..]) => Some(u32::from_be_bytes([*a, *b, *c, *d]) as f64),
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn smc_sensors() -> Result<Vec<SensorReading>, String> {
+    let smc = SmcConnection::open()?;
+    let mut readings = Vec::new();
+
+    for (key, label) in SMC_TEMPERATURE_KEYS {
+        // Unpopulated sensors read as 0 or wildly out of range
+        if let Some(value) = smc.read(key).filter(|v| *v > 0.0 && *v < 150.0) {
+            readings.push(SensorReading::new(label, SensorKind::Temperature, value));
+        }
+    }
+
+    let fans = smc.read("FNum").unwrap_or(0.0) as usize;
+    for fan in 0..fans {
+        if let Some(rpm) = smc.read(&format!("F{}Ac", fan)) {
+            readings.push(SensorReading::new(
+                &format!("Fan {}", fan + 1),
+                SensorKind::Fan,
+                rpm,
+            ));
+        }
+    }
+
+    for (key, label) in SMC_POWER_KEYS {
+        if let Some(watts) = smc.read(key).filter(|v| *v >= 0.0) {
+            readings.push(SensorReading::new(label, SensorKind::Power, watts));
+        }
+    }
+
+    Ok(readings)
+}
@@ -0,0 +1,192 @@
+// Snapshot module for Crusty-Crawler
+// `snapshot` collects every check and metric once, prints them and exits with the overall
+// Nagios state, so the binary can run from cron or as a local check plugin
+
+#[derive(Clone, Copy, PartialEq)]
+enum SnapshotFormat {
+    Text,
+    Json,
+    Nagios,
+}
+
+impl SnapshotFormat {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "text" => Ok(SnapshotFormat::Text),
+            "json" => Ok(SnapshotFormat::Json),
+            "nagios" => Ok(SnapshotFormat::Nagios),
+            other => Err(format!(
+                "Unknown format '{}', expected json, text or nagios",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Snapshot {
+    host: String,
+    collected_at: String,
+    overall: CheckState,
+    checks: Vec<CheckResult>,
+    metrics: Vec<Metric>,
+}
+
+fn parse_snapshot_args(args: &[String]) -> Result<SnapshotFormat, String> {
+    let mut format = SnapshotFormat::Text;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" | "-f" => {
+                format = SnapshotFormat::parse(args.next().ok_or("--format needs a value")?)?
+            }
+            other => match other.strip_prefix("--format=") {
+                Some(value) => format = SnapshotFormat::parse(value)?,
+                None => return Err(format!("Unknown snapshot option '{}'", other)),
+            },
+        }
+    }
+    Ok(format)
+}
+
+async fn collect_snapshot(config: &AuthConfig) -> Snapshot {
+    let system = system_provider();
+    let mut checks = Vec::new();
+    for name in available_checks(&config.checks) {
+        if let Some(result) = run_check(
+            &name,
+            &config.checks,
+            &config.metrics.devices,
+            system.as_ref(),
+        )
+        .await
+        {
+            checks.push(result);
+        }
+    }
+
+    let mut metrics = collect_metrics(&config.metrics).await;
+    // No background collectors are running, so run each configured tool once
+    for collector in &config.metrics.collectors {
+        match run_collector(collector).await {
+            Ok(tool_metrics) => metrics.extend(tool_metrics),
+            Err(e) => eprintln!("❌ {} collector failed: {}", collector.parser.name(), e),
+        }
+    }
+
+    Snapshot {
+        host: sysinfo::System::host_name().unwrap_or_else(|| "unknown host".to_string()),
+        collected_at: chrono::Utc::now().to_rfc3339(),
+        overall: checks
+            .iter()
+            .map(|c| c.state)
+            .max()
+            .unwrap_or(CheckState::Ok),
+        checks,
+        metrics,
+    }
+}
+
+fn format_snapshot_text(snapshot: &Snapshot) -> String {
+    let mut out = format!(
+        "{} at {}: {}\n\nChecks:\n",
+        snapshot.host,
+        snapshot.collected_at,
+        snapshot.overall.label()
+    );
+    for check in &snapshot.checks {
+        out.push_str(&format!(
+            "  [{}] {}: {}\n",
+            check.state.label(),
+            check.name,
+            check.output
+        ));
+    }
+    out.push_str("\nMetrics:\n");
+    for metric in &snapshot.metrics {
+        out.push_str(&format!("  {}\n", metric));
+    }
+    out
+}
+
+// One summary line with every check's perfdata, then one long-output line per check
+fn format_snapshot_nagios(snapshot: &Snapshot) -> String {
+    let problems: Vec<&str> = snapshot
+        .checks
+        .iter()
+        .filter(|c| c.state != CheckState::Ok)
+        .map(|c| c.name.as_str())
+        .collect();
+    let mut summary = format!(
+        "CRUSTY {} - {} checks",
+        snapshot.overall.label(),
+        snapshot.checks.len()
+    );
+    if !problems.is_empty() {
+        summary.push_str(&format!(", problems: {}", problems.join(", ")));
+    }
+
+    // Prefixed with the check name so labels stay unique across checks
+    let perfdata: Vec<PerfData> = snapshot
+        .checks
+        .iter()
+        .flat_map(|check| {
+            check.perfdata.iter().map(move |perf| PerfData {
+                label: format!("{}_{}", check.name, perf.label),
+                ..perf.clone()
+            })
+        })
+        .collect();
+    if !perfdata.is_empty() {
+        summary.push_str(&format!(" | {}", format_perfdata(&perfdata)));
+    }
+
+    let mut out = summary;
+    for check in &snapshot.checks {
+        out.push_str(&format!(
+            "\n[{}] {}: {}",
+            check.state.label(),
+            check.name,
+            check.output
+        ));
+    }
+    out.push('\n');
+    out
+}
+
+// Returns the process exit code
+pub fn run_snapshot(args: &[String]) -> i32 {
+    let format = match parse_snapshot_args(args) {
+        Ok(format) => format,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            eprintln!("Usage: snapshot [--format json|text|nagios]");
+            return CheckState::Unknown.exit_code();
+        }
+    };
+
+    let rt = match Runtime::new() {
+        Ok(rt) => rt,
+        Err(e) => {
+            eprintln!("❌ Failed to start runtime: {}", e);
+            return CheckState::Unknown.exit_code();
+        }
+    };
+    // A snapshot shouldn't leave a config file behind
+    let config = AuthConfig::load_or_default(&data_file(AUTH_CONFIG_FILE));
+    let snapshot = rt.block_on(collect_snapshot(&config));
+
+    let output = match format {
+        SnapshotFormat::Text => format_snapshot_text(&snapshot),
+        SnapshotFormat::Nagios => format_snapshot_nagios(&snapshot),
+        SnapshotFormat::Json => match serde_json::to_string_pretty(&snapshot) {
+            Ok(json) => json + "\n",
+            Err(e) => {
+                eprintln!("❌ Failed to serialize snapshot: {}", e);
+                return CheckState::Unknown.exit_code();
+            }
+        },
+    };
+    print!("{}", output);
+    snapshot.overall.exit_code()
+}
@@ -0,0 +1,274 @@
+// Speed test module for Crusty-Crawler
+// Measures link throughput on a schedule against an iperf3 server or an HTTP download and
+// keeps the results, so a remote site can show when its ISP link got slower. Off by default,
+// every run uses real bandwidth
+
+const SPEED_TEST_FILE: &str = "crusty_speedtest.json";
+const MAX_SPEED_TEST_RESULTS: usize = 500;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeedTestMethod {
+    Iperf3,
+    Http,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SpeedTestConfig {
+    pub enabled: bool,
+    pub method: SpeedTestMethod,
+    pub iperf3_server: String,
+    pub iperf3_port: u16,
+    // A large file, the download stops after duration_secs or max_bytes
+    pub http_url: String,
+    pub interval_secs: u64,
+    // Cron expression, overrides interval_secs
+    pub schedule: Option<String>,
+    pub duration_secs: u64,
+    // Caps what one HTTP run downloads, for metered links
+    pub max_bytes: u64,
+}
+
+impl Default for SpeedTestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            method: SpeedTestMethod::Http,
+            iperf3_server: String::new(),
+            iperf3_port: 5201,
+            http_url: String::new(),
+            interval_secs: 6 * 60 * 60,
+            schedule: None,
+            duration_secs: 10,
+            max_bytes: 100 * 1024 * 1024,
+        }
+    }
+}
+
+impl SpeedTestConfig {
+    fn target(&self) -> Result<String, String> {
+        match self.method {
+            SpeedTestMethod::Iperf3 if self.iperf3_server.is_empty() => {
+                Err("speed_test.iperf3_server is not set".to_string())
+            }
+            SpeedTestMethod::Iperf3 => Ok(host_port(&self.iperf3_server, self.iperf3_port)),
+            SpeedTestMethod::Http if self.http_url.is_empty() => {
+                Err("speed_test.http_url is not set".to_string())
+            }
+            SpeedTestMethod::Http => Ok(self.http_url.clone()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SpeedTestResult {
+    pub timestamp: String,
+    pub method: SpeedTestMethod,
+    pub target: String,
+    #[serde(default)]
+    pub download_mbps: Option<f64>,
+    // HTTP tests only download
+    #[serde(default)]
+    pub upload_mbps: Option<f64>,
+    #[serde(default)]
+    pub latency_ms: Option<f64>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SpeedTestHistory {
+    pub enabled: bool,
+    pub results: Vec<SpeedTestResult>,
+}
+
+static SPEED_TEST_RESULTS: Mutex<Option<VecDeque<SpeedTestResult>>> = Mutex::new(None);
+// A manual run and a scheduled one at the same time would each measure half the link
+static SPEED_TEST_RUNNING: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+fn with_speed_test_results<T>(f: impl FnOnce(&mut VecDeque<SpeedTestResult>) -> T) -> T {
+    let mut results = SPEED_TEST_RESULTS.lock().unwrap();
+    let results = results.get_or_insert_with(|| {
+        fs::read_to_string(data_path(SPEED_TEST_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    });
+    f(results)
+}
+
+fn store_speed_test_result(result: SpeedTestResult) {
+    with_speed_test_results(|results| {
+        if results.len() == MAX_SPEED_TEST_RESULTS {
+            results.pop_front();
+        }
+        results.push_back(result);
+        let saved = serde_json::to_string(&*results)
+            .map_err(|e| e.to_string())
+            .and_then(|data| {
+                fs::write(data_path(SPEED_TEST_FILE), data).map_err(|e| e.to_string())
+            });
+        if let Err(e) = saved {
+            eprintln!(
+                "⚠️  Failed to save speed test results to {}: {}",
+                SPEED_TEST_FILE, e
+            );
+        }
+    });
+}
+
+pub fn speed_test_history() -> Vec<SpeedTestResult> {
+    with_speed_test_results(|results| results.iter().cloned().collect())
+}
+
+// One iperf3 run in JSON mode, bits per second as measured by the receiving side and the
+// sender's mean RTT when the platform reports it
+async fn iperf3_run(config: &SpeedTestConfig, reverse: bool) -> Result<(f64, Option<f64>), String> {
+    let mut command = tokio::process::Command::new("iperf3");
+    command
+        .args(["-c", bare_host(&config.iperf3_server)])
+        .args(["-p", &config.iperf3_port.to_string()])
+        .args(["-t", &config.duration_secs.to_string()])
+        .arg("-J");
+    if reverse {
+        command.arg("-R");
+    }
+    let output = tokio::time::timeout(
+        Duration::from_secs(config.duration_secs + 30),
+        command.output(),
+    )
+    .await
+    .map_err(|_| "iperf3 did not finish".to_string())?
+    .map_err(|e| format!("Failed to run iperf3: {}", e))?;
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).map_err(|_| {
+        format!(
+            "Unexpected iperf3 output: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+    })?;
+    if let Some(error) = report["error"].as_str() {
+        return Err(format!("iperf3: {}", error));
+    }
+    let bits_per_second = report["end"]["sum_received"]["bits_per_second"]
+        .as_f64()
+        .ok_or("iperf3 reported no throughput")?;
+    // Microseconds
+    let rtt = report["end"]["streams"][0]["sender"]["mean_rtt"]
+        .as_f64()
+        .map(|rtt| rtt / 1000.0);
+    Ok((bits_per_second / 1_000_000.0, rtt))
+}
+
+async fn iperf3_test(config: &SpeedTestConfig, result: &mut SpeedTestResult) -> Result<(), String> {
+    let (upload, rtt) = iperf3_run(config, false).await?;
+    result.upload_mbps = Some(upload);
+    result.latency_ms = rtt;
+    let (download, _) = iperf3_run(config, true).await?;
+    result.download_mbps = Some(download);
+    Ok(())
+}
+
+// Time to the response headers stands in for latency
+async fn http_test(config: &SpeedTestConfig, result: &mut SpeedTestResult) -> Result<(), String> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.duration_secs + 30))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let started = Instant::now();
+    let mut response = client
+        .get(&config.http_url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+    result.latency_ms = Some(started.elapsed().as_secs_f64() * 1000.0);
+
+    let started = Instant::now();
+    let deadline = Duration::from_secs(config.duration_secs);
+    let mut bytes = 0u64;
+    while started.elapsed() < deadline && bytes < config.max_bytes {
+        match response.chunk().await.map_err(|e| e.to_string())? {
+            Some(chunk) => bytes += chunk.len() as u64,
+            None => break,
+        }
+    }
+    let elapsed = started.elapsed().as_secs_f64().max(0.001);
+    // Too little data says more about the file than about the link
+    if bytes < 1024 * 1024 {
+        return Err(format!(
+            "Only {} bytes in {:.1}s, use a larger file for speed tests",
+            bytes, elapsed
+        ));
+    }
+    result.download_mbps = Some(bytes as f64 * 8.0 / elapsed / 1_000_000.0);
+    Ok(())
+}
+
+// Runs one test and keeps the result, failures included so gaps in the history have a reason
+pub async fn run_speed_test(config: &SpeedTestConfig) -> Result<SpeedTestResult, String> {
+    let target = config.target()?;
+    if SPEED_TEST_RUNNING.swap(true, std::sync::atomic::Ordering::SeqCst) {
+        return Err("A speed test is already running".to_string());
+    }
+
+    let mut result = SpeedTestResult {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        method: config.method,
+        target,
+        download_mbps: None,
+        upload_mbps: None,
+        latency_ms: None,
+        error: None,
+    };
+    let outcome = match config.method {
+        SpeedTestMethod::Iperf3 => iperf3_test(config, &mut result).await,
+        SpeedTestMethod::Http => http_test(config, &mut result).await,
+    };
+    SPEED_TEST_RUNNING.store(false, std::sync::atomic::Ordering::SeqCst);
+
+    if let Err(e) = outcome {
+        eprintln!("⚠️  Speed test against {} failed: {}", result.target, e);
+        result.error = Some(e);
+    }
+    store_speed_test_result(result.clone());
+    Ok(result)
+}
+
+pub fn speed_test_metrics() -> Vec<Metric> {
+    let Some(last) = with_speed_test_results(|results| results.back().cloned()) else {
+        return Vec::new();
+    };
+    let mut metrics = Vec::new();
+    if let Ok(timestamp) = chrono::DateTime::parse_from_rfc3339(&last.timestamp) {
+        metrics.push(Metric::new(
+            "speedtest_last_run_timestamp_seconds",
+            timestamp.timestamp() as f64,
+        ));
+    }
+    metrics.push(Metric::new(
+        "speedtest_last_run_failed",
+        if last.error.is_some() { 1.0 } else { 0.0 },
+    ));
+    for (name, value) in [
+        ("speedtest_download_mbps", last.download_mbps),
+        ("speedtest_upload_mbps", last.upload_mbps),
+        ("speedtest_latency_ms", last.latency_ms),
+    ] {
+        if let Some(value) = value {
+            metrics.push(Metric::new(name, value).label("target", &last.target));
+        }
+    }
+    metrics
+}
+
+// When the newest stored result was taken, the schedule follows it so a restart doesn't trigger
+// an extra run
+pub fn latest_speed_test_time() -> Option<chrono::DateTime<chrono::Utc>> {
+    let last = with_speed_test_results(|results| results.back().map(|r| r.timestamp.clone()))?;
+    chrono::DateTime::parse_from_rfc3339(&last)
+        .ok()
+        .map(|last| last.with_timezone(&chrono::Utc))
+}
@@ -0,0 +1,265 @@
+// StatsD module for Crusty-Crawler
+// Optional UDP listener that aggregates StatsD counters, gauges, timers and sets from local
+// applications and publishes them with the system metrics, so every exporter relays them
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct StatsdConfig {
+    pub enabled: bool,
+    // Keep this on loopback unless other hosts should be able to push metrics
+    pub bind: String,
+    // Timers and sets are summarised over this window, counters and gauges keep running
+    pub flush_interval_secs: u64,
+}
+
+impl Default for StatsdConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: "127.0.0.1:8125".to_string(),
+            flush_interval_secs: 10,
+        }
+    }
+}
+
+// Like MAX_CUSTOM_SERIES, a misbehaving sender can't grow memory and the scrape without limit
+const MAX_STATSD_SERIES: usize = 1000;
+// Distinct set members counted per flush window
+const MAX_STATSD_SET_MEMBERS: usize = 10000;
+// Series nobody sent to for this long are dropped at the next flush
+const STATSD_SERIES_TTL_SECS: u64 = 3600;
+
+type StatsdKey = (String, BTreeMap<String, String>);
+
+#[derive(Default)]
+struct StatsdAggregator {
+    counters: BTreeMap<StatsdKey, f64>,
+    gauges: BTreeMap<StatsdKey, f64>,
+    timers: BTreeMap<StatsdKey, Vec<f64>>,
+    sets: BTreeMap<StatsdKey, std::collections::HashSet<String>>,
+    invalid_lines: u64,
+    // Timer and set summaries of the last complete flush window
+    flushed: Vec<Metric>,
+    // When each series was last sent to, also what MAX_STATSD_SERIES counts
+    updated: HashMap<StatsdKey, Instant>,
+}
+
+static STATSD: Mutex<Option<StatsdAggregator>> = Mutex::new(None);
+
+// name:value|type[|@rate][|#tag:value,...], the tag section is the DogStatsD extension
+fn parse_statsd_line(line: &str) -> Result<(StatsdKey, String, char, f64), String> {
+    let (name, rest) = line.split_once(':').ok_or("missing ':'")?;
+    let mut fields = rest.split('|');
+    let value = fields.next().unwrap_or_default().to_string();
+    let kind = match fields.next() {
+        Some("c") => 'c',
+        Some("g") => 'g',
+        Some("ms") | Some("h") | Some("d") => 't',
+        Some("s") => 's',
+        other => return Err(format!("unsupported type {:?}", other.unwrap_or_default())),
+    };
+
+    let mut rate = 1.0;
+    let mut tags = BTreeMap::new();
+    for field in fields {
+        if let Some(sample_rate) = field.strip_prefix('@') {
+            rate = sample_rate
+                .parse::<f64>()
+                .ok()
+                .filter(|r| *r > 0.0 && *r <= 1.0)
+                .ok_or("invalid sample rate")?;
+        } else if let Some(tag_list) = field.strip_prefix('#') {
+            for tag in tag_list.split(',').filter(|tag| !tag.is_empty()) {
+                let (key, value) = tag.split_once(':').unwrap_or((tag, ""));
+                tags.insert(prometheus_name(key), value.to_string());
+            }
+        }
+    }
+
+    let name = format!("statsd_{}", prometheus_name(name.trim()));
+    Ok(((name, tags), value, kind, rate))
+}
+
+impl StatsdAggregator {
+    fn record(&mut self, line: &str) -> Result<(), String> {
+        let (key, value, kind, rate) = parse_statsd_line(line)?;
+        if !self.updated.contains_key(&key) && self.updated.len() >= MAX_STATSD_SERIES {
+            return Err(format!("more than {} series", MAX_STATSD_SERIES));
+        }
+        if kind == 's' {
+            let members = self.sets.entry(key.clone()).or_default();
+            if !members.contains(&value) && members.len() >= MAX_STATSD_SET_MEMBERS {
+                return Err(format!("more than {} set members", MAX_STATSD_SET_MEMBERS));
+            }
+            members.insert(value);
+            self.updated.insert(key, Instant::now());
+            return Ok(());
+        }
+
+        let number: f64 = value
+            .parse()
+            .ok()
+            .filter(|number: &f64| number.is_finite())
+            .ok_or_else(|| format!("invalid value '{}'", value))?;
+        self.updated.insert(key.clone(), Instant::now());
+        match kind {
+            'c' => *self.counters.entry(key).or_default() += number / rate,
+            // A leading sign adjusts the gauge instead of setting it
+            'g' if value.starts_with(['+', '-']) => *self.gauges.entry(key).or_default() += number,
+            'g' => {
+                self.gauges.insert(key, number);
+            }
+            _ => self.timers.entry(key).or_default().push(number),
+        }
+        Ok(())
+    }
+
+    // Series stay after going quiet and report a count of 0, like StatsD does by default, until
+    // STATSD_SERIES_TTL_SECS without anything sent to them
+    fn flush(&mut self) {
+        let ttl = Duration::from_secs(STATSD_SERIES_TTL_SECS);
+        self.updated.retain(|_, updated| updated.elapsed() < ttl);
+        let updated = &self.updated;
+        self.counters.retain(|key, _| updated.contains_key(key));
+        self.gauges.retain(|key, _| updated.contains_key(key));
+        self.timers.retain(|key, _| updated.contains_key(key));
+        self.sets.retain(|key, _| updated.contains_key(key));
+
+        let mut flushed = Vec::new();
+        for ((name, tags), samples) in self.timers.iter_mut() {
+            let mut samples = std::mem::take(samples);
+            if samples.is_empty() {
+                flushed.push(Metric {
+                    name: format!("{}_count", name),
+                    value: 0.0,
+                    labels: tags.clone(),
+                });
+                continue;
+            }
+            samples.sort_by(|a, b| a.total_cmp(b));
+            let count = samples.len();
+            let p95 = samples[((count as f64 * 0.95).ceil() as usize).clamp(1, count) - 1];
+            let stats = [
+                ("count", count as f64),
+                ("min", samples[0]),
+                ("max", samples[count - 1]),
+                ("mean", samples.iter().sum::<f64>() / count as f64),
+                ("p95", p95),
+            ];
+            for (stat, value) in stats {
+                flushed.push(Metric {
+                    name: format!("{}_{}", name, stat),
+                    value,
+                    labels: tags.clone(),
+                });
+            }
+        }
+        for ((name, tags), values) in self.sets.iter_mut() {
+            flushed.push(Metric {
+                name: format!("{}_unique", name),
+                value: values.len() as f64,
+                labels: tags.clone(),
+            });
+            values.clear();
+        }
+        self.flushed = flushed;
+    }
+
+    fn metrics(&self) -> Vec<Metric> {
+        let counters = self.counters.iter().map(|((name, tags), value)| Metric {
+            name: format!("{}_total", name),
+            value: *value,
+            labels: tags.clone(),
+        });
+        let gauges = self.gauges.iter().map(|((name, tags), value)| Metric {
+            name: name.clone(),
+            value: *value,
+            labels: tags.clone(),
+        });
+        counters
+            .chain(gauges)
+            .chain(self.flushed.iter().cloned())
+            .chain(std::iter::once(Metric::new(
+                "statsd_invalid_lines_total",
+                self.invalid_lines as f64,
+            )))
+            .collect()
+    }
+}
+
+// Empty unless the listener is running
+pub fn statsd_metrics() -> Vec<Metric> {
+    STATSD
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|aggregator| aggregator.metrics())
+        .unwrap_or_default()
+}
+
+// The bind address is read once at startup, changing it needs a restart
+fn spawn_statsd_listener(server_state: Arc<Mutex<ServerState>>) {
+    let config = {
+        let state = server_state.lock().unwrap();
+        let auth_manager = state.auth_manager.lock().unwrap();
+        auth_manager.config.metrics.statsd.clone()
+    };
+    if !config.enabled {
+        return;
+    }
+
+    std::thread::spawn(move || {
+        let rt = match Runtime::new() {
+            Ok(rt) => rt,
+            Err(e) => {
+                eprintln!("❌ Failed to start StatsD listener: {}", e);
+                return;
+            }
+        };
+
+        rt.block_on(async {
+            let socket = match tokio::net::UdpSocket::bind(&config.bind).await {
+                Ok(socket) => socket,
+                Err(e) => {
+                    eprintln!(
+                        "❌ Failed to bind StatsD listener to {}: {}",
+                        config.bind, e
+                    );
+                    return;
+                }
+            };
+            *STATSD.lock().unwrap() = Some(StatsdAggregator::default());
+            println!("📥 StatsD listener on udp://{}", config.bind);
+
+            let mut flush =
+                tokio::time::interval(Duration::from_secs(config.flush_interval_secs.max(1)));
+            let mut buffer = vec![0u8; 65536];
+            loop {
+                tokio::select! {
+                    received = socket.recv_from(&mut buffer) => {
+                        let Ok((len, _)) = received else {
+                            continue;
+                        };
+                        let packet = String::from_utf8_lossy(&buffer[..len]);
+                        let mut statsd = STATSD.lock().unwrap();
+                        let Some(aggregator) = statsd.as_mut() else {
+                            continue;
+                        };
+                        for line in packet.lines().filter(|line| !line.trim().is_empty()) {
+                            // Counted rather than logged, one chatty application shouldn't
+                            // flood the log
+                            if aggregator.record(line.trim()).is_err() {
+                                aggregator.invalid_lines += 1;
+                            }
+                        }
+                    }
+                    _ = flush.tick() => {
+                        if let Some(aggregator) = STATSD.lock().unwrap().as_mut() {
+                            aggregator.flush();
+                        }
+                    }
+                }
+            }
+        });
+    });
+}
@@ -0,0 +1,275 @@
+// Status pages module for Crusty-Crawler
+// Renders the web status page from Tera templates so admins can build their own views,
+// one template per URL under /status/<name>
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct StatusPageConfig {
+    // Templates here override the built-in ones or add new pages, e.g. noc.html -> /status/noc
+    pub template_dir: String,
+    pub refresh_secs: u64,
+    pub public: PublicStatusConfig,
+}
+
+impl Default for StatusPageConfig {
+    fn default() -> Self {
+        Self {
+            template_dir: "templates".to_string(),
+            refresh_secs: 10,
+            public: PublicStatusConfig::default(),
+        }
+    }
+}
+
+// Unauthenticated page at /public, only ever shows what is listed here
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PublicStatusConfig {
+    pub enabled: bool,
+    // Shown instead of the host name
+    pub title: String,
+    // Metric names to publish
+    pub metrics: Vec<String>,
+    // Checks whose state (not output) is published, the overall health always is
+    pub checks: Vec<String>,
+}
+
+impl Default for PublicStatusConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            title: "System Status".to_string(),
+            metrics: vec![
+                "cpu_usage_percent".to_string(),
+                "memory_used_bytes".to_string(),
+                "memory_total_bytes".to_string(),
+            ],
+            checks: Vec::new(),
+        }
+    }
+}
+
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("default", include_str!("../templates/default.html")),
+    // Big numbers for NOC screens
+    ("wall", include_str!("../templates/wall.html")),
+    ("public", include_str!("../templates/public.html")),
+];
+
+#[derive(Serialize)]
+struct StatusPageCheck {
+    name: String,
+    state: CheckState,
+    output: String,
+}
+
+fn load_template(config: &StatusPageConfig, name: &str) -> Option<String> {
+    // Template names end up in a file path
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+
+    fs::read_to_string(resource_dir(&config.template_dir).join(format!("{}.html", name)))
+        .ok()
+        .or_else(|| {
+            BUILTIN_TEMPLATES
+                .iter()
+                .find(|(builtin, _)| *builtin == name)
+                .map(|(_, template)| template.to_string())
+        })
+}
+
+// Everything a template can use: host, generated_at, refresh_secs, overall, problems,
+// health_score, checks, metrics, values (unlabelled metrics by name) and memory_percent
+async fn status_page_context(
+    runner: &CheckRunner,
+    metrics_config: &MetricsConfig,
+    config: &StatusPageConfig,
+) -> tera::Context {
+    let max_age = Duration::from_secs(runner.config.cache_max_age_secs);
+    let mut results = Vec::new();
+    for name in available_checks(&runner.config) {
+        if let Some((result, _)) = runner.cached(&name, max_age).await {
+            results.push(result);
+        }
+    }
+    let health_score = compute_health_score(
+        &runner.config.health_score,
+        &results,
+        &resource_usage(&runner.devices),
+    );
+    let checks: Vec<StatusPageCheck> = results
+        .into_iter()
+        .map(|result| StatusPageCheck {
+            name: result.name,
+            state: result.state,
+            output: result.output,
+        })
+        .collect();
+
+    let metrics = collect_metrics(metrics_config).await;
+    let mut values = BTreeMap::new();
+    for metric in metrics.iter().filter(|m| m.labels.is_empty()) {
+        values.entry(metric.name.clone()).or_insert(metric.value);
+    }
+    let memory_percent = match (
+        values.get("memory_used_bytes"),
+        values.get("memory_total_bytes"),
+    ) {
+        (Some(used), Some(total)) if *total > 0.0 => used / total * 100.0,
+        _ => 0.0,
+    };
+
+    let overall = checks
+        .iter()
+        .map(|c| c.state)
+        .max()
+        .unwrap_or(CheckState::Ok);
+    let problems = checks.iter().filter(|c| c.state != CheckState::Ok).count();
+
+    let mut context = tera::Context::new();
+    context.insert(
+        "host",
+        &sysinfo::System::host_name().unwrap_or_else(|| "unknown host".to_string()),
+    );
+    context.insert(
+        "generated_at",
+        &chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    );
+    context.insert("refresh_secs", &config.refresh_secs.max(1));
+    context.insert("overall", &overall);
+    context.insert("problems", &problems);
+    context.insert("health_score", &health_score.score);
+    context.insert("checks", &checks);
+    context.insert("metrics", &metrics);
+    context.insert("values", &values);
+    context.insert("memory_percent", &memory_percent);
+    context
+}
+
+fn format_uptime(seconds: u64) -> String {
+    let days = seconds / 86400;
+    let hours = seconds % 86400 / 3600;
+    let minutes = seconds % 3600 / 60;
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else {
+        format!("{}h {}m", hours, minutes)
+    }
+}
+
+// The public page's metrics are collected at most this often, so anonymous visitors can't make
+// the host run its collectors, and the programs some of them start, at request rate
+const PUBLIC_METRICS_TTL_SECS: u64 = 30;
+
+// Held while collecting, so visitors arriving together wait for one collection
+static PUBLIC_METRICS: tokio::sync::Mutex<Option<(Instant, Vec<Metric>)>> =
+    tokio::sync::Mutex::const_new(None);
+
+async fn public_metrics(metrics_config: &MetricsConfig) -> Vec<Metric> {
+    let mut cached = PUBLIC_METRICS.lock().await;
+    if let Some((collected_at, metrics)) = cached.as_ref()
+        && collected_at.elapsed() < Duration::from_secs(PUBLIC_METRICS_TTL_SECS)
+    {
+        return metrics.clone();
+    }
+    let metrics = collect_metrics(metrics_config).await;
+    *cached = Some((Instant::now(), metrics.clone()));
+    metrics
+}
+
+// Only the overall state, uptime and the admin's allowlisted checks and metrics
+async fn public_status_context(
+    runner: &CheckRunner,
+    metrics_config: &MetricsConfig,
+    config: &StatusPageConfig,
+) -> tera::Context {
+    let public = &config.public;
+    let max_age = Duration::from_secs(runner.config.cache_max_age_secs);
+
+    let mut overall = CheckState::Ok;
+    let mut checks = Vec::new();
+    for name in available_checks(&runner.config) {
+        if let Some((result, _)) = runner.cached(&name, max_age).await {
+            overall = overall.max(result.state);
+            if public.checks.contains(&result.name) {
+                checks.push(StatusPageCheck {
+                    name: result.name,
+                    state: result.state,
+                    output: String::new(),
+                });
+            }
+        }
+    }
+
+    let metrics: Vec<Metric> = public_metrics(metrics_config)
+        .await
+        .into_iter()
+        .filter(|m| public.metrics.contains(&m.name))
+        .collect();
+    let mut values = BTreeMap::new();
+    for metric in metrics.iter().filter(|m| m.labels.is_empty()) {
+        values.entry(metric.name.clone()).or_insert(metric.value);
+    }
+
+    let mut context = tera::Context::new();
+    context.insert("title", &public.title);
+    context.insert(
+        "generated_at",
+        &chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    );
+    context.insert("refresh_secs", &config.refresh_secs.max(1));
+    context.insert("uptime", &format_uptime(sysinfo::System::uptime()));
+    context.insert("overall", &overall);
+    context.insert("checks", &checks);
+    context.insert("metrics", &metrics);
+    context.insert("values", &values);
+    context
+}
+
+fn render_template(
+    name: &str,
+    template: &str,
+    context: &tera::Context,
+) -> Result<String, StatusCode> {
+    tera::Tera::one_off(template, context, true).map_err(|e| {
+        eprintln!(
+            "❌ Failed to render status page {}: {}",
+            name,
+            tera_error_message(&e)
+        );
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+pub async fn render_public_status_page(
+    runner: &CheckRunner,
+    metrics_config: &MetricsConfig,
+    config: &StatusPageConfig,
+) -> Result<String, StatusCode> {
+    if !config.public.enabled {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let template = load_template(config, "public").ok_or(StatusCode::NOT_FOUND)?;
+    let context = public_status_context(runner, metrics_config, config).await;
+    render_template("public", &template, &context)
+}
+
+pub async fn render_status_page(
+    runner: &CheckRunner,
+    metrics_config: &MetricsConfig,
+    config: &StatusPageConfig,
+    name: &str,
+) -> Result<String, StatusCode> {
+    // Served at /public with its own context
+    if name == "public" {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let template = load_template(config, name).ok_or(StatusCode::NOT_FOUND)?;
+    let context = status_page_context(runner, metrics_config, config).await;
+    render_template(name, &template, &context)
+}
@@ -0,0 +1,199 @@
+// Stream module for Crusty-Crawler
+// Live overview for dashboards over server-sent events. One broadcaster builds the overview,
+// serializes it and its delta against the last one once, and every viewer gets the same shared
+// copy. Each viewer has a small queue; one that can't keep up loses its oldest messages and is
+// sent a full snapshot instead of the deltas it missed
+
+use axum::response::sse::{Event as SseEvent, KeepAlive, Sse};
+use tokio::sync::broadcast::error::RecvError;
+
+const STREAM_INTERVAL: Duration = Duration::from_secs(5);
+// Messages queued per viewer before the oldest are dropped
+const STREAM_BUFFER: usize = 16;
+
+static STREAM_CLIENTS: AtomicU64 = AtomicU64::new(0);
+static STREAM_DROPPED: AtomicU64 = AtomicU64::new(0);
+static STREAM_SENDER: std::sync::OnceLock<tokio::sync::broadcast::Sender<Arc<StreamMessage>>> =
+    std::sync::OnceLock::new();
+// Where new viewers start, cleared while nobody is watching
+static LATEST_STREAM_MESSAGE: Mutex<Option<Arc<StreamMessage>>> = Mutex::new(None);
+
+pub struct StreamMessage {
+    pub id: u64,
+    pub snapshot: Arc<str>,
+    // JSON merge patch (RFC 7386) from the previous message, None for the first one
+    pub delta: Option<Arc<str>>,
+}
+
+fn stream_sender() -> &'static tokio::sync::broadcast::Sender<Arc<StreamMessage>> {
+    STREAM_SENDER.get_or_init(|| tokio::sync::broadcast::channel(STREAM_BUFFER).0)
+}
+
+// What changed from `previous` to `current` as a merge patch, None when nothing did. Removed keys
+// are null and arrays are replaced whole
+pub fn json_delta(
+    previous: &serde_json::Value,
+    current: &serde_json::Value,
+) -> Option<serde_json::Value> {
+    use serde_json::Value;
+
+    let (Value::Object(previous), Value::Object(current)) = (previous, current) else {
+        return (previous != current).then(|| current.clone());
+    };
+    let mut delta = serde_json::Map::new();
+    for (key, value) in current {
+        match previous.get(key) {
+            Some(old) => {
+                if let Some(changed) = json_delta(old, value) {
+                    delta.insert(key.clone(), changed);
+                }
+            }
+            None => {
+                delta.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    for key in previous.keys() {
+        if !current.contains_key(key) {
+            delta.insert(key.clone(), Value::Null);
+        }
+    }
+    (!delta.is_empty()).then_some(Value::Object(delta))
+}
+
+// Builds the next message, None when the overview didn't change
+fn stream_message(
+    id: u64,
+    previous: Option<&serde_json::Value>,
+    current: &serde_json::Value,
+) -> Option<StreamMessage> {
+    let delta = match previous {
+        Some(previous) => Some(json_delta(previous, current)?.to_string().into()),
+        None => None,
+    };
+    Some(StreamMessage {
+        id,
+        snapshot: current.to_string().into(),
+        delta,
+    })
+}
+
+fn spawn_dashboard_stream(server_state: Arc<Mutex<ServerState>>) {
+    std::thread::spawn(move || {
+        let rt = match Runtime::new() {
+            Ok(rt) => rt,
+            Err(e) => {
+                eprintln!("❌ Failed to start the dashboard stream: {}", e);
+                return;
+            }
+        };
+
+        rt.block_on(async {
+            let mut id = 0;
+            let mut previous: Option<serde_json::Value> = None;
+            loop {
+                tokio::time::sleep(STREAM_INTERVAL).await;
+                if STREAM_CLIENTS.load(Ordering::Relaxed) == 0 {
+                    previous = None;
+                    *LATEST_STREAM_MESSAGE.lock().unwrap() = None;
+                    continue;
+                }
+
+                let runner = CheckRunner::from_state(&server_state.lock().unwrap());
+                let Ok(current) = serde_json::to_value(overview(&runner).await) else {
+                    continue;
+                };
+                let Some(message) = stream_message(id + 1, previous.as_ref(), &current) else {
+                    continue;
+                };
+                id = message.id;
+                let message = Arc::new(message);
+                *LATEST_STREAM_MESSAGE.lock().unwrap() = Some(message.clone());
+                // Only fails without receivers, the next viewer starts from the latest message
+                let _ = stream_sender().send(message);
+                previous = Some(current);
+            }
+        });
+    });
+}
+
+// One viewer's place in the stream, counted in dashboard_stream_clients while it's open
+struct StreamClient {
+    receiver: tokio::sync::broadcast::Receiver<Arc<StreamMessage>>,
+    latest: Option<Arc<StreamMessage>>,
+    // Zero until the first message, so that one is always a snapshot
+    last_id: u64,
+}
+
+impl StreamClient {
+    fn new() -> Self {
+        STREAM_CLIENTS.fetch_add(1, Ordering::Relaxed);
+        // Subscribed first so nothing sent after the latest message is missed
+        let receiver = stream_sender().subscribe();
+        Self {
+            receiver,
+            latest: LATEST_STREAM_MESSAGE.lock().unwrap().clone(),
+            last_id: 0,
+        }
+    }
+
+    async fn next_message(&mut self) -> Option<Arc<StreamMessage>> {
+        if let Some(latest) = self.latest.take() {
+            return Some(latest);
+        }
+        loop {
+            match self.receiver.recv().await {
+                Ok(message) if message.id > self.last_id => return Some(message),
+                Ok(_) => {}
+                // The ids skip ahead, so the next message goes out as a snapshot
+                Err(RecvError::Lagged(skipped)) => {
+                    STREAM_DROPPED.fetch_add(skipped, Ordering::Relaxed);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    async fn next_event(&mut self) -> Option<SseEvent> {
+        let message = self.next_message().await?;
+        // A delta only applies to the message right before it
+        let event = match &message.delta {
+            Some(delta) if message.id == self.last_id + 1 => {
+                SseEvent::default().event("delta").data(&**delta)
+            }
+            _ => SseEvent::default()
+                .event("snapshot")
+                .data(&*message.snapshot),
+        };
+        self.last_id = message.id;
+        Some(event.id(message.id.to_string()))
+    }
+}
+
+impl Drop for StreamClient {
+    fn drop(&mut self) {
+        STREAM_CLIENTS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub fn dashboard_stream()
+-> Sse<impl futures_util::Stream<Item = Result<SseEvent, std::convert::Infallible>>> {
+    let events = futures_util::stream::unfold(StreamClient::new(), |mut client| async move {
+        let event = client.next_event().await?;
+        Some((Ok(event), client))
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+pub fn dashboard_stream_metrics() -> Vec<Metric> {
+    vec![
+        Metric::new(
+            "dashboard_stream_clients",
+            STREAM_CLIENTS.load(Ordering::Relaxed) as f64,
+        ),
+        Metric::new(
+            "dashboard_stream_dropped_total",
+            STREAM_DROPPED.load(Ordering::Relaxed) as f64,
+        ),
+    ]
+}
@@ -0,0 +1,198 @@
+// Templates module for Crusty-Crawler
+// Tera templates for alert notifications. Every channel ships with a default and admins can
+// override any of them under notification_templates in the config, using the alert's host,
+// check, state, message, value, threshold and duration. Value and threshold come with one
+// decimal like the check output, so 97.0 doesn't render as 97
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TemplateKind {
+    // Email subject and Pushover title
+    Subject,
+    Email,
+    Pushover,
+    Telegram,
+    Sms,
+    // Must render to JSON, use `| json_encode()` for strings
+    Webhook,
+}
+
+const TEMPLATE_KINDS: [TemplateKind; 6] = [
+    TemplateKind::Subject,
+    TemplateKind::Email,
+    TemplateKind::Pushover,
+    TemplateKind::Telegram,
+    TemplateKind::Sms,
+    TemplateKind::Webhook,
+];
+
+const DEFAULT_SUBJECT_TEMPLATE: &str = "[Crusty] {{ state }} {{ check }} on {{ host }}";
+const DEFAULT_EMAIL_TEMPLATE: &str = "{{ check }} on {{ host }} is {{ state }}.
+
+{{ message }}
+{%- if value %}
+Value: {{ value }}{% if threshold %} (threshold {{ threshold }}){% endif %}
+{%- endif %}
+
+{% if resolved %}Resolved after {{ duration }}.{% else %}Raised at {{ raised_at }}, {{ duration }} ago.{% endif %}
+";
+const DEFAULT_PUSHOVER_TEMPLATE: &str = "{{ message }}";
+const DEFAULT_TELEGRAM_TEMPLATE: &str =
+    "[Crusty] {{ state }} {{ check }} on {{ host }}\n{{ message }}";
+const DEFAULT_SMS_TEMPLATE: &str = "[Crusty] {{ state }} {{ check }} on {{ host }}: {{ message }}";
+const DEFAULT_WEBHOOK_TEMPLATE: &str = r#"{
+  "host": {{ host | json_encode() }},
+  "check": {{ check | json_encode() }},
+  "state": {{ state | json_encode() }},
+  "message": {{ message | json_encode() }},
+  "value": {% if value %}{{ value }}{% else %}null{% endif %},
+  "threshold": {% if threshold %}{{ threshold }}{% else %}null{% endif %},
+  "raised_at": {{ raised_at | json_encode() }},
+  "duration_secs": {{ duration_secs }},
+  "resolved": {{ resolved }}
+}"#;
+
+impl TemplateKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TemplateKind::Subject => "subject",
+            TemplateKind::Email => "email",
+            TemplateKind::Pushover => "pushover",
+            TemplateKind::Telegram => "telegram",
+            TemplateKind::Sms => "sms",
+            TemplateKind::Webhook => "webhook",
+        }
+    }
+
+    fn default_template(&self) -> &'static str {
+        match self {
+            TemplateKind::Subject => DEFAULT_SUBJECT_TEMPLATE,
+            TemplateKind::Email => DEFAULT_EMAIL_TEMPLATE,
+            TemplateKind::Pushover => DEFAULT_PUSHOVER_TEMPLATE,
+            TemplateKind::Telegram => DEFAULT_TELEGRAM_TEMPLATE,
+            TemplateKind::Sms => DEFAULT_SMS_TEMPLATE,
+            TemplateKind::Webhook => DEFAULT_WEBHOOK_TEMPLATE,
+        }
+    }
+}
+
+// Overrides of the shipped templates, None uses the default
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct NotificationTemplates {
+    pub subject: Option<String>,
+    pub email: Option<String>,
+    pub pushover: Option<String>,
+    pub telegram: Option<String>,
+    pub sms: Option<String>,
+    pub webhook: Option<String>,
+}
+
+// Tera hides the useful part of the message in the error's source
+fn tera_error_message(e: &tera::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    message
+}
+
+fn format_alert_duration(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+    let (days, hours, minutes) = (seconds / 86400, seconds % 86400 / 3600, seconds % 3600 / 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+pub fn alert_template_context(
+    alert: &Alert,
+    host: &str,
+    now: chrono::DateTime<chrono::Utc>,
+) -> tera::Context {
+    let parse = |timestamp: &str| {
+        chrono::DateTime::parse_from_rfc3339(timestamp)
+            .map(|t| t.with_timezone(&chrono::Utc))
+            .ok()
+    };
+    let ended = alert.resolved_at.as_deref().and_then(parse).unwrap_or(now);
+    let duration_secs = parse(&alert.raised_at).map_or(0, |raised| (ended - raised).num_seconds());
+
+    let mut context = tera::Context::new();
+    context.insert("host", host);
+    context.insert("check", &alert.check);
+    context.insert("state", alert.state.label());
+    context.insert("message", &alert.message);
+    let decimal = |number: Option<f64>| number.map(|number| format!("{:.1}", number));
+    context.insert("value", &decimal(alert.value));
+    context.insert("threshold", &decimal(alert.threshold));
+    context.insert("raised_at", &format_timestamp(&alert.raised_at));
+    context.insert("duration", &format_alert_duration(duration_secs));
+    context.insert("duration_secs", &duration_secs.max(0));
+    context.insert("resolved", &alert.resolved_at.is_some());
+    context
+}
+
+impl NotificationTemplates {
+    pub fn template(&self, kind: TemplateKind) -> &str {
+        let custom = match kind {
+            TemplateKind::Subject => &self.subject,
+            TemplateKind::Email => &self.email,
+            TemplateKind::Pushover => &self.pushover,
+            TemplateKind::Telegram => &self.telegram,
+            TemplateKind::Sms => &self.sms,
+            TemplateKind::Webhook => &self.webhook,
+        };
+        custom.as_deref().unwrap_or(kind.default_template())
+    }
+
+    pub fn render(&self, kind: TemplateKind, context: &tera::Context) -> Result<String, String> {
+        let rendered = tera::Tera::one_off(self.template(kind), context, false)
+            .map_err(|e| tera_error_message(&e))?;
+        if kind == TemplateKind::Webhook {
+            serde_json::from_str::<serde_json::Value>(&rendered)
+                .map_err(|e| format!("doesn't render to JSON: {}", e))?;
+        }
+        Ok(rendered.trim().to_string())
+    }
+
+    // A broken override falls back to the shipped template, so the alert still goes out
+    pub fn render_or_default(&self, kind: TemplateKind, context: &tera::Context) -> String {
+        self.render(kind, context).unwrap_or_else(|e| {
+            eprintln!("❌ {} notification template: {}", kind.name(), e);
+            NotificationTemplates::default()
+                .render(kind, context)
+                .unwrap_or_default()
+        })
+    }
+
+    // Renders every template against a sample alert
+    pub fn validate(&self) -> Result<(), String> {
+        let now = chrono::Utc::now();
+        let sample = Alert {
+            id: 1,
+            check: "cpu".to_string(),
+            state: CheckState::Critical,
+            message: "CPU usage is 97.0%".to_string(),
+            raised_at: (now - chrono::Duration::minutes(5)).to_rfc3339(),
+            resolved_at: None,
+            acknowledged_by: None,
+            value: Some(97.0),
+            threshold: Some(95.0),
+            parent_id: None,
+        };
+        let context = alert_template_context(&sample, "example-host", now);
+        for kind in TEMPLATE_KINDS {
+            self.render(kind, &context)
+                .map_err(|e| format!("notification_templates.{}: {}", kind.name(), e))?;
+        }
+        Ok(())
+    }
+}
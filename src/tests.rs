@@ -0,0 +1,2956 @@
+// Tests for Crusty-Crawler
+// AuthManager, sessions, config persistence and the HTTP routes, run against a mocked system,
+// clock and mail server
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::ServiceExt;
+
+    const TOKEN: &str = "token-alice-1";
+
+    struct MockSystem;
+
+    impl SystemInfoProvider for MockSystem {
+        fn system_name(&self) -> Option<String> {
+            Some("MockOS".to_string())
+        }
+
+        fn cpu_usage(&self) -> f32 {
+            42.0
+        }
+
+        fn memory(&self) -> (u64, u64) {
+            (2048 * 1024 * 1024, 8192 * 1024 * 1024)
+        }
+    }
+
+    // Only moves when told to
+    struct MockClock {
+        start: Instant,
+        offset: Mutex<Duration>,
+    }
+
+    impl MockClock {
+        fn new() -> Self {
+            Self {
+                start: Instant::now(),
+                offset: Mutex::new(Duration::ZERO),
+            }
+        }
+
+        fn advance(&self, by: Duration) {
+            *self.offset.lock().unwrap() += by;
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            self.start + *self.offset.lock().unwrap()
+        }
+
+        fn utc_now(&self) -> chrono::DateTime<chrono::Utc> {
+            let offset = chrono::Duration::from_std(*self.offset.lock().unwrap()).unwrap();
+            chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap() + offset
+        }
+    }
+
+    // Keeps (recipient, subject, body) of every message instead of sending it
+    #[derive(Default)]
+    struct MockMailer {
+        sent: Mutex<Vec<(String, String, String)>>,
+        // Fails every send like an unreachable SMTP server
+        failing: Mutex<bool>,
+    }
+
+    impl EmailSender for MockMailer {
+        fn send(
+            &self,
+            _config: &SmtpConfig,
+            to: &str,
+            subject: &str,
+            body: &str,
+        ) -> Result<(), String> {
+            if *self.failing.lock().unwrap() {
+                return Err("Connection refused".to_string());
+            }
+            self.sent
+                .lock()
+                .unwrap()
+                .push((to.to_string(), subject.to_string(), body.to_string()));
+            Ok(())
+        }
+    }
+
+    // A config file in its own temporary directory, removed with everything next to it
+    struct TempConfig {
+        dir: PathBuf,
+    }
+
+    impl TempConfig {
+        fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            // Login history and token usage go to the data directory, keep them out of the
+            // real one
+            DATA_DIR.get_or_init(|| {
+                let dir = env::temp_dir().join(format!("crusty-test-data-{}", std::process::id()));
+                fs::create_dir_all(&dir).unwrap();
+                dir
+            });
+            let dir = env::temp_dir().join(format!(
+                "crusty-test-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&dir).unwrap();
+            Self { dir }
+        }
+
+        fn path(&self) -> String {
+            self.dir
+                .join(AUTH_CONFIG_FILE)
+                .to_string_lossy()
+                .to_string()
+        }
+    }
+
+    impl Drop for TempConfig {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn manager_with_user(config: &TempConfig) -> AuthManager {
+        let mut auth_manager = AuthManager::new(&config.path()).unwrap();
+        auth_manager
+            .register_user("alice", "correct horse", "alice@example.com", TOKEN)
+            .unwrap();
+        auth_manager
+    }
+
+    async fn get(app: Router, uri: &str) -> (StatusCode, String) {
+        let response = app
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8_lossy(&body).to_string())
+    }
+
+    fn test_app(auth_manager: AuthManager) -> Router {
+        let state = ServerState::new(auth_manager, Arc::new(MockSystem));
+        create_app(Arc::new(Mutex::new(state)))
+    }
+
+    #[test]
+    fn register_user_validates_input() {
+        let config = TempConfig::new();
+        let mut auth_manager = manager_with_user(&config);
+
+        let register = |auth_manager: &mut AuthManager, username, password, token| {
+            auth_manager.register_user(username, password, "bob@example.com", token)
+        };
+        assert!(register(&mut auth_manager, "bo", "long enough", "token-bob-1").is_err());
+        assert!(register(&mut auth_manager, "bob", "short", "token-bob-1").is_err());
+        assert!(register(&mut auth_manager, "bob", "long enough", "short").is_err());
+        assert!(register(&mut auth_manager, "alice", "long enough", "token-bob-1").is_err());
+        assert!(register(&mut auth_manager, "bob", "long enough", TOKEN).is_err());
+        assert!(register(&mut auth_manager, "bob", "long enough", "token-bob-1").is_ok());
+    }
+
+    #[test]
+    fn authenticate_checks_the_password() {
+        let config = TempConfig::new();
+        let auth_manager = manager_with_user(&config);
+
+        assert_eq!(auth_manager.authenticate("alice", "correct horse"), Ok(()));
+        assert!(auth_manager.authenticate("alice", "wrong horse").is_err());
+        assert!(
+            auth_manager
+                .authenticate("mallory", "correct horse")
+                .is_err()
+        );
+        assert_eq!(auth_manager.validate_token(TOKEN), Ok("alice".to_string()));
+        assert!(auth_manager.validate_token("token-nobody").is_err());
+    }
+
+    #[test]
+    fn password_policy_is_enforced() {
+        let config = TempConfig::new();
+        let mut auth_manager = manager_with_user(&config);
+        let register = |auth_manager: &mut AuthManager, username, password| {
+            let email = format!("{}@example.com", username);
+            let token = format!("token-{}-1", username);
+            auth_manager.register_user(username, password, &email, &token)
+        };
+
+        assert!(register(&mut auth_manager, "bob", "Password2025!").is_err());
+        assert!(register(&mut auth_manager, "bob", "qwertyuiop").is_err());
+
+        let policy = PasswordPolicy {
+            min_length: 10,
+            require_uppercase: true,
+            require_digit: true,
+            ..PasswordPolicy::default()
+        };
+        assert_eq!(
+            policy.describe(),
+            "at least 10 characters with an uppercase letter and a digit"
+        );
+        auth_manager.configure_password_policy(policy).unwrap();
+        assert!(register(&mut auth_manager, "bob", "long enough").is_err());
+        assert!(register(&mut auth_manager, "bob", "Long enough").is_err());
+        assert!(register(&mut auth_manager, "bob", "Long enough 2").is_ok());
+
+        // Kept across restarts
+        let reloaded = AuthManager::new(&config.path()).unwrap();
+        assert_eq!(reloaded.config.password_policy.min_length, 10);
+    }
+
+    #[test]
+    fn expired_passwords_have_to_be_changed() {
+        let config = TempConfig::new();
+        let mut auth_manager = AuthManager::new(&config.path()).unwrap();
+        let clock = Arc::new(MockClock::new());
+        auth_manager.clock = clock.clone();
+        auth_manager
+            .register_user("alice", "correct horse", "alice@example.com", TOKEN)
+            .unwrap();
+        auth_manager.config.password_policy.max_age_days = 30;
+
+        clock.advance(Duration::from_secs(29 * 86400));
+        assert!(!auth_manager.password_expired("alice"));
+        clock.advance(Duration::from_secs(86400));
+        assert!(auth_manager.password_expired("alice"));
+
+        let change = |auth_manager: &mut AuthManager, current, new| {
+            auth_manager.change_password("alice", current, new)
+        };
+        assert!(change(&mut auth_manager, "wrong horse", "battery staple").is_err());
+        assert!(change(&mut auth_manager, "correct horse", "correct horse").is_err());
+        assert!(change(&mut auth_manager, "correct horse", "letmein1").is_err());
+        assert_eq!(
+            change(&mut auth_manager, "correct horse", "battery staple"),
+            Ok(())
+        );
+        assert!(!auth_manager.password_expired("alice"));
+        assert_eq!(auth_manager.authenticate("alice", "battery staple"), Ok(()));
+        assert!(auth_manager.authenticate("alice", "correct horse").is_err());
+    }
+
+    #[test]
+    fn invited_users_sign_up_once() {
+        let config = TempConfig::new();
+        let mut auth_manager = manager_with_user(&config);
+        let mailer = Arc::new(MockMailer::default());
+        auth_manager.mailer = mailer.clone();
+        let clock = Arc::new(MockClock::new());
+        auth_manager.clock = clock.clone();
+        let base_url = "http://crusty.local:3000/";
+
+        let invite = |auth_manager: &mut AuthManager, email| {
+            auth_manager.invite_user("alice", email, UserRole::Viewer, base_url)
+        };
+        assert!(invite(&mut auth_manager, "bob@example.com").is_err());
+        auth_manager
+            .configure_smtp(SmtpConfig {
+                server: "smtp.example.com".to_string(),
+                port: 587,
+                username: "crusty@example.com".to_string(),
+                password: "secret".to_string(),
+                use_tls: true,
+            })
+            .unwrap();
+        assert!(invite(&mut auth_manager, "alice@example.com").is_err());
+        let code = invite(&mut auth_manager, "bob@example.com").unwrap();
+        let expiring = invite(&mut auth_manager, "carol@example.com").unwrap();
+        {
+            let sent = mailer.sent.lock().unwrap();
+            assert_eq!(sent[0].0, "bob@example.com");
+            assert!(
+                sent[0]
+                    .2
+                    .contains(&format!("{}signup?code={}", base_url, code))
+            );
+        }
+        assert_eq!(auth_manager.pending_invitations().len(), 2);
+
+        let signup = |code: &str, username: &str, password: &str| Signup {
+            code: code.to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+        };
+        assert!(
+            auth_manager
+                .accept_invitation(&signup("wrong-code", "bob", "long enough"))
+                .is_err()
+        );
+        // A refused password leaves the invitation usable
+        assert!(
+            auth_manager
+                .accept_invitation(&signup(&code, "bob", "short"))
+                .is_err()
+        );
+        // So does a config that can't be saved, without leaving the user behind
+        fs::remove_file(config.path()).unwrap();
+        fs::create_dir(config.path()).unwrap();
+        assert!(
+            auth_manager
+                .accept_invitation(&signup(&code, "bob", "long enough"))
+                .is_err()
+        );
+        assert!(!auth_manager.config.users.contains_key("bob"));
+        assert_eq!(auth_manager.pending_invitations().len(), 2);
+        fs::remove_dir(config.path()).unwrap();
+        let result = auth_manager
+            .accept_invitation(&signup(&code, "bob", "long enough"))
+            .unwrap();
+        assert_eq!(
+            auth_manager.validate_token(&result.access_token),
+            Ok("bob".to_string())
+        );
+        let bob = &auth_manager.config.users["bob"];
+        assert_eq!(bob.role, UserRole::Viewer);
+        assert_eq!(bob.email, "bob@example.com");
+        assert!(
+            auth_manager
+                .accept_invitation(&signup(&code, "bob2", "long enough"))
+                .is_err()
+        );
+
+        clock.advance(Duration::from_secs(7 * 86400));
+        assert!(auth_manager.pending_invitations().is_empty());
+        assert!(
+            auth_manager
+                .accept_invitation(&signup(&expiring, "carol", "long enough"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn users_are_stamped_with_the_clock() {
+        let config = TempConfig::new();
+        let mut auth_manager = AuthManager::new(&config.path()).unwrap();
+        auth_manager.clock = Arc::new(MockClock::new());
+        auth_manager
+            .register_user("alice", "correct horse", "alice@example.com", TOKEN)
+            .unwrap();
+
+        assert_eq!(
+            auth_manager.config.users["alice"].created_at,
+            "2023-11-14T22:13:20+00:00"
+        );
+    }
+
+    #[test]
+    fn web_sessions_expire() {
+        let config = TempConfig::new();
+        let mut auth_manager = manager_with_user(&config);
+        let clock = Arc::new(MockClock::new());
+        auth_manager.clock = clock.clone();
+        auth_manager.config.sessions.web_session_hours = 2;
+
+        let session = auth_manager.create_web_session("alice");
+        clock.advance(Duration::from_secs(3600));
+        assert_eq!(
+            auth_manager.validate_token(&session),
+            Ok("alice".to_string())
+        );
+        clock.advance(Duration::from_secs(3601));
+        assert!(auth_manager.validate_token(&session).is_err());
+    }
+
+    #[test]
+    fn login_links_are_single_use() {
+        let config = TempConfig::new();
+        let auth_manager = manager_with_user(&config);
+
+        let link = auth_manager.create_login_link("alice", Duration::from_secs(60));
+        // Not a session until it is redeemed
+        assert!(auth_manager.validate_web_session(&link).is_none());
+        assert_eq!(
+            auth_manager.redeem_login_link(&link),
+            Some("alice".to_string())
+        );
+        assert_eq!(auth_manager.redeem_login_link(&link), None);
+    }
+
+    #[test]
+    fn recovery_mail_goes_through_the_sender() {
+        let config = TempConfig::new();
+        let mut auth_manager = manager_with_user(&config);
+        let mailer = Arc::new(MockMailer::default());
+        auth_manager.mailer = mailer.clone();
+
+        assert!(
+            auth_manager
+                .recover_credentials("alice@example.com")
+                .is_err()
+        );
+        auth_manager
+            .configure_smtp(SmtpConfig {
+                server: "smtp.example.com".to_string(),
+                port: 587,
+                username: "crusty@example.com".to_string(),
+                password: "secret".to_string(),
+                use_tls: true,
+            })
+            .unwrap();
+        assert!(
+            auth_manager
+                .recover_credentials("nobody@example.com")
+                .is_err()
+        );
+        auth_manager
+            .recover_credentials("alice@example.com")
+            .unwrap();
+
+        // Asking only mails a code, the token keeps working until it is confirmed
+        let code = {
+            let sent = mailer.sent.lock().unwrap();
+            assert_eq!(sent.len(), 1);
+            assert_eq!(sent[0].0, "alice@example.com");
+            sent[0]
+                .2
+                .split("recovery code is ")
+                .nth(1)
+                .and_then(|rest| rest.split('.').next())
+                .unwrap_or_default()
+                .to_string()
+        };
+        assert_eq!(auth_manager.validate_token(TOKEN), Ok("alice".to_string()));
+        assert!(
+            auth_manager
+                .confirm_recovery("alice@example.com", "not-it")
+                .is_err()
+        );
+
+        // A failed delivery leaves the old token in place and the code usable
+        *mailer.failing.lock().unwrap() = true;
+        assert!(
+            auth_manager
+                .confirm_recovery("alice@example.com", &code)
+                .is_err()
+        );
+        assert_eq!(auth_manager.validate_token(TOKEN), Ok("alice".to_string()));
+        *mailer.failing.lock().unwrap() = false;
+        auth_manager
+            .confirm_recovery("alice@example.com", &code)
+            .unwrap();
+        assert!(
+            auth_manager
+                .confirm_recovery("alice@example.com", &code)
+                .is_err()
+        );
+
+        // The token can't be recovered, a new one is mailed instead
+        let sent = mailer.sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert!(!sent[1].2.contains(TOKEN));
+        assert!(auth_manager.validate_token(TOKEN).is_err());
+        let new_token = sent[1]
+            .2
+            .split("New Access Token: ")
+            .nth(1)
+            .and_then(|rest| rest.lines().next())
+            .unwrap_or_default();
+        assert_eq!(
+            auth_manager.validate_token(new_token),
+            Ok("alice".to_string())
+        );
+    }
+
+    #[test]
+    fn access_tokens_are_stored_hashed() {
+        let config = TempConfig::new();
+        let mut auth_manager = manager_with_user(&config);
+
+        assert!(!fs::read_to_string(config.path()).unwrap().contains(TOKEN));
+        let session = auth_manager.create_web_session("alice");
+        let rotated = auth_manager.rotate_access_token("alice").unwrap();
+        assert!(auth_manager.validate_token(TOKEN).is_err());
+        assert!(auth_manager.validate_token(&session).is_err());
+        assert_eq!(
+            auth_manager.validate_token(&rotated),
+            Ok("alice".to_string())
+        );
+    }
+
+    #[test]
+    fn plain_text_tokens_are_migrated() {
+        let config = TempConfig::new();
+        let user = serde_json::json!({
+            "username": "alice",
+            "email": "alice@example.com",
+            "password_hash": "",
+            "access_token": TOKEN,
+            "created_at": "2023-11-14T22:13:20+00:00"
+        });
+        let legacy = serde_json::json!({
+            "version": 2,
+            "users": { "alice": user },
+            "smtp_config": null
+        });
+        fs::write(config.path(), legacy.to_string()).unwrap();
+
+        let auth_manager = AuthManager::new(&config.path()).unwrap();
+        assert_eq!(auth_manager.validate_token(TOKEN), Ok("alice".to_string()));
+        // Neither the config nor its backup keep the token
+        assert!(!fs::read_to_string(config.path()).unwrap().contains(TOKEN));
+        let backups = config_backups(Path::new(&config.path()));
+        assert_eq!(backups.len(), 1);
+        assert!(!fs::read_to_string(&backups[0]).unwrap().contains(TOKEN));
+    }
+
+    #[test]
+    fn unversioned_configs_are_migrated() {
+        let config = TempConfig::new();
+        fs::write(config.path(), r#"{"users": {}, "smtp_config": null}"#).unwrap();
+
+        AuthManager::new(&config.path()).unwrap();
+        let saved: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(config.path()).unwrap()).unwrap();
+        assert_eq!(saved["version"], AUTH_CONFIG_VERSION);
+        // The original is kept as a backup
+        assert_eq!(config_backups(Path::new(&config.path())).len(), 1);
+    }
+
+    #[test]
+    fn damaged_configs_fall_back_to_a_backup() {
+        let config = TempConfig::new();
+        let mut auth_manager = manager_with_user(&config);
+        auth_manager.set_allow_remember_me(false).unwrap();
+        fs::write(config.path(), "{\"users\": ").unwrap();
+
+        let restored = AuthManager::new(&config.path()).unwrap();
+        assert!(restored.config.users.contains_key("alice"));
+    }
+
+    #[test]
+    fn saves_merge_changes_from_other_processes() {
+        let config = TempConfig::new();
+        let mut first = manager_with_user(&config);
+        let mut second = AuthManager::new(&config.path()).unwrap();
+
+        first
+            .configure_sessions(SessionConfig {
+                gui_idle_lock_mins: 5,
+                web_session_hours: 1,
+            })
+            .unwrap();
+        second.set_allow_remember_me(false).unwrap();
+
+        let reloaded = AuthManager::new(&config.path()).unwrap();
+        assert_eq!(reloaded.config.sessions.gui_idle_lock_mins, 5);
+        assert!(!reloaded.config.allow_remember_me);
+    }
+
+    #[test]
+    fn conflicting_saves_are_rejected() {
+        let config = TempConfig::new();
+        let mut first = manager_with_user(&config);
+        let mut second = AuthManager::new(&config.path()).unwrap();
+
+        let sessions = |gui_idle_lock_mins| SessionConfig {
+            gui_idle_lock_mins,
+            web_session_hours: 12,
+        };
+        first.configure_sessions(sessions(5)).unwrap();
+        let error = second.configure_sessions(sessions(30)).unwrap_err();
+        assert!(error.contains("sessions.gui_idle_lock_mins"));
+    }
+
+    #[tokio::test]
+    async fn status_needs_a_valid_token() {
+        let config = TempConfig::new();
+        let app = test_app(manager_with_user(&config));
+
+        let (status, _) = get(app.clone(), "/api/status").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, body) = get(app, "/api/status?token=token-nobody").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body, r#"{"error":"Invalid or expired token"}"#);
+    }
+
+    #[tokio::test]
+    async fn bearer_tokens_are_accepted() {
+        let config = TempConfig::new();
+        let app = test_app(manager_with_user(&config));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/history")
+                    .header("Authorization", format!("Bearer {}", TOKEN))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn viewers_cannot_use_admin_routes() {
+        let config = TempConfig::new();
+        let mut auth_manager = manager_with_user(&config);
+        auth_manager
+            .register_user("bob", "long enough", "bob@example.com", "token-bob-1")
+            .unwrap();
+        auth_manager.config.users.get_mut("bob").unwrap().role = UserRole::Viewer;
+        let app = test_app(auth_manager);
+
+        let run = |token: &str| {
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/speedtest/run?token={}", token))
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = app.clone().oneshot(run("token-bob-1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        // Past the role check, the admin gets the missing speed test target instead
+        let response = app.clone().oneshot(run(TOKEN)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        // Viewers can still read
+        let (status, _) = get(app, "/api/status?token=token-bob-1").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn users_change_their_own_password_and_email() {
+        let config = TempConfig::new();
+        let mut auth_manager = manager_with_user(&config);
+        auth_manager
+            .register_user("bob", "long enough", "bob@example.com", "token-bob-1")
+            .unwrap();
+        let app = test_app(auth_manager);
+
+        let put = |path: &str, body: serde_json::Value| {
+            Request::builder()
+                .method("PUT")
+                .uri(format!("{}?token={}", path, TOKEN))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let change_email = |current_password: &str, email: &str| {
+            put(
+                "/api/account/email",
+                serde_json::json!({ "current_password": current_password, "email": email }),
+            )
+        };
+        let response = app
+            .clone()
+            .oneshot(put(
+                "/api/account/password",
+                serde_json::json!({
+                    "current_password": "correct horse",
+                    "new_password": "battery staple",
+                }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        // The old password no longer counts, and bob's address is taken
+        for (password, email) in [
+            ("correct horse", "alice@example.org"),
+            ("battery staple", "bob@example.com"),
+            ("battery staple", "not an address"),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(change_email(password, email))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        let response = app
+            .clone()
+            .oneshot(change_email("battery staple", "alice@example.org"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let (status, body) = get(app, &format!("/api/account?token={}", TOKEN)).await;
+        assert_eq!(status, StatusCode::OK);
+        let account: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(account["email"], "alice@example.org");
+        assert_eq!(account["role"], "admin");
+    }
+
+    #[tokio::test]
+    async fn kiosk_tokens_only_open_the_dashboard() {
+        let config = TempConfig::new();
+        let mut auth_manager = manager_with_user(&config);
+        let token = auth_manager.create_kiosk_token("Lobby", "alice").unwrap();
+        assert!(auth_manager.create_kiosk_token("lobby", "alice").is_err());
+        let id = auth_manager.kiosk_tokens()[0].id.clone();
+        let state = Arc::new(Mutex::new(ServerState::new(
+            auth_manager,
+            Arc::new(MockSystem),
+        )));
+        let app = create_app(state.clone());
+
+        let (status, body) = get(app.clone(), &format!("/?token={}", token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("const KIOSK = true;"));
+        for path in ["/api/status", "/api/overview", "/api/checks"] {
+            let (status, _) = get(app.clone(), &format!("{}?token={}", path, token)).await;
+            assert_eq!(status, StatusCode::OK, "{}", path);
+        }
+        for path in [
+            "/api/history",
+            "/api/events",
+            "/api/credentials",
+            "/api/security",
+        ] {
+            let (status, body) = get(app.clone(), &format!("{}?token={}", path, token)).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", path);
+            assert!(body.contains("Kiosk tokens can only open the dashboard"));
+        }
+        let (_, body) = get(app.clone(), &format!("/api/layout?token={}", token)).await;
+        let layout: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let visible: Vec<&str> = layout["panels"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|panel| panel["visible"] == true)
+            .map(|panel| panel["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(visible, ["status"]);
+
+        {
+            let state = state.lock().unwrap();
+            let mut auth_manager = state.auth_manager.lock().unwrap();
+            assert_eq!(auth_manager.kiosk_tokens()[0].usage.requests, 5);
+            assert_eq!(auth_manager.revoke_kiosk_token(&id, "alice"), Ok(true));
+        }
+        let (status, _) = get(app, &format!("/api/status?token={}", token)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn status_reports_the_system_provider() {
+        let config = TempConfig::new();
+        let app = test_app(manager_with_user(&config));
+
+        let (status, body) = get(app, &format!("/api/status?token={}", TOKEN)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("System name: \"MockOS\""));
+        assert!(body.contains("Memory in Use: 2048 MB"));
+        assert!(body.contains("CPU usage: 42.0%"));
+    }
+
+    #[tokio::test]
+    async fn credential_usage_is_reported_per_user() {
+        // Token usage is kept process-wide, so these users appear in no other test
+        let config = TempConfig::new();
+        let mut auth_manager = AuthManager::new(&config.path()).unwrap();
+        for (username, token) in [("carol", "token-carol-1"), ("dave", "token-dave-1")] {
+            let email = format!("{}@example.com", username);
+            auth_manager
+                .register_user(username, "long enough", &email, token)
+                .unwrap();
+        }
+        auth_manager.config.users.get_mut("dave").unwrap().role = UserRole::Viewer;
+        let session = auth_manager.create_web_session("dave");
+        let app = test_app(auth_manager);
+
+        get(app.clone(), "/api/history?token=token-carol-1").await;
+        get(app.clone(), &format!("/api/history?token={}", session)).await;
+        let (status, body) = get(app.clone(), "/api/credentials?token=token-carol-1").await;
+        assert_eq!(status, StatusCode::OK);
+        let report: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+        assert_eq!(report.len(), 3);
+        // This request counts too
+        assert_eq!(report[0]["username"], "carol");
+        assert_eq!(report[0]["requests"], 2);
+        assert_eq!(report[1]["username"], "dave");
+        assert!(report[1]["last_used"].is_null());
+        assert_eq!(report[2]["kind"], "web_session");
+        assert_eq!(report[2]["requests"], 1);
+
+        // Viewers only see their own
+        let (_, body) = get(app, &format!("/api/credentials?token={}", session)).await;
+        let report: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+        assert!(!report.is_empty());
+        assert!(
+            report
+                .iter()
+                .all(|credential| credential["username"] == "dave")
+        );
+    }
+
+    #[tokio::test]
+    async fn web_logins_are_recorded() {
+        // The history is process-wide, so these users appear in no other test
+        let config = TempConfig::new();
+        let mut auth_manager = AuthManager::new(&config.path()).unwrap();
+        for (username, token) in [("erin", "token-erin-1"), ("frank", "token-frank-1")] {
+            let email = format!("{}@example.com", username);
+            auth_manager
+                .register_user(username, "long enough", &email, token)
+                .unwrap();
+        }
+        auth_manager.config.users.get_mut("frank").unwrap().role = UserRole::Viewer;
+        let app = test_app(auth_manager);
+
+        get(app.clone(), "/?token=token-erin-1").await;
+        get(app.clone(), "/?token=token-frank-1").await;
+        let history = login_history(Some("erin"), None);
+        assert_eq!(history.len(), 1);
+        assert!(history[0].success);
+        assert!(history[0].method == LoginMethod::AccessToken);
+
+        let (status, body) =
+            get(app.clone(), "/api/logins?token=token-erin-1&username=frank").await;
+        assert_eq!(status, StatusCode::OK);
+        let logins: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+        assert_eq!(logins.len(), 1);
+        assert_eq!(logins[0]["username"], "frank");
+        // Viewers only get their own
+        let (status, _) = get(app, "/api/logins?token=token-frank-1&username=erin").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn index_swaps_the_access_token_for_a_session() {
+        let config = TempConfig::new();
+        let app = test_app(manager_with_user(&config));
+
+        let (status, _) = get(app.clone(), "/?token=token-nobody").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, body) = get(app.clone(), &format!("/?token={}", TOKEN)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!body.contains(TOKEN));
+
+        // The session token the page was given works for the API
+        let session = body
+            .split("const SESSION_TOKEN = \"")
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .unwrap_or_default();
+        let (status, _) = get(app, &format!("/api/status?token={}", session)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[test]
+    fn packaged_default_config_seeds_new_data_dirs() {
+        let install = TempConfig::new();
+        let data = TempConfig::new();
+        fs::write(
+            install.dir.join(DEFAULT_CONFIG_FILE),
+            r#"{"version": 3, "users": {}, "smtp_config": null}"#,
+        )
+        .unwrap();
+
+        seed_default_config(&data.dir, std::slice::from_ref(&install.dir));
+        let (config, _) = parse_auth_config(&fs::read_to_string(data.path()).unwrap()).unwrap();
+        assert!(config.users.is_empty());
+
+        // An existing config is never replaced
+        fs::write(data.path(), "{}").unwrap();
+        seed_default_config(&data.dir, std::slice::from_ref(&install.dir));
+        assert_eq!(fs::read_to_string(data.path()).unwrap(), "{}");
+    }
+
+    #[test]
+    fn container_config_comes_from_the_environment() {
+        let config = TempConfig::new();
+        let mut auth_manager = AuthManager::new(&config.path()).unwrap();
+        let vars = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+
+        let applied = auth_manager
+            .configure_from_env(&vars(&[
+                ("CRUSTY_CONFIG", r#"{"otlp": {"service_name": "edge-7"}}"#),
+                ("CRUSTY_CONFIG__OTLP__ENABLED", "true"),
+                ("CRUSTY_CONFIG__HTTP__TLS_CERT", "/data/cert.pem"),
+                ("CRUSTY_CONFIG__HTTP__TLS_KEY", "/data/key.pem"),
+                ("CRUSTY_CONFIG__CONFIG_BACKUPS", "2"),
+                ("HOME", "/root"),
+            ]))
+            .unwrap();
+        assert_eq!(applied.len(), 5);
+        let reloaded = AuthManager::new(&config.path()).unwrap();
+        assert!(reloaded.config.otlp.enabled);
+        assert_eq!(reloaded.config.otlp.service_name, "edge-7");
+        assert_eq!(
+            reloaded.config.http.tls_cert.as_deref(),
+            Some("/data/cert.pem")
+        );
+        assert_eq!(
+            reloaded.config.http.tls_key.as_deref(),
+            Some("/data/key.pem")
+        );
+        assert_eq!(reloaded.config.config_backups, 2);
+
+        // Invalid settings leave the config alone
+        assert!(
+            auth_manager
+                .configure_from_env(&vars(&[("CRUSTY_CONFIG__CHECKS__CPU__WARNING", "150")]))
+                .is_err()
+        );
+        assert!(
+            auth_manager
+                .configure_from_env(&vars(&[("CRUSTY_CONFIG__OTLP__ENABLED__X", "1")]))
+                .is_err()
+        );
+
+        let admin = vars(&[
+            ("CRUSTY_ADMIN_USER", "root-admin"),
+            ("CRUSTY_ADMIN_PASSWORD", "container pass"),
+            ("CRUSTY_ADMIN_TOKEN", "token-root-admin"),
+        ]);
+        assert!(auth_manager.bootstrap_admin_from_env(&admin).unwrap());
+        assert!(!auth_manager.bootstrap_admin_from_env(&admin).unwrap());
+        assert!(
+            auth_manager
+                .authenticate("root-admin", "container pass")
+                .is_ok()
+        );
+        let line: serde_json::Value =
+            serde_json::from_str(&json_log_line(true, "⚠️  careful")).unwrap();
+        assert_eq!(line["level"], "warn");
+    }
+
+    #[test]
+    fn process_metrics_are_grouped_and_capped() {
+        let sample = |name: &str, cpu_percent: f64| ProcessSample {
+            name: name.to_string(),
+            cpu_percent,
+            memory_bytes: 1024,
+        };
+        let samples = [
+            sample("php-fpm8.2", 10.0),
+            sample("php-fpm8.3", 5.0),
+            sample("postgres", 20.0),
+            sample("postgres", 1.0),
+            sample("sshd", 0.5),
+            sample("cron", 0.1),
+        ];
+        let mut config = ProcessMetricsConfig {
+            enabled: true,
+            groups: vec![ProcessGroupRule {
+                pattern: r"^(php)-fpm".to_string(),
+                group: "$1".to_string(),
+            }],
+            ..Default::default()
+        };
+        let series = |config: &ProcessMetricsConfig| -> BTreeMap<String, f64> {
+            process_group_metrics(config, &samples)
+                .into_iter()
+                .filter(|metric| metric.name == "process_group_count")
+                .map(|metric| (metric.labels["group"].clone(), metric.value))
+                .collect()
+        };
+
+        let groups = series(&config);
+        assert_eq!(groups.len(), 4);
+        assert_eq!(groups["php"], 2.0);
+        assert_eq!(groups["postgres"], 2.0);
+
+        // The quietest groups are folded into "other", which counts against the cap
+        config.max_series = 3;
+        let groups = series(&config);
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups["other"], 2.0);
+        assert!(groups.contains_key("postgres") && groups.contains_key("php"));
+
+        config.group_by_name = false;
+        config.max_series = 50;
+        let groups = series(&config);
+        assert_eq!(groups.keys().collect::<Vec<_>>(), ["other", "php"]);
+
+        config.groups[0].pattern = "(".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn collection_backs_off_under_pressure() {
+        assert_eq!(
+            parse_pressure(
+                "some avg10=85.50 avg60=40.00 avg300=10.00 total=123\n\
+                 full avg10=2.00 avg60=1.00 avg300=0.50 total=45\n"
+            ),
+            Some(85.5)
+        );
+        assert_eq!(parse_pressure("full avg10=2.00"), None);
+
+        let config = AdaptiveCollectionConfig::default();
+        let now = chrono::Utc::now();
+        assert!(next_collection_state(&config, None, Some(50.0), Some(10.0), now).is_none());
+        let degraded = next_collection_state(&config, None, Some(50.0), Some(55.0), now).unwrap();
+        assert_eq!(degraded.reason, "I/O pressure 55%");
+        assert_eq!(degraded.backoff_factor, 4);
+
+        // Stays degraded just below the threshold and keeps its start time
+        let later = now + chrono::Duration::minutes(5);
+        let still = next_collection_state(&config, Some(&degraded), None, Some(35.0), later);
+        assert_eq!(still.unwrap().since, degraded.since);
+        assert!(next_collection_state(&config, Some(&degraded), None, Some(20.0), later).is_none());
+
+        let disabled = AdaptiveCollectionConfig {
+            enabled: false,
+            ..AdaptiveCollectionConfig::default()
+        };
+        assert!(next_collection_state(&disabled, None, Some(100.0), None, now).is_none());
+        assert!(
+            AdaptiveCollectionConfig {
+                backoff_factor: 0,
+                ..AdaptiveCollectionConfig::default()
+            }
+            .validate()
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn collectors_report_failing_after_the_error_budget() {
+        let now = chrono::Utc::now();
+        let mut health = CollectorHealth::new("hardware_query");
+        health.record(Ok(()), now);
+        assert_eq!(health.state, CollectorState::Ok);
+
+        health.record(Err("not supported on this VM"), now);
+        assert_eq!(health.state, CollectorState::Degraded);
+        for _ in 1..COLLECTOR_ERROR_BUDGET {
+            health.record(Err("not supported on this VM"), now);
+        }
+        assert_eq!(health.state, CollectorState::Failing);
+        assert_eq!(health.consecutive_failures, COLLECTOR_ERROR_BUDGET);
+        assert_eq!(
+            health.last_error.as_deref(),
+            Some("not supported on this VM")
+        );
+
+        health.record(Ok(()), now);
+        assert_eq!(health.state, CollectorState::Ok);
+        assert_eq!(health.total_failures, u64::from(COLLECTOR_ERROR_BUDGET));
+        assert_eq!(health.total_runs, u64::from(COLLECTOR_ERROR_BUDGET) + 2);
+
+        let collectors = vec![
+            ToolCollector {
+                parser: ToolParser::Smartctl,
+                command: Vec::new(),
+                interval_secs: 60,
+                schedule: None,
+            },
+            ToolCollector {
+                parser: ToolParser::Sensors,
+                command: Vec::new(),
+                interval_secs: 60,
+                schedule: None,
+            },
+            ToolCollector {
+                parser: ToolParser::Smartctl,
+                command: vec!["smartctl".to_string(), "-a".to_string()],
+                interval_secs: 60,
+                schedule: None,
+            },
+        ];
+        assert_eq!(collector_name(&collectors, 1), "sensors");
+        assert_eq!(collector_name(&collectors, 2), "smartctl #2");
+    }
+
+    #[test]
+    fn unsupported_sensors_are_reported_per_platform() {
+        assert_eq!(
+            detect_virtualization("Standard PC (Q35 + ICH9, 2009)\n", "QEMU\n").as_deref(),
+            Some("qemu")
+        );
+        assert_eq!(
+            detect_virtualization("Virtual Machine", "Microsoft Corporation").as_deref(),
+            Some("hyper-v")
+        );
+        assert_eq!(detect_virtualization("ThinkPad X1 Carbon", "LENOVO"), None);
+
+        let vm = capabilities_from(Some("kvm".to_string()), false, false, false);
+        let collectors: Vec<&str> = vm
+            .unsupported
+            .iter()
+            .map(|unsupported| unsupported.collector.as_str())
+            .collect();
+        assert_eq!(collectors, ["components", "sensors", "hardware_query"]);
+        assert_eq!(vm.unsupported[0].reason, "unsupported on a kvm VM");
+
+        let container = capabilities_from(None, true, true, false);
+        assert_eq!(container.unsupported.len(), 1);
+        assert_eq!(
+            container.unsupported[0].reason,
+            "unsupported in a container"
+        );
+
+        assert!(
+            capabilities_from(None, false, true, true)
+                .unsupported
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn notification_templates_render_alert_fields() {
+        let now = chrono::Utc::now();
+        let mut alerts = AlertManager::default();
+        let result = CheckResult::new(
+            "disk",
+            CheckState::Critical,
+            "Disk / is 97.0% full".to_string(),
+            vec![PerfData::new("disk", 97.0, "%").thresholds(&Thresholds {
+                warning: 85.0,
+                critical: 95.0,
+            })],
+        );
+        let mut alert = alerts
+            .process_result(&result, &AlertGroupingConfig::default())
+            .unwrap();
+        assert_eq!((alert.value, alert.threshold), (Some(97.0), Some(95.0)));
+        alert.raised_at = (now - chrono::Duration::seconds(312)).to_rfc3339();
+        let context = alert_template_context(&alert, "web-01", now);
+
+        let defaults = NotificationTemplates::default();
+        assert_eq!(
+            defaults.render(TemplateKind::Subject, &context).unwrap(),
+            "[Crusty] CRITICAL disk on web-01"
+        );
+        let email = defaults.render(TemplateKind::Email, &context).unwrap();
+        assert!(email.contains("Value: 97.0 (threshold 95.0)"));
+        assert!(email.contains("5m 12s ago"));
+        let webhook: serde_json::Value =
+            serde_json::from_str(&defaults.render(TemplateKind::Webhook, &context).unwrap())
+                .unwrap();
+        assert_eq!(webhook["check"], "disk");
+        assert_eq!(webhook["value"], 97.0);
+        assert_eq!(webhook["duration_secs"], 312);
+
+        let custom = NotificationTemplates {
+            telegram: Some("🔥 {{ host }}/{{ check }} {{ value }}% for {{ duration }}".to_string()),
+            ..NotificationTemplates::default()
+        };
+        assert!(custom.validate().is_ok());
+        assert_eq!(
+            custom.render(TemplateKind::Telegram, &context).unwrap(),
+            "🔥 web-01/disk 97.0% for 5m 12s"
+        );
+
+        let broken = NotificationTemplates {
+            sms: Some("{{ check".to_string()),
+            webhook: Some("{{ check }}".to_string()),
+            ..NotificationTemplates::default()
+        };
+        assert!(
+            broken
+                .validate()
+                .unwrap_err()
+                .starts_with("notification_templates.sms")
+        );
+        assert!(broken.render(TemplateKind::Webhook, &context).is_err());
+        assert_eq!(
+            broken.render_or_default(TemplateKind::Sms, &context),
+            "[Crusty] CRITICAL disk on web-01: Disk / is 97.0% full"
+        );
+    }
+
+    #[test]
+    fn alerts_raised_together_are_one_incident() {
+        let failing = |name: &str| {
+            CheckResult::new(
+                name,
+                CheckState::Critical,
+                format!("{} failed", name),
+                vec![],
+            )
+        };
+        let ok = |name: &str| CheckResult::new(name, CheckState::Ok, "fine".to_string(), vec![]);
+        let mut grouping = AlertGroupingConfig::default();
+        grouping
+            .tags
+            .insert("disk".to_string(), vec!["storage".to_string()]);
+        grouping
+            .tags
+            .insert("postgres".to_string(), vec!["storage".to_string()]);
+        grouping
+            .tags
+            .insert("wifi".to_string(), vec!["network".to_string()]);
+
+        let mut alerts = AlertManager::default();
+        let disk = alerts.process_result(&failing("disk"), &grouping).unwrap();
+        assert!(disk.parent_id.is_none() && alerts.notifies(&disk));
+
+        // Shares a tag, and an untagged check joins anything in the window
+        let postgres = alerts
+            .process_result(&failing("postgres"), &grouping)
+            .unwrap();
+        let backup = alerts
+            .process_result(&failing("backup"), &grouping)
+            .unwrap();
+        assert_eq!(postgres.parent_id, Some(disk.id));
+        assert_eq!(backup.parent_id, Some(disk.id));
+        assert!(!alerts.notifies(&postgres));
+        assert_eq!(alerts.grouped(disk.id).len(), 2);
+
+        // A different tag opens its own incident
+        let wifi = alerts.process_result(&failing("wifi"), &grouping).unwrap();
+        assert!(wifi.parent_id.is_none() && alerts.notifies(&wifi));
+
+        // The recovery goes out once, when the last alert of the incident clears
+        let recovered = alerts.process_result(&ok("disk"), &grouping).unwrap();
+        assert!(!alerts.notifies(&recovered));
+        alerts.process_result(&ok("postgres"), &grouping);
+        let last = alerts.process_result(&ok("backup"), &grouping).unwrap();
+        assert_eq!(last.incident_id(), disk.id);
+        assert!(alerts.notifies(&last));
+
+        grouping.enabled = false;
+        let cpu = alerts.process_result(&failing("cpu"), &grouping).unwrap();
+        assert!(cpu.parent_id.is_none());
+
+        // A grouped alert worse than what its incident paged at pages again, once
+        let grouping = AlertGroupingConfig::default();
+        let warning =
+            |name: &str| CheckResult::new(name, CheckState::Warning, "slow".to_string(), vec![]);
+        let mut alerts = AlertManager::default();
+        let nginx = alerts.process_result(&warning("nginx"), &grouping).unwrap();
+        assert!(alerts.notifies(&nginx));
+        let smart = alerts.process_result(&warning("smart"), &grouping).unwrap();
+        assert_eq!(smart.parent_id, Some(nginx.id));
+        assert!(!alerts.notifies(&smart));
+        let escalated = alerts.process_result(&failing("smart"), &grouping).unwrap();
+        assert!(alerts.notifies(&escalated));
+        let joined = alerts.process_result(&failing("raid"), &grouping).unwrap();
+        assert_eq!(joined.parent_id, Some(nginx.id));
+        assert!(!alerts.notifies(&joined));
+    }
+
+    #[test]
+    fn child_checks_stay_quiet_while_their_parent_is_down() {
+        let mut dependencies = BTreeMap::new();
+        dependencies.insert("website".to_string(), vec!["nginx".to_string()]);
+        dependencies.insert("nginx".to_string(), vec!["disk".to_string()]);
+
+        let names = vec![
+            "website".to_string(),
+            "cpu".to_string(),
+            "nginx".to_string(),
+        ];
+        assert_eq!(
+            order_by_dependencies(names, &dependencies),
+            ["cpu", "nginx", "website"]
+        );
+        assert!(validate_dependencies(&dependencies).is_ok());
+
+        let grouping = AlertGroupingConfig {
+            enabled: false,
+            ..AlertGroupingConfig::default()
+        };
+        let mut alerts = AlertManager::default();
+        let website = CheckResult::new("website", CheckState::Critical, "down".to_string(), vec![]);
+        alerts.process_result(&website, &grouping);
+        assert_eq!(alerts.failing_parent("website", &dependencies), None);
+        let nginx = CheckResult::new("nginx", CheckState::Critical, "down".to_string(), vec![]);
+        alerts.process_result(&nginx, &grouping);
+        assert_eq!(
+            alerts.failing_parent("website", &dependencies).as_deref(),
+            Some("nginx")
+        );
+        assert_eq!(alerts.failing_parent("cpu", &dependencies), None);
+
+        dependencies.insert("disk".to_string(), vec!["website".to_string()]);
+        assert_eq!(
+            validate_dependencies(&dependencies).unwrap_err(),
+            "checks.dependencies: circular dependency disk -> website -> nginx -> disk"
+        );
+    }
+
+    #[test]
+    fn notifications_go_to_whoever_is_on_call() {
+        let contact = |name: &str| Contact {
+            name: name.to_string(),
+            severities: vec![CheckState::Critical],
+            channels: Vec::new(),
+        };
+        let contacts = vec![contact("alice"), contact("bob"), contact("ops-list")];
+        let at = |time: &str| {
+            chrono::DateTime::parse_from_rfc3339(time)
+                .unwrap()
+                .with_timezone(&chrono::Utc)
+        };
+        let mut on_call = OnCallConfig {
+            rotations: vec![Rotation {
+                name: "primary".to_string(),
+                contacts: vec!["alice".to_string(), "bob".to_string()],
+                start: "2026-01-05T09:00:00Z".to_string(),
+                shift_hours: 168,
+            }],
+            overrides: Vec::new(),
+        };
+        assert!(on_call.validate(&contacts).is_ok());
+
+        // Contacts outside the rotation always hear about alerts
+        let names = |now| -> Vec<String> {
+            on_call
+                .recipients(&contacts, now)
+                .iter()
+                .map(|c| c.name.clone())
+                .collect()
+        };
+        assert_eq!(names(at("2026-01-06T00:00:00Z")), ["alice", "ops-list"]);
+        assert_eq!(names(at("2026-01-13T08:59:59Z")), ["bob", "ops-list"]);
+        // Before the start the schedule runs backwards
+        assert_eq!(names(at("2026-01-01T00:00:00Z")), ["bob", "ops-list"]);
+
+        let shift = on_call
+            .shift(&on_call.rotations[0], at("2026-01-06T00:00:00Z"))
+            .unwrap();
+        assert_eq!(shift.contact, "alice");
+        assert_eq!(shift.next, "bob");
+        assert_eq!(at(&shift.until), at("2026-01-12T09:00:00Z"));
+
+        on_call.overrides.push(OnCallOverride {
+            rotation: "primary".to_string(),
+            contact: "bob".to_string(),
+            start: "2026-01-06T00:00:00Z".to_string(),
+            end: "2026-01-07T00:00:00Z".to_string(),
+        });
+        let shift = on_call
+            .shift(&on_call.rotations[0], at("2026-01-06T12:00:00Z"))
+            .unwrap();
+        assert!(shift.overridden);
+        assert_eq!(
+            (shift.contact.as_str(), shift.next.as_str()),
+            ("bob", "alice")
+        );
+
+        on_call.rotations[0].contacts.push("carol".to_string());
+        assert_eq!(
+            on_call.validate(&contacts).unwrap_err(),
+            "on_call.rotations 'primary': no contact named 'carol'"
+        );
+    }
+
+    #[test]
+    fn sla_counts_critical_time_as_downtime() {
+        let at = |time: &str| {
+            chrono::DateTime::parse_from_rfc3339(time)
+                .unwrap()
+                .with_timezone(&chrono::Utc)
+        };
+        let change = |timestamp: &str, state| StateChange {
+            timestamp: timestamp.to_string(),
+            state,
+        };
+        let mut changes = vec![
+            change("2026-01-01T00:00:00Z", CheckState::Ok),
+            change("2026-01-01T06:00:00Z", CheckState::Critical),
+            change("2026-01-01T09:00:00Z", CheckState::Warning),
+            change("2026-01-01T10:00:00Z", CheckState::Ok),
+            change("2026-01-01T23:00:00Z", CheckState::Critical),
+        ];
+
+        // The day from 00:00 to 24:00: 3h and the last 1h down
+        let sla = check_sla(
+            "nginx",
+            &changes,
+            at("2026-01-01T00:00:00Z"),
+            at("2026-01-02T00:00:00Z"),
+        )
+        .unwrap();
+        assert_eq!(sla.observed_secs, 86400);
+        assert_eq!(sla.time_in_state[&CheckState::Critical], 4 * 3600);
+        assert_eq!(sla.time_in_state[&CheckState::Warning], 3600);
+        assert_eq!(sla.availability_percent, 83.333);
+        assert_eq!(sla.incidents.len(), 2);
+        assert!(sla.incidents[1].end.is_none());
+
+        // A window opening mid-incident only counts the part inside it
+        let sla = check_sla(
+            "nginx",
+            &changes,
+            at("2026-01-01T08:00:00Z"),
+            at("2026-01-01T12:00:00Z"),
+        )
+        .unwrap();
+        assert_eq!(sla.incidents[0].duration_secs, 3600);
+        assert_eq!(sla.availability_percent, 75.0);
+
+        assert!(
+            check_sla(
+                "nginx",
+                &changes,
+                at("2025-12-01T00:00:00Z"),
+                at("2025-12-02T00:00:00Z"),
+            )
+            .is_none()
+        );
+
+        // The last change before the cutoff still says how the window started
+        prune_state_changes(&mut changes, at("2026-01-01T09:30:00Z"));
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].state, CheckState::Warning);
+    }
+
+    #[tokio::test]
+    async fn badges_need_a_token_unless_published() {
+        let config = TempConfig::new();
+        let app = test_app(manager_with_user(&config));
+        let (status, _) = get(app.clone(), "/badge/memory").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/badge/memory.svg?token={}", TOKEN))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "image/svg+xml");
+        assert_eq!(response.headers()["cache-control"], "private, max-age=30");
+
+        let public_config = TempConfig::new();
+        let mut auth_manager = manager_with_user(&public_config);
+        auth_manager.config.status_pages.public.enabled = true;
+        auth_manager.config.status_pages.public.checks = vec!["memory".to_string()];
+        let app = test_app(auth_manager);
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/badge/memory")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["cache-control"], "public, max-age=30");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(String::from_utf8_lossy(&body).contains(">memory</text>"));
+
+        let (status, _) = get(app.clone(), "/badge/disk").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = get(app, &format!("/badge/nonsense?token={}", TOKEN)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn derived_metrics_are_computed_from_collected_ones() {
+        let metrics = vec![
+            Metric::new("memory_used_bytes", 3.0),
+            Metric::new("memory_total_bytes", 4.0),
+            Metric::new("disk_total_bytes", 100.0).label("mount", "/"),
+            Metric::new("disk_total_bytes", 300.0).label("mount", "/home"),
+        ];
+        let derived = |name: &str, expression: &str| DerivedMetric {
+            name: name.to_string(),
+            expression: expression.to_string(),
+            thresholds: None,
+        };
+        let mut definitions = vec![
+            derived(
+                "mem_pressure",
+                "memory_used_bytes / (memory_total_bytes) * 100",
+            ),
+            derived(
+                "root_share",
+                "disk_total_bytes{mount=\"/\"} / disk_total_bytes",
+            ),
+            // Later definitions see the earlier ones
+            derived("mem_headroom", "100 - mem_pressure"),
+        ];
+        definitions[0].thresholds = Some(Thresholds {
+            warning: 70.0,
+            critical: 90.0,
+        });
+        assert!(validate_derived_metrics(&definitions).is_ok());
+
+        let values: Vec<(String, f64)> = evaluate_derived_metrics(&definitions, &metrics)
+            .into_iter()
+            .map(|metric| (metric.name, metric.value))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                ("mem_pressure".to_string(), 75.0),
+                ("root_share".to_string(), 0.25),
+                ("mem_headroom".to_string(), 25.0),
+            ]
+        );
+
+        // Only the one with thresholds is a check
+        assert_eq!(derived_checks(), ["mem_pressure"]);
+        let check = check_derived("mem_pressure").unwrap();
+        assert_eq!(check.state, CheckState::Warning);
+        assert_eq!(check.output, "mem_pressure is 75");
+        assert!(check_derived("root_share").is_none());
+
+        // A missing input leaves the metric out and the check UNKNOWN
+        evaluate_derived_metrics(&definitions, &metrics[2..]);
+        assert_eq!(
+            check_derived("mem_pressure").unwrap().state,
+            CheckState::Unknown
+        );
+
+        assert_eq!(
+            validate_derived_metrics(&[derived("broken", "(cpu_usage_percent * 2")]).unwrap_err(),
+            "metrics.derived 'broken': expected ')' at the end"
+        );
+        assert!(validate_derived_metrics(&[derived("bad-name", "1")]).is_err());
+    }
+
+    #[test]
+    fn device_filters_skip_pseudo_filesystems_and_container_interfaces() {
+        let devices = DeviceFilters::default();
+        assert!(devices.disks.allows(&["/", "/dev/sda1", "ext4"]));
+        assert!(!devices.disks.allows(&["/run", "tmpfs", "tmpfs"]));
+        assert!(
+            !devices
+                .disks
+                .allows(&["/snap/core/123", "/dev/loop3", "squashfs"])
+        );
+        assert!(devices.allows_interface("eth0"));
+        assert!(!devices.allows_interface("veth1a2b"));
+        assert!(!devices.allows_interface("docker0"));
+        assert!(devices.allows_component("coretemp Package id 0"));
+
+        // An include list keeps only what it names, excludes still win
+        let filter = PatternFilter {
+            include: vec!["/".to_string(), "/home*".to_string()],
+            exclude: vec!["/home/scratch".to_string()],
+        };
+        assert!(filter.allows(&["/home", "/dev/sdb1"]));
+        assert!(!filter.allows(&["/home/scratch", "/dev/sdc1"]));
+        assert!(!filter.allows(&["/boot", "/dev/sda2"]));
+
+        let mut devices = DeviceFilters::default();
+        devices.interfaces.exclude.push(" ".to_string());
+        assert_eq!(
+            devices.validate().unwrap_err(),
+            "metrics.devices.interfaces: empty pattern"
+        );
+    }
+
+    #[test]
+    fn health_score_weighs_check_states_and_resource_pressure() {
+        let result = |name: &str, state: CheckState| {
+            CheckResult::new(name, state, String::new(), Vec::new())
+        };
+        let results = [
+            result("cpu", CheckState::Ok),
+            result("website", CheckState::Critical),
+        ];
+        let usage = ResourceUsage {
+            cpu_percent: None,
+            memory_percent: 85.0,
+            disk_percent: 40.0,
+        };
+        let mut config = HealthScoreConfig::default();
+
+        // (100 + 0 + 50 for memory halfway past 70% + 100 for disk) / 4
+        let score = compute_health_score(&config, &results, &usage);
+        assert_eq!(score.score, 62.5);
+        assert_eq!(score.components.len(), 4);
+
+        config.weights.insert("website".to_string(), 6.0);
+        config.memory_weight = 0.0;
+        config.disk_weight = 0.0;
+        let score = compute_health_score(&config, &results, &usage);
+        assert_eq!(score.score, 14.3);
+        assert!(score.components.iter().all(|c| c.name != "memory"));
+
+        config.check_weight = 0.0;
+        config.weights.clear();
+        assert_eq!(compute_health_score(&config, &results, &usage).score, 100.0);
+
+        config.cpu_weight = -1.0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn diagnose_lists_what_grew_since_then() {
+        let start = chrono::Utc::now() - chrono::Duration::minutes(30);
+        let snapshot = |minute: i64, java: u64, var: u64, eth0: u64| DiagnosticSnapshot {
+            taken_at: start + chrono::Duration::minutes(minute),
+            process_memory: BTreeMap::from([
+                ("java".to_string(), java),
+                ("sshd".to_string(), 1000),
+            ]),
+            disk_used: BTreeMap::from([("/var".to_string(), var)]),
+            interface_bytes: BTreeMap::from([("eth0".to_string(), eth0)]),
+        };
+        assert!(diagnose(&[snapshot(0, 0, 0, 0)], 15, 5).is_err());
+
+        let snapshots = [
+            snapshot(0, 100, 500, 0),
+            snapshot(1, 200, 500, 60),
+            snapshot(2, 300, 900, 120),
+            snapshot(3, 900, 1500, 6120),
+        ];
+        // Compared against minute 1, the newest snapshot at least two minutes old
+        let report = diagnose(&snapshots, 2, 5).unwrap();
+        assert_eq!(report.since, snapshots[1].taken_at.to_rfc3339());
+        assert_eq!(
+            report.processes,
+            [Mover {
+                name: "java".to_string(),
+                before: 200.0,
+                after: 900.0,
+                change: 700.0,
+            }]
+        );
+        assert_eq!(report.disks[0].change, 1000.0);
+        // 1 B/s before minute 1, 100 B/s in the last minute
+        assert_eq!(report.interfaces[0].before, 1.0);
+        assert_eq!(report.interfaces[0].after, 100.0);
+
+        // Further back than the history goes falls back to the oldest snapshot
+        let report = diagnose(&snapshots, 600, 5).unwrap();
+        assert_eq!(report.since, snapshots[0].taken_at.to_rfc3339());
+        assert_eq!(report.interfaces[0].before, 0.0);
+    }
+
+    #[test]
+    fn oom_kills_and_core_dumps_are_found_in_the_journal() {
+        let crashes: Vec<CrashEvent> = [
+            "1714564800.250000 web1 kernel: Out of memory: Killed process 4242 (java) \
+             total-vm:8123456kB, anon-rss:4000000kB",
+            "1714564801.000000 web1 kernel: Out of memory: Kill process 77 (php) score 900",
+            "1714564802.000000 web1 kernel: e1000e: eth0 NIC Link is Down",
+            "1714564803.000000 web1 systemd-coredump[900]: Process 1234 (nginx) of user 33 \
+             dumped core.",
+            "-- No entries --",
+        ]
+        .iter()
+        .filter_map(|line| parse_journal_line(line))
+        .filter_map(|line| parse_crash(&line))
+        .collect();
+        assert_eq!(crashes.len(), 2);
+        assert_eq!(crashes[0].kind, CrashKind::OomKill);
+        assert_eq!(
+            (crashes[0].pid, crashes[0].process.as_str()),
+            (4242, "java")
+        );
+        assert_eq!(crashes[0].timestamp, "2024-05-01T12:00:00.250+00:00");
+        assert_eq!(crashes[1].kind, CrashKind::CoreDump);
+        assert_eq!(crashes[1].process, "nginx");
+
+        let config = CrashConfig::default();
+        let then = chrono::DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let result = crash_check_result(&crashes, &config, then + chrono::Duration::minutes(5));
+        assert_eq!(result.state, CheckState::Critical);
+        assert!(result.output.contains("OOM killer ended java (PID 4242)"));
+        let result = crash_check_result(&crashes[1..], &config, then);
+        assert_eq!(result.state, CheckState::Warning);
+        // Out of the alert window
+        let result = crash_check_result(&crashes, &config, then + chrono::Duration::hours(2));
+        assert_eq!(result.state, CheckState::Ok);
+    }
+
+    #[test]
+    fn kernel_log_hardware_errors_are_matched_and_alerted() {
+        let mut config = KernelLogConfig::default();
+        config.signatures.push(KernelSignature {
+            name: "gpu".to_string(),
+            contains: vec!["NVRM: Xid".to_string()],
+            critical: false,
+        });
+        assert!(config.validate().is_ok());
+        let signatures = config.all_signatures();
+        let matched = |line: &str| {
+            parse_journal_line(line).and_then(|line| match_kernel_line(&signatures, &line))
+        };
+
+        let io = matched(
+            "1714564800.000000 db1 kernel: blk_update_request: I/O error, dev sda, sector 2048",
+        )
+        .unwrap();
+        assert_eq!((io.signature.as_str(), io.critical), ("io_error", true));
+        let link = matched("1714564900.000000 db1 kernel: e1000e: eth0 NIC Link is Down").unwrap();
+        assert_eq!(
+            (link.signature.as_str(), link.critical),
+            ("link_flap", false)
+        );
+        assert_eq!(
+            matched("1714564950.000000 db1 kernel: NVRM: Xid (PCI:0000:01:00): 79")
+                .unwrap()
+                .signature,
+            "gpu"
+        );
+        assert!(
+            matched("1714565000.000000 db1 kernel: usb 1-1: USB disconnect, device number 3")
+                .is_none()
+        );
+
+        let then = chrono::DateTime::from_timestamp(1714564900, 0).unwrap();
+        // Newest first, like the report
+        let result = kernel_log_check_result(&[link.clone(), io.clone()], &config, then);
+        assert_eq!(result.state, CheckState::Critical);
+        assert!(result.output.contains("1 io_error, 1 link_flap"));
+        assert!(
+            result
+                .output
+                .ends_with("latest: e1000e: eth0 NIC Link is Down")
+        );
+        // The I/O error has left the alert window, the link flap hasn't
+        let later = then + chrono::Duration::minutes(59);
+        let result = kernel_log_check_result(&[link, io], &config, later);
+        assert_eq!(result.state, CheckState::Warning);
+
+        config.signatures[0].contains.clear();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn kernel_limits_are_measured_against_their_maximum() {
+        assert_eq!(
+            parse_file_nr("9760\t0\t9223372036854775807\n"),
+            Some(LimitUsage {
+                used: 9760,
+                max: 9223372036854775807,
+            })
+        );
+        assert_eq!(parse_file_nr("9760 0"), None);
+        assert_eq!(
+            parse_loadavg_threads("0.52 0.58 0.59 3/1024 12345\n"),
+            Some(1024)
+        );
+
+        let config = CheckConfig::default();
+        let mut limits = KernelLimits {
+            file_descriptors: Some(LimitUsage { used: 50, max: 100 }),
+            pids: Some(LimitUsage {
+                used: 31000,
+                max: 32768,
+            }),
+            conntrack: None,
+            entropy_bits: Some(256),
+        };
+        let result = kernel_limits_result(&limits, &config.kernel_limits, &config.entropy);
+        assert_eq!(result.state, CheckState::Warning);
+        assert_eq!(
+            result.output,
+            "Kernel limits: file descriptors 50/100 (50.0%), pids 31000/32768 (94.6%), \
+             entropy 256 bits"
+        );
+
+        limits.pids = None;
+        limits.entropy_bits = Some(32);
+        let result = kernel_limits_result(&limits, &config.kernel_limits, &config.entropy);
+        assert_eq!(result.state, CheckState::Critical);
+
+        let names: Vec<String> = kernel_limit_metrics(&limits)
+            .into_iter()
+            .map(|metric| metric.name)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "kernel_file_descriptors_used",
+                "kernel_file_descriptors_max",
+                "kernel_entropy_available_bits",
+            ]
+        );
+    }
+
+    #[test]
+    fn history_query_downsamples_rollups_and_raw_samples() {
+        let rollups = VecDeque::from([
+            Rollup::of(0, &[1.0, 2.0, 3.0]).unwrap(),
+            Rollup::of(300, &[10.0, 20.0]).unwrap(),
+            // Covered by the raw samples below
+            Rollup::of(600, &[99.0]).unwrap(),
+        ]);
+        let at = |secs: i64| {
+            chrono::DateTime::from_timestamp(secs, 0)
+                .unwrap()
+                .to_rfc3339()
+        };
+        let raw = VecDeque::from([(at(600), 4.0), (at(630), 8.0), (at(900), 6.0)]);
+        let all = series_rollups(&raw, Some(&rollups));
+        assert_eq!(all.len(), 5);
+
+        let avg = downsample(&all, 0, 1200, 600, Aggregation::Avg);
+        assert_eq!(avg, [(at(0), 7.2), (at(600), 6.0)]);
+        let max = downsample(&all, 0, 1200, 600, Aggregation::Max);
+        assert_eq!(max, [(at(0), 20.0), (at(600), 8.0)]);
+        let min = downsample(&all, 300, 1200, 600, Aggregation::Min);
+        assert_eq!(min, [(at(300), 4.0), (at(900), 6.0)]);
+        assert_eq!(all[0].p95, 3.0);
+        let p95 = downsample(&all, 0, 600, 600, Aggregation::P95);
+        assert_eq!(p95, [(at(0), 20.0)]);
+    }
+
+    #[test]
+    fn exports_redact_command_lines_and_usernames() {
+        let event = |kind: EventKind, title: &str, detail: &str| TimelineEvent {
+            id: 1,
+            kind,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            title: title.to_string(),
+            detail: detail.to_string(),
+            source: None,
+            state: None,
+        };
+        let mut export = DataExport {
+            host: "web1".to_string(),
+            exported_at: chrono::Utc::now().to_rfc3339(),
+            events: vec![
+                event(EventKind::Account, "Email of alice changed", "a@x -> b@x"),
+                event(
+                    EventKind::Security,
+                    "Autorun: Run\\updater = C:\\up.exe --token secret",
+                    "updater runs C:\\up.exe --token secret at logon",
+                ),
+                event(EventKind::Alert, "cpu CRITICAL", "malice at 99%"),
+            ],
+            logins: vec![LoginRecord {
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                username: Some("alice".to_string()),
+                method: LoginMethod::Password,
+                ip: None,
+                success: true,
+                reason: None,
+            }],
+            crashes: Vec::new(),
+            security_findings: vec![SecurityFinding {
+                kind: SecurityFindingKind::Process,
+                key: "/home/alice/.cache/xmrig".to_string(),
+                detail: "pid 4242".to_string(),
+            }],
+            history: BTreeMap::new(),
+        };
+        let usernames = ["alice".to_string()];
+
+        redact_export(&mut export, &PrivacyConfig::default(), &usernames).unwrap();
+        assert_eq!(export.events[0].title, "Email of alice changed");
+
+        let config = PrivacyConfig {
+            redact_command_lines: true,
+            redact_usernames: true,
+            ..Default::default()
+        };
+        redact_export(&mut export, &config, &usernames).unwrap();
+        assert_eq!(export.events[0].title, "Email of [redacted] changed");
+        assert_eq!(export.events[1].title, "Autorun: Run\\updater = [redacted]");
+        assert_eq!(export.events[1].detail, "[redacted]");
+        // Whole words only
+        assert_eq!(export.events[2].detail, "malice at 99%");
+        assert_eq!(export.logins[0].username.as_deref(), Some("[redacted]"));
+        assert_eq!(
+            export.security_findings[0].key,
+            "/home/[redacted]/.cache/xmrig"
+        );
+
+        // Crash log lines lose the usernames even when command lines are kept
+        export.crashes.push(CrashEvent {
+            kind: CrashKind::CoreDump,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            process: "app".to_string(),
+            pid: 4242,
+            message: "Process 4242 (/home/alice/app) dumped core".to_string(),
+        });
+        let usernames_only = PrivacyConfig {
+            redact_usernames: true,
+            ..Default::default()
+        };
+        redact_export(&mut export, &usernames_only, &usernames).unwrap();
+        assert_eq!(
+            export.crashes[0].message,
+            "Process 4242 (/home/[redacted]/app) dumped core"
+        );
+    }
+
+    #[test]
+    fn cron_schedules_find_their_next_run() {
+        let at =
+            |text: &str| chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap();
+        // A Wednesday
+        let now = at("2025-01-01 10:07");
+
+        let every_quarter = CronSchedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(every_quarter.next_after(now), Some(at("2025-01-01 10:15")));
+        let nightly = CronSchedule::parse("@daily").unwrap();
+        assert_eq!(nightly.next_after(now), Some(at("2025-01-02 00:00")));
+        let weekdays = CronSchedule::parse("30 2 * * mon-fri").unwrap();
+        assert_eq!(
+            weekdays.next_after(at("2025-01-03 03:00")),
+            Some(at("2025-01-06 02:30"))
+        );
+        // Either day field matches when both are set
+        let either = CronSchedule::parse("0 0 15 * 0").unwrap();
+        assert_eq!(either.next_after(now), Some(at("2025-01-05 00:00")));
+        assert_eq!(
+            CronSchedule::parse("0 0 * * 7").unwrap().next_after(now),
+            Some(at("2025-01-05 00:00"))
+        );
+        assert_eq!(
+            CronSchedule::parse("0 0 31 2 *").unwrap().next_after(now),
+            None
+        );
+
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+
+        let utc = chrono::Utc::now();
+        let every = Schedule::new(None, Duration::from_secs(60)).unwrap();
+        assert_eq!(every.next_run(None, utc), Some(utc));
+        assert_eq!(
+            every.next_run(Some(utc), utc),
+            Some(utc + chrono::Duration::seconds(60))
+        );
+    }
+
+    #[test]
+    fn notification_storms_are_throttled_into_a_summary() {
+        let config = NotificationThrottleConfig {
+            enabled: true,
+            global: BucketConfig {
+                burst: 3,
+                per_minute: 60.0,
+            },
+            per_channel: BucketConfig {
+                burst: 2,
+                per_minute: 6.0,
+            },
+        };
+        let alert = |check: &str, state: CheckState| Alert {
+            id: 1,
+            check: check.to_string(),
+            state,
+            message: String::new(),
+            raised_at: chrono::Utc::now().to_rfc3339(),
+            resolved_at: None,
+            acknowledged_by: None,
+            value: None,
+            threshold: None,
+            parent_id: None,
+        };
+        let start = Instant::now();
+        let mut throttle = NotificationThrottle::default();
+        let admit = |throttle: &mut NotificationThrottle, channel: &str, check: &str| {
+            throttle.admit(&config, channel, &alert(check, CheckState::Warning), start)
+        };
+
+        assert!(matches!(
+            admit(&mut throttle, "alice via email", "cpu"),
+            Admission::Send
+        ));
+        assert!(matches!(
+            admit(&mut throttle, "alice via email", "disk"),
+            Admission::Send
+        ));
+        // The channel's burst is used up
+        assert!(matches!(
+            admit(&mut throttle, "alice via email", "load"),
+            Admission::Suppressed { first: true }
+        ));
+        throttle.admit(
+            &config,
+            "alice via email",
+            &alert("load", CheckState::Critical),
+            start,
+        );
+        // Another channel still has its own burst, until the global bucket runs dry
+        assert!(matches!(
+            admit(&mut throttle, "bob via sms", "cpu"),
+            Admission::Send
+        ));
+        assert!(matches!(
+            admit(&mut throttle, "bob via sms", "cpu"),
+            Admission::Suppressed { first: true }
+        ));
+
+        // One token every ten seconds for alice's email
+        let wait = throttle
+            .take_summary(&config, "alice via email", start)
+            .unwrap_err();
+        assert_eq!(wait.as_secs(), 10);
+        let summary = throttle
+            .take_summary(&config, "alice via email", start + wait)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            summary,
+            SuppressedNotifications {
+                count: 2,
+                worst: CheckState::Critical,
+                checks: vec!["load".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn stream_deltas_carry_only_changed_fields() {
+        let previous = serde_json::json!({
+            "overall": "ok",
+            "memory_percent": 41.0,
+            "problems": [],
+            "degraded": { "since": "earlier" },
+            "host": "box",
+        });
+        let current = serde_json::json!({
+            "overall": "warning",
+            "memory_percent": 41.0,
+            "problems": [{ "name": "disk", "state": "warning" }],
+            "host": "box",
+        });
+        assert_eq!(
+            json_delta(&previous, &current),
+            Some(serde_json::json!({
+                "overall": "warning",
+                "problems": [{ "name": "disk", "state": "warning" }],
+                "degraded": null,
+            }))
+        );
+        assert_eq!(json_delta(&current, &current), None);
+
+        // The first message is a snapshot only, an unchanged overview sends nothing
+        let first = stream_message(1, None, &previous).unwrap();
+        assert!(first.delta.is_none());
+        assert!(stream_message(2, Some(&previous), &previous).is_none());
+        let second = stream_message(2, Some(&previous), &current).unwrap();
+        assert_eq!(&*second.snapshot, current.to_string());
+        assert!(second.delta.unwrap().contains("\"degraded\":null"));
+    }
+
+    #[test]
+    fn reporter_diffs_are_reassembled_by_the_manager() {
+        let now = chrono::Utc::now();
+        let check = |name: &str, state: CheckState| {
+            CheckResult::new(name, state, String::new(), Vec::new())
+        };
+        let first = host_snapshot(
+            &[
+                Metric::new("memory_used_percent", 41.004),
+                Metric::new("disk_used_percent", 70.0).label("mount", "/"),
+                Metric::new("swap_used_percent", 0.0),
+            ],
+            &[check("cpu", CheckState::Ok)],
+        );
+        assert_eq!(first.metrics["memory_used_percent"], 41.0);
+        assert!(first.metrics.contains_key("disk_used_percent{mount=\"/\"}"));
+        let second = host_snapshot(
+            &[
+                Metric::new("memory_used_percent", 41.001),
+                Metric::new("disk_used_percent", 71.5).label("mount", "/"),
+            ],
+            &[check("cpu", CheckState::Warning)],
+        );
+
+        let mut reporter = SnapshotReporter::default();
+        let mut hosts = BTreeMap::new();
+        let report = reporter.next_report("box", first.clone(), 4, now);
+        assert!(matches!(report.body, ReportBody::Full { .. }));
+        receive_report(&mut hosts, report, now).unwrap();
+
+        // Only the disk, the removed swap series and the check state travel
+        let report = reporter.next_report("box", second.clone(), 4, now);
+        let ReportBody::Diff { diff } = &report.body else {
+            panic!("expected a diff");
+        };
+        assert_eq!(diff.metrics.len(), 1);
+        assert_eq!(diff.removed_metrics, vec!["swap_used_percent".to_string()]);
+        assert_eq!(diff.checks["cpu"].state, CheckState::Warning);
+        receive_report(&mut hosts, report, now).unwrap();
+        assert_eq!(hosts["box"].snapshot, second);
+        assert_eq!(hosts["box"].overall, CheckState::Warning);
+
+        // A lost report makes the manager refuse the next diff until a full snapshot comes
+        reporter.next_report("box", first.clone(), 4, now);
+        let report = reporter.next_report("box", second.clone(), 4, now);
+        assert!(matches!(report.body, ReportBody::Diff { .. }));
+        assert!(receive_report(&mut hosts, report, now).is_err());
+        reporter.resync();
+        let report = reporter.next_report("box", first.clone(), 4, now);
+        assert!(matches!(report.body, ReportBody::Full { .. }));
+        receive_report(&mut hosts, report, now).unwrap();
+        assert_eq!(hosts["box"].snapshot, first);
+
+        // And every fourth report is a full snapshot anyway
+        reporter.next_report("box", second.clone(), 4, now);
+        reporter.next_report("box", first.clone(), 4, now);
+        reporter.next_report("box", second.clone(), 4, now);
+        let report = reporter.next_report("box", first, 4, now);
+        assert!(matches!(report.body, ReportBody::Full { .. }));
+    }
+
+    #[test]
+    fn battery_power_turns_on_power_saving() {
+        let supply = |kind: &str, online: Option<bool>, status: Option<&str>| PowerSupply {
+            kind: kind.to_string(),
+            scope: None,
+            online,
+            status: status.map(str::to_string),
+        };
+        let laptop = |plugged_in: bool| {
+            vec![
+                supply("Mains", Some(plugged_in), None),
+                supply("Battery", None, Some("Discharging")),
+            ]
+        };
+        assert_eq!(power_source_of(&laptop(true)), PowerSource::Ac);
+        assert_eq!(power_source_of(&laptop(false)), PowerSource::Battery);
+        assert_eq!(
+            power_source_of(&[supply("Battery", None, Some("Charging"))]),
+            PowerSource::Ac
+        );
+        // A wireless mouse doesn't make a desktop a laptop
+        let mouse = PowerSupply {
+            scope: Some("Device".to_string()),
+            ..supply("Battery", None, Some("Discharging"))
+        };
+        assert_eq!(power_source_of(&[mouse]), PowerSource::Unknown);
+        assert_eq!(
+            parse_pmset_power_source("Now drawing from 'Battery Power'\n -InternalBattery-0"),
+            PowerSource::Battery
+        );
+
+        let now = chrono::Utc::now();
+        let mut config = PowerConfig::default();
+        let saving = next_power_saving(&config, None, PowerSource::Battery, now).unwrap();
+        assert_eq!(saving.backoff_factor, 4);
+        assert!(next_power_saving(&config, Some(&saving), PowerSource::Ac, now).is_none());
+        config.saving = PowerSaving::Never;
+        assert!(next_power_saving(&config, None, PowerSource::Battery, now).is_none());
+        config.saving = PowerSaving::Always;
+        assert!(next_power_saving(&config, None, PowerSource::Ac, now).is_some());
+    }
+
+    #[test]
+    fn down_hosts_can_be_woken_by_mac() {
+        let mac = parse_mac_address("00:1A:2b:3c:4d:5e").unwrap();
+        assert_eq!(mac, [0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e]);
+        assert_eq!(parse_mac_address("00-1a-2b-3c-4d-5e"), Ok(mac));
+        assert!(parse_mac_address("00:1a:2b:3c:4d").is_err());
+        assert!(parse_mac_address("00:1a:2b:3c:4d:zz").is_err());
+        let packet = magic_packet(&mac);
+        assert_eq!(packet.len(), 102);
+        assert_eq!(packet[..6], [0xFF; 6]);
+        assert_eq!(packet[96..], mac);
+
+        let now = chrono::Utc::now();
+        let config = ManagerConfig {
+            enabled: true,
+            offline_after_secs: 300,
+            hosts: vec![ManagedHost {
+                name: "nas".to_string(),
+                mac: Some("00:1a:2b:3c:4d:5e".to_string()),
+                wake_broadcast: None,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let reported = |name: &str, ago: i64| ReportedHost {
+            host: name.to_string(),
+            sequence: 1,
+            collected_at: now.to_rfc3339(),
+            last_report: (now - chrono::Duration::seconds(ago)).to_rfc3339(),
+            overall: CheckState::Warning,
+            snapshot: HostSnapshot::default(),
+        };
+        let mut hosts = BTreeMap::new();
+        hosts.insert("web".to_string(), reported("web", 10));
+        let fleet = fleet_hosts(&config, &hosts, now);
+        // Configured hosts show up before their first report, as down
+        assert_eq!(fleet.len(), 2);
+        assert_eq!(fleet[0].name, "nas");
+        assert!(!fleet[0].online);
+        assert_eq!(fleet[0].overall, CheckState::Unknown);
+        assert!(fleet[0].mac.is_some());
+        assert!(fleet[1].online);
+        assert_eq!(fleet[1].overall, CheckState::Warning);
+
+        hosts.insert("nas".to_string(), reported("nas", 600));
+        assert!(!fleet_hosts(&config, &hosts, now)[0].online);
+    }
+
+    #[test]
+    fn agents_apply_only_newer_bundles_signed_by_their_manager() {
+        let config = TempConfig::new();
+        let mut auth_manager = manager_with_user(&config);
+        let key = random_secret_key();
+        let mut checks = auth_manager.config.checks.clone();
+        checks.cpu.warning = 60.0;
+        let bundle = |version| ConfigBundle {
+            version,
+            issued_at: chrono::Utc::now().to_rfc3339(),
+            settings: FleetSettings {
+                checks: Some(checks.clone()),
+                ..Default::default()
+            },
+        };
+        let signed = sign_config_bundle(&key, &bundle(2)).unwrap();
+
+        // Nothing is taken before the manager's key is configured
+        assert!(auth_manager.apply_config_bundle(&signed, "alice").is_err());
+        auth_manager.config.reporter.manager_public_key = base64url(&public_key_bytes(&key));
+
+        let forged = sign_config_bundle(&random_secret_key(), &bundle(3)).unwrap();
+        assert!(auth_manager.apply_config_bundle(&forged, "alice").is_err());
+        let mut tampered = signed.clone();
+        tampered.bundle = tampered.bundle.replace("60.0", "99.0");
+        assert!(
+            auth_manager
+                .apply_config_bundle(&tampered, "alice")
+                .is_err()
+        );
+
+        let changes = auth_manager.apply_config_bundle(&signed, "alice").unwrap();
+        assert_eq!(changes, vec!["checks.cpu.warning: 80.0 -> 60.0"]);
+        assert_eq!(auth_manager.config.reporter.applied_config_version, 2);
+        // Replays and older bundles are refused
+        assert!(auth_manager.apply_config_bundle(&signed, "alice").is_err());
+        let older = sign_config_bundle(&key, &bundle(1)).unwrap();
+        assert!(auth_manager.apply_config_bundle(&older, "alice").is_err());
+
+        let reloaded = AuthManager::new(&config.path()).unwrap();
+        assert_eq!(reloaded.config.checks.cpu.warning, 60.0);
+    }
+
+    #[tokio::test]
+    async fn enrolled_agents_report_once_approved() {
+        let config = TempConfig::new();
+        let mut auth_manager = manager_with_user(&config);
+        auth_manager.config.manager.enabled = true;
+        let join_token = auth_manager.create_join_token("alice").unwrap().token;
+        let app = test_app(auth_manager);
+
+        let post = |path: &str, body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri(path)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let enroll = serde_json::json!({
+            "join_token": join_token,
+            "host": "enrolled-nas",
+            "metadata": { "os": "Linux", "agent_url": "http://nas:3000" },
+            "agent_token": "agent-secret-1",
+        });
+        let response = app
+            .clone()
+            .oneshot(post("/api/central/enroll", enroll.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let enrolled: EnrollmentResponse = serde_json::from_slice(&body).unwrap();
+        assert!(!enrolled.manager_public_key.is_empty());
+        // Join tokens work once
+        let response = app
+            .clone()
+            .oneshot(post("/api/central/enroll", enroll))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let report = |host: &str| {
+            post(
+                &format!("/api/central/report?token={}", enrolled.credential),
+                serde_json::json!({
+                    "host": host,
+                    "sequence": 1,
+                    "collected_at": chrono::Utc::now().to_rfc3339(),
+                    "kind": "full",
+                    "snapshot": { "metrics": {}, "checks": {} },
+                }),
+            )
+        };
+        let response = app.clone().oneshot(report("enrolled-nas")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = app
+            .clone()
+            .oneshot(post(
+                &format!("/api/central/hosts/enrolled-nas/approve?token={}", TOKEN),
+                serde_json::json!({}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = app.clone().oneshot(report("enrolled-nas")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        // The credential only reports as its own host
+        let response = app.clone().oneshot(report("other-host")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let (_, hosts) = get(app, &format!("/api/central/hosts?token={}", TOKEN)).await;
+        let hosts: serde_json::Value = serde_json::from_str(&hosts).unwrap();
+        let host = hosts
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|host| host["name"] == "enrolled-nas")
+            .unwrap();
+        assert_eq!(host["enrollment"], "approved");
+        assert_eq!(host["metadata"]["agent_url"], "http://nas:3000");
+        assert!(!hosts.to_string().contains("credential"));
+    }
+
+    #[test]
+    fn standby_manager_syncs_and_takes_over() {
+        let now = chrono::Utc::now();
+        let reported = |name: &str, sequence: u64, ago: i64| ReportedHost {
+            host: name.to_string(),
+            sequence,
+            collected_at: now.to_rfc3339(),
+            last_report: (now - chrono::Duration::seconds(ago)).to_rfc3339(),
+            overall: CheckState::Ok,
+            snapshot: HostSnapshot::default(),
+        };
+        let mut local = BTreeMap::new();
+        local.insert("web".to_string(), reported("web", 7, 5));
+        local.insert("db".to_string(), reported("db", 3, 90));
+        // The newer report of each host wins, whichever manager received it
+        let taken = merge_reported_hosts(
+            &mut local,
+            vec![
+                reported("web", 6, 65),
+                reported("db", 4, 30),
+                reported("nas", 1, 10),
+            ],
+        );
+        assert_eq!(taken, 2);
+        assert_eq!(local["web"].sequence, 7);
+        assert_eq!(local["db"].sequence, 4);
+        assert!(local.contains_key("nas"));
+
+        let cluster = ClusterConfig {
+            role: ClusterRole::Standby,
+            peer_url: "http://primary:3000".to_string(),
+            peer_token: "token-primary".to_string(),
+            ..Default::default()
+        };
+        assert!(cluster.validate().is_ok());
+        assert!(!cluster_active(&cluster, None, now));
+        let down_since = |secs| Some(now - chrono::Duration::seconds(secs));
+        assert!(!cluster_active(&cluster, down_since(60), now));
+        assert!(cluster_active(&cluster, down_since(120), now));
+        let primary = ClusterConfig {
+            role: ClusterRole::Primary,
+            ..cluster.clone()
+        };
+        assert!(cluster_active(&primary, down_since(600), now));
+
+        let config = TempConfig::new();
+        let mut auth_manager = manager_with_user(&config);
+        let state = ClusterState {
+            hosts: vec![ManagedHost {
+                name: "nas".to_string(),
+                mac: Some("00:1a:2b:3c:4d:5e".to_string()),
+                ..Default::default()
+            }],
+            join_tokens: Vec::new(),
+            fleet_settings: FleetSettings::default(),
+            reported: Vec::new(),
+        };
+        assert!(auth_manager.apply_cluster_state(&state).unwrap());
+        assert!(!auth_manager.apply_cluster_state(&state).unwrap());
+        let reloaded = AuthManager::new(&config.path()).unwrap();
+        assert_eq!(reloaded.config.manager.hosts, state.hosts);
+    }
+
+    #[test]
+    fn fleet_grid_filters_by_environment_and_tag() {
+        let now = chrono::Utc::now();
+        let host = |name: &str, environment: &str, tags: &[&str]| ManagedHost {
+            name: name.to_string(),
+            environment: Some(environment.to_string()),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            agent_url: Some(format!("http://{}:3000", name)),
+            agent_token: Some("token-agent".to_string()),
+            ..Default::default()
+        };
+        let config = ManagerConfig {
+            enabled: true,
+            hosts: vec![
+                host("web1", "production", &["web"]),
+                host("db1", "production", &["db"]),
+                host("web2", "staging", &["web"]),
+            ],
+            ..Default::default()
+        };
+        let snapshot = HostSnapshot {
+            metrics: BTreeMap::new(),
+            checks: [
+                ("cpu", CheckState::Warning),
+                ("disk", CheckState::Critical),
+                ("memory", CheckState::Ok),
+            ]
+            .into_iter()
+            .map(|(name, state)| {
+                let output = String::new();
+                (name.to_string(), ReportedCheck { state, output })
+            })
+            .collect(),
+        };
+        let mut reported = BTreeMap::new();
+        reported.insert(
+            "web1".to_string(),
+            ReportedHost {
+                host: "web1".to_string(),
+                sequence: 1,
+                collected_at: now.to_rfc3339(),
+                last_report: now.to_rfc3339(),
+                overall: snapshot.overall(),
+                snapshot,
+            },
+        );
+        let hosts = fleet_hosts(&config, &reported, now);
+
+        let grid = fleet_grid(&hosts, &FleetGridQuery::default());
+        assert_eq!(grid.tiles.len(), 3);
+        assert_eq!(grid.environments, vec!["production", "staging"]);
+        assert_eq!(grid.tags, vec!["db", "web"]);
+        let web1 = grid.tiles.iter().find(|tile| tile.name == "web1").unwrap();
+        assert_eq!(web1.state, CheckState::Critical);
+        assert_eq!(web1.failing, vec!["disk", "cpu"]);
+        assert_eq!(web1.dashboard_url.as_deref(), Some("http://web1:3000"));
+        let db1 = grid.tiles.iter().find(|tile| tile.name == "db1").unwrap();
+        assert!(!db1.online);
+        assert_eq!(db1.state, CheckState::Unknown);
+
+        let filtered = |environment: &str, tag: &str| {
+            let query = FleetGridQuery {
+                environment: Some(environment.to_string()),
+                tag: Some(tag.to_string()),
+            };
+            fleet_grid(&hosts, &query)
+                .tiles
+                .into_iter()
+                .map(|tile| tile.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(filtered("production", ""), vec!["db1", "web1"]);
+        assert_eq!(filtered("", "web"), vec!["web1", "web2"]);
+        assert_eq!(filtered("staging", "db"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn host_comparison_points_out_the_outlier() {
+        let start = chrono::DateTime::from_timestamp(1_700_000_100, 0).unwrap();
+        let snapshot = |cpu: f64| HostSnapshot {
+            metrics: [
+                ("cpu_usage_percent".to_string(), cpu),
+                ("memory_used_bytes".to_string(), 2.0),
+                ("memory_total_bytes".to_string(), 8.0),
+            ]
+            .into_iter()
+            .collect(),
+            checks: BTreeMap::new(),
+        };
+        let mut history = FleetHistory::default();
+        for (host, cpu) in [("web1", 20.0), ("web2", 25.0), ("web3", 90.0)] {
+            assert!(!history.record(host, &snapshot(cpu), start));
+            assert!(!history.record(host, &snapshot(cpu + 10.0), start));
+            let next_bucket = start + chrono::Duration::seconds(ROLLUP_SECS);
+            assert!(history.record(host, &snapshot(cpu), next_bucket));
+        }
+
+        let query = |metric: ComparedMetric, hosts: Option<&str>| ComparisonQuery {
+            metric,
+            hosts: hosts.map(str::to_string),
+            from: None,
+            to: None,
+            interval: None,
+        };
+        let now = start + chrono::Duration::seconds(ROLLUP_SECS + 60);
+        let cpu = compare_hosts(&history, &query(ComparedMetric::Cpu, None), now).unwrap();
+        let names: Vec<&str> = cpu.hosts.iter().map(|host| host.host.as_str()).collect();
+        assert_eq!(names, vec!["web1", "web2", "web3"]);
+        let web1 = &cpu.hosts[0];
+        assert_eq!(web1.points.len(), 2);
+        assert_eq!(web1.points[0].1, 25.0);
+        assert_eq!(web1.points[1].1, 20.0);
+        assert_eq!(web1.average, Some(70.0 / 3.0));
+        assert_eq!(cpu.outlier.as_deref(), Some("web3"));
+
+        // Everyone uses the same share of memory, so nobody stands out
+        let memory = compare_hosts(&history, &query(ComparedMetric::Memory, None), now).unwrap();
+        assert!(memory.hosts.iter().all(|host| host.average == Some(25.0)));
+        assert_eq!(memory.outlier, None);
+
+        // Two hosts aren't enough to tell which one is off
+        let two = query(ComparedMetric::Cpu, Some("web1,web3"));
+        assert_eq!(compare_hosts(&history, &two, now).unwrap().outlier, None);
+        let unknown = query(ComparedMetric::Cpu, Some("web4"));
+        assert!(compare_hosts(&history, &unknown, now).is_err());
+        let no_disk = query(ComparedMetric::Disk, Some("web1"));
+        assert!(compare_hosts(&history, &no_disk, now).is_err());
+
+        // Only finished buckets are saved
+        let saved = serde_json::to_string(&history).unwrap();
+        let loaded: FleetHistory = serde_json::from_str(&saved).unwrap();
+        let loaded = compare_hosts(&loaded, &query(ComparedMetric::Cpu, None), now).unwrap();
+        assert_eq!(loaded.hosts[0].points.len(), 1);
+        assert_eq!(loaded.outlier.as_deref(), Some("web3"));
+    }
+
+    #[test]
+    fn fleet_alerts_are_grouped_then_routed_by_host() {
+        let now = chrono::Utc::now();
+        let managed = |name: &str, tags: &[&str]| ManagedHost {
+            name: name.to_string(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        };
+        let route = |name: &str, hosts: &[&str], tags: &[&str]| AlertRoute {
+            name: name.to_string(),
+            hosts: hosts.iter().map(|host| host.to_string()).collect(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            environments: Vec::new(),
+            contacts: vec![name.to_string()],
+        };
+        let config = ManagerConfig {
+            enabled: true,
+            hosts: vec![managed("db-1", &[]), managed("cache-1", &["db"])],
+            alert_routes: vec![route("dba", &["db-*"], &[]), route("storage", &[], &["db"])],
+            ..Default::default()
+        };
+        let contact = |name: &str| Contact {
+            name: name.to_string(),
+            severities: vec![CheckState::Critical],
+            channels: Vec::new(),
+        };
+        assert!(
+            config
+                .validate_alert_routes(&[contact("dba"), contact("storage")])
+                .is_ok()
+        );
+        assert_eq!(
+            config.validate_alert_routes(&[contact("dba")]).unwrap_err(),
+            "manager.alert_routes 'storage': no contact named 'storage'"
+        );
+
+        let failing = |checks: &[&str]| HostSnapshot {
+            metrics: BTreeMap::new(),
+            checks: checks
+                .iter()
+                .map(|name| {
+                    let state = CheckState::Critical;
+                    let output = "Full".to_string();
+                    (name.to_string(), ReportedCheck { state, output })
+                })
+                .collect(),
+        };
+        let reported = |name: &str, ago: i64, snapshot: HostSnapshot| ReportedHost {
+            host: name.to_string(),
+            sequence: 1,
+            collected_at: now.to_rfc3339(),
+            last_report: (now - chrono::Duration::seconds(ago)).to_rfc3339(),
+            overall: snapshot.overall(),
+            snapshot,
+        };
+        let mut reports = BTreeMap::new();
+        for host in [
+            reported("db-1", 10, failing(&["disk", "postgres"])),
+            reported("cache-1", 10, failing(&["redis"])),
+            reported("web-1", 10, failing(&["nginx"])),
+        ] {
+            reports.insert(host.host.clone(), host);
+        }
+        let grouping = AlertGroupingConfig::default();
+        let mut alerts = BTreeMap::new();
+
+        // The disk and postgres alerts on db-1 are one incident, paged once
+        let hosts = fleet_hosts(&config, &reports, now);
+        let notify = evaluate_fleet_alerts(&mut alerts, &hosts, &config, &grouping);
+        let paged: Vec<(&str, &str, Option<&str>)> = notify
+            .iter()
+            .map(|(host, alert)| {
+                let route = route_alert(&config.alert_routes, host);
+                (
+                    host.name.as_str(),
+                    alert.check.as_str(),
+                    route.map(|route| route.name.as_str()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            paged,
+            vec![
+                ("cache-1", "redis", Some("storage")),
+                ("db-1", "disk", Some("dba")),
+                ("web-1", "nginx", None),
+            ]
+        );
+        assert_eq!(alerts["db-1"].active().len(), 2);
+        assert!(evaluate_fleet_alerts(&mut alerts, &hosts, &config, &grouping).is_empty());
+
+        // A host that stopped reporting pages about that alone, once the incident's window is over
+        reports.insert(
+            "db-1".to_string(),
+            reported("db-1", 600, failing(&["disk", "postgres"])),
+        );
+        let hosts = fleet_hosts(&config, &reports, now);
+        let later = AlertGroupingConfig {
+            window_secs: 0,
+            ..Default::default()
+        };
+        let notify = evaluate_fleet_alerts(&mut alerts, &hosts, &config, &later);
+        assert_eq!(notify.len(), 1);
+        assert_eq!(notify[0].0.name, "db-1");
+        assert_eq!(notify[0].1.check, "reporting");
+        assert_eq!(notify[0].1.state, CheckState::Critical);
+    }
+
+    #[test]
+    fn print_queues_show_offline_printers_and_stuck_jobs() {
+        let printers = parse_lpstat_printers(
+            "printer Office is idle.  enabled since Fri Oct 16 09:12:44 2026\n\
+             printer HP-Laser now printing HP-Laser-42.  enabled since Fri Oct 16 09:00:00 2026\n\
+             \tPrinter is offline\n\
+             printer Labels disabled since Fri Oct 16 08:00:00 2026 -\n\
+             \tPaused\n",
+        );
+        let states: Vec<(&str, &str, bool)> = printers
+            .iter()
+            .map(|p| (p.name.as_str(), p.state.as_str(), p.offline))
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                ("Office", "idle", false),
+                ("HP-Laser", "printing", true),
+                ("Labels", "disabled", true),
+            ]
+        );
+        assert_eq!(printers[1].message.as_deref(), Some("Printer is offline"));
+
+        let jobs = parse_lpstat_jobs(
+            "HP-Laser-42             alice           102400   Fri Oct 16 08:30:01 2026\n\
+             Office-7                bob               2048   Fri Oct  9 09:05:00 2026\n",
+        );
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].printer, "HP-Laser");
+        assert_eq!(jobs[0].size_bytes, 102400);
+        assert_eq!(jobs[1].printer, "Office");
+        // A weekday that doesn't match the date doesn't lose the time
+        let mismatched = parse_lpstat_jobs("Labels-3  carol  512  Thu Oct 16 08:30:01 2026\n");
+        assert_eq!(mismatched[0].submitted_at, jobs[0].submitted_at);
+        assert_eq!(mismatched[0].unreadable_date, None);
+        let garbled = parse_lpstat_jobs("Labels-4  carol  512  16/10/2026 08:30\n");
+        assert_eq!(garbled[0].submitted_at, None);
+        assert_eq!(
+            garbled[0].unreadable_date.as_deref(),
+            Some("16/10/2026 08:30")
+        );
+        let submitted = |job: &PrintJob| {
+            chrono::DateTime::parse_from_rfc3339(job.submitted_at.as_ref().unwrap())
+                .unwrap()
+                .with_timezone(&chrono::Utc)
+        };
+        assert_eq!(
+            submitted(&jobs[0]) - submitted(&jobs[1]),
+            chrono::Duration::days(7) - chrono::Duration::minutes(35)
+                + chrono::Duration::seconds(1)
+        );
+
+        // Ten minutes after the first job only the week old one is stuck
+        let config = PeripheralConfig {
+            enabled: true,
+            stuck_job_minutes: 30,
+            ..Default::default()
+        };
+        let now = submitted(&jobs[0]) + chrono::Duration::minutes(10);
+        let report = peripheral_report(printers.clone(), jobs, Vec::new(), &config, now);
+        let office = &report.printers[0];
+        assert_eq!((office.queued_jobs, office.stuck_jobs), (1, 1));
+        let laser = &report.printers[1];
+        assert_eq!((laser.queued_jobs, laser.stuck_jobs), (1, 0));
+        let result = printers_check_result(&report);
+        assert_eq!(result.state, CheckState::Critical);
+        assert_eq!(
+            result.output,
+            "HP-Laser offline (Printer is offline), Labels offline (Paused), Office has 1 stuck job(s)"
+        );
+
+        let ready: Vec<Printer> = printers.into_iter().take(1).collect();
+        let report = peripheral_report(ready, Vec::new(), Vec::new(), &config, now);
+        let result = printers_check_result(&report);
+        assert_eq!(result.state, CheckState::Ok);
+        assert_eq!(result.output, "1 printer(s) ready");
+    }
+
+    #[test]
+    fn kiosk_displays_alert_when_a_monitor_is_unplugged() {
+        assert_eq!(drm_display("card0", "", "", ""), None);
+        assert_eq!(
+            drm_display("card0-Writeback-1", "unknown\n", "disabled\n", ""),
+            None
+        );
+        let hdmi = drm_display(
+            "card0-HDMI-A-1",
+            "connected\n",
+            "enabled\n",
+            "1920x1080\n1280x720\n",
+        )
+        .unwrap();
+        assert_eq!(hdmi.connector, "HDMI-A-1");
+        assert_eq!(hdmi.resolution.as_deref(), Some("1920x1080"));
+        let dp = drm_display("card0-DP-1", "disconnected\n", "disabled\n", "").unwrap();
+        assert!(!dp.connected);
+
+        let audio = parse_asound_cards(
+            " 0 [PCH            ]: HDA-Intel - HDA Intel PCH\n\
+             \x20                     HDA Intel PCH at 0xf7f10000 irq 32\n\
+             \x201 [Device         ]: USB-Audio - USB Audio Device\n\
+             \x20                     Generic USB Audio Device at usb-0000:00:14.0-2, full speed\n",
+        );
+        let names: Vec<(u32, &str, &str)> = audio
+            .iter()
+            .map(|device| (device.index, device.id.as_str(), device.name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (0, "PCH", "HDA Intel PCH"),
+                (1, "Device", "USB Audio Device")
+            ]
+        );
+
+        let config = PeripheralConfig {
+            enabled: true,
+            expected_monitors: 1,
+            expected_audio: vec!["usb audio".to_string()],
+            ..Default::default()
+        };
+        let report = PeripheralReport {
+            displays: vec![hdmi.clone(), dp.clone()],
+            audio_devices: audio.clone(),
+            ..Default::default()
+        };
+        let seen: BTreeSet<String> = ["card0-HDMI-A-1".to_string()].into();
+        let result = displays_check_result(&report, &config, &seen);
+        assert_eq!(result.state, CheckState::Ok);
+        assert_eq!(
+            result.output,
+            "1 monitor(s): card0-HDMI-A-1 1920x1080, 2 audio device(s)"
+        );
+
+        // The HDMI monitor goes away and the USB sound card with it
+        let unplugged = DisplayOutput {
+            connected: false,
+            ..hdmi.clone()
+        };
+        let report = PeripheralReport {
+            displays: vec![unplugged.clone(), dp],
+            audio_devices: audio.into_iter().take(1).collect(),
+            ..Default::default()
+        };
+        let result = displays_check_result(&report, &config, &seen);
+        assert_eq!(result.state, CheckState::Critical);
+        assert_eq!(
+            result.output,
+            "0 of 1 monitors connected, monitor on card0-HDMI-A-1 disconnected, \
+             no audio device matching 'usb audio'"
+        );
+
+        // A second GPU with a connector of the same name doesn't stand in for the first
+        let other_card = DisplayOutput {
+            card: "card1".to_string(),
+            ..hdmi.clone()
+        };
+        let report = PeripheralReport {
+            displays: vec![unplugged.clone(), other_card],
+            ..Default::default()
+        };
+        let result = displays_check_result(&report, &config, &seen);
+        assert_eq!(result.state, CheckState::Critical);
+        let labels: Vec<String> = av_device_metrics(&report)
+            .iter()
+            .filter(|metric| metric.name == "display_connected")
+            .map(|metric| metric.to_string())
+            .collect();
+        assert_eq!(labels.len(), 2);
+        assert_ne!(labels[0], labels[1]);
+
+        // Without an expected count only monitors seen before count
+        let config = PeripheralConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let result = displays_check_result(&report, &config, &BTreeSet::new());
+        assert_eq!(result.state, CheckState::Ok);
+    }
+
+    #[test]
+    fn pushed_tag_values_cant_add_lines_to_the_scrape() {
+        let mut tags = BTreeMap::new();
+        tags.insert(
+            "queue".to_string(),
+            "mail\"}\nfake_metric{a=\"b\"} 1".to_string(),
+        );
+        record_custom_metrics(CustomMetricsPush {
+            metrics: vec![CustomMetricSample {
+                name: "escaped_tags".to_string(),
+                value: 3.0,
+                kind: CustomMetricKind::Gauge,
+                tags,
+            }],
+        })
+        .unwrap();
+        let line = custom_metrics()
+            .into_iter()
+            .find(|metric| metric.name == "custom_escaped_tags")
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            line,
+            r#"custom_escaped_tags{queue="mail\"}\nfake_metric{a=\"b\"} 1"} 3"#
+        );
+    }
+
+    #[test]
+    fn statsd_rejects_non_finite_values_and_caps_series() {
+        let mut statsd = StatsdAggregator::default();
+        assert!(statsd.record("requests:1|c").is_ok());
+        assert!(statsd.record("requests:nan|c").is_err());
+        assert!(statsd.record("temperature:inf|g").is_err());
+        assert!(statsd.record("temperature:-inf|g").is_err());
+
+        for i in 1..MAX_STATSD_SERIES {
+            statsd.record(&format!("series_{}:1|g", i)).unwrap();
+        }
+        assert!(statsd.record("one_too_many:1|g").is_err());
+        // Known series keep updating
+        assert!(statsd.record("requests:2|c").is_ok());
+        let requests = statsd
+            .metrics()
+            .into_iter()
+            .find(|metric| metric.name == "statsd_requests_total")
+            .unwrap();
+        assert_eq!(requests.value, 3.0);
+    }
+
+    #[test]
+    fn recordings_parse_spans_and_replay_their_frames() {
+        assert_eq!(parse_span("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_span("15m"), Some(Duration::from_secs(900)));
+        assert_eq!(parse_span("2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_span("0"), None);
+        assert_eq!(parse_span("5d"), None);
+        assert_eq!(parse_span("m"), None);
+
+        let args: Vec<String> = ["out.jsonl", "--duration", "10m", "--interval", "30s"]
+            .map(String::from)
+            .into();
+        let parsed = parse_record_args(&args).unwrap();
+        assert_eq!(parsed.path, PathBuf::from("out.jsonl"));
+        assert_eq!(parsed.duration, Duration::from_secs(600));
+        assert_eq!(parsed.interval, Duration::from_secs(30));
+        assert!(parse_record_args(&["--duration".to_string()]).is_err());
+        assert!(parse_record_args(&["--verbose".to_string()]).is_err());
+        assert!(parse_record_args(&[]).is_err());
+
+        // Frames written the way `record` does read back in order
+        let dir = TempConfig::new();
+        let path = dir.dir.join("recording.jsonl");
+        let start = chrono::Utc::now();
+        let frames: Vec<String> = [(0, 10.0), (10, 55.0), (20, 90.0)]
+            .iter()
+            .map(|(secs, cpu)| {
+                serde_json::to_string(&RecordedFrame {
+                    at: start + chrono::Duration::seconds(*secs),
+                    metrics: vec![
+                        Metric::new("cpu_usage_percent", *cpu),
+                        Metric::new("disk_used_percent", 40.0).label("mount", "/"),
+                    ],
+                })
+                .unwrap()
+            })
+            .collect();
+        fs::write(&path, frames.join("\n") + "\n\n").unwrap();
+
+        let replay = MetricReplay::load(&path, 1.0).unwrap();
+        let offsets: Vec<f64> = replay.frames.iter().map(|(offset, _)| *offset).collect();
+        assert_eq!(offsets, vec![0.0, 10.0, 20.0]);
+        // Right after loading the first sample plays, labelled metrics come back as recorded
+        assert_eq!(replay.cpu_usage(), 10.0);
+        assert_eq!(replay.metrics()[1].labels["mount"], "/");
+        // Far past the end the last one is held
+        let fast = MetricReplay::load(&path, 1e9).unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(fast.cpu_usage(), 90.0);
+
+        fs::write(&path, format!("{}\nnot json\n", frames[0])).unwrap();
+        let error = MetricReplay::load(&path, 1.0).err().unwrap();
+        assert!(error.contains("recording.jsonl line 2: "));
+        fs::write(&path, "").unwrap();
+        assert!(
+            MetricReplay::load(&path, 1.0)
+                .err()
+                .unwrap()
+                .ends_with("has no samples")
+        );
+    }
+
+    #[test]
+    fn request_latencies_fill_cumulative_histogram_buckets() {
+        for millis in [3, 10, 300, 20_000] {
+            record_latency("GET", "/test/latency", Duration::from_millis(millis));
+        }
+        let metrics: Vec<Metric> = http_latency_metrics()
+            .into_iter()
+            .filter(|metric| {
+                metric.labels.get("route").map(String::as_str) == Some("/test/latency")
+            })
+            .collect();
+        let buckets: Vec<(&str, f64)> = metrics
+            .iter()
+            .filter(|metric| metric.name == "http_request_duration_seconds_bucket")
+            .map(|metric| (metric.labels["le"].as_str(), metric.value))
+            .collect();
+        // A request right on a bound counts in that bucket, one past the last only in +Inf
+        assert_eq!(
+            buckets,
+            vec![
+                ("0.005", 1.0),
+                ("0.01", 2.0),
+                ("0.025", 2.0),
+                ("0.05", 2.0),
+                ("0.1", 2.0),
+                ("0.25", 2.0),
+                ("0.5", 3.0),
+                ("1", 3.0),
+                ("2.5", 3.0),
+                ("5", 3.0),
+                ("10", 3.0),
+                ("+Inf", 4.0),
+            ]
+        );
+        let value = |name: &str| {
+            metrics
+                .iter()
+                .find(|metric| metric.name == name)
+                .unwrap()
+                .value
+        };
+        assert_eq!(value("http_request_duration_seconds_count"), 4.0);
+        assert!((value("http_request_duration_seconds_sum") - 20.313).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,278 @@
+// Throttle module for Crusty-Crawler
+// Token buckets in front of outbound notifications, one shared by every channel and one per
+// contact channel, so an alert storm can't send thousands of emails or webhooks. Notifications
+// that don't fit are counted and go out as one summary once the buckets have refilled
+
+// How many checks a summary names before "..."
+const SUMMARY_CHECKS: usize = 5;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct BucketConfig {
+    // Sent back to back before throttling starts
+    pub burst: u32,
+    // Refill rate once the burst is used up
+    pub per_minute: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct NotificationThrottleConfig {
+    pub enabled: bool,
+    pub global: BucketConfig,
+    // Each contact's channel of one type, e.g. alice's email
+    pub per_channel: BucketConfig,
+}
+
+impl Default for NotificationThrottleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            global: BucketConfig {
+                burst: 60,
+                per_minute: 20.0,
+            },
+            per_channel: BucketConfig {
+                burst: 10,
+                per_minute: 2.0,
+            },
+        }
+    }
+}
+
+impl NotificationThrottleConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, bucket) in [("global", &self.global), ("per_channel", &self.per_channel)] {
+            if bucket.burst == 0 {
+                return Err(format!(
+                    "notification_throttle.{}.burst must be above 0",
+                    name
+                ));
+            }
+            if bucket.per_minute <= 0.0 {
+                return Err(format!(
+                    "notification_throttle.{}.per_minute must be above 0",
+                    name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    pub fn full(config: &BucketConfig, now: Instant) -> Self {
+        Self {
+            tokens: config.burst as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, config: &BucketConfig, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * config.per_minute / 60.0).min(config.burst as f64);
+        self.updated = now;
+    }
+
+    // Until the next token is there, zero when one is
+    pub fn wait(&mut self, config: &BucketConfig, now: Instant) -> Duration {
+        self.refill(config, now);
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) * 60.0 / config.per_minute)
+        }
+    }
+
+    fn take(&mut self) {
+        self.tokens -= 1.0;
+    }
+}
+
+// What a channel didn't send while throttled
+#[derive(Clone, Debug, PartialEq)]
+pub struct SuppressedNotifications {
+    pub count: u32,
+    pub worst: CheckState,
+    // Distinct checks, first ones first
+    pub checks: Vec<String>,
+}
+
+impl SuppressedNotifications {
+    // Sent through the channel's normal templates
+    fn summary_alert(&self) -> Alert {
+        let mut checks = self.checks.clone();
+        if checks.len() > SUMMARY_CHECKS {
+            checks.truncate(SUMMARY_CHECKS);
+            checks.push("...".to_string());
+        }
+        Alert {
+            id: 0,
+            check: "notifications".to_string(),
+            state: self.worst,
+            message: format!(
+                "{} notification(s) were held back by the throttle, for {}",
+                self.count,
+                checks.join(", ")
+            ),
+            raised_at: chrono::Utc::now().to_rfc3339(),
+            resolved_at: None,
+            acknowledged_by: None,
+            value: Some(self.count as f64),
+            threshold: None,
+            parent_id: None,
+        }
+    }
+}
+
+pub enum Admission {
+    Send,
+    // The first suppression since the last summary asks for one to be sent
+    Suppressed { first: bool },
+}
+
+#[derive(Default)]
+pub struct NotificationThrottle {
+    global: Option<TokenBucket>,
+    channels: BTreeMap<String, (TokenBucket, Option<SuppressedNotifications>)>,
+}
+
+impl NotificationThrottle {
+    // Both buckets need a token, the notification takes one from each
+    fn take_tokens(
+        &mut self,
+        config: &NotificationThrottleConfig,
+        channel: &str,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        let global = self
+            .global
+            .get_or_insert_with(|| TokenBucket::full(&config.global, now));
+        let (bucket, _) = self
+            .channels
+            .entry(channel.to_string())
+            .or_insert_with(|| (TokenBucket::full(&config.per_channel, now), None));
+        let wait = global
+            .wait(&config.global, now)
+            .max(bucket.wait(&config.per_channel, now));
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+        global.take();
+        bucket.take();
+        Ok(())
+    }
+
+    pub fn admit(
+        &mut self,
+        config: &NotificationThrottleConfig,
+        channel: &str,
+        alert: &Alert,
+        now: Instant,
+    ) -> Admission {
+        let has_suppressed = self
+            .channels
+            .get(channel)
+            .is_some_and(|(_, suppressed)| suppressed.is_some());
+        // Once throttled, everything waits for the summary so nothing is sent out of order
+        if !has_suppressed && self.take_tokens(config, channel, now).is_ok() {
+            return Admission::Send;
+        }
+
+        let (_, suppressed) = self
+            .channels
+            .entry(channel.to_string())
+            .or_insert_with(|| (TokenBucket::full(&config.per_channel, now), None));
+        let first = suppressed.is_none();
+        let suppressed = suppressed.get_or_insert(SuppressedNotifications {
+            count: 0,
+            worst: alert.state,
+            checks: Vec::new(),
+        });
+        suppressed.count += 1;
+        suppressed.worst = suppressed.worst.max(alert.state);
+        if !suppressed.checks.contains(&alert.check) {
+            suppressed.checks.push(alert.check.clone());
+        }
+        Admission::Suppressed { first }
+    }
+
+    // The suppressed notifications once a token is free for their summary, or how long to wait
+    pub fn take_summary(
+        &mut self,
+        config: &NotificationThrottleConfig,
+        channel: &str,
+        now: Instant,
+    ) -> Result<Option<SuppressedNotifications>, Duration> {
+        self.take_tokens(config, channel, now)?;
+        Ok(self
+            .channels
+            .get_mut(channel)
+            .and_then(|(_, suppressed)| suppressed.take()))
+    }
+}
+
+static NOTIFICATION_THROTTLE: Mutex<Option<NotificationThrottle>> = Mutex::new(None);
+
+fn with_notification_throttle<T>(f: impl FnOnce(&mut NotificationThrottle) -> T) -> T {
+    let mut throttle = NOTIFICATION_THROTTLE.lock().unwrap();
+    f(throttle.get_or_insert_with(NotificationThrottle::default))
+}
+
+pub fn admit_notification(
+    config: &NotificationThrottleConfig,
+    channel: &str,
+    alert: &Alert,
+) -> Admission {
+    if !config.enabled {
+        return Admission::Send;
+    }
+    with_notification_throttle(|throttle| throttle.admit(config, channel, alert, Instant::now()))
+}
+
+// Waits for the buckets to refill, then sends what the channel held back as one notification
+pub async fn send_throttle_summary(
+    client: reqwest::Client,
+    settings: NotificationSettings,
+    channel_key: String,
+    channel: NotificationChannel,
+) {
+    let summary = loop {
+        let next = with_notification_throttle(|throttle| {
+            throttle.take_summary(&settings.throttle, &channel_key, Instant::now())
+        });
+        match next {
+            Ok(Some(summary)) => break summary,
+            Ok(None) => return,
+            Err(wait) => tokio::time::sleep(wait).await,
+        }
+    };
+
+    let alert = summary.summary_alert();
+    let host = sysinfo::System::host_name().unwrap_or_else(|| "unknown host".to_string());
+    let context = alert_template_context(&alert, &host, chrono::Utc::now());
+    match channel
+        .send(
+            &client,
+            &settings.templates,
+            &context,
+            alert.state,
+            settings.smtp_config.as_ref(),
+        )
+        .await
+    {
+        Ok(()) => println!(
+            "📨 Sent {} the {} notification(s) it was throttled on as a summary",
+            channel_key, summary.count
+        ),
+        Err(e) => eprintln!(
+            "❌ Failed to send the throttle summary to {}: {}",
+            channel_key, e
+        ),
+    }
+}
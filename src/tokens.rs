@@ -0,0 +1,73 @@
+// Tokens module for Crusty-Crawler
+// Access tokens are stored as salted SHA-256 hashes and the raw token is only shown when it is
+// created or rotated. They are random rather than chosen like passwords, so a fast hash is
+// enough and every API request can afford to check one
+
+const TOKEN_HASH_SCHEME: &str = "sha256";
+
+fn salted_token_digest(salt: &str, token: &str) -> String {
+    use sha2::Digest;
+
+    format!(
+        "{:x}",
+        sha2::Sha256::digest(format!("{}:{}", salt, token).as_bytes())
+    )
+}
+
+// "sha256$<salt>$<digest>", a fresh salt every time
+pub fn hash_access_token(token: &str) -> String {
+    let salt = AuthManager::generate_suggested_token();
+    format!(
+        "{}${}${}",
+        TOKEN_HASH_SCHEME,
+        salt,
+        salted_token_digest(&salt, token)
+    )
+}
+
+// Takes as long for a near miss as for a token that's wrong from the first character
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+// The salt, which identifies the token without revealing anything about it
+pub fn access_token_id(token_hash: &str) -> &str {
+    token_hash.split('$').nth(1).unwrap_or_default()
+}
+
+pub fn verify_access_token(token: &str, token_hash: &str) -> bool {
+    let mut parts = token_hash.splitn(3, '$');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(TOKEN_HASH_SCHEME), Some(salt), Some(digest)) => constant_time_eq(
+            salted_token_digest(salt, token).as_bytes(),
+            digest.as_bytes(),
+        ),
+        _ => false,
+    }
+}
+
+impl AuthManager {
+    // Replaces the user's access token and returns the new one, which isn't stored anywhere
+    // and can't be shown again. The user's web sessions end too, the old token may have been
+    // what opened them
+    pub fn rotate_access_token(&mut self, username: &str) -> Result<String, String> {
+        let token = AuthManager::generate_suggested_token();
+        self.replace_access_token(username, &token)?;
+        Ok(token)
+    }
+
+    fn replace_access_token(&mut self, username: &str, token: &str) -> Result<(), String> {
+        let user = self
+            .config
+            .users
+            .get_mut(username)
+            .ok_or("User not found")?;
+        user.access_token_hash = hash_access_token(token);
+        self.save_config().map_err(|e| e.to_string())?;
+        self.sessions
+            .lock()
+            .unwrap()
+            .retain(|_, session| session.username != username);
+        Ok(())
+    }
+}
@@ -0,0 +1,332 @@
+// Top module for Crusty-Crawler
+// `top` terminal UI with live CPU, memory, network, disks and active alerts, for use over SSH
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Gauge, Paragraph, Row, Sparkline, Table};
+
+const TOP_REFRESH: Duration = Duration::from_secs(1);
+const CPU_HISTORY: usize = 120;
+
+struct TopState {
+    sys: sysinfo::System,
+    networks: Networks,
+    disks: sysinfo::Disks,
+    cpu_history: VecDeque<u64>,
+    // Bytes per second received and transmitted since the previous refresh
+    network_rates: Vec<(String, f64, f64)>,
+    last_refresh: Instant,
+    alerts: Arc<Mutex<AlertManager>>,
+}
+
+impl TopState {
+    fn new(alerts: Arc<Mutex<AlertManager>>) -> Self {
+        let mut state = Self {
+            sys: sysinfo::System::new(),
+            networks: Networks::new_with_refreshed_list(),
+            disks: sysinfo::Disks::new_with_refreshed_list(),
+            cpu_history: VecDeque::with_capacity(CPU_HISTORY),
+            network_rates: Vec::new(),
+            last_refresh: Instant::now(),
+            alerts,
+        };
+        state.sys.refresh_cpu_usage();
+        state
+    }
+
+    fn refresh(&mut self) {
+        let elapsed = self.last_refresh.elapsed().as_secs_f64().max(0.001);
+        self.last_refresh = Instant::now();
+
+        self.sys.refresh_cpu_usage();
+        self.sys.refresh_memory();
+        self.disks.refresh(true);
+        self.networks.refresh(true);
+
+        if self.cpu_history.len() == CPU_HISTORY {
+            self.cpu_history.pop_front();
+        }
+        self.cpu_history
+            .push_back(self.sys.global_cpu_usage().round() as u64);
+
+        let mut rates: Vec<(String, f64, f64)> = self
+            .networks
+            .iter()
+            .map(|(name, data)| {
+                (
+                    name.to_string(),
+                    data.received() as f64 / elapsed,
+                    data.transmitted() as f64 / elapsed,
+                )
+            })
+            .collect();
+        rates.sort_by(|a, b| a.0.cmp(&b.0));
+        self.network_rates = rates;
+    }
+}
+
+fn format_rate(bytes_per_sec: f64) -> String {
+    if bytes_per_sec >= 1024.0 * 1024.0 {
+        format!("{:.1} MB/s", bytes_per_sec / 1024.0 / 1024.0)
+    } else {
+        format!("{:.1} kB/s", bytes_per_sec / 1024.0)
+    }
+}
+
+fn gauge_color(percent: f64) -> Color {
+    if percent >= 90.0 {
+        Color::Red
+    } else if percent >= 75.0 {
+        Color::Yellow
+    } else {
+        Color::Green
+    }
+}
+
+fn tui_state_color(state: CheckState) -> Color {
+    match state {
+        CheckState::Ok => Color::Green,
+        CheckState::Warning => Color::Yellow,
+        CheckState::Critical => Color::Red,
+        CheckState::Unknown => Color::Gray,
+    }
+}
+
+fn draw_top(frame: &mut ratatui::Frame, state: &TopState) {
+    let [header, gauges, tables, alerts, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(7),
+        Constraint::Min(6),
+        Constraint::Length(8),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let host = sysinfo::System::host_name().unwrap_or_else(|| "unknown host".to_string());
+    let uptime = sysinfo::System::uptime();
+    frame.render_widget(
+        Paragraph::new(Line::from(vec![
+            Span::styled(
+                "🦀 Crusty-Crawler ",
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(format!(
+                "{}  up {}d {}h {}m",
+                host,
+                uptime / 86400,
+                uptime % 86400 / 3600,
+                uptime % 3600 / 60
+            )),
+        ])),
+        header,
+    );
+
+    // CPU history on the left, memory and swap gauges on the right
+    let [cpu_area, memory_area] =
+        Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(gauges);
+    let cpu = state.sys.global_cpu_usage() as f64;
+    let history: Vec<u64> = state.cpu_history.iter().copied().collect();
+    frame.render_widget(
+        Sparkline::default()
+            .block(Block::default().borders(Borders::ALL).title(format!(
+                " CPU {:.1}% ({} cores) ",
+                cpu,
+                state.sys.cpus().len()
+            )))
+            .data(&history)
+            .max(100)
+            .style(Style::default().fg(gauge_color(cpu))),
+        cpu_area,
+    );
+
+    let [ram_area, swap_area] =
+        Layout::vertical([Constraint::Length(3), Constraint::Length(3)]).areas(memory_area);
+    for (area, title, used, total) in [
+        (
+            ram_area,
+            "Memory",
+            state.sys.used_memory(),
+            state.sys.total_memory(),
+        ),
+        (
+            swap_area,
+            "Swap",
+            state.sys.used_swap(),
+            state.sys.total_swap(),
+        ),
+    ] {
+        let percent = if total > 0 {
+            used as f64 / total as f64 * 100.0
+        } else {
+            0.0
+        };
+        frame.render_widget(
+            Gauge::default()
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(format!(" {} ", title)),
+                )
+                .gauge_style(Style::default().fg(gauge_color(percent)))
+                .ratio((percent / 100.0).clamp(0.0, 1.0))
+                .label(format!(
+                    "{:.1}% of {:.1} GB",
+                    percent,
+                    total as f64 / 1024.0 / 1024.0 / 1024.0
+                )),
+            area,
+        );
+    }
+
+    let [network_area, disk_area] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(tables);
+    let network_rows = state
+        .network_rates
+        .iter()
+        .map(|(name, rx, tx)| Row::new(vec![name.clone(), format_rate(*rx), format_rate(*tx)]));
+    frame.render_widget(
+        Table::new(
+            network_rows,
+            [
+                Constraint::Percentage(40),
+                Constraint::Percentage(30),
+                Constraint::Percentage(30),
+            ],
+        )
+        .header(
+            Row::new(vec!["Interface", "↓ In", "↑ Out"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::default().borders(Borders::ALL).title(" Network ")),
+        network_area,
+    );
+
+    let disk_rows = state.disks.list().iter().map(|disk| {
+        let total = disk.total_space();
+        let used = total.saturating_sub(disk.available_space());
+        let percent = if total > 0 {
+            used as f64 / total as f64 * 100.0
+        } else {
+            0.0
+        };
+        Row::new(vec![
+            disk.mount_point().to_string_lossy().to_string(),
+            format!("{:.1} GB", total as f64 / 1e9),
+            format!("{:.1}%", percent),
+        ])
+        .style(Style::default().fg(gauge_color(percent)))
+    });
+    frame.render_widget(
+        Table::new(
+            disk_rows,
+            [
+                Constraint::Percentage(60),
+                Constraint::Percentage(20),
+                Constraint::Percentage(20),
+            ],
+        )
+        .header(
+            Row::new(vec!["Mount", "Size", "Used"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::default().borders(Borders::ALL).title(" Disks ")),
+        disk_area,
+    );
+
+    let active = state.alerts.lock().unwrap().active();
+    let alert_lines: Vec<Line> = if active.is_empty() {
+        vec![Line::styled(
+            "✅ No active alerts",
+            Style::default().fg(Color::Green),
+        )]
+    } else {
+        active
+            .iter()
+            .map(|alert| {
+                Line::from(vec![
+                    Span::styled(
+                        format!("{:<9}", alert.state.label()),
+                        Style::default().fg(tui_state_color(alert.state)),
+                    ),
+                    Span::styled(
+                        format!("{} ", alert.check),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(alert.message.clone()),
+                ])
+            })
+            .collect()
+    };
+    frame.render_widget(
+        Paragraph::new(alert_lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(" Active Alerts ({}) ", active.len())),
+        ),
+        alerts,
+    );
+
+    frame.render_widget(
+        Paragraph::new("q / Esc: quit").style(Style::default().fg(Color::DarkGray)),
+        footer,
+    );
+}
+
+// Runs the checks in the background and tracks alerts locally, notifications stay with
+// the server so nobody gets paged twice
+fn spawn_top_checks(config: CheckConfig, devices: DeviceFilters, alerts: Arc<Mutex<AlertManager>>) {
+    std::thread::spawn(move || {
+        let Ok(rt) = Runtime::new() else {
+            return;
+        };
+        let system = system_provider();
+        rt.block_on(async {
+            loop {
+                for name in available_checks(&config) {
+                    if let Some(result) = run_check(&name, &config, &devices, system.as_ref()).await
+                    {
+                        alerts
+                            .lock()
+                            .unwrap()
+                            .process_result(&result, &config.grouping);
+                    }
+                }
+                tokio::time::sleep(Duration::from_secs(config.interval_secs.max(5))).await;
+            }
+        });
+    });
+}
+
+pub fn run_top() -> Result<(), Box<dyn std::error::Error>> {
+    let config = AuthConfig::load_or_default(&data_file(AUTH_CONFIG_FILE));
+    let alerts = Arc::new(Mutex::new(AlertManager::default()));
+    spawn_top_checks(config.checks, config.metrics.devices, alerts.clone());
+
+    let mut state = TopState::new(alerts);
+    let mut terminal = ratatui::init();
+    let result = (|| -> io::Result<()> {
+        loop {
+            if state.last_refresh.elapsed() >= TOP_REFRESH {
+                state.refresh();
+            }
+            terminal.draw(|frame| draw_top(frame, &state))?;
+
+            if event::poll(Duration::from_millis(250))?
+                && let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(());
+                    }
+                    _ => {}
+                }
+            }
+        }
+    })();
+    ratatui::restore();
+    Ok(result?)
+}
@@ -0,0 +1,93 @@
+// Wake module for Crusty-Crawler
+// Wake-on-LAN for hosts reporting to a manager: their MAC addresses are kept in manager.hosts and
+// an admin can send the magic packet from the fleet panel or the API once a host is down
+
+const WAKE_PORT: u16 = 9;
+
+// Body of PUT /api/central/hosts/{host}/mac, null forgets the address
+#[derive(Deserialize)]
+pub struct HostMacUpdate {
+    pub mac: Option<String>,
+}
+
+// Six hex pairs separated by colons or dashes
+pub fn parse_mac_address(mac: &str) -> Result<[u8; 6], String> {
+    let invalid = || format!("'{}' is not a MAC address like 00:11:22:33:44:55", mac);
+    let parts: Vec<&str> = mac.trim().split([':', '-']).collect();
+    if parts.len() != 6 {
+        return Err(invalid());
+    }
+    let mut bytes = [0; 6];
+    for (byte, part) in bytes.iter_mut().zip(parts) {
+        if part.len() != 2 {
+            return Err(invalid());
+        }
+        *byte = u8::from_str_radix(part, 16).map_err(|_| invalid())?;
+    }
+    Ok(bytes)
+}
+
+// Six 0xFF bytes, then the MAC sixteen times
+pub fn magic_packet(mac: &[u8; 6]) -> Vec<u8> {
+    let mut packet = vec![0xFF; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(mac);
+    }
+    packet
+}
+
+pub async fn wake_host(host: &ManagedHost) -> Result<(), String> {
+    let Some(mac) = &host.mac else {
+        return Err(format!("No MAC address stored for {}", host.name));
+    };
+    let packet = magic_packet(&parse_mac_address(mac)?);
+    let broadcast: std::net::Ipv4Addr = match &host.wake_broadcast {
+        Some(address) => address
+            .parse()
+            .map_err(|_| format!("wake_broadcast '{}' is not an IPv4 address", address))?,
+        None => std::net::Ipv4Addr::BROADCAST,
+    };
+    let socket = tokio::net::UdpSocket::bind((std::net::Ipv4Addr::UNSPECIFIED, 0))
+        .await
+        .map_err(|e| e.to_string())?;
+    socket.set_broadcast(true).map_err(|e| e.to_string())?;
+    socket
+        .send_to(&packet, (broadcast, WAKE_PORT))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+impl AuthManager {
+    // Adds the host to manager.hosts the first time it gets an address
+    pub fn set_host_mac(
+        &mut self,
+        name: &str,
+        mac: Option<String>,
+        changed_by: &str,
+    ) -> Result<(), String> {
+        let mac = match mac.as_deref().map(str::trim) {
+            Some("") | None => None,
+            Some(mac) => {
+                parse_mac_address(mac)?;
+                Some(mac.to_lowercase())
+            }
+        };
+        let hosts = &mut self.config.manager.hosts;
+        match hosts.iter_mut().find(|host| host.name == name) {
+            Some(host) => host.mac = mac.clone(),
+            None => hosts.push(ManagedHost {
+                name: name.to_string(),
+                mac: mac.clone(),
+                ..Default::default()
+            }),
+        }
+        self.save_config().map_err(|e| e.to_string())?;
+        record_event(
+            EventKind::ConfigChange,
+            &format!("{} set the MAC address of {}", changed_by, name),
+            mac.as_deref().unwrap_or("removed"),
+        );
+        Ok(())
+    }
+}
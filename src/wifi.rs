@@ -0,0 +1,297 @@
+// Wi-Fi module for Crusty-Crawler
+// Reads SSID, signal strength and link rates of wireless interfaces for laptops and edge
+// devices, and puts access point changes on the event timeline
+
+#[derive(Clone, Default)]
+pub struct WifiInterface {
+    pub name: String,
+    pub ssid: Option<String>,
+    // Access point, None while disconnected
+    pub bssid: Option<String>,
+    pub signal_dbm: Option<f64>,
+    pub link_quality_percent: Option<f64>,
+    pub rx_rate_mbps: Option<f64>,
+    pub tx_rate_mbps: Option<f64>,
+}
+
+impl WifiInterface {
+    fn connected(&self) -> bool {
+        self.bssid.is_some()
+    }
+
+    pub fn summary(&self) -> String {
+        if !self.connected() {
+            return format!("{}: not connected", self.name);
+        }
+        let mut parts = vec![format!(
+            "{}: {}",
+            self.name,
+            self.ssid.as_deref().unwrap_or("unknown network")
+        )];
+        if let Some(signal) = self.signal_dbm {
+            parts.push(format!("{:.0} dBm", signal));
+        }
+        if let Some(quality) = self.link_quality_percent {
+            parts.push(format!("{:.0}% quality", quality));
+        }
+        match (self.rx_rate_mbps, self.tx_rate_mbps) {
+            (Some(rx), Some(tx)) => parts.push(format!("{:.0}/{:.0} Mbit/s", rx, tx)),
+            (None, Some(rate)) | (Some(rate), None) => parts.push(format!("{:.0} Mbit/s", rate)),
+            (None, None) => {}
+        }
+        parts.join(", ")
+    }
+}
+
+// Access point per interface from the previous read, and how often each interface roamed
+type WifiRoaming = (HashMap<String, Option<String>>, HashMap<String, u64>);
+static WIFI_ROAMING: Mutex<Option<WifiRoaming>> = Mutex::new(None);
+
+// "2.5 MBit/s ..." or "866.7 MBit/s VHT-MCS 9 ..."
+fn leading_number(value: &str) -> Option<f64> {
+    value.split_whitespace().next()?.parse().ok()
+}
+
+#[cfg(target_os = "linux")]
+fn read_wifi_interfaces() -> Vec<WifiInterface> {
+    // Link quality and signal as "wlan0: 0000   70.  -40.  -256 ..."
+    let wireless = fs::read_to_string("/proc/net/wireless").unwrap_or_default();
+    let proc_stats: HashMap<&str, Vec<&str>> = wireless
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (name, fields) = line.split_once(':')?;
+            Some((name.trim(), fields.split_whitespace().collect()))
+        })
+        .collect();
+
+    let Ok(entries) = fs::read_dir("/sys/class/net") else {
+        return Vec::new();
+    };
+    let mut interfaces: Vec<WifiInterface> = entries
+        .flatten()
+        .filter(|entry| entry.path().join("wireless").exists())
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let mut interface = WifiInterface {
+                name: name.clone(),
+                ..Default::default()
+            };
+            if let Some(fields) = proc_stats.get(name.as_str()) {
+                let number = |index: usize| {
+                    fields
+                        .get(index)
+                        .and_then(|value| value.trim_end_matches('.').parse::<f64>().ok())
+                };
+                // Quality is out of 70 with most drivers
+                interface.link_quality_percent =
+                    number(1).map(|quality| (quality / 70.0 * 100.0).min(100.0));
+                interface.signal_dbm = number(2).filter(|signal| *signal < 0.0);
+            }
+
+            // iw has the SSID and bitrates, without it only the signal is known
+            if let Ok(output) = std::process::Command::new("iw")
+                .args(["dev", &name, "link"])
+                .output()
+            {
+                for line in String::from_utf8_lossy(&output.stdout).lines() {
+                    let line = line.trim();
+                    if let Some(rest) = line.strip_prefix("Connected to ") {
+                        interface.bssid = rest.split_whitespace().next().map(str::to_string);
+                    } else if let Some((key, value)) = line.split_once(": ") {
+                        match key {
+                            "SSID" => interface.ssid = Some(value.to_string()),
+                            "signal" => interface.signal_dbm = leading_number(value),
+                            "rx bitrate" => interface.rx_rate_mbps = leading_number(value),
+                            "tx bitrate" => interface.tx_rate_mbps = leading_number(value),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            if interface.bssid.is_none() && interface.signal_dbm.is_some() {
+                // Associated, but iw isn't installed to say with what
+                interface.bssid = Some(String::new());
+            }
+            interface
+        })
+        .collect();
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    interfaces
+}
+
+// `netsh wlan show interfaces` prints one "Key : value" block per adapter
+#[cfg(windows)]
+fn read_wifi_interfaces() -> Vec<WifiInterface> {
+    let Ok(output) = std::process::Command::new("netsh")
+        .args(["wlan", "show", "interfaces"])
+        .output()
+    else {
+        return Vec::new();
+    };
+
+    let mut interfaces: Vec<WifiInterface> = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some((key, value)) = line.split_once(" : ") else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim().to_string());
+        if key == "Name" {
+            interfaces.push(WifiInterface {
+                name: value,
+                ..Default::default()
+            });
+            continue;
+        }
+        let Some(interface) = interfaces.last_mut() else {
+            continue;
+        };
+        match key {
+            "SSID" => interface.ssid = Some(value),
+            "BSSID" | "AP BSSID" => interface.bssid = Some(value),
+            "Receive rate (Mbps)" => interface.rx_rate_mbps = value.parse().ok(),
+            "Transmit rate (Mbps)" => interface.tx_rate_mbps = value.parse().ok(),
+            // Windows reports a percentage, roughly linear between -100 and -50 dBm
+            "Signal" => {
+                let percent: Option<f64> = value.trim_end_matches('%').parse().ok();
+                interface.link_quality_percent = percent;
+                if interface.signal_dbm.is_none() {
+                    interface.signal_dbm = percent.map(|percent| percent / 2.0 - 100.0);
+                }
+            }
+            // Newer builds also print the real value
+            "Rssi" => interface.signal_dbm = value.parse().ok(),
+            _ => {}
+        }
+    }
+    interfaces
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn read_wifi_interfaces() -> Vec<WifiInterface> {
+    Vec::new()
+}
+
+// Compares access points with the previous read, roams and disconnects go on the timeline
+fn note_wifi_changes(interfaces: &[WifiInterface]) {
+    let mut roaming = WIFI_ROAMING.lock().unwrap();
+    let first_read = roaming.is_none();
+    let (access_points, roams) = roaming.get_or_insert_default();
+    for interface in interfaces {
+        let previous = access_points.insert(interface.name.clone(), interface.bssid.clone());
+        if first_read {
+            continue;
+        }
+        let Some(previous) = previous else { continue };
+        let (title, detail) = match (&previous, &interface.bssid) {
+            (Some(old), Some(new)) if old != new => {
+                *roams.entry(interface.name.clone()).or_default() += 1;
+                (
+                    format!("{} roamed to another access point", interface.name),
+                    format!("{} -> {}, {}", old, new, interface.summary()),
+                )
+            }
+            (None, Some(_)) => (format!("{} connected", interface.name), interface.summary()),
+            (Some(old), None) => (
+                format!("{} disconnected", interface.name),
+                format!("Was connected to {}", old),
+            ),
+            _ => continue,
+        };
+        record_event(EventKind::Network, &title, &detail);
+    }
+}
+
+pub fn wifi_metrics(interfaces: &[WifiInterface]) -> Vec<Metric> {
+    let roams = WIFI_ROAMING
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|(_, roams)| roams.clone())
+        .unwrap_or_default();
+    let mut metrics = Vec::new();
+    for interface in interfaces {
+        let ssid = interface.ssid.as_deref().unwrap_or("");
+        let metric = |name: &str, value: f64| {
+            Metric::new(name, value)
+                .label("interface", &interface.name)
+                .label("ssid", ssid)
+        };
+        metrics.push(
+            Metric::new(
+                "wifi_connected",
+                if interface.connected() { 1.0 } else { 0.0 },
+            )
+            .label("interface", &interface.name),
+        );
+        metrics.push(
+            Metric::new(
+                "wifi_roams_total",
+                roams.get(&interface.name).copied().unwrap_or(0) as f64,
+            )
+            .label("interface", &interface.name),
+        );
+        if !interface.connected() {
+            continue;
+        }
+        if let Some(signal) = interface.signal_dbm {
+            metrics.push(metric("wifi_signal_dbm", signal));
+        }
+        if let Some(quality) = interface.link_quality_percent {
+            metrics.push(metric("wifi_link_quality_percent", quality));
+        }
+        if let Some(rate) = interface.rx_rate_mbps {
+            metrics.push(metric("wifi_rx_bitrate_mbps", rate));
+        }
+        if let Some(rate) = interface.tx_rate_mbps {
+            metrics.push(metric("wifi_tx_bitrate_mbps", rate));
+        }
+    }
+    metrics
+}
+
+pub fn wifi_status(interfaces: &[WifiInterface]) -> String {
+    let mut output = String::from("\nWi-Fi:\n");
+    for interface in interfaces {
+        output.push_str(&format!("  {}\n", interface.summary()));
+    }
+    output
+}
+
+pub fn wifi_available() -> bool {
+    !read_wifi_interfaces().is_empty()
+}
+
+// Weak signal drops throughput long before the connection goes away
+pub fn check_wifi(thresholds: &Thresholds) -> CheckResult {
+    let interfaces = read_wifi_interfaces();
+    note_wifi_changes(&interfaces);
+    let connected: Vec<&WifiInterface> = interfaces.iter().filter(|i| i.connected()).collect();
+    if connected.is_empty() {
+        return CheckResult::new(
+            "wifi",
+            CheckState::Ok,
+            "No Wi-Fi connection".to_string(),
+            Vec::new(),
+        );
+    }
+
+    let mut state = CheckState::Ok;
+    let mut perfdata = Vec::new();
+    for interface in &connected {
+        if let Some(signal) = interface.signal_dbm {
+            state = state.max(thresholds.evaluate_below(signal));
+            perfdata.push(PerfData::new(
+                &format!("{}_signal", interface.name),
+                signal,
+                "",
+            ));
+        }
+    }
+    let output = connected
+        .iter()
+        .map(|interface| interface.summary())
+        .collect::<Vec<_>>()
+        .join("; ");
+    CheckResult::new("wifi", state, output, perfdata)
+}
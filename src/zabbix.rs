@@ -0,0 +1,233 @@
+// Zabbix module for Crusty-Crawler
+// Delivers metrics and check states to a Zabbix server or proxy with the sender/trapper protocol
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ZabbixConfig {
+    pub enabled: bool,
+    pub server: String,
+    pub port: u16,
+    // Host name as configured in Zabbix, the machine's hostname when empty
+    pub host: String,
+    pub interval_secs: u64,
+    // Item keys are <key_prefix><metric>[label values], e.g. crusty.disk_total_bytes[/]
+    pub key_prefix: String,
+    // Metric name patterns with * wildcards. Every item needs a trapper item in Zabbix, so
+    // sending everything mostly produces "failed" counts
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    // Also send <key_prefix>check.state[name] as 0 OK, 1 WARNING, 2 CRITICAL, 3 UNKNOWN
+    pub send_checks: bool,
+}
+
+impl Default for ZabbixConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            server: String::new(),
+            port: 10051,
+            host: String::new(),
+            interval_secs: 60,
+            key_prefix: "crusty.".to_string(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            send_checks: true,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ZabbixItem {
+    host: String,
+    key: String,
+    value: String,
+    clock: i64,
+}
+
+#[derive(Serialize)]
+struct ZabbixSenderRequest<'a> {
+    request: &'static str,
+    data: &'a [ZabbixItem],
+    clock: i64,
+}
+
+#[derive(Deserialize)]
+struct ZabbixSenderResponse {
+    response: String,
+    #[serde(default)]
+    info: String,
+}
+
+// Parameters with separators or quotes have to be quoted
+fn zabbix_key_param(value: &str) -> String {
+    if value.contains([',', ']', '[', '"', ' ']) {
+        format!("\"{}\"", value.replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn zabbix_key(prefix: &str, name: &str, params: &[&str]) -> String {
+    if params.is_empty() {
+        format!("{}{}", prefix, name)
+    } else {
+        let params: Vec<String> = params.iter().map(|p| zabbix_key_param(p)).collect();
+        format!("{}{}[{}]", prefix, name, params.join(","))
+    }
+}
+
+impl ZabbixConfig {
+    fn exports(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| wildcard_match(p, name)))
+            && !self.exclude.iter().any(|p| wildcard_match(p, name))
+    }
+
+    fn host_name(&self) -> String {
+        if self.host.is_empty() {
+            sysinfo::System::host_name().unwrap_or_else(|| "localhost".to_string())
+        } else {
+            self.host.clone()
+        }
+    }
+
+    fn items(&self, metrics: &[Metric], checks: &[CheckResult]) -> Vec<ZabbixItem> {
+        let host = self.host_name();
+        let clock = chrono::Utc::now().timestamp();
+        let item = |key: String, value: String| ZabbixItem {
+            host: host.clone(),
+            key,
+            value,
+            clock,
+        };
+
+        // Label values become key parameters in label name order
+        let mut items: Vec<ZabbixItem> = metrics
+            .iter()
+            .filter(|metric| self.exports(&metric.name))
+            .map(|metric| {
+                let params: Vec<&str> = metric.labels.values().map(String::as_str).collect();
+                item(
+                    zabbix_key(&self.key_prefix, &metric.name, &params),
+                    metric.value.to_string(),
+                )
+            })
+            .collect();
+        if self.send_checks {
+            items.extend(checks.iter().map(|check| {
+                item(
+                    zabbix_key(&self.key_prefix, "check.state", &[&check.name]),
+                    check.state.exit_code().to_string(),
+                )
+            }));
+        }
+        items
+    }
+}
+
+// ZBXD, protocol flags, then the payload length as a little-endian u64
+async fn send_zabbix_items(config: &ZabbixConfig, items: &[ZabbixItem]) -> Result<String, String> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let payload = serde_json::to_vec(&ZabbixSenderRequest {
+        request: "sender data",
+        data: items,
+        clock: chrono::Utc::now().timestamp(),
+    })
+    .map_err(|e| e.to_string())?;
+
+    let mut packet = b"ZBXD\x01".to_vec();
+    packet.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    packet.extend_from_slice(&payload);
+
+    let address = host_port(&config.server, config.port);
+    let exchange = async {
+        let mut stream = tokio::net::TcpStream::connect(&address).await?;
+        stream.write_all(&packet).await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        Ok::<_, io::Error>(response)
+    };
+    let response = tokio::time::timeout(Duration::from_secs(15), exchange)
+        .await
+        .map_err(|_| format!("Timed out talking to {}", address))?
+        .map_err(|e| format!("{}: {}", address, e))?;
+
+    if response.len() < 13 || !response.starts_with(b"ZBXD") {
+        return Err("Unexpected response from Zabbix".to_string());
+    }
+    let response: ZabbixSenderResponse =
+        serde_json::from_slice(&response[13..]).map_err(|e| e.to_string())?;
+    if response.response != "success" {
+        return Err(format!(
+            "Zabbix answered {}: {}",
+            response.response, response.info
+        ));
+    }
+    Ok(response.info)
+}
+
+fn spawn_zabbix_sender(server_state: Arc<Mutex<ServerState>>) {
+    std::thread::spawn(move || {
+        let rt = match Runtime::new() {
+            Ok(rt) => rt,
+            Err(e) => {
+                eprintln!("❌ Failed to start Zabbix sender: {}", e);
+                return;
+            }
+        };
+
+        rt.block_on(async {
+            // Only logged when it changes, missing trapper items fail on every send
+            let mut last_failed = String::new();
+            loop {
+                let (config, metrics_config, runner) = {
+                    let state = server_state.lock().unwrap();
+                    let runner = CheckRunner::from_state(&state);
+                    let auth_manager = state.auth_manager.lock().unwrap();
+                    (
+                        auth_manager.config.zabbix.clone(),
+                        auth_manager.config.metrics.clone(),
+                        runner,
+                    )
+                };
+
+                if config.enabled && !config.server.is_empty() {
+                    let metrics = collect_metrics(&metrics_config).await;
+                    let mut checks = Vec::new();
+                    if config.send_checks {
+                        // The check loop keeps the cache warm, this rarely runs anything
+                        let max_age = Duration::from_secs(runner.config.interval_secs.max(5) * 2);
+                        for name in available_checks(&runner.config) {
+                            if let Some((result, _)) = runner.cached(&name, max_age).await {
+                                checks.push(result);
+                            }
+                        }
+                    }
+
+                    let items = config.items(&metrics, &checks);
+                    match send_zabbix_items(&config, &items).await {
+                        Ok(info) => {
+                            let failed = info
+                                .split(';')
+                                .find_map(|part| part.trim().strip_prefix("failed: "))
+                                .unwrap_or("0")
+                                .to_string();
+                            if failed != last_failed && failed != "0" {
+                                eprintln!(
+                                    "⚠️  Zabbix rejected {} item(s), check that trapper items exist for host {} ({})",
+                                    failed,
+                                    config.host_name(),
+                                    info
+                                );
+                            }
+                            last_failed = failed;
+                        }
+                        Err(e) => eprintln!("❌ Zabbix sender failed: {}", e),
+                    }
+                }
+
+                tokio::time::sleep(Duration::from_secs(config.interval_secs.max(10))).await;
+            }
+        });
+    });
+}